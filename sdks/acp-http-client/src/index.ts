@@ -32,8 +32,10 @@ import {
   type SetSessionModeResponse,
   type Stream,
 } from "@agentclientprotocol/sdk";
+import { SseParser, type SseEvent } from "./sse.ts";
 
 const DEFAULT_ACP_PATH = "/v1/rpc";
+const DEFAULT_SSE_RECONNECT_DELAY_MS = 150;
 
 export interface ProblemDetails {
   type: string;
@@ -268,6 +270,7 @@ class StreamableHttpAcpTransport {
   private sseAbortController: AbortController | null = null;
   private sseLoop: Promise<void> | null = null;
   private lastEventId: string | null = null;
+  private reconnectDelayMs = DEFAULT_SSE_RECONNECT_DELAY_MS;
   private closed = false;
   private closingPromise: Promise<void> | null = null;
   private postedOnce = false;
@@ -443,7 +446,7 @@ class StreamableHttpAcpTransport {
         await this.consumeSse(response.body);
 
         if (!this.closed) {
-          await delay(150);
+          await delay(this.reconnectDelayMs);
         }
       } catch (error) {
         if (this.closed || isAbortError(error)) {
@@ -459,69 +462,34 @@ class StreamableHttpAcpTransport {
 
   private async consumeSse(body: ReadableStream<Uint8Array>): Promise<void> {
     const reader = body.getReader();
-    const decoder = new TextDecoder();
-    let buffer = "";
+    const parser = new SseParser(this.lastEventId ?? "");
 
     try {
       while (!this.closed) {
         const { done, value } = await reader.read();
+        const events = done ? parser.end() : parser.push(value);
+        this.lastEventId = parser.lastEventId || null;
+        if (parser.retry !== null) {
+          this.reconnectDelayMs = parser.retry;
+        }
+        for (const event of events) {
+          this.processSseEvent(event);
+        }
         if (done) {
           return;
         }
-
-        buffer += decoder.decode(value, { stream: true }).replace(/\r\n/g, "\n");
-
-        let separatorIndex = buffer.indexOf("\n\n");
-        while (separatorIndex !== -1) {
-          const eventChunk = buffer.slice(0, separatorIndex);
-          buffer = buffer.slice(separatorIndex + 2);
-          this.processSseEvent(eventChunk);
-          separatorIndex = buffer.indexOf("\n\n");
-        }
       }
     } finally {
       reader.releaseLock();
     }
   }
 
-  private processSseEvent(chunk: string): void {
-    if (!chunk.trim()) {
-      return;
-    }
-
-    let eventName = "message";
-    let eventId: string | null = null;
-    const dataLines: string[] = [];
-
-    for (const line of chunk.split("\n")) {
-      if (!line || line.startsWith(":")) {
-        continue;
-      }
-
-      if (line.startsWith("event:")) {
-        eventName = line.slice(6).trim();
-        continue;
-      }
-
-      if (line.startsWith("id:")) {
-        eventId = line.slice(3).trim();
-        continue;
-      }
-
-      if (line.startsWith("data:")) {
-        dataLines.push(line.slice(5).trimStart());
-      }
-    }
-
-    if (eventId) {
-      this.lastEventId = eventId;
-    }
-
-    if (eventName !== "message" || dataLines.length === 0) {
+  private processSseEvent(event: SseEvent): void {
+    if (event.type !== "message") {
       return;
     }
 
-    const payloadText = dataLines.join("\n");
+    const payloadText = event.data;
     if (!payloadText.trim()) {
       return;
     }
@@ -683,3 +651,5 @@ function buildQueryParams(source: Record<string, QueryValue>): URLSearchParams {
 
 export type * from "@agentclientprotocol/sdk";
 export { PROTOCOL_VERSION } from "@agentclientprotocol/sdk";
+export { SseParser } from "./sse.ts";
+export type { SseEvent } from "./sse.ts";
@@ -0,0 +1,146 @@
+/**
+ * Incremental parser for `text/event-stream` bodies, following the
+ * WHATWG server-sent events processing model.
+ *
+ * Feed it raw chunks as they arrive. Bytes are decoded with a streaming
+ * `TextDecoder`, so a multi-byte character split across chunks survives, and
+ * CRLF, LF and lone CR all end a line, even when a CRLF pair is split.
+ */
+export interface SseEvent {
+  /** The `event:` field, or `"message"` when the event had none. */
+  type: string;
+  data: string;
+  /** The last `id:` seen on the stream up to and including this event. */
+  lastEventId: string;
+}
+
+export class SseParser {
+  private readonly decoder = new TextDecoder();
+  private buffer = "";
+  private sawCr = false;
+  private sawFirstChunk = false;
+  private eventType = "";
+  private dataLines: string[] = [];
+  private idBuffer = "";
+
+  /** Last event id to send back as `Last-Event-ID` when reconnecting. */
+  lastEventId = "";
+  /** Reconnection delay in milliseconds requested by the server's `retry:` field. */
+  retry: number | null = null;
+
+  /** Pass the id from a previous connection so a resumed stream keeps it until the server sends a new one. */
+  constructor(lastEventId = "") {
+    this.idBuffer = lastEventId;
+    this.lastEventId = lastEventId;
+  }
+
+  /** Parse a chunk and return the events it completed. */
+  push(chunk: Uint8Array | string): SseEvent[] {
+    let text = typeof chunk === "string" ? chunk : this.decoder.decode(chunk, { stream: true });
+    if (!this.sawFirstChunk && text.length > 0) {
+      this.sawFirstChunk = true;
+      if (text.charCodeAt(0) === 0xfeff) {
+        text = text.slice(1);
+      }
+    }
+    return this.consume(text);
+  }
+
+  /**
+   * Flush the decoder at end of stream. An event that was not terminated by a
+   * blank line is discarded, as the spec requires.
+   */
+  end(): SseEvent[] {
+    const events = this.consume(this.decoder.decode());
+    this.buffer = "";
+    this.eventType = "";
+    this.dataLines = [];
+    return events;
+  }
+
+  private consume(text: string): SseEvent[] {
+    const events: SseEvent[] = [];
+    if (text.length === 0) {
+      return events;
+    }
+    let start = 0;
+    if (this.sawCr && text.startsWith("\n")) {
+      start = 1;
+    }
+    this.sawCr = false;
+
+    for (let index = start; index < text.length; index += 1) {
+      const char = text[index];
+      if (char !== "\n" && char !== "\r") {
+        continue;
+      }
+      const line = this.buffer + text.slice(start, index);
+      this.buffer = "";
+      if (char === "\r") {
+        if (index + 1 === text.length) {
+          this.sawCr = true;
+        } else if (text[index + 1] === "\n") {
+          index += 1;
+        }
+      }
+      start = index + 1;
+      const event = this.processLine(line);
+      if (event) {
+        events.push(event);
+      }
+    }
+
+    this.buffer += text.slice(start);
+    return events;
+  }
+
+  private processLine(line: string): SseEvent | null {
+    if (line === "") {
+      return this.dispatch();
+    }
+    if (line.startsWith(":")) {
+      return null;
+    }
+
+    const colon = line.indexOf(":");
+    const field = colon === -1 ? line : line.slice(0, colon);
+    let value = colon === -1 ? "" : line.slice(colon + 1);
+    if (value.startsWith(" ")) {
+      value = value.slice(1);
+    }
+
+    switch (field) {
+      case "event":
+        this.eventType = value;
+        break;
+      case "data":
+        this.dataLines.push(value);
+        break;
+      case "id":
+        if (!value.includes("\0")) {
+          this.idBuffer = value;
+        }
+        break;
+      case "retry":
+        if (/^[0-9]+$/.test(value)) {
+          this.retry = Number.parseInt(value, 10);
+        }
+        break;
+      default:
+        break;
+    }
+    return null;
+  }
+
+  private dispatch(): SseEvent | null {
+    this.lastEventId = this.idBuffer;
+    const dataLines = this.dataLines;
+    const type = this.eventType || "message";
+    this.dataLines = [];
+    this.eventType = "";
+    if (dataLines.length === 0) {
+      return null;
+    }
+    return { type, data: dataLines.join("\n"), lastEventId: this.lastEventId };
+  }
+}
@@ -0,0 +1,52 @@
+import { describe, expect, it } from "vitest";
+import { SseParser } from "../src/sse.ts";
+
+const encoder = new TextEncoder();
+
+describe("SseParser", () => {
+  it("parses event, id and multi-line data fields", () => {
+    const parser = new SseParser();
+    const events = parser.push("event: update\nid: 7\ndata: first\ndata:second\n\n");
+    expect(events).toEqual([{ type: "update", data: "first\nsecond", lastEventId: "7" }]);
+    expect(parser.lastEventId).toBe("7");
+  });
+
+  it("strips only one leading space and ignores comments and unknown fields", () => {
+    const parser = new SseParser();
+    const events = parser.push(": keep-alive\nfoo: bar\ndata:   indented\n\n");
+    expect(events).toEqual([{ type: "message", data: "  indented", lastEventId: "" }]);
+  });
+
+  it("accepts CRLF, LF and lone CR line endings split across chunks", () => {
+    const parser = new SseParser();
+    const events = ["data: a\r", "\n\r", "\ndata: b\r\r"].flatMap((chunk) => parser.push(chunk));
+    expect(events).toEqual([
+      { type: "message", data: "a", lastEventId: "" },
+      { type: "message", data: "b", lastEventId: "" },
+    ]);
+  });
+
+  it("keeps multi-byte characters split across chunks", () => {
+    const parser = new SseParser();
+    const bytes = encoder.encode("data: héllo ✓\n\n");
+    const split = bytes.indexOf(0xe2) + 1;
+    expect(parser.push(bytes.slice(0, split))).toEqual([]);
+    expect(parser.push(bytes.slice(split))).toEqual([{ type: "message", data: "héllo ✓", lastEventId: "" }]);
+  });
+
+  it("records retry and keeps the last id across events and reconnects", () => {
+    const parser = new SseParser();
+    parser.push("retry: 2500\nretry: soon\nid: 3\n\ndata: x\n\n");
+    expect(parser.retry).toBe(2500);
+    expect(parser.lastEventId).toBe("3");
+
+    const resumed = new SseParser(parser.lastEventId);
+    expect(resumed.push("data: y\n\n")).toEqual([{ type: "message", data: "y", lastEventId: "3" }]);
+  });
+
+  it("drops an unterminated event at end of stream", () => {
+    const parser = new SseParser();
+    expect(parser.push("﻿data: partial\n")).toEqual([]);
+    expect(parser.end()).toEqual([]);
+  });
+});
@@ -0,0 +1,109 @@
+import type { ContentBlock, McpServer, PromptRequest } from "acp-http-client";
+import type { SessionCreateRequest } from "./client.ts";
+
+/**
+ * Agent ids the server ships installers for. Other ids are still accepted so
+ * custom registry agents keep working, but these autocomplete.
+ */
+export type KnownAgentId = "claude" | "codex" | "opencode" | "amp" | "pi" | "cursor" | "mock";
+
+export class SessionRequestBuilder {
+  private readonly agent: string;
+  private sessionId?: string;
+  private workingDirectory?: string;
+  private readonly mcpServers: McpServer[] = [];
+
+  private constructor(agent: string) {
+    this.agent = agent;
+  }
+
+  static for(agent: KnownAgentId | (string & {})): SessionRequestBuilder {
+    return new SessionRequestBuilder(agent);
+  }
+
+  id(id: string): this {
+    this.sessionId = id;
+    return this;
+  }
+
+  cwd(cwd: string): this {
+    this.workingDirectory = cwd;
+    return this;
+  }
+
+  mcpServer(server: McpServer): this {
+    this.mcpServers.push(server);
+    return this;
+  }
+
+  /** Validate and return the request. Throws on the mistakes the server would reject. */
+  build(): SessionCreateRequest {
+    const agent = this.agent.trim();
+    if (!agent) {
+      throw new Error("session request requires a non-empty agent");
+    }
+    if (this.sessionId !== undefined && !this.sessionId.trim()) {
+      throw new Error("session id must not be blank");
+    }
+    if (this.workingDirectory !== undefined && !isAbsolutePath(this.workingDirectory)) {
+      throw new Error(`session cwd must be an absolute path, got '${this.workingDirectory}'`);
+    }
+    const names = new Set<string>();
+    for (const server of this.mcpServers) {
+      if (!server.name.trim()) {
+        throw new Error("MCP server name must not be blank");
+      }
+      if (names.has(server.name)) {
+        throw new Error(`MCP server '${server.name}' is configured twice`);
+      }
+      names.add(server.name);
+    }
+
+    const request: SessionCreateRequest = { agent };
+    if (this.sessionId !== undefined) {
+      request.id = this.sessionId.trim();
+    }
+    if (this.workingDirectory !== undefined || this.mcpServers.length > 0) {
+      // createSession fills in the default cwd when it is missing.
+      request.sessionInit = {
+        ...(this.workingDirectory !== undefined ? { cwd: this.workingDirectory } : {}),
+        mcpServers: [...this.mcpServers],
+      } as SessionCreateRequest["sessionInit"];
+    }
+    return request;
+  }
+}
+
+export class PromptBuilder {
+  private readonly blocks: ContentBlock[] = [];
+
+  text(text: string): this {
+    this.blocks.push({ type: "text", text });
+    return this;
+  }
+
+  /** Attach base64-encoded image data. */
+  image(data: string, mimeType: `image/${string}`): this {
+    this.blocks.push({ type: "image", data, mimeType });
+    return this;
+  }
+
+  resourceLink(uri: string, name: string): this {
+    this.blocks.push({ type: "resource_link", uri, name });
+    return this;
+  }
+
+  build(): PromptRequest["prompt"] {
+    if (this.blocks.length === 0) {
+      throw new Error("prompt requires at least one content block");
+    }
+    if (this.blocks.every((block) => block.type === "text" && !block.text.trim())) {
+      throw new Error("prompt text must not be blank");
+    }
+    return [...this.blocks];
+  }
+}
+
+function isAbsolutePath(path: string): boolean {
+  return path.startsWith("/") || /^[A-Za-z]:[\\/]/.test(path);
+}
@@ -13,13 +13,47 @@ import {
   type SetSessionConfigOptionRequest,
   type SetSessionModeRequest,
 } from "acp-http-client";
+import { renderSessionNotebook, type SessionNotebookOptions } from "./notebook.ts";
 import type { SandboxAgentSpawnHandle, SandboxAgentSpawnOptions } from "./spawn.ts";
 import {
+  type AcpAttachmentsQuery,
+  type AcpAttachmentsResponse,
+  type AcpChildSessionsResponse,
+  type AcpCompactResponse,
+  type AcpEnvelope,
+  type AcpEventsQuery,
+  type AcpEventsResponse,
+  type AcpInterruptResponse,
+  type AcpPendingRequestsQuery,
+  type AcpPendingRequestsResponse,
+  type AcpPlanApproveRequest,
+  type AcpPlanApproveResponse,
+  type AcpPlanResponse,
+  type AcpQueueMessageRequest,
+  type AcpQueuedMessage,
+  type AcpQueuedMessagesResponse,
+  type AcpRetryRequest,
+  type AcpRetryResponse,
+  type AcpSecretInput,
+  type AcpSecretsResponse,
   type AcpServerListResponse,
+  type AcpSessionDetailResponse,
+  type AcpSessionUpdateRequest,
+  type AcpTranscriptResponse,
+  type AcpTurnResultQuery,
+  type AcpTurnResultResponse,
+  type AcpValidateResponse,
   type AgentInfo,
   type AgentInstallRequest,
   type AgentInstallResponse,
   type AgentListResponse,
+  type AgentModelsResponse,
+  type AgentPinInfo,
+  type AgentUninstallResponse,
+  type AgentUpgradeRequest,
+  type AgentUpgradeResponse,
+  type AgentVersionsResponse,
+  type ErrorCode,
   type FsActionResponse,
   type FsDeleteQuery,
   type FsEntriesQuery,
@@ -30,20 +64,32 @@ import {
   type FsStat,
   type FsUploadBatchQuery,
   type FsUploadBatchResponse,
+  type FsWorkspaceInfo,
   type FsWriteResponse,
   type HealthResponse,
   InMemorySessionPersistDriver,
   type ListEventsRequest,
   type ListPage,
   type ListPageRequest,
+  type LogsQuery,
+  type LogsResponse,
   type McpConfigQuery,
   type McpServerConfig,
   type ProblemDetails,
+  type ScheduleCreateRequest,
+  type ScheduleInfo,
+  type ScheduleListResponse,
+  type ServerConfigResponse,
   type SessionEvent,
   type SessionPersistDriver,
   type SessionRecord,
   type SkillsConfig,
   type SkillsConfigQuery,
+  type StreamSubscribeRequest,
+  type StreamSubscriptionsResponse,
+  type TaskCreateRequest,
+  type TaskInfo,
+  type TaskListResponse,
 } from "./types.ts";
 
 const API_PREFIX = "/v1";
@@ -52,6 +98,7 @@ const FS_PATH = `${API_PREFIX}/fs`;
 const DEFAULT_REPLAY_MAX_EVENTS = 50;
 const DEFAULT_REPLAY_MAX_CHARS = 12_000;
 const EVENT_INDEX_SCAN_EVENTS_LIMIT = 500;
+const EXPORT_EVENTS_PAGE_SIZE = 500;
 
 export interface SandboxAgentConnectOptions {
   baseUrl: string;
@@ -89,6 +136,10 @@ export class SandboxAgentError extends Error {
   readonly status: number;
   readonly problem?: ProblemDetails;
   readonly response: Response;
+  /** Stable cause of the failure, when the server sent one. */
+  readonly code?: ErrorCode;
+  /** Whether sending the same request again may succeed. */
+  readonly retryable: boolean;
 
   constructor(status: number, problem: ProblemDetails | undefined, response: Response) {
     super(problem?.title ?? `Request failed with status ${status}`);
@@ -96,6 +147,8 @@ export class SandboxAgentError extends Error {
     this.status = status;
     this.problem = problem;
     this.response = response;
+    this.code = problem?.code;
+    this.retryable = problem?.retryable ?? false;
   }
 }
 
@@ -560,6 +613,13 @@ export class SandboxAgent {
     return this.createSession(request);
   }
 
+  /** Render a session's persisted history as a Markdown or HTML report. */
+  async exportSession(id: string, options: SessionNotebookOptions = {}): Promise<string> {
+    const record = await this.requireSessionRecord(id);
+    const events = await this.collectSessionEvents(id, EXPORT_EVENTS_PAGE_SIZE);
+    return renderSessionNotebook(record, events, options);
+  }
+
   async destroySession(id: string): Promise<Session> {
     const existing = await this.persist.getSession(id);
     if (!existing) {
@@ -622,6 +682,21 @@ export class SandboxAgent {
     return this.requestJson("GET", `${API_PREFIX}/health`);
   }
 
+  /** Settings the server is running with. The token and agent env values are masked. */
+  async getServerConfig(): Promise<ServerConfigResponse> {
+    return this.requestJson("GET", `${API_PREFIX}/config`);
+  }
+
+  /** Recent server log events, oldest first. Pass `nextSince` back as `since` to page. */
+  async getLogs(query: LogsQuery = {}): Promise<LogsResponse> {
+    return this.requestJson("GET", `${API_PREFIX}/logs`, { query });
+  }
+
+  /** OpenAPI document for the connected server, for generating clients at runtime. */
+  async getOpenApiDocument(): Promise<Record<string, unknown>> {
+    return this.requestJson("GET", `${API_PREFIX}/openapi.json`);
+  }
+
   async listAgents(options?: { config?: boolean }): Promise<AgentListResponse> {
     return this.requestJson("GET", `${API_PREFIX}/agents`, {
       query: options?.config ? { config: "true" } : undefined,
@@ -634,16 +709,339 @@ export class SandboxAgent {
     });
   }
 
+  async getAgentModels(agent: string): Promise<AgentModelsResponse> {
+    return this.requestJson("GET", `${API_PREFIX}/agents/${encodeURIComponent(agent)}/models`);
+  }
+
   async installAgent(agent: string, request: AgentInstallRequest = {}): Promise<AgentInstallResponse> {
     return this.requestJson("POST", `${API_PREFIX}/agents/${encodeURIComponent(agent)}/install`, {
       body: request,
     });
   }
 
+  /** Remove an agent's installed files. Pins are kept. */
+  async uninstallAgent(agent: string): Promise<AgentUninstallResponse> {
+    return this.requestJson("DELETE", `${API_PREFIX}/agents/${encodeURIComponent(agent)}`);
+  }
+
+  async getAgentVersions(agent: string): Promise<AgentVersionsResponse> {
+    return this.requestJson("GET", `${API_PREFIX}/agents/${encodeURIComponent(agent)}/versions`);
+  }
+
+  async pinAgent(agent: string, pin: AgentPinInfo): Promise<AgentPinInfo> {
+    return this.requestJson("PUT", `${API_PREFIX}/agents/${encodeURIComponent(agent)}/pin`, {
+      body: pin,
+    });
+  }
+
+  async upgradeAgent(agent: string, request: AgentUpgradeRequest = {}): Promise<AgentUpgradeResponse> {
+    return this.requestJson("POST", `${API_PREFIX}/agents/${encodeURIComponent(agent)}/upgrade`, {
+      body: request,
+    });
+  }
+
   async listAcpServers(): Promise<AcpServerListResponse> {
     return this.requestJson("GET", `${API_PREFIX}/acp`);
   }
 
+  /**
+   * Read buffered envelopes after `query.offset`. With `waitMs`, the server
+   * holds the request until one arrives, so pollers need not busy-loop.
+   */
+  async pollAcpEvents(serverId: string, query: AcpEventsQuery = {}): Promise<AcpEventsResponse> {
+    return this.requestJson("GET", `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/events`, {
+      query,
+    });
+  }
+
+  /**
+   * List agent requests, such as permission prompts, that no client has
+   * answered yet, so a reconnecting client can pick them back up.
+   */
+  async listAcpPendingRequests(
+    serverId: string,
+    query: AcpPendingRequestsQuery = {},
+  ): Promise<AcpPendingRequestsResponse> {
+    return this.requestJson("GET", `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/requests`, {
+      query,
+    });
+  }
+
+  /**
+   * Everything the agent process printed during turn `turn`, one `[stdout]`
+   * or `[stderr]` line each. Requires `SANDBOX_AGENT_TURN_LOG_DIR` on the server.
+   */
+  async getAcpTurnLogs(serverId: string, turn: number): Promise<string> {
+    const response = await this.requestRaw(
+      "GET",
+      `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/turns/${turn}/logs`,
+      { accept: "text/plain" },
+    );
+    return response.text();
+  }
+
+  async getAcpSessionArtifact(serverId: string, sessionId: string, artifactId: string): Promise<string> {
+    const response = await this.requestRaw(
+      "GET",
+      `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/sessions/${encodeURIComponent(sessionId)}/artifacts/${encodeURIComponent(artifactId)}`,
+    );
+    return response.text();
+  }
+
+  async getAcpSession(serverId: string, sessionId: string): Promise<AcpSessionDetailResponse> {
+    return this.requestJson(
+      "GET",
+      `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/sessions/${encodeURIComponent(sessionId)}`,
+    );
+  }
+
+  /**
+   * Change the session's mode, model or permission mode. Returns the
+   * session snapshot after the change.
+   */
+  async updateAcpSession(
+    serverId: string,
+    sessionId: string,
+    request: AcpSessionUpdateRequest,
+  ): Promise<AcpSessionDetailResponse> {
+    return this.requestJson(
+      "PATCH",
+      `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/sessions/${encodeURIComponent(sessionId)}`,
+      { body: request },
+    );
+  }
+
+  /**
+   * The session's messages merged from streamed chunks. Use
+   * `getAcpSessionTranscriptText` for the `text` and `markdown` formats.
+   */
+  async getAcpSessionTranscript(serverId: string, sessionId: string): Promise<AcpTranscriptResponse> {
+    return this.requestJson(
+      "GET",
+      `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/sessions/${encodeURIComponent(sessionId)}/transcript`,
+    );
+  }
+
+  async getAcpSessionTranscriptText(
+    serverId: string,
+    sessionId: string,
+    format: "text" | "markdown" = "text",
+  ): Promise<string> {
+    const response = await this.requestRaw(
+      "GET",
+      `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/sessions/${encodeURIComponent(sessionId)}/transcript`,
+      { query: { format }, accept: format === "markdown" ? "text/markdown" : "text/plain" },
+    );
+    return response.text();
+  }
+
+  /**
+   * Wait up to `timeoutMs` for the session's prompt to finish, then return
+   * its final reply, status, usage and changed files.
+   */
+  async getAcpSessionResult(
+    serverId: string,
+    sessionId: string,
+    query: AcpTurnResultQuery = {},
+  ): Promise<AcpTurnResultResponse> {
+    return this.requestJson(
+      "GET",
+      `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/sessions/${encodeURIComponent(sessionId)}/result`,
+      { query },
+    );
+  }
+
+  async uploadAcpAttachments(
+    serverId: string,
+    body: FormData,
+    query?: AcpAttachmentsQuery,
+  ): Promise<AcpAttachmentsResponse> {
+    // No content type: fetch sets multipart/form-data with its boundary.
+    const response = await this.requestRaw(
+      "POST",
+      `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/attachments`,
+      { query, rawBody: body, accept: "application/json" },
+    );
+    return (await response.json()) as AcpAttachmentsResponse;
+  }
+
+  async compactAcpSession(serverId: string, sessionId: string): Promise<AcpCompactResponse> {
+    return this.requestJson("POST", `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/compact`, {
+      body: { sessionId },
+    });
+  }
+
+  /**
+   * The plan from the session's latest turn in plan mode, with any file
+   * changes the agent made while planning.
+   */
+  async getAcpSessionPlan(serverId: string, sessionId: string): Promise<AcpPlanResponse> {
+    return this.requestJson(
+      "GET",
+      `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/sessions/${encodeURIComponent(sessionId)}/plan`,
+    );
+  }
+
+  /**
+   * Approve the session's latest plan: switch it to build mode and send the
+   * plan back as the next prompt. Resolves when the build turn finishes.
+   */
+  async approveAcpSessionPlan(
+    serverId: string,
+    sessionId: string,
+    request: AcpPlanApproveRequest = {},
+  ): Promise<AcpPlanApproveResponse> {
+    return this.requestJson(
+      "POST",
+      `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/sessions/${encodeURIComponent(sessionId)}/plan/approve`,
+      { body: request },
+    );
+  }
+
+  /**
+   * Sub-agents the session started, such as Claude `Task` calls, with the
+   * child each nested one came from.
+   */
+  async getAcpSessionChildren(serverId: string, sessionId: string): Promise<AcpChildSessionsResponse> {
+    return this.requestJson(
+      "GET",
+      `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/sessions/${encodeURIComponent(sessionId)}/children`,
+    );
+  }
+
+  /**
+   * Retract the session's last turn and run its prompt again, with its text
+   * replaced when `request.text` is set. Resolves when the new turn finishes.
+   */
+  async retryAcpSessionTurn(
+    serverId: string,
+    sessionId: string,
+    request: AcpRetryRequest = {},
+  ): Promise<AcpRetryResponse> {
+    return this.requestJson(
+      "POST",
+      `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/sessions/${encodeURIComponent(sessionId)}/retry`,
+      { body: request },
+    );
+  }
+
+  /**
+   * Interrupt the session's turn. The agent gets `session/cancel`; one that
+   * ignores it has its process killed.
+   */
+  async interruptAcpSession(serverId: string, sessionId: string): Promise<AcpInterruptResponse> {
+    return this.requestJson(
+      "POST",
+      `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/sessions/${encodeURIComponent(sessionId)}/interrupt`,
+    );
+  }
+
+  /**
+   * Queue a prompt to send once the session's current turn completes, after
+   * any messages queued before it.
+   */
+  async queueAcpSessionMessage(
+    serverId: string,
+    sessionId: string,
+    request: AcpQueueMessageRequest,
+  ): Promise<AcpQueuedMessage> {
+    return this.requestJson(
+      "POST",
+      `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/sessions/${encodeURIComponent(sessionId)}/messages`,
+      { body: request },
+    );
+  }
+
+  async listAcpSessionMessages(serverId: string, sessionId: string): Promise<AcpQueuedMessagesResponse> {
+    return this.requestJson(
+      "GET",
+      `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/sessions/${encodeURIComponent(sessionId)}/messages`,
+    );
+  }
+
+  async cancelAcpSessionMessage(serverId: string, sessionId: string, messageId: string): Promise<void> {
+    await this.requestRaw(
+      "DELETE",
+      `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/sessions/${encodeURIComponent(sessionId)}/messages/${encodeURIComponent(messageId)}`,
+    );
+  }
+
+  async setAcpSecrets(serverId: string, secrets: AcpSecretInput[]): Promise<AcpSecretsResponse> {
+    return this.requestJson("POST", `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/secrets`, {
+      body: { secrets },
+    });
+  }
+
+  async validateAcpServer(
+    serverId: string,
+    envelope: AcpEnvelope,
+    options?: { agent?: string },
+  ): Promise<AcpValidateResponse> {
+    return this.requestJson("POST", `${API_PREFIX}/acp/${encodeURIComponent(serverId)}/validate`, {
+      query: options?.agent ? { agent: options.agent } : undefined,
+      body: envelope,
+    });
+  }
+
+  async subscribeStream(
+    channelId: string,
+    request: StreamSubscribeRequest,
+  ): Promise<StreamSubscriptionsResponse> {
+    return this.requestJson("POST", `${API_PREFIX}/stream/${encodeURIComponent(channelId)}/subscribe`, {
+      body: request,
+    });
+  }
+
+  async unsubscribeStream(channelId: string, serverIds: string[]): Promise<StreamSubscriptionsResponse> {
+    return this.requestJson("POST", `${API_PREFIX}/stream/${encodeURIComponent(channelId)}/unsubscribe`, {
+      body: { serverIds },
+    });
+  }
+
+  /**
+   * Send one prompt to several agents, each in its own ACP server. Returns
+   * immediately; poll `getTask` until `finished` to read every run's output.
+   */
+  async createTask(request: TaskCreateRequest): Promise<TaskInfo> {
+    return this.requestJson("POST", `${API_PREFIX}/tasks`, {
+      body: request,
+    });
+  }
+
+  async listTasks(): Promise<TaskListResponse> {
+    return this.requestJson("GET", `${API_PREFIX}/tasks`);
+  }
+
+  async getTask(taskId: string): Promise<TaskInfo> {
+    return this.requestJson("GET", `${API_PREFIX}/tasks/${encodeURIComponent(taskId)}`);
+  }
+
+  async deleteTask(taskId: string): Promise<void> {
+    await this.requestRaw("DELETE", `${API_PREFIX}/tasks/${encodeURIComponent(taskId)}`);
+  }
+
+  /**
+   * Start a task each time a UTC cron expression fires. Each run's task id
+   * is listed on the schedule; read it with `getTask`.
+   */
+  async createSchedule(request: ScheduleCreateRequest): Promise<ScheduleInfo> {
+    return this.requestJson("POST", `${API_PREFIX}/schedules`, {
+      body: request,
+    });
+  }
+
+  async listSchedules(): Promise<ScheduleListResponse> {
+    return this.requestJson("GET", `${API_PREFIX}/schedules`);
+  }
+
+  async getSchedule(scheduleId: string): Promise<ScheduleInfo> {
+    return this.requestJson("GET", `${API_PREFIX}/schedules/${encodeURIComponent(scheduleId)}`);
+  }
+
+  async deleteSchedule(scheduleId: string): Promise<void> {
+    await this.requestRaw("DELETE", `${API_PREFIX}/schedules/${encodeURIComponent(scheduleId)}`);
+  }
+
   async listFsEntries(query: FsEntriesQuery = {}): Promise<FsEntry[]> {
     return this.requestJson("GET", `${FS_PATH}/entries`, {
       query,
@@ -685,6 +1083,10 @@ export class SandboxAgent {
     return this.requestJson("GET", `${FS_PATH}/stat`, { query });
   }
 
+  async getFsWorkspace(query: FsPathQuery): Promise<FsWorkspaceInfo> {
+    return this.requestJson("GET", `${FS_PATH}/workspace`, { query });
+  }
+
   async uploadFsBatch(body: BodyInit, query?: FsUploadBatchQuery): Promise<FsUploadBatchResponse> {
     const response = await this.requestRaw("POST", `${FS_PATH}/upload-batch`, {
       query,
@@ -829,6 +1231,11 @@ export class SandboxAgent {
   }
 
   private async collectReplayEvents(sessionId: string, maxEvents: number): Promise<SessionEvent[]> {
+    const all = await this.collectSessionEvents(sessionId, Math.max(100, maxEvents));
+    return all.slice(-maxEvents);
+  }
+
+  private async collectSessionEvents(sessionId: string, pageSize: number): Promise<SessionEvent[]> {
     const all: SessionEvent[] = [];
     let cursor: string | undefined;
 
@@ -836,7 +1243,7 @@ export class SandboxAgent {
       const page = await this.persist.listEvents({
         sessionId,
         cursor,
-        limit: Math.max(100, maxEvents),
+        limit: pageSize,
       });
 
       all.push(...page.items);
@@ -848,7 +1255,7 @@ export class SandboxAgent {
       cursor = page.nextCursor;
     }
 
-    return all.slice(-maxEvents);
+    return all;
   }
 
   private upsertSessionHandle(record: SessionRecord): Session {
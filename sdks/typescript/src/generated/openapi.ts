@@ -13,15 +13,99 @@ export interface paths {
     post: operations["post_v1_acp"];
     delete: operations["delete_v1_acp"];
   };
+  "/v1/acp/{server_id}/attachments": {
+    post: operations["post_v1_acp_attachments"];
+  };
+  "/v1/acp/{server_id}/compact": {
+    post: operations["post_v1_acp_compact"];
+  };
+  "/v1/acp/{server_id}/events": {
+    get: operations["get_v1_acp_events"];
+  };
+  "/v1/acp/{server_id}/import": {
+    post: operations["post_v1_acp_import"];
+  };
+  "/v1/acp/{server_id}/requests": {
+    get: operations["get_v1_acp_requests"];
+  };
+  "/v1/acp/{server_id}/secrets": {
+    post: operations["post_v1_acp_secrets"];
+  };
+  "/v1/acp/{server_id}/sessions/{session_id}": {
+    get: operations["get_v1_acp_session"];
+    patch: operations["patch_v1_acp_session"];
+  };
+  "/v1/acp/{server_id}/sessions/{session_id}/artifacts/{artifact_id}": {
+    get: operations["get_v1_acp_session_artifact"];
+  };
+  "/v1/acp/{server_id}/sessions/{session_id}/children": {
+    get: operations["get_v1_acp_session_children"];
+  };
+  "/v1/acp/{server_id}/sessions/{session_id}/interrupt": {
+    post: operations["post_v1_acp_session_interrupt"];
+  };
+  "/v1/acp/{server_id}/sessions/{session_id}/messages": {
+    get: operations["get_v1_acp_session_messages"];
+    post: operations["post_v1_acp_session_messages"];
+  };
+  "/v1/acp/{server_id}/sessions/{session_id}/messages/{message_id}": {
+    delete: operations["delete_v1_acp_session_message"];
+  };
+  "/v1/acp/{server_id}/sessions/{session_id}/plan": {
+    get: operations["get_v1_acp_session_plan"];
+  };
+  "/v1/acp/{server_id}/sessions/{session_id}/plan/approve": {
+    post: operations["post_v1_acp_session_plan_approve"];
+  };
+  "/v1/acp/{server_id}/sessions/{session_id}/result": {
+    get: operations["get_v1_acp_session_result"];
+  };
+  "/v1/acp/{server_id}/sessions/{session_id}/retry": {
+    post: operations["post_v1_acp_session_retry"];
+  };
+  "/v1/acp/{server_id}/sessions/{session_id}/transcript": {
+    get: operations["get_v1_acp_session_transcript"];
+  };
+  "/v1/acp/{server_id}/share": {
+    post: operations["post_v1_acp_share"];
+  };
+  "/v1/acp/{server_id}/turns/{turn}/logs": {
+    get: operations["get_v1_acp_turn_logs"];
+  };
+  "/v1/acp/{server_id}/validate": {
+    post: operations["post_v1_acp_validate"];
+  };
   "/v1/agents": {
     get: operations["get_v1_agents"];
   };
   "/v1/agents/{agent}": {
     get: operations["get_v1_agent"];
+    delete: operations["delete_v1_agent"];
   };
   "/v1/agents/{agent}/install": {
     post: operations["post_v1_agent_install"];
   };
+  "/v1/agents/{agent}/install/progress": {
+    get: operations["get_v1_agent_install_progress"];
+  };
+  "/v1/agents/{agent}/models": {
+    get: operations["get_v1_agent_models"];
+  };
+  "/v1/agents/{agent}/pin": {
+    put: operations["put_v1_agent_pin"];
+  };
+  "/v1/agents/{agent}/upgrade": {
+    post: operations["post_v1_agent_upgrade"];
+  };
+  "/v1/agents/{agent}/versions": {
+    get: operations["get_v1_agent_versions"];
+  };
+  "/v1/compat/anthropic/v1/messages": {
+    post: operations["post_v1_compat_anthropic_messages"];
+  };
+  "/v1/config": {
+    get: operations["get_v1_config"];
+  };
   "/v1/config/mcp": {
     get: operations["get_v1_config_mcp"];
     put: operations["put_v1_config_mcp"];
@@ -54,15 +138,120 @@ export interface paths {
   "/v1/fs/upload-batch": {
     post: operations["post_v1_fs_upload_batch"];
   };
+  "/v1/fs/workspace": {
+    get: operations["get_v1_fs_workspace"];
+  };
   "/v1/health": {
     get: operations["get_v1_health"];
   };
+  "/v1/logs": {
+    get: operations["get_v1_logs"];
+  };
+  "/v1/logs/stream": {
+    get: operations["get_v1_logs_stream"];
+  };
+  "/v1/openapi.json": {
+    get: operations["get_v1_openapi"];
+  };
+  "/v1/schedules": {
+    get: operations["get_v1_schedules"];
+    post: operations["post_v1_schedules"];
+  };
+  "/v1/schedules/{schedule_id}": {
+    get: operations["get_v1_schedule"];
+    delete: operations["delete_v1_schedule"];
+  };
+  "/v1/stream": {
+    get: operations["get_v1_stream"];
+  };
+  "/v1/stream/{channel_id}/subscribe": {
+    post: operations["post_v1_stream_subscribe"];
+  };
+  "/v1/stream/{channel_id}/unsubscribe": {
+    post: operations["post_v1_stream_unsubscribe"];
+  };
+  "/v1/tasks": {
+    get: operations["get_v1_tasks"];
+    post: operations["post_v1_tasks"];
+  };
+  "/v1/tasks/{task_id}": {
+    get: operations["get_v1_task"];
+    delete: operations["delete_v1_task"];
+  };
 }
 
 export type webhooks = Record<string, never>;
 
 export interface components {
   schemas: {
+    AcpAttachmentInfo: {
+      mimeType?: string | null;
+      name: string;
+      path: string;
+      /** Format: int64 */
+      sizeBytes: number;
+      /** @description `file://` URI of `path`, ready for a `resource_link` prompt block. */
+      uri: string;
+    };
+    AcpAttachmentsQuery: {
+      dir?: string | null;
+    };
+    AcpAttachmentsResponse: {
+      attachments: components["schemas"]["AcpAttachmentInfo"][];
+    };
+    AcpChangedFile: {
+      /** @description `edit`, `delete` or `move`. */
+      kind: string;
+      path: string;
+    };
+    AcpChildSession: {
+      /** @description Sub-agent type the tool call asked for. */
+      agent?: string | null;
+      description?: string | null;
+      /**
+       * Format: int64
+       * @description Envelopes the sub-agent sent itself.
+       */
+      eventCount: number;
+      /** @description `toolCallId` of the tool call that started the sub-agent. */
+      id: string;
+      /** @description Child session this one was started from, when sub-agents nest. */
+      parentId?: string | null;
+      /** Format: int64 */
+      startedSequence: number;
+      /** @description Latest tool call status. */
+      status?: string | null;
+    };
+    AcpChildSessionsResponse: {
+      childSessions: components["schemas"]["AcpChildSession"][];
+      sessionId: string;
+    };
+    AcpCompactRequest: {
+      sessionId: string;
+    };
+    AcpCompactResponse: {
+      /**
+       * Format: int64
+       * @description Estimated tokens of the compaction turn's reply.
+       */
+      afterTokens: number;
+      /**
+       * Format: int64
+       * @description Estimated tokens of session text since the last compaction.
+       */
+      beforeTokens: number;
+      mode: components["schemas"]["AcpCompactionMode"];
+      sessionId: string;
+      stopReason?: string | null;
+      /** @description The agent's summary, in `summary` mode, for seeding a new session. */
+      summary?: string | null;
+    };
+    /**
+     * @description `native` ran the agent's own `/compact`; `summary` asked the agent to
+     * summarize the session.
+     * @enum {string}
+     */
+    AcpCompactionMode: "native" | "summary";
     AcpEnvelope: {
       error?: unknown;
       id?: unknown;
@@ -71,18 +260,390 @@ export interface components {
       params?: unknown;
       result?: unknown;
     };
+    AcpEventEnvelope: {
+      /** @description Child session that sent this envelope, when it came from a sub-agent. */
+      parentId?: string | null;
+      payload: unknown;
+      /** Format: int64 */
+      sequence: number;
+    };
+    AcpEventsResponse: {
+      events: components["schemas"]["AcpEventEnvelope"][];
+      /**
+       * Format: int64
+       * @description Pass back as `offset` to read the envelopes after these.
+       */
+      nextOffset: number;
+    };
+    AcpImportSessionRequest: {
+      agent: string;
+      /**
+       * @description Working directory the agent loads the session in. Required, since
+       * agents look native sessions up by the directory they ran in.
+       */
+      cwd: string;
+      nativeSessionId: string;
+    };
+    AcpImportSessionResponse: {
+      agent: string;
+      result?: unknown;
+      serverId: string;
+      sessionId: string;
+    };
+    AcpInterruptResponse: {
+      /**
+       * @description `idle` when no prompt was in flight, `cancelled` when the agent ended
+       * the turn after `session/cancel`, or `killed` when it ignored it and
+       * its process was stopped.
+       */
+      outcome: string;
+      sessionId: string;
+    };
+    AcpPendingRequestInfo: {
+      /** Format: int64 */
+      ageMs: number;
+      /**
+       * Format: int64
+       * @description When the server rejects the request on the client's behalf.
+       */
+      expiresAtMs?: number | null;
+      /** @description JSON-RPC id to answer with. */
+      id: unknown;
+      method: string;
+      /** @description The request envelope as the agent sent it. */
+      payload: unknown;
+      /** Format: int64 */
+      receivedAtMs: number;
+      /**
+       * Format: int64
+       * @description Event id of the request on the server's stream.
+       */
+      sequence: number;
+      sessionId?: string | null;
+    };
+    AcpPendingRequestsResponse: {
+      requests: components["schemas"]["AcpPendingRequestInfo"][];
+    };
+    AcpPipelineInfo: {
+      /**
+       * Format: int64
+       * @description Approximate size of the buffered envelopes in bytes.
+       */
+      bufferedBytes: number;
+      /**
+       * Format: int64
+       * @description Envelopes held in the replay buffer.
+       */
+      bufferedEnvelopes: number;
+      /**
+       * Format: int64
+       * @description Agent stdout lines discarded for exceeding the line limit.
+       */
+      oversizedLines: number;
+    };
+    AcpPlanApprovalInfo: {
+      /** Format: int64 */
+      approvedAtMs: number;
+      /** @description Mode the session was switched to. */
+      modeId: string;
+      note?: string | null;
+    };
+    AcpPlanApproveRequest: {
+      /**
+       * @description Mode to build in; defaults to the agent's build mode (`default` for
+       * Claude, `auto` for Codex, `build` for OpenCode).
+       */
+      modeId?: string | null;
+      /** @description Added to the build prompt after the plan. */
+      note?: string | null;
+    };
+    AcpPlanApproveResponse: {
+      approval: components["schemas"]["AcpPlanApprovalInfo"];
+      sessionId: string;
+      /** @description Stop reason of the build turn. */
+      stopReason?: string | null;
+    };
+    AcpPlanResponse: {
+      approval?: components["schemas"]["AcpPlanApprovalInfo"] | null;
+      /** Format: int64 */
+      createdAtMs: number;
+      /** @description Entries of the ACP `plan` update, when the plan came from one. */
+      entries: unknown[];
+      /** @description True when no tool call changed files during the turn. */
+      readOnly: boolean;
+      sessionId: string;
+      /** @description Where the text came from: `plan_update`, `exit_plan_mode` or `reply`. */
+      source: string;
+      text: string;
+      /**
+       * Format: int64
+       * @description Turn number from `_adapter/turn_started`.
+       */
+      turn?: number | null;
+      violations: components["schemas"]["AcpPlanViolation"][];
+    };
+    AcpPlanViolation: {
+      /** @description ACP tool kind: `edit`, `delete` or `move`. */
+      kind: string;
+      title?: string | null;
+      toolCallId?: string | null;
+    };
     AcpPostQuery: {
       agent?: string | null;
     };
+    AcpQueueMessageRequest: {
+      /** @description ACP content blocks, as in `session/prompt`. */
+      prompt: unknown[];
+      /** @description When to send. Only `after_current_turn`, the default, is supported. */
+      when?: string | null;
+    };
+    AcpQueuedMessage: {
+      /** Format: int64 */
+      createdAtMs: number;
+      id: string;
+      prompt: unknown[];
+      /** @description JSON-RPC id of the prompt, and `requestId` of its turn notifications. */
+      requestId: string;
+      sessionId: string;
+      /** @description `queued`, or `sending` once its prompt went to the agent. */
+      status: string;
+    };
+    AcpQueuedMessagesResponse: {
+      messages: components["schemas"]["AcpQueuedMessage"][];
+      sessionId: string;
+    };
+    AcpRetractedTurn: {
+      /**
+       * Format: int64
+       * @description Envelopes of the session from this sequence to `lastSequence`
+       * belong to the retracted turn.
+       */
+      firstSequence: number;
+      /** Format: int64 */
+      lastSequence: number;
+      /**
+       * Format: int64
+       * @description Turn number from `_adapter/turn_started`.
+       */
+      turn?: number | null;
+    };
+    AcpRetryRequest: {
+      /** @description Replaces the text of the last prompt. Images and resources are kept. */
+      text?: string | null;
+    };
+    AcpRetryResponse: {
+      retracted: components["schemas"]["AcpRetractedTurn"];
+      sessionId: string;
+      /** @description Stop reason of the new turn. */
+      stopReason?: string | null;
+    };
+    AcpSecretInput: {
+      /** @description Write the value to an owner-only file and set the variable to its path. */
+      file?: boolean;
+      /** @description Environment variable name the agent process sees. */
+      name: string;
+      value: string;
+    };
+    AcpSecretsRequest: {
+      secrets: components["schemas"]["AcpSecretInput"][];
+    };
+    AcpSecretsResponse: {
+      names: string[];
+      /**
+       * @description The agent process is already running and will not see these values
+       * until the server is recreated.
+       */
+      restartRequired: boolean;
+    };
     AcpServerInfo: {
       agent: string;
       /** Format: int64 */
       createdAtMs: number;
+      lastTurnTiming?: components["schemas"]["AcpTurnTimingInfo"] | null;
+      pipeline: components["schemas"]["AcpPipelineInfo"];
       serverId: string;
+      startupTiming: components["schemas"]["AcpStartupTimingInfo"];
     };
     AcpServerListResponse: {
       servers: components["schemas"]["AcpServerInfo"][];
     };
+    AcpSessionDetailResponse: {
+      agent: string;
+      availableModels: unknown[];
+      /**
+       * @description `modes.availableModes` from the agent's `session/new` or
+       * `session/load` result.
+       */
+      availableModes: unknown[];
+      /**
+       * Format: int64
+       * @description Envelopes for the session still in the server's replay buffer.
+       */
+      bufferedEvents: number;
+      /** Format: int64 */
+      createdAtMs: number;
+      ended?: components["schemas"]["AcpSessionEndInfo"] | null;
+      /** Format: int64 */
+      lastPromptAtMs?: number | null;
+      /** @description Current ACP mode id, while the session is open. */
+      modeId?: string | null;
+      /** @description Current model id, from that result or the last `session/set_model`. */
+      modelId?: string | null;
+      /**
+       * Format: int64
+       * @description The server's latest event id. Pass it as `offset` or `Last-Event-ID`
+       * to follow the session from this snapshot on.
+       */
+      nextOffset: number;
+      /**
+       * Format: int64
+       * @description Unanswered `session/request_permission` requests from the agent.
+       */
+      pendingPermissions: number;
+      /**
+       * Format: int64
+       * @description Unanswered agent requests of other methods.
+       */
+      pendingQuestions: number;
+      promptInFlight: boolean;
+      /**
+       * Format: int64
+       * @description `session/prompt` requests sent to the session.
+       */
+      prompts: number;
+      serverId: string;
+      sessionId: string;
+    };
+    AcpSessionEndInfo: {
+      /**
+       * Format: int64
+       * @description Time from `session/new` or `session/load` to the end.
+       */
+      durationMs: number;
+      /** Format: int64 */
+      endedAtMs: number;
+      /** Format: int32 */
+      exitCode?: number | null;
+      /**
+       * @description `completed` or `crashed` when the agent process exited on its own,
+       * `timeout` when a turn limit killed it, or `terminated` when the
+       * server was deleted or an interrupt killed it.
+       */
+      reason: string;
+      /**
+       * Format: int32
+       * @description Signal that terminated the process (unix only).
+       */
+      signal?: number | null;
+    };
+    AcpSessionUpdateRequest: {
+      /** @description ACP mode id to switch to with `session/set_mode`. */
+      modeId?: string | null;
+      /** @description Model id to switch to with `session/set_model`. */
+      modelId?: string | null;
+      /**
+       * @description Permission mode, as in `_meta["sandboxagent.dev"].permissionMode`.
+       * Only agents that enforce it per session (Claude) can change it on an
+       * open session.
+       */
+      permissionMode?: unknown;
+    };
+    AcpShareRequest: {
+      /** Format: int64 */
+      ttlMs?: number | null;
+    };
+    AcpShareResponse: {
+      /** Format: int64 */
+      expiresAtMs: number;
+      url: string;
+    };
+    AcpStartupTimingInfo: {
+      /** Format: int64 */
+      installMs: number;
+      /** Format: int64 */
+      resolveMs: number;
+      /** Format: int64 */
+      spawnMs: number;
+      /** @description The server adopted a standby process from the warm pool. */
+      warm: boolean;
+    };
+    AcpTranscriptMessage: {
+      /** @description `user` or `assistant`. */
+      role: string;
+      text: string;
+      /**
+       * Format: int64
+       * @description Turn number from `_adapter/turn_started`; absent for replayed history.
+       */
+      turn?: number | null;
+    };
+    AcpTranscriptResponse: {
+      messages: components["schemas"]["AcpTranscriptMessage"][];
+      sessionId: string;
+    };
+    AcpTurnResultResponse: {
+      changedFiles: components["schemas"]["AcpChangedFile"][];
+      /** @description JSON-RPC error of a failed turn. */
+      error?: unknown;
+      /** @description The last assistant message of the turn, merged from its chunks. */
+      message?: string | null;
+      sessionId: string;
+      /**
+       * @description `completed`, `cancelled`, or `failed` when the agent answered with an
+       * error or the turn ended without an answer.
+       */
+      status: string;
+      stopReason?: string | null;
+      /** Format: int64 */
+      turn?: number | null;
+      usage?: components["schemas"]["AcpTurnUsageInfo"] | null;
+    };
+    AcpTurnTimingInfo: {
+      /** Format: int64 */
+      durationMs: number;
+      /** Format: int64 */
+      instanceMs: number;
+      sessionId?: string | null;
+      /** Format: int64 */
+      startedAtMs: number;
+      stopReason?: string | null;
+      /** Format: int64 */
+      timeToFirstEventMs?: number | null;
+      usage?: components["schemas"]["AcpTurnUsageInfo"] | null;
+    };
+    AcpTurnUsageInfo: {
+      /** Format: int64 */
+      cachedReadTokens?: number | null;
+      /** Format: int64 */
+      cachedWriteTokens?: number | null;
+      /** Format: double */
+      cost?: number | null;
+      costCurrency?: string | null;
+      /** Format: int64 */
+      inputTokens?: number | null;
+      /** Format: int64 */
+      outputTokens?: number | null;
+      /** Format: int64 */
+      thoughtTokens?: number | null;
+      /** Format: int64 */
+      totalTokens?: number | null;
+    };
+    AcpValidateResponse: {
+      /** @description Agent the server is, or would be, bound to. */
+      agent?: string | null;
+      agentInstalled: boolean;
+      /** @description Variables from `_meta["sandboxagent.dev"].env`. Values are not echoed. */
+      envKeys: string[];
+      /** @description The server already has a running agent process. */
+      exists: boolean;
+      problems: components["schemas"]["AcpValidationProblemInfo"][];
+      serverId: string;
+      valid: boolean;
+    };
+    AcpValidationProblemInfo: {
+      field: string;
+      message: string;
+    };
     AgentCapabilities: {
       commandExecution: boolean;
       errorEvents: boolean;
@@ -91,6 +652,11 @@ export interface components {
       images: boolean;
       itemStarted: boolean;
       mcpTools: boolean;
+      /**
+       * @description Values `_meta["sandboxagent.dev"].permissionMode` accepts for this
+       * agent.
+       */
+      permissionModes: string[];
       permissions: boolean;
       planMode: boolean;
       questions: boolean;
@@ -103,16 +669,36 @@ export interface components {
       toolCalls: boolean;
       toolResults: boolean;
     };
+    AgentIncompatibilityInfo: {
+      autoInstalled: boolean;
+      detail: string;
+      /** Format: int64 */
+      detectedAtMs: number;
+      installedVersion?: string | null;
+      /** @description `usage_error` or `schema_drift`. */
+      reason: string;
+      suggestedAgentProcessVersion?: string | null;
+      suggestedVersion?: string | null;
+    };
     AgentInfo: {
       capabilities: components["schemas"]["AgentCapabilities"];
       configError?: string | null;
       configOptions?: unknown[] | null;
       credentialsAvailable: boolean;
+      /**
+       * Format: int64
+       * @description Bytes the agent's binary, launcher and extracted files take up in the
+       * install directory. Binaries found on `PATH` count as 0.
+       */
+      diskUsageBytes: number;
       id: string;
+      incompatibility?: components["schemas"]["AgentIncompatibilityInfo"] | null;
       installed: boolean;
       path?: string | null;
       serverStatus?: components["schemas"]["ServerStatusInfo"] | null;
+      sessionDefaults?: components["schemas"]["AgentSessionDefaultsInfo"] | null;
       version?: string | null;
+      warmPool?: components["schemas"]["WarmPoolInfo"] | null;
     };
     AgentInstallArtifact: {
       kind: string;
@@ -120,9 +706,21 @@ export interface components {
       source: string;
       version?: string | null;
     };
+    AgentInstallProgressEvent: {
+      /** @description False once no install is running; this is always the last event. */
+      active: boolean;
+      agent: string;
+      /** Format: int64 */
+      downloadedBytes: number;
+      /** Format: int64 */
+      totalBytes?: number | null;
+      url?: string | null;
+    };
     AgentInstallRequest: {
       agentProcessVersion?: string | null;
       agentVersion?: string | null;
+      /** @description Install from this server-local directory instead of downloading. */
+      artifactDir?: string | null;
       reinstall?: boolean | null;
     };
     AgentInstallResponse: {
@@ -131,9 +729,101 @@ export interface components {
     };
     AgentListResponse: {
       agents: components["schemas"]["AgentInfo"][];
+      /**
+       * Format: int64
+       * @description Quota the install directory is held to, from
+       * `SANDBOX_AGENT_INSTALL_QUOTA_BYTES`.
+       */
+      diskQuotaBytes?: number | null;
+      /**
+       * Format: int64
+       * @description Bytes the whole install directory takes up.
+       */
+      diskUsageBytes: number;
+    };
+    AgentModelInfo: {
+      /** Format: int64 */
+      contextWindow?: number | null;
+      id: string;
+      name: string;
+      provider?: string | null;
+    };
+    AgentModelsResponse: {
+      agent: string;
+      defaultModel?: string | null;
+      models: components["schemas"]["AgentModelInfo"][];
+    };
+    AgentPinInfo: {
+      agentProcessVersion?: string | null;
+      agentVersion?: string | null;
+    };
+    AgentReleaseInfo: {
+      notes?: string | null;
+      publishedAt?: string | null;
+      url?: string | null;
+      version: string;
+    };
+    AgentSessionDefaultsInfo: {
+      /** @description ACP mode id. */
+      mode?: string | null;
+      model?: string | null;
+    };
+    AgentUninstallResponse: {
+      agent: string;
+      /** Format: int64 */
+      freedBytes: number;
+    };
+    AgentUpgradeRequest: {
+      agentProcessVersion?: string | null;
+      agentVersion?: string | null;
+    };
+    AgentUpgradeResponse: {
+      artifacts: components["schemas"]["AgentInstallArtifact"][];
+      /** @description Releases newer than `previousVersion`, up to the installed one. */
+      changelog: components["schemas"]["AgentReleaseInfo"][];
+      installedVersion?: string | null;
+      previousVersion?: string | null;
     };
+    AgentVersionsResponse: {
+      agent: string;
+      installedVersion?: string | null;
+      pinned?: components["schemas"]["AgentPinInfo"] | null;
+      releases: components["schemas"]["AgentReleaseInfo"][];
+    };
+    AnthropicMessageParam: {
+      /** @description A string or an array of `text` content blocks. */
+      content: unknown;
+      /** @description `user` or `assistant`. */
+      role: string;
+    };
+    AnthropicMessagesRequest: {
+      /**
+       * Format: int64
+       * @description Accepted for compatibility; agents do not take a token limit.
+       */
+      max_tokens?: number | null;
+      messages: components["schemas"]["AnthropicMessageParam"][];
+      /**
+       * @description Agent to run, such as `claude`, optionally followed by `/` and
+       * anything else. The `x-sandbox-agent` header takes precedence.
+       */
+      model: string;
+      stream?: boolean;
+      /** @description System prompt as a string or an array of text blocks. */
+      system?: unknown;
+    };
+    /**
+     * @description Stable, machine-readable cause of a failure.
+     *
+     * `type` names the broad category and fixes the HTTP status; `code` says
+     * which step failed, so clients can tell an install failure from a spawn
+     * failure or an agent that rejected the request even when both arrive as a
+     * `stream_error`. New codes may be added; existing ones keep their meaning.
+     * @enum {string}
+     */
+    ErrorCode: "invalid_request" | "conflict" | "not_found" | "not_acceptable" | "unsupported_media_type" | "unsupported_agent" | "mode_not_supported" | "policy_violation" | "payload_too_large" | "agent_not_installed" | "install_failed" | "spawn_failed" | "agent_process_exited" | "agent_auth_failed" | "agent_rate_limited" | "conversion_failed" | "upstream_agent_error" | "upstream_agent_no_response" | "token_invalid" | "permission_denied" | "timeout" | "internal";
     /** @enum {string} */
-    ErrorType: "invalid_request" | "conflict" | "unsupported_agent" | "agent_not_installed" | "install_failed" | "agent_process_exited" | "token_invalid" | "permission_denied" | "not_acceptable" | "unsupported_media_type" | "session_not_found" | "session_already_exists" | "mode_not_supported" | "stream_error" | "timeout";
+    ErrorType: "invalid_request" | "conflict" | "unsupported_agent" | "agent_not_installed" | "install_failed" | "agent_process_exited" | "token_invalid" | "permission_denied" | "not_acceptable" | "unsupported_media_type" | "session_not_found" | "session_already_exists" | "mode_not_supported" | "stream_error" | "timeout" | "policy_violation" | "payload_too_large";
     FsActionResponse: {
       path: string;
     };
@@ -180,14 +870,45 @@ export interface components {
       paths: string[];
       truncated: boolean;
     };
+    FsWorkspaceInfo: {
+      languages: string[];
+      packageManagers: string[];
+      path: string;
+      testCommands: string[];
+    };
     FsWriteResponse: {
       /** Format: int64 */
       bytesWritten: number;
       path: string;
     };
     HealthResponse: {
+      preinstall?: components["schemas"]["PreinstallStatusInfo"][];
       status: string;
     };
+    LogEntryInfo: {
+      /**
+       * @description Event fields, with fields of enclosing spans such as `http.request`
+       * merged in.
+       */
+      fields: unknown;
+      /** @description `error`, `warn`, `info`, `debug` or `trace`. */
+      level: string;
+      message: string;
+      /** Format: int64 */
+      sequence: number;
+      /** @description Module that logged the event. */
+      target: string;
+      /** Format: int64 */
+      timestampMs: number;
+    };
+    LogsResponse: {
+      entries: components["schemas"]["LogEntryInfo"][];
+      /**
+       * Format: int64
+       * @description Pass back as `since` to read the entries after these.
+       */
+      nextSince: number;
+    };
     McpConfigQuery: {
       directory: string;
       mcpName: string;
@@ -221,15 +942,132 @@ export interface components {
       type: "remote";
       url: string;
     });
+    /** @enum {string} */
+    PreinstallStatus: "pending" | "installing" | "installed" | "failed";
+    PreinstallStatusInfo: {
+      agent: string;
+      error?: string | null;
+      status: components["schemas"]["PreinstallStatus"];
+      version?: string | null;
+    };
     ProblemDetails: {
+      code: components["schemas"]["ErrorCode"];
       detail?: string | null;
       instance?: string | null;
+      retryable: boolean;
       /** Format: int32 */
       status: number;
       title: string;
       type: string;
       [key: string]: unknown;
     };
+    ScheduleCreateRequest: {
+      agents: components["schemas"]["TaskAgentConfig"][];
+      /**
+       * @description Five-field cron expression (minute, hour, day of month, month, day of
+       * week) evaluated in UTC, or one of `@hourly`, `@daily`, `@weekly`,
+       * `@monthly`, `@yearly`.
+       */
+      cron: string;
+      /** @description Working directory for every session. Defaults to the server's. */
+      cwd?: string | null;
+      prompt: string;
+    };
+    ScheduleInfo: {
+      agents: string[];
+      /** Format: int64 */
+      createdAtMs: number;
+      cron: string;
+      cwd: string;
+      /**
+       * Format: int64
+       * @description Absent when the expression never fires again.
+       */
+      nextRunAtMs?: number | null;
+      prompt: string;
+      /** @description Most recent runs, oldest first. */
+      runs: components["schemas"]["ScheduleRunInfo"][];
+      scheduleId: string;
+      /**
+       * Format: int64
+       * @description Firings skipped because the previous run had not finished.
+       */
+      skippedRuns: number;
+    };
+    ScheduleListResponse: {
+      schedules: components["schemas"]["ScheduleInfo"][];
+    };
+    ScheduleRunInfo: {
+      /** Format: int64 */
+      startedAtMs: number;
+      /** @description Task started for this firing, readable through `/v1/tasks/{task_id}`. */
+      taskId: string;
+    };
+    ServerConfigAgentInfo: {
+      env: {
+        [key: string]: string;
+      };
+      mode?: string | null;
+      model?: string | null;
+    };
+    ServerConfigAuthInfo: {
+      /** @description `********` when a token is required; absent otherwise. */
+      token?: string | null;
+    };
+    ServerConfigInstallInfo: {
+      dir?: string | null;
+      preinstall: string[];
+      requirePreinstall: boolean;
+    };
+    ServerConfigLimitsInfo: {
+      /** Format: int64 */
+      acpRequestTimeoutMs?: number | null;
+      /** Format: int64 */
+      maxBodyBytes?: number | null;
+      /** Format: int64 */
+      maxPromptBytes?: number | null;
+      /** Format: int64 */
+      maxRawBytes?: number | null;
+      /** Format: int64 */
+      pendingRequestTimeoutMs?: number | null;
+      /** Format: int64 */
+      turnIdleTimeoutMs?: number | null;
+      /** Format: int64 */
+      turnTimeoutMs?: number | null;
+    };
+    ServerConfigResponse: {
+      agents: {
+        [key: string]: components["schemas"]["ServerConfigAgentInfo"];
+      };
+      auth: components["schemas"]["ServerConfigAuthInfo"];
+      install: components["schemas"]["ServerConfigInstallInfo"];
+      limits: components["schemas"]["ServerConfigLimitsInfo"];
+      models: {
+        [key: string]: string[];
+      };
+      server: components["schemas"]["ServerConfigServerInfo"];
+      /** @description Config file the server read, if any. */
+      source?: string | null;
+      telemetry: components["schemas"]["ServerConfigTelemetryInfo"];
+    };
+    ServerConfigServerInfo: {
+      corsAllowCredentials: boolean;
+      /** @description Empty allows any header. */
+      corsAllowHeaders: string[];
+      /** @description Empty allows any method. */
+      corsAllowMethods: string[];
+      corsAllowOrigins: string[];
+      host?: string | null;
+      /** Format: int32 */
+      port?: number | null;
+      /** @description True when serving over stdin/stdout. */
+      stdio: boolean;
+      /** @description Set when serving on a Unix domain socket instead of TCP. */
+      unixSocket?: string | null;
+    };
+    ServerConfigTelemetryInfo: {
+      enabled?: boolean | null;
+    };
     /** @enum {string} */
     ServerStatus: "running" | "stopped";
     ServerStatusInfo: {
@@ -251,6 +1089,78 @@ export interface components {
       directory: string;
       skillName: string;
     };
+    StreamSubscribeRequest: {
+      lastEventIds?: {
+        [key: string]: number;
+      };
+      serverIds: string[];
+    };
+    StreamSubscriptionsResponse: {
+      channelId: string;
+      serverIds: string[];
+    };
+    StreamUnsubscribeRequest: {
+      serverIds: string[];
+    };
+    TaskAgentConfig: {
+      agent: string;
+      env?: {
+        [key: string]: string;
+      };
+    };
+    TaskCreateRequest: {
+      agents: components["schemas"]["TaskAgentConfig"][];
+      /** @description Working directory for every session. Defaults to the server's. */
+      cwd?: string | null;
+      prompt: string;
+    };
+    TaskInfo: {
+      /** Format: int64 */
+      createdAtMs: number;
+      /** @description Whether every run has completed or failed. */
+      finished: boolean;
+      prompt: string;
+      runs: components["schemas"]["TaskRunInfo"][];
+      taskId: string;
+    };
+    TaskListResponse: {
+      tasks: components["schemas"]["TaskInfo"][];
+    };
+    TaskRunInfo: {
+      agent: string;
+      error?: string | null;
+      /** Format: int64 */
+      finishedAtMs?: number | null;
+      /** @description Text the agent replied with. */
+      output: string;
+      /** @description ACP server running this agent, readable through `/v1/acp/{server_id}`. */
+      serverId: string;
+      sessionId?: string | null;
+      status: components["schemas"]["TaskStatus"];
+      stopReason?: string | null;
+    };
+    /** @enum {string} */
+    TaskStatus: "starting" | "running" | "completed" | "failed";
+    WarmPoolInfo: {
+      /** Format: int64 */
+      coldStartMsAvg?: number | null;
+      /**
+       * Format: int64
+       * @description Servers that spawned their own process.
+       */
+      coldStarts: number;
+      /** @description Standbys spawned and waiting for a server. */
+      ready: number;
+      /** @description Standbys the pool keeps. */
+      size: number;
+      /** Format: int64 */
+      warmStartMsAvg?: number | null;
+      /**
+       * Format: int64
+       * @description Servers that adopted a standby.
+       */
+      warmStarts: number;
+    };
   };
   responses: never;
   parameters: never;
@@ -277,6 +1187,18 @@ export interface operations {
   };
   get_v1_acp: {
     parameters: {
+      query?: {
+        /** @description Replay buffered envelopes after this event id. The Last-Event-ID header wins when both are set */
+        offset?: number | null;
+        /** @description When true, name each event after the envelope's method, or `response` for responses, instead of `message` */
+        namedEvents?: boolean | null;
+        /** @description When true, include `_adapter/log` notifications carrying the agent process's stderr lines */
+        includeLogs?: boolean | null;
+        /** @description Comma-separated event kinds to keep: a `sessionUpdate` value such as `agent_message_chunk`, a method such as `session/request_permission`, or `response` */
+        types?: string | null;
+        /** @description Comma-separated event kinds to drop, matched like `types` */
+        exclude?: string | null;
+      };
       path: {
         /** @description Client-defined ACP server id */
         server_id: string;
@@ -313,6 +1235,10 @@ export interface operations {
         /** @description Agent id required for first POST */
         agent?: string | null;
       };
+      header?: {
+        /** @description Run the request once per key; retries with the same key and body get the first response, with `Idempotent-Replayed: true` */
+        "Idempotency-Key"?: string | null;
+      };
       path: {
         /** @description Client-defined ACP server id */
         server_id: string;
@@ -352,12 +1278,18 @@ export interface operations {
           "application/json": components["schemas"]["ProblemDetails"];
         };
       };
-      /** @description ACP server bound to different agent */
+      /** @description ACP server bound to different agent, or Idempotency-Key reused with a different request */
       409: {
         content: {
           "application/json": components["schemas"]["ProblemDetails"];
         };
       };
+      /** @description Request body or prompt text over the size limit */
+      413: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
       /** @description Unsupported media type */
       415: {
         content: {
@@ -386,91 +1318,1029 @@ export interface operations {
       };
     };
   };
-  get_v1_agents: {
+  post_v1_acp_attachments: {
     parameters: {
       query?: {
-        /** @description When true, include version/path/configOptions (slower) */
-        config?: boolean | null;
-        /** @description When true, bypass version cache */
-        no_cache?: boolean | null;
-      };
-    };
-    responses: {
-      /** @description List of v1 agents */
-      200: {
-        content: {
-          "application/json": components["schemas"]["AgentListResponse"];
-        };
+        /** @description Directory to store the files in, such as the session `cwd`; defaults to a directory removed with the server */
+        dir?: string | null;
       };
-      /** @description Authentication required */
-      401: {
-        content: {
-          "application/json": components["schemas"]["ProblemDetails"];
-        };
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
       };
     };
-  };
-  get_v1_agent: {
-    parameters: {
-      query?: {
-        /** @description When true, include version/path/configOptions (slower) */
-        config?: boolean | null;
-        /** @description When true, bypass version cache */
-        no_cache?: boolean | null;
-      };
-      path: {
-        /** @description Agent id */
-        agent: string;
+    /** @description multipart/form-data body; every part with a filename is stored as one file */
+    requestBody: {
+      content: {
+        "multipart/form-data": string;
       };
     };
     responses: {
-      /** @description Agent info */
+      /** @description Stored files, in upload order */
       200: {
         content: {
-          "application/json": components["schemas"]["AgentInfo"];
+          "application/json": components["schemas"]["AcpAttachmentsResponse"];
         };
       };
-      /** @description Unknown agent */
+      /** @description Body is not multipart or a part has no filename */
       400: {
         content: {
           "application/json": components["schemas"]["ProblemDetails"];
         };
       };
-      /** @description Authentication required */
-      401: {
+      /** @description Unknown ACP server */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Upload is larger than `SANDBOX_AGENT_MAX_ATTACHMENT_BYTES` */
+      413: {
         content: {
           "application/json": components["schemas"]["ProblemDetails"];
         };
       };
     };
   };
-  post_v1_agent_install: {
+  post_v1_acp_compact: {
     parameters: {
       path: {
-        /** @description Agent id */
-        agent: string;
+        /** @description Client-defined ACP server id */
+        server_id: string;
       };
     };
     requestBody: {
       content: {
-        "application/json": components["schemas"]["AgentInstallRequest"];
+        "application/json": components["schemas"]["AcpCompactRequest"];
       };
     };
     responses: {
-      /** @description Agent install result */
+      /** @description Compaction turn finished; an `_adapter/compaction` notification was appended to the stream */
       200: {
         content: {
-          "application/json": components["schemas"]["AgentInstallResponse"];
+          "application/json": components["schemas"]["AcpCompactResponse"];
         };
       };
-      /** @description Invalid request */
+      /** @description Missing session id */
       400: {
         content: {
           "application/json": components["schemas"]["ProblemDetails"];
         };
       };
-      /** @description Install failed */
+      /** @description Unknown ACP server */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Agent rejected the compaction turn */
+      502: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  get_v1_acp_events: {
+    parameters: {
+      query?: {
+        /** @description Return envelopes after this event id */
+        offset?: number | null;
+        /** @description When no envelope is buffered after `offset`, wait up to this long for one (capped at 20000) */
+        waitMs?: number | null;
+        /** @description Maximum envelopes to return (default 100, max 1000) */
+        limit?: number | null;
+        /** @description When true, include `_adapter/log` notifications carrying the agent process's stderr lines */
+        includeLogs?: boolean | null;
+        /** @description Comma-separated event kinds to keep: a `sessionUpdate` value such as `agent_message_chunk`, a method such as `session/request_permission`, or `response` */
+        types?: string | null;
+        /** @description Comma-separated event kinds to drop, matched like `types` */
+        exclude?: string | null;
+      };
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
+      };
+    };
+    responses: {
+      /** @description Buffered ACP envelopes */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AcpEventsResponse"];
+        };
+      };
+      /** @description Same page as the ETag in `If-None-Match` */
+      304: {
+        content: never;
+      };
+      /** @description Unknown ACP server */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  post_v1_acp_import: {
+    parameters: {
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
+      };
+    };
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["AcpImportSessionRequest"];
+      };
+    };
+    responses: {
+      /** @description Native session loaded into the ACP server */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AcpImportSessionResponse"];
+        };
+      };
+      /** @description Empty nativeSessionId or cwd, or agent cannot load native sessions */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description ACP server bound to different agent */
+      409: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Agent rejected the session load */
+      502: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  get_v1_acp_requests: {
+    parameters: {
+      query?: {
+        /** @description Only list requests for this ACP session */
+        sessionId?: string | null;
+      };
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
+      };
+    };
+    responses: {
+      /** @description Agent requests waiting for a client response, oldest first */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AcpPendingRequestsResponse"];
+        };
+      };
+      /** @description Unknown ACP server */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  post_v1_acp_secrets: {
+    parameters: {
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
+      };
+    };
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["AcpSecretsRequest"];
+      };
+    };
+    responses: {
+      /** @description Stored secret names (values are never returned) */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AcpSecretsResponse"];
+        };
+      };
+      /** @description Invalid secret name */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  get_v1_acp_session: {
+    parameters: {
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
+        /** @description ACP session id */
+        session_id: string;
+      };
+    };
+    responses: {
+      /** @description Snapshot of the session: modes, model, pending requests, buffered events, and how it ended once its agent process exited */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AcpSessionDetailResponse"];
+        };
+      };
+      /** @description No such session on the server */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  patch_v1_acp_session: {
+    parameters: {
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
+        /** @description ACP session id */
+        session_id: string;
+      };
+    };
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["AcpSessionUpdateRequest"];
+      };
+    };
+    responses: {
+      /** @description Settings applied; snapshot of the session after the change */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AcpSessionDetailResponse"];
+        };
+      };
+      /** @description No field set, or a mode, model or permission mode the agent does not offer */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description No such session on the server */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description The session has ended, or the agent only applies the permission mode at launch */
+      409: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Agent rejected `session/set_mode` or `session/set_model` */
+      502: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  get_v1_acp_session_artifact: {
+    parameters: {
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
+        /** @description Agent session id */
+        session_id: string;
+        /** @description Artifact id from `_meta["sandboxagent.dev"].artifacts` of a tool call update */
+        artifact_id: string;
+      };
+    };
+    responses: {
+      /** @description Full tool output the update carries a preview of, as text/plain, or application/json for a structured `rawOutput` */
+      200: {
+        content: never;
+      };
+      /** @description No such artifact */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  get_v1_acp_session_children: {
+    parameters: {
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
+        /** @description ACP session id */
+        session_id: string;
+      };
+    };
+    responses: {
+      /** @description Sub-agents the session started, in the order they started */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AcpChildSessionsResponse"];
+        };
+      };
+      /** @description Unknown ACP server */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  post_v1_acp_session_interrupt: {
+    parameters: {
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
+        /** @description ACP session id */
+        session_id: string;
+      };
+    };
+    responses: {
+      /** @description The session's turn ended, or it had none in flight */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AcpInterruptResponse"];
+        };
+      };
+      /** @description Unknown ACP server */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  get_v1_acp_session_messages: {
+    parameters: {
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
+        /** @description ACP session id */
+        session_id: string;
+      };
+    };
+    responses: {
+      /** @description Messages queued or being sent, oldest first */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AcpQueuedMessagesResponse"];
+        };
+      };
+      /** @description Unknown ACP server */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  post_v1_acp_session_messages: {
+    parameters: {
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
+        /** @description ACP session id */
+        session_id: string;
+      };
+    };
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["AcpQueueMessageRequest"];
+      };
+    };
+    responses: {
+      /** @description Message queued; it is sent once the session has no prompt in flight */
+      201: {
+        content: {
+          "application/json": components["schemas"]["AcpQueuedMessage"];
+        };
+      };
+      /** @description Empty prompt or unsupported `when` */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Unknown ACP server */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  delete_v1_acp_session_message: {
+    parameters: {
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
+        /** @description ACP session id */
+        session_id: string;
+        /** @description Queued message id */
+        message_id: string;
+      };
+    };
+    responses: {
+      /** @description Message removed from the queue */
+      204: {
+        content: never;
+      };
+      /** @description Unknown ACP server or message */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description The message was already sent */
+      409: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  get_v1_acp_session_plan: {
+    parameters: {
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
+        /** @description ACP session id */
+        session_id: string;
+      };
+    };
+    responses: {
+      /** @description The plan from the session's latest planning turn */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AcpPlanResponse"];
+        };
+      };
+      /** @description Unknown ACP server, or no planning turn produced a plan */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  post_v1_acp_session_plan_approve: {
+    parameters: {
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
+        /** @description ACP session id */
+        session_id: string;
+      };
+    };
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["AcpPlanApproveRequest"];
+      };
+    };
+    responses: {
+      /** @description Session switched out of plan mode and the build turn finished */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AcpPlanApproveResponse"];
+        };
+      };
+      /** @description `modeId` is `plan` */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Unknown ACP server, or the session has no plan */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description The latest plan is already approved */
+      409: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Agent rejected the mode switch or the build turn */
+      502: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  get_v1_acp_session_result: {
+    parameters: {
+      query?: {
+        /** @description How long to wait for a prompt in flight to finish (default 60000, max 1800000) */
+        timeoutMs?: number | null;
+      };
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
+        /** @description ACP session id */
+        session_id: string;
+      };
+    };
+    responses: {
+      /** @description Outcome of the session's latest turn */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AcpTurnResultResponse"];
+        };
+      };
+      /** @description Unknown ACP server, or the session has no prompt yet */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description The prompt was still in flight when the timeout expired */
+      504: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  post_v1_acp_session_retry: {
+    parameters: {
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
+        /** @description ACP session id */
+        session_id: string;
+      };
+    };
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["AcpRetryRequest"];
+      };
+    };
+    responses: {
+      /** @description The last turn was retracted and its prompt ran again */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AcpRetryResponse"];
+        };
+      };
+      /** @description Empty text */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Unknown ACP server, or the session has no prompt yet */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description The session has a prompt in flight */
+      409: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Agent rejected the new turn */
+      502: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  get_v1_acp_session_transcript: {
+    parameters: {
+      query?: {
+        /** @description `json` (default), `text` for the assistant's messages as text/plain, or `markdown` for every message under a role heading */
+        format?: string | null;
+      };
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
+        /** @description ACP session id */
+        session_id: string;
+      };
+    };
+    responses: {
+      /** @description Messages merged from the session's buffered `agent_message_chunk` and `user_message_chunk` updates */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AcpTranscriptResponse"];
+        };
+      };
+      /** @description Unknown format */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Unknown ACP server */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  post_v1_acp_share: {
+    parameters: {
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
+      };
+    };
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["AcpShareRequest"];
+      };
+    };
+    responses: {
+      /** @description Short-lived signed SSE URL */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AcpShareResponse"];
+        };
+      };
+      /** @description Invalid TTL */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Unknown ACP server */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  get_v1_acp_turn_logs: {
+    parameters: {
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
+        /** @description Turn number from the `turn` field of `_adapter/turn_started` */
+        turn: number;
+      };
+    };
+    responses: {
+      /** @description Every stdout and stderr line the agent process wrote during the turn, as text/plain, one `[stdout]` or `[stderr]` line each */
+      200: {
+        content: never;
+      };
+      /** @description Turn logs are off (`SANDBOX_AGENT_TURN_LOG_DIR` is unset) */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description No log kept for this turn */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  post_v1_acp_validate: {
+    parameters: {
+      query?: {
+        /** @description Agent id the first POST would bootstrap */
+        agent?: string | null;
+      };
+      path: {
+        /** @description Client-defined ACP server id */
+        server_id: string;
+      };
+    };
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["AcpEnvelope"];
+      };
+    };
+    responses: {
+      /** @description Validation result; nothing is installed or started */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AcpValidateResponse"];
+        };
+      };
+    };
+  };
+  get_v1_agents: {
+    parameters: {
+      query?: {
+        /** @description When true, include version/path/configOptions (slower) */
+        config?: boolean | null;
+        /** @description When true, bypass version cache */
+        no_cache?: boolean | null;
+      };
+    };
+    responses: {
+      /** @description List of v1 agents */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AgentListResponse"];
+        };
+      };
+      /** @description Unchanged since the ETag in `If-None-Match` */
+      304: {
+        content: never;
+      };
+      /** @description Authentication required */
+      401: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  get_v1_agent: {
+    parameters: {
+      query?: {
+        /** @description When true, include version/path/configOptions (slower) */
+        config?: boolean | null;
+        /** @description When true, bypass version cache */
+        no_cache?: boolean | null;
+      };
+      path: {
+        /** @description Agent id */
+        agent: string;
+      };
+    };
+    responses: {
+      /** @description Agent info */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AgentInfo"];
+        };
+      };
+      /** @description Unchanged since the ETag in `If-None-Match` */
+      304: {
+        content: never;
+      };
+      /** @description Unknown agent */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Authentication required */
+      401: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  delete_v1_agent: {
+    parameters: {
+      path: {
+        /** @description Agent id */
+        agent: string;
+      };
+    };
+    responses: {
+      /** @description Agent files removed; pins are kept */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AgentUninstallResponse"];
+        };
+      };
+      /** @description Unknown agent */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description A server of the agent is running */
+      409: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  post_v1_agent_install: {
+    parameters: {
+      path: {
+        /** @description Agent id */
+        agent: string;
+      };
+    };
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["AgentInstallRequest"];
+      };
+    };
+    responses: {
+      /** @description Agent install result */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AgentInstallResponse"];
+        };
+      };
+      /** @description Invalid request */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Install failed */
+      500: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  get_v1_agent_install_progress: {
+    parameters: {
+      path: {
+        /** @description Agent id */
+        agent: string;
+      };
+    };
+    responses: {
+      /** @description SSE stream of `progress` events (AgentInstallProgressEvent) */
+      200: {
+        content: never;
+      };
+      /** @description Unknown agent */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  get_v1_agent_models: {
+    parameters: {
+      path: {
+        /** @description Agent id */
+        agent: string;
+      };
+    };
+    responses: {
+      /** @description Models the agent accepts */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AgentModelsResponse"];
+        };
+      };
+      /** @description Unchanged since the ETag in `If-None-Match` */
+      304: {
+        content: never;
+      };
+      /** @description Unknown agent */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Authentication required */
+      401: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  put_v1_agent_pin: {
+    parameters: {
+      path: {
+        /** @description Agent id */
+        agent: string;
+      };
+    };
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["AgentPinInfo"];
+      };
+    };
+    responses: {
+      /** @description Stored pin; an empty body clears it */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AgentPinInfo"];
+        };
+      };
+      /** @description Unknown agent */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  post_v1_agent_upgrade: {
+    parameters: {
+      path: {
+        /** @description Agent id */
+        agent: string;
+      };
+    };
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["AgentUpgradeRequest"];
+      };
+    };
+    responses: {
+      /** @description Upgrade result with changelog */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AgentUpgradeResponse"];
+        };
+      };
+      /** @description Invalid request */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Install failed */
+      500: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  get_v1_agent_versions: {
+    parameters: {
+      path: {
+        /** @description Agent id */
+        agent: string;
+      };
+    };
+    responses: {
+      /** @description Available releases and the current pin */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AgentVersionsResponse"];
+        };
+      };
+      /** @description Unknown agent */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Release listing failed */
+      502: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  post_v1_compat_anthropic_messages: {
+    parameters: {
+      header?: {
+        /** @description Agent to run; overrides `model` */
+        "x-sandbox-agent"?: string | null;
+      };
+    };
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["AnthropicMessagesRequest"];
+      };
+    };
+    responses: {
+      /** @description Messages API `message`, or its SSE events when `stream` is true */
+      200: {
+        content: never;
+      };
+      /** @description Unknown agent or unsupported message content */
+      400: {
+        content: never;
+      };
+      /** @description The agent failed before answering */
       500: {
+        content: never;
+      };
+    };
+  };
+  get_v1_config: {
+    responses: {
+      /** @description Effective server settings with secrets masked */
+      200: {
+        content: {
+          "application/json": components["schemas"]["ServerConfigResponse"];
+        };
+      };
+      /** @description Authentication required */
+      401: {
         content: {
           "application/json": components["schemas"]["ProblemDetails"];
         };
@@ -738,6 +2608,22 @@ export interface operations {
       };
     };
   };
+  get_v1_fs_workspace: {
+    parameters: {
+      query: {
+        /** @description Workspace root directory */
+        path: string;
+      };
+    };
+    responses: {
+      /** @description Detected languages, package managers, and test commands */
+      200: {
+        content: {
+          "application/json": components["schemas"]["FsWorkspaceInfo"];
+        };
+      };
+    };
+  };
   get_v1_health: {
     responses: {
       /** @description Service health response */
@@ -748,4 +2634,306 @@ export interface operations {
       };
     };
   };
+  get_v1_logs: {
+    parameters: {
+      query?: {
+        /** @description Only entries whose `server_id` or `session_id` field, on the event or an enclosing span, equals this */
+        session?: string | null;
+        /** @description Least severe level to include: `error`, `warn`, `info`, `debug` or `trace` */
+        level?: string | null;
+        /** @description Return entries after this sequence number */
+        since?: number | null;
+        /** @description Maximum entries to return (default 100, max 1000) */
+        limit?: number | null;
+      };
+    };
+    responses: {
+      /** @description Buffered server log entries, oldest first */
+      200: {
+        content: {
+          "application/json": components["schemas"]["LogsResponse"];
+        };
+      };
+      /** @description Unknown level */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Authentication required */
+      401: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  get_v1_logs_stream: {
+    parameters: {
+      query?: {
+        /** @description Only entries whose `server_id` or `session_id` field, on the event or an enclosing span, equals this */
+        session?: string | null;
+        /** @description Least severe level to include: `error`, `warn`, `info`, `debug` or `trace` */
+        level?: string | null;
+        /** @description Replay buffered entries after this sequence number. The Last-Event-ID header wins when both are set */
+        since?: number | null;
+      };
+    };
+    responses: {
+      /** @description SSE stream of `log` events (LogEntryInfo), replaying buffered matches first; the event id is the sequence number */
+      200: {
+        content: never;
+      };
+      /** @description Unknown level */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Client does not accept SSE responses */
+      406: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  get_v1_openapi: {
+    responses: {
+      /** @description OpenAPI document for this server's HTTP API, with JSON Schemas for every request, response and envelope body. Its `info.version` is the server version */
+      200: {
+        content: never;
+      };
+    };
+  };
+  get_v1_schedules: {
+    responses: {
+      /** @description Every schedule, oldest first */
+      200: {
+        content: {
+          "application/json": components["schemas"]["ScheduleListResponse"];
+        };
+      };
+    };
+  };
+  post_v1_schedules: {
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["ScheduleCreateRequest"];
+      };
+    };
+    responses: {
+      /** @description Schedule created; each firing starts a task */
+      201: {
+        content: {
+          "application/json": components["schemas"]["ScheduleInfo"];
+        };
+      };
+      /** @description Invalid cron expression, empty prompt, no agents, or an unknown agent */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  get_v1_schedule: {
+    parameters: {
+      path: {
+        /** @description Schedule id returned when the schedule was created */
+        schedule_id: string;
+      };
+    };
+    responses: {
+      /** @description Schedule with its next firing and recent runs */
+      200: {
+        content: {
+          "application/json": components["schemas"]["ScheduleInfo"];
+        };
+      };
+      /** @description Unknown schedule */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  delete_v1_schedule: {
+    parameters: {
+      path: {
+        /** @description Schedule id returned when the schedule was created */
+        schedule_id: string;
+      };
+    };
+    responses: {
+      /** @description Schedule stopped; tasks it started are kept */
+      204: {
+        content: never;
+      };
+      /** @description Unknown schedule */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  get_v1_stream: {
+    parameters: {
+      query?: {
+        /** @description When true, follow every ACP server: running ones from their latest envelope and new ones from their first */
+        all?: boolean | null;
+        /** @description With all=true, only follow servers running this agent */
+        agent?: string | null;
+        /** @description With all=true, comma-separated envelope methods to forward; responses and other methods are dropped */
+        methods?: string | null;
+      };
+    };
+    responses: {
+      /** @description SSE stream: a `channel` event with the channel id, then `message` events ({serverId, sequence, payload}) and `closed` events for subscribed servers */
+      200: {
+        content: never;
+      };
+      /** @description Unknown agent, or a filter without all=true */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Client does not accept SSE responses */
+      406: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  post_v1_stream_subscribe: {
+    parameters: {
+      path: {
+        /** @description Channel id from the stream's `channel` event */
+        channel_id: string;
+      };
+    };
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["StreamSubscribeRequest"];
+      };
+    };
+    responses: {
+      /** @description Servers the channel is subscribed to */
+      200: {
+        content: {
+          "application/json": components["schemas"]["StreamSubscriptionsResponse"];
+        };
+      };
+      /** @description Unknown channel or ACP server */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  post_v1_stream_unsubscribe: {
+    parameters: {
+      path: {
+        /** @description Channel id from the stream's `channel` event */
+        channel_id: string;
+      };
+    };
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["StreamUnsubscribeRequest"];
+      };
+    };
+    responses: {
+      /** @description Servers the channel is still subscribed to */
+      200: {
+        content: {
+          "application/json": components["schemas"]["StreamSubscriptionsResponse"];
+        };
+      };
+      /** @description Unknown channel */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  get_v1_tasks: {
+    responses: {
+      /** @description Every task, oldest first */
+      200: {
+        content: {
+          "application/json": components["schemas"]["TaskListResponse"];
+        };
+      };
+    };
+  };
+  post_v1_tasks: {
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["TaskCreateRequest"];
+      };
+    };
+    responses: {
+      /** @description Task started; poll it for each run's status and output */
+      201: {
+        content: {
+          "application/json": components["schemas"]["TaskInfo"];
+        };
+      };
+      /** @description Empty prompt, no agents, or an unknown agent */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  get_v1_task: {
+    parameters: {
+      path: {
+        /** @description Task id returned when the task was created */
+        task_id: string;
+      };
+    };
+    responses: {
+      /** @description Status and output of every run */
+      200: {
+        content: {
+          "application/json": components["schemas"]["TaskInfo"];
+        };
+      };
+      /** @description Unknown task */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  delete_v1_task: {
+    parameters: {
+      path: {
+        /** @description Task id returned when the task was created */
+        task_id: string;
+      };
+    };
+    responses: {
+      /** @description Task forgotten and its ACP servers closed */
+      204: {
+        content: never;
+      };
+      /** @description Unknown task */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
 }
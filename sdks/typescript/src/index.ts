@@ -7,8 +7,14 @@ export {
 
 export { AcpRpcError } from "acp-http-client";
 
+export { PromptBuilder, SessionRequestBuilder } from "./builders.ts";
+export type { KnownAgentId } from "./builders.ts";
+
 export { buildInspectorUrl } from "./inspector.ts";
 
+export { renderSessionNotebook } from "./notebook.ts";
+export type { SessionNotebookFormat, SessionNotebookOptions } from "./notebook.ts";
+
 export type {
   SandboxAgentConnectOptions,
   SandboxAgentStartOptions,
@@ -25,13 +31,59 @@ export {
 } from "./types.ts";
 
 export type {
+  AcpAttachmentInfo,
+  AcpAttachmentsQuery,
+  AcpAttachmentsResponse,
+  AcpChangedFile,
+  AcpChildSession,
+  AcpChildSessionsResponse,
+  AcpCompactResponse,
   AcpEnvelope,
+  AcpEventsQuery,
+  AcpEventsResponse,
+  AcpInterruptResponse,
+  AcpPendingRequestInfo,
+  AcpPendingRequestsQuery,
+  AcpPendingRequestsResponse,
+  AcpPlanApprovalInfo,
+  AcpPlanApproveRequest,
+  AcpPlanApproveResponse,
+  AcpPlanResponse,
+  AcpPlanViolation,
+  AcpQueueMessageRequest,
+  AcpQueuedMessage,
+  AcpQueuedMessagesResponse,
+  AcpRetractedTurn,
+  AcpRetryRequest,
+  AcpRetryResponse,
+  AcpSecretInput,
+  AcpSecretsResponse,
   AcpServerInfo,
   AcpServerListResponse,
+  AcpSessionDetailResponse,
+  AcpSessionEndInfo,
+  AcpSessionUpdateRequest,
+  AcpTranscriptMessage,
+  AcpTranscriptQuery,
+  AcpTranscriptResponse,
+  AcpTurnResultQuery,
+  AcpTurnResultResponse,
+  AcpValidateResponse,
   AgentInfo,
   AgentInstallRequest,
   AgentInstallResponse,
   AgentListResponse,
+  AgentModelInfo,
+  AgentModelsResponse,
+  AgentPinInfo,
+  AgentReleaseInfo,
+  AgentSessionDefaultsInfo,
+  AgentUninstallResponse,
+  AgentUpgradeRequest,
+  AgentUpgradeResponse,
+  AgentVersionsResponse,
+  AnthropicMessagesRequest,
+  ErrorCode,
   FsActionResponse,
   FsDeleteQuery,
   FsEntriesQuery,
@@ -42,20 +94,36 @@ export type {
   FsStat,
   FsUploadBatchQuery,
   FsUploadBatchResponse,
+  FsWorkspaceInfo,
   FsWriteResponse,
   HealthResponse,
   InMemorySessionPersistDriverOptions,
   ListEventsRequest,
   ListPage,
   ListPageRequest,
+  LogEntryInfo,
+  LogsQuery,
+  LogsResponse,
   McpConfigQuery,
   McpServerConfig,
   ProblemDetails,
+  ScheduleCreateRequest,
+  ScheduleInfo,
+  ScheduleListResponse,
+  ScheduleRunInfo,
+  ServerConfigResponse,
   SessionEvent,
   SessionPersistDriver,
   SessionRecord,
   SkillsConfig,
   SkillsConfigQuery,
+  StreamSubscribeRequest,
+  StreamSubscriptionsResponse,
+  TaskCreateRequest,
+  TaskInfo,
+  TaskListResponse,
+  TaskRunInfo,
+  WarmPoolInfo,
 } from "./types.ts";
 
 export type {
@@ -0,0 +1,350 @@
+import type { SessionEvent, SessionRecord } from "./types.ts";
+
+export type SessionNotebookFormat = "markdown" | "html";
+
+export interface SessionNotebookOptions {
+  format?: SessionNotebookFormat;
+  /** Include agent thought chunks. Defaults to `true`. */
+  includeThoughts?: boolean;
+}
+
+interface NotebookDiff {
+  path: string;
+  oldText: string | null;
+  newText: string;
+}
+
+type NotebookBlock =
+  | { kind: "prompt"; text: string }
+  | { kind: "message"; text: string }
+  | { kind: "thought"; text: string }
+  | {
+      kind: "tool";
+      id: string;
+      title: string;
+      toolKind: string;
+      status: string;
+      command: string | null;
+      output: string[];
+      diffs: NotebookDiff[];
+    };
+
+type ToolBlock = Extract<NotebookBlock, { kind: "tool" }>;
+
+/**
+ * Render a session's persisted events as a single report: prompts, agent
+ * replies and thoughts, executed commands with their output, and file diffs,
+ * in the order they happened.
+ */
+export function renderSessionNotebook(
+  record: SessionRecord,
+  events: SessionEvent[],
+  options: SessionNotebookOptions = {},
+): string {
+  const blocks = collectBlocks(events, options.includeThoughts ?? true);
+  return options.format === "html" ? renderHtml(record, blocks) : renderMarkdown(record, blocks);
+}
+
+function collectBlocks(events: SessionEvent[], includeThoughts: boolean): NotebookBlock[] {
+  const blocks: NotebookBlock[] = [];
+  const tools = new Map<string, ToolBlock>();
+
+  const appendText = (kind: "message" | "thought", text: string) => {
+    const last = blocks[blocks.length - 1];
+    if (last && last.kind === kind) {
+      last.text += text;
+    } else {
+      blocks.push({ kind, text });
+    }
+  };
+
+  for (const event of [...events].sort((a, b) => a.eventIndex - b.eventIndex)) {
+    const payload = asRecord(event.payload);
+    const params = asRecord(payload?.params);
+    if (!payload || !params) {
+      continue;
+    }
+
+    if (event.sender === "client" && payload.method === "session/prompt") {
+      const text = contentText(params.prompt);
+      if (text) {
+        blocks.push({ kind: "prompt", text });
+      }
+      continue;
+    }
+
+    if (event.sender !== "agent" || payload.method !== "session/update") {
+      continue;
+    }
+    const update = asRecord(params.update);
+    if (!update) {
+      continue;
+    }
+
+    switch (update.sessionUpdate) {
+      case "agent_message_chunk":
+        appendText("message", contentText(update.content));
+        break;
+      case "agent_thought_chunk":
+        if (includeThoughts) {
+          appendText("thought", contentText(update.content));
+        }
+        break;
+      case "tool_call":
+      case "tool_call_update": {
+        const id = typeof update.toolCallId === "string" ? update.toolCallId : "";
+        let tool = tools.get(id);
+        if (!tool) {
+          tool = {
+            kind: "tool",
+            id,
+            title: "",
+            toolKind: "other",
+            status: "pending",
+            command: null,
+            output: [],
+            diffs: [],
+          };
+          tools.set(id, tool);
+          blocks.push(tool);
+        }
+        applyToolUpdate(tool, update);
+        break;
+      }
+      default:
+        break;
+    }
+  }
+
+  return blocks.filter((block) => block.kind === "tool" || block.text.trim().length > 0);
+}
+
+function applyToolUpdate(tool: ToolBlock, update: Record<string, unknown>): void {
+  if (typeof update.title === "string" && update.title) {
+    tool.title = update.title;
+  }
+  if (typeof update.kind === "string") {
+    tool.toolKind = update.kind;
+  }
+  if (typeof update.status === "string") {
+    tool.status = update.status;
+  }
+
+  const rawInput = asRecord(update.rawInput);
+  const command = rawInput?.command;
+  if (typeof command === "string") {
+    tool.command = command;
+  } else if (Array.isArray(command)) {
+    tool.command = command.map(String).join(" ");
+  }
+
+  if (Array.isArray(update.content)) {
+    // Each update carries the full content list, so it replaces the last one.
+    tool.output = [];
+    tool.diffs = [];
+    for (const item of update.content) {
+      const entry = asRecord(item);
+      if (entry?.type === "diff" && typeof entry.path === "string") {
+        tool.diffs.push({
+          path: entry.path,
+          oldText: typeof entry.oldText === "string" ? entry.oldText : null,
+          newText: typeof entry.newText === "string" ? entry.newText : "",
+        });
+      } else if (entry?.type === "content") {
+        const text = contentText(entry.content);
+        if (text) {
+          tool.output.push(text);
+        }
+      }
+    }
+  }
+
+  if (tool.output.length === 0 && update.rawOutput !== undefined) {
+    const rawOutput = update.rawOutput;
+    const text = typeof rawOutput === "string" ? rawOutput : JSON.stringify(rawOutput, null, 2);
+    if (text) {
+      tool.output = [text];
+    }
+  }
+}
+
+function contentText(content: unknown): string {
+  if (Array.isArray(content)) {
+    return content.map(contentText).filter(Boolean).join("\n");
+  }
+  const block = asRecord(content);
+  if (!block) {
+    return "";
+  }
+  switch (block.type) {
+    case "text":
+      return typeof block.text === "string" ? block.text : "";
+    case "resource_link":
+      return `[${String(block.name ?? block.uri)}](${String(block.uri)})`;
+    case "image":
+      return "[image]";
+    default:
+      return "";
+  }
+}
+
+/** Line diff of `oldText` → `newText` with the shared head and tail kept as context. */
+function diffLines(diff: NotebookDiff): string {
+  const before = diff.oldText === null ? [] : diff.oldText.split("\n");
+  const after = diff.newText.split("\n");
+  let head = 0;
+  while (head < before.length && head < after.length && before[head] === after[head]) {
+    head += 1;
+  }
+  let tail = 0;
+  while (
+    tail < before.length - head &&
+    tail < after.length - head &&
+    before[before.length - 1 - tail] === after[after.length - 1 - tail]
+  ) {
+    tail += 1;
+  }
+
+  const context = 3;
+  const lines = [`--- ${diff.oldText === null ? "/dev/null" : `a/${diff.path}`}`, `+++ b/${diff.path}`];
+  lines.push(...before.slice(Math.max(0, head - context), head).map((line) => ` ${line}`));
+  lines.push(...before.slice(head, before.length - tail).map((line) => `-${line}`));
+  lines.push(...after.slice(head, after.length - tail).map((line) => `+${line}`));
+  lines.push(...after.slice(after.length - tail, after.length - tail + context).map((line) => ` ${line}`));
+  return lines.join("\n");
+}
+
+function fence(text: string, language = ""): string {
+  const longest = Math.max(2, ...(text.match(/`+/g) ?? []).map((run) => run.length));
+  const marker = "`".repeat(longest + 1);
+  return `${marker}${language}\n${text}\n${marker}`;
+}
+
+function toolHeading(tool: ToolBlock): string {
+  const label = tool.toolKind === "execute" ? "Command" : tool.toolKind === "edit" ? "Edit" : "Tool";
+  return `${label}: ${tool.title || tool.id} (${tool.status})`;
+}
+
+function renderMarkdown(record: SessionRecord, blocks: NotebookBlock[]): string {
+  const out = [
+    `# Session ${record.id}`,
+    "",
+    `- Agent: ${record.agent}`,
+    `- Agent session: ${record.agentSessionId}`,
+    `- Created: ${new Date(record.createdAt).toISOString()}`,
+  ];
+  let turn = 0;
+
+  for (const block of blocks) {
+    out.push("");
+    switch (block.kind) {
+      case "prompt":
+        turn += 1;
+        out.push(`## Prompt ${turn}`, "", block.text.replace(/^/gm, "> "));
+        break;
+      case "message":
+        out.push("### Assistant", "", block.text.trim());
+        break;
+      case "thought":
+        out.push("<details><summary>Thinking</summary>", "", block.text.trim(), "", "</details>");
+        break;
+      case "tool":
+        out.push(`### ${toolHeading(block)}`);
+        if (block.command) {
+          out.push("", fence(block.command, "sh"));
+        }
+        for (const output of block.output) {
+          out.push("", fence(output.trimEnd()));
+        }
+        for (const diff of block.diffs) {
+          out.push("", fence(diffLines(diff), "diff"));
+        }
+        break;
+    }
+  }
+
+  return `${out.join("\n")}\n`;
+}
+
+function renderHtml(record: SessionRecord, blocks: NotebookBlock[]): string {
+  const out = [
+    "<!doctype html>",
+    '<html><head><meta charset="utf-8">',
+    `<title>Session ${escapeHtml(record.id)}</title>`,
+    "<style>body{font-family:system-ui,sans-serif;max-width:960px;margin:2rem auto;padding:0 1rem}" +
+      "pre{background:#f6f8fa;padding:.75rem;overflow-x:auto}blockquote{border-left:4px solid #ccc;margin:0;padding-left:1rem}" +
+      ".add{color:#116329}.del{color:#82071e}</style>",
+    "</head><body>",
+    `<h1>Session ${escapeHtml(record.id)}</h1>`,
+    `<ul><li>Agent: ${escapeHtml(record.agent)}</li><li>Agent session: ${escapeHtml(record.agentSessionId)}</li>` +
+      `<li>Created: ${new Date(record.createdAt).toISOString()}</li></ul>`,
+  ];
+  let turn = 0;
+
+  for (const block of blocks) {
+    switch (block.kind) {
+      case "prompt":
+        turn += 1;
+        out.push(`<h2>Prompt ${turn}</h2>`, `<blockquote>${paragraphs(block.text)}</blockquote>`);
+        break;
+      case "message":
+        out.push("<h3>Assistant</h3>", paragraphs(block.text.trim()));
+        break;
+      case "thought":
+        out.push(`<details><summary>Thinking</summary>${paragraphs(block.text.trim())}</details>`);
+        break;
+      case "tool":
+        out.push(`<h3>${escapeHtml(toolHeading(block))}</h3>`);
+        if (block.command) {
+          out.push(`<pre><code>${escapeHtml(block.command)}</code></pre>`);
+        }
+        for (const output of block.output) {
+          out.push(`<pre>${escapeHtml(output.trimEnd())}</pre>`);
+        }
+        for (const diff of block.diffs) {
+          const lines = diffLines(diff)
+            .split("\n")
+            .map((line, index) => {
+              const escaped = escapeHtml(line);
+              if (index < 2) {
+                return `<strong>${escaped}</strong>`;
+              }
+              if (line.startsWith("+")) {
+                return `<span class="add">${escaped}</span>`;
+              }
+              if (line.startsWith("-")) {
+                return `<span class="del">${escaped}</span>`;
+              }
+              return escaped;
+            });
+          out.push(`<pre>${lines.join("\n")}</pre>`);
+        }
+        break;
+    }
+  }
+
+  out.push("</body></html>");
+  return `${out.join("\n")}\n`;
+}
+
+function paragraphs(text: string): string {
+  return text
+    .split(/\n{2,}/)
+    .map((paragraph) => `<p>${escapeHtml(paragraph).replace(/\n/g, "<br>")}</p>`)
+    .join("");
+}
+
+function escapeHtml(text: string): string {
+  return text
+    .replace(/&/g, "&amp;")
+    .replace(/</g, "&lt;")
+    .replace(/>/g, "&gt;")
+    .replace(/"/g, "&quot;")
+    .replace(/'/g, "&#39;");
+}
+
+function asRecord(value: unknown): Record<string, unknown> | null {
+  return value !== null && typeof value === "object" && !Array.isArray(value)
+    ? (value as Record<string, unknown>)
+    : null;
+}
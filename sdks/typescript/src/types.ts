@@ -2,16 +2,81 @@ import type { AnyMessage, NewSessionRequest } from "acp-http-client";
 import type { components, operations } from "./generated/openapi.ts";
 
 export type ProblemDetails = components["schemas"]["ProblemDetails"];
+export type ErrorCode = components["schemas"]["ErrorCode"];
 
 export type HealthResponse = JsonResponse<operations["get_v1_health"], 200>;
+export type ServerConfigResponse = JsonResponse<operations["get_v1_config"], 200>;
+export type LogEntryInfo = components["schemas"]["LogEntryInfo"];
+export type LogsQuery = QueryParams<operations["get_v1_logs"]>;
+export type LogsResponse = JsonResponse<operations["get_v1_logs"], 200>;
 export type AgentListResponse = JsonResponse<operations["get_v1_agents"], 200>;
 export type AgentInfo = components["schemas"]["AgentInfo"];
+export type AgentModelInfo = components["schemas"]["AgentModelInfo"];
+export type AgentModelsResponse = JsonResponse<operations["get_v1_agent_models"], 200>;
 export type AgentInstallRequest = JsonRequestBody<operations["post_v1_agent_install"]>;
 export type AgentInstallResponse = JsonResponse<operations["post_v1_agent_install"], 200>;
-
+export type AgentUninstallResponse = JsonResponse<operations["delete_v1_agent"], 200>;
+export type AgentPinInfo = components["schemas"]["AgentPinInfo"];
+export type AgentReleaseInfo = components["schemas"]["AgentReleaseInfo"];
+export type AgentSessionDefaultsInfo = components["schemas"]["AgentSessionDefaultsInfo"];
+export type AgentVersionsResponse = JsonResponse<operations["get_v1_agent_versions"], 200>;
+export type AgentUpgradeRequest = JsonRequestBody<operations["post_v1_agent_upgrade"]>;
+export type AgentUpgradeResponse = JsonResponse<operations["post_v1_agent_upgrade"], 200>;
+export type WarmPoolInfo = components["schemas"]["WarmPoolInfo"];
+
+export type AcpAttachmentInfo = components["schemas"]["AcpAttachmentInfo"];
+export type AcpAttachmentsQuery = QueryParams<operations["post_v1_acp_attachments"]>;
+export type AcpAttachmentsResponse = JsonResponse<operations["post_v1_acp_attachments"], 200>;
+export type AcpChangedFile = components["schemas"]["AcpChangedFile"];
+export type AcpChildSession = components["schemas"]["AcpChildSession"];
+export type AcpChildSessionsResponse = JsonResponse<operations["get_v1_acp_session_children"], 200>;
+export type AcpCompactResponse = JsonResponse<operations["post_v1_acp_compact"], 200>;
 export type AcpEnvelope = components["schemas"]["AcpEnvelope"];
+export type AcpEventsQuery = QueryParams<operations["get_v1_acp_events"]>;
+export type AcpEventsResponse = JsonResponse<operations["get_v1_acp_events"], 200>;
+export type AcpInterruptResponse = JsonResponse<operations["post_v1_acp_session_interrupt"], 200>;
+export type AcpPendingRequestInfo = components["schemas"]["AcpPendingRequestInfo"];
+export type AcpPendingRequestsQuery = QueryParams<operations["get_v1_acp_requests"]>;
+export type AcpPendingRequestsResponse = JsonResponse<operations["get_v1_acp_requests"], 200>;
+export type AcpPlanApprovalInfo = components["schemas"]["AcpPlanApprovalInfo"];
+export type AcpPlanApproveRequest = components["schemas"]["AcpPlanApproveRequest"];
+export type AcpPlanApproveResponse = JsonResponse<operations["post_v1_acp_session_plan_approve"], 200>;
+export type AcpPlanResponse = JsonResponse<operations["get_v1_acp_session_plan"], 200>;
+export type AcpPlanViolation = components["schemas"]["AcpPlanViolation"];
+export type AcpQueueMessageRequest = components["schemas"]["AcpQueueMessageRequest"];
+export type AcpQueuedMessage = components["schemas"]["AcpQueuedMessage"];
+export type AcpQueuedMessagesResponse = JsonResponse<operations["get_v1_acp_session_messages"], 200>;
+export type AcpRetractedTurn = components["schemas"]["AcpRetractedTurn"];
+export type AcpRetryRequest = components["schemas"]["AcpRetryRequest"];
+export type AcpRetryResponse = JsonResponse<operations["post_v1_acp_session_retry"], 200>;
 export type AcpServerInfo = components["schemas"]["AcpServerInfo"];
 export type AcpServerListResponse = JsonResponse<operations["get_v1_acp_servers"], 200>;
+export type AcpSessionDetailResponse = JsonResponse<operations["get_v1_acp_session"], 200>;
+export type AcpSessionEndInfo = components["schemas"]["AcpSessionEndInfo"];
+export type AcpSessionUpdateRequest = components["schemas"]["AcpSessionUpdateRequest"];
+export type AcpSecretInput = components["schemas"]["AcpSecretInput"];
+export type AcpSecretsResponse = JsonResponse<operations["post_v1_acp_secrets"], 200>;
+export type AcpTranscriptMessage = components["schemas"]["AcpTranscriptMessage"];
+export type AcpTranscriptQuery = QueryParams<operations["get_v1_acp_session_transcript"]>;
+export type AcpTranscriptResponse = JsonResponse<operations["get_v1_acp_session_transcript"], 200>;
+export type AcpTurnResultQuery = QueryParams<operations["get_v1_acp_session_result"]>;
+export type AcpTurnResultResponse = JsonResponse<operations["get_v1_acp_session_result"], 200>;
+export type AcpValidateResponse = JsonResponse<operations["post_v1_acp_validate"], 200>;
+
+export type StreamSubscribeRequest = JsonRequestBody<operations["post_v1_stream_subscribe"]>;
+export type StreamSubscriptionsResponse = JsonResponse<operations["post_v1_stream_subscribe"], 200>;
+
+export type TaskCreateRequest = JsonRequestBody<operations["post_v1_tasks"]>;
+export type TaskInfo = components["schemas"]["TaskInfo"];
+export type TaskListResponse = JsonResponse<operations["get_v1_tasks"], 200>;
+export type TaskRunInfo = components["schemas"]["TaskRunInfo"];
+
+export type ScheduleCreateRequest = JsonRequestBody<operations["post_v1_schedules"]>;
+export type ScheduleInfo = components["schemas"]["ScheduleInfo"];
+export type ScheduleListResponse = JsonResponse<operations["get_v1_schedules"], 200>;
+export type ScheduleRunInfo = components["schemas"]["ScheduleRunInfo"];
+
+export type AnthropicMessagesRequest = JsonRequestBody<operations["post_v1_compat_anthropic_messages"]>;
 
 export type FsEntriesQuery = QueryParams<operations["get_v1_fs_entries"]>;
 export type FsEntry = components["schemas"]["FsEntry"];
@@ -23,6 +88,7 @@ export type FsActionResponse = JsonResponse<operations["delete_v1_fs_entry"], 20
 export type FsMoveRequest = JsonRequestBody<operations["post_v1_fs_move"]>;
 export type FsMoveResponse = JsonResponse<operations["post_v1_fs_move"], 200>;
 export type FsStat = JsonResponse<operations["get_v1_fs_stat"], 200>;
+export type FsWorkspaceInfo = JsonResponse<operations["get_v1_fs_workspace"], 200>;
 export type FsUploadBatchResponse = JsonResponse<operations["post_v1_fs_upload_batch"], 200>;
 
 export type McpConfigQuery = QueryParams<operations["get_v1_config_mcp"]>;
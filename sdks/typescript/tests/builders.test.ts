@@ -0,0 +1,88 @@
+import { describe, it, expect } from "vitest";
+import { PromptBuilder, SessionRequestBuilder } from "../src/builders.ts";
+
+describe("SessionRequestBuilder", () => {
+  it("builds a bare request from the agent alone", () => {
+    expect(SessionRequestBuilder.for("claude").build()).toEqual({ agent: "claude" });
+  });
+
+  it("trims the agent and id and collects cwd and MCP servers into sessionInit", () => {
+    const request = SessionRequestBuilder.for(" codex ")
+      .id(" session-1 ")
+      .cwd("/workspace")
+      .mcpServer({ name: "files", command: "mcp-files", args: [], env: [] })
+      .build();
+
+    expect(request).toEqual({
+      agent: "codex",
+      id: "session-1",
+      sessionInit: {
+        cwd: "/workspace",
+        mcpServers: [{ name: "files", command: "mcp-files", args: [], env: [] }],
+      },
+    });
+  });
+
+  it("leaves cwd out so createSession can fill in the default", () => {
+    const request = SessionRequestBuilder.for("mock")
+      .mcpServer({ name: "files", command: "mcp-files", args: [], env: [] })
+      .build();
+
+    expect(request.sessionInit).toEqual({
+      mcpServers: [{ name: "files", command: "mcp-files", args: [], env: [] }],
+    });
+  });
+
+  it("accepts Windows drive paths as absolute", () => {
+    expect(SessionRequestBuilder.for("mock").cwd("C:\\work").build().sessionInit).toMatchObject({
+      cwd: "C:\\work",
+    });
+  });
+
+  it("rejects what the server would reject", () => {
+    expect(() => SessionRequestBuilder.for("  ").build()).toThrow("non-empty agent");
+    expect(() => SessionRequestBuilder.for("mock").id(" ").build()).toThrow("session id");
+    expect(() => SessionRequestBuilder.for("mock").cwd("work").build()).toThrow("absolute path");
+    expect(() =>
+      SessionRequestBuilder.for("mock")
+        .mcpServer({ name: " ", command: "a", args: [], env: [] })
+        .build(),
+    ).toThrow("name must not be blank");
+    expect(() =>
+      SessionRequestBuilder.for("mock")
+        .mcpServer({ name: "files", command: "a", args: [], env: [] })
+        .mcpServer({ name: "files", command: "b", args: [], env: [] })
+        .build(),
+    ).toThrow("configured twice");
+  });
+});
+
+describe("PromptBuilder", () => {
+  it("keeps content blocks in order", () => {
+    const prompt = new PromptBuilder()
+      .text("Describe this screenshot.")
+      .image("aGVsbG8=", "image/png")
+      .resourceLink("file:///workspace/notes.md", "notes.md")
+      .build();
+
+    expect(prompt).toEqual([
+      { type: "text", text: "Describe this screenshot." },
+      { type: "image", data: "aGVsbG8=", mimeType: "image/png" },
+      { type: "resource_link", uri: "file:///workspace/notes.md", name: "notes.md" },
+    ]);
+  });
+
+  it("returns a copy so later calls do not change a built prompt", () => {
+    const builder = new PromptBuilder().text("first");
+    const prompt = builder.build();
+    builder.text("second");
+
+    expect(prompt).toEqual([{ type: "text", text: "first" }]);
+  });
+
+  it("rejects empty and blank prompts", () => {
+    expect(() => new PromptBuilder().build()).toThrow("at least one content block");
+    expect(() => new PromptBuilder().text("  ").text("").build()).toThrow("must not be blank");
+    expect(new PromptBuilder().text(" ").image("aGVsbG8=", "image/png").build()).toHaveLength(2);
+  });
+});
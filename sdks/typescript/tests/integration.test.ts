@@ -133,6 +133,12 @@ describe("Integration: TypeScript SDK flat session API", () => {
       expect(events.items[i]!.eventIndex).toBeGreaterThanOrEqual(events.items[i - 1]!.eventIndex);
     }
 
+    const report = await sdk.exportSession(session.id);
+    expect(report).toContain(`# Session ${session.id}`);
+    expect(report).toContain("> hello flat sdk");
+    const html = await sdk.exportSession(session.id, { format: "html" });
+    expect(html).toContain("<blockquote><p>hello flat sdk</p></blockquote>");
+
     off();
     await sdk.dispose();
   });
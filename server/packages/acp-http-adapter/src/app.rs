@@ -93,6 +93,15 @@ fn map_error(err: AdapterError) -> Response {
             "timeout",
             "timed out waiting for agent response",
         ),
+        AdapterError::Exited(exit) => problem(
+            StatusCode::BAD_GATEWAY,
+            "agent_exited",
+            &match (exit.code, exit.signal) {
+                (Some(code), _) => format!("agent process exited with code {code}"),
+                (None, Some(signal)) => format!("agent process killed by signal {signal}"),
+                (None, None) => "agent process exited".to_string(),
+            },
+        ),
         AdapterError::Write(write) => problem(
             StatusCode::BAD_GATEWAY,
             "write_failed",
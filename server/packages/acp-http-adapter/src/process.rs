@@ -1,21 +1,39 @@
 use std::collections::{HashMap, VecDeque};
 use std::convert::Infallible;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex as StdMutex, RwLock};
 use std::time::{Duration, Instant};
 
 use axum::response::sse::Event;
 use futures::{stream, Stream, StreamExt};
 use serde_json::{json, Value};
 use thiserror::Error;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, ChildStdin, Command};
-use tokio::sync::{broadcast, oneshot, Mutex};
-use tokio_stream::wrappers::BroadcastStream;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, oneshot, Mutex, Notify};
+use tokio::task::JoinHandle;
 
 use crate::registry::LaunchSpec;
 
 const RING_BUFFER_SIZE: usize = 1024;
+/// The replay ring also drops its oldest envelopes once they add up to this
+/// many bytes, so a few huge payloads cannot pin the whole buffer in memory.
+const RING_BUFFER_MAX_BYTES: usize = 64 * 1024 * 1024;
+/// Longer stdout lines are discarded and reported with `_adapter/stdout_overflow`.
+const MAX_STDOUT_LINE_BYTES: usize = 16 * 1024 * 1024;
+/// Longer stderr lines are cut before they are logged.
+const MAX_STDERR_LINE_BYTES: usize = 16 * 1024;
+const REDACTED: &str = "[REDACTED]";
+const STDERR_TAIL_LINES: usize = 20;
+const STDERR_DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+const DEFAULT_MAX_RAW_BYTES: usize = 16 * 1024;
+const DEFAULT_ARTIFACT_MIN_BYTES: usize = 64 * 1024;
+const DEFAULT_ARTIFACT_PREVIEW_BYTES: usize = 2 * 1024;
+const RAW_OUTPUT_CHANNEL_SIZE: usize = 1024;
+/// Method of the notifications that carry agent stderr lines.
+pub const LOG_METHOD: &str = "_adapter/log";
 
 #[derive(Debug, Error)]
 pub enum AdapterError {
@@ -35,6 +53,82 @@ pub enum AdapterError {
     Write(std::io::Error),
     #[error("timeout waiting for response")]
     Timeout,
+    #[error("agent process exited")]
+    Exited(ProcessExit),
+}
+
+/// How the agent process ended, with the last stderr lines it wrote.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcessExit {
+    pub code: Option<i32>,
+    /// Signal that terminated the process (unix only).
+    pub signal: Option<i32>,
+    /// Up to the last 20 stderr lines, already redacted.
+    pub stderr: Vec<String>,
+}
+
+/// Cap on the `raw` line carried by `_adapter/invalid_stdout`. Longer lines
+/// are cut to `max_bytes` and, when `dir` is set, written there in full.
+#[derive(Debug, Clone)]
+pub struct RawSpill {
+    pub max_bytes: usize,
+    pub dir: Option<PathBuf>,
+}
+
+impl Default for RawSpill {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_MAX_RAW_BYTES,
+            dir: None,
+        }
+    }
+}
+
+/// Tool output stored out of band. Text content blocks and `rawOutput`
+/// values of `tool_call` and `tool_call_update` notifications over
+/// `min_bytes` are written to `<dir>/<session>/<artifact id>` and cut to
+/// `preview_bytes` in the envelope, whose update lists them under
+/// `_meta["sandboxagent.dev"].artifacts`. Off while `dir` is `None` or
+/// `min_bytes` is 0.
+#[derive(Debug, Clone)]
+pub struct ArtifactSpill {
+    pub min_bytes: usize,
+    pub preview_bytes: usize,
+    pub dir: Option<PathBuf>,
+}
+
+impl Default for ArtifactSpill {
+    fn default() -> Self {
+        Self {
+            min_bytes: DEFAULT_ARTIFACT_MIN_BYTES,
+            preview_bytes: DEFAULT_ARTIFACT_PREVIEW_BYTES,
+            dir: None,
+        }
+    }
+}
+
+/// Which pipe a [`RawOutputLine`] came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+impl OutputStream {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+        }
+    }
+}
+
+/// One line the agent process wrote, exactly as read but with secrets
+/// redacted. Stdout lines over the line limit are replaced by a note.
+#[derive(Debug, Clone)]
+pub struct RawOutputLine {
+    pub stream: OutputStream,
+    pub line: String,
 }
 
 #[derive(Debug)]
@@ -43,24 +137,67 @@ pub enum PostOutcome {
     Accepted,
 }
 
+/// Depth of a process's output pipeline.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PipelineStats {
+    /// Envelopes held in the replay buffer.
+    pub buffered_envelopes: usize,
+    /// Approximate size of those envelopes in bytes.
+    pub buffered_bytes: usize,
+    /// Stdout lines discarded for exceeding the line limit.
+    pub oversized_lines: u64,
+}
+
 #[derive(Debug, Clone)]
 struct StreamMessage {
     sequence: u64,
     payload: Value,
+    received_at: Instant,
+    bytes: usize,
+}
+
+#[derive(Debug, Default)]
+struct ReplayRing {
+    messages: VecDeque<StreamMessage>,
+    bytes: usize,
+}
+
+impl ReplayRing {
+    fn push(&mut self, message: StreamMessage) {
+        self.bytes += message.bytes;
+        self.messages.push_back(message);
+        while self.messages.len() > RING_BUFFER_SIZE
+            || (self.bytes > RING_BUFFER_MAX_BYTES && self.messages.len() > 1)
+        {
+            if let Some(evicted) = self.messages.pop_front() {
+                self.bytes -= evicted.bytes;
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct AdapterRuntime {
     stdin: Arc<Mutex<ChildStdin>>,
     child: Arc<Mutex<Child>>,
+    /// Asks the exit watcher, which holds `child` while it waits, to kill
+    /// the process.
+    kill: Arc<Notify>,
     pending: Arc<Mutex<HashMap<String, oneshot::Sender<Value>>>>,
     sender: broadcast::Sender<StreamMessage>,
-    ring: Arc<Mutex<VecDeque<StreamMessage>>>,
+    ring: Arc<Mutex<ReplayRing>>,
+    oversized_lines: Arc<AtomicU64>,
     sequence: Arc<AtomicU64>,
     request_timeout: Duration,
     shutting_down: AtomicBool,
     spawned_at: Instant,
     first_stdout: Arc<AtomicBool>,
+    redactions: Arc<RwLock<Vec<String>>>,
+    stderr_tail: Arc<StdMutex<VecDeque<String>>>,
+    exit: Arc<RwLock<Option<ProcessExit>>>,
+    raw_spill: Arc<RwLock<RawSpill>>,
+    artifact_spill: Arc<RwLock<ArtifactSpill>>,
+    raw_output: broadcast::Sender<RawOutputLine>,
 }
 
 impl AdapterRuntime {
@@ -112,24 +249,42 @@ impl AdapterRuntime {
         let runtime = Self {
             stdin: Arc::new(Mutex::new(stdin)),
             child: Arc::new(Mutex::new(child)),
+            kill: Arc::new(Notify::new()),
             pending: Arc::new(Mutex::new(HashMap::new())),
             sender,
-            ring: Arc::new(Mutex::new(VecDeque::with_capacity(RING_BUFFER_SIZE))),
+            ring: Arc::new(Mutex::new(ReplayRing::default())),
+            oversized_lines: Arc::new(AtomicU64::new(0)),
             sequence: Arc::new(AtomicU64::new(0)),
             request_timeout,
             shutting_down: AtomicBool::new(false),
             spawned_at: spawn_start,
             first_stdout: Arc::new(AtomicBool::new(false)),
+            redactions: Arc::new(RwLock::new(Vec::new())),
+            stderr_tail: Arc::new(StdMutex::new(VecDeque::with_capacity(STDERR_TAIL_LINES))),
+            exit: Arc::new(RwLock::new(None)),
+            raw_spill: Arc::new(RwLock::new(RawSpill::default())),
+            artifact_spill: Arc::new(RwLock::new(ArtifactSpill::default())),
+            raw_output: broadcast::channel(RAW_OUTPUT_CHANNEL_SIZE).0,
         };
 
         runtime.spawn_stdout_loop(stdout);
-        runtime.spawn_stderr_loop(stderr);
-        runtime.spawn_exit_watcher();
+        let stderr_done = runtime.spawn_stderr_loop(stderr);
+        runtime.spawn_exit_watcher(stderr_done);
 
         Ok(runtime)
     }
 
     pub async fn post(&self, payload: Value) -> Result<PostOutcome, AdapterError> {
+        self.post_with_timeout(payload, self.request_timeout).await
+    }
+
+    /// Like [`Self::post`], but waits up to `request_timeout` for the
+    /// response instead of the runtime's default.
+    pub async fn post_with_timeout(
+        &self,
+        payload: Value,
+        request_timeout: Duration,
+    ) -> Result<PostOutcome, AdapterError> {
         if !payload.is_object() {
             return Err(AdapterError::InvalidEnvelope);
         }
@@ -156,6 +311,12 @@ impl AdapterRuntime {
             );
 
             self.pending.lock().await.insert(key.clone(), tx);
+            // The exit watcher records the exit before clearing `pending`, so
+            // a request registered after that clear is caught here.
+            if let Some(exit) = self.exit_status() {
+                self.pending.lock().await.remove(&key);
+                return Err(AdapterError::Exited(exit));
+            }
 
             let write_start = Instant::now();
             if let Err(err) = self.send_to_subprocess(&payload).await {
@@ -166,7 +327,7 @@ impl AdapterRuntime {
                     "post: failed to write to agent stdin"
                 );
                 self.pending.lock().await.remove(&key);
-                return Err(err);
+                return Err(self.exit_status().map_or(err, AdapterError::Exited));
             }
             let write_ms = write_start.elapsed().as_millis() as u64;
             tracing::debug!(
@@ -177,7 +338,7 @@ impl AdapterRuntime {
             );
 
             let wait_start = Instant::now();
-            match tokio::time::timeout(self.request_timeout, rx).await {
+            match tokio::time::timeout(request_timeout, rx).await {
                 Ok(Ok(response)) => {
                     let wait_ms = wait_start.elapsed().as_millis() as u64;
                     tracing::info!(
@@ -198,7 +359,9 @@ impl AdapterRuntime {
                         "post: response channel dropped (agent process may have exited)"
                     );
                     self.pending.lock().await.remove(&key);
-                    Err(AdapterError::Timeout)
+                    Err(self
+                        .exit_status()
+                        .map_or(AdapterError::Timeout, AdapterError::Exited))
                 }
                 Err(_) => {
                     let pending_keys: Vec<String> =
@@ -206,7 +369,7 @@ impl AdapterRuntime {
                     tracing::error!(
                         method = %method,
                         id = %key,
-                        timeout_ms = self.request_timeout.as_millis() as u64,
+                        timeout_ms = request_timeout.as_millis() as u64,
                         age_ms = self.spawned_at.elapsed().as_millis() as u64,
                         pending_keys = ?pending_keys,
                         first_stdout_seen = self.first_stdout.load(Ordering::Relaxed),
@@ -221,58 +384,112 @@ impl AdapterRuntime {
                 method = %method,
                 "post: notification → agent (fire-and-forget)"
             );
-            self.send_to_subprocess(&payload).await?;
+            if let Some(exit) = self.exit_status() {
+                return Err(AdapterError::Exited(exit));
+            }
+            self.send_to_subprocess(&payload)
+                .await
+                .map_err(|err| self.exit_status().map_or(err, AdapterError::Exited))?;
             Ok(PostOutcome::Accepted)
         }
     }
 
+    /// Sequence number of the most recent message on the stream.
+    pub fn last_sequence(&self) -> u64 {
+        self.sequence.load(Ordering::SeqCst)
+    }
+
+    /// When the first agent notification after `sequence` arrived, if it is
+    /// still in the replay buffer.
+    pub async fn first_notification_after(&self, sequence: u64) -> Option<Instant> {
+        self.ring
+            .lock()
+            .await
+            .messages
+            .iter()
+            .find(|message| {
+                message.sequence > sequence
+                    && message.payload.get("method").is_some()
+                    && !is_log_envelope(&message.payload)
+            })
+            .map(|message| message.received_at)
+    }
+
+    /// How the agent process ended, once it has.
+    pub fn exit_status(&self) -> Option<ProcessExit> {
+        self.exit.read().unwrap().clone()
+    }
+
+    pub async fn pipeline_stats(&self) -> PipelineStats {
+        let ring = self.ring.lock().await;
+        PipelineStats {
+            buffered_envelopes: ring.messages.len(),
+            buffered_bytes: ring.bytes,
+            oversized_lines: self.oversized_lines.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Replace these values in every string the agent emits (responses and
+    /// notifications) and in logged stderr lines.
+    pub fn set_redactions(&self, values: Vec<String>) {
+        let values = values.into_iter().filter(|v| !v.is_empty()).collect();
+        *self.redactions.write().unwrap() = values;
+    }
+
+    /// Set how unparseable stdout lines are truncated and where the full
+    /// line is kept.
+    pub fn set_raw_spill(&self, spill: RawSpill) {
+        *self.raw_spill.write().unwrap() = spill;
+    }
+
+    /// Set which tool outputs are stored as artifacts and where.
+    pub fn set_artifact_spill(&self, spill: ArtifactSpill) {
+        *self.artifact_spill.write().unwrap() = spill;
+    }
+
+    /// Every stdout and stderr line written from now on, before parsing.
+    /// Lines are only copied while a receiver is alive.
+    pub fn subscribe_raw_output(&self) -> broadcast::Receiver<RawOutputLine> {
+        self.raw_output.subscribe()
+    }
+
+    /// Append an adapter-generated notification to the stream so SSE
+    /// subscribers see it in order with agent output.
+    pub async fn publish_notification(&self, payload: Value) {
+        let seq = self.sequence.fetch_add(1, Ordering::SeqCst) + 1;
+        let message = StreamMessage {
+            sequence: seq,
+            bytes: payload.to_string().len(),
+            payload,
+            received_at: Instant::now(),
+        };
+        self.ring.lock().await.push(message.clone());
+        let _ = self.sender.send(message);
+    }
+
     async fn subscribe(
         &self,
         last_event_id: Option<u64>,
     ) -> (Vec<(u64, Value)>, broadcast::Receiver<StreamMessage>) {
-        let replay = {
-            let ring = self.ring.lock().await;
-            ring.iter()
-                .filter(|message| {
-                    if let Some(last_event_id) = last_event_id {
-                        message.sequence > last_event_id
-                    } else {
-                        true
-                    }
-                })
-                .map(|message| (message.sequence, message.payload.clone()))
-                .collect::<Vec<_>>()
-        };
-        (replay, self.sender.subscribe())
+        // Subscribe before reading the ring so nothing published in between
+        // is missed. Duplicates are skipped by sequence in `live_messages`.
+        let rx = self.sender.subscribe();
+        let replay = ring_after(&*self.ring.lock().await, last_event_id.unwrap_or(0));
+        (replay, rx)
     }
 
     pub async fn sse_stream(
         self: Arc<Self>,
         last_event_id: Option<u64>,
     ) -> impl Stream<Item = Result<Event, Infallible>> + Send + 'static {
-        let (replay, rx) = self.subscribe(last_event_id).await;
-        let replay_stream = stream::iter(replay.into_iter().map(|(sequence, payload)| {
-            let event = Event::default()
-                .event("message")
-                .id(sequence.to_string())
-                .data(payload.to_string());
-            Ok(event)
-        }));
-
-        let live_stream = BroadcastStream::new(rx).filter_map(|item| async move {
-            match item {
-                Ok(message) => {
-                    let event = Event::default()
-                        .event("message")
-                        .id(message.sequence.to_string())
-                        .data(message.payload.to_string());
-                    Some(Ok(event))
-                }
-                Err(_) => None,
-            }
-        });
-
-        replay_stream.chain(live_stream)
+        self.sequenced_stream(last_event_id)
+            .await
+            .map(|(sequence, payload)| {
+                Ok(Event::default()
+                    .event("message")
+                    .id(sequence.to_string())
+                    .data(payload.to_string()))
+            })
     }
 
     /// Stream of raw JSON-RPC `Value` payloads (without SSE framing).
@@ -282,15 +499,23 @@ impl AdapterRuntime {
         self: Arc<Self>,
         last_event_id: Option<u64>,
     ) -> impl Stream<Item = Value> + Send + 'static {
+        self.sequenced_stream(last_event_id)
+            .await
+            .map(|(_sequence, payload)| payload)
+    }
+
+    /// Like [`Self::value_stream`], but keeps each payload's sequence number
+    /// so consumers can resume with it later.
+    pub async fn sequenced_stream(
+        self: Arc<Self>,
+        last_event_id: Option<u64>,
+    ) -> impl Stream<Item = (u64, Value)> + Send + 'static {
         let (replay, rx) = self.subscribe(last_event_id).await;
-        let replay_stream = stream::iter(replay.into_iter().map(|(_sequence, payload)| payload));
-        let live_stream = BroadcastStream::new(rx).filter_map(|item| async move {
-            match item {
-                Ok(message) => Some(message.payload),
-                Err(_) => None,
-            }
-        });
-        replay_stream.chain(live_stream)
+        let last = replay
+            .last()
+            .map_or(last_event_id.unwrap_or(0), |(sequence, _)| *sequence);
+        let live_stream = live_messages(self.ring.clone(), rx, last);
+        stream::iter(replay).chain(Box::pin(live_stream))
     }
 
     pub async fn shutdown(&self) {
@@ -304,6 +529,7 @@ impl AdapterRuntime {
         );
 
         self.pending.lock().await.clear();
+        self.kill.notify_one();
         let mut child = self.child.lock().await;
         match child.try_wait() {
             Ok(Some(_)) => {}
@@ -324,12 +550,28 @@ impl AdapterRuntime {
         let sequence = self.sequence.clone();
         let spawned_at = self.spawned_at;
         let first_stdout = self.first_stdout.clone();
+        let redactions = self.redactions.clone();
+        let raw_spill = self.raw_spill.clone();
+        let artifact_spill = self.artifact_spill.clone();
+        let oversized_lines = self.oversized_lines.clone();
+        let raw_output = self.raw_output.clone();
+        // Prefixes artifact ids, so a restarted agent cannot overwrite the
+        // artifacts of the process it replaced.
+        let artifact_prefix = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis())
+            .unwrap_or(0);
 
         tokio::spawn(async move {
-            let mut lines = BufReader::new(stdout).lines();
+            let mut reader = BufReader::new(stdout);
+            let mut buf = Vec::new();
             let mut line_count: u64 = 0;
+            let mut artifact_count: u64 = 0;
 
-            while let Ok(Some(line)) = lines.next_line().await {
+            while let Ok(Some(line_bytes)) =
+                read_line_bounded(&mut reader, &mut buf, MAX_STDOUT_LINE_BYTES).await
+            {
+                let line = String::from_utf8_lossy(&buf);
                 let trimmed = line.trim();
                 if trimmed.is_empty() {
                     continue;
@@ -337,6 +579,47 @@ impl AdapterRuntime {
 
                 line_count += 1;
 
+                if raw_output.receiver_count() > 0 {
+                    let mut raw = if line_bytes > MAX_STDOUT_LINE_BYTES {
+                        format!("[line of {line_bytes} bytes discarded]")
+                    } else {
+                        trimmed.to_string()
+                    };
+                    redact_text(&mut raw, &redactions.read().unwrap());
+                    let _ = raw_output.send(RawOutputLine {
+                        stream: OutputStream::Stdout,
+                        line: raw,
+                    });
+                }
+
+                if line_bytes > MAX_STDOUT_LINE_BYTES {
+                    oversized_lines.fetch_add(1, Ordering::Relaxed);
+                    tracing::warn!(
+                        line_number = line_count,
+                        line_bytes,
+                        max_bytes = MAX_STDOUT_LINE_BYTES,
+                        "agent stdout: line exceeds limit; discarded"
+                    );
+                    let payload = json!({
+                        "jsonrpc": "2.0",
+                        "method": "_adapter/stdout_overflow",
+                        "params": {
+                            "lineNumber": line_count,
+                            "lineBytes": line_bytes,
+                            "maxBytes": MAX_STDOUT_LINE_BYTES,
+                        },
+                    });
+                    let message = StreamMessage {
+                        sequence: sequence.fetch_add(1, Ordering::SeqCst) + 1,
+                        bytes: payload.to_string().len(),
+                        payload,
+                        received_at: Instant::now(),
+                    };
+                    ring.lock().await.push(message.clone());
+                    let _ = sender.send(message);
+                    continue;
+                }
+
                 if !first_stdout.swap(true, Ordering::Relaxed) {
                     tracing::info!(
                         first_stdout_ms = spawned_at.elapsed().as_millis() as u64,
@@ -345,7 +628,7 @@ impl AdapterRuntime {
                     );
                 }
 
-                let payload = match serde_json::from_str::<Value>(trimmed) {
+                let mut payload = match serde_json::from_str::<Value>(trimmed) {
                     Ok(payload) => payload,
                     Err(err) => {
                         tracing::warn!(
@@ -358,17 +641,43 @@ impl AdapterRuntime {
                             },
                             "agent stdout: invalid JSON"
                         );
+                        // Redact before truncating so a cut cannot leave part
+                        // of a secret behind.
+                        let mut raw = trimmed.to_string();
+                        redact_text(&mut raw, &redactions.read().unwrap());
+                        let spill = raw_spill.read().unwrap().clone();
+                        let mut params = json!({
+                            "error": err.to_string(),
+                            "truncated": false,
+                        });
+                        if raw.len() > spill.max_bytes {
+                            params["truncated"] = json!(true);
+                            params["rawBytes"] = json!(raw.len());
+                            if let Some(dir) = &spill.dir {
+                                let path = dir.join(format!("invalid-stdout-{line_count}.log"));
+                                match write_spill(&path, &raw).await {
+                                    Ok(()) => params["rawPath"] = json!(path),
+                                    Err(err) => tracing::warn!(
+                                        path = %path.display(),
+                                        error = %err,
+                                        "agent stdout: failed to spill invalid line"
+                                    ),
+                                }
+                            }
+                            params["raw"] = json!(truncate_at_char_boundary(&raw, spill.max_bytes));
+                        } else {
+                            params["raw"] = json!(raw);
+                        }
                         json!({
                             "jsonrpc": "2.0",
                             "method": "_adapter/invalid_stdout",
-                            "params": {
-                                "error": err.to_string(),
-                                "raw": trimmed,
-                            }
+                            "params": params,
                         })
                     }
                 };
 
+                redact_value(&mut payload, &redactions.read().unwrap());
+
                 let is_response = payload.get("id").is_some() && payload.get("method").is_none();
                 if is_response {
                     let key = id_key(payload.get("id").expect("checked"));
@@ -389,14 +698,10 @@ impl AdapterRuntime {
                         let message = StreamMessage {
                             sequence: seq,
                             payload,
+                            received_at: Instant::now(),
+                            bytes: line_bytes,
                         };
-                        {
-                            let mut guard = ring.lock().await;
-                            guard.push_back(message.clone());
-                            while guard.len() > RING_BUFFER_SIZE {
-                                guard.pop_front();
-                            }
-                        }
+                        ring.lock().await.push(message.clone());
                         let _ = sender.send(message);
                         continue;
                     } else {
@@ -408,6 +713,25 @@ impl AdapterRuntime {
                     }
                 }
 
+                let spill = artifact_spill.read().unwrap().clone();
+                let artifacts = extract_artifacts(&mut payload, &spill, || {
+                    artifact_count += 1;
+                    format!("artifact-{artifact_prefix}-{artifact_count}")
+                });
+                let mut bytes = line_bytes;
+                if !artifacts.is_empty() {
+                    for (path, content) in &artifacts {
+                        if let Err(err) = write_spill(path, content).await {
+                            tracing::warn!(
+                                path = %path.display(),
+                                error = %err,
+                                "agent stdout: failed to store artifact"
+                            );
+                        }
+                    }
+                    bytes = payload.to_string().len();
+                }
+
                 let method = payload
                     .get("method")
                     .and_then(|v| v.as_str())
@@ -422,16 +746,10 @@ impl AdapterRuntime {
                 let message = StreamMessage {
                     sequence: seq,
                     payload,
+                    received_at: Instant::now(),
+                    bytes,
                 };
-
-                {
-                    let mut guard = ring.lock().await;
-                    guard.push_back(message.clone());
-                    while guard.len() > RING_BUFFER_SIZE {
-                        guard.pop_front();
-                    }
-                }
-
+                ring.lock().await.push(message.clone());
                 let _ = sender.send(message);
             }
 
@@ -443,21 +761,73 @@ impl AdapterRuntime {
         });
     }
 
-    fn spawn_stderr_loop(&self, stderr: tokio::process::ChildStderr) {
+    fn spawn_stderr_loop(&self, stderr: tokio::process::ChildStderr) -> JoinHandle<()> {
         let spawned_at = self.spawned_at;
+        let redactions = self.redactions.clone();
+        let stderr_tail = self.stderr_tail.clone();
+        let sender = self.sender.clone();
+        let ring = self.ring.clone();
+        let sequence = self.sequence.clone();
+        let raw_output = self.raw_output.clone();
 
         tokio::spawn(async move {
-            let mut lines = BufReader::new(stderr).lines();
+            let mut reader = BufReader::new(stderr);
+            let mut buf = Vec::new();
             let mut line_count: u64 = 0;
 
-            while let Ok(Some(line)) = lines.next_line().await {
+            while let Ok(Some(_)) =
+                read_line_bounded(&mut reader, &mut buf, MAX_STDERR_LINE_BYTES).await
+            {
+                let mut line = String::from_utf8_lossy(&buf)
+                    .trim_end_matches('\r')
+                    .to_string();
                 line_count += 1;
+                for value in redactions.read().unwrap().iter() {
+                    if line.contains(value.as_str()) {
+                        line = line.replace(value.as_str(), REDACTED);
+                    }
+                }
                 tracing::info!(
                     line_number = line_count,
                     age_ms = spawned_at.elapsed().as_millis() as u64,
                     "agent stderr: {}",
                     line
                 );
+                {
+                    let mut tail = stderr_tail.lock().unwrap();
+                    if tail.len() == STDERR_TAIL_LINES {
+                        tail.pop_front();
+                    }
+                    tail.push_back(line.clone());
+                }
+                if raw_output.receiver_count() > 0 {
+                    let _ = raw_output.send(RawOutputLine {
+                        stream: OutputStream::Stderr,
+                        line: line.clone(),
+                    });
+                }
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let payload = json!({
+                    "jsonrpc": "2.0",
+                    "method": LOG_METHOD,
+                    "params": {
+                        "stream": "stderr",
+                        "level": stderr_level(&line),
+                        "text": line,
+                    }
+                });
+                let seq = sequence.fetch_add(1, Ordering::SeqCst) + 1;
+                let message = StreamMessage {
+                    sequence: seq,
+                    bytes: payload.to_string().len(),
+                    payload,
+                    received_at: Instant::now(),
+                };
+                ring.lock().await.push(message.clone());
+                let _ = sender.send(message);
             }
 
             tracing::debug!(
@@ -465,30 +835,50 @@ impl AdapterRuntime {
                 age_ms = spawned_at.elapsed().as_millis() as u64,
                 "agent stderr: stream ended"
             );
-        });
+        })
     }
 
-    fn spawn_exit_watcher(&self) {
+    fn spawn_exit_watcher(&self, stderr_done: JoinHandle<()>) {
         let child = self.child.clone();
+        let kill = self.kill.clone();
         let sender = self.sender.clone();
         let ring = self.ring.clone();
         let sequence = self.sequence.clone();
         let spawned_at = self.spawned_at;
         let pending = self.pending.clone();
+        let stderr_tail = self.stderr_tail.clone();
+        let exit = self.exit.clone();
 
         tokio::spawn(async move {
             let status = {
                 let mut guard = child.lock().await;
-                guard.wait().await.ok()
+                tokio::select! {
+                    status = guard.wait() => status.ok(),
+                    _ = kill.notified() => {
+                        let _ = guard.kill().await;
+                        guard.wait().await.ok()
+                    }
+                }
             };
 
             let age_ms = spawned_at.elapsed().as_millis() as u64;
             let pending_count = pending.lock().await.len();
 
             if let Some(status) = status {
+                // Let the stderr loop catch up so the tail includes the
+                // process's last words. A grandchild holding stderr open must
+                // not delay the exit report for long.
+                let _ = tokio::time::timeout(STDERR_DRAIN_TIMEOUT, stderr_done).await;
+
+                let process_exit = ProcessExit {
+                    code: status.code(),
+                    signal: exit_signal(&status),
+                    stderr: stderr_tail.lock().unwrap().iter().cloned().collect(),
+                };
                 tracing::warn!(
                     success = status.success(),
-                    code = status.code(),
+                    code = process_exit.code,
+                    signal = process_exit.signal,
                     age_ms = age_ms,
                     pending_requests = pending_count,
                     "agent process exited"
@@ -499,24 +889,25 @@ impl AdapterRuntime {
                     "method": "_adapter/agent_exited",
                     "params": {
                         "success": status.success(),
-                        "code": status.code(),
+                        "code": process_exit.code,
+                        "signal": process_exit.signal,
+                        "stderr": process_exit.stderr,
                     }
                 });
 
+                // Record the exit before failing in-flight requests so their
+                // callers see `Exited` rather than a generic timeout.
+                *exit.write().unwrap() = Some(process_exit);
+                pending.lock().await.clear();
+
                 let seq = sequence.fetch_add(1, Ordering::SeqCst) + 1;
                 let message = StreamMessage {
                     sequence: seq,
+                    bytes: payload.to_string().len(),
                     payload,
+                    received_at: Instant::now(),
                 };
-
-                {
-                    let mut guard = ring.lock().await;
-                    guard.push_back(message.clone());
-                    while guard.len() > RING_BUFFER_SIZE {
-                        guard.pop_front();
-                    }
-                }
-
+                ring.lock().await.push(message.clone());
                 let _ = sender.send(message);
             } else {
                 tracing::error!(
@@ -562,6 +953,295 @@ impl AdapterRuntime {
     }
 }
 
+/// Whether `payload` is an `_adapter/log` diagnostic rather than part of
+/// the conversation.
+pub fn is_log_envelope(payload: &Value) -> bool {
+    payload.get("method").and_then(Value::as_str) == Some(LOG_METHOD)
+}
+
+/// Best-effort severity of a stderr line, from the words agents and their
+/// runtimes (node, npm, rust tracing) put in front of it.
+fn stderr_level(line: &str) -> &'static str {
+    let lower = line.to_ascii_lowercase();
+    if ["error", "fatal", "panic", "exception"]
+        .iter()
+        .any(|word| lower.contains(word))
+    {
+        "error"
+    } else if lower.contains("warn") || lower.contains("deprecat") {
+        "warn"
+    } else {
+        "info"
+    }
+}
+
+fn ring_after(ring: &ReplayRing, sequence: u64) -> Vec<(u64, Value)> {
+    ring.messages
+        .iter()
+        .filter(|message| message.sequence > sequence)
+        .map(|message| (message.sequence, message.payload.clone()))
+        .collect()
+}
+
+/// Broadcast messages after `last`, in order. A receiver that lags behind the
+/// channel re-reads what it missed from the replay ring. Messages already
+/// evicted from the ring are reported with one `_adapter/events_dropped`
+/// notification, carrying the sequence of the last dropped message, so a
+/// subscriber never sees a silent gap.
+fn live_messages(
+    ring: Arc<Mutex<ReplayRing>>,
+    rx: broadcast::Receiver<StreamMessage>,
+    last: u64,
+) -> impl Stream<Item = (u64, Value)> + Send + 'static {
+    let pending = VecDeque::<(u64, Value)>::new();
+    stream::unfold(
+        (ring, rx, last, pending),
+        |(ring, mut rx, mut last, mut pending)| async move {
+            loop {
+                if let Some((sequence, payload)) = pending.pop_front() {
+                    return Some(((sequence, payload), (ring, rx, last, pending)));
+                }
+                match rx.recv().await {
+                    Ok(message) if message.sequence <= last => continue,
+                    Ok(message) => {
+                        last = message.sequence;
+                        return Some((
+                            (message.sequence, message.payload),
+                            (ring, rx, last, pending),
+                        ));
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        let missed = ring_after(&*ring.lock().await, last);
+                        let first = missed.first().map(|(sequence, _)| *sequence);
+                        tracing::warn!(
+                            skipped,
+                            after = last,
+                            "stream subscriber lagged; catching up from replay buffer"
+                        );
+                        if let Some(first) = first.filter(|first| *first > last + 1) {
+                            pending.push_back((
+                                first - 1,
+                                json!({
+                                    "jsonrpc": "2.0",
+                                    "method": "_adapter/events_dropped",
+                                    "params": { "from": last + 1, "to": first - 1 },
+                                }),
+                            ));
+                        }
+                        if let Some((sequence, _)) = missed.last() {
+                            last = *sequence;
+                        }
+                        pending.extend(missed);
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        },
+    )
+}
+
+/// Read one `\n`-terminated line into `buf`, keeping at most `max_bytes` of
+/// it and discarding the rest. Returns the line's full length, without the
+/// newline, or `None` at end of stream.
+async fn read_line_bounded<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    max_bytes: usize,
+) -> std::io::Result<Option<usize>> {
+    buf.clear();
+    let mut total = 0;
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Ok((total > 0).then_some(total));
+        }
+        let newline = available.iter().position(|byte| *byte == b'\n');
+        let chunk = &available[..newline.unwrap_or(available.len())];
+        let keep = max_bytes.saturating_sub(buf.len()).min(chunk.len());
+        buf.extend_from_slice(&chunk[..keep]);
+        total += chunk.len();
+        let consumed = chunk.len() + usize::from(newline.is_some());
+        reader.consume(consumed);
+        if newline.is_some() {
+            return Ok(Some(total));
+        }
+    }
+}
+
+/// Move tool output over `spill.min_bytes` out of a `session/update`
+/// notification. Returns the files to write, each named by `next_id`.
+fn extract_artifacts(
+    payload: &mut Value,
+    spill: &ArtifactSpill,
+    mut next_id: impl FnMut() -> String,
+) -> Vec<(PathBuf, String)> {
+    let Some(dir) = &spill.dir else {
+        return Vec::new();
+    };
+    if spill.min_bytes == 0
+        || payload.get("method").and_then(Value::as_str) != Some("session/update")
+    {
+        return Vec::new();
+    }
+    let Some(session_id) = payload
+        .pointer("/params/sessionId")
+        .and_then(Value::as_str)
+        .map(artifact_dir_name)
+    else {
+        return Vec::new();
+    };
+    let Some(update) = payload.pointer_mut("/params/update") else {
+        return Vec::new();
+    };
+    if !matches!(
+        update.get("sessionUpdate").and_then(Value::as_str),
+        Some("tool_call" | "tool_call_update")
+    ) {
+        return Vec::new();
+    }
+
+    let mut candidates = Vec::new();
+    if let Some(content) = update.get("content").and_then(Value::as_array) {
+        for (index, block) in content.iter().enumerate() {
+            if block.get("type").and_then(Value::as_str) != Some("content")
+                || block.pointer("/content/type").and_then(Value::as_str) != Some("text")
+            {
+                continue;
+            }
+            if let Some(text) = block.pointer("/content/text").and_then(Value::as_str) {
+                candidates.push((
+                    format!("/content/{index}/content/text"),
+                    text.to_string(),
+                    "text/plain",
+                ));
+            }
+        }
+    }
+    match update.get("rawOutput") {
+        None | Some(Value::Null) => {}
+        Some(Value::String(text)) => {
+            candidates.push(("/rawOutput".to_string(), text.clone(), "text/plain"));
+        }
+        Some(value) => {
+            candidates.push((
+                "/rawOutput".to_string(),
+                value.to_string(),
+                "application/json",
+            ));
+        }
+    }
+
+    let mut files = Vec::new();
+    let mut records = Vec::new();
+    for (pointer, content, mime_type) in candidates {
+        if content.len() <= spill.min_bytes {
+            continue;
+        }
+        let Some(slot) = update.pointer_mut(&pointer) else {
+            continue;
+        };
+        let extension = if mime_type == "application/json" {
+            "json"
+        } else {
+            "txt"
+        };
+        let id = format!("{}.{extension}", next_id());
+        *slot = json!(truncate_at_char_boundary(&content, spill.preview_bytes));
+        records.push(json!({
+            "id": id,
+            "pointer": pointer,
+            "bytes": content.len(),
+            "mimeType": mime_type,
+        }));
+        files.push((dir.join(&session_id).join(&id), content));
+    }
+    if records.is_empty() {
+        return files;
+    }
+    if let Some(update) = update.as_object_mut() {
+        let meta = update.entry("_meta").or_insert_with(|| json!({}));
+        if let Some(meta) = meta.as_object_mut() {
+            meta.insert(
+                "sandboxagent.dev".to_string(),
+                json!({ "artifacts": records }),
+            );
+        }
+    }
+    files
+}
+
+/// Directory name of a session's artifacts. Session ids come from the
+/// agent, so anything but `[A-Za-z0-9_-]` is replaced.
+pub fn artifact_dir_name(session_id: &str) -> String {
+    session_id
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+async fn write_spill(path: &std::path::Path, raw: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(path, raw).await
+}
+
+fn truncate_at_char_boundary(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+#[cfg(unix)]
+fn exit_signal(status: &std::process::ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: &std::process::ExitStatus) -> Option<i32> {
+    None
+}
+
+fn redact_value(value: &mut Value, redactions: &[String]) {
+    if redactions.is_empty() {
+        return;
+    }
+    match value {
+        Value::String(text) => redact_text(text, redactions),
+        Value::Array(items) => {
+            for item in items {
+                redact_value(item, redactions);
+            }
+        }
+        Value::Object(map) => {
+            for item in map.values_mut() {
+                redact_value(item, redactions);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn redact_text(text: &mut String, redactions: &[String]) {
+    for secret in redactions {
+        if text.contains(secret.as_str()) {
+            *text = text.replace(secret.as_str(), REDACTED);
+        }
+    }
+}
+
 fn id_key(value: &Value) -> String {
     serde_json::to_string(value).unwrap_or_else(|_| "null".to_string())
 }
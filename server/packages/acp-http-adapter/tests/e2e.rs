@@ -1,9 +1,13 @@
+use std::collections::HashMap;
 use std::io;
 use std::net::TcpListener;
 use std::path::PathBuf;
 use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use acp_http_adapter::process::{AdapterRuntime, PostOutcome};
+use acp_http_adapter::registry::LaunchSpec;
 use futures::StreamExt;
 use reqwest::{Client, StatusCode};
 use serde_json::{json, Value};
@@ -149,6 +153,59 @@ async fn sse_request_and_client_response_flow() {
     );
 }
 
+#[cfg(unix)]
+#[tokio::test]
+async fn lagging_subscriber_catches_up_from_replay_buffer() {
+    let launch = LaunchSpec {
+        program: PathBuf::from("sh"),
+        args: vec![
+            "-c".to_string(),
+            r#"read -r line
+i=1
+while [ "$i" -le 800 ]; do
+  echo "{\"jsonrpc\":\"2.0\",\"method\":\"mock/tick\",\"params\":{\"i\":$i}}"
+  i=$((i + 1))
+done
+sleep 5"#
+                .to_string(),
+        ],
+        env: HashMap::new(),
+    };
+    let runtime = Arc::new(
+        AdapterRuntime::start(launch, Duration::from_secs(5))
+            .await
+            .expect("start runtime"),
+    );
+    let mut stream = Box::pin(runtime.clone().sequenced_stream(None).await);
+
+    let outcome = runtime
+        .post(json!({ "jsonrpc": "2.0", "method": "mock/start", "params": {} }))
+        .await
+        .expect("post notification");
+    assert!(matches!(outcome, PostOutcome::Accepted));
+
+    // Leave the stream unpolled until the agent has written more than the
+    // broadcast channel holds.
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while runtime.last_sequence() < 800 {
+        assert!(Instant::now() < deadline, "agent output timed out");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    let mut sequences = Vec::new();
+    while sequences.len() < 800 {
+        let (sequence, payload) = tokio::time::timeout(Duration::from_secs(3), stream.next())
+            .await
+            .expect("stream item")
+            .expect("stream open");
+        assert_eq!(payload["method"], "mock/tick");
+        sequences.push(sequence);
+    }
+    assert_eq!(sequences, (1..=800).collect::<Vec<_>>());
+
+    runtime.shutdown().await;
+}
+
 struct SseReader {
     stream: futures::stream::BoxStream<'static, Result<bytes::Bytes, reqwest::Error>>,
     buffer: Vec<u8>,
@@ -1,9 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::fs;
 use std::io::{self, Read};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use flate2::read::GzDecoder;
 use reqwest::blocking::Client;
@@ -13,6 +15,22 @@ use url::Url;
 
 const DEFAULT_ACP_REGISTRY_URL: &str =
     "https://cdn.agentclientprotocol.com/registry/v1/latest/registry.json";
+const PINS_FILE: &str = "pins.json";
+const KNOWN_GOOD_FILE: &str = "known-good.json";
+const REGISTRY_ARTIFACT: &str = "registry.json";
+const RELEASE_LIST_LIMIT: usize = 30;
+const DEFAULT_DOWNLOAD_ATTEMPTS: u32 = 3;
+const DOWNLOAD_BACKOFF_BASE: Duration = Duration::from_millis(500);
+const DOWNLOAD_BACKOFF_MAX: Duration = Duration::from_secs(8);
+const DOWNLOAD_CHUNK_BYTES: usize = 64 * 1024;
+const DEFAULT_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(600);
+const INSTALL_LOCK_POLL: Duration = Duration::from_millis(200);
+const INSTALL_LOCK_STALE: Duration = Duration::from_secs(30 * 60);
+const AGENT_PROCESSES_DIR: &str = "agent_processes";
+const EXTRACT_DIR: &str = ".extract";
+
+/// Called with the URL, bytes received so far and the expected total.
+type DownloadProgress<'a> = &'a dyn Fn(&Url, u64, Option<u64>);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -178,6 +196,7 @@ pub enum InstallSource {
     Fallback,
     LocalPath,
     Builtin,
+    Artifact,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -201,11 +220,14 @@ pub struct InstallResult {
     pub already_installed: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct InstallOptions {
     pub reinstall: bool,
     pub version: Option<String>,
     pub agent_process_version: Option<String>,
+    /// Read artifacts from this directory instead of downloading them.
+    /// Overrides `SANDBOX_AGENT_ARTIFACT_DIR`.
+    pub artifact_dir: Option<PathBuf>,
 }
 
 impl Default for InstallOptions {
@@ -214,6 +236,7 @@ impl Default for InstallOptions {
             reinstall: false,
             version: None,
             agent_process_version: None,
+            artifact_dir: None,
         }
     }
 }
@@ -239,11 +262,54 @@ pub struct AgentProcessLaunchSpec {
     pub version: Option<String>,
 }
 
+/// Versions that installs fall back to when the caller does not ask for one.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentPin {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_process_version: Option<String>,
+}
+
+impl AgentPin {
+    pub fn is_empty(&self) -> bool {
+        self.version.is_none() && self.agent_process_version.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentRelease {
+    pub version: String,
+    pub published_at: Option<String>,
+    pub url: Option<String>,
+    pub notes: Option<String>,
+}
+
+/// Snapshot of an install that is still running.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstallProgress {
+    pub url: Option<String>,
+    pub downloaded_bytes: u64,
+    pub total_bytes: Option<u64>,
+}
+
+/// What [`AgentManager::collect_garbage`] removed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GcReport {
+    pub freed_bytes: u64,
+    /// Agents uninstalled to get back under the quota, oldest install first.
+    pub uninstalled: Vec<AgentId>,
+}
+
 #[derive(Debug, Clone)]
 pub struct AgentManager {
     install_dir: PathBuf,
     platform: Platform,
     registry_url: Url,
+    artifact_dir: Option<PathBuf>,
+    progress: Arc<Mutex<HashMap<AgentId, InstallProgress>>>,
+    inflight: Arc<Mutex<HashMap<AgentId, Arc<InstallFlight>>>>,
 }
 
 impl AgentManager {
@@ -252,6 +318,9 @@ impl AgentManager {
             install_dir: install_dir.into(),
             platform: Platform::detect()?,
             registry_url: registry_url_from_env()?,
+            artifact_dir: artifact_dir_from_env(),
+            progress: Arc::default(),
+            inflight: Arc::default(),
         })
     }
 
@@ -263,6 +332,9 @@ impl AgentManager {
             install_dir: install_dir.into(),
             platform,
             registry_url,
+            artifact_dir: artifact_dir_from_env(),
+            progress: Arc::default(),
+            inflight: Arc::default(),
         }
     }
 
@@ -275,7 +347,7 @@ impl AgentManager {
     }
 
     pub fn agent_process_path(&self, agent: AgentId) -> PathBuf {
-        let base = self.install_dir.join("agent_processes");
+        let base = self.install_dir.join(AGENT_PROCESSES_DIR);
         if cfg!(windows) {
             base.join(format!("{}-acp.cmd", agent.as_str()))
         } else {
@@ -285,7 +357,7 @@ impl AgentManager {
 
     pub fn agent_process_storage_dir(&self, agent: AgentId) -> PathBuf {
         self.install_dir
-            .join("agent_processes")
+            .join(AGENT_PROCESSES_DIR)
             .join(agent.as_str())
     }
 
@@ -316,19 +388,92 @@ impl AgentManager {
             .collect()
     }
 
+    /// Install `agent`. Concurrent calls with the same options share one
+    /// install and its result; calls with different options run after it.
+    /// Installs in other processes sharing the install directory are
+    /// serialized through a lock file.
     pub fn install(
         &self,
         agent: AgentId,
         options: InstallOptions,
     ) -> Result<InstallResult, AgentError> {
+        loop {
+            let (flight, leading) = {
+                let mut inflight = self.inflight.lock().unwrap_or_else(PoisonError::into_inner);
+                match inflight.get(&agent) {
+                    Some(flight) => (flight.clone(), false),
+                    None => {
+                        let flight = Arc::new(InstallFlight::new(options.clone()));
+                        inflight.insert(agent, flight.clone());
+                        (flight, true)
+                    }
+                }
+            };
+
+            if leading {
+                let lease = FlightLease {
+                    inflight: &self.inflight,
+                    agent,
+                    flight,
+                };
+                let result = self.install_exclusive(agent, options);
+                lease
+                    .flight
+                    .finish(result.as_ref().cloned().map_err(ToString::to_string));
+                return result;
+            }
+
+            let outcome = flight.wait();
+            if flight.options == options {
+                return outcome.map_err(AgentError::ConcurrentInstallFailed);
+            }
+        }
+    }
+
+    fn install_exclusive(
+        &self,
+        agent: AgentId,
+        mut options: InstallOptions,
+    ) -> Result<InstallResult, AgentError> {
+        if let Some(pin) = self.pin(agent) {
+            options.version = options.version.or(pin.version);
+            options.agent_process_version =
+                options.agent_process_version.or(pin.agent_process_version);
+        }
+
         fs::create_dir_all(&self.install_dir)?;
-        fs::create_dir_all(self.install_dir.join("agent_processes"))?;
+        fs::create_dir_all(self.install_dir.join(AGENT_PROCESSES_DIR))?;
+
+        let _lock = InstallFileLock::acquire(self.install_lock_path(agent))?;
+        let _tracking = ProgressTracking::start(&self.progress, agent);
+        let progress = |url: &Url, downloaded_bytes: u64, total_bytes: Option<u64>| {
+            if let Ok(mut entries) = self.progress.lock() {
+                entries.insert(
+                    agent,
+                    InstallProgress {
+                        url: Some(url.to_string()),
+                        downloaded_bytes,
+                        total_bytes,
+                    },
+                );
+            }
+        };
+
+        let mirrors = download_mirrors(agent);
+        let fetcher = ArtifactFetcher {
+            dir: options
+                .artifact_dir
+                .as_deref()
+                .or(self.artifact_dir.as_deref()),
+            mirrors: &mirrors,
+            progress: &progress,
+        };
 
         let mut artifacts = Vec::new();
         let mut already_installed = true;
 
         if agent.native_required() {
-            let native_artifact = self.install_native(agent, &options)?;
+            let native_artifact = self.install_native(agent, &options, &fetcher)?;
             if native_artifact.is_some() {
                 already_installed = false;
             }
@@ -337,7 +482,7 @@ impl AgentManager {
             }
         }
 
-        let agent_process_artifact = self.install_agent_process(agent, &options)?;
+        let agent_process_artifact = self.install_agent_process(agent, &options, &fetcher)?;
         if agent_process_artifact.is_some() {
             already_installed = false;
         }
@@ -351,6 +496,225 @@ impl AgentManager {
         })
     }
 
+    fn install_lock_path(&self, agent: AgentId) -> PathBuf {
+        self.install_dir
+            .join(format!(".{}.install.lock", agent.as_str()))
+    }
+
+    /// Bytes `agent`'s files take up in the install directory. Binaries
+    /// found on `PATH` are not counted.
+    pub fn disk_usage(&self, agent: AgentId) -> u64 {
+        self.install_paths(agent)
+            .iter()
+            .map(|path| path_size(path))
+            .sum()
+    }
+
+    /// Bytes the whole install directory takes up, the figure
+    /// [`Self::collect_garbage`] holds to its quota.
+    pub fn install_dir_usage(&self) -> u64 {
+        path_size(&self.install_dir)
+    }
+
+    /// Remove `agent`'s binary, launcher and extracted agent process, and
+    /// return the bytes freed. Pins are kept, so the next install returns to
+    /// the same versions.
+    pub fn uninstall(&self, agent: AgentId) -> Result<u64, AgentError> {
+        if !self.install_dir.is_dir() {
+            return Ok(0);
+        }
+        let _lock = InstallFileLock::acquire(self.install_lock_path(agent))?;
+        self.remove_install(agent)
+    }
+
+    /// Clean up the install directory: install locks and extraction
+    /// directories left behind by crashed installs, and extracted agent
+    /// processes no launcher uses. With `quota_bytes`, agents not in `keep`
+    /// are then uninstalled, least recently installed first, until the
+    /// directory fits. Agents with an install running are skipped.
+    pub fn collect_garbage(
+        &self,
+        quota_bytes: Option<u64>,
+        keep: &[AgentId],
+    ) -> Result<GcReport, AgentError> {
+        let mut report = GcReport::default();
+        if !self.install_dir.is_dir() {
+            return Ok(report);
+        }
+
+        let mut leftovers = Vec::new();
+        for entry in fs::read_dir(&self.install_dir)? {
+            let path = entry?.path();
+            let is_lock = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with('.') && name.ends_with(".install.lock"));
+            if is_lock && older_than(&path, INSTALL_LOCK_STALE) {
+                leftovers.push(path);
+            }
+        }
+        let extract_dir = self.install_dir.join(EXTRACT_DIR);
+        if extract_dir.is_dir() {
+            for entry in fs::read_dir(&extract_dir)? {
+                let path = entry?.path();
+                if older_than(&path, INSTALL_LOCK_STALE) {
+                    leftovers.push(path);
+                }
+            }
+        }
+        let processes_dir = self.install_dir.join(AGENT_PROCESSES_DIR);
+        if processes_dir.is_dir() {
+            for entry in fs::read_dir(&processes_dir)? {
+                let path = entry?.path();
+                if !path.is_dir() {
+                    continue;
+                }
+                let orphaned = match path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(AgentId::parse)
+                {
+                    Some(agent) => {
+                        !self.agent_process_path(agent).exists()
+                            && !self.install_lock_path(agent).exists()
+                    }
+                    None => true,
+                };
+                if orphaned {
+                    leftovers.push(path);
+                }
+            }
+        }
+        for path in leftovers {
+            report.freed_bytes += path_size(&path);
+            remove_path(&path)?;
+        }
+
+        let Some(quota_bytes) = quota_bytes else {
+            return Ok(report);
+        };
+        let mut usage = self.install_dir_usage();
+        let mut candidates = AgentId::all()
+            .iter()
+            .copied()
+            .filter(|agent| !keep.contains(agent))
+            .filter_map(|agent| Some((self.installed_at(agent)?, agent)))
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(installed_at, _)| *installed_at);
+        for (_, agent) in candidates {
+            if usage <= quota_bytes {
+                break;
+            }
+            let Some(_lock) = InstallFileLock::try_acquire(self.install_lock_path(agent))? else {
+                continue;
+            };
+            let freed = self.remove_install(agent)?;
+            usage = usage.saturating_sub(freed);
+            report.freed_bytes += freed;
+            report.uninstalled.push(agent);
+        }
+        Ok(report)
+    }
+
+    fn install_paths(&self, agent: AgentId) -> [PathBuf; 3] {
+        [
+            self.binary_path(agent),
+            self.agent_process_path(agent),
+            self.agent_process_storage_dir(agent),
+        ]
+    }
+
+    fn remove_install(&self, agent: AgentId) -> Result<u64, AgentError> {
+        let mut freed = 0;
+        for path in self.install_paths(agent) {
+            freed += path_size(&path);
+            remove_path(&path)?;
+        }
+        Ok(freed)
+    }
+
+    /// When `agent` was last installed into the install directory, if it is.
+    fn installed_at(&self, agent: AgentId) -> Option<SystemTime> {
+        [self.binary_path(agent), self.agent_process_path(agent)]
+            .iter()
+            .filter_map(|path| fs::symlink_metadata(path).and_then(|m| m.modified()).ok())
+            .max()
+    }
+
+    /// Progress of the install currently running for `agent`, if any.
+    pub fn install_progress(&self, agent: AgentId) -> Option<InstallProgress> {
+        self.progress
+            .lock()
+            .ok()
+            .and_then(|entries| entries.get(&agent).cloned())
+    }
+
+    pub fn pin(&self, agent: AgentId) -> Option<AgentPin> {
+        self.read_pins().remove(agent.as_str())
+    }
+
+    /// Store (or clear, when empty) the versions future installs use by default.
+    pub fn set_pin(&self, agent: AgentId, pin: AgentPin) -> Result<(), AgentError> {
+        let mut pins = self.read_pins();
+        if pin.is_empty() {
+            pins.remove(agent.as_str());
+        } else {
+            pins.insert(agent.as_str().to_string(), pin);
+        }
+        fs::create_dir_all(&self.install_dir)?;
+        let contents = serde_json::to_string_pretty(&pins)
+            .map_err(|err| AgentError::ExtractFailed(err.to_string()))?;
+        write_text_file(&self.install_dir.join(PINS_FILE), &contents)
+    }
+
+    fn read_pins(&self) -> BTreeMap<String, AgentPin> {
+        read_pin_table(&self.install_dir.join(PINS_FILE))
+    }
+
+    /// Versions of `agent` known to work with this server: the last versions
+    /// that completed a turn here, else the entry in the compatibility table
+    /// at `SANDBOX_AGENT_COMPAT_TABLE` (a JSON object of agent id to pin).
+    pub fn known_good(&self, agent: AgentId) -> Option<AgentPin> {
+        read_pin_table(&self.install_dir.join(KNOWN_GOOD_FILE))
+            .remove(agent.as_str())
+            .or_else(|| {
+                let table = std::env::var_os("SANDBOX_AGENT_COMPAT_TABLE")?;
+                read_pin_table(Path::new(&table)).remove(agent.as_str())
+            })
+            .filter(|pin| !pin.is_empty())
+    }
+
+    /// Remember `pin` as the versions of `agent` that last worked.
+    pub fn record_known_good(&self, agent: AgentId, pin: AgentPin) -> Result<(), AgentError> {
+        let path = self.install_dir.join(KNOWN_GOOD_FILE);
+        let mut table = read_pin_table(&path);
+        if table.get(agent.as_str()) == Some(&pin) {
+            return Ok(());
+        }
+        table.insert(agent.as_str().to_string(), pin);
+        fs::create_dir_all(&self.install_dir)?;
+        let contents = serde_json::to_string_pretty(&table)
+            .map_err(|err| AgentError::ExtractFailed(err.to_string()))?;
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// Releases of the native agent binary, newest first. Agents without a
+    /// native binary have none.
+    pub fn available_versions(&self, agent: AgentId) -> Result<Vec<AgentRelease>, AgentError> {
+        match agent {
+            AgentId::Codex => github_releases("openai/codex"),
+            AgentId::Opencode => github_releases("anomalyco/opencode"),
+            AgentId::Claude => latest_text_release(
+                "https://storage.googleapis.com/claude-code-dist-86c565f3-f756-42ad-8dfa-d59b1c096819/claude-code-releases/latest",
+            ),
+            AgentId::Amp => latest_text_release(
+                "https://storage.googleapis.com/amp-public-assets-prod-0/cli/cli-version.txt",
+            ),
+            AgentId::Pi | AgentId::Cursor | AgentId::Mock => Ok(Vec::new()),
+        }
+    }
+
     pub fn is_installed(&self, agent: AgentId) -> bool {
         let native_ok = !agent.native_required() || self.native_installed(agent);
         native_ok && self.agent_process_status(agent).is_some()
@@ -453,19 +817,19 @@ impl AgentManager {
         &self,
         agent: AgentId,
         options: &InstallOptions,
+        fetcher: &ArtifactFetcher<'_>,
     ) -> Result<Option<InstalledArtifact>, AgentError> {
         if !options.reinstall && self.native_installed(agent) {
             return Ok(None);
         }
 
         let path = self.binary_path(agent);
+        let version = options.version.as_deref();
         match agent {
-            AgentId::Claude => install_claude(&path, self.platform, options.version.as_deref())?,
-            AgentId::Codex => install_codex(&path, self.platform, options.version.as_deref())?,
-            AgentId::Opencode => {
-                install_opencode(&path, self.platform, options.version.as_deref())?
-            }
-            AgentId::Amp => install_amp(&path, self.platform, options.version.as_deref())?,
+            AgentId::Claude => install_claude(&path, self.platform, version, fetcher)?,
+            AgentId::Codex => install_codex(&path, self.platform, version, fetcher)?,
+            AgentId::Opencode => install_opencode(&path, self.platform, version, fetcher)?,
+            AgentId::Amp => install_amp(&path, self.platform, version, fetcher)?,
             AgentId::Pi | AgentId::Cursor => {
                 return Ok(None);
             }
@@ -478,7 +842,11 @@ impl AgentManager {
             kind: InstalledArtifactKind::NativeAgent,
             path,
             version: self.version(agent).ok().flatten(),
-            source: InstallSource::Fallback,
+            source: if fetcher.dir.is_some() {
+                InstallSource::Artifact
+            } else {
+                InstallSource::Fallback
+            },
         }))
     }
 
@@ -486,6 +854,7 @@ impl AgentManager {
         &self,
         agent: AgentId,
         options: &InstallOptions,
+        fetcher: &ArtifactFetcher<'_>,
     ) -> Result<Option<InstalledArtifact>, AgentError> {
         if !options.reinstall {
             if self.agent_process_status(agent).is_some() {
@@ -504,7 +873,7 @@ impl AgentManager {
             }));
         }
 
-        if let Some(artifact) = self.install_agent_process_from_registry(agent, options)? {
+        if let Some(artifact) = self.install_agent_process_from_registry(agent, options, fetcher)? {
             return Ok(Some(artifact));
         }
 
@@ -539,12 +908,23 @@ impl AgentManager {
         &self,
         agent: AgentId,
         options: &InstallOptions,
+        fetcher: &ArtifactFetcher<'_>,
     ) -> Result<Option<InstalledArtifact>, AgentError> {
         let Some(registry_id) = agent.agent_process_registry_id() else {
             return Ok(None);
         };
 
-        let registry = fetch_registry(&self.registry_url)?;
+        let registry = match fetcher.dir {
+            Some(dir) => {
+                let path = dir.join(REGISTRY_ARTIFACT);
+                if !path.is_file() {
+                    return Ok(None);
+                }
+                serde_json::from_slice::<RegistryDocument>(&fs::read(path)?)
+                    .map_err(|err| AgentError::RegistryParse(err.to_string()))?
+            }
+            None => fetch_registry(&self.registry_url)?,
+        };
         let Some(entry) = registry.agents.into_iter().find(|a| a.id == registry_id) else {
             return Ok(None);
         };
@@ -571,7 +951,7 @@ impl AgentManager {
             let key = self.platform.registry_key();
             if let Some(target) = binary.get(key) {
                 let archive_url = Url::parse(&target.archive)?;
-                let payload = download_bytes(&archive_url)?;
+                let payload = fetcher.fetch(&archive_url, &url_file_name(&archive_url), None)?;
                 let root = self.agent_process_storage_dir(agent);
                 if root.exists() {
                     fs::remove_dir_all(&root)?;
@@ -718,8 +1098,18 @@ pub enum AgentError {
         agent: AgentId,
         hint: Option<String>,
     },
-    #[error("download failed: {url}")]
-    DownloadFailed { url: Url },
+    #[error("download failed after {attempts} attempt(s): {url}: {reason}")]
+    DownloadFailed {
+        url: Url,
+        attempts: u32,
+        reason: String,
+    },
+    #[error("download timed out: {url}")]
+    DownloadTimeout { url: Url },
+    #[error("artifact not found in {}: expected {}", .dir.display(), .expected.join(" or "))]
+    ArtifactMissing { dir: PathBuf, expected: Vec<String> },
+    #[error("concurrent install failed: {0}")]
+    ConcurrentInstallFailed(String),
     #[error("http error: {0}")]
     Http(#[from] reqwest::Error),
     #[error("url parse error: {0}")]
@@ -750,6 +1140,12 @@ fn registry_url_from_env() -> Result<Url, AgentError> {
     }
 }
 
+fn artifact_dir_from_env() -> Option<PathBuf> {
+    std::env::var_os("SANDBOX_AGENT_ARTIFACT_DIR")
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
 fn apply_npx_version_override(package: &str, version: Option<&str>) -> String {
     let Some(version) = version else {
         return package.to_string();
@@ -867,6 +1263,13 @@ fn shell_escape(value: &str) -> String {
     value.replace('\'', "'\\''")
 }
 
+fn read_pin_table(path: &Path) -> BTreeMap<String, AgentPin> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
 fn write_text_file(path: &Path, contents: &str) -> Result<(), AgentError> {
     fs::write(path, contents)?;
     set_executable(path)?;
@@ -901,7 +1304,11 @@ fn fetch_registry(url: &Url) -> Result<RegistryDocument, AgentError> {
     let client = Client::builder().build()?;
     let response = client.get(url.clone()).send()?;
     if !response.status().is_success() {
-        return Err(AgentError::DownloadFailed { url: url.clone() });
+        return Err(AgentError::DownloadFailed {
+            url: url.clone(),
+            attempts: 1,
+            reason: format!("HTTP {}", response.status()),
+        });
     }
     response
         .json::<RegistryDocument>()
@@ -982,21 +1389,446 @@ fn find_in_path(binary_name: &str) -> Option<PathBuf> {
     None
 }
 
-fn download_bytes(url: &Url) -> Result<Vec<u8>, AgentError> {
-    let client = Client::builder().build()?;
-    let mut response = client.get(url.clone()).send()?;
+/// An install in progress that identical concurrent requests wait on.
+#[derive(Debug)]
+struct InstallFlight {
+    options: InstallOptions,
+    outcome: Mutex<Option<Result<InstallResult, String>>>,
+    done: Condvar,
+}
+
+impl InstallFlight {
+    fn new(options: InstallOptions) -> Self {
+        Self {
+            options,
+            outcome: Mutex::new(None),
+            done: Condvar::new(),
+        }
+    }
+
+    fn wait(&self) -> Result<InstallResult, String> {
+        let mut outcome = self.outcome.lock().unwrap_or_else(PoisonError::into_inner);
+        loop {
+            if let Some(result) = outcome.as_ref() {
+                return result.clone();
+            }
+            outcome = self
+                .done
+                .wait(outcome)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    fn finish(&self, result: Result<InstallResult, String>) {
+        let mut outcome = self.outcome.lock().unwrap_or_else(PoisonError::into_inner);
+        if outcome.is_none() {
+            *outcome = Some(result);
+        }
+        self.done.notify_all();
+    }
+}
+
+/// Held by the caller running an install; releases waiters even if the
+/// install panics.
+struct FlightLease<'a> {
+    inflight: &'a Mutex<HashMap<AgentId, Arc<InstallFlight>>>,
+    agent: AgentId,
+    flight: Arc<InstallFlight>,
+}
+
+impl Drop for FlightLease<'_> {
+    fn drop(&mut self) {
+        self.flight
+            .finish(Err("install was interrupted".to_string()));
+        let mut inflight = self.inflight.lock().unwrap_or_else(PoisonError::into_inner);
+        if inflight
+            .get(&self.agent)
+            .is_some_and(|flight| Arc::ptr_eq(flight, &self.flight))
+        {
+            inflight.remove(&self.agent);
+        }
+    }
+}
+
+/// Exclusive lock file guarding an agent's install across processes. A lock
+/// older than `INSTALL_LOCK_STALE` is treated as left behind by a crash.
+struct InstallFileLock {
+    path: PathBuf,
+}
+
+impl InstallFileLock {
+    fn acquire(path: PathBuf) -> Result<Self, AgentError> {
+        loop {
+            if let Some(lock) = Self::try_acquire(path.clone())? {
+                return Ok(lock);
+            }
+            std::thread::sleep(INSTALL_LOCK_POLL);
+        }
+    }
+
+    /// Take the lock, or return `None` when another install holds it.
+    fn try_acquire(path: PathBuf) -> Result<Option<Self>, AgentError> {
+        loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    let _ =
+                        io::Write::write_all(&mut file, std::process::id().to_string().as_bytes());
+                    return Ok(Some(Self { path }));
+                }
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    if !older_than(&path, INSTALL_LOCK_STALE) {
+                        return Ok(None);
+                    }
+                    let _ = fs::remove_file(&path);
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+impl Drop for InstallFileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn older_than(path: &Path, age: Duration) -> bool {
+    fs::symlink_metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|elapsed| elapsed > age)
+}
+
+/// Size of a file or directory tree. Symlinks count as themselves.
+fn path_size(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| path_size(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+fn remove_path(path: &Path) -> io::Result<()> {
+    let result = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(err) => Err(err),
+    };
+    match result {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Clears an agent's progress entry when its install returns, including on
+/// error.
+struct ProgressTracking<'a> {
+    entries: &'a Mutex<HashMap<AgentId, InstallProgress>>,
+    agent: AgentId,
+}
+
+impl<'a> ProgressTracking<'a> {
+    fn start(entries: &'a Mutex<HashMap<AgentId, InstallProgress>>, agent: AgentId) -> Self {
+        if let Ok(mut entries) = entries.lock() {
+            entries.insert(agent, InstallProgress::default());
+        }
+        Self { entries, agent }
+    }
+}
+
+impl Drop for ProgressTracking<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.remove(&self.agent);
+        }
+    }
+}
+
+fn download_timeout() -> Duration {
+    std::env::var("SANDBOX_AGENT_DOWNLOAD_TIMEOUT_SECS")
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_DOWNLOAD_TIMEOUT)
+}
+
+/// Where installers read artifacts from. With a directory set nothing is
+/// downloaded; each artifact is looked up by file name, first under a
+/// `<version>/` subdirectory when a version was requested.
+struct ArtifactFetcher<'a> {
+    dir: Option<&'a Path>,
+    mirrors: &'a [Url],
+    progress: DownloadProgress<'a>,
+}
+
+impl ArtifactFetcher<'_> {
+    fn fetch(&self, url: &Url, name: &str, version: Option<&str>) -> Result<Vec<u8>, AgentError> {
+        let Some(dir) = self.dir else {
+            return download_bytes(url, self.mirrors, self.progress);
+        };
+
+        let candidates: Vec<PathBuf> = version
+            .map(|version| Path::new(version).join(name))
+            .into_iter()
+            .chain([PathBuf::from(name)])
+            .collect();
+        for candidate in &candidates {
+            let path = dir.join(candidate);
+            if path.is_file() {
+                let bytes = fs::read(&path)?;
+                (self.progress)(url, bytes.len() as u64, Some(bytes.len() as u64));
+                return Ok(bytes);
+            }
+        }
+        Err(AgentError::ArtifactMissing {
+            dir: dir.to_path_buf(),
+            expected: candidates
+                .iter()
+                .map(|candidate| candidate.display().to_string())
+                .collect(),
+        })
+    }
+}
+
+fn url_file_name(url: &Url) -> String {
+    url.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn download_attempts() -> u32 {
+    std::env::var("SANDBOX_AGENT_DOWNLOAD_ATTEMPTS")
+        .ok()
+        .and_then(|value| value.trim().parse::<u32>().ok())
+        .filter(|attempts| *attempts > 0)
+        .unwrap_or(DEFAULT_DOWNLOAD_ATTEMPTS)
+}
+
+/// Mirror base URLs for `agent`, tried in order when the upstream URL fails.
+/// `SANDBOX_AGENT_DOWNLOAD_MIRRORS_<AGENT>` replaces the shared
+/// `SANDBOX_AGENT_DOWNLOAD_MIRRORS` list for that agent.
+fn download_mirrors(agent: AgentId) -> Vec<Url> {
+    let key = format!(
+        "SANDBOX_AGENT_DOWNLOAD_MIRRORS_{}",
+        agent.as_str().to_ascii_uppercase()
+    );
+    std::env::var(key)
+        .or_else(|_| std::env::var("SANDBOX_AGENT_DOWNLOAD_MIRRORS"))
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|base| !base.is_empty())
+        .filter_map(|base| Url::parse(base).ok())
+        .collect()
+}
+
+/// `url` served from `base`: the upstream path and query appended to the
+/// mirror's path.
+fn mirror_url(base: &Url, url: &Url) -> Url {
+    let mut mirrored = base.clone();
+    mirrored.set_path(&format!(
+        "{}{}",
+        base.path().trim_end_matches('/'),
+        url.path()
+    ));
+    mirrored.set_query(url.query());
+    mirrored
+}
+
+/// Exponential backoff before retry `attempt + 1`, plus up to 25% jitter so
+/// parallel installs do not retry in lockstep.
+fn download_backoff(attempt: u32) -> Duration {
+    let delay = DOWNLOAD_BACKOFF_BASE
+        .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+        .min(DOWNLOAD_BACKOFF_MAX);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or_default();
+    delay + delay.mul_f64(f64::from(nanos % 1000) / 4000.0)
+}
+
+/// Download `url` in chunks, reporting progress as bytes arrive. Each source,
+/// upstream first and then every mirror, gets `SANDBOX_AGENT_DOWNLOAD_ATTEMPTS`
+/// tries. Transient failures are retried and resume from the bytes already
+/// received when the server honours range requests; HTTP errors move straight
+/// to the next mirror.
+fn download_bytes(
+    url: &Url,
+    mirrors: &[Url],
+    progress: DownloadProgress<'_>,
+) -> Result<Vec<u8>, AgentError> {
+    let max_attempts = download_attempts();
+    let timeout = download_timeout();
+    let client = Client::builder().timeout(timeout).build()?;
+    let sources =
+        std::iter::once(url.clone()).chain(mirrors.iter().map(|base| mirror_url(base, url)));
+
+    let mut attempts = 0;
+    let mut reason = String::new();
+    for source in sources {
+        let deadline = Instant::now() + timeout;
+        let mut bytes = Vec::new();
+        for attempt in 1..=max_attempts {
+            attempts += 1;
+            let err = match download_attempt(&client, &source, &mut bytes, deadline, progress) {
+                Ok(()) => return Ok(bytes),
+                Err(err) => err,
+            };
+            let retryable = matches!(err, AgentError::Http(_) | AgentError::Io(_));
+            reason = match err {
+                AgentError::DownloadFailed { reason, .. } if mirrors.is_empty() => reason,
+                AgentError::DownloadFailed { url, reason, .. } => format!("{url}: {reason}"),
+                err => err.to_string(),
+            };
+            if !retryable || attempt == max_attempts || Instant::now() >= deadline {
+                break;
+            }
+            std::thread::sleep(download_backoff(attempt));
+        }
+    }
+
+    Err(AgentError::DownloadFailed {
+        url: url.clone(),
+        attempts,
+        reason,
+    })
+}
+
+fn download_attempt(
+    client: &Client,
+    url: &Url,
+    bytes: &mut Vec<u8>,
+    deadline: Instant,
+    progress: DownloadProgress<'_>,
+) -> Result<(), AgentError> {
+    let mut request = client.get(url.clone());
+    if !bytes.is_empty() {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", bytes.len()));
+    }
+    let mut response = request.send()?;
+    let status = response.status();
+    if status.is_server_error() {
+        response.error_for_status_ref()?;
+    }
+    if !status.is_success() {
+        return Err(AgentError::DownloadFailed {
+            url: url.clone(),
+            attempts: 1,
+            reason: format!("HTTP {status}"),
+        });
+    }
+    if status != reqwest::StatusCode::PARTIAL_CONTENT {
+        bytes.clear();
+    }
+
+    let total = response
+        .content_length()
+        .map(|len| len + bytes.len() as u64);
+    progress(url, bytes.len() as u64, total);
+    let mut chunk = vec![0; DOWNLOAD_CHUNK_BYTES];
+    loop {
+        if Instant::now() >= deadline {
+            return Err(AgentError::DownloadTimeout { url: url.clone() });
+        }
+        let read = response.read(&mut chunk)?;
+        if read == 0 {
+            break;
+        }
+        bytes.extend_from_slice(&chunk[..read]);
+        progress(url, bytes.len() as u64, total);
+    }
+
+    if total.is_some_and(|total| (bytes.len() as u64) < total) {
+        return Err(AgentError::Io(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("download of {url} ended early"),
+        )));
+    }
+    Ok(())
+}
+
+fn github_releases(repo: &str) -> Result<Vec<AgentRelease>, AgentError> {
+    #[derive(Deserialize)]
+    struct GithubRelease {
+        tag_name: String,
+        #[serde(default)]
+        published_at: Option<String>,
+        #[serde(default)]
+        html_url: Option<String>,
+        #[serde(default)]
+        body: Option<String>,
+        #[serde(default)]
+        draft: bool,
+        #[serde(default)]
+        prerelease: bool,
+    }
+
+    let url = Url::parse(&format!(
+        "https://api.github.com/repos/{repo}/releases?per_page={RELEASE_LIST_LIMIT}"
+    ))?;
+    let client = Client::builder().user_agent("sandbox-agent").build()?;
+    let response = client.get(url.clone()).send()?;
     if !response.status().is_success() {
-        return Err(AgentError::DownloadFailed { url: url.clone() });
+        return Err(AgentError::DownloadFailed {
+            url,
+            attempts: 1,
+            reason: format!("HTTP {}", response.status()),
+        });
     }
-    let mut bytes = Vec::new();
-    response.read_to_end(&mut bytes)?;
-    Ok(bytes)
+    let releases: Vec<GithubRelease> = response
+        .json()
+        .map_err(|err| AgentError::RegistryParse(err.to_string()))?;
+    Ok(releases
+        .into_iter()
+        .filter(|release| !release.draft && !release.prerelease)
+        .map(|release| AgentRelease {
+            version: release.tag_name,
+            published_at: release.published_at,
+            url: release.html_url,
+            notes: release.body.filter(|body| !body.trim().is_empty()),
+        })
+        .collect())
+}
+
+fn latest_text_release(url: &str) -> Result<Vec<AgentRelease>, AgentError> {
+    let url = Url::parse(url)?;
+    let text = String::from_utf8(download_bytes(&url, &[], &|_, _, _| {})?)
+        .map_err(|err| AgentError::ExtractFailed(err.to_string()))?;
+    let version = text.trim();
+    if version.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(vec![AgentRelease {
+        version: version.to_string(),
+        published_at: None,
+        url: None,
+        notes: None,
+    }])
 }
 
 fn install_claude(
     path: &Path,
     platform: Platform,
     version: Option<&str>,
+    fetcher: &ArtifactFetcher<'_>,
 ) -> Result<(), AgentError> {
     let version = match version {
         Some(version) => version.to_string(),
@@ -1004,7 +1836,7 @@ fn install_claude(
             let url = Url::parse(
                 "https://storage.googleapis.com/claude-code-dist-86c565f3-f756-42ad-8dfa-d59b1c096819/claude-code-releases/latest",
             )?;
-            let text = String::from_utf8(download_bytes(&url)?)
+            let text = String::from_utf8(fetcher.fetch(&url, "claude-version.txt", None)?)
                 .map_err(|err| AgentError::ExtractFailed(err.to_string()))?;
             text.trim().to_string()
         }
@@ -1023,19 +1855,28 @@ fn install_claude(
     let url = Url::parse(&format!(
         "https://storage.googleapis.com/claude-code-dist-86c565f3-f756-42ad-8dfa-d59b1c096819/claude-code-releases/{version}/{platform_segment}/claude"
     ))?;
-    let bytes = download_bytes(&url)?;
+    let bytes = fetcher.fetch(
+        &url,
+        &format!("claude-{platform_segment}"),
+        Some(version.as_str()),
+    )?;
     write_executable(path, &bytes)?;
     Ok(())
 }
 
-fn install_amp(path: &Path, platform: Platform, version: Option<&str>) -> Result<(), AgentError> {
+fn install_amp(
+    path: &Path,
+    platform: Platform,
+    version: Option<&str>,
+    fetcher: &ArtifactFetcher<'_>,
+) -> Result<(), AgentError> {
     let version = match version {
         Some(version) => version.to_string(),
         None => {
             let url = Url::parse(
                 "https://storage.googleapis.com/amp-public-assets-prod-0/cli/cli-version.txt",
             )?;
-            let text = String::from_utf8(download_bytes(&url)?)
+            let text = String::from_utf8(fetcher.fetch(&url, "amp-version.txt", None)?)
                 .map_err(|err| AgentError::ExtractFailed(err.to_string()))?;
             text.trim().to_string()
         }
@@ -1053,12 +1894,21 @@ fn install_amp(path: &Path, platform: Platform, version: Option<&str>) -> Result
     let url = Url::parse(&format!(
         "https://storage.googleapis.com/amp-public-assets-prod-0/cli/{version}/amp-{platform_segment}"
     ))?;
-    let bytes = download_bytes(&url)?;
+    let bytes = fetcher.fetch(
+        &url,
+        &format!("amp-{platform_segment}"),
+        Some(version.as_str()),
+    )?;
     write_executable(path, &bytes)?;
     Ok(())
 }
 
-fn install_codex(path: &Path, platform: Platform, version: Option<&str>) -> Result<(), AgentError> {
+fn install_codex(
+    path: &Path,
+    platform: Platform,
+    version: Option<&str>,
+    fetcher: &ArtifactFetcher<'_>,
+) -> Result<(), AgentError> {
     let target = match platform {
         Platform::LinuxX64 | Platform::LinuxX64Musl => "x86_64-unknown-linux-musl",
         Platform::LinuxArm64 => "aarch64-unknown-linux-musl",
@@ -1077,8 +1927,8 @@ fn install_codex(path: &Path, platform: Platform, version: Option<&str>) -> Resu
         ))?,
     };
 
-    let bytes = download_bytes(&url)?;
-    let temp_dir = tempfile::tempdir()?;
+    let bytes = fetcher.fetch(&url, &url_file_name(&url), version)?;
+    let temp_dir = extract_dir(path)?;
     let cursor = io::Cursor::new(bytes);
     let mut archive = tar::Archive::new(GzDecoder::new(cursor));
     archive.unpack(temp_dir.path())?;
@@ -1099,6 +1949,7 @@ fn install_opencode(
     path: &Path,
     platform: Platform,
     version: Option<&str>,
+    fetcher: &ArtifactFetcher<'_>,
 ) -> Result<(), AgentError> {
     match platform {
         Platform::MacosArm64 => {
@@ -1110,7 +1961,7 @@ fn install_opencode(
                     "https://github.com/anomalyco/opencode/releases/latest/download/opencode-darwin-arm64.zip",
                 )?,
             };
-            install_zip_binary(path, &url, "opencode")
+            install_zip_binary(path, &url, "opencode", version, fetcher)
         }
         Platform::MacosX64 => {
             let url = match version {
@@ -1121,7 +1972,7 @@ fn install_opencode(
                     "https://github.com/anomalyco/opencode/releases/latest/download/opencode-darwin-x64.zip",
                 )?,
             };
-            install_zip_binary(path, &url, "opencode")
+            install_zip_binary(path, &url, "opencode", version, fetcher)
         }
         _ => {
             let platform_segment = match platform {
@@ -1141,8 +1992,8 @@ fn install_opencode(
                 ))?,
             };
 
-            let bytes = download_bytes(&url)?;
-            let temp_dir = tempfile::tempdir()?;
+            let bytes = fetcher.fetch(&url, &url_file_name(&url), version)?;
+            let temp_dir = extract_dir(path)?;
             let cursor = io::Cursor::new(bytes);
             let mut archive = tar::Archive::new(GzDecoder::new(cursor));
             archive.unpack(temp_dir.path())?;
@@ -1155,12 +2006,18 @@ fn install_opencode(
     }
 }
 
-fn install_zip_binary(path: &Path, url: &Url, binary_name: &str) -> Result<(), AgentError> {
-    let bytes = download_bytes(url)?;
+fn install_zip_binary(
+    path: &Path,
+    url: &Url,
+    binary_name: &str,
+    version: Option<&str>,
+    fetcher: &ArtifactFetcher<'_>,
+) -> Result<(), AgentError> {
+    let bytes = fetcher.fetch(url, &url_file_name(url), version)?;
     let reader = io::Cursor::new(bytes);
     let mut archive =
         zip::ZipArchive::new(reader).map_err(|err| AgentError::ExtractFailed(err.to_string()))?;
-    let temp_dir = tempfile::tempdir()?;
+    let temp_dir = extract_dir(path)?;
     for i in 0..archive.len() {
         let mut file = archive
             .by_index(i)
@@ -1179,6 +2036,18 @@ fn install_zip_binary(path: &Path, url: &Url, binary_name: &str) -> Result<(), A
     Err(AgentError::ExtractFailed(format!("missing {binary_name}")))
 }
 
+/// Scratch directory for unpacking the archive of the binary at `dest`. It
+/// lives under the install directory so `collect_garbage` can clear what an
+/// interrupted install leaves behind.
+fn extract_dir(dest: &Path) -> Result<tempfile::TempDir, AgentError> {
+    let root = dest
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(EXTRACT_DIR);
+    fs::create_dir_all(&root)?;
+    Ok(tempfile::tempdir_in(root)?)
+}
+
 fn write_executable(path: &Path, bytes: &[u8]) -> Result<(), AgentError> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)?;
@@ -1321,6 +2190,91 @@ mod tests {
         stream.flush().expect("flush response");
     }
 
+    #[test]
+    fn download_resumes_with_range_after_truncated_response() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind download server");
+        let addr = listener.local_addr().expect("local addr");
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("first connection");
+            let mut buffer = [0_u8; 4096];
+            let _ = stream.read(&mut buffer);
+            stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\nConnection: close\r\n\r\n0123",
+                )
+                .expect("write partial body");
+            drop(stream);
+
+            let (mut stream, _) = listener.accept().expect("second connection");
+            let read = stream.read(&mut buffer).expect("read resumed request");
+            let request = String::from_utf8_lossy(&buffer[..read]).to_ascii_lowercase();
+            stream
+                .write_all(
+                    b"HTTP/1.1 206 Partial Content\r\nContent-Length: 6\r\nConnection: close\r\n\r\n456789",
+                )
+                .expect("write remaining body");
+            request
+        });
+
+        let url = Url::parse(&format!("http://{addr}/agent.bin")).expect("download url");
+        let reported = Mutex::new(Vec::new());
+        let bytes = download_bytes(&url, &[], &|_, downloaded, total| {
+            reported
+                .lock()
+                .expect("progress lock")
+                .push((downloaded, total));
+        })
+        .expect("download should resume");
+
+        assert_eq!(bytes, b"0123456789");
+        let request = server.join().expect("server thread");
+        assert!(request.contains("range: bytes=4-"), "{request}");
+        assert_eq!(
+            reported.lock().expect("progress lock").last(),
+            Some(&(10, Some(10)))
+        );
+    }
+
+    #[test]
+    fn download_falls_back_to_mirror_after_upstream_error() {
+        let upstream = TcpListener::bind("127.0.0.1:0").expect("bind upstream");
+        let upstream_addr = upstream.local_addr().expect("upstream addr");
+        thread::spawn(move || {
+            let (mut stream, _) = upstream.accept().expect("upstream connection");
+            let mut buffer = [0_u8; 4096];
+            let _ = stream.read(&mut buffer);
+            let _ = stream.write_all(
+                b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            );
+        });
+
+        let mirror = TcpListener::bind("127.0.0.1:0").expect("bind mirror");
+        let mirror_addr = mirror.local_addr().expect("mirror addr");
+        let server = thread::spawn(move || {
+            let (mut stream, _) = mirror.accept().expect("mirror connection");
+            let mut buffer = [0_u8; 4096];
+            let read = stream.read(&mut buffer).expect("read mirror request");
+            stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nagent",
+                )
+                .expect("write mirror body");
+            String::from_utf8_lossy(&buffer[..read]).into_owned()
+        });
+
+        let url = Url::parse(&format!("http://{upstream_addr}/releases/v1/agent.bin"))
+            .expect("upstream url");
+        let mirrors = [Url::parse(&format!("http://{mirror_addr}/cache/")).expect("mirror url")];
+        let bytes = download_bytes(&url, &mirrors, &|_, _, _| {}).expect("mirror download");
+
+        assert_eq!(bytes, b"agent");
+        let request = server.join().expect("mirror thread");
+        assert!(
+            request.starts_with("GET /cache/releases/v1/agent.bin "),
+            "{request}"
+        );
+    }
+
     #[test]
     fn install_is_idempotent_when_native_and_agent_process_exists() {
         let temp_dir = tempfile::tempdir().expect("create tempdir");
@@ -1439,6 +2393,7 @@ mod tests {
                     reinstall: false,
                     version: None,
                     agent_process_version: Some("9.9.9".to_string()),
+                    artifact_dir: None,
                 },
             )
             .expect("install succeeds");
@@ -1496,6 +2451,84 @@ mod tests {
         assert_eq!(agent_process_artifact.source, InstallSource::Fallback);
     }
 
+    #[test]
+    fn install_reads_native_binary_from_artifact_dir() {
+        let _env_lock = env_lock().lock().expect("env lock");
+
+        let temp_dir = tempfile::tempdir().expect("create tempdir");
+        let manager = AgentManager::with_platform(temp_dir.path(), Platform::LinuxX64);
+
+        let bin_dir = temp_dir.path().join("bin");
+        fs::create_dir_all(&bin_dir).expect("create bin dir");
+        write_exec(&bin_dir.join("npx"), "#!/usr/bin/env sh\nexit 0\n");
+        let original_path = std::env::var_os("PATH").unwrap_or_default();
+        let mut paths = vec![bin_dir.clone()];
+        paths.extend(std::env::split_paths(&original_path));
+        let combined_path = std::env::join_paths(paths).expect("join PATH");
+        let _path_guard = EnvVarGuard::set("PATH", &combined_path);
+
+        let artifact_dir = temp_dir.path().join("artifacts");
+        let options = InstallOptions {
+            reinstall: true,
+            version: Some("rust-v1.0.0".to_string()),
+            artifact_dir: Some(artifact_dir.clone()),
+            ..InstallOptions::default()
+        };
+
+        fs::create_dir_all(&artifact_dir).expect("create artifact dir");
+        let err = manager
+            .install(AgentId::Codex, options.clone())
+            .expect_err("missing artifact");
+        let message = err.to_string();
+        assert!(
+            message.contains("rust-v1.0.0/codex-x86_64-unknown-linux-musl.tar.gz")
+                && message.contains("or codex-x86_64-unknown-linux-musl.tar.gz"),
+            "error should list expected artifacts: {message}"
+        );
+
+        let script = b"#!/usr/bin/env sh\necho codex-cli 1.0.0\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(script.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        builder
+            .append_data(&mut header, "codex-x86_64-unknown-linux-musl", &script[..])
+            .expect("append binary");
+        let archive = builder
+            .into_inner()
+            .expect("finish tar")
+            .finish()
+            .expect("finish gzip");
+        fs::write(
+            artifact_dir.join("codex-x86_64-unknown-linux-musl.tar.gz"),
+            archive,
+        )
+        .expect("write archive");
+
+        let result = manager
+            .install(AgentId::Codex, options)
+            .expect("offline install succeeds");
+        let native = result
+            .artifacts
+            .iter()
+            .find(|artifact| artifact.kind == InstalledArtifactKind::NativeAgent)
+            .expect("native artifact");
+        assert_eq!(native.source, InstallSource::Artifact);
+        assert!(manager.binary_path(AgentId::Codex).exists());
+
+        // Without registry.json in the artifact dir the npx launcher is used.
+        let agent_process = result
+            .artifacts
+            .iter()
+            .find(|artifact| artifact.kind == InstalledArtifactKind::AgentProcess)
+            .expect("agent process artifact");
+        assert_eq!(agent_process.source, InstallSource::Fallback);
+    }
+
     #[test]
     fn reinstall_mock_returns_agent_process_artifact() {
         let temp_dir = tempfile::tempdir().expect("create tempdir");
@@ -1508,6 +2541,7 @@ mod tests {
                     reinstall: true,
                     version: None,
                     agent_process_version: None,
+                    artifact_dir: None,
                 },
             )
             .expect("mock reinstall");
@@ -1521,6 +2555,89 @@ mod tests {
         assert_eq!(result.artifacts[0].source, InstallSource::Builtin);
     }
 
+    #[test]
+    fn concurrent_installs_wait_for_lock_file_and_share_result() {
+        let temp_dir = tempfile::tempdir().expect("create tempdir");
+        let manager = AgentManager::with_platform(temp_dir.path(), Platform::LinuxX64);
+        let lock_path = manager.install_lock_path(AgentId::Mock);
+        fs::write(&lock_path, b"held by another process").expect("write foreign lock");
+
+        let options = InstallOptions {
+            reinstall: true,
+            version: None,
+            agent_process_version: None,
+            artifact_dir: None,
+        };
+        let handles = (0..2)
+            .map(|_| {
+                let manager = manager.clone();
+                let options = options.clone();
+                thread::spawn(move || manager.install(AgentId::Mock, options))
+            })
+            .collect::<Vec<_>>();
+
+        thread::sleep(Duration::from_millis(300));
+        assert!(
+            handles.iter().all(|handle| !handle.is_finished()),
+            "installs should wait for the foreign lock"
+        );
+        assert_eq!(manager.inflight.lock().expect("inflight").len(), 1);
+        fs::remove_file(&lock_path).expect("release foreign lock");
+
+        let results = handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .expect("install thread")
+                    .expect("install should succeed")
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(results[0].already_installed, results[1].already_installed);
+        assert_eq!(results[0].artifacts[0].path, results[1].artifacts[0].path);
+        assert!(!lock_path.exists());
+        assert!(manager.inflight.lock().expect("inflight").is_empty());
+    }
+
+    #[test]
+    fn collect_garbage_clears_leftovers_and_enforces_quota() {
+        let temp_dir = tempfile::tempdir().expect("create tempdir");
+        let manager = AgentManager::with_platform(temp_dir.path(), Platform::LinuxX64);
+        let processes_dir = temp_dir.path().join(AGENT_PROCESSES_DIR);
+        fs::create_dir_all(&processes_dir).expect("create agent_processes");
+
+        fs::write(manager.binary_path(AgentId::Claude), vec![0_u8; 4096]).expect("claude");
+        write_exec(&manager.agent_process_path(AgentId::Claude), "#!/bin/sh\n");
+        thread::sleep(Duration::from_millis(20));
+        fs::write(manager.binary_path(AgentId::Codex), vec![0_u8; 2048]).expect("codex");
+        write_exec(&manager.agent_process_path(AgentId::Codex), "#!/bin/sh\n");
+        let orphan = manager.agent_process_storage_dir(AgentId::Amp);
+        fs::create_dir_all(&orphan).expect("orphan dir");
+        fs::write(orphan.join("amp-acp"), vec![0_u8; 512]).expect("orphan file");
+
+        assert!(manager.disk_usage(AgentId::Claude) >= 4096);
+
+        let report = manager.collect_garbage(None, &[]).expect("collect garbage");
+        assert_eq!(report.uninstalled, Vec::<AgentId>::new());
+        assert!(report.freed_bytes >= 512);
+        assert!(!orphan.exists());
+        assert!(manager.binary_path(AgentId::Claude).exists());
+
+        let report = manager
+            .collect_garbage(Some(3000), &[AgentId::Codex])
+            .expect("collect garbage over quota");
+        assert_eq!(report.uninstalled, vec![AgentId::Claude]);
+        assert!(!manager.binary_path(AgentId::Claude).exists());
+        assert!(!manager.agent_process_path(AgentId::Claude).exists());
+        assert!(manager.binary_path(AgentId::Codex).exists());
+        assert_eq!(manager.disk_usage(AgentId::Claude), 0);
+
+        let freed = manager.uninstall(AgentId::Codex).expect("uninstall");
+        assert!(freed >= 2048);
+        assert!(!manager.binary_path(AgentId::Codex).exists());
+        assert!(!manager.install_lock_path(AgentId::Codex).exists());
+    }
+
     #[test]
     fn install_pi_skips_native_and_writes_fallback_npx_launcher() {
         let _env_lock = env_lock().lock().expect("env lock");
@@ -1589,6 +2706,92 @@ mod tests {
         );
     }
 
+    #[test]
+    fn install_honors_pinned_agent_process_version() {
+        let _env_lock = env_lock().lock().expect("env lock");
+
+        let temp_dir = tempfile::tempdir().expect("create tempdir");
+        let mut manager = AgentManager::with_platform(temp_dir.path(), Platform::LinuxX64);
+
+        let bin_dir = temp_dir.path().join("bin");
+        fs::create_dir_all(&bin_dir).expect("create bin dir");
+        write_exec(&bin_dir.join("npx"), "#!/usr/bin/env sh\nexit 0\n");
+
+        let original_path = std::env::var_os("PATH").unwrap_or_default();
+        let mut paths = vec![bin_dir.clone()];
+        paths.extend(std::env::split_paths(&original_path));
+        let combined_path = std::env::join_paths(paths).expect("join PATH");
+        let _path_guard = EnvVarGuard::set("PATH", &combined_path);
+
+        manager
+            .set_pin(
+                AgentId::Pi,
+                AgentPin {
+                    version: None,
+                    agent_process_version: Some("0.4.2".to_string()),
+                },
+            )
+            .expect("set pin");
+        assert_eq!(
+            manager
+                .pin(AgentId::Pi)
+                .and_then(|pin| pin.agent_process_version),
+            Some("0.4.2".to_string())
+        );
+
+        manager.registry_url = serve_registry_once(serde_json::json!({ "agents": [] }));
+        let result = manager
+            .install(AgentId::Pi, InstallOptions::default())
+            .expect("pi install succeeds");
+        let agent_process = result
+            .artifacts
+            .iter()
+            .find(|a| a.kind == InstalledArtifactKind::AgentProcess)
+            .expect("pi agent process artifact");
+        assert_eq!(agent_process.version.as_deref(), Some("0.4.2"));
+        let launcher =
+            fs::read_to_string(manager.agent_process_path(AgentId::Pi)).expect("read pi launcher");
+        assert!(launcher.contains("pi-acp@0.4.2"));
+
+        manager
+            .set_pin(AgentId::Pi, AgentPin::default())
+            .expect("clear pin");
+        assert!(manager.pin(AgentId::Pi).is_none());
+    }
+
+    #[test]
+    fn known_good_prefers_local_record_over_compat_table() {
+        let _env_lock = env_lock().lock().expect("env lock");
+
+        let temp_dir = tempfile::tempdir().expect("create tempdir");
+        let manager = AgentManager::with_platform(temp_dir.path(), Platform::LinuxX64);
+        let table_path = temp_dir.path().join("compat.json");
+        fs::write(
+            &table_path,
+            r#"{"codex": {"version": "0.40.0", "agentProcessVersion": "0.3.0"}}"#,
+        )
+        .expect("write compat table");
+        let _table_guard = EnvVarGuard::set("SANDBOX_AGENT_COMPAT_TABLE", table_path.as_os_str());
+
+        assert!(manager.known_good(AgentId::Claude).is_none());
+        assert_eq!(
+            manager.known_good(AgentId::Codex),
+            Some(AgentPin {
+                version: Some("0.40.0".to_string()),
+                agent_process_version: Some("0.3.0".to_string()),
+            })
+        );
+
+        let recorded = AgentPin {
+            version: Some("0.41.2".to_string()),
+            agent_process_version: None,
+        };
+        manager
+            .record_known_good(AgentId::Codex, recorded.clone())
+            .expect("record known good");
+        assert_eq!(manager.known_good(AgentId::Codex), Some(recorded));
+    }
+
     #[test]
     fn install_cursor_skips_native_and_writes_fallback_npx_launcher() {
         let _env_lock = env_lock().lock().expect("env lock");
@@ -22,6 +22,8 @@ pub enum ErrorType {
     ModeNotSupported,
     StreamError,
     Timeout,
+    PolicyViolation,
+    PayloadTooLarge,
 }
 
 impl ErrorType {
@@ -42,6 +44,8 @@ impl ErrorType {
             Self::ModeNotSupported => "urn:sandbox-agent:error:mode_not_supported",
             Self::StreamError => "urn:sandbox-agent:error:stream_error",
             Self::Timeout => "urn:sandbox-agent:error:timeout",
+            Self::PolicyViolation => "urn:sandbox-agent:error:policy_violation",
+            Self::PayloadTooLarge => "urn:sandbox-agent:error:payload_too_large",
         }
     }
 
@@ -62,6 +66,8 @@ impl ErrorType {
             Self::ModeNotSupported => "Mode Not Supported",
             Self::StreamError => "Stream Error",
             Self::Timeout => "Timeout",
+            Self::PolicyViolation => "Policy Violation",
+            Self::PayloadTooLarge => "Payload Too Large",
         }
     }
 
@@ -82,6 +88,118 @@ impl ErrorType {
             Self::ModeNotSupported => 400,
             Self::StreamError => 502,
             Self::Timeout => 504,
+            Self::PolicyViolation => 422,
+            Self::PayloadTooLarge => 413,
+        }
+    }
+}
+
+/// Stable, machine-readable cause of a failure.
+///
+/// `type` names the broad category and fixes the HTTP status; `code` says
+/// which step failed, so clients can tell an install failure from a spawn
+/// failure or an agent that rejected the request even when both arrive as a
+/// `stream_error`. New codes may be added; existing ones keep their meaning.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, JsonSchema, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    InvalidRequest,
+    Conflict,
+    NotFound,
+    NotAcceptable,
+    UnsupportedMediaType,
+    UnsupportedAgent,
+    ModeNotSupported,
+    PolicyViolation,
+    /// The request body or prompt is over the server's size limit.
+    PayloadTooLarge,
+    /// The agent binary is missing and the server will not install it.
+    AgentNotInstalled,
+    /// Downloading or unpacking the agent failed.
+    InstallFailed,
+    /// The agent process could not be started.
+    SpawnFailed,
+    /// The agent process exited while handling the request.
+    AgentProcessExited,
+    /// The agent process exited because its credentials were rejected.
+    AgentAuthFailed,
+    /// The agent process exited because its provider rate-limited it.
+    AgentRateLimited,
+    /// A payload could not be converted between protocols.
+    ConversionFailed,
+    /// The agent answered with a JSON-RPC error or an unusable result.
+    UpstreamAgentError,
+    /// The agent never answered the request.
+    UpstreamAgentNoResponse,
+    /// The server token was missing or wrong.
+    TokenInvalid,
+    PermissionDenied,
+    Timeout,
+    /// Any other server-side failure, such as a filesystem error.
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::InvalidRequest => "invalid_request",
+            Self::Conflict => "conflict",
+            Self::NotFound => "not_found",
+            Self::NotAcceptable => "not_acceptable",
+            Self::UnsupportedMediaType => "unsupported_media_type",
+            Self::UnsupportedAgent => "unsupported_agent",
+            Self::ModeNotSupported => "mode_not_supported",
+            Self::PolicyViolation => "policy_violation",
+            Self::PayloadTooLarge => "payload_too_large",
+            Self::AgentNotInstalled => "agent_not_installed",
+            Self::InstallFailed => "install_failed",
+            Self::SpawnFailed => "spawn_failed",
+            Self::AgentProcessExited => "agent_process_exited",
+            Self::AgentAuthFailed => "agent_auth_failed",
+            Self::AgentRateLimited => "agent_rate_limited",
+            Self::ConversionFailed => "conversion_failed",
+            Self::UpstreamAgentError => "upstream_agent_error",
+            Self::UpstreamAgentNoResponse => "upstream_agent_no_response",
+            Self::TokenInvalid => "token_invalid",
+            Self::PermissionDenied => "permission_denied",
+            Self::Timeout => "timeout",
+            Self::Internal => "internal",
+        }
+    }
+
+    /// Whether sending the same request again may succeed without changes.
+    pub fn retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::InstallFailed
+                | Self::AgentProcessExited
+                | Self::AgentRateLimited
+                | Self::UpstreamAgentNoResponse
+                | Self::Timeout
+        )
+    }
+}
+
+impl From<&ErrorType> for ErrorCode {
+    fn from(value: &ErrorType) -> Self {
+        match value {
+            ErrorType::InvalidRequest => Self::InvalidRequest,
+            ErrorType::Conflict => Self::Conflict,
+            ErrorType::UnsupportedAgent => Self::UnsupportedAgent,
+            ErrorType::AgentNotInstalled => Self::AgentNotInstalled,
+            ErrorType::InstallFailed => Self::InstallFailed,
+            ErrorType::AgentProcessExited => Self::AgentProcessExited,
+            ErrorType::TokenInvalid => Self::TokenInvalid,
+            ErrorType::PermissionDenied => Self::PermissionDenied,
+            ErrorType::NotAcceptable => Self::NotAcceptable,
+            ErrorType::UnsupportedMediaType => Self::UnsupportedMediaType,
+            ErrorType::SessionNotFound => Self::NotFound,
+            ErrorType::SessionAlreadyExists => Self::Conflict,
+            ErrorType::ModeNotSupported => Self::ModeNotSupported,
+            ErrorType::StreamError => Self::Internal,
+            ErrorType::Timeout => Self::Timeout,
+            ErrorType::PolicyViolation => Self::PolicyViolation,
+            ErrorType::PayloadTooLarge => Self::PayloadTooLarge,
         }
     }
 }
@@ -92,6 +210,8 @@ pub struct ProblemDetails {
     pub type_: String,
     pub title: String,
     pub status: u16,
+    pub code: ErrorCode,
+    pub retryable: bool,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub detail: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -102,10 +222,13 @@ pub struct ProblemDetails {
 
 impl ProblemDetails {
     pub fn new(error_type: ErrorType, detail: Option<String>) -> Self {
+        let code = ErrorCode::from(&error_type);
         Self {
             type_: error_type.as_urn().to_string(),
             title: error_type.title().to_string(),
             status: error_type.status_code(),
+            code,
+            retryable: code.retryable(),
             detail,
             instance: None,
             extensions: Map::new(),
@@ -145,6 +268,8 @@ pub enum SandboxError {
     AgentProcessExited {
         agent: String,
         exit_code: Option<i32>,
+        /// Classified cause, e.g. `auth_failed` or `crashed`.
+        reason: Option<String>,
         stderr: Option<String>,
     },
     #[error("token invalid")]
@@ -165,6 +290,18 @@ pub enum SandboxError {
     StreamError { message: String },
     #[error("timeout")]
     Timeout { message: Option<String> },
+    #[error("policy violation: {message}")]
+    PolicyViolation { message: String, rules: Vec<String> },
+    #[error("payload too large: {message}")]
+    PayloadTooLarge { message: String, limit_bytes: u64 },
+    #[error("failed to start {agent}: {message}")]
+    SpawnFailed { agent: String, message: String },
+    #[error("conversion failed: {message}")]
+    ConversionFailed { message: String },
+    #[error("{message}")]
+    UpstreamAgentError { message: String },
+    #[error("{message}")]
+    UpstreamAgentNoResponse { message: String },
 }
 
 impl SandboxError {
@@ -185,6 +322,28 @@ impl SandboxError {
             Self::ModeNotSupported { .. } => ErrorType::ModeNotSupported,
             Self::StreamError { .. } => ErrorType::StreamError,
             Self::Timeout { .. } => ErrorType::Timeout,
+            Self::PolicyViolation { .. } => ErrorType::PolicyViolation,
+            Self::PayloadTooLarge { .. } => ErrorType::PayloadTooLarge,
+            // Reported as stream errors, as before; `code` tells them apart.
+            Self::SpawnFailed { .. }
+            | Self::ConversionFailed { .. }
+            | Self::UpstreamAgentError { .. }
+            | Self::UpstreamAgentNoResponse { .. } => ErrorType::StreamError,
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::AgentProcessExited { reason, .. } => match reason.as_deref() {
+                Some("auth_failed") => ErrorCode::AgentAuthFailed,
+                Some("rate_limited") => ErrorCode::AgentRateLimited,
+                _ => ErrorCode::AgentProcessExited,
+            },
+            Self::SpawnFailed { .. } => ErrorCode::SpawnFailed,
+            Self::ConversionFailed { .. } => ErrorCode::ConversionFailed,
+            Self::UpstreamAgentError { .. } => ErrorCode::UpstreamAgentError,
+            Self::UpstreamAgentNoResponse { .. } => ErrorCode::UpstreamAgentNoResponse,
+            other => ErrorCode::from(&other.error_type()),
         }
     }
 
@@ -216,6 +375,7 @@ impl SandboxError {
             Self::AgentProcessExited {
                 agent,
                 exit_code,
+                reason,
                 stderr,
             } => {
                 let mut map = Map::new();
@@ -225,6 +385,9 @@ impl SandboxError {
                         Value::Number(serde_json::Number::from(*code as i64)),
                     );
                 }
+                if let Some(reason) = reason {
+                    map.insert("reason".to_string(), Value::String(reason.clone()));
+                }
                 if let Some(stderr) = stderr {
                     map.insert("stderr".to_string(), Value::String(stderr.clone()));
                 }
@@ -284,6 +447,36 @@ impl SandboxError {
                 });
                 (None, None, details)
             }
+            Self::PolicyViolation { message, rules } => {
+                let mut map = Map::new();
+                map.insert("message".to_string(), Value::String(message.clone()));
+                map.insert(
+                    "rules".to_string(),
+                    Value::Array(rules.iter().cloned().map(Value::String).collect()),
+                );
+                (None, None, Some(Value::Object(map)))
+            }
+            Self::PayloadTooLarge {
+                message,
+                limit_bytes,
+            } => {
+                let mut map = Map::new();
+                map.insert("message".to_string(), Value::String(message.clone()));
+                map.insert("limitBytes".to_string(), Value::from(*limit_bytes));
+                (None, None, Some(Value::Object(map)))
+            }
+            Self::SpawnFailed { agent, message } => {
+                let mut map = Map::new();
+                map.insert("message".to_string(), Value::String(message.clone()));
+                (Some(agent.clone()), None, Some(Value::Object(map)))
+            }
+            Self::ConversionFailed { message }
+            | Self::UpstreamAgentError { message }
+            | Self::UpstreamAgentNoResponse { message } => {
+                let mut map = Map::new();
+                map.insert("message".to_string(), Value::String(message.clone()));
+                (None, None, Some(Value::Object(map)))
+            }
         };
 
         AgentError {
@@ -297,6 +490,9 @@ impl SandboxError {
 
     pub fn to_problem_details(&self) -> ProblemDetails {
         let mut problem = ProblemDetails::new(self.error_type(), Some(self.to_string()));
+        let code = self.code();
+        problem.code = code;
+        problem.retryable = code.retryable();
         let agent_error = self.to_agent_error();
 
         let mut extensions = Map::new();
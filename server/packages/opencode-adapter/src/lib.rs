@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::convert::Infallible;
 use std::future::Future;
@@ -18,12 +19,13 @@ use axum::routing::{get, patch, post};
 use axum::{Json, Router};
 use futures::stream;
 use futures::{Stream, StreamExt};
-use sandbox_agent_opencode_server_manager::OpenCodeServerManager;
+use sandbox_agent_error::SandboxError;
+use sandbox_agent_opencode_server_manager::{OpenCodeServerManager, OpenCodeServerRestart};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
 use sqlx::{Row, SqlitePool};
-use tokio::sync::{broadcast, Mutex, OnceCell};
+use tokio::sync::{broadcast, Mutex, OnceCell, RwLock};
 use tokio::time::interval;
 use tracing::warn;
 
@@ -31,6 +33,9 @@ const DEFAULT_REPLAY_MAX_EVENTS: usize = 50;
 const DEFAULT_REPLAY_MAX_CHARS: usize = 12_000;
 const EVENT_LOG_SIZE: usize = 4096;
 const EVENT_CHANNEL_SIZE: usize = 2048;
+const ACP_STREAM_RECONNECT_ATTEMPTS: u32 = 6;
+const ACP_STREAM_RECONNECT_INITIAL_DELAY: Duration = Duration::from_millis(250);
+const ACP_STREAM_RECONNECT_MAX_DELAY: Duration = Duration::from_secs(5);
 const MODEL_CHANGE_ERROR: &str = "OpenCode compatibility currently does not support changing the model after creating a session. Export with /export and load in to a new session.";
 
 // ---------------------------------------------------------------------------
@@ -38,8 +43,9 @@ const MODEL_CHANGE_ERROR: &str = "OpenCode compatibility currently does not supp
 // without depending on the `sandbox-agent` crate (which would be circular).
 // ---------------------------------------------------------------------------
 
-/// Stream of raw JSON-RPC payloads from the ACP agent process.
-pub type AcpPayloadStream = Pin<Box<dyn Stream<Item = Value> + Send>>;
+/// Stream of raw JSON-RPC payloads from the ACP agent process, each paired
+/// with its stream sequence number.
+pub type AcpPayloadStream = Pin<Box<dyn Stream<Item = (u64, Value)> + Send>>;
 
 #[derive(Debug)]
 pub enum AcpDispatchResult {
@@ -64,7 +70,9 @@ pub trait AcpDispatch: Send + Sync + 'static {
 
     /// Open a stream of raw JSON-RPC notification payloads from the agent
     /// process. Each item is a `serde_json::Value` containing a complete
-    /// JSON-RPC message (notification or response).
+    /// JSON-RPC message (notification or response) and its sequence number.
+    /// With `last_event_id`, buffered messages after that sequence are
+    /// replayed first.
     fn notification_stream(
         &self,
         server_id: &str,
@@ -85,6 +93,10 @@ pub struct OpenCodeAdapterConfig {
     pub replay_max_chars: usize,
     pub native_proxy_base_url: Option<String>,
     pub native_proxy_manager: Option<Arc<OpenCodeServerManager>>,
+    /// Headers sent on every request proxied to native OpenCode, e.g. a
+    /// session cookie or API key for a managed deployment. These replace any
+    /// header of the same name forwarded from the incoming request.
+    pub native_proxy_headers: HashMap<String, String>,
     /// Optional ACP dispatch backend. When `Some`, prompts for non-mock agents
     /// are routed through real ACP agent processes instead of the mock handler.
     pub acp_dispatch: Option<Arc<dyn AcpDispatch>>,
@@ -102,6 +114,7 @@ impl Default for OpenCodeAdapterConfig {
             replay_max_chars: DEFAULT_REPLAY_MAX_CHARS,
             native_proxy_base_url: None,
             native_proxy_manager: None,
+            native_proxy_headers: HashMap::new(),
             acp_dispatch: None,
             provider_payload: None,
         }
@@ -112,6 +125,29 @@ impl Default for OpenCodeAdapterConfig {
 struct OpenCodeStreamEvent {
     id: u64,
     payload: Value,
+    /// The ACP envelope this event was translated from, if any.
+    raw: Option<Arc<Value>>,
+}
+
+impl OpenCodeStreamEvent {
+    fn into_sse(self, include_raw: bool) -> Event {
+        let mut payload = self.payload;
+        if include_raw {
+            if let (Some(object), Some(raw)) = (payload.as_object_mut(), self.raw) {
+                object.insert("raw".to_string(), Arc::unwrap_or_clone(raw));
+            }
+        }
+        Event::default()
+            .id(self.id.to_string())
+            .json_data(payload)
+            .unwrap_or_else(|_| Event::default().data("{}"))
+    }
+}
+
+tokio::task_local! {
+    /// The ACP envelope an `acp_sse_translation_task` is translating, so
+    /// events it emits can carry their source for `includeRaw` subscribers.
+    static ACP_SOURCE: RefCell<Option<Arc<Value>>>;
 }
 
 #[derive(Clone, Debug)]
@@ -148,6 +184,25 @@ struct SessionMeta {
     last_connection_id: String,
     session_init_json: Option<Value>,
     destroyed_at: Option<i64>,
+    /// Native agent session id to attach with ACP `session/load` on the first
+    /// prompt instead of creating a fresh session.
+    #[serde(default)]
+    resume_session_id: Option<String>,
+    /// Replaces the agent's default system prompt for every turn.
+    #[serde(default)]
+    system_prompt: Option<String>,
+    /// Appended to the agent's default system prompt for every turn.
+    #[serde(default)]
+    append_instructions: Option<String>,
+    /// Tools auto-approved without asking the client.
+    #[serde(default)]
+    tool_allow: Vec<String>,
+    /// Tools auto-rejected without asking the client. Wins over `tool_allow`.
+    #[serde(default)]
+    tool_deny: Vec<String>,
+    /// Drop agent thought chunks instead of emitting `reasoning` parts.
+    #[serde(default)]
+    hide_reasoning: bool,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -176,10 +231,13 @@ struct AdapterState {
     sqlite_path: String,
     sqlite_connect_options: SqliteConnectOptions,
     proxy_http_client: reqwest::Client,
+    proxy_headers: reqwest::header::HeaderMap,
     pool: OnceCell<SqlitePool>,
     initialized: OnceCell<()>,
     project_id: String,
-    projection: Mutex<Projection>,
+    /// Read by every GET handler and written by event recording. Guards are
+    /// never held across an await, so writers only block for in-memory updates.
+    projection: RwLock<Projection>,
     pending_replay: Mutex<HashMap<String, String>>,
     agent_connections: Mutex<HashMap<String, String>>,
     event_broadcaster: broadcast::Sender<OpenCodeStreamEvent>,
@@ -300,7 +358,7 @@ impl AdapterState {
             apply_envelope(&mut projection, &session_id, &sender, &payload);
         }
 
-        let mut guard = self.projection.lock().await;
+        let mut guard = self.projection.write().await;
         *guard = projection;
         Ok(())
     }
@@ -309,6 +367,10 @@ impl AdapterState {
         let event = OpenCodeStreamEvent {
             id: self.next_event_id.fetch_add(1, Ordering::Relaxed),
             payload,
+            raw: ACP_SOURCE
+                .try_with(|source| source.borrow().clone())
+                .ok()
+                .flatten(),
         };
 
         if let Ok(mut guard) = self.event_log.lock() {
@@ -445,7 +507,7 @@ impl AdapterState {
         let id = format!("evt_{}", self.next_id(""));
         let created_at = now_ms();
         let connection_id = {
-            let projection = self.projection.lock().await;
+            let projection = self.projection.read().await;
             projection
                 .sessions
                 .get(session_id)
@@ -466,7 +528,7 @@ impl AdapterState {
         .await
         .map_err(|err| err.to_string())?;
 
-        let mut projection = self.projection.lock().await;
+        let mut projection = self.projection.write().await;
         apply_envelope(&mut projection, session_id, sender, payload);
 
         Ok(())
@@ -513,7 +575,7 @@ impl AdapterState {
 
     async fn maybe_restore_session(&self, session_id: &str) -> Result<(), String> {
         let (agent, stale) = {
-            let projection = self.projection.lock().await;
+            let projection = self.projection.read().await;
             let Some(state) = projection.sessions.get(session_id) else {
                 return Ok(());
             };
@@ -559,7 +621,7 @@ impl AdapterState {
 
         let mut updated_meta = None;
         {
-            let mut projection = self.projection.lock().await;
+            let mut projection = self.projection.write().await;
             if let Some(session) = projection.sessions.get_mut(session_id) {
                 session.meta.agent_session_id = new_agent_session_id;
                 session.meta.last_connection_id = current;
@@ -587,7 +649,7 @@ impl AdapterState {
         directory: String,
     ) -> Result<SessionMeta, String> {
         {
-            let projection = self.projection.lock().await;
+            let projection = self.projection.read().await;
             if let Some(existing) = projection.sessions.get(session_id) {
                 return Ok(existing.meta.clone());
             }
@@ -614,13 +676,19 @@ impl AdapterState {
             last_connection_id: connection_id,
             session_init_json: Some(json!({"cwd": "/", "mcpServers": []})),
             destroyed_at: None,
+            resume_session_id: None,
+            system_prompt: None,
+            append_instructions: None,
+            tool_allow: Vec::new(),
+            tool_deny: Vec::new(),
+            hide_reasoning: false,
         };
 
         self.persist_session(&meta).await?;
 
         let session_value = session_to_value(&meta);
         {
-            let mut projection = self.projection.lock().await;
+            let mut projection = self.projection.write().await;
             projection.sessions.insert(
                 session_id.to_string(),
                 SessionState {
@@ -641,12 +709,15 @@ impl AdapterState {
     }
 }
 
-pub fn build_opencode_router(config: OpenCodeAdapterConfig) -> Result<Router, String> {
+/// Resolve proxy and persistence settings from `config` and the environment
+/// and build the shared adapter state. Nothing is opened until first use.
+fn build_adapter_state(config: OpenCodeAdapterConfig) -> Result<Arc<AdapterState>, String> {
     let proxy_base_url = config
         .native_proxy_base_url
         .clone()
         .or_else(|| std::env::var("OPENCODE_COMPAT_PROXY_URL").ok())
         .and_then(normalize_proxy_base_url);
+    let proxy_headers = proxy_default_headers(&config.native_proxy_headers)?;
     let config = OpenCodeAdapterConfig {
         native_proxy_base_url: proxy_base_url,
         ..config
@@ -671,7 +742,7 @@ pub fn build_opencode_router(config: OpenCodeAdapterConfig) -> Result<Router, St
 
     let (event_broadcaster, _) = broadcast::channel(EVENT_CHANNEL_SIZE);
 
-    let state = Arc::new(AdapterState {
+    Ok(Arc::new(AdapterState {
         config,
         sqlite_path,
         sqlite_connect_options: connect,
@@ -679,10 +750,11 @@ pub fn build_opencode_router(config: OpenCodeAdapterConfig) -> Result<Router, St
             .timeout(Duration::from_secs(10))
             .build()
             .unwrap_or_else(|_| reqwest::Client::new()),
+        proxy_headers,
         pool: OnceCell::new(),
         initialized: OnceCell::new(),
         project_id: format!("proj_{}", now_ms()),
-        projection: Mutex::new(Projection::default()),
+        projection: RwLock::new(Projection::default()),
         pending_replay: Mutex::new(HashMap::new()),
         agent_connections: Mutex::new(HashMap::new()),
         event_broadcaster,
@@ -692,7 +764,21 @@ pub fn build_opencode_router(config: OpenCodeAdapterConfig) -> Result<Router, St
         acp_initialized: Mutex::new(HashMap::new()),
         acp_request_ids: Mutex::new(HashMap::new()),
         last_user_message_id: Mutex::new(HashMap::new()),
-    });
+    }))
+}
+
+pub fn build_opencode_router(config: OpenCodeAdapterConfig) -> Result<Router, String> {
+    let state = build_adapter_state(config)?;
+
+    if let (Some(manager), Ok(runtime)) = (
+        state.config.native_proxy_manager.as_ref(),
+        tokio::runtime::Handle::try_current(),
+    ) {
+        runtime.spawn(forward_sidecar_restarts(
+            manager.subscribe_restarts(),
+            Arc::downgrade(&state),
+        ));
+    }
 
     let mut router = Router::new()
         .route("/agent", get(oc_agent_list))
@@ -700,6 +786,7 @@ pub fn build_opencode_router(config: OpenCodeAdapterConfig) -> Result<Router, St
         .route("/config", get(oc_config_get).patch(oc_config_patch))
         .route("/config/providers", get(oc_config_providers))
         .route("/event", get(oc_event_subscribe))
+        .route("/event/subscribe", get(oc_event_subscribe))
         .route("/global/event", get(oc_global_event))
         .route("/global/health", get(oc_global_health))
         .route(
@@ -756,6 +843,7 @@ pub fn build_opencode_router(config: OpenCodeAdapterConfig) -> Result<Router, St
             "/session/:sessionID/message/:messageID/part/:partID",
             patch(oc_part_update).delete(oc_part_delete),
         )
+        .route("/session/:sessionID/prompt", post(oc_session_prompt))
         .route(
             "/session/:sessionID/prompt_async",
             post(oc_session_prompt_async),
@@ -819,6 +907,15 @@ struct DirectoryQuery {
     directory: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EventQuery {
+    directory: Option<String>,
+    /// Attach the ACP envelope each event was translated from as `raw`.
+    #[serde(default)]
+    include_raw: bool,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct SessionCreateBody {
@@ -828,6 +925,30 @@ struct SessionCreateBody {
     permission: Option<Value>,
     #[serde(alias = "permission_mode")]
     permission_mode: Option<String>,
+    resume: Option<SessionResumeBody>,
+    #[serde(alias = "system_prompt")]
+    system_prompt: Option<String>,
+    #[serde(alias = "append_instructions")]
+    append_instructions: Option<String>,
+    tools: Option<SessionToolsBody>,
+    #[serde(default, alias = "hide_reasoning")]
+    hide_reasoning: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SessionToolsBody {
+    #[serde(default)]
+    allow: Vec<String>,
+    #[serde(default)]
+    deny: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionResumeBody {
+    agent: Option<String>,
+    #[serde(alias = "agentSessionID", alias = "agent_session_id")]
+    agent_session_id: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -984,8 +1105,9 @@ async fn oc_config_providers(State(state): State<Arc<AdapterState>>) -> Response
 async fn oc_event_subscribe(
     State(state): State<Arc<AdapterState>>,
     headers: HeaderMap,
-    Query(query): Query<DirectoryQuery>,
+    Query(query): Query<EventQuery>,
 ) -> Sse<impl futures::Stream<Item = Result<Event, Infallible>>> {
+    let include_raw = query.include_raw;
     let _ = state.ensure_initialized().await;
 
     let directory = resolve_directory(&headers, query.directory.as_ref());
@@ -1003,13 +1125,9 @@ async fn oc_event_subscribe(
             VecDeque::from(replay),
             interval(Duration::from_secs(30)),
         ),
-        |(mut rx, mut replay, mut ticker)| async move {
+        move |(mut rx, mut replay, mut ticker)| async move {
             if let Some(item) = replay.pop_front() {
-                let evt = Event::default()
-                    .id(item.id.to_string())
-                    .json_data(item.payload)
-                    .unwrap_or_else(|_| Event::default().data("{}"));
-                return Some((Ok(evt), (rx, replay, ticker)));
+                return Some((Ok(item.into_sse(include_raw)), (rx, replay, ticker)));
             }
 
             loop {
@@ -1021,12 +1139,8 @@ async fn oc_event_subscribe(
                     }
                     item = rx.recv() => {
                         match item {
-                            Ok(payload) => {
-                                let evt = Event::default()
-                                    .id(payload.id.to_string())
-                                    .json_data(payload.payload)
-                                    .unwrap_or_else(|_| Event::default().data("{}"));
-                                return Some((Ok(evt), (rx, replay, ticker)));
+                            Ok(item) => {
+                                return Some((Ok(item.into_sse(include_raw)), (rx, replay, ticker)));
                             }
                             Err(broadcast::error::RecvError::Lagged(_)) => continue,
                             Err(broadcast::error::RecvError::Closed) => return None,
@@ -1043,7 +1157,7 @@ async fn oc_event_subscribe(
 async fn oc_global_event(
     State(state): State<Arc<AdapterState>>,
     headers: HeaderMap,
-    Query(query): Query<DirectoryQuery>,
+    Query(query): Query<EventQuery>,
 ) -> Sse<impl futures::Stream<Item = Result<Event, Infallible>>> {
     oc_event_subscribe(State(state), headers, Query(query)).await
 }
@@ -1462,14 +1576,61 @@ async fn oc_session_create(
         parent_id: None,
         permission: None,
         permission_mode: None,
+        resume: None,
+        system_prompt: None,
+        append_instructions: None,
+        tools: None,
+        hide_reasoning: false,
     });
 
     let id = state.next_id("ses_");
     let now = now_ms();
     let directory = resolve_directory(&headers, query.directory.as_ref());
 
-    let default_agent = "mock";
-    let connection_id = state.current_connection_for_agent(default_agent).await;
+    // A resumed session is pinned to the agent that owns the native session;
+    // everything else starts on the mock agent until the first prompt picks one.
+    let (agent, provider_id, model_id, resume_session_id) = match body.resume {
+        Some(resume) => {
+            if resume.agent_session_id.trim().is_empty() {
+                return bad_request("resume.agentSessionId is required");
+            }
+            let Some(agent) = resume.agent.filter(|agent| agent != "mock") else {
+                return bad_request("resume.agent is required when resuming a session");
+            };
+            if provider_to_agent(&agent) != agent {
+                return bad_request(&format!(
+                    "agent '{agent}' does not support resuming sessions"
+                ));
+            }
+            let model_id = default_model_for_provider(&agent).unwrap_or("default");
+            (
+                agent.clone(),
+                agent,
+                model_id.to_string(),
+                Some(resume.agent_session_id),
+            )
+        }
+        None => (
+            "mock".to_string(),
+            "mock".to_string(),
+            default_model_for_provider("mock")
+                .unwrap_or("default")
+                .to_string(),
+            None,
+        ),
+    };
+    let tools = body.tools.unwrap_or_default();
+    if let Some(tool) = tools.allow.iter().find(|tool| tools.deny.contains(tool)) {
+        return sandbox_error(&SandboxError::ModeNotSupported {
+            agent: agent.clone(),
+            mode: format!("tools.allow and tools.deny both list '{tool}'"),
+        });
+    }
+    if let Err(err) = tool_launch_meta(&agent, &tools.allow, &tools.deny) {
+        return sandbox_error(&err);
+    }
+
+    let connection_id = state.current_connection_for_agent(&agent).await;
     let meta = SessionMeta {
         id: id.clone(),
         slug: format!("session-{id}"),
@@ -1482,15 +1643,19 @@ async fn oc_session_create(
         updated_at: now,
         share_url: None,
         permission_mode: body.permission_mode,
-        agent: default_agent.to_string(),
-        provider_id: default_agent.to_string(),
-        model_id: default_model_for_provider(default_agent)
-            .unwrap_or("default")
-            .to_string(),
+        agent,
+        provider_id,
+        model_id,
         agent_session_id: format!("acp_{}", state.next_id("ses_")),
         last_connection_id: connection_id,
         session_init_json: Some(json!({"cwd": "/", "mcpServers": []})),
         destroyed_at: None,
+        resume_session_id,
+        system_prompt: non_empty(body.system_prompt),
+        append_instructions: non_empty(body.append_instructions),
+        tool_allow: tools.allow,
+        tool_deny: tools.deny,
+        hide_reasoning: body.hide_reasoning,
     };
 
     if let Err(err) = state.persist_session(&meta).await {
@@ -1498,7 +1663,7 @@ async fn oc_session_create(
     }
 
     {
-        let mut projection = state.projection.lock().await;
+        let mut projection = state.projection.write().await;
         projection.sessions.insert(
             id,
             SessionState {
@@ -1521,7 +1686,7 @@ async fn oc_session_list(State(state): State<Arc<AdapterState>>) -> Response {
         return internal_error(err);
     }
 
-    let projection = state.projection.lock().await;
+    let projection = state.projection.read().await;
     let mut values = projection
         .sessions
         .values()
@@ -1544,7 +1709,7 @@ async fn oc_session_get(
         return internal_error(err);
     }
 
-    let projection = state.projection.lock().await;
+    let projection = state.projection.read().await;
     let Some(session) = projection.sessions.get(&session_id) else {
         return not_found("Session not found");
     };
@@ -1566,7 +1731,7 @@ async fn oc_session_update(
     }
 
     let meta = {
-        let mut projection = state.projection.lock().await;
+        let mut projection = state.projection.write().await;
         let Some(session) = projection.sessions.get_mut(&session_id) else {
             return not_found("Session not found");
         };
@@ -1597,7 +1762,7 @@ async fn oc_session_delete(
     }
 
     let removed = {
-        let mut projection = state.projection.lock().await;
+        let mut projection = state.projection.write().await;
         projection.permissions.retain(|_, value| {
             value
                 .get("sessionID")
@@ -1659,7 +1824,7 @@ async fn oc_session_status(State(state): State<Arc<AdapterState>>) -> Response {
     if let Err(err) = state.ensure_initialized().await {
         return internal_error(err);
     }
-    let projection = state.projection.lock().await;
+    let projection = state.projection.read().await;
     let mut map = serde_json::Map::new();
     for (id, session) in &projection.sessions {
         map.insert(id.clone(), json!({"type": session.status}));
@@ -1677,7 +1842,7 @@ async fn oc_session_abort(
 
     let mut should_emit_idle = false;
     {
-        let mut projection = state.projection.lock().await;
+        let mut projection = state.projection.write().await;
         let Some(session) = projection.sessions.get_mut(&session_id) else {
             return not_found("Session not found");
         };
@@ -1704,7 +1869,7 @@ async fn oc_session_abort(
     // Send session/cancel to the ACP agent if dispatch is available.
     if let Some(dispatch) = state.config.acp_dispatch.as_ref() {
         let agent_session_id = {
-            let projection = state.projection.lock().await;
+            let projection = state.projection.read().await;
             projection
                 .sessions
                 .get(&session_id)
@@ -1770,7 +1935,7 @@ async fn oc_session_init(
     let model_id = body.model_id.unwrap_or_else(|| "mock".to_string());
 
     let meta = {
-        let mut projection = state.projection.lock().await;
+        let mut projection = state.projection.write().await;
         let Some(session) = projection.sessions.get_mut(&session_id) else {
             return not_found("Session not found");
         };
@@ -1805,7 +1970,7 @@ async fn oc_session_fork(
     }
 
     let parent = {
-        let projection = state.projection.lock().await;
+        let projection = state.projection.read().await;
         projection.sessions.get(&session_id).cloned()
     };
     let Some(parent) = parent else {
@@ -1836,6 +2001,12 @@ async fn oc_session_fork(
         last_connection_id: connection_id,
         session_init_json: parent.meta.session_init_json.clone(),
         destroyed_at: None,
+        resume_session_id: None,
+        system_prompt: parent.meta.system_prompt.clone(),
+        append_instructions: parent.meta.append_instructions.clone(),
+        tool_allow: parent.meta.tool_allow.clone(),
+        tool_deny: parent.meta.tool_deny.clone(),
+        hide_reasoning: parent.meta.hide_reasoning,
     };
 
     if let Err(err) = state.persist_session(&meta).await {
@@ -1843,7 +2014,7 @@ async fn oc_session_fork(
     }
 
     {
-        let mut projection = state.projection.lock().await;
+        let mut projection = state.projection.write().await;
         projection.sessions.insert(
             id.clone(),
             SessionState {
@@ -1884,7 +2055,7 @@ async fn oc_session_messages(
         return internal_error(err);
     }
 
-    let projection = state.projection.lock().await;
+    let projection = state.projection.read().await;
     let Some(session) = projection.sessions.get(&session_id) else {
         return not_found("Session not found");
     };
@@ -1922,7 +2093,7 @@ async fn oc_session_prompt(
     }
 
     let has_messages = {
-        let projection = state.projection.lock().await;
+        let projection = state.projection.read().await;
         projection
             .sessions
             .get(&session_id)
@@ -1945,6 +2116,9 @@ async fn oc_session_prompt(
         }
         meta.agent = agent.clone();
     }
+    if let Err(err) = tool_launch_meta(&meta.agent, &meta.tool_allow, &meta.tool_deny) {
+        return sandbox_error(&err);
+    }
 
     let parts_input = body.parts.unwrap_or_default();
     if parts_input.is_empty() {
@@ -1952,7 +2126,7 @@ async fn oc_session_prompt(
     }
 
     if let Some(session_mode) = {
-        let projection = state.projection.lock().await;
+        let projection = state.projection.read().await;
         projection
             .sessions
             .get(&session_id)
@@ -1962,7 +2136,7 @@ async fn oc_session_prompt(
     }
 
     {
-        let mut projection = state.projection.lock().await;
+        let mut projection = state.projection.write().await;
         if let Some(session) = projection.sessions.get_mut(&session_id) {
             session.meta.agent = meta.agent.clone();
             session.meta.provider_id = meta.provider_id.clone();
@@ -1984,7 +2158,7 @@ async fn oc_session_prompt(
     // agent_session_id (e.g. when the agent changed from "mock" to a real agent
     // and the connection_id differs).
     {
-        let projection = state.projection.lock().await;
+        let projection = state.projection.read().await;
         if let Some(session) = projection.sessions.get(&session_id) {
             meta = session.meta.clone();
         }
@@ -2085,6 +2259,17 @@ async fn oc_session_prompt(
                 tracing::info!(server_id = %server_id, "bootstrapping ACP session (initialize + session/new)");
                 // 1) initialize
                 let init_id = state.next_id("oc_rpc_");
+                let mut launch_meta = json!({ "agent": meta.agent.clone() });
+                match tool_launch_meta(&meta.agent, &meta.tool_allow, &meta.tool_deny) {
+                    Ok(Some(permission_mode)) => {
+                        launch_meta["permissionMode"] = permission_mode;
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        let _ = set_session_status(&state, &session_id, "idle").await;
+                        return sandbox_error(&err);
+                    }
+                }
                 let init_payload = json!({
                     "jsonrpc": "2.0",
                     "id": init_id,
@@ -2097,13 +2282,11 @@ async fn oc_session_prompt(
                             "version": "0.1.0"
                         },
                         "_meta": {
-                            "sandboxagent.dev": {
-                                "agent": meta.agent.clone()
-                            }
+                            "sandboxagent.dev": launch_meta
                         }
                     }
                 });
-                match dispatch
+                let supports_load = match dispatch
                     .post(&server_id, Some(&meta.agent), init_payload)
                     .await
                 {
@@ -2114,54 +2297,83 @@ async fn oc_session_prompt(
                             return internal_error(format!("ACP initialize error: {err}"));
                         }
                         tracing::info!(server_id = %server_id, "ACP initialize succeeded");
+                        resp.pointer("/result/agentCapabilities/loadSession")
+                            .and_then(Value::as_bool)
+                            .unwrap_or(false)
                     }
                     Ok(AcpDispatchResult::Accepted) => {
                         tracing::info!(server_id = %server_id, "ACP initialize accepted");
+                        false
                     }
                     Err(err) => {
                         let _ = set_session_status(&state, &session_id, "idle").await;
                         return internal_error(format!("ACP initialize failed: {err}"));
                     }
-                }
+                };
 
-                // 2) session/new
+                // 2) session/new, or session/load when resuming a native session
+                if meta.resume_session_id.is_some() && !supports_load {
+                    let _ = set_session_status(&state, &session_id, "idle").await;
+                    return bad_request(&format!(
+                        "agent '{}' does not support resuming sessions",
+                        meta.agent
+                    ));
+                }
                 let new_id = state.next_id("oc_rpc_");
-                let new_payload = json!({
-                    "jsonrpc": "2.0",
-                    "id": new_id,
-                    "method": "session/new",
-                    "params": {
-                        "cwd": directory,
-                        "mcpServers": [],
-                        "_meta": {
-                            "sandboxagent.dev": {
-                                "model": meta.model_id.clone()
+                let (new_method, new_payload) = match meta.resume_session_id.as_ref() {
+                    Some(resume_session_id) => (
+                        "session/load",
+                        json!({
+                            "jsonrpc": "2.0",
+                            "id": new_id,
+                            "method": "session/load",
+                            "params": {
+                                "sessionId": resume_session_id,
+                                "cwd": directory,
+                                "mcpServers": [],
+                                "_meta": session_new_meta(&meta)
                             }
-                        }
-                    }
-                });
+                        }),
+                    ),
+                    None => (
+                        "session/new",
+                        json!({
+                            "jsonrpc": "2.0",
+                            "id": new_id,
+                            "method": "session/new",
+                            "params": {
+                                "cwd": directory,
+                                "mcpServers": [],
+                                "_meta": session_new_meta(&meta)
+                            }
+                        }),
+                    ),
+                };
                 let acp_session_id = match dispatch.post(&server_id, None, new_payload).await {
                     Ok(AcpDispatchResult::Response(ref resp)) => {
                         if let Some(err) = resp.get("error") {
-                            tracing::error!(server_id = %server_id, error = %err, "ACP session/new returned JSON-RPC error");
+                            tracing::error!(server_id = %server_id, error = %err, method = new_method, "ACP session bootstrap returned JSON-RPC error");
                             let _ = set_session_status(&state, &session_id, "idle").await;
-                            return internal_error(format!("ACP session/new error: {err}"));
+                            return internal_error(format!("ACP {new_method} error: {err}"));
                         }
+                        // session/load has no sessionId in its result; the
+                        // resumed native id is the ACP session id.
                         let sid = resp
                             .pointer("/result/sessionId")
                             .and_then(Value::as_str)
-                            .unwrap_or("")
-                            .to_string();
-                        tracing::info!(server_id = %server_id, acp_session_id = %sid, "ACP session/new succeeded");
+                            .map(ToOwned::to_owned)
+                            .or_else(|| meta.resume_session_id.clone())
+                            .unwrap_or_default();
+                        tracing::info!(server_id = %server_id, acp_session_id = %sid, method = new_method, "ACP session bootstrap succeeded");
                         sid
                     }
                     Ok(AcpDispatchResult::Accepted) => {
-                        tracing::info!(server_id = %server_id, "ACP session/new accepted");
-                        String::new()
+                        tracing::info!(server_id = %server_id, method = new_method, "ACP session bootstrap accepted");
+                        meta.resume_session_id.clone().unwrap_or_default()
                     }
                     Err(err) => {
                         let _ = set_session_status(&state, &session_id, "idle").await;
-                        return internal_error(format!("ACP session/new failed: {err}"));
+                        return internal_error(format!("ACP {new_method} failed: {err}"));
                     }
                 };
 
@@ -2174,14 +2386,24 @@ async fn oc_session_prompt(
                         let agent_for_task = meta.agent.clone();
                         let provider_for_task = meta.provider_id.clone();
                         let model_for_task = meta.model_id.clone();
-                        tokio::spawn(acp_sse_translation_task(
-                            state_for_task,
+                        let stream = resumable_acp_stream(
+                            state.clone(),
+                            dispatch.clone(),
+                            server_id.clone(),
+                            session_id.clone(),
                             stream,
-                            session_id_for_task,
-                            directory_for_task,
-                            agent_for_task,
-                            provider_for_task,
-                            model_for_task,
+                        );
+                        tokio::spawn(ACP_SOURCE.scope(
+                            RefCell::new(None),
+                            acp_sse_translation_task(
+                                state_for_task,
+                                stream,
+                                session_id_for_task,
+                                directory_for_task,
+                                agent_for_task,
+                                provider_for_task,
+                                model_for_task,
+                            ),
                         ));
                     }
                     Err(err) => {
@@ -2270,7 +2492,7 @@ async fn oc_session_prompt(
         .to_string();
 
     let auto_allow = {
-        let projection = state.projection.lock().await;
+        let projection = state.projection.read().await;
         projection
             .sessions
             .get(&session_id)
@@ -2518,7 +2740,7 @@ async fn oc_session_prompt(
         return internal_error(err);
     }
 
-    let projection = state.projection.lock().await;
+    let projection = state.projection.read().await;
     let parts = projection
         .sessions
         .get(&session_id)
@@ -2549,7 +2771,7 @@ async fn oc_session_message_get(
         return internal_error(err);
     }
 
-    let projection = state.projection.lock().await;
+    let projection = state.projection.read().await;
     let Some(session) = projection.sessions.get(&session_id) else {
         return not_found("Session not found");
     };
@@ -2587,7 +2809,7 @@ async fn oc_part_update(
     }
 
     {
-        let mut projection = state.projection.lock().await;
+        let mut projection = state.projection.write().await;
         if let Some(session) = projection.sessions.get_mut(&session_id) {
             if let Some(message) = session.messages.iter_mut().find(|record| {
                 record.info.get("id").and_then(Value::as_str) == Some(message_id.as_str())
@@ -2624,7 +2846,7 @@ async fn oc_part_delete(
     }
 
     {
-        let mut projection = state.projection.lock().await;
+        let mut projection = state.projection.write().await;
         if let Some(session) = projection.sessions.get_mut(&session_id) {
             if let Some(message) = session.messages.iter_mut().find(|record| {
                 record.info.get("id").and_then(Value::as_str) == Some(message_id.as_str())
@@ -2690,7 +2912,7 @@ async fn oc_permission_reply(
 
     let reply = body.reply.unwrap_or_else(|| "once".to_string());
     let session_id = {
-        let projection = state.projection.lock().await;
+        let projection = state.projection.read().await;
         projection
             .permissions
             .get(&request_id)
@@ -2715,7 +2937,7 @@ async fn oc_permission_list(State(state): State<Arc<AdapterState>>) -> Response
         return internal_error(err);
     }
 
-    let projection = state.projection.lock().await;
+    let projection = state.projection.read().await;
     let mut values = projection.permissions.values().cloned().collect::<Vec<_>>();
     values.sort_by(|a, b| {
         let a_id = a.get("id").and_then(Value::as_str).unwrap_or_default();
@@ -2730,7 +2952,7 @@ async fn oc_question_list(State(state): State<Arc<AdapterState>>) -> Response {
         return internal_error(err);
     }
 
-    let projection = state.projection.lock().await;
+    let projection = state.projection.read().await;
     let mut values = projection.questions.values().cloned().collect::<Vec<_>>();
     values.sort_by(|a, b| {
         let a_id = a.get("id").and_then(Value::as_str).unwrap_or_default();
@@ -2750,7 +2972,7 @@ async fn oc_question_reply(
     }
 
     let session_id = {
-        let projection = state.projection.lock().await;
+        let projection = state.projection.read().await;
         projection
             .questions
             .get(&request_id)
@@ -2771,7 +2993,7 @@ async fn oc_question_reply(
     if let Some(pending) = &pending {
         if let Some(dispatch) = state.config.acp_dispatch.as_ref() {
             let agent_session_id = {
-                let projection = state.projection.lock().await;
+                let projection = state.projection.read().await;
                 projection
                     .sessions
                     .get(&session_id)
@@ -2831,7 +3053,7 @@ async fn oc_question_reject(
     }
 
     let session_id = {
-        let projection = state.projection.lock().await;
+        let projection = state.projection.read().await;
         projection
             .questions
             .get(&request_id)
@@ -2850,7 +3072,7 @@ async fn oc_question_reject(
     if let Some(pending) = &pending {
         if let Some(dispatch) = state.config.acp_dispatch.as_ref() {
             let agent_session_id = {
-                let projection = state.projection.lock().await;
+                let projection = state.projection.read().await;
                 projection
                     .sessions
                     .get(&session_id)
@@ -2938,7 +3160,7 @@ async fn resolve_permission_inner(
     if let Some(pending) = &pending {
         if let Some(dispatch) = state.config.acp_dispatch.as_ref() {
             let agent_session_id = {
-                let projection = state.projection.lock().await;
+                let projection = state.projection.read().await;
                 projection
                     .sessions
                     .get(session_id)
@@ -2987,7 +3209,7 @@ async fn resolve_permission_inner(
     }));
 
     if reply == "always" {
-        let mut projection = state.projection.lock().await;
+        let mut projection = state.projection.write().await;
         if let Some(session) = projection.sessions.get_mut(session_id) {
             session.always_permissions.insert("execute".to_string());
         }
@@ -3002,7 +3224,7 @@ async fn set_session_status(
     status: &str,
 ) -> Result<(), String> {
     let updated_meta = {
-        let mut projection = state.projection.lock().await;
+        let mut projection = state.projection.write().await;
         let Some(session) = projection.sessions.get_mut(session_id) else {
             return Err(format!("session '{session_id}' not found"));
         };
@@ -3395,6 +3617,30 @@ fn build_completed_assistant_message(
     })
 }
 
+/// Copy token counts and cost from a `session/prompt` response's `usage`
+/// (or `_meta.usage`) into an assistant message's `tokens` and `cost`.
+fn apply_prompt_usage(info: &mut Value, response: &Value) {
+    let Some(usage) = response
+        .pointer("/result/usage")
+        .or_else(|| response.pointer("/result/_meta/usage"))
+    else {
+        return;
+    };
+    let count = |key: &str| usage.get(key).and_then(Value::as_u64).unwrap_or(0);
+    info["tokens"] = json!({
+        "input": count("inputTokens"),
+        "output": count("outputTokens"),
+        "reasoning": count("thoughtTokens"),
+        "cache": {
+            "read": count("cachedReadTokens"),
+            "write": count("cachedWriteTokens"),
+        },
+    });
+    if let Some(amount) = usage.pointer("/cost/amount").and_then(Value::as_f64) {
+        info["cost"] = json!(amount);
+    }
+}
+
 /// Wrap a message info Value into a `message.updated` SSE event, matching
 /// the reference OpenCode format which includes `sessionID` at the
 /// `properties` level alongside `info`.
@@ -3659,7 +3905,7 @@ fn runtime_unique_seed() -> u64 {
 
 async fn acp_sse_translation_task(
     state: Arc<AdapterState>,
-    mut stream: AcpPayloadStream,
+    mut stream: Pin<Box<dyn Stream<Item = Value> + Send>>,
     session_id: String,
     directory: String,
     agent: String,
@@ -3671,11 +3917,12 @@ async fn acp_sse_translation_task(
     // Running assistant message ID (set on first update, used to group parts).
     let mut assistant_message_id: Option<String> = None;
     let mut part_counter: u64 = 0;
-    // Accumulated text for the current streaming text part.
-    let mut text_accum = String::new();
-    let mut text_part_id: Option<String> = None;
+    let mut text_part = StreamingTextPart::default();
+    let mut tool_parts = ToolCallParts::default();
 
     while let Some(payload) = stream.next().await {
+        let _ =
+            ACP_SOURCE.try_with(|source| *source.borrow_mut() = Some(Arc::new(payload.clone())));
         // Determine whether this is a notification (no `id`) or a response.
         let method = payload.get("method").and_then(Value::as_str);
         let has_result = payload.get("result").is_some();
@@ -3716,8 +3963,8 @@ async fn acp_sse_translation_task(
                     &session_id,
                     msg_id,
                     &mut part_counter,
-                    &mut text_accum,
-                    &mut text_part_id,
+                    &mut text_part,
+                    &mut tool_parts,
                     &directory,
                     &agent,
                     &provider_id,
@@ -3729,8 +3976,36 @@ async fn acp_sse_translation_task(
 
             // --- Permission request from agent ---
             Some("session/request_permission") => {
-                let request_id = state.next_id("perm_");
                 let params = payload.get("params").cloned().unwrap_or(json!({}));
+                let policy = {
+                    let projection = state.projection.read().await;
+                    projection.sessions.get(&session_id).and_then(|session| {
+                        tool_policy_decision(&session.meta, &params)
+                            .map(|kind| (kind, session.meta.agent_session_id.clone()))
+                    })
+                };
+                if let (Some((option_kind, server_id)), Some(jrpc_id)) =
+                    (policy, jsonrpc_id.clone())
+                {
+                    if let Some(dispatch) = state.config.acp_dispatch.as_ref() {
+                        let response = json!({
+                            "jsonrpc": "2.0",
+                            "id": jrpc_id,
+                            "result": {
+                                "outcome": "selected",
+                                "selectedOption": {
+                                    "kind": option_kind
+                                }
+                            }
+                        });
+                        if let Err(err) = dispatch.post(&server_id, None, response).await {
+                            warn!(?err, "failed to answer permission request from tool policy");
+                        }
+                        continue;
+                    }
+                }
+
+                let request_id = state.next_id("perm_");
                 let permission_request = json!({
                     "id": request_id,
                     "sessionID": session_id,
@@ -3841,25 +4116,8 @@ async fn acp_sse_translation_task(
                 }
 
                 // Persist any remaining accumulated text part.
-                if let Some(tid) = text_part_id.take() {
-                    let msg_id = assistant_message_id.as_deref().unwrap_or("");
-                    let part = json!({
-                        "id": tid,
-                        "sessionID": session_id,
-                        "messageID": msg_id,
-                        "type": "text",
-                        "text": text_accum,
-                    });
-                    let env = json!({
-                        "jsonrpc":"2.0",
-                        "method":"_sandboxagent/opencode/message",
-                        "params":{"message":{"info":{"id": msg_id},"parts":[part]}}
-                    });
-                    if let Err(err) = state.persist_event(&session_id, "agent", &env).await {
-                        warn!(?err, "failed to persist ACP text part at turn end");
-                    }
-                    text_accum.clear();
-                }
+                let msg_id = assistant_message_id.as_deref().unwrap_or("");
+                text_part.finish(&state, &session_id, msg_id).await;
 
                 // Finalize the assistant message.
                 if let Some(msg_id) = assistant_message_id.as_ref() {
@@ -3871,7 +4129,7 @@ async fn acp_sse_translation_task(
                         .cloned()
                         .unwrap_or_default();
                     let now = now_ms();
-                    let info = build_completed_assistant_message(
+                    let mut info = build_completed_assistant_message(
                         &session_id,
                         msg_id,
                         &parent_id,
@@ -3881,6 +4139,7 @@ async fn acp_sse_translation_task(
                         &provider_id,
                         &model_id,
                     );
+                    apply_prompt_usage(&mut info, &payload);
                     state.emit_event(message_event("message.updated", &info));
                 }
 
@@ -3889,6 +4148,7 @@ async fn acp_sse_translation_task(
                 // Reset for next turn (if the SSE stream stays open).
                 assistant_message_id = None;
                 part_counter = 0;
+                tool_parts = ToolCallParts::default();
             }
 
             _ => {
@@ -3902,6 +4162,280 @@ async fn acp_sse_translation_task(
     }
 }
 
+/// Wrap the agent notification stream so it survives interruptions.
+///
+/// When the stream ends it is reopened with jittered exponential backoff,
+/// resuming after the last sequence seen. A jump in sequence numbers means
+/// the subscriber lagged and dropped messages, so the stream is reopened
+/// immediately to replay them from the runtime's buffer; if the buffer has
+/// already dropped them too, the gap is logged and translation carries on.
+/// Reconnecting stops once the session is deleted, the ACP server is gone, or
+/// `ACP_STREAM_RECONNECT_ATTEMPTS` consecutive attempts yield nothing.
+fn resumable_acp_stream(
+    state: Arc<AdapterState>,
+    dispatch: Arc<dyn AcpDispatch>,
+    server_id: String,
+    session_id: String,
+    stream: AcpPayloadStream,
+) -> Pin<Box<dyn Stream<Item = Value> + Send>> {
+    struct Resume {
+        state: Arc<AdapterState>,
+        dispatch: Arc<dyn AcpDispatch>,
+        server_id: String,
+        session_id: String,
+        stream: Option<AcpPayloadStream>,
+        last_sequence: Option<u64>,
+        attempts: u32,
+        reopen_now: bool,
+        replaying: bool,
+    }
+
+    let resume = Resume {
+        state,
+        dispatch,
+        server_id,
+        session_id,
+        stream: Some(stream),
+        last_sequence: None,
+        attempts: 0,
+        reopen_now: false,
+        replaying: false,
+    };
+
+    Box::pin(stream::unfold(resume, |mut resume| async move {
+        loop {
+            if let Some(stream) = resume.stream.as_mut() {
+                match stream.next().await {
+                    Some((sequence, payload)) => {
+                        if let Some(last) = resume.last_sequence {
+                            if sequence <= last {
+                                continue;
+                            }
+                            if sequence > last + 1 {
+                                if !resume.replaying {
+                                    resume.stream = None;
+                                    resume.reopen_now = true;
+                                    continue;
+                                }
+                                warn!(
+                                    session_id = %resume.session_id,
+                                    missed = sequence - last - 1,
+                                    "ACP events were dropped before they could be replayed"
+                                );
+                            }
+                        }
+                        resume.last_sequence = Some(sequence);
+                        resume.attempts = 0;
+                        resume.replaying = false;
+                        return Some((payload, resume));
+                    }
+                    None => {
+                        resume.stream = None;
+                        warn!(
+                            session_id = %resume.session_id,
+                            last_sequence = ?resume.last_sequence,
+                            "ACP event stream ended; reconnecting"
+                        );
+                    }
+                }
+            }
+
+            if resume.attempts >= ACP_STREAM_RECONNECT_ATTEMPTS {
+                warn!(session_id = %resume.session_id, "giving up on ACP event stream");
+                return None;
+            }
+            if !resume
+                .state
+                .acp_initialized
+                .lock()
+                .await
+                .contains_key(&resume.server_id)
+            {
+                return None;
+            }
+            if !std::mem::take(&mut resume.reopen_now) {
+                tokio::time::sleep(acp_stream_reconnect_delay(resume.attempts)).await;
+            }
+            resume.attempts += 1;
+            match resume
+                .dispatch
+                .notification_stream(&resume.server_id, resume.last_sequence)
+                .await
+            {
+                Ok(stream) => {
+                    resume.stream = Some(stream);
+                    resume.replaying = true;
+                }
+                Err(err) => {
+                    tracing::info!(
+                        session_id = %resume.session_id,
+                        error = %err,
+                        "ACP server is gone; stopping event translation"
+                    );
+                    return None;
+                }
+            }
+        }
+    }))
+}
+
+/// Doubling delay before reconnect `attempt`, capped and spread by up to 25%
+/// so sessions that lost their streams together do not reconnect in step.
+fn acp_stream_reconnect_delay(attempt: u32) -> Duration {
+    let delay = ACP_STREAM_RECONNECT_INITIAL_DELAY
+        .saturating_mul(1 << attempt.min(16))
+        .min(ACP_STREAM_RECONNECT_MAX_DELAY);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or_default();
+    delay + delay.mul_f64(f64::from(nanos % 1000) / 4000.0)
+}
+
+/// The text or reasoning part an assistant message is currently streaming.
+/// Chunks of the same kind accumulate into one part so the UI updates it in
+/// place.
+#[derive(Debug, Default)]
+struct StreamingTextPart {
+    id: Option<String>,
+    /// `text` for agent message chunks, `reasoning` for thought chunks.
+    part_type: &'static str,
+    text: String,
+    started_at: i64,
+}
+
+impl StreamingTextPart {
+    fn to_value(&self, session_id: &str, message_id: &str) -> Value {
+        let mut part = json!({
+            "id": self.id,
+            "sessionID": session_id,
+            "messageID": message_id,
+            "type": self.part_type,
+            "text": self.text,
+        });
+        if self.part_type == "reasoning" {
+            part["time"] = json!({ "start": self.started_at });
+        }
+        part
+    }
+
+    /// Persist the part, if one is open, and reset for the next one.
+    async fn finish(&mut self, state: &Arc<AdapterState>, session_id: &str, message_id: &str) {
+        if self.id.is_none() {
+            return;
+        }
+        let mut part = self.to_value(session_id, message_id);
+        if self.part_type == "reasoning" {
+            part["time"]["end"] = json!(now_ms());
+        }
+        let env = json!({
+            "jsonrpc":"2.0",
+            "method":"_sandboxagent/opencode/message",
+            "params":{"message":{"info":{"id": message_id},"parts":[part]}}
+        });
+        if let Err(err) = state.persist_event(session_id, "agent", &env).await {
+            warn!(?err, "failed to persist ACP text part");
+        }
+        *self = Self::default();
+    }
+}
+
+/// Tool parts of the assistant message being streamed, keyed by ACP
+/// `toolCallId`. Each call keeps the part id it was first given, so a
+/// `tool_call_update` replaces that part instead of adding another, and
+/// updates that leave the part unchanged are dropped.
+#[derive(Debug, Default)]
+struct ToolCallParts {
+    parts: HashMap<String, Value>,
+}
+
+/// Merge the ToolCall / ToolCallUpdate fields in `update` into an OpenCode
+/// tool part. Fields the update omits keep their previous value. The ACP
+/// tool `kind`, `locations`, file diffs and terminal id go under
+/// `state.metadata`.
+fn apply_tool_call_fields(part: &mut Value, update: &Value, now: i64) {
+    if let Some(title) = update.get("title").and_then(Value::as_str) {
+        part["tool"] = json!(title);
+        part["state"]["title"] = json!(title);
+    }
+    if let Some(input) = update.get("rawInput") {
+        part["state"]["input"] = input.clone();
+    }
+    // ACP content is a list of ToolCallContent; older agents put `text` on
+    // the item itself rather than in a nested content block.
+    let output = update
+        .get("content")
+        .and_then(Value::as_array)
+        .and_then(|items| {
+            items
+                .iter()
+                .filter_map(|item| {
+                    item.get("text")
+                        .or_else(|| item.pointer("/content/text"))
+                        .and_then(Value::as_str)
+                })
+                .next()
+        });
+    if let Some(output) = output {
+        part["state"]["output"] = json!(output);
+    }
+    if let Some(kind) = update.get("kind").and_then(Value::as_str) {
+        part["state"]["metadata"]["kind"] = json!(kind);
+    }
+    if let Some(locations) = update.get("locations").filter(|v| v.is_array()) {
+        part["state"]["metadata"]["locations"] = locations.clone();
+    }
+    let diffs = update
+        .get("content")
+        .and_then(Value::as_array)
+        .map(|items| {
+            items
+                .iter()
+                .filter(|item| item.get("type").and_then(Value::as_str) == Some("diff"))
+                .map(|item| {
+                    json!({
+                        "path": item.get("path"),
+                        "oldText": item.get("oldText"),
+                        "newText": item.get("newText"),
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if !diffs.is_empty() {
+        part["state"]["metadata"]["diffs"] = json!(diffs);
+    }
+    if let Some(terminal_id) = update
+        .get("content")
+        .and_then(Value::as_array)
+        .and_then(|items| {
+            items
+                .iter()
+                .find(|item| item.get("type").and_then(Value::as_str) == Some("terminal"))
+        })
+        .and_then(|item| item.get("terminalId"))
+    {
+        part["state"]["metadata"]["terminalId"] = terminal_id.clone();
+    }
+    if let Some(status) = update.get("status").and_then(Value::as_str) {
+        let status = match status {
+            "in_progress" => "running",
+            "failed" => "error",
+            other => other,
+        };
+        part["state"]["status"] = json!(status);
+        if matches!(status, "completed" | "error") && part.pointer("/state/time/end").is_none() {
+            part["state"]["time"]["end"] = json!(now);
+        }
+        if status == "error" && part.pointer("/state/error").is_none() {
+            part["state"]["error"] = part
+                .pointer("/state/output")
+                .cloned()
+                .unwrap_or_else(|| json!("tool call failed"));
+        }
+    }
+}
+
 /// Translate an ACP `session/update` notification into OpenCode SSE events.
 ///
 /// ACP `session/update` params use a discriminator field `sessionUpdate` to
@@ -3914,8 +4448,8 @@ async fn translate_session_update(
     session_id: &str,
     message_id: &str,
     part_counter: &mut u64,
-    text_accum: &mut String,
-    text_part_id: &mut Option<String>,
+    text_part: &mut StreamingTextPart,
+    tool_parts: &mut ToolCallParts,
     directory: &str,
     agent: &str,
     provider_id: &str,
@@ -3978,22 +4512,36 @@ async fn translate_session_update(
             if chunk.is_empty() {
                 return;
             }
+            let part_type = if kind == "agent_thought_chunk" {
+                let hidden = state
+                    .projection
+                    .read()
+                    .await
+                    .sessions
+                    .get(session_id)
+                    .is_some_and(|session| session.meta.hide_reasoning);
+                if hidden {
+                    return;
+                }
+                "reasoning"
+            } else {
+                "text"
+            };
 
             // Accumulate into a single part — reuse the same part ID so the
             // UI updates in-place instead of creating a new line per chunk.
-            text_accum.push_str(chunk);
-            let part_id = text_part_id.get_or_insert_with(|| {
-                let id = format!("part_{message_id}_{part_counter}");
+            // Switching between thoughts and reply text starts a new part.
+            if text_part.part_type != part_type {
+                text_part.finish(state, session_id, message_id).await;
+            }
+            if text_part.id.is_none() {
+                text_part.id = Some(format!("part_{message_id}_{part_counter}"));
+                text_part.part_type = part_type;
+                text_part.started_at = now_ms();
                 *part_counter += 1;
-                id
-            });
-            let part = json!({
-                "id": *part_id,
-                "sessionID": session_id,
-                "messageID": message_id,
-                "type": "text",
-                "text": *text_accum,
-            });
+            }
+            text_part.text.push_str(chunk);
+            let part = text_part.to_value(session_id, message_id);
             state.emit_event(json!({
                 "type":"message.part.updated",
                 "properties":{
@@ -4005,53 +4553,44 @@ async fn translate_session_update(
             }));
         }
 
-        // ── Tool call initiation ───────────────────────────────────────
-        "tool_call" => {
-            // Finalize any accumulated text part before switching to tool.
-            if let Some(tid) = text_part_id.take() {
-                let part = json!({
-                    "id": tid,
-                    "sessionID": session_id,
-                    "messageID": message_id,
-                    "type": "text",
-                    "text": *text_accum,
-                });
-                let env = json!({
-                    "jsonrpc":"2.0",
-                    "method":"_sandboxagent/opencode/message",
-                    "params":{"message":{"info":{"id": message_id},"parts":[part]}}
-                });
-                if let Err(err) = state.persist_event(session_id, "agent", &env).await {
-                    warn!(?err, "failed to persist ACP text part");
-                }
-                text_accum.clear();
+        // ── Tool call initiation and status updates ────────────────────
+        "tool_call" | "tool_call_update" => {
+            if kind == "tool_call" {
+                // Finalize any accumulated text part before switching to tool.
+                text_part.finish(state, session_id, message_id).await;
             }
             let call_id = update
                 .get("toolCallId")
                 .and_then(Value::as_str)
                 .unwrap_or("unknown");
-            let tool_title = update
-                .get("title")
-                .and_then(Value::as_str)
-                .unwrap_or("unknown");
-            let part_id = format!("part_{message_id}_{part_counter}");
-            *part_counter += 1;
             let now = now_ms();
-            let part = json!({
-                "id": part_id,
-                "sessionID": session_id,
-                "messageID": message_id,
-                "type": "tool",
-                "callID": call_id,
-                "tool": tool_title,
-                "state": {
-                    "status": "running",
-                    "input": update.get("rawInput").cloned().unwrap_or(json!({})),
-                    "title": tool_title,
-                    "metadata": {},
-                    "time": {"start": now}
+            let mut part = match tool_parts.parts.get(call_id) {
+                Some(part) => part.clone(),
+                None => {
+                    let part_id = format!("part_{message_id}_{part_counter}");
+                    *part_counter += 1;
+                    json!({
+                        "id": part_id,
+                        "sessionID": session_id,
+                        "messageID": message_id,
+                        "type": "tool",
+                        "callID": call_id,
+                        "tool": "unknown",
+                        "state": {
+                            "status": "running",
+                            "input": {},
+                            "title": "unknown",
+                            "metadata": {},
+                            "time": {"start": now}
+                        }
+                    })
                 }
-            });
+            };
+            apply_tool_call_fields(&mut part, update, now);
+            if tool_parts.parts.get(call_id) == Some(&part) {
+                return;
+            }
+            tool_parts.parts.insert(call_id.to_string(), part.clone());
             let env = json!({
                 "jsonrpc":"2.0",
                 "method":"_sandboxagent/opencode/message",
@@ -4070,48 +4609,6 @@ async fn translate_session_update(
             }));
         }
 
-        // ── Tool call status update ────────────────────────────────────
-        "tool_call_update" => {
-            let call_id = update
-                .get("toolCallId")
-                .and_then(Value::as_str)
-                .unwrap_or("unknown");
-            let status = update
-                .get("status")
-                .and_then(Value::as_str)
-                .unwrap_or("completed");
-            let output = update
-                .get("content")
-                .and_then(|v| v.as_array())
-                .and_then(|arr| {
-                    arr.iter()
-                        .filter_map(|c| c.get("text").and_then(Value::as_str))
-                        .next()
-                })
-                .unwrap_or("");
-            let now = now_ms();
-            let part = json!({
-                "id": format!("part_tc_{call_id}"),
-                "sessionID": session_id,
-                "messageID": message_id,
-                "type": "tool",
-                "callID": call_id,
-                "state": {
-                    "status": status,
-                    "output": output,
-                    "time": {"end": now}
-                }
-            });
-            state.emit_event(json!({
-                "type":"message.part.updated",
-                "properties":{
-                    "sessionID": session_id,
-                    "messageID": message_id,
-                    "part": part
-                }
-            }));
-        }
-
         _ => {
             tracing::debug!(
                 session_id = %session_id,
@@ -4135,6 +4632,65 @@ fn normalize_proxy_base_url(value: String) -> Option<String> {
     }
 }
 
+/// Default proxy headers from `configured`, falling back to the JSON object
+/// in `OPENCODE_COMPAT_PROXY_HEADERS` for names it does not set.
+fn proxy_default_headers(
+    configured: &HashMap<String, String>,
+) -> Result<reqwest::header::HeaderMap, String> {
+    let mut merged: HashMap<String, String> = match std::env::var("OPENCODE_COMPAT_PROXY_HEADERS") {
+        Ok(raw) if !raw.trim().is_empty() => serde_json::from_str(&raw).map_err(|err| {
+            format!("OPENCODE_COMPAT_PROXY_HEADERS must be a JSON object of strings: {err}")
+        })?,
+        _ => HashMap::new(),
+    };
+    merged.extend(configured.clone());
+
+    let mut headers = reqwest::header::HeaderMap::new();
+    for (name, value) in merged {
+        let header_name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+            .map_err(|err| format!("invalid proxy header name {name:?}: {err}"))?;
+        let mut header_value = reqwest::header::HeaderValue::from_str(&value)
+            .map_err(|err| format!("invalid value for proxy header {name:?}: {err}"))?;
+        header_value.set_sensitive(true);
+        headers.insert(header_name, header_value);
+    }
+    Ok(headers)
+}
+
+/// Headers copied from the incoming request onto proxied requests.
+static PROXY_FORWARDED_HEADERS: [HeaderName; 4] = [
+    header::AUTHORIZATION,
+    header::ACCEPT,
+    header::COOKIE,
+    HeaderName::from_static("x-opencode-directory"),
+];
+
+/// Tell `/event` subscribers when the native OpenCode sidecar came back after
+/// a crash so they can refetch state that was served by the old process.
+async fn forward_sidecar_restarts(
+    mut restarts: broadcast::Receiver<OpenCodeServerRestart>,
+    state: std::sync::Weak<AdapterState>,
+) {
+    loop {
+        let restart = match restarts.recv().await {
+            Ok(restart) => restart,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        let Some(state) = state.upgrade() else {
+            return;
+        };
+        state.emit_event(json!({
+            "type": "server.connected",
+            "properties": {
+                "reconnected": true,
+                "restartCount": restart.restart_count,
+                "reason": restart.reason,
+            }
+        }));
+    }
+}
+
 async fn resolve_proxy_base_url(state: &Arc<AdapterState>, path: &str) -> Option<String> {
     if let Some(base_url) = state.config.native_proxy_base_url.as_ref() {
         return Some(base_url.clone());
@@ -4163,15 +4719,12 @@ async fn proxy_native_opencode(
         .proxy_http_client
         .request(method, format!("{base_url}{path}"));
 
-    for header_name in [
-        header::AUTHORIZATION,
-        header::ACCEPT,
-        HeaderName::from_static("x-opencode-directory"),
-    ] {
-        if let Some(value) = headers.get(&header_name) {
+    for header_name in &PROXY_FORWARDED_HEADERS {
+        if let Some(value) = headers.get(header_name) {
             request = request.header(header_name.as_str(), value.as_bytes());
         }
     }
+    request = request.headers(state.proxy_headers.clone());
 
     if let Some(body) = body {
         request = request.json(&body);
@@ -4238,15 +4791,12 @@ async fn proxy_native_opencode_json(
         .proxy_http_client
         .request(method, format!("{base_url}{path}"));
 
-    for header_name in [
-        header::AUTHORIZATION,
-        header::ACCEPT,
-        HeaderName::from_static("x-opencode-directory"),
-    ] {
-        if let Some(value) = headers.get(&header_name) {
+    for header_name in &PROXY_FORWARDED_HEADERS {
+        if let Some(value) = headers.get(header_name) {
             request = request.header(header_name.as_str(), value.as_bytes());
         }
     }
+    request = request.headers(state.proxy_headers.clone());
 
     if let Some(body) = body {
         request = request.json(&body);
@@ -4337,6 +4887,130 @@ fn bool_ok(value: bool) -> (StatusCode, Json<Value>) {
     (StatusCode::OK, Json(json!(value)))
 }
 
+/// `_meta` for `session/new` and `session/load`. Claude's ACP process reads
+/// `systemPrompt` directly (a string replaces the preset, `{ append }` extends
+/// it); other agent processes can pick the same values up from
+/// `sandboxagent.dev`.
+fn session_new_meta(meta: &SessionMeta) -> Value {
+    let mut sandbox_meta = json!({ "model": meta.model_id.clone() });
+    if let Some(system_prompt) = meta.system_prompt.as_ref() {
+        sandbox_meta["systemPrompt"] = json!(system_prompt);
+    }
+    if let Some(append) = meta.append_instructions.as_ref() {
+        sandbox_meta["appendInstructions"] = json!(append);
+    }
+    if !meta.tool_allow.is_empty() || !meta.tool_deny.is_empty() {
+        sandbox_meta["tools"] = json!({
+            "allow": meta.tool_allow.clone(),
+            "deny": meta.tool_deny.clone(),
+        });
+    }
+
+    let mut out = json!({ "sandboxagent.dev": sandbox_meta });
+    match (
+        meta.system_prompt.as_ref(),
+        meta.append_instructions.as_ref(),
+    ) {
+        (Some(system_prompt), Some(append)) => {
+            out["systemPrompt"] = json!(format!("{system_prompt}\n\n{append}"));
+        }
+        (Some(system_prompt), None) => {
+            out["systemPrompt"] = json!(system_prompt);
+        }
+        (None, Some(append)) => {
+            out["systemPrompt"] = json!({ "append": append });
+        }
+        (None, None) => {}
+    }
+    if !meta.tool_allow.is_empty() || !meta.tool_deny.is_empty() {
+        out["claudeCode"] = json!({
+            "options": {
+                "allowedTools": meta.tool_allow.clone(),
+                "disallowedTools": meta.tool_deny.clone(),
+            }
+        });
+    }
+    out
+}
+
+/// Option kind to answer a `session/request_permission` with on the client's
+/// behalf, when the session's tool lists cover the requested tool.
+fn tool_policy_decision(meta: &SessionMeta, params: &Value) -> Option<&'static str> {
+    let names = ["/toolCall/title", "/toolCall/kind"]
+        .iter()
+        .filter_map(|pointer| params.pointer(pointer).and_then(Value::as_str))
+        .collect::<Vec<_>>();
+    let matches = |list: &[String]| {
+        list.iter()
+            .any(|entry| entry == "*" || names.iter().any(|name| name.eq_ignore_ascii_case(entry)))
+    };
+    if matches(&meta.tool_deny) {
+        Some("reject_once")
+    } else if matches(&meta.tool_allow) {
+        Some("allow_once")
+    } else {
+        None
+    }
+}
+
+/// Tool names that map onto each rule of a `custom` permission mode, for
+/// agents that only take tool settings when their process starts.
+const TOOL_RULES: [(&str, &[&str]); 3] = [
+    ("edit", &["edit", "write"]),
+    ("bash", &["bash", "execute"]),
+    ("webfetch", &["webfetch", "fetch"]),
+];
+
+/// The `permissionMode` to start `agent`'s process with so it enforces the
+/// session's tool lists itself: Codex turns it into its sandbox mode and
+/// approval policy, OpenCode into its permission config. Claude takes the
+/// lists per session instead (see `session_new_meta`) and the mock agent has
+/// no tools. Lists another agent cannot enforce are rejected.
+fn tool_launch_meta(
+    agent: &str,
+    allow: &[String],
+    deny: &[String],
+) -> Result<Option<Value>, SandboxError> {
+    if allow.is_empty() && deny.is_empty() {
+        return Ok(None);
+    }
+    let unsupported = |mode: String| SandboxError::ModeNotSupported {
+        agent: agent.to_string(),
+        mode,
+    };
+    match agent {
+        "claude" | "mock" => return Ok(None),
+        "codex" | "opencode" => {}
+        _ => return Err(unsupported("tools".to_string())),
+    }
+    let mut policy = serde_json::Map::new();
+    for (rule, list) in [("deny", deny), ("allow", allow)] {
+        for tool in list {
+            let keys = TOOL_RULES
+                .iter()
+                .filter(|(_, names)| {
+                    tool == "*" || names.iter().any(|name| tool.eq_ignore_ascii_case(name))
+                })
+                .map(|(key, _)| *key)
+                .collect::<Vec<_>>();
+            if keys.is_empty() {
+                return Err(unsupported(format!("tools.{rule} '{tool}'")));
+            }
+            for key in keys {
+                policy.entry(key).or_insert_with(|| json!(rule));
+            }
+        }
+    }
+    if agent == "codex" && policy.get("bash") == Some(&json!("deny")) {
+        return Err(unsupported("tools.deny 'bash'".to_string()));
+    }
+    Ok(Some(json!({ "mode": "custom", "policy": policy })))
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.filter(|value| !value.trim().is_empty())
+}
+
 fn bad_request(message: &str) -> Response {
     (
         StatusCode::BAD_REQUEST,
@@ -4345,6 +5019,16 @@ fn bad_request(message: &str) -> Response {
         .into_response()
 }
 
+fn sandbox_error(err: &SandboxError) -> Response {
+    let problem = err.to_problem_details();
+    (
+        StatusCode::from_u16(problem.status).unwrap_or(StatusCode::BAD_REQUEST),
+        [(header::CONTENT_TYPE, "application/problem+json")],
+        Json(problem),
+    )
+        .into_response()
+}
+
 fn not_found(message: &str) -> Response {
     (
         StatusCode::NOT_FOUND,
@@ -4361,3 +5045,150 @@ fn internal_error(message: String) -> Response {
     )
         .into_response()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hands out queued streams from `notification_stream` and records the
+    /// `last_event_id` of every reopen. Once the queue is empty the server
+    /// counts as gone.
+    struct ScriptedDispatch {
+        streams: StdMutex<VecDeque<Vec<(u64, Value)>>>,
+        reopened_after: StdMutex<Vec<Option<u64>>>,
+    }
+
+    impl AcpDispatch for ScriptedDispatch {
+        fn post(
+            &self,
+            _server_id: &str,
+            _bootstrap_agent: Option<&str>,
+            _payload: Value,
+        ) -> Pin<Box<dyn Future<Output = Result<AcpDispatchResult, String>> + Send + '_>> {
+            Box::pin(async { Ok(AcpDispatchResult::Accepted) })
+        }
+
+        fn notification_stream(
+            &self,
+            _server_id: &str,
+            last_event_id: Option<u64>,
+        ) -> Pin<Box<dyn Future<Output = Result<AcpPayloadStream, String>> + Send + '_>> {
+            self.reopened_after.lock().unwrap().push(last_event_id);
+            let next = self.streams.lock().unwrap().pop_front();
+            Box::pin(async move {
+                let items = next.ok_or_else(|| "server not found".to_string())?;
+                Ok(Box::pin(stream::iter(items)) as AcpPayloadStream)
+            })
+        }
+
+        fn delete(
+            &self,
+            _server_id: &str,
+        ) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send + '_>> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    fn payload(n: u64) -> (u64, Value) {
+        (n, json!({ "n": n }))
+    }
+
+    fn test_state(sqlite_path: &str) -> Arc<AdapterState> {
+        build_adapter_state(OpenCodeAdapterConfig {
+            sqlite_path: Some(sqlite_path.to_string()),
+            ..OpenCodeAdapterConfig::default()
+        })
+        .expect("adapter state")
+    }
+
+    async fn drain_resumable(
+        first: Vec<(u64, Value)>,
+        reopens: Vec<Vec<(u64, Value)>>,
+    ) -> (Vec<Value>, Vec<Option<u64>>) {
+        let state = test_state("/nonexistent/opencode.db");
+        state
+            .acp_initialized
+            .lock()
+            .await
+            .insert("server-1".to_string(), "acp-1".to_string());
+        let dispatch = Arc::new(ScriptedDispatch {
+            streams: StdMutex::new(reopens.into()),
+            reopened_after: StdMutex::new(Vec::new()),
+        });
+
+        let items = resumable_acp_stream(
+            state,
+            dispatch.clone(),
+            "server-1".to_string(),
+            "ses_1".to_string(),
+            Box::pin(stream::iter(first)),
+        )
+        .collect::<Vec<_>>();
+        let items = tokio::time::timeout(Duration::from_secs(10), items)
+            .await
+            .expect("stream finished");
+        let reopened_after = dispatch.reopened_after.lock().unwrap().clone();
+        (items, reopened_after)
+    }
+
+    #[tokio::test]
+    async fn resumable_stream_reconnects_after_the_last_sequence() {
+        let (items, reopened_after) = drain_resumable(
+            vec![payload(1), payload(2)],
+            vec![vec![payload(2), payload(3)]],
+        )
+        .await;
+
+        assert_eq!(
+            items,
+            vec![json!({ "n": 1 }), json!({ "n": 2 }), json!({ "n": 3 })],
+            "the replayed duplicate is skipped"
+        );
+        assert_eq!(reopened_after, vec![Some(2), Some(3)]);
+    }
+
+    #[tokio::test]
+    async fn resumable_stream_replays_a_sequence_gap() {
+        let (items, reopened_after) = drain_resumable(
+            vec![payload(1), payload(3), payload(4)],
+            vec![vec![payload(2), payload(3)], vec![payload(5), payload(6)]],
+        )
+        .await;
+
+        assert_eq!(
+            items,
+            vec![
+                json!({ "n": 1 }),
+                json!({ "n": 2 }),
+                json!({ "n": 3 }),
+                json!({ "n": 5 }),
+                json!({ "n": 6 }),
+            ],
+            "a gap the replay cannot fill is skipped"
+        );
+        assert_eq!(reopened_after, vec![Some(1), Some(3), Some(6)]);
+    }
+
+    #[tokio::test]
+    async fn projection_reads_do_not_wait_for_each_other() {
+        let sqlite_path =
+            std::env::temp_dir().join(format!("opencode-projection-{}.db", runtime_unique_seed()));
+        let state = test_state(sqlite_path.to_str().expect("utf-8 temp path"));
+        state.ensure_initialized().await.expect("initialize");
+
+        let reader = state.projection.read().await;
+        let response = tokio::time::timeout(
+            Duration::from_secs(2),
+            oc_session_list(State(state.clone())),
+        )
+        .await
+        .expect("listing sessions waits for another reader");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        drop(reader);
+        drop(state);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", sqlite_path.display()));
+        }
+    }
+}
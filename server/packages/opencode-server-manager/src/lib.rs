@@ -1,20 +1,28 @@
 use std::fs::{self, OpenOptions};
 use std::net::TcpListener;
 use std::path::{Path, PathBuf};
-use std::process::{Child, Command, ExitStatus, Stdio};
-use std::sync::{Arc, Mutex as StdMutex};
-use std::time::Duration;
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use reqwest::Client;
 use sandbox_agent_agent_management::agents::{AgentId, AgentManager};
-use tokio::sync::Mutex;
+use tokio::process::{Child, Command};
+use tokio::sync::{broadcast, Mutex, Notify};
 use tokio::time::sleep;
 use tracing::warn;
 
 const HEALTH_ENDPOINTS: [&str; 4] = ["health", "healthz", "app/agents", "agents"];
-const HEALTH_ATTEMPTS: usize = 20;
-const HEALTH_DELAY_MS: u64 = 150;
+const HEALTH_DEADLINE: Duration = Duration::from_secs(10);
+const HEALTH_INITIAL_DELAY: Duration = Duration::from_millis(50);
+const HEALTH_MAX_DELAY: Duration = Duration::from_secs(1);
 const MONITOR_DELAY_MS: u64 = 500;
+const HEALTH_PING_INTERVAL: Duration = Duration::from_secs(5);
+const HEALTH_PING_TIMEOUT: Duration = Duration::from_secs(2);
+const HEALTH_PING_FAILURES: u32 = 3;
+const RESTART_CHANNEL_SIZE: usize = 16;
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[derive(Debug, Clone)]
 pub struct OpenCodeServerManagerConfig {
@@ -31,6 +39,15 @@ impl Default for OpenCodeServerManagerConfig {
     }
 }
 
+/// Sent to [`OpenCodeServerManager::subscribe_restarts`] subscribers after
+/// the sidecar was restarted following a crash or failed health pings.
+#[derive(Debug, Clone)]
+pub struct OpenCodeServerRestart {
+    pub base_url: String,
+    pub restart_count: u64,
+    pub reason: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct OpenCodeServerManager {
     inner: Arc<Inner>,
@@ -43,6 +60,15 @@ struct Inner {
     config: OpenCodeServerManagerConfig,
     ensure_lock: Mutex<()>,
     state: Mutex<ManagerState>,
+    restarts: broadcast::Sender<OpenCodeServerRestart>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        if let Some(server) = self.state.get_mut().server.take() {
+            server.stop.notify_one();
+        }
+    }
 }
 
 #[derive(Debug, Default)]
@@ -53,10 +79,15 @@ struct ManagerState {
     last_error: Option<String>,
 }
 
+/// The child itself is owned by its monitor task; the manager only keeps
+/// what it needs to check on it and to ask for it to be killed.
 #[derive(Debug, Clone)]
 struct RunningServer {
     base_url: String,
-    child: Arc<StdMutex<Option<Child>>>,
+    alive: Arc<AtomicBool>,
+    stop: Arc<Notify>,
+    /// Notified by the monitor once it has reaped the child.
+    stopped: Arc<Notify>,
     instance_id: u64,
 }
 
@@ -69,6 +100,7 @@ impl OpenCodeServerManager {
                 config,
                 ensure_lock: Mutex::new(()),
                 state: Mutex::new(ManagerState::default()),
+                restarts: broadcast::channel(RESTART_CHANNEL_SIZE).0,
             }),
         }
     }
@@ -80,15 +112,18 @@ impl OpenCodeServerManager {
             return Ok(base_url);
         }
 
-        let (base_url, child) = self.spawn_http_server().await?;
+        let (base_url, mut child) = self.spawn_http_server().await?;
 
         if let Err(err) = self.wait_for_http_server(&base_url).await {
-            kill_child(&child);
+            let _ = child.kill().await;
             let mut state = self.inner.state.lock().await;
             state.last_error = Some(err.clone());
             return Err(err);
         }
 
+        let alive = Arc::new(AtomicBool::new(true));
+        let stop = Arc::new(Notify::new());
+        let stopped = Arc::new(Notify::new());
         let instance_id = {
             let mut state = self.inner.state.lock().await;
             state.shutdown_requested = false;
@@ -96,29 +131,44 @@ impl OpenCodeServerManager {
             let instance_id = state.restart_count;
             state.server = Some(RunningServer {
                 base_url: base_url.clone(),
-                child: child.clone(),
+                alive: alive.clone(),
+                stop: stop.clone(),
+                stopped: stopped.clone(),
                 instance_id,
             });
             state.last_error = None;
             instance_id
         };
 
-        self.spawn_monitor_task(instance_id, child);
+        self.spawn_monitor_task(instance_id, base_url.clone(), child, alive, stop, stopped);
 
         Ok(base_url)
     }
 
+    pub fn subscribe_restarts(&self) -> broadcast::Receiver<OpenCodeServerRestart> {
+        self.inner.restarts.subscribe()
+    }
+
+    /// Stop the server and wait, up to `SHUTDOWN_TIMEOUT`, for its process
+    /// to be killed and reaped.
     pub async fn shutdown(&self) {
         let _guard = self.inner.ensure_lock.lock().await;
 
-        let child = {
+        let server = {
             let mut state = self.inner.state.lock().await;
             state.shutdown_requested = true;
-            state.server.take().map(|server| server.child)
+            state.server.take()
         };
 
-        if let Some(child) = child {
-            kill_child(&child);
+        if let Some(server) = server {
+            server.stop.notify_one();
+            if server.alive.load(Ordering::Acquire)
+                && tokio::time::timeout(SHUTDOWN_TIMEOUT, server.stopped.notified())
+                    .await
+                    .is_err()
+            {
+                warn!("timed out waiting for the OpenCode server to exit");
+            }
         }
     }
 
@@ -128,7 +178,7 @@ impl OpenCodeServerManager {
             state.server.clone()
         }?;
 
-        if child_is_alive(&running.child) {
+        if running.alive.load(Ordering::Acquire) {
             return Some(running.base_url);
         }
 
@@ -145,77 +195,160 @@ impl OpenCodeServerManager {
         None
     }
 
+    /// Poll the health endpoints with jittered exponential backoff until one
+    /// answers or `HEALTH_DEADLINE` passes. The error names the attempt count
+    /// and the last failure seen.
     async fn wait_for_http_server(&self, base_url: &str) -> Result<(), String> {
-        for _ in 0..HEALTH_ATTEMPTS {
+        let started = Instant::now();
+        let mut delay = HEALTH_INITIAL_DELAY;
+        let mut attempts = 0;
+        let mut last_error = None;
+        loop {
+            attempts += 1;
             for endpoint in HEALTH_ENDPOINTS {
                 let url = format!("{base_url}/{endpoint}");
                 match self.inner.http_client.get(&url).send().await {
                     Ok(response) if response.status().is_success() => return Ok(()),
-                    Ok(_) | Err(_) => {}
+                    Ok(response) => {
+                        last_error = Some(format!("{url} returned {}", response.status()));
+                    }
+                    Err(err) => last_error = Some(format!("{url}: {err}")),
                 }
             }
-            sleep(Duration::from_millis(HEALTH_DELAY_MS)).await;
+
+            let remaining = HEALTH_DEADLINE.saturating_sub(started.elapsed());
+            if remaining.is_zero() {
+                break;
+            }
+            sleep(with_jitter(delay).min(remaining)).await;
+            delay = (delay * 2).min(HEALTH_MAX_DELAY);
         }
 
-        Err("OpenCode server health check failed".to_string())
+        Err(format!(
+            "OpenCode server health check failed after {attempts} attempts in {}ms: {}",
+            started.elapsed().as_millis(),
+            last_error.unwrap_or_else(|| "no response".to_string())
+        ))
     }
 
-    async fn spawn_http_server(&self) -> Result<(String, Arc<StdMutex<Option<Child>>>), String> {
-        let agent_manager = self.inner.agent_manager.clone();
-        let log_dir = self.inner.config.log_dir.clone();
+    /// One pass over the health endpoints, used by the monitor once the
+    /// server is up.
+    async fn ping(inner: &Inner, base_url: &str) -> Result<(), String> {
+        let mut last_error = None;
+        for endpoint in HEALTH_ENDPOINTS {
+            let url = format!("{base_url}/{endpoint}");
+            match inner
+                .http_client
+                .get(&url)
+                .timeout(HEALTH_PING_TIMEOUT)
+                .send()
+                .await
+            {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) => last_error = Some(format!("{url} returned {}", response.status())),
+                Err(err) => last_error = Some(format!("{url}: {err}")),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| "no response".to_string()))
+    }
 
-        let (base_url, child) = tokio::task::spawn_blocking(move || {
-            let path = agent_manager
+    async fn spawn_http_server(&self) -> Result<(String, Child), String> {
+        let agent_manager = self.inner.agent_manager.clone();
+        let path = tokio::task::spawn_blocking(move || {
+            agent_manager
                 .resolve_binary(AgentId::Opencode)
-                .map_err(|err| err.to_string())?;
-            let port = find_available_port()?;
-            let mut command = Command::new(path);
-            let stderr = open_opencode_log(&log_dir).unwrap_or_else(|_| Stdio::null());
-            command
-                .arg("serve")
-                .arg("--port")
-                .arg(port.to_string())
-                .stdout(Stdio::null())
-                .stderr(stderr);
-
-            let child = command.spawn().map_err(|err| err.to_string())?;
-            Ok::<(String, Child), String>((format!("http://127.0.0.1:{port}"), child))
+                .map_err(|err| err.to_string())
         })
         .await
         .map_err(|err| err.to_string())??;
 
-        Ok((base_url, Arc::new(StdMutex::new(Some(child)))))
+        let port = find_available_port()?;
+        let stderr =
+            open_opencode_log(&self.inner.config.log_dir).unwrap_or_else(|_| Stdio::null());
+        let mut command = Command::new(path);
+        command
+            .arg("serve")
+            .arg("--port")
+            .arg(port.to_string())
+            .stdout(Stdio::null())
+            .stderr(stderr)
+            .kill_on_drop(true);
+
+        let child = command.spawn().map_err(|err| err.to_string())?;
+        Ok((format!("http://127.0.0.1:{port}"), child))
     }
 
-    fn spawn_monitor_task(&self, instance_id: u64, child: Arc<StdMutex<Option<Child>>>) {
-        let manager = self.clone();
+    /// Own the child until it exits, misses `HEALTH_PING_FAILURES` pings in a
+    /// row, or `stop` is notified. Exits and ping failures are handed off to
+    /// `handle_server_lost`. Holds only a weak reference so dropping the
+    /// manager still stops the server.
+    fn spawn_monitor_task(
+        &self,
+        instance_id: u64,
+        base_url: String,
+        mut child: Child,
+        alive: Arc<AtomicBool>,
+        stop: Arc<Notify>,
+        stopped: Arc<Notify>,
+    ) {
+        let inner = Arc::downgrade(&self.inner);
         tokio::spawn(async move {
+            let _reaped = NotifyOnDrop(stopped);
+            let mut pings = tokio::time::interval_at(
+                tokio::time::Instant::now() + HEALTH_PING_INTERVAL,
+                HEALTH_PING_INTERVAL,
+            );
+            let mut failed_pings = 0;
             loop {
-                let status = {
-                    let mut guard = match child.lock() {
-                        Ok(guard) => guard,
-                        Err(_) => return,
-                    };
-                    match guard.as_mut() {
-                        Some(child) => match child.try_wait() {
-                            Ok(status) => status,
-                            Err(_) => None,
-                        },
-                        None => return,
+                tokio::select! {
+                    status = child.wait() => {
+                        alive.store(false, Ordering::Release);
+                        let message = match status {
+                            Ok(status) => exit_message(status),
+                            Err(err) => format!("failed to wait for OpenCode server: {err}"),
+                        };
+                        if let Some(manager) = Self::upgrade(&inner) {
+                            manager.handle_server_lost(instance_id, message).await;
+                        }
+                        return;
+                    }
+                    _ = stop.notified() => {
+                        let _ = child.kill().await;
+                        alive.store(false, Ordering::Release);
+                        return;
+                    }
+                    _ = pings.tick() => {
+                        let Some(manager) = Self::upgrade(&inner) else {
+                            let _ = child.kill().await;
+                            return;
+                        };
+                        match Self::ping(&manager.inner, &base_url).await {
+                            Ok(()) => failed_pings = 0,
+                            Err(err) => {
+                                failed_pings += 1;
+                                warn!(error = %err, failed_pings, "OpenCode server health ping failed");
+                                if failed_pings >= HEALTH_PING_FAILURES {
+                                    let _ = child.kill().await;
+                                    alive.store(false, Ordering::Release);
+                                    let message = format!(
+                                        "OpenCode server stopped answering health checks: {err}"
+                                    );
+                                    manager.handle_server_lost(instance_id, message).await;
+                                    return;
+                                }
+                            }
+                        }
                     }
-                };
-
-                if let Some(status) = status {
-                    manager.handle_process_exit(instance_id, status).await;
-                    return;
                 }
-
-                sleep(Duration::from_millis(MONITOR_DELAY_MS)).await;
             }
         });
     }
 
-    async fn handle_process_exit(&self, instance_id: u64, status: ExitStatus) {
+    fn upgrade(inner: &Weak<Inner>) -> Option<Self> {
+        inner.upgrade().map(|inner| Self { inner })
+    }
+
+    async fn handle_server_lost(&self, instance_id: u64, message: String) {
         let (should_restart, error_message) = {
             let mut state = self.inner.state.lock().await;
             let Some(server) = state.server.as_ref() else {
@@ -225,7 +358,6 @@ impl OpenCodeServerManager {
                 return;
             }
 
-            let message = format!("OpenCode server exited with status {:?}", status);
             let shutdown_requested = state.shutdown_requested;
             if !shutdown_requested {
                 state.last_error = Some(message.clone());
@@ -245,17 +377,46 @@ impl OpenCodeServerManager {
         let manager = self.clone();
         tokio::spawn(async move {
             sleep(Duration::from_millis(MONITOR_DELAY_MS)).await;
-            if let Err(err) = manager.ensure_server().await {
-                warn!(
-                    error = ?err,
-                    prior_exit = %error_message,
-                    "failed to restart OpenCode compat sidecar"
-                );
+            match manager.ensure_server().await {
+                Ok(base_url) => {
+                    let restart_count = manager
+                        .inner
+                        .state
+                        .lock()
+                        .await
+                        .restart_count
+                        .saturating_sub(1);
+                    let _ = manager.inner.restarts.send(OpenCodeServerRestart {
+                        base_url,
+                        restart_count,
+                        reason: error_message,
+                    });
+                }
+                Err(err) => {
+                    warn!(
+                        error = ?err,
+                        prior_exit = %error_message,
+                        "failed to restart OpenCode compat sidecar"
+                    );
+                }
             }
         });
     }
 }
 
+/// Wakes a `shutdown` waiting on the monitor, whichever way it returns.
+struct NotifyOnDrop(Arc<Notify>);
+
+impl Drop for NotifyOnDrop {
+    fn drop(&mut self) {
+        self.0.notify_one();
+    }
+}
+
+fn exit_message(status: ExitStatus) -> String {
+    format!("OpenCode server exited with status {:?}", status)
+}
+
 fn default_log_dir() -> PathBuf {
     let mut base = dirs::data_local_dir().unwrap_or_else(|| std::env::temp_dir());
     base.push("sandbox-agent");
@@ -275,36 +436,19 @@ fn open_opencode_log(log_dir: &Path) -> Result<Stdio, String> {
     Ok(file.into())
 }
 
+/// Add up to 25% to `delay` so sidecars started together do not poll in
+/// lockstep.
+fn with_jitter(delay: Duration) -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or_default();
+    delay + delay.mul_f64(f64::from(nanos % 1000) / 4000.0)
+}
+
 fn find_available_port() -> Result<u16, String> {
     let listener = TcpListener::bind("127.0.0.1:0").map_err(|err| err.to_string())?;
     let port = listener.local_addr().map_err(|err| err.to_string())?.port();
     drop(listener);
     Ok(port)
 }
-
-fn child_is_alive(child: &Arc<StdMutex<Option<Child>>>) -> bool {
-    let mut guard = match child.lock() {
-        Ok(guard) => guard,
-        Err(_) => return false,
-    };
-    let Some(child) = guard.as_mut() else {
-        return false;
-    };
-    match child.try_wait() {
-        Ok(Some(_)) => {
-            *guard = None;
-            false
-        }
-        Ok(None) => true,
-        Err(_) => false,
-    }
-}
-
-fn kill_child(child: &Arc<StdMutex<Option<Child>>>) {
-    if let Ok(mut guard) = child.lock() {
-        if let Some(child) = guard.as_mut() {
-            let _ = child.kill();
-        }
-        *guard = None;
-    }
-}
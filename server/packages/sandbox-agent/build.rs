@@ -46,6 +46,7 @@ fn main() {
     let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR"));
     generate_version(&out_dir);
     generate_build_id(&out_dir);
+    generate_grpc(&manifest_dir);
 
     let skip = env::var("SANDBOX_AGENT_SKIP_INSPECTOR").is_ok() || !dist_exists;
     let out_file = out_dir.join("inspector_assets.rs");
@@ -125,3 +126,18 @@ fn generate_build_id(out_dir: &Path) {
 
     fs::write(&out_file, contents).expect("write build_id.rs");
 }
+
+fn generate_grpc(manifest_dir: &Path) {
+    // protox compiles the proto in-process, so building needs no protoc.
+    let proto_dir = manifest_dir.join("proto");
+    let proto = proto_dir
+        .join("sandbox_agent")
+        .join("v1")
+        .join("sandbox_agent.proto");
+    println!("cargo:rerun-if-changed={}", proto.display());
+
+    let descriptors = protox::compile([&proto], [&proto_dir]).expect("compile protos");
+    tonic_build::configure()
+        .compile_fds(descriptors)
+        .expect("generate grpc code");
+}
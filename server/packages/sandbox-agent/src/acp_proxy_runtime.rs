@@ -1,20 +1,89 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::future::Future;
+use std::path::PathBuf;
 use std::pin::Pin;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, PoisonError};
+use std::time::{Duration, Instant};
 
-use acp_http_adapter::process::{AdapterError, AdapterRuntime, PostOutcome};
+use acp_http_adapter::process::{
+    artifact_dir_name, is_log_envelope, AdapterError, AdapterRuntime, ArtifactSpill, PipelineStats,
+    PostOutcome, RawSpill,
+};
 use acp_http_adapter::registry::LaunchSpec;
 use axum::response::sse::Event;
-use futures::Stream;
-use sandbox_agent_agent_management::agents::{AgentId, AgentManager, InstallOptions};
+use futures::{FutureExt, Stream, StreamExt};
+use sandbox_agent_agent_management::agents::{AgentId, AgentManager, AgentPin, InstallOptions};
 use sandbox_agent_error::SandboxError;
 use sandbox_agent_opencode_adapter::{AcpDispatch, AcpDispatchResult, AcpPayloadStream};
-use serde_json::Value;
-use tokio::sync::{Mutex, RwLock};
+use serde_json::{json, Value};
+use tokio::sync::{broadcast, Mutex, Notify, RwLock};
+
+use crate::agent_compat::{
+    AgentCompat, AgentIncompatibility, IncompatibilityReason, SCHEMA_DRIFT_THRESHOLD,
+};
+use crate::agent_exit::{classify_exit, AgentExitReason};
+use crate::agent_failover::{fallback_agents_from_payload, AgentFailover, AGENT_FAILOVER_METHOD};
+use crate::attachments::{Attachments, PromptCapabilities};
+use crate::cdc::CdcFeed;
+use crate::claude_settings::ClaudeSettings;
+use crate::compaction::{self, CompactionMode};
+use crate::config::ServerConfig;
+use crate::event_filter::EventFilter;
+use crate::idempotency::{self, Claim, IdempotencyCache};
+use crate::message_queue::{MessageQueue, QueuedMessage};
+use crate::model_routing::{self, ModelRoutes, MODEL_FALLBACK_METHOD};
+use crate::permission_mode::{CodexOptions, PermissionLaunch, PermissionMode};
+use crate::plans::{self, PlanApproval, SessionPlan};
+use crate::prompt_policy::PromptPolicy;
+use crate::request_limits::PromptLimit;
+use crate::retry::{self, LastPrompt, RetractedTurn};
+use crate::secrets::{secrets_to_env, SecretInput, SecretVault};
+use crate::session_defaults::SessionDefaults;
+use crate::session_end::{
+    SessionEndReason, SessionRecord, SESSION_ENDED_METHOD, SESSION_UPDATED_METHOD,
+};
+use crate::session_home::SessionHomes;
+use crate::subagents::{self, ChildSession};
+use crate::turn_limits::{TurnLimits, TurnTimeoutKind};
+use crate::turn_logs::TurnLogs;
+use crate::turn_result::{self, TurnSummary};
+use crate::unattended::UnattendedPolicy;
+use crate::warm_pool::{Standby, WarmPool, WarmPoolStatus};
+
+mod compat;
+mod files;
+mod idempotent;
+mod installs;
+mod message_queue;
+mod plan_mode;
+mod session_settings;
+mod sessions;
+mod standby;
+use self::session_settings::apply_session_defaults;
 
 const DEFAULT_REQUEST_TIMEOUT_MS: u64 = 120_000;
+/// How long an agent gets to answer `session/cancel` after a turn limit
+/// fires before its process is killed.
+const TURN_CANCEL_GRACE: Duration = Duration::from_secs(5);
+/// How long in-flight prompts get to answer `session/cancel` on shutdown.
+const SHUTDOWN_CANCEL_GRACE: Duration = Duration::from_secs(3);
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long shutdown waits for queued change records to reach the feed file.
+const CDC_FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+const CREATED_CHANNEL_SIZE: usize = 64;
+const DEFAULT_SPAWN_ENV_ALLOWLIST: &[&str] = &[
+    "PATH",
+    "HOME",
+    "LANG",
+    "TERM",
+    "HTTP_PROXY",
+    "HTTPS_PROXY",
+    "NO_PROXY",
+    "http_proxy",
+    "https_proxy",
+    "no_proxy",
+];
 
 #[derive(Debug, Clone)]
 pub struct AcpProxyRuntime {
@@ -25,10 +94,49 @@ pub struct AcpProxyRuntime {
 struct AcpProxyRuntimeInner {
     agent_manager: Arc<AgentManager>,
     require_preinstall: bool,
+    /// Disk quota for the install directory, from
+    /// `SANDBOX_AGENT_INSTALL_QUOTA_BYTES`.
+    install_quota_bytes: Option<u64>,
     request_timeout: Duration,
+    turn_limits: TurnLimits,
+    spawn_env_allowlist: Vec<String>,
+    /// Spawn env from `[agents.<id>.env]` in the config file; request env
+    /// wins.
+    agent_env: HashMap<AgentId, HashMap<String, String>>,
+    /// Model and mode from `[agents.<id>]` in the config file, for new
+    /// sessions that do not pick their own.
+    session_defaults: HashMap<AgentId, SessionDefaults>,
+    /// Model aliases from `[models]` in the config file.
+    model_routes: ModelRoutes,
+    raw_spill: RawSpill,
+    /// Tool outputs stored out of band, with `dir` as the root of the
+    /// per-server directories.
+    artifact_spill: ArtifactSpill,
+    prompt_policy: PromptPolicy,
+    prompt_limit: PromptLimit,
+    cdc: CdcFeed,
+    compat: AgentCompat,
+    session_homes: SessionHomes,
+    turn_logs: TurnLogs,
+    attachments: Attachments,
+    claude_settings: ClaudeSettings,
+    /// Turns started per server id. Kept across process restarts so turn
+    /// numbers, and their log files, are never reused.
+    turn_counts: std::sync::Mutex<HashMap<String, u64>>,
+    /// Sessions by server id, then session id. Kept across process restarts
+    /// so ended sessions stay visible until the server is deleted.
+    sessions: std::sync::Mutex<HashMap<String, HashMap<String, SessionRecord>>>,
+    secrets: Mutex<SecretVault>,
+    /// Outcomes of POSTs sent with an `Idempotency-Key`.
+    idempotency: IdempotencyCache<ProxyPostOutcome>,
+    /// How agent requests nobody answers are resolved.
+    unattended: UnattendedPolicy,
+    warm_pool: WarmPool,
     instances: RwLock<HashMap<String, Arc<ProxyInstance>>>,
     instance_locks: Mutex<HashMap<String, Arc<Mutex<()>>>>,
     install_locks: Mutex<HashMap<AgentId, Arc<Mutex<()>>>>,
+    /// Id and agent of every server as it is created.
+    created: broadcast::Sender<(String, AgentId)>,
 }
 
 #[derive(Debug)]
@@ -37,9 +145,68 @@ struct ProxyInstance {
     agent: AgentId,
     runtime: Arc<AdapterRuntime>,
     created_at_ms: i64,
+    startup_timing: AcpStartupTiming,
+    last_turn_timing: std::sync::Mutex<Option<AcpTurnTiming>>,
+    /// Set once a turn completes, after the working versions were recorded.
+    verified: AtomicBool,
+    /// Limits from `session/new` overrides, by session id.
+    session_limits: std::sync::Mutex<HashMap<String, TurnLimits>>,
+    /// `session/prompt` requests in flight, by session id.
+    active_prompts: std::sync::Mutex<HashMap<String, usize>>,
+    /// Notified whenever a session has no prompt in flight any more.
+    prompts_finished: Notify,
+    /// Agent requests, such as `session/request_permission`, that no client
+    /// has answered yet, by JSON-RPC id.
+    pending_requests: Arc<std::sync::Mutex<HashMap<String, AcpPendingRequest>>>,
+    /// Unattended policies from `session/new` overrides, by session id.
+    session_unattended: Arc<std::sync::Mutex<HashMap<String, UnattendedPolicy>>>,
+    /// What the agent accepts in prompts, once it answered `initialize`.
+    prompt_capabilities: std::sync::Mutex<Option<PromptCapabilities>>,
+    permission_mode: PermissionMode,
+    /// How the permission mode chosen at bootstrap is enforced.
+    permissions: PermissionLaunch,
+    /// Current ACP mode id, by session id, once the agent or client set one.
+    session_modes: std::sync::Mutex<HashMap<String, String>>,
+    /// Latest plan from a planning turn, by session id.
+    plans: std::sync::Mutex<HashMap<String, SessionPlan>>,
+    /// Latest `session/prompt`, by session id, for retries.
+    last_prompts: std::sync::Mutex<HashMap<String, LastPrompt>>,
+    /// Messages waiting for their session's current turn to complete.
+    messages: std::sync::Mutex<MessageQueue>,
+    /// Sessions this process created or loaded.
+    session_ids: std::sync::Mutex<Vec<String>>,
+    /// Why the server stopped the process, once it has.
+    stopped_by: std::sync::Mutex<Option<SessionEndReason>>,
+    /// Models left to fall back to, by session id, when the session's
+    /// model came from an alias with more than one target.
+    model_fallbacks: std::sync::Mutex<HashMap<String, Vec<String>>>,
 }
 
-#[derive(Debug)]
+/// Counts a prompt in [`ProxyInstance::active_prompts`] until dropped, so a
+/// caller that disconnects mid-turn does not leave a stale entry.
+struct ActivePrompt<'a> {
+    instance: &'a ProxyInstance,
+    session_id: String,
+}
+
+impl Drop for ActivePrompt<'_> {
+    fn drop(&mut self) {
+        let mut active = self
+            .instance
+            .active_prompts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(count) = active.get_mut(&self.session_id) {
+            *count -= 1;
+            if *count == 0 {
+                active.remove(&self.session_id);
+                self.instance.prompts_finished.notify_waiters();
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum ProxyPostOutcome {
     Response(Value),
     Accepted,
@@ -50,61 +217,446 @@ pub struct AcpServerInstanceInfo {
     pub server_id: String,
     pub agent: AgentId,
     pub created_at_ms: i64,
+    pub startup_timing: AcpStartupTiming,
+    pub last_turn_timing: Option<AcpTurnTiming>,
+    pub pipeline: PipelineStats,
+}
+
+/// A request from the agent that is waiting for a client response.
+#[derive(Debug, Clone)]
+pub struct AcpPendingRequest {
+    pub sequence: u64,
+    pub id: Value,
+    pub method: String,
+    pub session_id: Option<String>,
+    pub payload: Value,
+    pub received_at_ms: i64,
+    pub expires_at_ms: Option<i64>,
+}
+
+/// What a POST to a server would do with a given envelope, checked without
+/// starting or installing anything.
+#[derive(Debug, Clone, Default)]
+pub struct AcpValidation {
+    pub agent: Option<AgentId>,
+    pub exists: bool,
+    pub agent_installed: bool,
+    pub env_keys: Vec<String>,
+    pub problems: Vec<AcpValidationProblem>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AcpValidationProblem {
+    pub field: String,
+    pub message: String,
+}
+
+impl AcpValidation {
+    fn problem(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.problems.push(AcpValidationProblem {
+            field: field.into(),
+            message: message.into(),
+        });
+    }
+}
+
+/// Where the time went while bringing an agent process up for a server.
+#[derive(Debug, Clone, Copy)]
+pub struct AcpStartupTiming {
+    pub install_ms: u64,
+    pub resolve_ms: u64,
+    pub spawn_ms: u64,
+    /// The server adopted a standby from the warm pool.
+    pub warm: bool,
+}
+
+/// Phases of the most recent `session/prompt` round trip.
+#[derive(Debug, Clone)]
+pub struct AcpTurnTiming {
+    pub session_id: Option<String>,
+    pub started_at_ms: i64,
+    pub instance_ms: u64,
+    pub time_to_first_event_ms: Option<u64>,
+    pub duration_ms: u64,
+    pub stop_reason: Option<String>,
+    pub usage: Option<AcpTurnUsage>,
+}
+
+/// Result of approving a session's plan.
+#[derive(Debug, Clone)]
+pub struct AcpPlanApproval {
+    pub session_id: String,
+    pub approval: PlanApproval,
+    /// Stop reason of the build turn.
+    pub stop_reason: Option<String>,
+}
+
+/// How an interrupt ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptOutcome {
+    /// The session had no prompt in flight.
+    Idle,
+    /// The agent ended the turn after `session/cancel`.
+    Cancelled,
+    /// The agent ignored `session/cancel`, so its process was killed.
+    Killed,
+}
+
+impl InterruptOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Idle => "idle",
+            Self::Cancelled => "cancelled",
+            Self::Killed => "killed",
+        }
+    }
+}
+
+/// Result of retrying a session's last turn.
+#[derive(Debug, Clone)]
+pub struct AcpTurnRetry {
+    pub session_id: String,
+    pub retracted: RetractedTurn,
+    /// Stop reason of the new turn.
+    pub stop_reason: Option<String>,
+}
+
+/// The latest turn of a session, once it has finished.
+#[derive(Debug, Clone)]
+pub struct AcpTurnResult {
+    pub session_id: String,
+    pub turn: Option<u64>,
+    pub summary: TurnSummary,
+}
+
+/// What a server knows about one of its sessions.
+#[derive(Debug, Clone)]
+pub struct AcpSessionDetail {
+    pub server_id: String,
+    pub session_id: String,
+    pub record: SessionRecord,
+    /// Current ACP mode id, while the session is open.
+    pub mode_id: Option<String>,
+    pub prompt_in_flight: bool,
+    /// Unanswered `session/request_permission` requests.
+    pub pending_permissions: usize,
+    /// Unanswered agent requests of any other method.
+    pub pending_questions: usize,
+    /// Envelopes for the session still in the replay buffer.
+    pub buffered_events: usize,
+    /// The server's latest event id, to resume its stream after.
+    pub next_offset: u64,
+}
+
+/// Settings to change on an open session. Unset fields are left alone.
+#[derive(Debug, Clone, Default)]
+pub struct AcpSessionPatch {
+    pub mode_id: Option<String>,
+    pub model_id: Option<String>,
+    /// A permission mode as given in `_meta["sandboxagent.dev"]`.
+    pub permission_mode: Option<Value>,
+}
+
+/// Result of compacting one session.
+#[derive(Debug, Clone)]
+pub struct AcpCompaction {
+    pub session_id: String,
+    pub mode: CompactionMode,
+    pub before_tokens: u64,
+    pub after_tokens: u64,
+    /// The agent's summary, in summary mode.
+    pub summary: Option<String>,
+    pub stop_reason: Option<String>,
+}
+
+/// Token counts and cost an agent reported for a turn in the `usage` of its
+/// `session/prompt` result, or under the result's `_meta`.
+#[derive(Debug, Clone, Default)]
+pub struct AcpTurnUsage {
+    pub input_tokens: Option<u64>,
+    pub output_tokens: Option<u64>,
+    pub cached_read_tokens: Option<u64>,
+    pub cached_write_tokens: Option<u64>,
+    pub thought_tokens: Option<u64>,
+    pub total_tokens: Option<u64>,
+    pub cost: Option<f64>,
+    pub cost_currency: Option<String>,
+}
+
+impl AcpTurnUsage {
+    pub(crate) fn from_response(response: &Value) -> Option<Self> {
+        let usage = response
+            .pointer("/result/usage")
+            .or_else(|| response.pointer("/result/_meta/usage"))?
+            .as_object()?;
+        let count = |key: &str| usage.get(key).and_then(Value::as_u64);
+        Some(Self {
+            input_tokens: count("inputTokens"),
+            output_tokens: count("outputTokens"),
+            cached_read_tokens: count("cachedReadTokens"),
+            cached_write_tokens: count("cachedWriteTokens"),
+            thought_tokens: count("thoughtTokens"),
+            total_tokens: count("totalTokens"),
+            cost: usage
+                .get("cost")
+                .and_then(|cost| cost.get("amount"))
+                .and_then(Value::as_f64),
+            cost_currency: usage
+                .get("cost")
+                .and_then(|cost| cost.get("currency"))
+                .and_then(Value::as_str)
+                .map(ToOwned::to_owned),
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "inputTokens": self.input_tokens,
+            "outputTokens": self.output_tokens,
+            "cachedReadTokens": self.cached_read_tokens,
+            "cachedWriteTokens": self.cached_write_tokens,
+            "thoughtTokens": self.thought_tokens,
+            "totalTokens": self.total_tokens,
+            "cost": self.cost,
+            "costCurrency": self.cost_currency,
+        })
+    }
 }
 
 pub type PinBoxSseStream =
     std::pin::Pin<Box<dyn Stream<Item = Result<Event, std::convert::Infallible>> + Send>>;
 
+pub type SequencedPayloadStream = Pin<Box<dyn Stream<Item = (u64, Value)> + Send>>;
+
 impl AcpProxyRuntime {
-    pub fn new(agent_manager: Arc<AgentManager>) -> Self {
-        let require_preinstall = std::env::var("SANDBOX_AGENT_REQUIRE_PREINSTALL")
+    /// Builds the runtime from the resolved server config; see
+    /// [`ServerConfig::effective`].
+    pub fn new(agent_manager: Arc<AgentManager>, config: &ServerConfig) -> Self {
+        let require_preinstall = config.install.require_preinstall.unwrap_or(false);
+
+        let install_quota_bytes = std::env::var("SANDBOX_AGENT_INSTALL_QUOTA_BYTES")
             .ok()
-            .is_some_and(|value| {
-                let trimmed = value.trim();
-                trimmed == "1"
-                    || trimmed.eq_ignore_ascii_case("true")
-                    || trimmed.eq_ignore_ascii_case("yes")
-            });
+            .and_then(|raw| raw.trim().parse::<u64>().ok())
+            .filter(|quota| *quota > 0);
 
-        let request_timeout = duration_from_env_ms(
-            "SANDBOX_AGENT_ACP_REQUEST_TIMEOUT_MS",
-            Duration::from_millis(DEFAULT_REQUEST_TIMEOUT_MS),
+        let request_timeout = Duration::from_millis(
+            config
+                .limits
+                .acp_request_timeout_ms
+                .filter(|value| *value > 0)
+                .unwrap_or(DEFAULT_REQUEST_TIMEOUT_MS),
         );
 
+        let spawn_env_allowlist = match std::env::var("SANDBOX_AGENT_SPAWN_ENV_ALLOWLIST") {
+            Ok(raw) => raw
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect(),
+            Err(_) => DEFAULT_SPAWN_ENV_ALLOWLIST
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+        };
+
+        let mut raw_spill = RawSpill {
+            dir: std::env::var_os("SANDBOX_AGENT_RAW_SPILL_DIR").map(PathBuf::from),
+            ..RawSpill::default()
+        };
+        if let Some(max_bytes) = config
+            .limits
+            .max_raw_bytes
+            .and_then(|max| usize::try_from(max).ok())
+        {
+            raw_spill.max_bytes = max_bytes;
+        }
+
+        let mut artifact_spill = ArtifactSpill {
+            dir: Some(
+                std::env::var_os("SANDBOX_AGENT_ARTIFACTS_DIR")
+                    .filter(|value| !value.is_empty())
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| std::env::temp_dir().join("sandbox-agent-artifacts")),
+            ),
+            ..ArtifactSpill::default()
+        };
+        if let Some(min_bytes) = std::env::var("SANDBOX_AGENT_ARTIFACT_MIN_BYTES")
+            .ok()
+            .and_then(|raw| raw.trim().parse::<usize>().ok())
+        {
+            artifact_spill.min_bytes = min_bytes;
+        }
+        if let Some(preview_bytes) = std::env::var("SANDBOX_AGENT_ARTIFACT_PREVIEW_BYTES")
+            .ok()
+            .and_then(|raw| raw.trim().parse::<usize>().ok())
+        {
+            artifact_spill.preview_bytes = preview_bytes;
+        }
+
         Self {
             inner: Arc::new(AcpProxyRuntimeInner {
                 agent_manager,
                 require_preinstall,
+                install_quota_bytes,
                 request_timeout,
+                turn_limits: TurnLimits::from_config(&config.limits),
+                spawn_env_allowlist,
+                agent_env: config.agent_env(),
+                session_defaults: config.session_defaults(),
+                model_routes: ModelRoutes::from_aliases(&config.models),
+                raw_spill,
+                artifact_spill,
+                prompt_policy: PromptPolicy::from_env(),
+                prompt_limit: PromptLimit::new(config.limits.max_prompt_bytes),
+                cdc: CdcFeed::from_env(),
+                compat: AgentCompat::from_env(),
+                session_homes: SessionHomes::from_env(),
+                turn_logs: TurnLogs::from_env(),
+                attachments: Attachments::from_env(),
+                claude_settings: ClaudeSettings::from_env(),
+                turn_counts: std::sync::Mutex::new(HashMap::new()),
+                sessions: std::sync::Mutex::new(HashMap::new()),
+                secrets: Mutex::new(SecretVault::new()),
+                idempotency: IdempotencyCache::from_env(),
+                unattended: UnattendedPolicy::from_config(&config.limits),
+                warm_pool: WarmPool::from_env(),
                 instances: RwLock::new(HashMap::new()),
                 instance_locks: Mutex::new(HashMap::new()),
                 install_locks: Mutex::new(HashMap::new()),
+                created: broadcast::channel(CREATED_CHANNEL_SIZE).0,
             }),
         }
     }
 
     pub async fn list_instances(&self) -> Vec<AcpServerInstanceInfo> {
-        let mut infos = self
+        let instances = self
             .inner
             .instances
             .read()
             .await
             .values()
-            .map(|instance| AcpServerInstanceInfo {
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut infos = Vec::with_capacity(instances.len());
+        for instance in instances {
+            let last_turn_timing = instance
+                .last_turn_timing
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone();
+            infos.push(AcpServerInstanceInfo {
                 server_id: instance.server_id.clone(),
                 agent: instance.agent,
                 created_at_ms: instance.created_at_ms,
-            })
-            .collect::<Vec<_>>();
+                startup_timing: instance.startup_timing,
+                last_turn_timing,
+                pipeline: instance.runtime.pipeline_stats().await,
+            });
+        }
         infos.sort_by(|left, right| left.server_id.cmp(&right.server_id));
         infos
     }
 
+    pub async fn has_server(&self, server_id: &str) -> bool {
+        self.inner.instances.read().await.contains_key(server_id)
+    }
+
+    /// Id, agent and latest envelope sequence of every running server.
+    pub async fn server_sequences(&self) -> Vec<(String, AgentId, u64)> {
+        self.inner
+            .instances
+            .read()
+            .await
+            .values()
+            .map(|instance| {
+                (
+                    instance.server_id.clone(),
+                    instance.agent,
+                    instance.runtime.last_sequence(),
+                )
+            })
+            .collect()
+    }
+
+    /// Agent requests on `server_id` still waiting for a client response,
+    /// oldest first, optionally only those for one session.
+    pub async fn pending_requests(
+        &self,
+        server_id: &str,
+        session_id: Option<&str>,
+    ) -> Result<Vec<AcpPendingRequest>, SandboxError> {
+        let instance = self.get_instance(server_id).await?;
+        let mut requests = instance
+            .pending_requests
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .filter(|request| session_id.is_none() || request.session_id.as_deref() == session_id)
+            .cloned()
+            .collect::<Vec<_>>();
+        requests.sort_by_key(|request| request.sequence);
+        Ok(requests)
+    }
+
+    /// Receives the id and agent of each server created from now on.
+    pub fn subscribe_created(&self) -> broadcast::Receiver<(String, AgentId)> {
+        self.inner.created.subscribe()
+    }
+
+    /// Forward `payload` to `server_id`, starting the server on
+    /// `bootstrap_agent` if it does not exist yet. When a bootstrap POST
+    /// lists `fallbackAgents` and the agent cannot start, the server is
+    /// started on the next one and the payload sent again.
     pub async fn post(
         &self,
         server_id: &str,
         bootstrap_agent: Option<AgentId>,
         payload: Value,
+    ) -> Result<ProxyPostOutcome, SandboxError> {
+        let fallback_agents = fallback_agents_from_payload(&payload)?;
+        let fresh = !self.inner.instances.read().await.contains_key(server_id);
+        let Some(mut agent) = bootstrap_agent.filter(|_| fresh && !fallback_agents.is_empty())
+        else {
+            return self.post_to(server_id, bootstrap_agent, payload, &[]).await;
+        };
+        let mut fallback_agents = fallback_agents
+            .into_iter()
+            .filter(|fallback| *fallback != agent)
+            .collect::<VecDeque<_>>();
+        let mut failovers = Vec::new();
+        loop {
+            let result = self
+                .post_to(server_id, Some(agent), payload.clone(), &failovers)
+                .await;
+            let failover = match (&result, fallback_agents.front()) {
+                (Err(err), Some(next)) => AgentFailover::after(agent, *next, err),
+                _ => None,
+            };
+            let Some(failover) = failover else {
+                return result;
+            };
+            tracing::warn!(
+                server_id = server_id,
+                from = failover.from.as_str(),
+                to = failover.to.as_str(),
+                reason = failover.reason,
+                message = %failover.message,
+                "acp_proxy: agent failed to start; failing over"
+            );
+            self.discard_failed_instance(server_id, agent).await;
+            agent = failover.to;
+            fallback_agents.pop_front();
+            failovers.push(failover);
+        }
+    }
+
+    async fn post_to(
+        &self,
+        server_id: &str,
+        bootstrap_agent: Option<AgentId>,
+        mut payload: Value,
+        failovers: &[AgentFailover],
     ) -> Result<ProxyPostOutcome, SandboxError> {
         let method: String = payload
             .get("method")
@@ -121,9 +673,32 @@ impl AcpProxyRuntime {
             "acp_proxy: POST received"
         );
 
-        let start = std::time::Instant::now();
+        if method == "session/prompt" {
+            self.inner.prompt_limit.check(&payload)?;
+            self.inner.attachments.check_inline(&payload)?;
+        }
+        let spawn_env = spawn_env_from_payload(&payload)?;
+        let permission_mode = PermissionMode::from_payload(&payload)?;
+        let codex_options = CodexOptions::from_payload(&payload)?;
+        let new_session_limits = (method == "session/new")
+            .then(|| self.inner.turn_limits.with_overrides(&payload))
+            .transpose()?;
+        let new_session_unattended = (method == "session/new")
+            .then(|| self.inner.unattended.with_overrides(&payload))
+            .transpose()?;
+        let new_session_defaults = (method == "session/new")
+            .then(|| SessionDefaults::from_payload(&payload))
+            .transpose()?;
+        let start = Instant::now();
         let instance = self
-            .get_or_create_instance(server_id, bootstrap_agent)
+            .get_or_create_instance(
+                server_id,
+                bootstrap_agent,
+                spawn_env,
+                permission_mode,
+                codex_options,
+                failovers,
+            )
             .await?;
         let instance_elapsed = start.elapsed();
 
@@ -134,8 +709,196 @@ impl AcpProxyRuntime {
             "acp_proxy: instance resolved"
         );
 
-        match instance.runtime.post(payload).await {
-            Ok(PostOutcome::Response(value)) => {
+        let new_session_defaults = new_session_defaults
+            .map(|defaults| self.resolve_session_defaults(defaults, instance.agent))
+            .transpose()?;
+
+        let mut claude_settings_file = None;
+        if matches!(method.as_str(), "session/new" | "session/load") {
+            instance.permissions.apply_to_session(&mut payload);
+            claude_settings_file = self
+                .inner
+                .claude_settings
+                .apply(instance.agent, &server_dir_name(server_id), &mut payload)
+                .await?;
+        }
+
+        if method == "session/prompt" && self.inner.prompt_policy.is_enabled() {
+            let report = self
+                .inner
+                .prompt_policy
+                .apply(server_id, &mut payload)
+                .await
+                .inspect_err(|err| {
+                    tracing::warn!(server_id = server_id, error = %err, "acp_proxy: prompt blocked by policy");
+                })?;
+            if !report.matched.is_empty() {
+                tracing::warn!(
+                    server_id = server_id,
+                    rules = ?report.matched,
+                    "acp_proxy: prompt matched content policy"
+                );
+                instance
+                    .runtime
+                    .publish_notification(json!({
+                        "jsonrpc": "2.0",
+                        "method": "_adapter/policy_warning",
+                        "params": {
+                            "sessionId": payload.pointer("/params/sessionId").cloned(),
+                            "rules": report.matched,
+                        }
+                    }))
+                    .await;
+            }
+        }
+
+        if method == "session/prompt" {
+            let capabilities = *instance
+                .prompt_capabilities
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let spilled = self
+                .inner
+                .attachments
+                .spill_inline(&server_dir_name(server_id), &mut payload, capabilities)
+                .await?;
+            if spilled > 0 {
+                tracing::info!(
+                    server_id = server_id,
+                    spilled = spilled,
+                    "acp_proxy: inline attachments written to files"
+                );
+            }
+        }
+
+        let loaded_session_id = (method == "session/load")
+            .then(|| payload.pointer("/params/sessionId").and_then(Value::as_str))
+            .flatten()
+            .map(ToOwned::to_owned);
+        let prompt_session_id = (method == "session/prompt").then(|| {
+            payload
+                .pointer("/params/sessionId")
+                .and_then(Value::as_str)
+                .map(ToOwned::to_owned)
+        });
+        if let Some(id) = payload
+            .get("id")
+            .filter(|_| payload.get("method").is_none())
+        {
+            instance
+                .pending_requests
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .remove(&id.to_string());
+        }
+        // A client response carrying `outcome` answers `session/request_permission`.
+        let permission_resolution = payload
+            .get("method")
+            .is_none()
+            .then(|| payload.pointer("/result/outcome"))
+            .flatten()
+            .map(|outcome| json!({ "requestId": payload.get("id"), "outcome": outcome }));
+        let turn_limits = match &prompt_session_id {
+            Some(session_id) => session_id
+                .as_ref()
+                .and_then(|session_id| {
+                    instance
+                        .session_limits
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .get(session_id)
+                        .copied()
+                })
+                .unwrap_or(self.inner.turn_limits),
+            None => TurnLimits::default(),
+        };
+        let started_at_ms = now_ms();
+        let post_start = Instant::now();
+
+        let _active_prompt = prompt_session_id
+            .as_ref()
+            .and_then(Option::as_deref)
+            .map(|session_id| instance.begin_prompt(session_id));
+        let turn = prompt_session_id.as_ref().map(|_| {
+            let mut counts = self
+                .inner
+                .turn_counts
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let count = counts.entry(server_id.to_string()).or_insert(0);
+            *count += 1;
+            *count
+        });
+        let planning = prompt_session_id
+            .clone()
+            .flatten()
+            .filter(|session_id| instance.in_plan_mode(session_id));
+        let set_mode = (method == "session/set_mode")
+            .then(|| {
+                let session_id = payload.pointer("/params/sessionId")?.as_str()?;
+                let mode_id = payload.pointer("/params/modeId")?.as_str()?;
+                Some((session_id.to_string(), mode_id.to_string()))
+            })
+            .flatten();
+        let set_model = (method == "session/set_model")
+            .then(|| {
+                let session_id = payload.pointer("/params/sessionId")?.as_str()?;
+                let model_id = payload.pointer("/params/modelId")?.as_str()?;
+                Some((session_id.to_string(), model_id.to_string()))
+            })
+            .flatten();
+        let turn_ref = prompt_session_id.as_ref().map(|session_id| {
+            json!({ "sessionId": session_id, "requestId": payload.get("id"), "turn": turn })
+        });
+        let turn_capture = turn.and_then(|turn| {
+            self.inner
+                .turn_logs
+                .start(&server_dir_name(server_id), turn, &instance.runtime)
+        });
+        if let Some(turn_ref) = &turn_ref {
+            instance
+                .publish_turn_event(
+                    "_adapter/turn_started",
+                    turn_ref,
+                    json!({ "startedAtMs": started_at_ms }),
+                )
+                .await;
+        }
+        // Taken after `_adapter/turn_started` so it is not mistaken for the
+        // agent's first event.
+        let sequence_before = instance.runtime.last_sequence();
+        if let (Some(Some(session_id)), Some(prompt)) =
+            (&prompt_session_id, payload.pointer("/params/prompt"))
+        {
+            instance
+                .last_prompts
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(
+                    session_id.clone(),
+                    LastPrompt {
+                        prompt: prompt.clone(),
+                        turn,
+                        first_sequence: sequence_before,
+                    },
+                );
+            self.update_session(server_id, session_id, |record| {
+                record.prompts += 1;
+                record.last_prompt_at_ms = Some(started_at_ms);
+            });
+        }
+        let outcome = match &prompt_session_id {
+            Some(session_id) => {
+                self.post_prompt(&instance, session_id.as_deref(), turn_limits, payload)
+                    .await
+            }
+            None => instance.runtime.post(payload).await,
+        };
+        if let Some(capture) = turn_capture {
+            capture.finish().await;
+        }
+        match outcome {
+            Ok(PostOutcome::Response(mut value)) => {
                 let total_ms = start.elapsed().as_millis() as u64;
                 tracing::info!(
                     server_id = server_id,
@@ -144,6 +907,139 @@ impl AcpProxyRuntime {
                     total_ms = total_ms,
                     "acp_proxy: POST → response"
                 );
+                if let (Some((defaults, fallbacks)), None) =
+                    (new_session_defaults, value.get("error"))
+                {
+                    apply_session_defaults(&instance, &defaults, &mut value).await;
+                    if let Some(session_id) =
+                        value.pointer("/result/sessionId").and_then(Value::as_str)
+                    {
+                        instance.set_model_fallbacks(session_id, fallbacks);
+                    }
+                }
+                if let Some(session_id) = prompt_session_id {
+                    let first_event = instance
+                        .runtime
+                        .first_notification_after(sequence_before)
+                        .await;
+                    let timing = AcpTurnTiming {
+                        session_id,
+                        started_at_ms,
+                        instance_ms: instance_elapsed.as_millis() as u64,
+                        time_to_first_event_ms: first_event
+                            .map(|at| at.saturating_duration_since(post_start).as_millis() as u64),
+                        duration_ms: post_start.elapsed().as_millis() as u64,
+                        stop_reason: value
+                            .pointer("/result/stopReason")
+                            .and_then(Value::as_str)
+                            .map(ToOwned::to_owned),
+                        usage: AcpTurnUsage::from_response(&value),
+                    };
+                    instance.record_turn_timing(timing).await;
+                    if value.get("error").is_none() {
+                        self.note_compatible(&instance);
+                    }
+                }
+                if let Some(turn_ref) = &turn_ref {
+                    match value.get("error") {
+                        Some(error) => {
+                            instance
+                                .publish_turn_event(
+                                    "_adapter/turn_failed",
+                                    turn_ref,
+                                    json!({ "error": error }),
+                                )
+                                .await
+                        }
+                        None => {
+                            instance
+                                .publish_turn_event(
+                                    "_adapter/turn_completed",
+                                    turn_ref,
+                                    json!({ "stopReason": value.pointer("/result/stopReason") }),
+                                )
+                                .await
+                        }
+                    }
+                }
+                if let Some(session_id) = &planning {
+                    self.capture_plan(&instance, server_id, session_id, turn, sequence_before)
+                        .await;
+                }
+                if let (Some((session_id, mode_id)), None) = (set_mode, value.get("error")) {
+                    instance
+                        .session_modes
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .insert(session_id, mode_id);
+                }
+                if method == "initialize" {
+                    if let Some(result) = value.get("result") {
+                        *instance
+                            .prompt_capabilities
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner) =
+                            Some(PromptCapabilities::from_initialize(result));
+                    }
+                }
+                if let Some(session_id) = value.pointer("/result/sessionId") {
+                    if let (Some(limits), Some(session_id)) =
+                        (new_session_limits, session_id.as_str())
+                    {
+                        instance
+                            .session_limits
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .insert(session_id.to_string(), limits);
+                    }
+                    if let (Some(policy), Some(session_id)) =
+                        (new_session_unattended, session_id.as_str())
+                    {
+                        instance
+                            .session_unattended
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .insert(session_id.to_string(), policy);
+                    }
+                    if let (Some(mode_id), Some(session_id)) = (
+                        value
+                            .pointer("/result/modes/currentModeId")
+                            .and_then(Value::as_str),
+                        session_id.as_str(),
+                    ) {
+                        instance
+                            .session_modes
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .insert(session_id.to_string(), mode_id.to_string());
+                    }
+                    if method == "session/new" {
+                        self.inner.cdc.record(
+                            "session.created",
+                            server_id,
+                            json!({ "sessionId": session_id }),
+                        );
+                    }
+                }
+                let started_session = match method.as_str() {
+                    "session/new" => value.pointer("/result/sessionId").and_then(Value::as_str),
+                    "session/load" if value.get("error").is_none() => loaded_session_id.as_deref(),
+                    _ => None,
+                };
+                if let Some(session_id) = started_session {
+                    self.record_session_started(&instance, session_id, value.get("result"));
+                }
+                if let Some(path) = claude_settings_file.take() {
+                    self.inner
+                        .claude_settings
+                        .settle(&server_dir_name(server_id), started_session, path)
+                        .await;
+                }
+                if let (Some((session_id, model_id)), None) = (set_model, value.get("error")) {
+                    self.update_session(server_id, &session_id, |record| {
+                        record.model_id = Some(model_id);
+                    });
+                }
                 let value = annotate_agent_error(instance.agent, value);
                 Ok(ProxyPostOutcome::Response(value))
             }
@@ -153,6 +1049,11 @@ impl AcpProxyRuntime {
                     method = method,
                     "acp_proxy: POST → accepted"
                 );
+                if let Some(resolution) = permission_resolution {
+                    self.inner
+                        .cdc
+                        .record("permission.resolved", server_id, resolution);
+                }
                 Ok(ProxyPostOutcome::Accepted)
             }
             Err(err) => {
@@ -165,29 +1066,786 @@ impl AcpProxyRuntime {
                     error = %err,
                     "acp_proxy: POST → error"
                 );
-                Err(map_adapter_error(err))
+                if let Some(turn_ref) = &turn_ref {
+                    instance
+                        .publish_turn_event(
+                            "_adapter/turn_failed",
+                            turn_ref,
+                            json!({ "error": { "message": err.to_string() } }),
+                        )
+                        .await;
+                }
+                if let AdapterError::Exited(exit) = &err {
+                    if classify_exit(instance.agent, exit) == AgentExitReason::UsageError {
+                        let detail =
+                            exit.stderr.last().cloned().unwrap_or_else(|| {
+                                "agent rejected its launch arguments".to_string()
+                            });
+                        self.note_incompatibility(
+                            instance.agent,
+                            IncompatibilityReason::UsageError,
+                            detail,
+                        );
+                    }
+                }
+                Err(map_adapter_error(instance.agent, err))
             }
         }
     }
 
+    /// Forward a `session/prompt` while enforcing `limits`. When one fires,
+    /// the turn is reported with `_adapter/turn_timeout` and a `turn.timeout`
+    /// change record, and the agent is sent `session/cancel`. If it still has
+    /// not answered after `TURN_CANCEL_GRACE`, its process is killed and the
+    /// server removed, so the next bootstrap POST starts a fresh process that
+    /// can `session/load` the session.
+    /// Send a prompt with [`Self::post_turn`]. While the agent answers with
+    /// a rate-limit error and the session has fallback models left, move
+    /// the session to the next one and send the prompt again.
+    async fn post_prompt(
+        &self,
+        instance: &Arc<ProxyInstance>,
+        session_id: Option<&str>,
+        limits: TurnLimits,
+        payload: Value,
+    ) -> Result<PostOutcome, AdapterError> {
+        loop {
+            let outcome = self
+                .post_turn(instance, session_id, limits, payload.clone())
+                .await;
+            let Some(session_id) = session_id else {
+                return outcome;
+            };
+            let error = match &outcome {
+                Ok(PostOutcome::Response(response)) => response
+                    .get("error")
+                    .filter(|error| model_routing::is_rate_limited(error))
+                    .cloned(),
+                _ => None,
+            };
+            let Some(error) = error else {
+                return outcome;
+            };
+            let next = instance
+                .model_fallbacks
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get_mut(session_id)
+                .filter(|models| !models.is_empty())
+                .map(|models| models.remove(0));
+            let Some(model_id) = next else {
+                return outcome;
+            };
+            if !self
+                .fall_back_to_model(instance, session_id, &model_id, error)
+                .await
+            {
+                return outcome;
+            }
+        }
+    }
+
+    /// Switch `session_id` to `model_id` after `error`, and record the
+    /// switch with an `_adapter/model_fallback` notification and a
+    /// `session.model_fallback` change record.
+    async fn fall_back_to_model(
+        &self,
+        instance: &ProxyInstance,
+        session_id: &str,
+        model_id: &str,
+        error: Value,
+    ) -> bool {
+        let rejection = match instance
+            .runtime
+            .post(json!({
+                "jsonrpc": "2.0",
+                "id": format!("sandboxagent-set-model-{}", now_ms()),
+                "method": "session/set_model",
+                "params": { "sessionId": session_id, "modelId": model_id }
+            }))
+            .await
+        {
+            Ok(PostOutcome::Response(response)) => response.get("error").map(ToString::to_string),
+            Ok(PostOutcome::Accepted) => None,
+            Err(err) => Some(err.to_string()),
+        };
+        if let Some(err) = rejection {
+            tracing::warn!(
+                server_id = %instance.server_id,
+                session_id = session_id,
+                model_id = model_id,
+                error = %err,
+                "acp_proxy: agent rejected a fallback model"
+            );
+            return false;
+        }
+
+        let mut from = None;
+        self.update_session(&instance.server_id, session_id, |record| {
+            from = record.model_id.replace(model_id.to_string());
+        });
+        let record = json!({
+            "sessionId": session_id,
+            "from": from,
+            "to": model_id,
+            "error": error,
+        });
+        tracing::info!(
+            server_id = %instance.server_id,
+            session_id = session_id,
+            from = ?from,
+            to = model_id,
+            "acp_proxy: prompt rate-limited; falling back to the next model"
+        );
+        instance
+            .runtime
+            .publish_notification(json!({
+                "jsonrpc": "2.0",
+                "method": MODEL_FALLBACK_METHOD,
+                "params": record.clone(),
+            }))
+            .await;
+        self.inner
+            .cdc
+            .record("session.model_fallback", &instance.server_id, record);
+        true
+    }
+
+    async fn post_turn(
+        &self,
+        instance: &Arc<ProxyInstance>,
+        session_id: Option<&str>,
+        limits: TurnLimits,
+        payload: Value,
+    ) -> Result<PostOutcome, AdapterError> {
+        if !limits.is_enabled() {
+            return instance.runtime.post(payload).await;
+        }
+
+        let started = Instant::now();
+        let request_timeout = limits
+            .turn
+            .map_or(self.inner.request_timeout, |turn| turn + TURN_CANCEL_GRACE);
+        let post = instance.runtime.post_with_timeout(payload, request_timeout);
+        tokio::pin!(post);
+        let kind = tokio::select! {
+            outcome = &mut post => return outcome,
+            kind = limits.watch(&instance.runtime, started) => kind,
+        };
+
+        let limit = match kind {
+            TurnTimeoutKind::Turn => limits.turn,
+            TurnTimeoutKind::Idle => limits.idle,
+        };
+        let details = json!({
+            "sessionId": session_id,
+            "reason": kind.as_str(),
+            "elapsedMs": started.elapsed().as_millis() as u64,
+            "limitMs": limit.map(|limit| limit.as_millis() as u64),
+        });
+        tracing::warn!(
+            server_id = %instance.server_id,
+            session_id = session_id,
+            reason = kind.as_str(),
+            "acp_proxy: turn exceeded its limit; cancelling"
+        );
+        instance
+            .runtime
+            .publish_notification(json!({
+                "jsonrpc": "2.0",
+                "method": "_adapter/turn_timeout",
+                "params": details.clone(),
+            }))
+            .await;
+        self.inner
+            .cdc
+            .record("turn.timeout", &instance.server_id, details);
+
+        if let Some(session_id) = session_id {
+            let _ = instance
+                .runtime
+                .post(json!({
+                    "jsonrpc": "2.0",
+                    "method": "session/cancel",
+                    "params": { "sessionId": session_id },
+                }))
+                .await;
+        }
+        if let Ok(outcome) = tokio::time::timeout(TURN_CANCEL_GRACE, &mut post).await {
+            return outcome;
+        }
+
+        tracing::warn!(
+            server_id = %instance.server_id,
+            session_id = session_id,
+            "acp_proxy: agent ignored session/cancel; killing process"
+        );
+        self.kill_instance(instance, SessionEndReason::Timeout)
+            .await;
+        Err(AdapterError::Timeout)
+    }
+
+    /// Stop `instance`'s process and remove its server, unless the server id
+    /// was already reused for a new process. Its sessions end with `reason`.
+    async fn kill_instance(&self, instance: &Arc<ProxyInstance>, reason: SessionEndReason) {
+        *instance
+            .stopped_by
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(reason);
+        let removed = {
+            let mut instances = self.inner.instances.write().await;
+            match instances.get(&instance.server_id) {
+                Some(current) if Arc::ptr_eq(current, instance) => {
+                    instances.remove(&instance.server_id)
+                }
+                _ => None,
+            }
+        };
+        instance.runtime.shutdown().await;
+        if removed.is_some() {
+            self.inner
+                .cdc
+                .record("server.closed", &instance.server_id, json!({}));
+        }
+    }
+
+    /// Interrupt the turn `session_id` has in flight. The agent is sent
+    /// `session/cancel`; if the turn has not ended after `TURN_CANCEL_GRACE`,
+    /// its process is killed and the server removed, as when a turn limit
+    /// fires.
+    pub async fn interrupt(
+        &self,
+        server_id: &str,
+        session_id: &str,
+    ) -> Result<InterruptOutcome, SandboxError> {
+        let instance = self.get_instance(server_id).await?;
+        let in_flight = |instance: &ProxyInstance| {
+            instance
+                .active_prompts
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .contains_key(session_id)
+        };
+        if !in_flight(&instance) {
+            return Ok(InterruptOutcome::Idle);
+        }
+        let _ = instance
+            .runtime
+            .post(json!({
+                "jsonrpc": "2.0",
+                "method": "session/cancel",
+                "params": { "sessionId": session_id },
+            }))
+            .await;
+
+        let ended = tokio::time::timeout(TURN_CANCEL_GRACE, async {
+            loop {
+                let finished = instance.prompts_finished.notified();
+                tokio::pin!(finished);
+                finished.as_mut().enable();
+                if !in_flight(&instance) {
+                    return;
+                }
+                finished.await;
+            }
+        })
+        .await
+        .is_ok();
+        let outcome = if ended {
+            InterruptOutcome::Cancelled
+        } else {
+            tracing::warn!(
+                server_id = server_id,
+                session_id = session_id,
+                "acp_proxy: agent ignored session/cancel; killing process"
+            );
+            self.kill_instance(&instance, SessionEndReason::Terminated)
+                .await;
+            InterruptOutcome::Killed
+        };
+        self.inner.cdc.record(
+            "turn.interrupted",
+            server_id,
+            json!({ "sessionId": session_id, "outcome": outcome.as_str() }),
+        );
+        Ok(outcome)
+    }
+
+    /// Wait up to `wait` for `session_id`'s prompt to finish, then summarize
+    /// its latest turn from the buffered events.
+    pub async fn turn_result(
+        &self,
+        server_id: &str,
+        session_id: &str,
+        wait: Duration,
+    ) -> Result<AcpTurnResult, SandboxError> {
+        let instance = self.get_instance(server_id).await?;
+        let in_flight = |instance: &ProxyInstance| {
+            instance
+                .active_prompts
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .contains_key(session_id)
+        };
+        let finished = tokio::time::timeout(wait, async {
+            loop {
+                let finished = instance.prompts_finished.notified();
+                tokio::pin!(finished);
+                finished.as_mut().enable();
+                if !in_flight(&instance) {
+                    return;
+                }
+                finished.await;
+            }
+        })
+        .await
+        .is_ok();
+        if !finished {
+            return Err(SandboxError::Timeout {
+                message: Some(format!(
+                    "session '{session_id}' still has a prompt in flight after {}ms",
+                    wait.as_millis()
+                )),
+            });
+        }
+        let last = instance
+            .last_prompts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(session_id)
+            .cloned()
+            .ok_or_else(|| SandboxError::SessionNotFound {
+                session_id: format!("{server_id}/sessions/{session_id}/turns/last"),
+            })?;
+        let events = self
+            .poll_events(
+                server_id,
+                Some(last.first_sequence),
+                usize::MAX,
+                Duration::ZERO,
+                false,
+            )
+            .await?;
+        Ok(AcpTurnResult {
+            session_id: session_id.to_string(),
+            turn: last.turn,
+            summary: turn_result::summarize(&events, session_id),
+        })
+    }
+
+    /// Retract the last turn of `session_id` with an
+    /// `_adapter/turn_retracted` notification and a `turn.retracted` change,
+    /// then send its prompt again, with the text replaced by `text` when set.
+    pub async fn retry_turn(
+        &self,
+        server_id: &str,
+        session_id: &str,
+        text: Option<String>,
+    ) -> Result<AcpTurnRetry, SandboxError> {
+        if text.as_deref().is_some_and(|text| text.trim().is_empty()) {
+            return Err(SandboxError::InvalidRequest {
+                message: "text must not be empty".to_string(),
+            });
+        }
+        let instance = self.get_instance(server_id).await?;
+        if instance
+            .active_prompts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains_key(session_id)
+        {
+            return Err(SandboxError::Conflict {
+                message: format!("session '{session_id}' has a prompt in flight"),
+            });
+        }
+        let last = instance
+            .last_prompts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(session_id)
+            .cloned()
+            .ok_or_else(|| SandboxError::SessionNotFound {
+                session_id: format!("{server_id}/sessions/{session_id}/turns/last"),
+            })?;
+
+        let retracted = RetractedTurn {
+            turn: last.turn,
+            first_sequence: last.first_sequence,
+            last_sequence: instance.runtime.last_sequence(),
+        };
+        let record = retracted.record(session_id);
+        instance
+            .runtime
+            .publish_notification(json!({
+                "jsonrpc": "2.0",
+                "method": retry::TURN_RETRACTED_METHOD,
+                "params": record,
+            }))
+            .await;
+        self.inner.cdc.record("turn.retracted", server_id, record);
+
+        let result = self
+            .post_expect_result(
+                server_id,
+                None,
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": format!("sandboxagent-retry-{}", now_ms()),
+                    "method": "session/prompt",
+                    "params": {
+                        "sessionId": session_id,
+                        "prompt": retry::edited_prompt(&last.prompt, text.as_deref()),
+                    }
+                }),
+            )
+            .await?;
+        Ok(AcpTurnRetry {
+            session_id: session_id.to_string(),
+            retracted,
+            stop_reason: result
+                .get("stopReason")
+                .and_then(Value::as_str)
+                .map(ToOwned::to_owned),
+        })
+    }
+
+    pub(crate) async fn post_expect_result(
+        &self,
+        server_id: &str,
+        agent: Option<AgentId>,
+        payload: Value,
+    ) -> Result<Value, SandboxError> {
+        let method = payload
+            .get("method")
+            .and_then(Value::as_str)
+            .unwrap_or("<none>")
+            .to_string();
+        match self.post(server_id, agent, payload).await? {
+            ProxyPostOutcome::Response(value) => {
+                if let Some(error) = value.get("error") {
+                    return Err(SandboxError::UpstreamAgentError {
+                        message: format!("agent rejected {method}: {error}"),
+                    });
+                }
+                Ok(value.get("result").cloned().unwrap_or(Value::Null))
+            }
+            ProxyPostOutcome::Accepted => Err(SandboxError::UpstreamAgentNoResponse {
+                message: format!("agent did not return a response for {method}"),
+            }),
+        }
+    }
+
+    /// SSE stream of envelopes for `server_id` that pass `filter`. Events
+    /// are named `message` unless `named_events` is set, which names them
+    /// after the envelope's method, or `response` for responses.
     pub async fn sse(
         &self,
         server_id: &str,
         last_event_id: Option<u64>,
+        named_events: bool,
+        include_logs: bool,
+        filter: EventFilter,
     ) -> Result<PinBoxSseStream, SandboxError> {
+        let stream = self
+            .sequenced_stream(server_id, last_event_id, include_logs)
+            .await?
+            .filter(move |(_, payload)| std::future::ready(filter.matches(payload)))
+            .map(move |(sequence, payload)| {
+                let name = if named_events {
+                    payload
+                        .get("method")
+                        .and_then(Value::as_str)
+                        .unwrap_or("response")
+                } else {
+                    "message"
+                };
+                Ok(Event::default()
+                    .event(name)
+                    .id(sequence.to_string())
+                    .data(payload.to_string()))
+            });
+        Ok(Box::pin(stream))
+    }
+
+    /// Envelopes for `server_id` with their sequence numbers, replaying
+    /// buffered ones after `last_event_id` first. `_adapter/log` stderr
+    /// diagnostics are skipped unless `include_logs` is set.
+    pub async fn sequenced_stream(
+        &self,
+        server_id: &str,
+        last_event_id: Option<u64>,
+        include_logs: bool,
+    ) -> Result<SequencedPayloadStream, SandboxError> {
         let instance = self.get_instance(server_id).await?;
-        let stream = instance.runtime.clone().sse_stream(last_event_id).await;
+        let stream = instance
+            .runtime
+            .clone()
+            .sequenced_stream(last_event_id)
+            .await
+            .filter(move |(_, payload)| {
+                std::future::ready(include_logs || !is_log_envelope(payload))
+            });
         Ok(Box::pin(stream))
     }
 
+    /// Up to `limit` buffered envelopes after `offset`. When none are
+    /// buffered yet, waits up to `wait` for the first one to arrive.
+    pub async fn poll_events(
+        &self,
+        server_id: &str,
+        offset: Option<u64>,
+        limit: usize,
+        wait: Duration,
+        include_logs: bool,
+    ) -> Result<Vec<(u64, Value)>, SandboxError> {
+        self.poll_filtered_events(
+            server_id,
+            offset,
+            limit,
+            wait,
+            include_logs,
+            &EventFilter::default(),
+        )
+        .await
+    }
+
+    /// [`Self::poll_events`] counting and waiting only for envelopes that
+    /// pass `filter`.
+    pub(crate) async fn poll_filtered_events(
+        &self,
+        server_id: &str,
+        offset: Option<u64>,
+        limit: usize,
+        wait: Duration,
+        include_logs: bool,
+        filter: &EventFilter,
+    ) -> Result<Vec<(u64, Value)>, SandboxError> {
+        let mut stream = self
+            .sequenced_stream(server_id, offset, include_logs)
+            .await?
+            .filter(|(_, payload)| std::future::ready(filter.matches(payload)));
+        let mut events = Vec::new();
+        while events.len() < limit {
+            match stream.next().now_or_never() {
+                Some(Some(event)) => events.push(event),
+                _ => break,
+            }
+        }
+        if events.is_empty() && !wait.is_zero() {
+            if let Ok(Some(event)) = tokio::time::timeout(wait, stream.next()).await {
+                events.push(event);
+            }
+        }
+        Ok(events)
+    }
+
+    /// Run the checks a POST to `server_id` would run and collect every
+    /// problem instead of stopping at the first.
+    pub async fn validate(
+        &self,
+        server_id: &str,
+        bootstrap_agent: Option<&str>,
+        payload: &Value,
+    ) -> AcpValidation {
+        let mut validation = AcpValidation::default();
+
+        if !payload.is_object() {
+            validation.problem("body", "request body must be a JSON-RPC object");
+        } else {
+            if payload.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+                validation.problem("jsonrpc", "must be \"2.0\"");
+            }
+            if payload
+                .get("method")
+                .is_some_and(|method| !method.is_string())
+            {
+                validation.problem("method", "must be a string");
+            }
+            if payload.get("method").is_none() && payload.get("id").is_none() {
+                validation.problem("method", "envelope needs a method or an id");
+            }
+        }
+
+        let requested = match bootstrap_agent {
+            Some(raw) => match AgentId::parse(raw) {
+                Some(agent) => Some(agent),
+                None => {
+                    validation.problem("agent", format!("unsupported agent '{raw}'"));
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let existing = self.inner.instances.read().await.get(server_id).cloned();
+        validation.exists = existing.is_some();
+        match (&existing, requested) {
+            (Some(existing), Some(agent)) if existing.agent != agent => {
+                validation.problem(
+                    "agent",
+                    format!(
+                        "server '{server_id}' already exists for agent '{}'",
+                        existing.agent.as_str()
+                    ),
+                );
+            }
+            (None, None) if bootstrap_agent.is_none() => {
+                validation.problem("agent", "required on the first POST to a server");
+            }
+            _ => {}
+        }
+        validation.agent = existing
+            .as_ref()
+            .map(|existing| existing.agent)
+            .or(requested);
+
+        if let Some(agent) = validation.agent {
+            validation.agent_installed = self.is_ready(agent).await;
+            if !validation.exists && !validation.agent_installed && self.inner.require_preinstall {
+                validation.problem(
+                    "agent",
+                    format!(
+                        "agent '{}' is not installed and SANDBOX_AGENT_REQUIRE_PREINSTALL is set",
+                        agent.as_str()
+                    ),
+                );
+            }
+        }
+
+        match spawn_env_from_payload(payload) {
+            Ok(env) => {
+                let mut keys = Vec::with_capacity(env.len());
+                for (key, value) in env {
+                    if let Err(message) =
+                        expand_env_template(&value, &self.inner.spawn_env_allowlist)
+                    {
+                        validation.problem(format!("env.{key}"), message);
+                    }
+                    keys.push(key);
+                }
+                keys.sort();
+                validation.env_keys = keys;
+            }
+            Err(err) => validation.problem("params._meta.sandboxagent.dev.env", err.to_string()),
+        }
+        if !validation.exists {
+            let checked =
+                PermissionMode::from_payload(payload).and_then(|mode| match validation.agent {
+                    Some(agent) => mode.launch_for(agent).map(|_| ()),
+                    None => Ok(()),
+                });
+            if let Err(err) = checked {
+                validation.problem(
+                    "params._meta.sandboxagent.dev.permissionMode",
+                    err.to_string(),
+                );
+            }
+        }
+
+        let method = payload.get("method").and_then(Value::as_str);
+        if method == Some("session/prompt") {
+            if let Err(err) = self.inner.prompt_limit.check(payload) {
+                validation.problem("params.prompt", err.to_string());
+            }
+            if let Err(err) = self.inner.attachments.check_inline(payload) {
+                validation.problem("params.prompt", err.to_string());
+            }
+        }
+        if matches!(method, Some("session/new" | "session/load")) {
+            match payload.pointer("/params/cwd").map(Value::as_str) {
+                None => validation.problem("params.cwd", "required"),
+                Some(None) => validation.problem("params.cwd", "must be a string"),
+                Some(Some(cwd)) => {
+                    let path = std::path::Path::new(cwd);
+                    if !path.is_absolute() {
+                        validation.problem("params.cwd", "must be an absolute path");
+                    } else if !path.is_dir() {
+                        validation.problem("params.cwd", format!("'{cwd}' is not a directory"));
+                    }
+                }
+            }
+        }
+
+        validation
+    }
+
+    /// Store secrets for `server_id`. They are injected when the agent process
+    /// spawns; returns the stored names and whether a process is already
+    /// running (and so will not see new values until it is recreated).
+    pub async fn put_secrets(
+        &self,
+        server_id: &str,
+        secrets: Vec<SecretInput>,
+    ) -> Result<(Vec<String>, bool), SandboxError> {
+        let (names, revealed) = {
+            let mut vault = self.inner.secrets.lock().await;
+            let names = vault.put(server_id, secrets)?;
+            (names, vault.reveal(server_id)?)
+        };
+
+        let running = self.inner.instances.read().await.get(server_id).cloned();
+        if let Some(instance) = &running {
+            instance
+                .runtime
+                .set_redactions(revealed.into_iter().map(|secret| secret.value).collect());
+        }
+        Ok((names, running.is_some()))
+    }
+
     pub async fn delete(&self, server_id: &str) -> Result<(), SandboxError> {
+        self.inner.secrets.lock().await.remove_server(server_id);
+        self.inner.idempotency.remove_server(server_id);
         let removed = self.inner.instances.write().await.remove(server_id);
         if let Some(instance) = removed {
+            *instance
+                .stopped_by
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = Some(SessionEndReason::Terminated);
             instance.runtime.shutdown().await;
+            let exit = instance.runtime.exit_status();
+            self.end_sessions(
+                &instance,
+                exit.as_ref().and_then(|exit| exit.code),
+                exit.as_ref().and_then(|exit| exit.signal),
+            )
+            .await;
+            self.inner.cdc.record("server.closed", server_id, json!({}));
+        }
+        self.inner
+            .sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(server_id);
+        if let Some(dir) = self.raw_spill_dir(server_id) {
+            let _ = tokio::fs::remove_dir_all(dir).await;
+        }
+        if let Some(dir) = self.artifact_dir(server_id) {
+            let _ = tokio::fs::remove_dir_all(dir).await;
         }
+        self.inner
+            .turn_counts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(server_id);
+        if let Some(dir) = self.inner.turn_logs.server_dir(&server_dir_name(server_id)) {
+            let _ = tokio::fs::remove_dir_all(dir).await;
+        }
+        let attachments = self
+            .inner
+            .attachments
+            .server_dir(&server_dir_name(server_id));
+        let _ = tokio::fs::remove_dir_all(attachments).await;
+        self.inner
+            .claude_settings
+            .remove_server(&server_dir_name(server_id))
+            .await;
         Ok(())
     }
 
+    /// Stop every agent process. Sessions with a prompt in flight are sent
+    /// `session/cancel` first and get up to `SHUTDOWN_CANCEL_GRACE` to answer,
+    /// so their callers get a response instead of a dropped connection. The
+    /// change feed is flushed last so `server.closed` records are not lost.
     pub async fn shutdown_all(&self) {
         let instances = {
             let mut guard = self.inner.instances.write().await;
@@ -197,9 +1855,54 @@ impl AcpProxyRuntime {
                 .collect::<Vec<_>>()
         };
 
+        for instance in &instances {
+            let sessions = instance
+                .active_prompts
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>();
+            for session_id in sessions {
+                tracing::info!(
+                    server_id = %instance.server_id,
+                    session_id = %session_id,
+                    "acp_proxy: cancelling in-flight prompt for shutdown"
+                );
+                let _ = instance
+                    .runtime
+                    .post(json!({
+                        "jsonrpc": "2.0",
+                        "method": "session/cancel",
+                        "params": { "sessionId": session_id },
+                    }))
+                    .await;
+            }
+        }
+        let deadline = Instant::now() + SHUTDOWN_CANCEL_GRACE;
+        while Instant::now() < deadline
+            && instances.iter().any(|instance| {
+                !instance
+                    .active_prompts
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .is_empty()
+            })
+        {
+            tokio::time::sleep(SHUTDOWN_POLL_INTERVAL).await;
+        }
+
         for instance in instances {
             instance.runtime.shutdown().await;
+            self.inner
+                .cdc
+                .record("server.closed", &instance.server_id, json!({}));
+        }
+        for agent in self.inner.warm_pool.agents() {
+            self.drain_warm_pool(agent).await;
         }
+        self.inner.claude_settings.remove_all().await;
+        self.inner.cdc.flush(CDC_FLUSH_TIMEOUT).await;
     }
 
     async fn get_instance(&self, server_id: &str) -> Result<Arc<ProxyInstance>, SandboxError> {
@@ -218,6 +1921,10 @@ impl AcpProxyRuntime {
         &self,
         server_id: &str,
         bootstrap_agent: Option<AgentId>,
+        spawn_env: HashMap<String, String>,
+        permission_mode: PermissionMode,
+        codex_options: Option<CodexOptions>,
+        failovers: &[AgentFailover],
     ) -> Result<Arc<ProxyInstance>, SandboxError> {
         if let Some(existing) = self.inner.instances.read().await.get(server_id).cloned() {
             if let Some(agent) = bootstrap_agent {
@@ -262,21 +1969,76 @@ impl AcpProxyRuntime {
             ),
         })?;
 
-        let created = self.create_instance(server_id, agent).await?;
+        let created = self
+            .create_instance(server_id, agent, spawn_env, permission_mode, codex_options)
+            .await?;
         self.inner
             .instances
             .write()
             .await
             .insert(server_id.to_string(), created.clone());
+        let _ = self
+            .inner
+            .created
+            .send((server_id.to_string(), created.agent));
+        self.record_server_created(&created).await;
+        self.watch_schema_drift(&created).await;
+        self.watch_agent_requests(&created).await;
+        self.watch_session_ends(&created).await;
+        for failover in failovers {
+            created
+                .runtime
+                .publish_notification(json!({
+                    "jsonrpc": "2.0",
+                    "method": AGENT_FAILOVER_METHOD,
+                    "params": failover.to_json(),
+                }))
+                .await;
+            self.inner
+                .cdc
+                .record("server.failover", server_id, failover.to_json());
+        }
 
         Ok(created)
     }
 
+    /// Drop the server a failed bootstrap POST left behind, if its process
+    /// started at all, so the next agent can take its id.
+    async fn discard_failed_instance(&self, server_id: &str, agent: AgentId) {
+        let removed = {
+            let mut instances = self.inner.instances.write().await;
+            match instances.get(server_id) {
+                Some(instance) if instance.agent == agent => instances.remove(server_id),
+                _ => None,
+            }
+        };
+        if let Some(instance) = removed {
+            instance.runtime.shutdown().await;
+        }
+        self.inner
+            .sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(server_id);
+        self.inner
+            .turn_counts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(server_id);
+    }
+
     async fn create_instance(
         &self,
         server_id: &str,
         agent: AgentId,
+        spawn_env: HashMap<String, String>,
+        permission_mode: PermissionMode,
+        codex_options: Option<CodexOptions>,
     ) -> Result<Arc<ProxyInstance>, SandboxError> {
+        let mut permissions = permission_mode.launch_for(agent)?;
+        if let Some(options) = &codex_options {
+            permissions = permissions.with_codex_options(agent, options)?;
+        }
         let start = std::time::Instant::now();
         tracing::info!(
             server_id = server_id,
@@ -293,41 +2055,75 @@ impl AcpProxyRuntime {
             "create_instance: agent installed/verified"
         );
 
-        let manager = self.inner.agent_manager.clone();
-        let launch = tokio::task::spawn_blocking(move || manager.resolve_agent_process(agent))
-            .await
-            .map_err(|err| SandboxError::StreamError {
-                message: format!("failed to resolve ACP agent process launch spec: {err}"),
-            })?
-            .map_err(|err| SandboxError::StreamError {
-                message: err.to_string(),
-            })?;
+        let mut spawn_env = self.resolve_spawn_env(spawn_env)?;
+        if let Some(defaults) = self.inner.agent_env.get(&agent) {
+            for (key, value) in defaults {
+                spawn_env
+                    .entry(key.clone())
+                    .or_insert_with(|| value.clone());
+            }
+        }
+        let secrets = self.inner.secrets.lock().await.reveal(server_id)?;
+        let redactions = secrets
+            .iter()
+            .map(|secret| secret.value.clone())
+            .collect::<Vec<_>>();
+        spawn_env.extend(secrets_to_env(server_id, secrets)?);
+        if !spawn_env.contains_key("HOME") {
+            let home = self
+                .inner
+                .session_homes
+                .prepare(&server_dir_name(server_id))
+                .map_err(|err| SandboxError::SpawnFailed {
+                    agent: agent.as_str().to_string(),
+                    message: format!("failed to prepare agent home directory: {err}"),
+                })?;
+            if let Some(home) = home {
+                spawn_env.insert("HOME".to_string(), home.to_string_lossy().into_owned());
+            }
+        }
+        let resolve_start = std::time::Instant::now();
+        let spec = self.launch_spec(agent, spawn_env, &permissions).await?;
 
         tracing::info!(
             server_id = server_id,
             agent = agent.as_str(),
-            program = ?launch.program,
-            args = ?launch.args,
+            program = ?spec.program,
+            args = ?spec.args,
             resolve_ms = start.elapsed().as_millis() as u64,
             "create_instance: launch spec resolved, spawning"
         );
 
-        let runtime = AdapterRuntime::start(
-            LaunchSpec {
-                program: launch.program,
-                args: launch.args,
-                env: launch.env,
-            },
-            self.inner.request_timeout,
-        )
-        .await
-        .map_err(map_adapter_error)?;
+        let resolve_elapsed = resolve_start.elapsed();
+        let spawn_start = std::time::Instant::now();
+        let standby = self.inner.warm_pool.take(agent, &spec);
+        let warm = standby.is_some();
+        let runtime = match standby {
+            Some(standby) => standby.runtime,
+            None => AdapterRuntime::start(spec, self.inner.request_timeout)
+                .await
+                .map_err(|err| map_adapter_error(agent, err))?,
+        };
+        self.inner
+            .warm_pool
+            .record_start(agent, warm, resolve_start.elapsed().as_millis() as u64);
+        self.refill_warm_pool(agent);
+        runtime.set_redactions(redactions);
+        runtime.set_raw_spill(RawSpill {
+            max_bytes: self.inner.raw_spill.max_bytes,
+            dir: self.raw_spill_dir(server_id),
+        });
+        runtime.set_artifact_spill(ArtifactSpill {
+            dir: self.artifact_dir(server_id),
+            ..self.inner.artifact_spill.clone()
+        });
 
         let total_ms = start.elapsed().as_millis() as u64;
         tracing::info!(
             server_id = server_id,
             agent = agent.as_str(),
             total_ms = total_ms,
+            warm = warm,
             "create_instance: ready"
         );
 
@@ -336,56 +2132,270 @@ impl AcpProxyRuntime {
             agent,
             runtime: Arc::new(runtime),
             created_at_ms: now_ms(),
+            startup_timing: AcpStartupTiming {
+                install_ms: install_elapsed.as_millis() as u64,
+                resolve_ms: resolve_elapsed.as_millis() as u64,
+                spawn_ms: spawn_start.elapsed().as_millis() as u64,
+                warm,
+            },
+            last_turn_timing: std::sync::Mutex::new(None),
+            verified: AtomicBool::new(false),
+            session_limits: std::sync::Mutex::new(HashMap::new()),
+            active_prompts: std::sync::Mutex::new(HashMap::new()),
+            prompts_finished: Notify::new(),
+            pending_requests: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            session_unattended: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            prompt_capabilities: std::sync::Mutex::new(None),
+            permission_mode,
+            permissions,
+            session_modes: std::sync::Mutex::new(HashMap::new()),
+            plans: std::sync::Mutex::new(HashMap::new()),
+            last_prompts: std::sync::Mutex::new(HashMap::new()),
+            messages: std::sync::Mutex::new(MessageQueue::default()),
+            session_ids: std::sync::Mutex::new(Vec::new()),
+            stopped_by: std::sync::Mutex::new(None),
+            model_fallbacks: std::sync::Mutex::new(HashMap::new()),
         }))
     }
 
-    async fn ensure_installed(&self, agent: AgentId) -> Result<(), SandboxError> {
-        if self.inner.require_preinstall {
-            if !self.is_ready(agent).await {
-                return Err(SandboxError::AgentNotInstalled {
-                    agent: agent.as_str().to_string(),
-                });
-            }
-            return Ok(());
-        }
-
-        if self.is_ready(agent).await {
-            return Ok(());
-        }
-
-        let lock = {
-            let mut locks = self.inner.install_locks.lock().await;
-            locks
-                .entry(agent)
-                .or_insert_with(|| Arc::new(Mutex::new(())))
-                .clone()
-        };
-        let _guard = lock.lock().await;
-
-        if self.is_ready(agent).await {
-            return Ok(());
-        }
-
+    /// How `agent` is launched with `spawn_env` and `permissions` on top of
+    /// its own launch spec.
+    async fn launch_spec(
+        &self,
+        agent: AgentId,
+        spawn_env: HashMap<String, String>,
+        permissions: &PermissionLaunch,
+    ) -> Result<LaunchSpec, SandboxError> {
         let manager = self.inner.agent_manager.clone();
-        tokio::task::spawn_blocking(move || manager.install(agent, InstallOptions::default()))
+        let launch = tokio::task::spawn_blocking(move || manager.resolve_agent_process(agent))
             .await
-            .map_err(|err| SandboxError::InstallFailed {
+            .map_err(|err| SandboxError::SpawnFailed {
                 agent: agent.as_str().to_string(),
-                stderr: Some(format!("installer task failed: {err}")),
+                message: format!("failed to resolve ACP agent process launch spec: {err}"),
             })?
-            .map_err(|err| SandboxError::InstallFailed {
+            .map_err(|err| SandboxError::SpawnFailed {
                 agent: agent.as_str().to_string(),
-                stderr: Some(err.to_string()),
+                message: err.to_string(),
             })?;
+        Ok(LaunchSpec {
+            program: launch.program,
+            args: launch
+                .args
+                .into_iter()
+                .chain(permissions.args.iter().cloned())
+                .collect(),
+            env: launch
+                .env
+                .into_iter()
+                .chain(spawn_env)
+                .chain(permissions.env.clone())
+                .collect(),
+        })
+    }
 
-        Ok(())
+    /// Emit `server.created` and mirror the server's envelopes into the
+    /// change feed until its stream ends.
+    async fn record_server_created(&self, instance: &ProxyInstance) {
+        let cdc = &self.inner.cdc;
+        if !cdc.is_enabled() {
+            return;
+        }
+        cdc.record(
+            "server.created",
+            &instance.server_id,
+            json!({ "agent": instance.agent.as_str() }),
+        );
+
+        let mut envelopes = instance.runtime.clone().sequenced_stream(None).await;
+        let cdc = cdc.clone();
+        let server_id = instance.server_id.clone();
+        tokio::spawn(async move {
+            while let Some((sequence, payload)) = envelopes.next().await {
+                cdc.record(
+                    "envelope.appended",
+                    &server_id,
+                    json!({
+                        "sequence": sequence,
+                        "method": payload.get("method"),
+                        "payload": payload,
+                    }),
+                );
+            }
+        });
+    }
+
+    /// Track requests the agent sends to the client until a client answers
+    /// them, answering any still pending after their session's unattended
+    /// timeout.
+    async fn watch_agent_requests(&self, instance: &ProxyInstance) {
+        let mut envelopes = instance.runtime.clone().sequenced_stream(None).await;
+        let runtime = instance.runtime.clone();
+        let pending = instance.pending_requests.clone();
+        let session_unattended = instance.session_unattended.clone();
+        let default_policy = self.inner.unattended;
+        let cdc = self.inner.cdc.clone();
+        let server_id = instance.server_id.clone();
+        tokio::spawn(async move {
+            while let Some((sequence, payload)) = envelopes.next().await {
+                let (Some(id), Some(method)) = (
+                    payload.get("id"),
+                    payload.get("method").and_then(Value::as_str),
+                ) else {
+                    continue;
+                };
+                let session_id = payload
+                    .pointer("/params/sessionId")
+                    .and_then(Value::as_str)
+                    .map(ToOwned::to_owned);
+                let policy = session_id
+                    .as_ref()
+                    .and_then(|session_id| {
+                        session_unattended
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .get(session_id)
+                            .copied()
+                    })
+                    .unwrap_or(default_policy);
+                let received_at_ms = now_ms();
+                let request = AcpPendingRequest {
+                    sequence,
+                    id: id.clone(),
+                    method: method.to_string(),
+                    session_id,
+                    payload: payload.clone(),
+                    received_at_ms,
+                    expires_at_ms: policy
+                        .timeout
+                        .map(|timeout| received_at_ms + timeout.as_millis() as i64),
+                };
+                let key = id.to_string();
+                pending
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .insert(key.clone(), request);
+                let Some(timeout) = policy.timeout else {
+                    continue;
+                };
+                let runtime = runtime.clone();
+                let pending = pending.clone();
+                let cdc = cdc.clone();
+                let server_id = server_id.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(timeout).await;
+                    let Some(request) = pending
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .remove(&key)
+                    else {
+                        return;
+                    };
+                    expire_agent_request(&runtime, &cdc, &server_id, &policy, request).await;
+                });
+            }
+        });
+    }
+
+    /// Expand `${VAR}` references in client-supplied spawn env. Only variables
+    /// on the server allowlist may be read from the server environment.
+    fn resolve_spawn_env(
+        &self,
+        env: HashMap<String, String>,
+    ) -> Result<HashMap<String, String>, SandboxError> {
+        env.into_iter()
+            .map(|(key, value)| {
+                let expanded = expand_env_template(&value, &self.inner.spawn_env_allowlist)
+                    .map_err(|message| SandboxError::InvalidRequest {
+                        message: format!("env '{key}': {message}"),
+                    })?;
+                Ok((key, expanded))
+            })
+            .collect()
+    }
+}
+
+impl ProxyInstance {
+    fn in_plan_mode(&self, session_id: &str) -> bool {
+        match self
+            .session_modes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(session_id)
+        {
+            Some(mode_id) => mode_id == plans::PLAN_MODE_ID,
+            None => self.permission_mode == PermissionMode::Plan,
+        }
     }
 
-    async fn is_ready(&self, agent: AgentId) -> bool {
-        if agent == AgentId::Mock {
-            return self.inner.agent_manager.agent_process_path(agent).exists();
+    /// Replace the models `session_id` falls back to.
+    fn set_model_fallbacks(&self, session_id: &str, models: Vec<String>) {
+        let mut fallbacks = self
+            .model_fallbacks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if models.is_empty() {
+            fallbacks.remove(session_id);
+        } else {
+            fallbacks.insert(session_id.to_string(), models);
         }
-        self.inner.agent_manager.is_installed(agent)
+    }
+
+    fn begin_prompt(&self, session_id: &str) -> ActivePrompt<'_> {
+        *self
+            .active_prompts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(session_id.to_string())
+            .or_default() += 1;
+        ActivePrompt {
+            instance: self,
+            session_id: session_id.to_string(),
+        }
+    }
+
+    /// Publish a turn boundary notification. `turn_ref` carries the
+    /// `sessionId` and prompt `requestId` shared by every event of a turn.
+    async fn publish_turn_event(&self, method: &str, turn_ref: &Value, extra: Value) {
+        let mut params = turn_ref.clone();
+        if let (Some(params), Value::Object(extra)) = (params.as_object_mut(), extra) {
+            params.extend(extra);
+        }
+        self.runtime
+            .publish_notification(json!({
+                "jsonrpc": "2.0",
+                "method": method,
+                "params": params,
+            }))
+            .await;
+    }
+
+    async fn record_turn_timing(&self, timing: AcpTurnTiming) {
+        tracing::info!(
+            server_id = %self.server_id,
+            session_id = ?timing.session_id,
+            time_to_first_event_ms = ?timing.time_to_first_event_ms,
+            duration_ms = timing.duration_ms,
+            "acp_proxy: turn finished"
+        );
+        self.runtime
+            .publish_notification(json!({
+                "jsonrpc": "2.0",
+                "method": "_adapter/turn_timing",
+                "params": {
+                    "sessionId": timing.session_id,
+                    "startedAtMs": timing.started_at_ms,
+                    "instanceMs": timing.instance_ms,
+                    "timeToFirstEventMs": timing.time_to_first_event_ms,
+                    "durationMs": timing.duration_ms,
+                    "stopReason": timing.stop_reason,
+                    "usage": timing.usage.as_ref().map(AcpTurnUsage::to_json),
+                }
+            }))
+            .await;
+        *self
+            .last_turn_timing
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(timing);
     }
 }
 
@@ -414,12 +2424,11 @@ impl AcpDispatch for AcpProxyRuntime {
     ) -> Pin<Box<dyn Future<Output = Result<AcpPayloadStream, String>> + Send + '_>> {
         let server_id = server_id.to_string();
         Box::pin(async move {
-            let instance = self
-                .get_instance(&server_id)
+            let stream = self
+                .sequenced_stream(&server_id, last_event_id, false)
                 .await
                 .map_err(|e| e.to_string())?;
-            let stream = instance.runtime.clone().value_stream(last_event_id).await;
-            Ok(Box::pin(stream) as AcpPayloadStream)
+            Ok(stream as AcpPayloadStream)
         })
     }
 
@@ -432,25 +2441,33 @@ impl AcpDispatch for AcpProxyRuntime {
     }
 }
 
-fn map_adapter_error(err: AdapterError) -> SandboxError {
+fn map_adapter_error(agent: AgentId, err: AdapterError) -> SandboxError {
     match err {
+        AdapterError::Exited(exit) => SandboxError::AgentProcessExited {
+            agent: agent.as_str().to_string(),
+            exit_code: exit.code,
+            reason: Some(classify_exit(agent, &exit).as_str().to_string()),
+            stderr: (!exit.stderr.is_empty()).then(|| exit.stderr.join("\n")),
+        },
         AdapterError::InvalidEnvelope => SandboxError::InvalidRequest {
             message: "request body must be a JSON-RPC object".to_string(),
         },
         AdapterError::Timeout => SandboxError::Timeout {
             message: Some("timed out waiting for agent response".to_string()),
         },
-        AdapterError::Serialize(error) => SandboxError::InvalidRequest {
+        AdapterError::Serialize(error) => SandboxError::ConversionFailed {
             message: format!("failed to serialize JSON payload: {error}"),
         },
         AdapterError::Write(error) => SandboxError::StreamError {
             message: format!("failed writing to agent stdin: {error}"),
         },
-        AdapterError::Spawn(error) => SandboxError::StreamError {
-            message: format!("failed to start agent process: {error}"),
+        AdapterError::Spawn(error) => SandboxError::SpawnFailed {
+            agent: agent.as_str().to_string(),
+            message: error.to_string(),
         },
         AdapterError::MissingStdin | AdapterError::MissingStdout | AdapterError::MissingStderr => {
-            SandboxError::StreamError {
+            SandboxError::SpawnFailed {
+                agent: agent.as_str().to_string(),
                 message: "agent subprocess pipes were not available".to_string(),
             }
         }
@@ -488,17 +2505,105 @@ fn annotate_agent_error(agent: AgentId, mut value: Value) -> Value {
     value
 }
 
-fn duration_from_env_ms(key: &str, default: Duration) -> Duration {
-    match std::env::var(key) {
-        Ok(raw) => raw
-            .trim()
-            .parse::<u64>()
-            .ok()
-            .filter(|value| *value > 0)
-            .map(Duration::from_millis)
-            .unwrap_or(default),
-        Err(_) => default,
+/// `server_id` reduced to characters that are safe in a directory name.
+fn server_dir_name(server_id: &str) -> String {
+    server_id
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Read `params._meta["sandboxagent.dev"].env` from a bootstrap envelope. It
+/// only takes effect on the POST that starts the agent process.
+fn spawn_env_from_payload(payload: &Value) -> Result<HashMap<String, String>, SandboxError> {
+    let Some(env) = payload.pointer("/params/_meta/sandboxagent.dev/env") else {
+        return Ok(HashMap::new());
+    };
+    let invalid = || SandboxError::InvalidRequest {
+        message: "_meta[\"sandboxagent.dev\"].env must be an object of string values".to_string(),
+    };
+    env.as_object()
+        .ok_or_else(invalid)?
+        .iter()
+        .map(|(key, value)| {
+            value
+                .as_str()
+                .map(|value| (key.clone(), value.to_string()))
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
+
+fn expand_env_template(value: &str, allowlist: &[String]) -> Result<String, String> {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| "unterminated ${ in value".to_string())?;
+        let name = &after[..end];
+        if !allowlist.iter().any(|allowed| allowed == name) {
+            return Err(format!(
+                "'{name}' is not in SANDBOX_AGENT_SPAWN_ENV_ALLOWLIST"
+            ));
+        }
+        out.push_str(&std::env::var(name).unwrap_or_default());
+        rest = &after[end + 1..];
     }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Answer an agent request no client responded to in time, as `policy`
+/// says, and record the answer on the stream.
+async fn expire_agent_request(
+    runtime: &AdapterRuntime,
+    cdc: &CdcFeed,
+    server_id: &str,
+    policy: &UnattendedPolicy,
+    request: AcpPendingRequest,
+) {
+    tracing::warn!(
+        server_id = server_id,
+        method = %request.method,
+        id = %request.id,
+        "acp_proxy: agent request expired without a client response"
+    );
+    let response = policy.response(&request.payload);
+    if let Some(outcome) = response.pointer("/result/outcome") {
+        cdc.record(
+            "permission.resolved",
+            server_id,
+            json!({ "requestId": request.id, "outcome": outcome, "expired": true }),
+        );
+    }
+    if let Err(err) = runtime.post(response.clone()).await {
+        tracing::warn!(
+            server_id = server_id,
+            error = %err,
+            "acp_proxy: failed to reject expired agent request"
+        );
+    }
+    runtime
+        .publish_notification(json!({
+            "jsonrpc": "2.0",
+            "method": "_adapter/request_expired",
+            "params": {
+                "sessionId": request.session_id,
+                "requestId": request.id,
+                "method": request.method,
+                "response": response,
+            }
+        }))
+        .await;
 }
 
 fn now_ms() -> i64 {
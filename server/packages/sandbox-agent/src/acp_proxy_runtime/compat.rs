@@ -0,0 +1,128 @@
+//! Agent versions that failed or passed a turn, and schema drift.
+
+use super::*;
+
+impl AcpProxyRuntime {
+    /// The most recent version incompatibility detected for `agent`.
+    pub fn agent_incompatibility(&self, agent: AgentId) -> Option<AgentIncompatibility> {
+        self.inner.compat.get(agent)
+    }
+
+    /// Flag `agent` once its process has written `SCHEMA_DRIFT_THRESHOLD`
+    /// stdout lines that are not JSON-RPC.
+    pub(super) async fn watch_schema_drift(&self, instance: &ProxyInstance) {
+        let mut envelopes = instance.runtime.clone().sequenced_stream(None).await;
+        let runtime = self.clone();
+        let agent = instance.agent;
+        tokio::spawn(async move {
+            let mut invalid = 0;
+            while let Some((_, payload)) = envelopes.next().await {
+                if payload.get("method").and_then(Value::as_str) != Some("_adapter/invalid_stdout")
+                {
+                    continue;
+                }
+                invalid += 1;
+                if invalid == SCHEMA_DRIFT_THRESHOLD {
+                    runtime.note_incompatibility(
+                        agent,
+                        IncompatibilityReason::SchemaDrift,
+                        format!("agent wrote {invalid} stdout lines that are not JSON-RPC"),
+                    );
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Record an incompatibility with the installed and known-good versions,
+    /// and install the known-good ones when auto-downgrade is on.
+    pub(super) fn note_incompatibility(
+        &self,
+        agent: AgentId,
+        reason: IncompatibilityReason,
+        detail: String,
+    ) {
+        let runtime = self.clone();
+        tokio::spawn(async move {
+            let manager = runtime.inner.agent_manager.clone();
+            let Ok((installed_version, known_good)) = tokio::task::spawn_blocking(move || {
+                (
+                    manager.version(agent).ok().flatten(),
+                    manager.known_good(agent),
+                )
+            })
+            .await
+            else {
+                return;
+            };
+            let suggested =
+                known_good.filter(|pin| pin.version.is_none() || pin.version != installed_version);
+            tracing::warn!(
+                agent = agent.as_str(),
+                reason = reason.as_str(),
+                installed_version = ?installed_version,
+                suggested_version = ?suggested.as_ref().and_then(|pin| pin.version.clone()),
+                detail = %detail,
+                "agent_incompatibility"
+            );
+            runtime.inner.compat.record(
+                agent,
+                AgentIncompatibility {
+                    reason,
+                    detail,
+                    installed_version,
+                    suggested: suggested.clone(),
+                    auto_installed: false,
+                    detected_at_ms: now_ms(),
+                },
+            );
+
+            let Some(pin) = suggested.filter(|_| runtime.inner.compat.auto_downgrade()) else {
+                return;
+            };
+            let lock = runtime.install_lock(agent).await;
+            let _guard = lock.lock().await;
+            let options = InstallOptions {
+                reinstall: true,
+                version: pin.version,
+                agent_process_version: pin.agent_process_version,
+                ..InstallOptions::default()
+            };
+            match runtime.run_install(agent, options).await {
+                Ok(()) => runtime.inner.compat.mark_auto_installed(agent),
+                Err(err) => tracing::warn!(
+                    agent = agent.as_str(),
+                    error = %err,
+                    "agent_incompatibility: known-good install failed"
+                ),
+            }
+        });
+    }
+
+    /// After the first completed turn on `instance`, remember the installed
+    /// versions as known good and drop any recorded incompatibility.
+    pub(super) fn note_compatible(&self, instance: &ProxyInstance) {
+        if instance.agent == AgentId::Mock || instance.verified.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        self.inner.compat.clear(instance.agent);
+        let manager = self.inner.agent_manager.clone();
+        let agent = instance.agent;
+        tokio::task::spawn_blocking(move || {
+            let Some(version) = manager.version(agent).ok().flatten() else {
+                return;
+            };
+            let pin = AgentPin {
+                version: Some(version),
+                agent_process_version: None,
+            };
+            if let Err(err) = manager.record_known_good(agent, pin) {
+                tracing::warn!(
+                    agent = agent.as_str(),
+                    error = %err,
+                    "failed to record known-good versions"
+                );
+            }
+        });
+    }
+}
@@ -0,0 +1,103 @@
+//! Attachments, turn logs and spilled artifacts on disk.
+
+use super::*;
+
+impl AcpProxyRuntime {
+    /// Largest attachment upload request, in bytes.
+    pub fn attachment_limit(&self) -> usize {
+        self.inner.attachments.max_bytes()
+    }
+
+    /// Where attachments for `server_id` are stored: `dir` when given,
+    /// otherwise the server's own directory, which is removed with it.
+    pub async fn attachment_dir(
+        &self,
+        server_id: &str,
+        dir: Option<PathBuf>,
+    ) -> Result<PathBuf, SandboxError> {
+        self.get_instance(server_id).await?;
+        Ok(dir.unwrap_or_else(|| {
+            self.inner
+                .attachments
+                .server_dir(&server_dir_name(server_id))
+        }))
+    }
+
+    /// Everything the agent process wrote during turn `turn` of
+    /// `server_id`, one `[stdout]` or `[stderr]` line each.
+    pub async fn turn_log(&self, server_id: &str, turn: u64) -> Result<String, SandboxError> {
+        if !self.inner.turn_logs.is_enabled() {
+            return Err(SandboxError::InvalidRequest {
+                message: "turn logs are off; set SANDBOX_AGENT_TURN_LOG_DIR".to_string(),
+            });
+        }
+        self.inner
+            .turn_logs
+            .read(&server_dir_name(server_id), turn)
+            .await
+            .map_err(|err| SandboxError::StreamError {
+                message: format!("failed to read turn log: {err}"),
+            })?
+            .ok_or_else(|| SandboxError::SessionNotFound {
+                session_id: format!("{server_id}/turns/{turn}"),
+            })
+    }
+
+    /// Full content of a tool output the agent sent in `session_id` that was
+    /// stored as an artifact, with its MIME type.
+    pub async fn artifact(
+        &self,
+        server_id: &str,
+        session_id: &str,
+        artifact_id: &str,
+    ) -> Result<(&'static str, Vec<u8>), SandboxError> {
+        let not_found = || SandboxError::SessionNotFound {
+            session_id: format!("{server_id}/{session_id}/artifacts/{artifact_id}"),
+        };
+        // Ids are generated by the adapter; anything else could name a path
+        // outside the session's directory.
+        let valid = artifact_id.starts_with("artifact-")
+            && !artifact_id.contains("..")
+            && artifact_id
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '.');
+        let Some(dir) = self.artifact_dir(server_id).filter(|_| valid) else {
+            return Err(not_found());
+        };
+        let path = dir.join(artifact_dir_name(session_id)).join(artifact_id);
+        let content = match tokio::fs::read(&path).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Err(not_found()),
+            Err(err) => {
+                return Err(SandboxError::StreamError {
+                    message: format!("failed to read artifact: {err}"),
+                })
+            }
+        };
+        let mime_type = if artifact_id.ends_with(".json") {
+            "application/json"
+        } else {
+            "text/plain; charset=utf-8"
+        };
+        Ok((mime_type, content))
+    }
+
+    /// Per-server directory for tool outputs stored as artifacts.
+    pub(super) fn artifact_dir(&self, server_id: &str) -> Option<PathBuf> {
+        self.inner
+            .artifact_spill
+            .dir
+            .as_ref()
+            .map(|dir| dir.join(server_dir_name(server_id)))
+    }
+
+    /// Per-server directory for spilled stdout lines. Server ids come from
+    /// the URL, so anything but `[A-Za-z0-9_-]` is replaced.
+    pub(super) fn raw_spill_dir(&self, server_id: &str) -> Option<PathBuf> {
+        self.inner
+            .raw_spill
+            .dir
+            .as_ref()
+            .map(|dir| dir.join(server_dir_name(server_id)))
+    }
+}
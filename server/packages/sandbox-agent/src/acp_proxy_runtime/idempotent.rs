@@ -0,0 +1,46 @@
+//! `Idempotency-Key` handling for proxied POSTs.
+
+use super::*;
+
+impl AcpProxyRuntime {
+    /// [`Self::post`] that runs at most once per `Idempotency-Key`. The
+    /// flag is true when the outcome is replayed from an earlier request.
+    /// The first request keeps running if its client hangs up, so the
+    /// client's retry gets the response instead of prompting again.
+    pub(crate) async fn post_idempotent(
+        &self,
+        server_id: &str,
+        agent: Option<AgentId>,
+        payload: Value,
+        key: &str,
+    ) -> Result<(ProxyPostOutcome, bool), SandboxError> {
+        let body = serde_json::to_vec(&payload).unwrap_or_default();
+        let agent_name = agent.map(AgentId::as_str).unwrap_or_default();
+        let fingerprint = idempotency::fingerprint(&[agent_name.as_bytes(), &body]);
+        loop {
+            match self.inner.idempotency.claim(server_id, key, fingerprint)? {
+                Claim::Run(guard) => {
+                    let runtime = self.clone();
+                    let server_id = server_id.to_string();
+                    let task = tokio::spawn(async move {
+                        let result = runtime.post(&server_id, agent, payload).await;
+                        if let Ok(outcome) = &result {
+                            guard.complete(outcome);
+                        }
+                        result
+                    });
+                    let outcome = task.await.map_err(|err| SandboxError::StreamError {
+                        message: format!("request task failed: {err}"),
+                    })??;
+                    return Ok((outcome, false));
+                }
+                Claim::Replay(outcome) => {
+                    if let Some(outcome) = idempotency::wait_for(outcome).await {
+                        return Ok((outcome, true));
+                    }
+                    // The first request failed, so this one runs instead.
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Agent installs, uninstalls and install garbage collection.
+
+use super::*;
+
+impl AcpProxyRuntime {
+    pub(super) async fn ensure_installed(&self, agent: AgentId) -> Result<(), SandboxError> {
+        if self.inner.require_preinstall {
+            if !self.is_ready(agent).await {
+                return Err(SandboxError::AgentNotInstalled {
+                    agent: agent.as_str().to_string(),
+                });
+            }
+            return Ok(());
+        }
+
+        if self.is_ready(agent).await {
+            return Ok(());
+        }
+
+        let lock = self.install_lock(agent).await;
+        let _guard = lock.lock().await;
+
+        if self.is_ready(agent).await {
+            return Ok(());
+        }
+
+        self.run_install(agent, InstallOptions::default()).await
+    }
+
+    /// Install `agent` ahead of the first session, sharing the per-agent lock
+    /// with lazy installs so a boot-time install and a concurrent first POST
+    /// never download the same artifacts twice.
+    pub async fn preinstall(
+        &self,
+        agent: AgentId,
+        version: Option<String>,
+    ) -> Result<(), SandboxError> {
+        let lock = self.install_lock(agent).await;
+        let _guard = lock.lock().await;
+
+        if version.is_none() && self.is_ready(agent).await {
+            return Ok(());
+        }
+
+        self.run_install(
+            agent,
+            InstallOptions {
+                version,
+                ..InstallOptions::default()
+            },
+        )
+        .await
+    }
+
+    pub fn install_quota_bytes(&self) -> Option<u64> {
+        self.inner.install_quota_bytes
+    }
+
+    /// Uninstall `agent` and return the bytes freed. Refused while a server
+    /// of the agent is running.
+    pub async fn uninstall_agent(&self, agent: AgentId) -> Result<u64, SandboxError> {
+        let lock = self.install_lock(agent).await;
+        let _guard = lock.lock().await;
+
+        let running = self
+            .inner
+            .instances
+            .read()
+            .await
+            .values()
+            .filter(|instance| instance.agent == agent)
+            .count();
+        if running > 0 {
+            return Err(SandboxError::Conflict {
+                message: format!(
+                    "agent '{agent}' has {running} running server(s); delete them before uninstalling"
+                ),
+            });
+        }
+
+        self.drain_warm_pool(agent).await;
+        let manager = self.inner.agent_manager.clone();
+        tokio::task::spawn_blocking(move || manager.uninstall(agent))
+            .await
+            .map_err(|err| SandboxError::StreamError {
+                message: format!("uninstall task failed: {err}"),
+            })?
+            .map_err(|err| SandboxError::StreamError {
+                message: format!("failed to uninstall {agent}: {err}"),
+            })
+    }
+
+    /// Clean up the install directory after installing `installed`. Over the
+    /// install quota, agents other than `installed`, those with a running
+    /// server and those with a warm pool are uninstalled, least recently
+    /// installed first.
+    pub async fn collect_agent_garbage(&self, installed: AgentId) {
+        let mut keep = self
+            .inner
+            .instances
+            .read()
+            .await
+            .values()
+            .map(|instance| instance.agent)
+            .collect::<Vec<_>>();
+        keep.push(installed);
+        keep.extend(self.inner.warm_pool.agents());
+
+        let manager = self.inner.agent_manager.clone();
+        let quota_bytes = self.inner.install_quota_bytes;
+        match tokio::task::spawn_blocking(move || manager.collect_garbage(quota_bytes, &keep)).await
+        {
+            Ok(Ok(report)) => {
+                if report.freed_bytes > 0 {
+                    tracing::info!(
+                        freed_bytes = report.freed_bytes,
+                        uninstalled = ?report.uninstalled,
+                        "collected agent install garbage"
+                    );
+                }
+            }
+            Ok(Err(err)) => tracing::warn!(error = %err, "agent install garbage collection failed"),
+            Err(err) => {
+                tracing::warn!(error = %err, "agent install garbage collection task failed")
+            }
+        }
+    }
+
+    pub(super) async fn install_lock(&self, agent: AgentId) -> Arc<Mutex<()>> {
+        let mut locks = self.inner.install_locks.lock().await;
+        locks
+            .entry(agent)
+            .or_insert_with(|| Arc::new(Mutex::new(())))
+            .clone()
+    }
+
+    pub(super) async fn run_install(
+        &self,
+        agent: AgentId,
+        options: InstallOptions,
+    ) -> Result<(), SandboxError> {
+        let manager = self.inner.agent_manager.clone();
+        tokio::task::spawn_blocking(move || manager.install(agent, options))
+            .await
+            .map_err(|err| SandboxError::InstallFailed {
+                agent: agent.as_str().to_string(),
+                stderr: Some(format!("installer task failed: {err}")),
+            })?
+            .map_err(|err| SandboxError::InstallFailed {
+                agent: agent.as_str().to_string(),
+                stderr: Some(err.to_string()),
+            })?;
+
+        // Standbys still run the previous install.
+        self.drain_warm_pool(agent).await;
+        self.refill_warm_pool(agent);
+        self.collect_agent_garbage(agent).await;
+        Ok(())
+    }
+
+    pub(super) async fn is_ready(&self, agent: AgentId) -> bool {
+        if agent == AgentId::Mock {
+            return self.inner.agent_manager.agent_process_path(agent).exists();
+        }
+        self.inner.agent_manager.is_installed(agent)
+    }
+}
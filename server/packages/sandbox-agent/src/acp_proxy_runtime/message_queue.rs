@@ -0,0 +1,114 @@
+//! Messages queued behind a session's current turn.
+
+use super::*;
+
+impl AcpProxyRuntime {
+    /// Queue `prompt` for `session_id`. It is sent once the session has no
+    /// prompt in flight, after any messages queued before it.
+    pub async fn queue_message(
+        &self,
+        server_id: &str,
+        session_id: &str,
+        prompt: Vec<Value>,
+    ) -> Result<QueuedMessage, SandboxError> {
+        if prompt.is_empty() {
+            return Err(SandboxError::InvalidRequest {
+                message: "prompt must have at least one content block".to_string(),
+            });
+        }
+        let instance = self.get_instance(server_id).await?;
+        let (message, start) = instance
+            .messages
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(session_id, prompt, now_ms());
+        if start {
+            self.spawn_message_dispatcher(instance, server_id, session_id);
+        }
+        Ok(message)
+    }
+
+    /// Messages still queued or being sent for `session_id`, oldest first.
+    pub async fn queued_messages(
+        &self,
+        server_id: &str,
+        session_id: &str,
+    ) -> Result<Vec<QueuedMessage>, SandboxError> {
+        let instance = self.get_instance(server_id).await?;
+        let messages = instance
+            .messages
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .list(session_id);
+        Ok(messages)
+    }
+
+    /// Remove a queued message before it is sent.
+    pub async fn cancel_message(
+        &self,
+        server_id: &str,
+        session_id: &str,
+        message_id: &str,
+    ) -> Result<QueuedMessage, SandboxError> {
+        let instance = self.get_instance(server_id).await?;
+        let cancelled = instance
+            .messages
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .cancel(session_id, message_id)?;
+        cancelled.ok_or_else(|| SandboxError::SessionNotFound {
+            session_id: format!("{server_id}/sessions/{session_id}/messages/{message_id}"),
+        })
+    }
+
+    /// Send the queued messages of `session_id` one at a time, each once the
+    /// session has no prompt in flight.
+    fn spawn_message_dispatcher(
+        &self,
+        instance: Arc<ProxyInstance>,
+        server_id: &str,
+        session_id: &str,
+    ) {
+        let runtime = self.clone();
+        let server_id = server_id.to_string();
+        let session_id = session_id.to_string();
+        tokio::spawn(async move {
+            loop {
+                let finished = instance.prompts_finished.notified();
+                tokio::pin!(finished);
+                finished.as_mut().enable();
+                if instance
+                    .active_prompts
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .contains_key(&session_id)
+                {
+                    finished.await;
+                    continue;
+                }
+                let next = instance
+                    .messages
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .next(&session_id);
+                let Some(message) = next else {
+                    return;
+                };
+                if let Err(err) = runtime.post(&server_id, None, message.envelope()).await {
+                    tracing::warn!(
+                        server_id = %server_id,
+                        session_id = %session_id,
+                        message_id = %message.id,
+                        error = %err,
+                        "acp_proxy: queued message failed"
+                    );
+                }
+                instance
+                    .messages
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .finish(&session_id, &message.id);
+            }
+        });
+    }
+}
@@ -0,0 +1,178 @@
+//! Plans captured from planning turns, and their approval.
+
+use super::*;
+
+impl AcpProxyRuntime {
+    /// Latest plan `session_id` on `server_id` produced in plan mode.
+    pub async fn session_plan(
+        &self,
+        server_id: &str,
+        session_id: &str,
+    ) -> Result<SessionPlan, SandboxError> {
+        let instance = self.get_instance(server_id).await?;
+        let plan = instance
+            .plans
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(session_id)
+            .cloned();
+        plan.ok_or_else(|| SandboxError::SessionNotFound {
+            session_id: format!("{server_id}/sessions/{session_id}/plan"),
+        })
+    }
+
+    /// Approve the latest plan of `session_id`: switch the session to
+    /// `mode_id`, or the agent's build mode, record the decision with an
+    /// `_adapter/plan_approved` notification and a `plan.approved` change,
+    /// and send the plan back as the next prompt.
+    pub async fn approve_plan(
+        &self,
+        server_id: &str,
+        session_id: &str,
+        mode_id: Option<String>,
+        note: Option<String>,
+    ) -> Result<AcpPlanApproval, SandboxError> {
+        let instance = self.get_instance(server_id).await?;
+        let plan = self.session_plan(server_id, session_id).await?;
+        if plan.approval.is_some() {
+            return Err(SandboxError::Conflict {
+                message: format!("the latest plan of session '{session_id}' is already approved"),
+            });
+        }
+        let mode_id = mode_id.unwrap_or_else(|| plans::build_mode_for(instance.agent).to_string());
+        if mode_id == plans::PLAN_MODE_ID {
+            return Err(SandboxError::InvalidRequest {
+                message: "modeId must be a build mode, not 'plan'".to_string(),
+            });
+        }
+
+        self.post_expect_result(
+            server_id,
+            None,
+            json!({
+                "jsonrpc": "2.0",
+                "id": format!("sandboxagent-set-mode-{}", now_ms()),
+                "method": "session/set_mode",
+                "params": { "sessionId": session_id, "modeId": mode_id }
+            }),
+        )
+        .await?;
+
+        let approval = PlanApproval {
+            mode_id,
+            note,
+            approved_at_ms: now_ms(),
+        };
+        let record = {
+            let mut plans = instance
+                .plans
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let plan = plans
+                .get_mut(session_id)
+                .ok_or_else(|| SandboxError::SessionNotFound {
+                    session_id: format!("{server_id}/sessions/{session_id}/plan"),
+                })?;
+            plan.approval = Some(approval.clone());
+            plan.record()
+        };
+        instance
+            .runtime
+            .publish_notification(json!({
+                "jsonrpc": "2.0",
+                "method": plans::PLAN_APPROVED_METHOD,
+                "params": record,
+            }))
+            .await;
+        self.inner.cdc.record("plan.approved", server_id, record);
+
+        let result = self
+            .post_expect_result(
+                server_id,
+                None,
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": format!("sandboxagent-build-{}", now_ms()),
+                    "method": "session/prompt",
+                    "params": {
+                        "sessionId": session_id,
+                        "prompt": [{
+                            "type": "text",
+                            "text": plans::build_prompt(&plan.text, approval.note.as_deref()),
+                        }]
+                    }
+                }),
+            )
+            .await?;
+        Ok(AcpPlanApproval {
+            session_id: session_id.to_string(),
+            approval,
+            stop_reason: result
+                .get("stopReason")
+                .and_then(Value::as_str)
+                .map(ToOwned::to_owned),
+        })
+    }
+
+    /// Take the plan from a planning turn's envelopes, keep it for the
+    /// session, and record an `_adapter/plan` notification and a
+    /// `plan.created` change.
+    pub(super) async fn capture_plan(
+        &self,
+        instance: &ProxyInstance,
+        server_id: &str,
+        session_id: &str,
+        turn: Option<u64>,
+        sequence_before: u64,
+    ) {
+        let events = match self
+            .poll_events(
+                server_id,
+                Some(sequence_before),
+                usize::MAX,
+                Duration::ZERO,
+                false,
+            )
+            .await
+        {
+            Ok(events) => events,
+            Err(err) => {
+                tracing::warn!(server_id = server_id, error = %err, "acp_proxy: failed to read planning turn");
+                return;
+            }
+        };
+        if let Some(mode_id) = plans::mode_switch(&events, session_id) {
+            instance
+                .session_modes
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(session_id.to_string(), mode_id);
+        }
+        let Some(plan) = plans::extract(&events, session_id, turn, now_ms()) else {
+            return;
+        };
+        if !plan.violations.is_empty() {
+            tracing::warn!(
+                server_id = server_id,
+                session_id = session_id,
+                violations = plan.violations.len(),
+                "acp_proxy: agent changed files in plan mode"
+            );
+        }
+        let record = plan.record();
+        instance
+            .plans
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(session_id.to_string(), plan);
+        instance
+            .runtime
+            .publish_notification(json!({
+                "jsonrpc": "2.0",
+                "method": plans::PLAN_METHOD,
+                "params": record,
+            }))
+            .await;
+        self.inner.cdc.record("plan.created", server_id, record);
+    }
+}
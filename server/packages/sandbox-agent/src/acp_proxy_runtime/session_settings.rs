@@ -0,0 +1,77 @@
+//! Model and mode defaults applied to new sessions.
+
+use super::*;
+
+impl AcpProxyRuntime {
+    /// Model and mode new sessions of `agent` switch to, when configured.
+    pub(crate) fn session_defaults(&self, agent: AgentId) -> Option<SessionDefaults> {
+        self.inner.session_defaults.get(&agent).cloned()
+    }
+
+    /// `defaults` from a `session/new` envelope, with the agent's
+    /// configured defaults filling the gaps. The session starts on the first
+    /// model its alias routes to; the rest are returned for rate-limit
+    /// fallbacks.
+    pub(super) fn resolve_session_defaults(
+        &self,
+        defaults: SessionDefaults,
+        agent: AgentId,
+    ) -> Result<(SessionDefaults, Vec<String>), SandboxError> {
+        let mut defaults = defaults.or(self.inner.session_defaults.get(&agent));
+        let mut models = match defaults.model.as_deref() {
+            Some(model) => self.inner.model_routes.resolve(model, agent)?,
+            None => Vec::new(),
+        };
+        defaults.model = (!models.is_empty()).then(|| models.remove(0));
+        Ok((defaults, models))
+    }
+}
+
+/// Switch the session a `session/new` response `value` created to
+/// `defaults`, and set the current ids in its result to the values the
+/// agent accepted. A rejected switch leaves the agent's choice in place.
+pub(super) async fn apply_session_defaults(
+    instance: &ProxyInstance,
+    defaults: &SessionDefaults,
+    value: &mut Value,
+) {
+    let (Some(session_id), Some(result)) = (
+        value
+            .pointer("/result/sessionId")
+            .and_then(Value::as_str)
+            .map(ToOwned::to_owned),
+        value.get("result"),
+    ) else {
+        return;
+    };
+    for (setting, id) in defaults.switches(result) {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": format!(
+                "sandboxagent-{}-{}",
+                setting.method.trim_start_matches("session/").replace('_', "-"),
+                now_ms()
+            ),
+            "method": setting.method,
+            "params": { "sessionId": session_id, setting.param: id },
+        });
+        let error = match instance.runtime.post(request).await {
+            Ok(PostOutcome::Response(response)) => match response.get("error") {
+                Some(error) => error.to_string(),
+                None => {
+                    value["result"][setting.group][setting.current] = json!(id);
+                    continue;
+                }
+            },
+            Ok(PostOutcome::Accepted) => continue,
+            Err(err) => err.to_string(),
+        };
+        tracing::warn!(
+            server_id = %instance.server_id,
+            session_id = %session_id,
+            method = setting.method,
+            error = %error,
+            "acp_proxy: agent rejected a session default"
+        );
+    }
+}
@@ -0,0 +1,480 @@
+//! Session records, and the session routes built on them.
+
+use super::*;
+
+impl AcpProxyRuntime {
+    /// `session_id` on `server_id`, open or ended. Ended sessions are kept
+    /// until the server is deleted, even when their process was replaced.
+    pub async fn session_detail(
+        &self,
+        server_id: &str,
+        session_id: &str,
+    ) -> Result<AcpSessionDetail, SandboxError> {
+        let record = self
+            .inner
+            .sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(server_id)
+            .and_then(|sessions| sessions.get(session_id))
+            .cloned()
+            .ok_or_else(|| SandboxError::SessionNotFound {
+                session_id: format!("{server_id}/sessions/{session_id}"),
+            })?;
+        let instance = self
+            .inner
+            .instances
+            .read()
+            .await
+            .get(server_id)
+            .cloned()
+            .filter(|_| record.ended.is_none());
+        let mode_id = instance.as_ref().and_then(|instance| {
+            instance
+                .session_modes
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get(session_id)
+                .cloned()
+        });
+        let prompt_in_flight = instance.as_ref().is_some_and(|instance| {
+            instance
+                .active_prompts
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .contains_key(session_id)
+        });
+        let (pending_permissions, pending_questions) = instance
+            .as_ref()
+            .map(|instance| {
+                instance
+                    .pending_requests
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .values()
+                    .filter(|request| request.session_id.as_deref() == Some(session_id))
+                    .fold((0, 0), |(permissions, questions), request| {
+                        if request.method == "session/request_permission" {
+                            (permissions + 1, questions)
+                        } else {
+                            (permissions, questions + 1)
+                        }
+                    })
+            })
+            .unwrap_or_default();
+        // Events stay readable after a crash, until the server is replaced.
+        let events = self
+            .poll_events(server_id, None, usize::MAX, Duration::ZERO, false)
+            .await
+            .unwrap_or_default();
+        let buffered_events = events
+            .iter()
+            .filter(|(_, payload)| {
+                payload.pointer("/params/sessionId").and_then(Value::as_str) == Some(session_id)
+            })
+            .count();
+        let next_offset = events.last().map_or(0, |(sequence, _)| *sequence);
+        Ok(AcpSessionDetail {
+            server_id: server_id.to_string(),
+            session_id: session_id.to_string(),
+            record,
+            mode_id,
+            prompt_in_flight,
+            pending_permissions,
+            pending_questions,
+            buffered_events,
+            next_offset,
+        })
+    }
+
+    /// Change the mode, model or permission mode of an open session with
+    /// `session/set_mode` and `session/set_model`, then record the change
+    /// with an `_adapter/session_updated` notification and a
+    /// `session.updated` change record. Values are checked against what the
+    /// agent advertised for the session, when it advertised anything.
+    pub async fn patch_session(
+        &self,
+        server_id: &str,
+        session_id: &str,
+        patch: AcpSessionPatch,
+    ) -> Result<AcpSessionDetail, SandboxError> {
+        if patch.mode_id.is_none() && patch.model_id.is_none() && patch.permission_mode.is_none() {
+            return Err(SandboxError::InvalidRequest {
+                message: "set at least one of modeId, modelId or permissionMode".to_string(),
+            });
+        }
+        let record = self.session_detail(server_id, session_id).await?.record;
+        if record.ended.is_some() {
+            return Err(SandboxError::Conflict {
+                message: format!("session '{session_id}' has ended"),
+            });
+        }
+        let instance = self.get_instance(server_id).await?;
+
+        let advertised = |list: &[Value], key: &str, value: &str, field: &str| {
+            let ids = list
+                .iter()
+                .filter_map(|entry| entry.get(key).and_then(Value::as_str))
+                .collect::<Vec<_>>();
+            if ids.is_empty() || ids.contains(&value) {
+                Ok(())
+            } else {
+                Err(SandboxError::InvalidRequest {
+                    message: format!("{field} '{value}' is not one of {}", ids.join(", ")),
+                })
+            }
+        };
+        if let Some(mode_id) = &patch.mode_id {
+            advertised(&record.available_modes, "id", mode_id, "modeId")?;
+        }
+        let mut models = match patch.model_id.as_deref() {
+            Some(model) => self.inner.model_routes.resolve(model, record.agent)?,
+            None => Vec::new(),
+        };
+        let model_id = (!models.is_empty()).then(|| models.remove(0));
+        if let Some(model_id) = &model_id {
+            advertised(&record.available_models, "modelId", model_id, "modelId")?;
+        }
+        let permission_mode = patch
+            .permission_mode
+            .as_ref()
+            .map(|raw| {
+                let mode =
+                    PermissionMode::parse(raw).map_err(|message| SandboxError::InvalidRequest {
+                        message: format!("permissionMode {message}"),
+                    })?;
+                mode.launch_for(record.agent)?;
+                mode.session_mode_for(record.agent)
+                    .map(|mode_id| (mode, mode_id))
+                    .ok_or_else(|| SandboxError::Conflict {
+                        message: format!(
+                            "agent '{}' applies permission modes when its process starts; \
+                             create a new server with _meta[\"sandboxagent.dev\"].permissionMode",
+                            record.agent.as_str()
+                        ),
+                    })
+            })
+            .transpose()?;
+        if patch.mode_id.is_some() && permission_mode.is_some() {
+            return Err(SandboxError::InvalidRequest {
+                message: "modeId and permissionMode both set the session mode; set one".to_string(),
+            });
+        }
+
+        let mode_id = patch
+            .mode_id
+            .clone()
+            .or_else(|| permission_mode.map(|(_, mode_id)| mode_id.to_string()));
+        if let Some(mode_id) = &mode_id {
+            self.post_expect_result(
+                server_id,
+                None,
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": format!("sandboxagent-set-mode-{}", now_ms()),
+                    "method": "session/set_mode",
+                    "params": { "sessionId": session_id, "modeId": mode_id }
+                }),
+            )
+            .await?;
+        }
+        if let Some(model_id) = &model_id {
+            self.post_expect_result(
+                server_id,
+                None,
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": format!("sandboxagent-set-model-{}", now_ms()),
+                    "method": "session/set_model",
+                    "params": { "sessionId": session_id, "modelId": model_id }
+                }),
+            )
+            .await?;
+            instance.set_model_fallbacks(session_id, models);
+        }
+
+        let mut update = json!({ "sessionId": session_id });
+        if let Some(mode_id) = mode_id {
+            update["modeId"] = json!(mode_id);
+        }
+        if let Some(model_id) = model_id {
+            update["modelId"] = json!(model_id);
+        }
+        if let Some((mode, _)) = permission_mode {
+            update["permissionMode"] = json!(mode.name());
+        }
+        instance
+            .runtime
+            .publish_notification(json!({
+                "jsonrpc": "2.0",
+                "method": SESSION_UPDATED_METHOD,
+                "params": update.clone(),
+            }))
+            .await;
+        self.inner.cdc.record("session.updated", server_id, update);
+        self.session_detail(server_id, session_id).await
+    }
+
+    /// Attach an existing native agent session to `server_id` using ACP
+    /// `session/load`. The agent process replays the stored transcript as
+    /// `session/update` notifications, so the imported history shows up on the
+    /// server's SSE stream and later prompts continue the native session.
+    pub async fn import_session(
+        &self,
+        server_id: &str,
+        agent: AgentId,
+        native_session_id: &str,
+        cwd: &str,
+    ) -> Result<Value, SandboxError> {
+        let initialize = json!({
+            "jsonrpc": "2.0",
+            "id": "sandboxagent-import-initialize",
+            "method": "initialize",
+            "params": {
+                "protocolVersion": 1,
+                "clientCapabilities": {}
+            }
+        });
+        let initialized = self
+            .post_expect_result(server_id, Some(agent), initialize)
+            .await?;
+
+        let supports_load = initialized
+            .pointer("/agentCapabilities/loadSession")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        if !supports_load {
+            return Err(SandboxError::ModeNotSupported {
+                agent: agent.as_str().to_string(),
+                mode: "session/load".to_string(),
+            });
+        }
+
+        let load = json!({
+            "jsonrpc": "2.0",
+            "id": "sandboxagent-import-load",
+            "method": "session/load",
+            "params": {
+                "sessionId": native_session_id,
+                "cwd": cwd,
+                "mcpServers": []
+            }
+        });
+        self.post_expect_result(server_id, Some(agent), load).await
+    }
+
+    /// Compact `session_id` on `server_id` with the agent's own `/compact`
+    /// when it offers one, or with a summarization turn otherwise, and
+    /// record an `_adapter/compaction` notification on the server's stream.
+    pub async fn compact(
+        &self,
+        server_id: &str,
+        session_id: &str,
+    ) -> Result<AcpCompaction, SandboxError> {
+        let instance = self.get_instance(server_id).await?;
+        let events = self
+            .poll_events(server_id, None, usize::MAX, Duration::ZERO, false)
+            .await?;
+        let mode = compaction::mode_for(&events, session_id);
+        let before_tokens = compaction::estimate_session_tokens(&events, session_id);
+        let sequence_before = instance.runtime.last_sequence();
+
+        let prompt = json!({
+            "jsonrpc": "2.0",
+            "id": format!("sandboxagent-compact-{}", now_ms()),
+            "method": "session/prompt",
+            "params": {
+                "sessionId": session_id,
+                "prompt": [{ "type": "text", "text": mode.prompt() }]
+            }
+        });
+        let result = self.post_expect_result(server_id, None, prompt).await?;
+
+        let reply = self
+            .poll_events(
+                server_id,
+                Some(sequence_before),
+                usize::MAX,
+                Duration::ZERO,
+                false,
+            )
+            .await?;
+        let reply = compaction::agent_reply(&reply, session_id);
+        let after_tokens = compaction::estimate_tokens(&reply);
+        let compaction = AcpCompaction {
+            session_id: session_id.to_string(),
+            mode,
+            before_tokens,
+            after_tokens,
+            summary: (mode == CompactionMode::Summary).then_some(reply),
+            stop_reason: result
+                .get("stopReason")
+                .and_then(Value::as_str)
+                .map(ToOwned::to_owned),
+        };
+        let record = json!({
+            "sessionId": session_id,
+            "mode": mode.as_str(),
+            "beforeTokens": before_tokens,
+            "afterTokens": after_tokens,
+        });
+        instance
+            .runtime
+            .publish_notification(json!({
+                "jsonrpc": "2.0",
+                "method": compaction::COMPACTION_METHOD,
+                "params": record,
+            }))
+            .await;
+        self.inner
+            .cdc
+            .record("session.compacted", server_id, record);
+        Ok(compaction)
+    }
+
+    /// Sub-agents `session_id` started, from the envelopes still buffered.
+    pub async fn child_sessions(
+        &self,
+        server_id: &str,
+        session_id: &str,
+    ) -> Result<Vec<ChildSession>, SandboxError> {
+        let events = self
+            .poll_events(server_id, None, usize::MAX, Duration::ZERO, false)
+            .await?;
+        Ok(subagents::child_sessions(&events, session_id))
+    }
+
+    /// End the sessions of `instance` once its agent process exits.
+    pub(super) async fn watch_session_ends(&self, instance: &Arc<ProxyInstance>) {
+        let mut envelopes = instance.runtime.clone().sequenced_stream(None).await;
+        let runtime = self.clone();
+        let instance = Arc::downgrade(instance);
+        tokio::spawn(async move {
+            while let Some((_, payload)) = envelopes.next().await {
+                if payload.get("method").and_then(Value::as_str) != Some("_adapter/agent_exited") {
+                    continue;
+                }
+                let status = |key: &str| {
+                    payload
+                        .pointer(&format!("/params/{key}"))
+                        .and_then(Value::as_i64)
+                        .map(|value| value as i32)
+                };
+                if let Some(instance) = instance.upgrade() {
+                    runtime
+                        .end_sessions(&instance, status("code"), status("signal"))
+                        .await;
+                }
+                return;
+            }
+        });
+    }
+
+    pub(super) fn record_session_started(
+        &self,
+        instance: &ProxyInstance,
+        session_id: &str,
+        result: Option<&Value>,
+    ) {
+        self.inner
+            .sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(instance.server_id.clone())
+            .or_default()
+            .insert(
+                session_id.to_string(),
+                SessionRecord::new(instance.agent, now_ms(), result),
+            );
+        let mut session_ids = instance
+            .session_ids
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if !session_ids.iter().any(|id| id == session_id) {
+            session_ids.push(session_id.to_string());
+        }
+    }
+
+    pub(super) fn update_session(
+        &self,
+        server_id: &str,
+        session_id: &str,
+        update: impl FnOnce(&mut SessionRecord),
+    ) {
+        if let Some(record) = self
+            .inner
+            .sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_mut(server_id)
+            .and_then(|sessions| sessions.get_mut(session_id))
+        {
+            update(record);
+        }
+    }
+
+    /// End the open sessions of `instance`, whose process exited with
+    /// `code` or `signal`, with an `_adapter/session_ended` notification and
+    /// a `session.ended` change record each.
+    pub(super) async fn end_sessions(
+        &self,
+        instance: &ProxyInstance,
+        code: Option<i32>,
+        signal: Option<i32>,
+    ) {
+        let reason = SessionEndReason::for_exit(
+            *instance
+                .stopped_by
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+            code,
+        );
+        let ended_at_ms = now_ms();
+        let records = {
+            let mut sessions = self
+                .inner
+                .sessions
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let Some(server) = sessions.get_mut(&instance.server_id) else {
+                return;
+            };
+            instance
+                .session_ids
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+                .filter_map(|session_id| {
+                    server
+                        .get_mut(session_id)?
+                        .end(session_id, reason, code, signal, ended_at_ms)
+                })
+                .collect::<Vec<_>>()
+        };
+        for record in records {
+            tracing::info!(
+                server_id = %instance.server_id,
+                session_id = ?record.get("sessionId"),
+                reason = reason.as_str(),
+                "acp_proxy: session ended"
+            );
+            if let Some(session_id) = record.get("sessionId").and_then(Value::as_str) {
+                self.inner
+                    .claude_settings
+                    .release(&server_dir_name(&instance.server_id), session_id)
+                    .await;
+            }
+            instance
+                .runtime
+                .publish_notification(json!({
+                    "jsonrpc": "2.0",
+                    "method": SESSION_ENDED_METHOD,
+                    "params": record.clone(),
+                }))
+                .await;
+            self.inner
+                .cdc
+                .record("session.ended", &instance.server_id, record);
+        }
+    }
+}
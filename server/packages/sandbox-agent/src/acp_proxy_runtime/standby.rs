@@ -0,0 +1,59 @@
+//! Standby agent processes that new servers adopt instead of spawning.
+
+use super::*;
+
+impl AcpProxyRuntime {
+    /// Spawn standbys for every agent with a warm pool.
+    pub fn start_warm_pool(&self) {
+        for agent in self.inner.warm_pool.agents() {
+            self.refill_warm_pool(agent);
+        }
+    }
+
+    /// Spawn standbys in the background until the pool of `agent` is full.
+    pub(super) fn refill_warm_pool(&self, agent: AgentId) {
+        while self.inner.warm_pool.reserve(agent) {
+            let runtime = self.clone();
+            tokio::spawn(async move {
+                let standby = runtime.spawn_standby(agent).await;
+                if let Err(err) = &standby {
+                    tracing::warn!(
+                        agent = agent.as_str(),
+                        error = %err,
+                        "warm pool: failed to spawn standby"
+                    );
+                }
+                runtime.inner.warm_pool.fill(agent, standby.ok());
+            });
+        }
+    }
+
+    /// Start a process of `agent` with its default environment.
+    async fn spawn_standby(&self, agent: AgentId) -> Result<Standby, SandboxError> {
+        self.ensure_installed(agent).await?;
+        let env = self
+            .inner
+            .agent_env
+            .get(&agent)
+            .cloned()
+            .unwrap_or_default();
+        let spec = self
+            .launch_spec(agent, env, &PermissionLaunch::default())
+            .await?;
+        let runtime = AdapterRuntime::start(spec.clone(), self.inner.request_timeout)
+            .await
+            .map_err(|err| map_adapter_error(agent, err))?;
+        Ok(Standby { runtime, spec })
+    }
+
+    /// Stop the standbys of `agent`, whose install is about to change.
+    pub(super) async fn drain_warm_pool(&self, agent: AgentId) {
+        for standby in self.inner.warm_pool.drain(agent) {
+            standby.runtime.shutdown().await;
+        }
+    }
+
+    pub fn warm_pool_status(&self, agent: AgentId) -> Option<WarmPoolStatus> {
+        self.inner.warm_pool.status(agent)
+    }
+}
@@ -0,0 +1,102 @@
+//! Track agent versions that stopped working with the proxy.
+//!
+//! An agent process that exits on a rejected flag (`usage_error`), or that
+//! keeps writing stdout lines that are not JSON-RPC, was most likely updated
+//! past what the proxy understands. The latest such incompatibility per agent
+//! is kept with the installed version and the versions known to work, so
+//! `GET /v1/agents` can suggest a downgrade. With
+//! `SANDBOX_AGENT_AUTO_DOWNGRADE=1` the proxy installs those versions itself.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+
+use sandbox_agent_agent_management::agents::{AgentId, AgentPin};
+
+/// `_adapter/invalid_stdout` notifications from one process before its agent
+/// is flagged for schema drift.
+pub(crate) const SCHEMA_DRIFT_THRESHOLD: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncompatibilityReason {
+    UsageError,
+    SchemaDrift,
+}
+
+impl IncompatibilityReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::UsageError => "usage_error",
+            Self::SchemaDrift => "schema_drift",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AgentIncompatibility {
+    pub reason: IncompatibilityReason,
+    pub detail: String,
+    pub installed_version: Option<String>,
+    pub suggested: Option<AgentPin>,
+    pub auto_installed: bool,
+    pub detected_at_ms: i64,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct AgentCompat {
+    auto_downgrade: bool,
+    incompatibilities: Mutex<HashMap<AgentId, AgentIncompatibility>>,
+}
+
+impl AgentCompat {
+    pub(crate) fn from_env() -> Self {
+        let auto_downgrade = std::env::var("SANDBOX_AGENT_AUTO_DOWNGRADE")
+            .ok()
+            .is_some_and(|value| {
+                let trimmed = value.trim();
+                trimmed == "1"
+                    || trimmed.eq_ignore_ascii_case("true")
+                    || trimmed.eq_ignore_ascii_case("yes")
+            });
+        Self {
+            auto_downgrade,
+            incompatibilities: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn auto_downgrade(&self) -> bool {
+        self.auto_downgrade
+    }
+
+    pub(crate) fn get(&self, agent: AgentId) -> Option<AgentIncompatibility> {
+        self.incompatibilities
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&agent)
+            .cloned()
+    }
+
+    pub(crate) fn record(&self, agent: AgentId, incompatibility: AgentIncompatibility) {
+        self.incompatibilities
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(agent, incompatibility);
+    }
+
+    pub(crate) fn mark_auto_installed(&self, agent: AgentId) {
+        if let Some(entry) = self
+            .incompatibilities
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_mut(&agent)
+        {
+            entry.auto_installed = true;
+        }
+    }
+
+    pub(crate) fn clear(&self, agent: AgentId) {
+        self.incompatibilities
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&agent);
+    }
+}
@@ -0,0 +1,118 @@
+//! Classify agent process exits into a small, agent-independent taxonomy.
+//!
+//! Agents disagree on exit codes: codex-acp (clap) exits 2 on bad arguments,
+//! Node-based agents exit 9 on invalid options and 134 when V8 aborts. Auth
+//! and rate-limit failures usually exit 1 everywhere, so the stderr tail is
+//! checked before the exit code.
+
+use acp_http_adapter::process::ProcessExit;
+use sandbox_agent_agent_management::agents::AgentId;
+
+const SIGILL: i32 = 4;
+const SIGABRT: i32 = 6;
+const SIGBUS: i32 = 7;
+const SIGFPE: i32 = 8;
+const SIGKILL: i32 = 9;
+const SIGSEGV: i32 = 11;
+const SIGTERM: i32 = 15;
+
+/// Lowercase stderr fragments, checked in order.
+const STDERR_PATTERNS: &[(&str, AgentExitReason)] = &[
+    ("invalid api key", AgentExitReason::AuthFailed),
+    ("invalid x-api-key", AgentExitReason::AuthFailed),
+    ("api key not found", AgentExitReason::AuthFailed),
+    ("missing api key", AgentExitReason::AuthFailed),
+    ("not logged in", AgentExitReason::AuthFailed),
+    ("please log in", AgentExitReason::AuthFailed),
+    ("please login", AgentExitReason::AuthFailed),
+    ("authentication failed", AgentExitReason::AuthFailed),
+    ("authentication_error", AgentExitReason::AuthFailed),
+    ("unauthorized", AgentExitReason::AuthFailed),
+    ("rate limit", AgentExitReason::RateLimited),
+    ("rate_limit", AgentExitReason::RateLimited),
+    ("too many requests", AgentExitReason::RateLimited),
+    ("quota exceeded", AgentExitReason::RateLimited),
+    ("insufficient_quota", AgentExitReason::RateLimited),
+    ("heap out of memory", AgentExitReason::OutOfMemory),
+    ("out of memory", AgentExitReason::OutOfMemory),
+    ("cannot allocate memory", AgentExitReason::OutOfMemory),
+    ("unexpected argument", AgentExitReason::UsageError),
+    ("unknown option", AgentExitReason::UsageError),
+    ("bad option", AgentExitReason::UsageError),
+    ("unrecognized option", AgentExitReason::UsageError),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AgentExitReason {
+    Completed,
+    AuthFailed,
+    UsageError,
+    RateLimited,
+    OutOfMemory,
+    Killed,
+    Crashed,
+    Failed,
+}
+
+impl AgentExitReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Completed => "completed",
+            Self::AuthFailed => "auth_failed",
+            Self::UsageError => "usage_error",
+            Self::RateLimited => "rate_limited",
+            Self::OutOfMemory => "out_of_memory",
+            Self::Killed => "killed",
+            Self::Crashed => "crashed",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+pub fn classify_exit(agent: AgentId, exit: &ProcessExit) -> AgentExitReason {
+    if exit.code == Some(0) {
+        return AgentExitReason::Completed;
+    }
+
+    let stderr = exit.stderr.join("\n").to_lowercase();
+    if let Some((_, reason)) = STDERR_PATTERNS
+        .iter()
+        .find(|(pattern, _)| stderr.contains(pattern))
+    {
+        return *reason;
+    }
+
+    if let Some(signal) = exit.signal {
+        return signal_reason(signal);
+    }
+
+    match exit.code {
+        Some(code) => code_reason(agent, code),
+        None => AgentExitReason::Failed,
+    }
+}
+
+/// Whether lowercase `text` matches a rate-limit pattern.
+pub(crate) fn mentions_rate_limit(text: &str) -> bool {
+    STDERR_PATTERNS
+        .iter()
+        .any(|(pattern, reason)| *reason == AgentExitReason::RateLimited && text.contains(pattern))
+}
+
+fn signal_reason(signal: i32) -> AgentExitReason {
+    match signal {
+        SIGKILL | SIGTERM => AgentExitReason::Killed,
+        SIGILL | SIGABRT | SIGBUS | SIGFPE | SIGSEGV => AgentExitReason::Crashed,
+        _ => AgentExitReason::Failed,
+    }
+}
+
+fn code_reason(agent: AgentId, code: i32) -> AgentExitReason {
+    match (agent, code) {
+        (AgentId::Codex, 2) => AgentExitReason::UsageError,
+        (AgentId::Claude | AgentId::Amp | AgentId::Pi, 9) => AgentExitReason::UsageError,
+        // Shells and Node wrappers report signals as 128 + signo.
+        (_, code) if code > 128 && code <= 128 + 64 => signal_reason(code - 128),
+        _ => AgentExitReason::Failed,
+    }
+}
@@ -0,0 +1,96 @@
+//! Failing over to another agent when the requested one cannot start.
+//!
+//! The bootstrap POST of a server can list agents to fall back to with
+//! `fallbackAgents` under `_meta["sandboxagent.dev"]`. When the requested
+//! agent fails to install or spawn, or its process exits before answering
+//! that first request, the server is started on the next agent in the list
+//! and the request sent again. Each switch is announced on the new server
+//! with an `_adapter/agent_failover` notification and a `server.failover`
+//! change record saying why.
+
+use sandbox_agent_agent_management::agents::AgentId;
+use sandbox_agent_error::SandboxError;
+use serde_json::{json, Value};
+
+pub(crate) const AGENT_FAILOVER_METHOD: &str = "_adapter/agent_failover";
+
+/// One switch from an agent that could not start to the next.
+#[derive(Debug, Clone)]
+pub(crate) struct AgentFailover {
+    pub(crate) from: AgentId,
+    pub(crate) to: AgentId,
+    /// `install_failed`, `not_installed`, `spawn_failed` or `exited`.
+    pub(crate) reason: &'static str,
+    pub(crate) message: String,
+}
+
+impl AgentFailover {
+    /// The failover from `from` to `to` after `err`, if `err` means `from`
+    /// could not start. Other errors are the request's own and are returned
+    /// to the client as they are.
+    pub(crate) fn after(from: AgentId, to: AgentId, err: &SandboxError) -> Option<Self> {
+        let reason = match err {
+            SandboxError::InstallFailed { .. } => "install_failed",
+            SandboxError::AgentNotInstalled { .. } => "not_installed",
+            SandboxError::SpawnFailed { .. } => "spawn_failed",
+            SandboxError::AgentProcessExited { .. } => "exited",
+            _ => return None,
+        };
+        let message = match err {
+            SandboxError::InstallFailed {
+                stderr: Some(stderr),
+                ..
+            }
+            | SandboxError::AgentProcessExited {
+                stderr: Some(stderr),
+                ..
+            } if !stderr.trim().is_empty() => format!("{err}: {}", stderr.trim()),
+            _ => err.to_string(),
+        };
+        Some(Self {
+            from,
+            to,
+            reason,
+            message,
+        })
+    }
+
+    /// Params of the `_adapter/agent_failover` notification and the
+    /// `server.failover` record.
+    pub(crate) fn to_json(&self) -> Value {
+        json!({
+            "from": self.from.as_str(),
+            "to": self.to.as_str(),
+            "reason": self.reason,
+            "message": self.message,
+        })
+    }
+}
+
+/// Read `params._meta["sandboxagent.dev"].fallbackAgents`: agent ids to try,
+/// in order, when the requested agent cannot start.
+pub(crate) fn fallback_agents_from_payload(payload: &Value) -> Result<Vec<AgentId>, SandboxError> {
+    let Some(raw) = payload.pointer("/params/_meta/sandboxagent.dev/fallbackAgents") else {
+        return Ok(Vec::new());
+    };
+    let invalid = |detail: String| SandboxError::InvalidRequest {
+        message: format!("_meta[\"sandboxagent.dev\"].fallbackAgents {detail}"),
+    };
+    let entries = match raw {
+        Value::Null => return Ok(Vec::new()),
+        Value::Array(entries) => entries,
+        _ => return Err(invalid("must be an array of agent ids".to_string())),
+    };
+    let mut agents = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let name = entry
+            .as_str()
+            .ok_or_else(|| invalid("must be an array of agent ids".to_string()))?;
+        let agent =
+            AgentId::parse(name).ok_or_else(|| invalid(format!("names unknown agent '{name}'")))?;
+        if !agents.contains(&agent) {
+            agents.push(agent);
+        }
+    }
+    Ok(agents)
+}
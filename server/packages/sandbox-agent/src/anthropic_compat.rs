@@ -0,0 +1,399 @@
+//! Anthropic Messages API on top of the ACP proxy.
+//!
+//! `POST /v1/compat/anthropic/v1/messages` lets tools that only speak the
+//! Messages API drive a sandboxed agent. Each request starts a one-off ACP
+//! server for the agent named by `model` (or the `x-sandbox-agent` header),
+//! creates a session with `system` as its system prompt, and sends the
+//! conversation as one `session/prompt`. The agent's `agent_message_chunk`
+//! updates become a single text block, streamed as Messages API SSE events
+//! when `stream` is set. The server is closed once the turn ends, since the
+//! Messages API resends the whole conversation on every call.
+
+use axum::http::StatusCode;
+use axum::response::sse::Event;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use futures::{stream, FutureExt, StreamExt};
+use sandbox_agent_agent_management::agents::AgentId;
+use sandbox_agent_error::SandboxError;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::acp_proxy_runtime::{
+    AcpProxyRuntime, AcpTurnUsage, PinBoxSseStream, SequencedPayloadStream,
+};
+use crate::router::{AnthropicMessageParam, AnthropicMessagesRequest};
+use crate::tasks::new_task_id;
+
+const PROMPT_REQUEST_ID: &str = "sandboxagent-anthropic-prompt";
+
+/// What the agent produced for the turn, in order.
+enum TurnEvent {
+    Text(String),
+    Done {
+        stop_reason: Option<String>,
+        usage: Option<AcpTurnUsage>,
+    },
+    Failed(SandboxError),
+}
+
+/// A started turn whose events arrive on `events`.
+pub(crate) struct Turn {
+    message_id: String,
+    model: String,
+    events: mpsc::Receiver<TurnEvent>,
+}
+
+/// The agent a request is for: the `x-sandbox-agent` header if set, else
+/// `model` itself or the part of it before a `/`.
+pub(crate) fn resolve_agent(header: Option<&str>, model: &str) -> Result<AgentId, SandboxError> {
+    let requested = header
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| model.split('/').next().unwrap_or(model).trim());
+    AgentId::parse(requested).ok_or_else(|| SandboxError::InvalidRequest {
+        message: format!(
+            "unknown agent {requested:?}; set model to an agent id such as \"claude\" or send x-sandbox-agent"
+        ),
+    })
+}
+
+/// Create the session and send the prompt. Events are delivered as the
+/// agent writes them; the server is closed after the last one.
+pub(crate) async fn start_turn(
+    acp_proxy: AcpProxyRuntime,
+    agent: AgentId,
+    request: &AnthropicMessagesRequest,
+) -> Result<Turn, SandboxError> {
+    let prompt = prompt_blocks(&request.messages)?;
+    let system = request.system.as_ref().map(text_of).transpose()?;
+    let message_id = format!("msg_{}", new_task_id());
+    let server_id = format!("anthropic-{message_id}");
+
+    let started = open_session(&acp_proxy, &server_id, agent, system).await;
+    let (session_id, envelopes) = match started {
+        Ok(started) => started,
+        Err(err) => {
+            if let Err(delete_err) = acp_proxy.delete(&server_id).await {
+                tracing::debug!(server_id = %server_id, error = %delete_err, "anthropic: cleanup failed");
+            }
+            return Err(err);
+        }
+    };
+
+    let (sender, events) = mpsc::channel(64);
+    let session_prompt = json!({
+        "jsonrpc": "2.0",
+        "id": PROMPT_REQUEST_ID,
+        "method": "session/prompt",
+        "params": { "sessionId": session_id, "prompt": prompt }
+    });
+    tokio::spawn(drive_turn(
+        acp_proxy,
+        server_id,
+        session_id,
+        envelopes,
+        session_prompt,
+        sender,
+    ));
+    Ok(Turn {
+        message_id,
+        model: request.model.clone(),
+        events,
+    })
+}
+
+async fn open_session(
+    acp_proxy: &AcpProxyRuntime,
+    server_id: &str,
+    agent: AgentId,
+    system: Option<String>,
+) -> Result<(String, SequencedPayloadStream), SandboxError> {
+    let initialize = json!({
+        "jsonrpc": "2.0",
+        "id": "sandboxagent-anthropic-initialize",
+        "method": "initialize",
+        "params": { "protocolVersion": 1, "clientCapabilities": {} }
+    });
+    acp_proxy
+        .post_expect_result(server_id, Some(agent), initialize)
+        .await?;
+
+    let mut params = json!({
+        "cwd": std::env::current_dir()
+            .map_err(|err| SandboxError::StreamError { message: err.to_string() })?
+            .to_string_lossy(),
+        "mcpServers": [],
+    });
+    if let Some(system) = system {
+        params["_meta"] = json!({ "systemPrompt": system });
+    }
+    let session_new = json!({
+        "jsonrpc": "2.0",
+        "id": "sandboxagent-anthropic-session-new",
+        "method": "session/new",
+        "params": params
+    });
+    let session = acp_proxy
+        .post_expect_result(server_id, None, session_new)
+        .await?;
+    let session_id = session
+        .get("sessionId")
+        .and_then(Value::as_str)
+        .ok_or_else(|| SandboxError::UpstreamAgentError {
+            message: "agent did not return a sessionId for session/new".to_string(),
+        })?
+        .to_string();
+
+    // Subscribe before prompting so no reply chunk is missed.
+    let envelopes = acp_proxy.sequenced_stream(server_id, None, false).await?;
+    Ok((session_id, envelopes))
+}
+
+async fn drive_turn(
+    acp_proxy: AcpProxyRuntime,
+    server_id: String,
+    session_id: String,
+    mut envelopes: SequencedPayloadStream,
+    session_prompt: Value,
+    sender: mpsc::Sender<TurnEvent>,
+) {
+    let prompt = acp_proxy.post_expect_result(&server_id, None, session_prompt);
+    tokio::pin!(prompt);
+    let outcome = loop {
+        tokio::select! {
+            result = &mut prompt => break result,
+            Some((_, payload)) = envelopes.next() => {
+                if let Some(text) = message_chunk(&payload, &session_id) {
+                    // A client that hung up still lets the turn finish.
+                    let _ = sender.send(TurnEvent::Text(text)).await;
+                }
+            }
+        }
+    };
+    // The agent writes its updates before the prompt response, so the rest
+    // are buffered by now.
+    while let Some(Some((_, payload))) = envelopes.next().now_or_never() {
+        if let Some(text) = message_chunk(&payload, &session_id) {
+            let _ = sender.send(TurnEvent::Text(text)).await;
+        }
+    }
+    let last = match outcome {
+        Ok(result) => TurnEvent::Done {
+            stop_reason: result
+                .get("stopReason")
+                .and_then(Value::as_str)
+                .map(ToOwned::to_owned),
+            usage: AcpTurnUsage::from_response(&json!({ "result": result })),
+        },
+        Err(err) => TurnEvent::Failed(err),
+    };
+    let _ = sender.send(last).await;
+
+    if let Err(err) = acp_proxy.delete(&server_id).await {
+        tracing::warn!(server_id = %server_id, error = %err, "anthropic: failed to close server");
+    }
+}
+
+fn message_chunk(payload: &Value, session_id: &str) -> Option<String> {
+    if payload.pointer("/params/sessionId").and_then(Value::as_str) != Some(session_id) {
+        return None;
+    }
+    let update = payload.pointer("/params/update")?;
+    if update.get("sessionUpdate").and_then(Value::as_str) != Some("agent_message_chunk") {
+        return None;
+    }
+    update
+        .pointer("/content/text")
+        .and_then(Value::as_str)
+        .map(ToOwned::to_owned)
+}
+
+impl Turn {
+    /// Wait for the turn to end and build a Messages API `message`.
+    pub(crate) async fn into_message(mut self) -> Result<Value, SandboxError> {
+        let mut text = String::new();
+        while let Some(event) = self.events.recv().await {
+            match event {
+                TurnEvent::Text(chunk) => text.push_str(&chunk),
+                TurnEvent::Done { stop_reason, usage } => {
+                    return Ok(json!({
+                        "id": self.message_id,
+                        "type": "message",
+                        "role": "assistant",
+                        "model": self.model,
+                        "content": [{ "type": "text", "text": text }],
+                        "stop_reason": anthropic_stop_reason(stop_reason.as_deref()),
+                        "stop_sequence": null,
+                        "usage": anthropic_usage(usage.as_ref()),
+                    }));
+                }
+                TurnEvent::Failed(err) => return Err(err),
+            }
+        }
+        Err(SandboxError::UpstreamAgentNoResponse {
+            message: "agent turn ended without a response".to_string(),
+        })
+    }
+
+    /// The turn as Messages API stream events: `message_start`, one text
+    /// content block, `message_delta` and `message_stop`, or `error`.
+    pub(crate) fn into_sse(self) -> PinBoxSseStream {
+        let head = [
+            sse_event(json!({
+                "type": "message_start",
+                "message": {
+                    "id": self.message_id,
+                    "type": "message",
+                    "role": "assistant",
+                    "model": self.model,
+                    "content": [],
+                    "stop_reason": null,
+                    "stop_sequence": null,
+                    "usage": { "input_tokens": 0, "output_tokens": 0 }
+                }
+            })),
+            sse_event(json!({
+                "type": "content_block_start",
+                "index": 0,
+                "content_block": { "type": "text", "text": "" }
+            })),
+        ];
+        let body = ReceiverStream::new(self.events).flat_map(|event| {
+            let events = match event {
+                TurnEvent::Text(text) => [sse_event(json!({
+                    "type": "content_block_delta",
+                    "index": 0,
+                    "delta": { "type": "text_delta", "text": text }
+                }))]
+                .to_vec(),
+                TurnEvent::Done { stop_reason, usage } => [
+                    sse_event(json!({ "type": "content_block_stop", "index": 0 })),
+                    sse_event(json!({
+                        "type": "message_delta",
+                        "delta": {
+                            "stop_reason": anthropic_stop_reason(stop_reason.as_deref()),
+                            "stop_sequence": null
+                        },
+                        "usage": anthropic_usage(usage.as_ref())
+                    })),
+                    sse_event(json!({ "type": "message_stop" })),
+                ]
+                .to_vec(),
+                TurnEvent::Failed(err) => [sse_event(json!({
+                    "type": "error",
+                    "error": { "type": "api_error", "message": err.to_string() }
+                }))]
+                .to_vec(),
+            };
+            stream::iter(events)
+        });
+        Box::pin(stream::iter(head).chain(body).map(Ok))
+    }
+}
+
+/// Events are named after their `type`, as the Messages API does.
+fn sse_event(data: Value) -> Event {
+    let name = data
+        .get("type")
+        .and_then(Value::as_str)
+        .unwrap_or("message")
+        .to_string();
+    Event::default().event(name).data(data.to_string())
+}
+
+fn anthropic_stop_reason(stop_reason: Option<&str>) -> &'static str {
+    match stop_reason {
+        Some("max_tokens") | Some("max_turn_requests") => "max_tokens",
+        Some("refusal") => "refusal",
+        _ => "end_turn",
+    }
+}
+
+fn anthropic_usage(usage: Option<&AcpTurnUsage>) -> Value {
+    json!({
+        "input_tokens": usage.and_then(|usage| usage.input_tokens).unwrap_or(0),
+        "output_tokens": usage.and_then(|usage| usage.output_tokens).unwrap_or(0),
+    })
+}
+
+/// ACP prompt blocks for a Messages API conversation. The last message must
+/// be the user's; earlier turns are replayed as a transcript block so the
+/// fresh session sees the whole conversation.
+fn prompt_blocks(messages: &[AnthropicMessageParam]) -> Result<Vec<Value>, SandboxError> {
+    let Some((last, earlier)) = messages.split_last() else {
+        return Err(invalid("messages must not be empty"));
+    };
+    if last.role != "user" {
+        return Err(invalid("the last message must have role \"user\""));
+    }
+    let mut blocks = Vec::new();
+    if !earlier.is_empty() {
+        let mut transcript = String::from("Conversation so far:\n");
+        for message in earlier {
+            let speaker = match message.role.as_str() {
+                "user" => "User",
+                "assistant" => "Assistant",
+                other => {
+                    return Err(invalid(&format!(
+                        "unsupported role {other:?}; expected \"user\" or \"assistant\""
+                    )))
+                }
+            };
+            transcript.push_str(&format!("\n{speaker}: {}\n", text_of(&message.content)?));
+        }
+        blocks.push(json!({ "type": "text", "text": transcript }));
+    }
+    blocks.push(json!({ "type": "text", "text": text_of(&last.content)? }));
+    Ok(blocks)
+}
+
+/// Text of a string or an array of `text` content blocks.
+fn text_of(content: &Value) -> Result<String, SandboxError> {
+    match content {
+        Value::String(text) => Ok(text.clone()),
+        Value::Array(blocks) => blocks
+            .iter()
+            .map(|block| match block.get("type").and_then(Value::as_str) {
+                Some("text") => Ok(block
+                    .get("text")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string()),
+                other => Err(invalid(&format!(
+                    "unsupported content block type {}; only text is supported",
+                    other.unwrap_or("<missing>")
+                ))),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(|texts| texts.join("\n")),
+        _ => Err(invalid(
+            "content must be a string or an array of text blocks",
+        )),
+    }
+}
+
+fn invalid(message: &str) -> SandboxError {
+    SandboxError::InvalidRequest {
+        message: message.to_string(),
+    }
+}
+
+/// A Messages API error body, so Anthropic clients surface the message.
+pub(crate) fn error_response(status: StatusCode, message: String) -> Response {
+    let kind = match status {
+        StatusCode::BAD_REQUEST => "invalid_request_error",
+        StatusCode::UNAUTHORIZED => "authentication_error",
+        StatusCode::NOT_FOUND => "not_found_error",
+        _ => "api_error",
+    };
+    (
+        status,
+        Json(json!({
+            "type": "error",
+            "error": { "type": kind, "message": message }
+        })),
+    )
+        .into_response()
+}
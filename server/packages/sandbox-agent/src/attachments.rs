@@ -0,0 +1,404 @@
+//! Files uploaded for a server's prompts.
+//!
+//! `POST /v1/acp/{server_id}/attachments` streams each multipart file part
+//! to disk, so large files never have to be base64-encoded into a prompt.
+//! Files land in `<dir>/<server_id>`, where `dir` is
+//! `SANDBOX_AGENT_ATTACHMENTS_DIR` or `sandbox-agent-attachments` under the
+//! system temp dir, unless the request names its own directory, such as the
+//! session's `cwd`, for agents that only read files inside their workspace.
+//! The per-server directory is removed with the server. An upload request
+//! may be up to `SANDBOX_AGENT_MAX_ATTACHMENT_BYTES` (default 100 MiB); the
+//! general body limit does not apply to it.
+//!
+//! Base64 data inlined in a `session/prompt` (`image` and `audio` blocks,
+//! and `resource` blocks carrying a `blob`) is checked and normalized here
+//! too. Each block may decode to at most
+//! `SANDBOX_AGENT_MAX_INLINE_ATTACHMENT_BYTES` (default 20 MiB, `0` off).
+//! Blocks over `SANDBOX_AGENT_INLINE_ATTACHMENT_SPILL_BYTES` (default
+//! 256 KiB), or of a kind the agent's `promptCapabilities` do not accept,
+//! are written to the server's directory and sent as `resource_link`
+//! blocks instead, so large images never reach the agent as data URLs.
+
+use std::path::{Path, PathBuf};
+
+use axum::extract::multipart::Field;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use sandbox_agent_error::SandboxError;
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+
+const DEFAULT_MAX_BYTES: usize = 100 * 1024 * 1024;
+const DEFAULT_MAX_INLINE_BYTES: usize = 20 * 1024 * 1024;
+const DEFAULT_INLINE_SPILL_BYTES: usize = 256 * 1024;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Attachments {
+    root: PathBuf,
+    max_bytes: usize,
+    max_inline_bytes: Option<usize>,
+    inline_spill_bytes: usize,
+}
+
+/// Content an agent accepts in prompts besides text and `resource_link`,
+/// from `agentCapabilities.promptCapabilities` in its `initialize` result.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct PromptCapabilities {
+    pub(crate) image: bool,
+    pub(crate) audio: bool,
+    pub(crate) embedded_context: bool,
+}
+
+impl PromptCapabilities {
+    pub(crate) fn from_initialize(result: &Value) -> Self {
+        let flag = |name: &str| {
+            result
+                .pointer(&format!("/agentCapabilities/promptCapabilities/{name}"))
+                .and_then(Value::as_bool)
+                .unwrap_or(false)
+        };
+        Self {
+            image: flag("image"),
+            audio: flag("audio"),
+            embedded_context: flag("embeddedContext"),
+        }
+    }
+
+    fn accepts(&self, kind: InlineKind) -> bool {
+        match kind {
+            InlineKind::Image => self.image,
+            InlineKind::Audio => self.audio,
+            InlineKind::Resource => self.embedded_context,
+        }
+    }
+}
+
+/// One stored file.
+#[derive(Debug, Clone)]
+pub(crate) struct StoredAttachment {
+    pub(crate) name: String,
+    pub(crate) path: PathBuf,
+    pub(crate) mime_type: Option<String>,
+    pub(crate) size_bytes: u64,
+}
+
+impl Attachments {
+    pub(crate) fn from_env() -> Self {
+        let root = std::env::var_os("SANDBOX_AGENT_ATTACHMENTS_DIR")
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| std::env::temp_dir().join("sandbox-agent-attachments"));
+        let max_bytes = std::env::var("SANDBOX_AGENT_MAX_ATTACHMENT_BYTES")
+            .ok()
+            .and_then(|raw| raw.trim().parse::<usize>().ok())
+            .filter(|max| *max > 0)
+            .unwrap_or(DEFAULT_MAX_BYTES);
+        let max_inline_bytes = std::env::var("SANDBOX_AGENT_MAX_INLINE_ATTACHMENT_BYTES")
+            .ok()
+            .and_then(|raw| raw.trim().parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MAX_INLINE_BYTES);
+        let inline_spill_bytes = std::env::var("SANDBOX_AGENT_INLINE_ATTACHMENT_SPILL_BYTES")
+            .ok()
+            .and_then(|raw| raw.trim().parse::<usize>().ok())
+            .unwrap_or(DEFAULT_INLINE_SPILL_BYTES);
+        Self {
+            root,
+            max_bytes,
+            max_inline_bytes: (max_inline_bytes > 0).then_some(max_inline_bytes),
+            inline_spill_bytes,
+        }
+    }
+
+    pub(crate) fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    /// Default directory for the server stored as `dir_name`.
+    pub(crate) fn server_dir(&self, dir_name: &str) -> PathBuf {
+        self.root.join(dir_name)
+    }
+
+    /// Reject a `session/prompt` envelope with inline data that is not valid
+    /// base64 or decodes to more than the inline limit.
+    pub(crate) fn check_inline(&self, payload: &Value) -> Result<(), SandboxError> {
+        let Some(blocks) = payload.pointer("/params/prompt").and_then(Value::as_array) else {
+            return Ok(());
+        };
+        for (index, block) in blocks.iter().enumerate() {
+            if let Some(inline) = InlineData::of(block) {
+                self.decode(index, &inline)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write inline data blocks that are too large, or that `capabilities`
+    /// rule out, to the directory of the server stored as `dir_name`, and
+    /// replace them with `resource_link` blocks. With no capabilities known
+    /// only the size decides. Returns how many blocks were replaced.
+    pub(crate) async fn spill_inline(
+        &self,
+        dir_name: &str,
+        payload: &mut Value,
+        capabilities: Option<PromptCapabilities>,
+    ) -> Result<usize, SandboxError> {
+        let Some(blocks) = payload
+            .pointer_mut("/params/prompt")
+            .and_then(Value::as_array_mut)
+        else {
+            return Ok(0);
+        };
+        let dir = self.server_dir(dir_name);
+        let mut spilled = 0;
+        for (index, block) in blocks.iter_mut().enumerate() {
+            let Some(inline) = InlineData::of(block) else {
+                continue;
+            };
+            let supported = match capabilities {
+                Some(capabilities) => capabilities.accepts(inline.kind),
+                None => true,
+            };
+            let bytes = self.decode(index, &inline)?;
+            if supported && bytes.len() <= self.inline_spill_bytes {
+                continue;
+            }
+
+            tokio::fs::create_dir_all(&dir)
+                .await
+                .map_err(|err| io_error(&dir, err))?;
+            let (path, mut file) = create_unique(&dir, &inline.file_name()).await?;
+            let written = async {
+                file.write_all(&bytes).await?;
+                file.flush().await
+            }
+            .await;
+            if let Err(err) = written {
+                let _ = tokio::fs::remove_file(&path).await;
+                return Err(io_error(&path, err));
+            }
+
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let mut link = json!({
+                "type": "resource_link",
+                "name": name,
+                "uri": format!("file://{}", path.display()),
+                "size": bytes.len(),
+            });
+            if let Some(mime_type) = &inline.mime_type {
+                link["mimeType"] = json!(mime_type);
+            }
+            *block = link;
+            spilled += 1;
+        }
+        Ok(spilled)
+    }
+
+    fn decode(&self, index: usize, inline: &InlineData<'_>) -> Result<Vec<u8>, SandboxError> {
+        // Decoded size is known from the encoded length, so oversized
+        // blocks are rejected before anything is allocated for them.
+        let estimate = inline.data.len() / 4 * 3;
+        if let Some(max_bytes) = self.max_inline_bytes {
+            if estimate > max_bytes + 2 {
+                return Err(inline_too_large(index, max_bytes));
+            }
+        }
+        let bytes = STANDARD
+            .decode(inline.data)
+            .map_err(|err| SandboxError::InvalidRequest {
+                message: format!("params.prompt[{index}] is not valid base64: {err}"),
+            })?;
+        match self.max_inline_bytes {
+            Some(max_bytes) if bytes.len() > max_bytes => Err(inline_too_large(index, max_bytes)),
+            _ => Ok(bytes),
+        }
+    }
+}
+
+fn inline_too_large(index: usize, max_bytes: usize) -> SandboxError {
+    SandboxError::PayloadTooLarge {
+        message: format!(
+            "params.prompt[{index}] inlines more than {max_bytes} bytes; upload it to /attachments instead"
+        ),
+        limit_bytes: max_bytes as u64,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InlineKind {
+    Image,
+    Audio,
+    Resource,
+}
+
+/// Base64 data carried by one prompt block.
+struct InlineData<'a> {
+    kind: InlineKind,
+    data: &'a str,
+    mime_type: Option<String>,
+    uri: Option<&'a str>,
+}
+
+impl<'a> InlineData<'a> {
+    fn of(block: &'a Value) -> Option<Self> {
+        let mime_type = |value: &Value| {
+            value
+                .get("mimeType")
+                .and_then(Value::as_str)
+                .map(ToOwned::to_owned)
+        };
+        match block.get("type").and_then(Value::as_str)? {
+            kind @ ("image" | "audio") => Some(Self {
+                kind: if kind == "image" {
+                    InlineKind::Image
+                } else {
+                    InlineKind::Audio
+                },
+                data: block.get("data").and_then(Value::as_str)?,
+                mime_type: mime_type(block),
+                uri: block.get("uri").and_then(Value::as_str),
+            }),
+            "resource" => {
+                let resource = block.get("resource")?;
+                Some(Self {
+                    kind: InlineKind::Resource,
+                    data: resource.get("blob").and_then(Value::as_str)?,
+                    mime_type: mime_type(resource),
+                    uri: resource.get("uri").and_then(Value::as_str),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Name for the spilled file: the last segment of the block's URI, or
+    /// its kind with an extension for its MIME type.
+    fn file_name(&self) -> String {
+        let from_uri = self
+            .uri
+            .and_then(|uri| uri.rsplit('/').next())
+            .map(sanitize_file_name)
+            .filter(|name| !name.is_empty());
+        from_uri.unwrap_or_else(|| {
+            let stem = match self.kind {
+                InlineKind::Image => "image",
+                InlineKind::Audio => "audio",
+                InlineKind::Resource => "resource",
+            };
+            let extension = match self.mime_type.as_deref() {
+                Some("image/png") => "png",
+                Some("image/jpeg") => "jpg",
+                Some("image/gif") => "gif",
+                Some("image/webp") => "webp",
+                Some("audio/wav" | "audio/x-wav") => "wav",
+                Some("audio/mpeg") => "mp3",
+                Some("application/pdf") => "pdf",
+                _ => "bin",
+            };
+            format!("{stem}.{extension}")
+        })
+    }
+}
+
+/// Stream `field` into `dir`, named after its file name. An existing file
+/// is never overwritten; a numeric suffix is added instead. A part cut off
+/// by the body limit leaves no file behind.
+pub(crate) async fn store(
+    dir: &Path,
+    mut field: Field<'_>,
+    limit_bytes: usize,
+) -> Result<StoredAttachment, SandboxError> {
+    let name = field
+        .file_name()
+        .map(sanitize_file_name)
+        .filter(|name| !name.is_empty())
+        .ok_or_else(|| SandboxError::InvalidRequest {
+            message: "attachment parts need a usable filename".to_string(),
+        })?;
+    let mime_type = field.content_type().map(ToOwned::to_owned);
+
+    tokio::fs::create_dir_all(dir)
+        .await
+        .map_err(|err| io_error(dir, err))?;
+    let (path, mut file) = create_unique(dir, &name).await?;
+
+    let mut size_bytes = 0u64;
+    let result = async {
+        while let Some(chunk) = field.chunk().await.map_err(|err| {
+            if err.status() == axum::http::StatusCode::PAYLOAD_TOO_LARGE {
+                crate::request_limits::body_too_large(limit_bytes)
+            } else {
+                SandboxError::InvalidRequest {
+                    message: format!("invalid multipart body: {err}"),
+                }
+            }
+        })? {
+            size_bytes += chunk.len() as u64;
+            file.write_all(&chunk)
+                .await
+                .map_err(|err| io_error(&path, err))?;
+        }
+        file.flush().await.map_err(|err| io_error(&path, err))
+    }
+    .await;
+    if let Err(err) = result {
+        let _ = tokio::fs::remove_file(&path).await;
+        return Err(err);
+    }
+
+    Ok(StoredAttachment {
+        name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or(name),
+        path,
+        mime_type,
+        size_bytes,
+    })
+}
+
+async fn create_unique(dir: &Path, name: &str) -> Result<(PathBuf, tokio::fs::File), SandboxError> {
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, Some(extension)),
+        _ => (name, None),
+    };
+    for attempt in 0u32.. {
+        let candidate = match (attempt, extension) {
+            (0, _) => name.to_string(),
+            (n, Some(extension)) => format!("{stem}-{n}.{extension}"),
+            (n, None) => format!("{stem}-{n}"),
+        };
+        let path = dir.join(candidate);
+        match tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await
+        {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(io_error(&path, err)),
+        }
+    }
+    unreachable!("attachment name attempts are unbounded")
+}
+
+/// Keep only the last path component and drop characters that are unsafe
+/// in file names.
+fn sanitize_file_name(raw: &str) -> String {
+    let base = raw.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base
+        .chars()
+        .filter(|ch| !ch.is_control() && !matches!(ch, ':' | '*' | '?' | '"' | '<' | '>' | '|'))
+        .collect();
+    let trimmed = cleaned.trim().trim_start_matches('.');
+    trimmed.to_string()
+}
+
+fn io_error(path: &Path, err: std::io::Error) -> SandboxError {
+    SandboxError::StreamError {
+        message: format!("failed to write attachment {}: {err}", path.display()),
+    }
+}
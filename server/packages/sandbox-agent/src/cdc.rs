@@ -0,0 +1,232 @@
+//! Change-data-capture feed of ACP server state for external analytics.
+//!
+//! Every state transition becomes one compact JSON line carrying a feed
+//! sequence number, a timestamp, a `kind` and the server id.
+//! `SANDBOX_AGENT_CDC_FILE` appends lines to a file. `SANDBOX_AGENT_CDC_SOCKET`
+//! serves them on a Unix socket, and each connection receives the records
+//! emitted after it connects. `SANDBOX_AGENT_CDC_NATS_URL` and
+//! `SANDBOX_AGENT_CDC_REDIS_URL` publish them to an event bus (see
+//! `cdc_bus`). A reader that falls more than
+//! `CDC_CHANNEL_SIZE` records behind gets a `lagged` record and should
+//! resync from the REST API.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::broadcast;
+
+use crate::cdc_bus::{self, BusKind, BusTarget};
+
+const CDC_CHANNEL_SIZE: usize = 4096;
+const FLUSH_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CdcFeed {
+    inner: Option<Arc<CdcInner>>,
+}
+
+#[derive(Debug)]
+struct CdcInner {
+    next_seq: AtomicU64,
+    records: broadcast::Sender<Arc<str>>,
+    /// Records written and flushed to the feed file, when there is one.
+    file_written: Option<Arc<AtomicU64>>,
+}
+
+impl CdcFeed {
+    pub(crate) fn from_env() -> Self {
+        let file = std::env::var_os("SANDBOX_AGENT_CDC_FILE").map(PathBuf::from);
+        let socket = std::env::var_os("SANDBOX_AGENT_CDC_SOCKET").map(PathBuf::from);
+        let buses = [
+            (BusKind::Nats, "SANDBOX_AGENT_CDC_NATS_URL"),
+            (BusKind::Redis, "SANDBOX_AGENT_CDC_REDIS_URL"),
+        ]
+        .into_iter()
+        .filter_map(|(kind, key)| {
+            let raw = std::env::var(key)
+                .ok()
+                .filter(|raw| !raw.trim().is_empty())?;
+            BusTarget::parse(kind, raw.trim())
+                .inspect_err(|err| tracing::warn!(key, error = %err, "cdc: ignoring event bus"))
+                .ok()
+        })
+        .collect::<Vec<_>>();
+        if file.is_none() && socket.is_none() && buses.is_empty() {
+            return Self::default();
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            tracing::warn!("cdc: no async runtime; change feed disabled");
+            return Self::default();
+        };
+
+        let (records, _) = broadcast::channel(CDC_CHANNEL_SIZE);
+        let file_written = file.map(|path| {
+            let written = Arc::new(AtomicU64::new(0));
+            runtime.spawn(write_file(path, records.subscribe(), written.clone()));
+            written
+        });
+        if let Some(path) = socket {
+            #[cfg(unix)]
+            runtime.spawn(serve_socket(path, records.clone()));
+            #[cfg(not(unix))]
+            tracing::warn!(path = %path.display(), "cdc: Unix sockets are not supported on this platform");
+        }
+        for target in buses {
+            runtime.spawn(cdc_bus::publish(target, records.subscribe()));
+        }
+
+        Self {
+            inner: Some(Arc::new(CdcInner {
+                next_seq: AtomicU64::new(1),
+                records,
+                file_written,
+            })),
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// Emit a `kind` record for `server_id`. The fields of `details`, which
+    /// must be an object, are merged into the record.
+    pub(crate) fn record(&self, kind: &str, server_id: &str, details: Value) {
+        let Some(inner) = self.inner.as_ref() else {
+            return;
+        };
+        let mut record = json!({
+            "seq": inner.next_seq.fetch_add(1, Ordering::Relaxed),
+            "ts": now_ms(),
+            "kind": kind,
+            "serverId": server_id,
+        });
+        if let (Value::Object(record), Value::Object(details)) = (&mut record, details) {
+            record.extend(details);
+        }
+        let _ = inner.records.send(Arc::from(record.to_string()));
+    }
+
+    /// Wait up to `timeout` for every record emitted so far to be flushed to
+    /// the feed file. Socket readers are not waited for.
+    pub(crate) async fn flush(&self, timeout: Duration) {
+        let Some(written) = self
+            .inner
+            .as_ref()
+            .and_then(|inner| inner.file_written.as_ref())
+        else {
+            return;
+        };
+        let emitted = self
+            .inner
+            .as_ref()
+            .map_or(0, |inner| inner.next_seq.load(Ordering::Relaxed) - 1);
+        let deadline = Instant::now() + timeout;
+        while written.load(Ordering::Relaxed) < emitted {
+            if Instant::now() >= deadline {
+                tracing::warn!(
+                    emitted,
+                    written = written.load(Ordering::Relaxed),
+                    "cdc: feed file flush timed out"
+                );
+                return;
+            }
+            tokio::time::sleep(FLUSH_POLL_INTERVAL).await;
+        }
+    }
+}
+
+async fn write_file(
+    path: PathBuf,
+    mut records: broadcast::Receiver<Arc<str>>,
+    written: Arc<AtomicU64>,
+) {
+    let mut file = match tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .await
+    {
+        Ok(file) => file,
+        Err(err) => {
+            tracing::error!(path = %path.display(), error = %err, "cdc: failed to open feed file");
+            return;
+        }
+    };
+    // Records covered by lines written since the last flush. A `lagged`
+    // line stands in for the records it reports as missed.
+    let mut unflushed = 0;
+    loop {
+        let (line, covers) = match records.recv().await {
+            Ok(record) => (format!("{record}\n"), 1),
+            Err(broadcast::error::RecvError::Lagged(missed)) => (lagged_line(missed), missed),
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        if let Err(err) = file.write_all(line.as_bytes()).await {
+            tracing::error!(path = %path.display(), error = %err, "cdc: failed to write feed file");
+            return;
+        }
+        unflushed += covers;
+        if records.is_empty() {
+            if let Err(err) = file.flush().await {
+                tracing::error!(path = %path.display(), error = %err, "cdc: failed to flush feed file");
+                return;
+            }
+            written.fetch_add(unflushed, Ordering::Relaxed);
+            unflushed = 0;
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn serve_socket(path: PathBuf, records: broadcast::Sender<Arc<str>>) {
+    let _ = tokio::fs::remove_file(&path).await;
+    let listener = match tokio::net::UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(err) => {
+            tracing::error!(path = %path.display(), error = %err, "cdc: failed to bind feed socket");
+            return;
+        }
+    };
+    loop {
+        let Ok((mut stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let mut receiver = records.subscribe();
+        tokio::spawn(async move {
+            while let Some(line) = next_line(&mut receiver).await {
+                if stream.write_all(line.as_bytes()).await.is_err() {
+                    return;
+                }
+            }
+        });
+    }
+}
+
+/// The next record as a newline-terminated line, or a `lagged` record when
+/// the receiver fell behind. `None` once the feed is gone.
+async fn next_line(records: &mut broadcast::Receiver<Arc<str>>) -> Option<String> {
+    match records.recv().await {
+        Ok(record) => Some(format!("{record}\n")),
+        Err(broadcast::error::RecvError::Lagged(missed)) => Some(lagged_line(missed)),
+        Err(broadcast::error::RecvError::Closed) => None,
+    }
+}
+
+fn lagged_line(missed: u64) -> String {
+    format!("{}\n", lagged_record(missed))
+}
+
+pub(crate) fn lagged_record(missed: u64) -> String {
+    json!({ "ts": now_ms(), "kind": "lagged", "missed": missed }).to_string()
+}
+
+fn now_ms() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or(0)
+}
@@ -0,0 +1,215 @@
+//! Mirrors the change feed onto NATS or Redis so a central orchestrator can
+//! aggregate many sandboxes without holding an HTTP connection to each.
+//!
+//! Every record is published under `sandbox.{serverId}.events`: as a NATS
+//! subject, or as the key of a Redis stream holding the record in its
+//! `record` field. Both clients reconnect by themselves once connected; the
+//! first connection is retried with backoff. Records emitted while the bus is
+//! unreachable wait in the feed channel, and a reader that overflows it
+//! publishes a `lagged` record instead.
+
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use redis::aio::ConnectionManager;
+use redis::streams::StreamMaxlen;
+use redis::AsyncCommands;
+use reqwest::Url;
+use serde_json::Value;
+use tokio::sync::broadcast;
+
+use crate::cdc::lagged_record;
+
+const RECONNECT_MIN: Duration = Duration::from_millis(500);
+const RECONNECT_MAX: Duration = Duration::from_secs(30);
+/// Redis streams are trimmed to roughly this many entries per server.
+const REDIS_STREAM_MAXLEN: usize = 10_000;
+/// Subject for records that are not about one server, such as `lagged`.
+const FEED_SUBJECT: &str = "sandbox._feed.events";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BusKind {
+    Nats,
+    Redis,
+}
+
+impl BusKind {
+    fn scheme(self) -> &'static str {
+        match self {
+            Self::Nats => "nats",
+            Self::Redis => "redis",
+        }
+    }
+
+    fn default_port(self) -> u16 {
+        match self {
+            Self::Nats => 4222,
+            Self::Redis => 6379,
+        }
+    }
+}
+
+/// Where to publish, parsed from `nats://[user:pass@|token@]host[:port]` or
+/// `redis://[user:pass@]host[:port][/db]`.
+#[derive(Debug, Clone)]
+pub(crate) struct BusTarget {
+    kind: BusKind,
+    url: String,
+    host: String,
+    port: u16,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+/// Connected client of one event bus.
+enum Publisher {
+    Nats(async_nats::Client),
+    Redis(ConnectionManager),
+}
+
+impl BusTarget {
+    pub(crate) fn parse(kind: BusKind, raw: &str) -> Result<Self, String> {
+        let url = Url::parse(raw).map_err(|err| format!("invalid URL {raw:?}: {err}"))?;
+        if url.scheme() != kind.scheme() {
+            return Err(format!("expected a {}:// URL, got {raw:?}", kind.scheme()));
+        }
+        let host = url
+            .host_str()
+            .ok_or_else(|| format!("missing host in {raw:?}"))?
+            .to_string();
+        match kind {
+            BusKind::Nats if !url.path().trim_start_matches('/').is_empty() => {
+                return Err(format!("unexpected path in {raw:?}"));
+            }
+            BusKind::Nats => {}
+            BusKind::Redis => {
+                redis::Client::open(raw).map_err(|err| format!("invalid URL {raw:?}: {err}"))?;
+            }
+        }
+        Ok(Self {
+            kind,
+            url: raw.to_string(),
+            host,
+            port: url.port().unwrap_or(kind.default_port()),
+            username: (!url.username().is_empty()).then(|| url.username().to_string()),
+            password: url.password().map(ToOwned::to_owned),
+        })
+    }
+}
+
+/// Publish every record from `records` to `target` until the feed closes.
+pub(crate) async fn publish(target: BusTarget, mut records: broadcast::Receiver<Arc<str>>) {
+    let mut backoff = RECONNECT_MIN;
+    let mut publisher = loop {
+        match Publisher::connect(&target).await {
+            Ok(publisher) => break publisher,
+            Err(err) => tracing::warn!(
+                bus = target.kind.scheme(),
+                host = %target.host,
+                port = target.port,
+                error = %err,
+                retry_ms = backoff.as_millis() as u64,
+                "cdc: event bus connection failed"
+            ),
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(RECONNECT_MAX);
+    };
+    tracing::info!(
+        bus = target.kind.scheme(),
+        host = %target.host,
+        port = target.port,
+        "cdc: connected to event bus"
+    );
+
+    loop {
+        let record = match records.recv().await {
+            Ok(record) => record,
+            Err(broadcast::error::RecvError::Lagged(missed)) => Arc::from(lagged_record(missed)),
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        if let Err(err) = publisher.publish(&subject(&record), &record).await {
+            tracing::warn!(
+                bus = target.kind.scheme(),
+                error = %err,
+                "cdc: event bus rejected a record"
+            );
+        }
+    }
+}
+
+impl Publisher {
+    async fn connect(target: &BusTarget) -> io::Result<Self> {
+        match target.kind {
+            BusKind::Nats => {
+                let mut options = async_nats::ConnectOptions::new().name("sandbox-agent");
+                match (&target.username, &target.password) {
+                    (Some(user), Some(pass)) => {
+                        options = options.user_and_password(user.clone(), pass.clone());
+                    }
+                    (Some(token), None) => options = options.token(token.clone()),
+                    _ => {}
+                }
+                let client = options
+                    .connect(format!("nats://{}:{}", target.host, target.port))
+                    .await
+                    .map_err(io::Error::other)?;
+                Ok(Self::Nats(client))
+            }
+            BusKind::Redis => {
+                let client = redis::Client::open(target.url.as_str()).map_err(io::Error::other)?;
+                let connection = ConnectionManager::new(client)
+                    .await
+                    .map_err(io::Error::other)?;
+                Ok(Self::Redis(connection))
+            }
+        }
+    }
+
+    async fn publish(&mut self, subject: &str, record: &str) -> io::Result<()> {
+        match self {
+            Self::Nats(client) => client
+                .publish(subject.to_string(), record.to_string().into())
+                .await
+                .map_err(io::Error::other),
+            Self::Redis(connection) => connection
+                .xadd_maxlen::<_, _, _, _, Option<String>>(
+                    subject,
+                    StreamMaxlen::Approx(REDIS_STREAM_MAXLEN),
+                    "*",
+                    &[("record", record)],
+                )
+                .await
+                .map(drop)
+                .map_err(io::Error::other),
+        }
+    }
+}
+
+/// `sandbox.{serverId}.events`, with characters NATS treats as token
+/// separators or wildcards replaced by `_`.
+fn subject(record: &str) -> String {
+    let server_id = serde_json::from_str::<Value>(record)
+        .ok()
+        .and_then(|record| {
+            record
+                .get("serverId")
+                .and_then(Value::as_str)
+                .map(ToOwned::to_owned)
+        });
+    let Some(server_id) = server_id.filter(|id| !id.is_empty()) else {
+        return FEED_SUBJECT.to_string();
+    };
+    let token = server_id
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' {
+                ch
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    format!("sandbox.{token}.events")
+}
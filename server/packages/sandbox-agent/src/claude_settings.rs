@@ -0,0 +1,215 @@
+//! Claude Code settings files for sessions.
+//!
+//! Claude Code reads hooks, output styles and other settings from settings
+//! files that the ACP adapter does not expose. A `session/new` or
+//! `session/load` envelope for Claude can carry them under
+//! `_meta["sandboxagent.dev"].claude`: `settingsJson` is written as is, with
+//! `hooks` and `outputStyle` set on top. The file reaches Claude as
+//! `--settings <file>` through `_meta.claudeCode.options.extraArgs`, so
+//! controllers can enforce hook-based guardrails on each session.
+//!
+//! Files live under `SANDBOX_AGENT_CLAUDE_SETTINGS_DIR`, or the server's
+//! local data directory, in a directory of this process's own: a restarted
+//! server, or a second one sharing the root, never rewrites a file a running
+//! session still reads. A file is removed when its session ends, and the
+//! process directory when the server shuts down.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::PoisonError;
+
+use sandbox_agent_agent_management::agents::AgentId;
+use sandbox_agent_error::SandboxError;
+use serde_json::{json, Map, Value};
+
+use crate::permission_mode::merge_missing;
+
+const KEYS: [&str; 3] = ["settingsJson", "hooks", "outputStyle"];
+
+#[derive(Debug)]
+pub(crate) struct ClaudeSettings {
+    /// This process's directory under the configured root.
+    dir: PathBuf,
+    written: AtomicU64,
+    /// Settings file of each session, by server directory and session id.
+    sessions: std::sync::Mutex<HashMap<(String, String), PathBuf>>,
+}
+
+impl ClaudeSettings {
+    pub(crate) fn from_env() -> Self {
+        let root = std::env::var_os("SANDBOX_AGENT_CLAUDE_SETTINGS_DIR")
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| {
+                dirs::data_local_dir()
+                    .unwrap_or_else(std::env::temp_dir)
+                    .join("sandbox-agent")
+                    .join("claude-settings")
+            });
+        let started_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis())
+            .unwrap_or_default();
+        Self {
+            dir: root.join(format!("{}-{started_ms}", std::process::id())),
+            written: AtomicU64::new(0),
+            sessions: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Directory for the settings files of the server stored as `dir_name`.
+    pub(crate) fn server_dir(&self, dir_name: &str) -> PathBuf {
+        self.dir.join(dir_name)
+    }
+
+    /// Tie a file [`Self::apply`] wrote to the session it started, or
+    /// remove it when the request started none.
+    pub(crate) async fn settle(&self, dir_name: &str, session_id: Option<&str>, path: PathBuf) {
+        let Some(session_id) = session_id else {
+            remove(&path).await;
+            return;
+        };
+        let replaced = self
+            .sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert((dir_name.to_string(), session_id.to_string()), path);
+        if let Some(replaced) = replaced {
+            remove(&replaced).await;
+        }
+    }
+
+    /// Remove the settings file of a session that ended.
+    pub(crate) async fn release(&self, dir_name: &str, session_id: &str) {
+        let path = self
+            .sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&(dir_name.to_string(), session_id.to_string()));
+        if let Some(path) = path {
+            remove(&path).await;
+        }
+    }
+
+    /// Forget the sessions of a deleted server and remove its directory.
+    pub(crate) async fn remove_server(&self, dir_name: &str) {
+        self.sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|(server, _), _| server != dir_name);
+        let _ = tokio::fs::remove_dir_all(self.server_dir(dir_name)).await;
+    }
+
+    /// Remove every file this process wrote.
+    pub(crate) async fn remove_all(&self) {
+        self.sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+        let _ = tokio::fs::remove_dir_all(&self.dir).await;
+    }
+
+    /// Write the settings a session envelope asks for and point Claude at
+    /// them, returning the file for [`Self::settle`]. Envelopes without
+    /// `claude` are left alone; other agents get `400`. Keys the client set
+    /// under `_meta.claudeCode` itself win.
+    pub(crate) async fn apply(
+        &self,
+        agent: AgentId,
+        dir_name: &str,
+        payload: &mut Value,
+    ) -> Result<Option<PathBuf>, SandboxError> {
+        let Some(settings) = settings_from_payload(payload)? else {
+            return Ok(None);
+        };
+        if agent != AgentId::Claude {
+            return Err(SandboxError::InvalidRequest {
+                message: format!(
+                    "_meta[\"sandboxagent.dev\"].claude only applies to agent 'claude', not '{}'",
+                    agent.as_str()
+                ),
+            });
+        }
+        let dir = self.server_dir(dir_name);
+        let path = dir.join(format!(
+            "session-{}.json",
+            self.written.fetch_add(1, Ordering::Relaxed) + 1
+        ));
+        let write = async {
+            tokio::fs::create_dir_all(&dir).await?;
+            let contents = serde_json::to_vec_pretty(&settings).map_err(std::io::Error::other)?;
+            tokio::fs::write(&path, contents).await
+        };
+        write.await.map_err(|err| SandboxError::SpawnFailed {
+            agent: agent.as_str().to_string(),
+            message: format!("failed to write Claude settings file: {err}"),
+        })?;
+
+        let meta = json!({
+            "claudeCode": {
+                "options": {
+                    "extraArgs": { "settings": path.to_string_lossy() }
+                }
+            }
+        });
+        if let Some(params) = payload.get_mut("params").and_then(Value::as_object_mut) {
+            let target = params
+                .entry("_meta")
+                .or_insert_with(|| Value::Object(Map::new()));
+            merge_missing(target, &meta);
+        }
+        Ok(Some(path))
+    }
+}
+
+async fn remove(path: &Path) {
+    if let Err(err) = tokio::fs::remove_file(path).await {
+        if err.kind() != std::io::ErrorKind::NotFound {
+            tracing::warn!(path = %path.display(), error = %err, "failed to remove Claude settings file");
+        }
+    }
+}
+
+/// The settings file contents for `_meta["sandboxagent.dev"].claude`, if set.
+fn settings_from_payload(payload: &Value) -> Result<Option<Value>, SandboxError> {
+    let invalid = |detail: &str| SandboxError::InvalidRequest {
+        message: format!("_meta[\"sandboxagent.dev\"].claude{detail}"),
+    };
+    let claude = match payload.pointer("/params/_meta/sandboxagent.dev/claude") {
+        None | Some(Value::Null) => return Ok(None),
+        Some(Value::Object(claude)) => claude,
+        Some(_) => return Err(invalid(" must be an object")),
+    };
+    if let Some(key) = claude.keys().find(|key| !KEYS.contains(&key.as_str())) {
+        return Err(invalid(&format!(
+            " has unknown key '{key}'; expected {}",
+            KEYS.join(", ")
+        )));
+    }
+    let mut settings = match claude.get("settingsJson") {
+        None | Some(Value::Null) => Map::new(),
+        Some(Value::Object(settings)) => settings.clone(),
+        // A settings file's contents, as a string.
+        Some(Value::String(raw)) => match serde_json::from_str(raw) {
+            Ok(Value::Object(settings)) => settings,
+            _ => return Err(invalid(".settingsJson must be a JSON object")),
+        },
+        Some(_) => return Err(invalid(".settingsJson must be a JSON object")),
+    };
+    match claude.get("hooks") {
+        None | Some(Value::Null) => {}
+        Some(hooks @ Value::Object(_)) => {
+            settings.insert("hooks".to_string(), hooks.clone());
+        }
+        Some(_) => return Err(invalid(".hooks must be an object of hook events")),
+    }
+    match claude.get("outputStyle") {
+        None | Some(Value::Null) => {}
+        Some(Value::String(style)) if !style.is_empty() => {
+            settings.insert("outputStyle".to_string(), json!(style));
+        }
+        Some(_) => return Err(invalid(".outputStyle must be a non-empty string")),
+    }
+    Ok(Some(Value::Object(settings)))
+}
@@ -11,11 +11,15 @@ mod build_version {
     include!(concat!(env!("OUT_DIR"), "/version.rs"));
 }
 
+use crate::config::{ServerConfig, ServerSettings};
+use crate::log_buffer;
+use crate::namespaces::Namespaces;
 use crate::router::{
     build_router_with_state, shutdown_servers, AppState, AuthConfig, BrandingMode,
 };
 use crate::server_logs::ServerLogs;
 use crate::telemetry;
+use crate::transport;
 use crate::ui;
 use reqwest::blocking::Client as HttpClient;
 use reqwest::Method;
@@ -27,7 +31,7 @@ use sandbox_agent_agent_management::agents::{AgentId, AgentManager, InstallOptio
 use serde::Serialize;
 use serde_json::{json, Value};
 use thiserror::Error;
-use tower_http::cors::{Any, CorsLayer};
+use tower_http::cors::{AllowHeaders, AllowMethods, Any, CorsLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 const API_PREFIX: &str = "/v1";
@@ -87,11 +91,25 @@ pub enum Command {
 
 #[derive(Args, Debug)]
 pub struct ServerArgs {
-    #[arg(long, short = 'H', default_value = DEFAULT_HOST)]
-    host: String,
+    /// Settings file; defaults to $SANDBOX_AGENT_CONFIG, then ./sandbox-agent.toml.
+    #[arg(long)]
+    config: Option<PathBuf>,
 
-    #[arg(long, short = 'p', default_value_t = DEFAULT_PORT)]
-    port: u16,
+    /// Bind address [default: 127.0.0.1]
+    #[arg(long, short = 'H')]
+    host: Option<String>,
+
+    /// Listen port [default: 2468]
+    #[arg(long, short = 'p')]
+    port: Option<u16>,
+
+    /// Serve on this Unix domain socket instead of TCP.
+    #[arg(long = "unix-socket", conflicts_with_all = ["host", "port", "stdio"])]
+    unix_socket: Option<PathBuf>,
+
+    /// Serve one HTTP/1.1 connection over stdin/stdout instead of TCP.
+    #[arg(long, conflicts_with_all = ["host", "port"])]
+    stdio: bool,
 
     #[arg(long = "cors-allow-origin", short = 'O')]
     cors_allow_origin: Vec<String>,
@@ -107,6 +125,9 @@ pub struct ServerArgs {
 
     #[arg(long = "no-telemetry")]
     no_telemetry: bool,
+
+    #[arg(long = "preinstall", value_delimiter = ',')]
+    preinstall: Vec<String>,
 }
 
 #[derive(Args, Debug)]
@@ -199,6 +220,14 @@ pub enum ApiCommand {
     Agents(AgentsArgs),
     /// Send and stream raw ACP JSON-RPC envelopes.
     Acp(AcpArgs),
+    /// Run one prompt across several agents and compare the results.
+    Tasks(TasksArgs),
+    /// Run a prompt on a cron schedule.
+    Schedules(SchedulesArgs),
+    /// Show the settings the server is running with.
+    Config(ClientArgs),
+    /// Read recent server log events from /v1/logs.
+    Logs(LogsArgs),
 }
 
 #[derive(Subcommand, Debug)]
@@ -222,6 +251,16 @@ pub enum AgentsCommand {
     List(ClientArgs),
     /// Install or reinstall an agent.
     Install(ApiInstallAgentArgs),
+    /// Remove an agent's installed files (pins are kept).
+    Uninstall(ApiAgentArgs),
+    /// List the models an agent accepts.
+    Models(ApiAgentArgs),
+    /// List available agent releases and the current pin.
+    Versions(ApiAgentArgs),
+    /// Pin the versions future installs use (omit both to clear).
+    Pin(ApiPinAgentArgs),
+    /// Reinstall an agent at a newer version and print the changelog.
+    Upgrade(ApiUpgradeAgentArgs),
 }
 
 #[derive(Args, Debug)]
@@ -234,12 +273,148 @@ pub struct AcpArgs {
 pub enum AcpCommand {
     /// Send one ACP JSON-RPC envelope to /v1/acp/{server_id}.
     Post(AcpPostArgs),
+    /// Check an envelope against /v1/acp/{server_id}/validate without
+    /// installing or starting anything.
+    Validate(AcpPostArgs),
     /// Stream ACP JSON-RPC envelopes from /v1/acp/{server_id} SSE.
     Stream(AcpStreamArgs),
+    /// Fetch buffered envelopes from /v1/acp/{server_id}/events, optionally
+    /// waiting for new ones.
+    Events(AcpEventsArgs),
+    /// List agent requests from /v1/acp/{server_id}/requests that are still
+    /// waiting for a client response.
+    Requests(AcpRequestsArgs),
+    /// Print an agent's raw stdout and stderr for one turn from
+    /// /v1/acp/{server_id}/turns/{turn}/logs.
+    TurnLogs(AcpTurnLogsArgs),
+    /// Print a tool output stored as an artifact from
+    /// /v1/acp/{server_id}/sessions/{session_id}/artifacts/{artifact_id}.
+    Artifact(AcpArtifactArgs),
+    /// Print a session's messages merged from streamed chunks, from
+    /// /v1/acp/{server_id}/sessions/{session_id}/transcript.
+    Transcript(AcpTranscriptArgs),
+    /// Wait for a session's prompt to finish and print its reply, status,
+    /// usage and changed files, from
+    /// /v1/acp/{server_id}/sessions/{session_id}/result.
+    Result(AcpResultArgs),
+    /// Upload files to /v1/acp/{server_id}/attachments and print their
+    /// paths for use in prompts.
+    Attach(AcpAttachArgs),
+    /// Compact a long session with /v1/acp/{server_id}/compact.
+    Compact(AcpCompactArgs),
+    /// Print the plan from a session's latest planning turn, from
+    /// /v1/acp/{server_id}/sessions/{session_id}/plan.
+    Plan(AcpPlanArgs),
+    /// Approve a session's plan with
+    /// /v1/acp/{server_id}/sessions/{session_id}/plan/approve, switching it
+    /// to build mode and sending the plan as the next prompt.
+    ApprovePlan(AcpApprovePlanArgs),
+    /// Print a session and, once its agent process exited, how it ended,
+    /// from /v1/acp/{server_id}/sessions/{session_id}.
+    Session(AcpSessionArgs),
+    /// Change a session's mode, model or permission mode with
+    /// PATCH /v1/acp/{server_id}/sessions/{session_id}.
+    UpdateSession(AcpUpdateSessionArgs),
+    /// List the sub-agents a session started, from
+    /// /v1/acp/{server_id}/sessions/{session_id}/children.
+    Children(AcpChildrenArgs),
+    /// Retract a session's last turn and run its prompt again, optionally
+    /// with new text, with /v1/acp/{server_id}/sessions/{session_id}/retry.
+    Retry(AcpRetryArgs),
+    /// Interrupt a session's turn with
+    /// /v1/acp/{server_id}/sessions/{session_id}/interrupt.
+    Interrupt(AcpInterruptArgs),
+    /// Queue a prompt to send once the session's current turn completes,
+    /// with /v1/acp/{server_id}/sessions/{session_id}/messages.
+    QueueMessage(AcpQueueMessageArgs),
+    /// List a session's queued messages.
+    Messages(AcpMessagesArgs),
+    /// Remove a queued message before it is sent.
+    CancelMessage(AcpCancelMessageArgs),
     /// Close an ACP server stream.
     Close(AcpCloseArgs),
 }
 
+#[derive(Args, Debug)]
+pub struct TasksArgs {
+    #[command(subcommand)]
+    command: TasksCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TasksCommand {
+    /// List every task.
+    List(ClientArgs),
+    /// Start a task that sends one prompt to each agent.
+    Create(TaskCreateArgs),
+    /// Show a task's runs with their status and output.
+    Get(TaskIdArgs),
+    /// Forget a task and close its ACP servers.
+    Delete(TaskIdArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct TaskCreateArgs {
+    #[arg(long)]
+    prompt: String,
+    /// Agent to run the prompt with. Repeat for each agent.
+    #[arg(long = "agent", required = true)]
+    agents: Vec<String>,
+    #[arg(long)]
+    cwd: Option<String>,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct TaskIdArgs {
+    task_id: String,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct SchedulesArgs {
+    #[command(subcommand)]
+    command: SchedulesCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SchedulesCommand {
+    /// List every schedule.
+    List(ClientArgs),
+    /// Create a schedule that starts a task each time the cron expression
+    /// fires.
+    Create(ScheduleCreateArgs),
+    /// Show a schedule with its next firing and recent runs.
+    Get(ScheduleIdArgs),
+    /// Stop a schedule. Tasks it already started are kept.
+    Delete(ScheduleIdArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ScheduleCreateArgs {
+    /// Five-field cron expression, evaluated in UTC.
+    #[arg(long)]
+    cron: String,
+    #[arg(long)]
+    prompt: String,
+    /// Agent to run the prompt with. Repeat for each agent.
+    #[arg(long = "agent", required = true)]
+    agents: Vec<String>,
+    #[arg(long)]
+    cwd: Option<String>,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct ScheduleIdArgs {
+    schedule_id: String,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
 #[derive(Args, Debug, Clone)]
 pub struct ClientArgs {
     #[arg(long, short = 'e')]
@@ -255,6 +430,37 @@ pub struct ApiInstallAgentArgs {
     agent_version: Option<String>,
     #[arg(long = "agent-process-version")]
     agent_process_version: Option<String>,
+    #[arg(long = "artifact-dir")]
+    artifact_dir: Option<String>,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct ApiAgentArgs {
+    agent: String,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct ApiPinAgentArgs {
+    agent: String,
+    #[arg(long = "agent-version")]
+    agent_version: Option<String>,
+    #[arg(long = "agent-process-version")]
+    agent_process_version: Option<String>,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct ApiUpgradeAgentArgs {
+    agent: String,
+    #[arg(long = "agent-version")]
+    agent_version: Option<String>,
+    #[arg(long = "agent-process-version")]
+    agent_process_version: Option<String>,
     #[command(flatten)]
     client: ClientArgs,
 }
@@ -269,6 +475,10 @@ pub struct AcpPostArgs {
     json: Option<String>,
     #[arg(long = "json-file")]
     json_file: Option<PathBuf>,
+    /// Send an Idempotency-Key so a retry with the same key replays the
+    /// first response instead of running the request again.
+    #[arg(long = "idempotency-key")]
+    idempotency_key: Option<String>,
     #[command(flatten)]
     client: ClientArgs,
 }
@@ -279,6 +489,270 @@ pub struct AcpStreamArgs {
     server_id: String,
     #[arg(long = "last-event-id")]
     last_event_id: Option<u64>,
+    /// Name events after the envelope method (or `response`) instead of `message`.
+    #[arg(long = "named-events")]
+    named_events: bool,
+    /// Include `_adapter/log` notifications carrying agent stderr lines.
+    #[arg(long = "include-logs")]
+    include_logs: bool,
+    /// Only these event kinds (`sessionUpdate` values, methods or `response`).
+    #[arg(long, value_delimiter = ',')]
+    types: Vec<String>,
+    /// Drop these event kinds.
+    #[arg(long, value_delimiter = ',')]
+    exclude: Vec<String>,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct AcpEventsArgs {
+    #[arg(long = "server-id")]
+    server_id: String,
+    #[arg(long)]
+    offset: Option<u64>,
+    /// Wait up to this many milliseconds when no envelope is buffered yet.
+    #[arg(long = "wait-ms")]
+    wait_ms: Option<u64>,
+    #[arg(long)]
+    limit: Option<usize>,
+    /// Include `_adapter/log` notifications carrying agent stderr lines.
+    #[arg(long = "include-logs")]
+    include_logs: bool,
+    /// Only these event kinds (`sessionUpdate` values, methods or `response`).
+    #[arg(long, value_delimiter = ',')]
+    types: Vec<String>,
+    /// Drop these event kinds.
+    #[arg(long, value_delimiter = ',')]
+    exclude: Vec<String>,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct LogsArgs {
+    /// Only events for this ACP server or session id.
+    #[arg(long)]
+    session: Option<String>,
+    /// Least severe level to include (error, warn, info, debug, trace).
+    #[arg(long)]
+    level: Option<String>,
+    /// Only events after this sequence number.
+    #[arg(long)]
+    since: Option<u64>,
+    #[arg(long)]
+    limit: Option<usize>,
+    /// Keep streaming new events from /v1/logs/stream.
+    #[arg(long, short = 'f')]
+    follow: bool,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct AcpRequestsArgs {
+    #[arg(long = "server-id")]
+    server_id: String,
+    /// Only list requests for this ACP session.
+    #[arg(long = "session-id")]
+    session_id: Option<String>,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct AcpTurnLogsArgs {
+    #[arg(long = "server-id")]
+    server_id: String,
+    /// Turn number from `_adapter/turn_started`.
+    #[arg(long)]
+    turn: u64,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct AcpAttachArgs {
+    #[arg(long = "server-id")]
+    server_id: String,
+    /// File to upload; repeat for several files.
+    #[arg(long = "file", required = true)]
+    files: Vec<PathBuf>,
+    /// Directory to store the files in, such as the session `cwd`.
+    #[arg(long)]
+    dir: Option<String>,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct AcpCompactArgs {
+    #[arg(long = "server-id")]
+    server_id: String,
+    #[arg(long = "session-id")]
+    session_id: String,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct AcpPlanArgs {
+    #[arg(long = "server-id")]
+    server_id: String,
+    #[arg(long = "session-id")]
+    session_id: String,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct AcpApprovePlanArgs {
+    #[arg(long = "server-id")]
+    server_id: String,
+    #[arg(long = "session-id")]
+    session_id: String,
+    /// Mode to build in; defaults to the agent's build mode.
+    #[arg(long = "mode-id")]
+    mode_id: Option<String>,
+    /// Added to the build prompt after the plan.
+    #[arg(long)]
+    note: Option<String>,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct AcpSessionArgs {
+    #[arg(long = "server-id")]
+    server_id: String,
+    #[arg(long = "session-id")]
+    session_id: String,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct AcpUpdateSessionArgs {
+    #[arg(long = "server-id")]
+    server_id: String,
+    #[arg(long = "session-id")]
+    session_id: String,
+    /// ACP mode id to switch to.
+    #[arg(long = "mode-id")]
+    mode_id: Option<String>,
+    /// Model id to switch to.
+    #[arg(long = "model-id")]
+    model_id: Option<String>,
+    /// Permission mode name, such as `plan` or `acceptEdits`.
+    #[arg(long = "permission-mode")]
+    permission_mode: Option<String>,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct AcpChildrenArgs {
+    #[arg(long = "server-id")]
+    server_id: String,
+    #[arg(long = "session-id")]
+    session_id: String,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct AcpRetryArgs {
+    #[arg(long = "server-id")]
+    server_id: String,
+    #[arg(long = "session-id")]
+    session_id: String,
+    /// Replaces the text of the last prompt.
+    #[arg(long)]
+    text: Option<String>,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct AcpArtifactArgs {
+    #[arg(long = "server-id")]
+    server_id: String,
+    #[arg(long = "session-id")]
+    session_id: String,
+    /// Artifact id from `_meta["sandboxagent.dev"].artifacts`.
+    #[arg(long = "artifact-id")]
+    artifact_id: String,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct AcpTranscriptArgs {
+    #[arg(long = "server-id")]
+    server_id: String,
+    #[arg(long = "session-id")]
+    session_id: String,
+    /// `json`, `text` (assistant messages only) or `markdown`.
+    #[arg(long, default_value = "json")]
+    format: String,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct AcpResultArgs {
+    #[arg(long = "server-id")]
+    server_id: String,
+    #[arg(long = "session-id")]
+    session_id: String,
+    /// How long the server waits for a prompt in flight.
+    #[arg(long = "timeout-ms", default_value_t = 60_000)]
+    timeout_ms: u64,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct AcpInterruptArgs {
+    #[arg(long = "server-id")]
+    server_id: String,
+    #[arg(long = "session-id")]
+    session_id: String,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct AcpQueueMessageArgs {
+    #[arg(long = "server-id")]
+    server_id: String,
+    #[arg(long = "session-id")]
+    session_id: String,
+    /// Prompt text.
+    #[arg(long)]
+    text: String,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct AcpMessagesArgs {
+    #[arg(long = "server-id")]
+    server_id: String,
+    #[arg(long = "session-id")]
+    session_id: String,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct AcpCancelMessageArgs {
+    #[arg(long = "server-id")]
+    server_id: String,
+    #[arg(long = "session-id")]
+    session_id: String,
+    #[arg(long = "message-id")]
+    message_id: String,
     #[command(flatten)]
     client: ClientArgs,
 }
@@ -300,6 +774,8 @@ pub struct InstallAgentArgs {
     agent_version: Option<String>,
     #[arg(long = "agent-process-version")]
     agent_process_version: Option<String>,
+    #[arg(long = "artifact-dir")]
+    artifact_dir: Option<PathBuf>,
 }
 
 #[derive(Args, Debug)]
@@ -330,6 +806,8 @@ pub struct CredentialsExtractEnvArgs {
 pub enum CliError {
     #[error("missing --token or --no-token for server mode")]
     MissingToken,
+    #[error("SANDBOX_AGENT_NAMESPACE_TOKENS requires a server token; set --token")]
+    NamespacesWithoutToken,
     #[error("invalid cors origin: {0}")]
     InvalidCorsOrigin(String),
     #[error("invalid cors method: {0}")]
@@ -377,8 +855,8 @@ pub fn run_sandbox_agent() -> Result<(), CliError> {
 }
 
 pub fn init_logging(command: &Command) -> Result<(), CliError> {
-    if matches!(command, Command::Server(_)) {
-        maybe_redirect_server_logs();
+    if let Command::Server(server) = command {
+        maybe_redirect_server_logs(server);
     }
 
     let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
@@ -389,6 +867,7 @@ pub fn init_logging(command: &Command) -> Result<(), CliError> {
                 .layer()
                 .with_writer(std::io::stderr),
         )
+        .with(log_buffer::layer())
         .init();
     Ok(())
 }
@@ -405,11 +884,62 @@ pub fn run_command(command: &Command, cli: &CliConfig) -> Result<(), CliError> {
 }
 
 fn run_server(cli: &CliConfig, server: &ServerArgs) -> Result<(), CliError> {
-    let auth = if let Some(token) = cli.token.clone() {
+    // File settings only fill in what the environment and flags leave unset.
+    let mut config = ServerConfig::load(server.config.as_deref()).map_err(CliError::Server)?;
+
+    let token = if cli.no_token {
+        None
+    } else {
+        cli.token.clone().or_else(|| config.auth.token.clone())
+    };
+    // Without a server token, /opencode, /ui and the install and config
+    // routes would have no owner to gate them against namespace tenants.
+    if token.is_none() && Namespaces::from_env().is_enabled() {
+        return Err(CliError::NamespacesWithoutToken);
+    }
+    let auth = if let Some(token) = token.clone() {
         AuthConfig::with_token(token)
     } else {
         AuthConfig::disabled()
     };
+    let host = server
+        .host
+        .clone()
+        .or_else(|| config.server.host.clone())
+        .unwrap_or_else(|| DEFAULT_HOST.to_string());
+    let port = server.port.or(config.server.port).unwrap_or(DEFAULT_PORT);
+    let install_dir = config
+        .install
+        .dir
+        .clone()
+        .unwrap_or_else(default_install_dir);
+    if !server.cors_allow_origin.is_empty() {
+        config.server.cors_allow_origins = server.cors_allow_origin.clone();
+    }
+    if !server.cors_allow_method.is_empty() {
+        config.server.cors_allow_methods = server.cors_allow_method.clone();
+    }
+    if !server.cors_allow_header.is_empty() {
+        config.server.cors_allow_headers = server.cors_allow_header.clone();
+    }
+    if server.cors_allow_credentials {
+        config.server.cors_allow_credentials = Some(true);
+    }
+    if server.unix_socket.is_some() {
+        config.server.unix_socket = server.unix_socket.clone();
+        config.server.stdio = None;
+    }
+    if server.stdio {
+        config.server.stdio = Some(true);
+        config.server.unix_socket = None;
+    }
+    let listen = if config.server.stdio == Some(true) {
+        Listen::Stdio
+    } else if let Some(path) = config.server.unix_socket.clone() {
+        Listen::Unix(path)
+    } else {
+        Listen::Tcp(format!("{}:{}", host, port))
+    };
 
     let branding = if cli.gigacode {
         BrandingMode::Gigacode
@@ -417,26 +947,40 @@ fn run_server(cli: &CliConfig, server: &ServerArgs) -> Result<(), CliError> {
         BrandingMode::SandboxAgent
     };
 
-    let agent_manager = AgentManager::new(default_install_dir())
-        .map_err(|err| CliError::Server(err.to_string()))?;
-    let state = Arc::new(AppState::with_branding(auth, agent_manager, branding));
-    let (mut router, state) = build_router_with_state(state);
+    let telemetry_enabled = telemetry::telemetry_enabled(
+        server.no_telemetry || config.telemetry.enabled == Some(false),
+    );
+
+    let cors = build_cors_layer(&config.server)?;
 
-    let cors = build_cors_layer(server)?;
+    config.auth.token = token;
+    if matches!(listen, Listen::Tcp(_)) {
+        config.server.host = Some(host.clone());
+        config.server.port = Some(port);
+    } else {
+        config.server.host = None;
+        config.server.port = None;
+    }
+    config.install.dir = Some(install_dir.clone());
+    config.telemetry.enabled = Some(telemetry_enabled);
+
+    let agent_manager =
+        AgentManager::new(install_dir).map_err(|err| CliError::Server(err.to_string()))?;
+    let state = Arc::new(AppState::with_config(auth, agent_manager, branding, config));
+    let (mut router, state) = build_router_with_state(state);
     router = router.layer(cors);
 
-    let addr = format!("{}:{}", server.host, server.port);
-    let display_host = match server.host.as_str() {
+    let display_host = match host.as_str() {
         "0.0.0.0" | "::" => "localhost",
         other => other,
     };
-    let inspector_url = format!("http://{}:{}/ui", display_host, server.port);
+    let inspector_url = format!("http://{}:{}/ui", display_host, port);
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
         .map_err(|err| CliError::Server(err.to_string()))?;
 
-    let telemetry_enabled = telemetry::telemetry_enabled(server.no_telemetry);
+    let preinstall = preinstall_specs(server, &state.server_config().install.preinstall);
 
     runtime.block_on(async move {
         if telemetry_enabled {
@@ -444,27 +988,198 @@ fn run_server(cli: &CliConfig, server: &ServerArgs) -> Result<(), CliError> {
             telemetry::spawn_telemetry_task();
         }
 
-        let listener = tokio::net::TcpListener::bind(&addr).await?;
-        tracing::info!(addr = %addr, "server listening");
-        if ui::is_enabled() {
-            tracing::info!(url = %inspector_url, "inspector ui available");
-        }
+        state
+            .start_preinstall(&preinstall)
+            .map_err(|err| CliError::Server(err.to_string()))?;
+        state.acp_proxy().start_warm_pool();
 
         let shutdown_state = state.clone();
-        axum::serve(listener, router)
-            .with_graceful_shutdown(async move {
-                let _ = tokio::signal::ctrl_c().await;
-                shutdown_servers(&shutdown_state).await;
-            })
-            .await
-            .map_err(|err| CliError::Server(err.to_string()))
+        let shutdown = async move {
+            shutdown_signal().await;
+            tracing::info!("shutdown requested; stopping agent processes");
+            shutdown_servers(&shutdown_state).await;
+        };
+        match listen {
+            Listen::Tcp(addr) => {
+                let listener = tokio::net::TcpListener::bind(&addr).await?;
+                tracing::info!(addr = %addr, "server listening");
+                if ui::is_enabled() {
+                    tracing::info!(url = %inspector_url, "inspector ui available");
+                }
+                axum::serve(listener, router)
+                    .with_graceful_shutdown(shutdown)
+                    .await
+                    .map_err(|err| CliError::Server(err.to_string()))
+            }
+            Listen::Unix(path) => {
+                tracing::info!(path = %path.display(), "server listening on unix socket");
+                transport::serve_unix(&path, router, shutdown)
+                    .await
+                    .map_err(|err| CliError::Server(format!("{}: {err}", path.display())))
+            }
+            Listen::Stdio => {
+                tracing::info!("serving over stdio");
+                transport::serve_stdio(router, shutdown).await?;
+                shutdown_servers(&state).await;
+                Ok(())
+            }
+        }
     })
 }
 
+/// Where `sandbox-agent server` accepts connections.
+enum Listen {
+    Tcp(String),
+    Unix(PathBuf),
+    Stdio,
+}
+
+/// Resolve on Ctrl-C, or on SIGTERM where supported, which is what
+/// container runtimes and process supervisors send.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(err) => {
+                tracing::warn!(error = %err, "failed to listen for SIGTERM");
+            }
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+fn preinstall_specs(server: &ServerArgs, configured: &[String]) -> Vec<String> {
+    server
+        .preinstall
+        .iter()
+        .chain(configured)
+        .map(|spec| spec.trim().to_string())
+        .filter(|spec| !spec.is_empty())
+        .collect()
+}
+
 fn run_api(command: &ApiCommand, cli: &CliConfig) -> Result<(), CliError> {
     match command {
         ApiCommand::Agents(subcommand) => run_agents(&subcommand.command, cli),
         ApiCommand::Acp(subcommand) => run_acp(&subcommand.command, cli),
+        ApiCommand::Tasks(subcommand) => run_tasks(&subcommand.command, cli),
+        ApiCommand::Schedules(subcommand) => run_schedules(&subcommand.command, cli),
+        ApiCommand::Config(args) => {
+            let ctx = ClientContext::new(cli, args)?;
+            let response = ctx.get(&format!("{API_PREFIX}/config"))?;
+            print_json_or_empty(response)
+        }
+        ApiCommand::Logs(args) => run_logs(args, cli),
+    }
+}
+
+fn run_logs(args: &LogsArgs, cli: &CliConfig) -> Result<(), CliError> {
+    let ctx = ClientContext::new(cli, &args.client)?;
+    let limit = if args.follow { None } else { args.limit };
+    let query = [
+        ("session", args.session.clone()),
+        ("level", args.level.clone()),
+        ("since", args.since.map(|value| value.to_string())),
+        ("limit", limit.map(|value| value.to_string())),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| value.map(|value| format!("{key}={value}")))
+    .collect::<Vec<_>>();
+    let path = if args.follow {
+        format!("{API_PREFIX}/logs/stream")
+    } else {
+        format!("{API_PREFIX}/logs")
+    };
+    let path = if query.is_empty() {
+        path
+    } else {
+        format!("{path}?{}", query.join("&"))
+    };
+    if args.follow {
+        let response = ctx
+            .request(Method::GET, &path)
+            .header("accept", "text/event-stream")
+            .send()?;
+        print_text_response(response)
+    } else {
+        print_json_or_empty(ctx.get(&path)?)
+    }
+}
+
+fn run_schedules(command: &SchedulesCommand, cli: &CliConfig) -> Result<(), CliError> {
+    match command {
+        SchedulesCommand::List(args) => {
+            let ctx = ClientContext::new(cli, args)?;
+            let response = ctx.get(&format!("{API_PREFIX}/schedules"))?;
+            print_json_or_empty(response)
+        }
+        SchedulesCommand::Create(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let body = json!({
+                "cron": args.cron,
+                "prompt": args.prompt,
+                "agents": args
+                    .agents
+                    .iter()
+                    .map(|agent| json!({ "agent": agent }))
+                    .collect::<Vec<_>>(),
+                "cwd": args.cwd,
+            });
+            let response = ctx.post(&format!("{API_PREFIX}/schedules"), &body)?;
+            print_json_or_empty(response)
+        }
+        SchedulesCommand::Get(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let response = ctx.get(&format!("{API_PREFIX}/schedules/{}", args.schedule_id))?;
+            print_json_or_empty(response)
+        }
+        SchedulesCommand::Delete(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let response = ctx.delete(&format!("{API_PREFIX}/schedules/{}", args.schedule_id))?;
+            print_empty_response(response)
+        }
+    }
+}
+
+fn run_tasks(command: &TasksCommand, cli: &CliConfig) -> Result<(), CliError> {
+    match command {
+        TasksCommand::List(args) => {
+            let ctx = ClientContext::new(cli, args)?;
+            let response = ctx.get(&format!("{API_PREFIX}/tasks"))?;
+            print_json_or_empty(response)
+        }
+        TasksCommand::Create(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let body = json!({
+                "prompt": args.prompt,
+                "agents": args
+                    .agents
+                    .iter()
+                    .map(|agent| json!({ "agent": agent }))
+                    .collect::<Vec<_>>(),
+                "cwd": args.cwd,
+            });
+            let response = ctx.post(&format!("{API_PREFIX}/tasks"), &body)?;
+            print_json_or_empty(response)
+        }
+        TasksCommand::Get(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let response = ctx.get(&format!("{API_PREFIX}/tasks/{}", args.task_id))?;
+            print_json_or_empty(response)
+        }
+        TasksCommand::Delete(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let response = ctx.delete(&format!("{API_PREFIX}/tasks/{}", args.task_id))?;
+            print_empty_response(response)
+        }
     }
 }
 
@@ -488,6 +1203,9 @@ fn run_agents(command: &AgentsCommand, cli: &CliConfig) -> Result<(), CliError>
             if let Some(version) = args.agent_process_version.clone() {
                 params.insert("agentProcessVersion".to_string(), Value::String(version));
             }
+            if let Some(dir) = args.artifact_dir.clone() {
+                params.insert("artifactDir".to_string(), Value::String(dir));
+            }
             let result = call_acp_extension(
                 &ctx,
                 ACP_EXTENSION_AGENT_INSTALL_METHOD,
@@ -495,6 +1213,42 @@ fn run_agents(command: &AgentsCommand, cli: &CliConfig) -> Result<(), CliError>
             )?;
             write_stdout_line(&serde_json::to_string_pretty(&result)?)
         }
+        AgentsCommand::Uninstall(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let response = ctx.delete(&format!("{API_PREFIX}/agents/{}", args.agent))?;
+            print_json_or_empty(response)
+        }
+        AgentsCommand::Models(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let response = ctx.get(&format!("{API_PREFIX}/agents/{}/models", args.agent))?;
+            print_json_or_empty(response)
+        }
+        AgentsCommand::Versions(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let response = ctx.get(&format!("{API_PREFIX}/agents/{}/versions", args.agent))?;
+            print_json_or_empty(response)
+        }
+        AgentsCommand::Pin(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let body = json!({
+                "agentVersion": args.agent_version,
+                "agentProcessVersion": args.agent_process_version,
+            });
+            let response = ctx.put(&format!("{API_PREFIX}/agents/{}/pin", args.agent), &body)?;
+            print_json_or_empty(response)
+        }
+        AgentsCommand::Upgrade(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let body = json!({
+                "agentVersion": args.agent_version,
+                "agentProcessVersion": args.agent_process_version,
+            });
+            let response = ctx.post(
+                &format!("{API_PREFIX}/agents/{}/upgrade", args.agent),
+                &body,
+            )?;
+            print_json_or_empty(response)
+        }
     }
 }
 
@@ -561,12 +1315,37 @@ fn run_acp(command: &AcpCommand, cli: &CliConfig) -> Result<(), CliError> {
             let ctx = ClientContext::new(cli, &args.client)?;
             let payload = load_json_payload(args.json.as_deref(), args.json_file.as_deref())?;
             let path = build_acp_server_path(&args.server_id, args.agent.as_deref())?;
+            let mut request = ctx.request(Method::POST, &path).json(&payload);
+            if let Some(key) = &args.idempotency_key {
+                request = request.header("Idempotency-Key", key);
+            }
+            print_json_or_empty(request.send()?)
+        }
+        AcpCommand::Validate(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let payload = load_json_payload(args.json.as_deref(), args.json_file.as_deref())?;
+            let path =
+                build_acp_server_subpath(&args.server_id, "/validate", args.agent.as_deref())?;
             let response = ctx.post(&path, &payload)?;
             print_json_or_empty(response)
         }
         AcpCommand::Stream(args) => {
             let ctx = ClientContext::new(cli, &args.client)?;
             let path = build_acp_server_path(&args.server_id, None)?;
+            let mut query = [
+                ("namedEvents", args.named_events),
+                ("includeLogs", args.include_logs),
+            ]
+            .into_iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(key, _)| format!("{key}=true"))
+            .collect::<Vec<_>>();
+            query.extend(event_filter_query(&args.types, &args.exclude));
+            let path = if query.is_empty() {
+                path
+            } else {
+                format!("{path}?{}", query.join("&"))
+            };
             let request = ctx
                 .request(Method::GET, &path)
                 .header("accept", "text/event-stream");
@@ -576,6 +1355,225 @@ fn run_acp(command: &AcpCommand, cli: &CliConfig) -> Result<(), CliError> {
             let response = request.send()?;
             print_text_response(response)
         }
+        AcpCommand::Events(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = build_acp_server_subpath(&args.server_id, "/events", None)?;
+            let query = [
+                ("offset", args.offset.map(|value| value.to_string())),
+                ("waitMs", args.wait_ms.map(|value| value.to_string())),
+                ("limit", args.limit.map(|value| value.to_string())),
+                ("includeLogs", args.include_logs.then(|| "true".to_string())),
+            ]
+            .into_iter()
+            .filter_map(|(key, value)| value.map(|value| format!("{key}={value}")))
+            .chain(event_filter_query(&args.types, &args.exclude))
+            .collect::<Vec<_>>();
+            let path = if query.is_empty() {
+                path
+            } else {
+                format!("{path}?{}", query.join("&"))
+            };
+            let response = ctx.get(&path)?;
+            print_json_or_empty(response)
+        }
+        AcpCommand::Requests(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = build_acp_server_subpath(&args.server_id, "/requests", None)?;
+            let path = match &args.session_id {
+                Some(session_id) => format!("{path}?sessionId={session_id}"),
+                None => path,
+            };
+            let response = ctx.get(&path)?;
+            print_json_or_empty(response)
+        }
+        AcpCommand::TurnLogs(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = build_acp_server_subpath(
+                &args.server_id,
+                &format!("/turns/{}/logs", args.turn),
+                None,
+            )?;
+            let response = ctx.get(&path)?;
+            print_text_response(response)
+        }
+        AcpCommand::Artifact(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = build_acp_server_subpath(
+                &args.server_id,
+                &format!(
+                    "/sessions/{}/artifacts/{}",
+                    args.session_id, args.artifact_id
+                ),
+                None,
+            )?;
+            let response = ctx.get(&path)?;
+            print_text_response(response)
+        }
+        AcpCommand::Transcript(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = build_acp_server_subpath(
+                &args.server_id,
+                &format!(
+                    "/sessions/{}/transcript?format={}",
+                    args.session_id, args.format
+                ),
+                None,
+            )?;
+            let response = ctx.get(&path)?;
+            if args.format == "json" {
+                print_json_or_empty(response)
+            } else {
+                print_text_response(response)
+            }
+        }
+        AcpCommand::Result(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = build_acp_server_subpath(
+                &args.server_id,
+                &format!(
+                    "/sessions/{}/result?timeoutMs={}",
+                    args.session_id, args.timeout_ms
+                ),
+                None,
+            )?;
+            // Outlast the server's wait rather than the client's default
+            // 30s timeout.
+            let response = ctx
+                .request(Method::GET, &path)
+                .timeout(Duration::from_millis(args.timeout_ms) + Duration::from_secs(30))
+                .send()?;
+            print_json_or_empty(response)
+        }
+        AcpCommand::Attach(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = build_acp_server_subpath(&args.server_id, "/attachments", None)?;
+            let mut form = reqwest::blocking::multipart::Form::new();
+            for file in &args.files {
+                form = form.file("file", file)?;
+            }
+            let mut request = ctx.request(Method::POST, &path).multipart(form);
+            if let Some(dir) = &args.dir {
+                request = request.query(&[("dir", dir)]);
+            }
+            print_json_or_empty(request.send()?)
+        }
+        AcpCommand::Compact(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = build_acp_server_subpath(&args.server_id, "/compact", None)?;
+            let response = ctx.post(&path, &json!({ "sessionId": args.session_id }))?;
+            print_json_or_empty(response)
+        }
+        AcpCommand::Plan(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = build_acp_server_subpath(
+                &args.server_id,
+                &format!("/sessions/{}/plan", args.session_id),
+                None,
+            )?;
+            let response = ctx.get(&path)?;
+            print_json_or_empty(response)
+        }
+        AcpCommand::ApprovePlan(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = build_acp_server_subpath(
+                &args.server_id,
+                &format!("/sessions/{}/plan/approve", args.session_id),
+                None,
+            )?;
+            let response =
+                ctx.post(&path, &json!({ "modeId": args.mode_id, "note": args.note }))?;
+            print_json_or_empty(response)
+        }
+        AcpCommand::Session(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = build_acp_server_subpath(
+                &args.server_id,
+                &format!("/sessions/{}", args.session_id),
+                None,
+            )?;
+            let response = ctx.get(&path)?;
+            print_json_or_empty(response)
+        }
+        AcpCommand::UpdateSession(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = build_acp_server_subpath(
+                &args.server_id,
+                &format!("/sessions/{}", args.session_id),
+                None,
+            )?;
+            let response = ctx.patch(
+                &path,
+                &json!({
+                    "modeId": args.mode_id,
+                    "modelId": args.model_id,
+                    "permissionMode": args.permission_mode,
+                }),
+            )?;
+            print_json_or_empty(response)
+        }
+        AcpCommand::Children(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = build_acp_server_subpath(
+                &args.server_id,
+                &format!("/sessions/{}/children", args.session_id),
+                None,
+            )?;
+            let response = ctx.get(&path)?;
+            print_json_or_empty(response)
+        }
+        AcpCommand::Retry(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = build_acp_server_subpath(
+                &args.server_id,
+                &format!("/sessions/{}/retry", args.session_id),
+                None,
+            )?;
+            let response = ctx.post(&path, &json!({ "text": args.text }))?;
+            print_json_or_empty(response)
+        }
+        AcpCommand::Interrupt(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = build_acp_server_subpath(
+                &args.server_id,
+                &format!("/sessions/{}/interrupt", args.session_id),
+                None,
+            )?;
+            let response = ctx.post(&path, &json!({}))?;
+            print_json_or_empty(response)
+        }
+        AcpCommand::QueueMessage(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = build_acp_server_subpath(
+                &args.server_id,
+                &format!("/sessions/{}/messages", args.session_id),
+                None,
+            )?;
+            let response = ctx.post(
+                &path,
+                &json!({ "prompt": [{ "type": "text", "text": args.text }] }),
+            )?;
+            print_json_or_empty(response)
+        }
+        AcpCommand::Messages(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = build_acp_server_subpath(
+                &args.server_id,
+                &format!("/sessions/{}/messages", args.session_id),
+                None,
+            )?;
+            let response = ctx.get(&path)?;
+            print_json_or_empty(response)
+        }
+        AcpCommand::CancelMessage(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = build_acp_server_subpath(
+                &args.server_id,
+                &format!("/sessions/{}/messages/{}", args.session_id, args.message_id),
+                None,
+            )?;
+            let response = ctx.delete(&path)?;
+            print_empty_response(response)
+        }
         AcpCommand::Close(args) => {
             let ctx = ClientContext::new(cli, &args.client)?;
             let path = build_acp_server_path(&args.server_id, None)?;
@@ -739,6 +1737,7 @@ fn install_agent_local(args: &InstallAgentArgs) -> Result<(), CliError> {
                 reinstall: args.reinstall,
                 version: args.agent_version.clone(),
                 agent_process_version: args.agent_process_version.clone(),
+                artifact_dir: args.artifact_dir.clone(),
             },
         )
         .map_err(|err| CliError::Server(err.to_string()))?;
@@ -939,6 +1938,14 @@ fn apply_last_event_id_header(
     }
 }
 
+fn event_filter_query(types: &[String], exclude: &[String]) -> Vec<String> {
+    [("types", types), ("exclude", exclude)]
+        .into_iter()
+        .filter(|(_, kinds)| !kinds.is_empty())
+        .map(|(key, kinds)| format!("{key}={}", kinds.join(",")))
+        .collect()
+}
+
 fn unique_cli_server_id(prefix: &str) -> String {
     let millis = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -950,6 +1957,14 @@ fn unique_cli_server_id(prefix: &str) -> String {
 fn build_acp_server_path(
     server_id: &str,
     bootstrap_agent: Option<&str>,
+) -> Result<String, CliError> {
+    build_acp_server_subpath(server_id, "", bootstrap_agent)
+}
+
+fn build_acp_server_subpath(
+    server_id: &str,
+    suffix: &str,
+    bootstrap_agent: Option<&str>,
 ) -> Result<String, CliError> {
     let server_id = server_id.trim();
     if server_id.is_empty() {
@@ -961,7 +1976,7 @@ fn build_acp_server_path(
         ));
     }
 
-    let mut path = format!("{API_PREFIX}/acp/{server_id}");
+    let mut path = format!("{API_PREFIX}/acp/{server_id}{suffix}");
     if let Some(agent) = bootstrap_agent {
         let agent = agent.trim();
         if agent.is_empty() {
@@ -985,22 +2000,31 @@ fn default_server_log_dir() -> PathBuf {
         .unwrap_or_else(|| PathBuf::from(".").join(".sandbox-agent").join("logs"))
 }
 
-fn maybe_redirect_server_logs() {
+fn maybe_redirect_server_logs(server: &ServerArgs) {
     if std::env::var("SANDBOX_AGENT_LOG_STDOUT").is_ok() {
         return;
     }
 
     let log_dir = default_server_log_dir();
-    if let Err(err) = ServerLogs::new(log_dir, LOGS_RETENTION).start_sync() {
+    let mut logs = ServerLogs::new(log_dir, LOGS_RETENTION);
+    // In stdio mode stdout carries HTTP responses.
+    let stdio = server.stdio
+        || ServerConfig::load(server.config.as_deref())
+            .is_ok_and(|config| config.server.stdio == Some(true));
+    if stdio {
+        logs = logs.keep_stdout();
+    }
+    if let Err(err) = logs.start_sync() {
         eprintln!("failed to redirect logs: {err}");
     }
 }
 
-fn build_cors_layer(server: &ServerArgs) -> Result<CorsLayer, CliError> {
+fn build_cors_layer(settings: &ServerSettings) -> Result<CorsLayer, CliError> {
     let mut cors = CorsLayer::new();
+    let credentials = settings.cors_allow_credentials == Some(true);
 
     let mut origins = Vec::new();
-    for origin in &server.cors_allow_origin {
+    for origin in &settings.cors_allow_origins {
         let value = origin
             .parse()
             .map_err(|_| CliError::InvalidCorsOrigin(origin.clone()))?;
@@ -1012,11 +2036,15 @@ fn build_cors_layer(server: &ServerArgs) -> Result<CorsLayer, CliError> {
         cors = cors.allow_origin(origins);
     }
 
-    if server.cors_allow_method.is_empty() {
+    // Browsers reject `*` on credentialed requests, so those get the
+    // requested method and headers echoed back instead.
+    if settings.cors_allow_methods.is_empty() && credentials {
+        cors = cors.allow_methods(AllowMethods::mirror_request());
+    } else if settings.cors_allow_methods.is_empty() {
         cors = cors.allow_methods(Any);
     } else {
         let mut methods = Vec::new();
-        for method in &server.cors_allow_method {
+        for method in &settings.cors_allow_methods {
             let parsed = method
                 .parse()
                 .map_err(|_| CliError::InvalidCorsMethod(method.clone()))?;
@@ -1025,11 +2053,13 @@ fn build_cors_layer(server: &ServerArgs) -> Result<CorsLayer, CliError> {
         cors = cors.allow_methods(methods);
     }
 
-    if server.cors_allow_header.is_empty() {
+    if settings.cors_allow_headers.is_empty() && credentials {
+        cors = cors.allow_headers(AllowHeaders::mirror_request());
+    } else if settings.cors_allow_headers.is_empty() {
         cors = cors.allow_headers(Any);
     } else {
         let mut headers = Vec::new();
-        for header in &server.cors_allow_header {
+        for header in &settings.cors_allow_headers {
             let parsed = header
                 .parse()
                 .map_err(|_| CliError::InvalidCorsHeader(header.clone()))?;
@@ -1038,7 +2068,7 @@ fn build_cors_layer(server: &ServerArgs) -> Result<CorsLayer, CliError> {
         cors = cors.allow_headers(headers);
     }
 
-    if server.cors_allow_credentials {
+    if credentials {
         cors = cors.allow_credentials(true);
     }
 
@@ -1095,6 +2125,22 @@ impl ClientContext {
         Ok(self.request(Method::POST, path).json(body).send()?)
     }
 
+    fn put<T: Serialize>(
+        &self,
+        path: &str,
+        body: &T,
+    ) -> Result<reqwest::blocking::Response, CliError> {
+        Ok(self.request(Method::PUT, path).json(body).send()?)
+    }
+
+    fn patch<T: Serialize>(
+        &self,
+        path: &str,
+        body: &T,
+    ) -> Result<reqwest::blocking::Response, CliError> {
+        Ok(self.request(Method::PATCH, path).json(body).send()?)
+    }
+
     fn delete(&self, path: &str) -> Result<reqwest::blocking::Response, CliError> {
         Ok(self.request(Method::DELETE, path).send()?)
     }
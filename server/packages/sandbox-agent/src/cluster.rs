@@ -0,0 +1,621 @@
+//! Several replicas behind one load balancer, sharing state through Redis.
+//!
+//! With `SANDBOX_AGENT_CLUSTER_REDIS_URL` set, a replica records each ACP
+//! server it runs in the `sandbox-agent:servers` hash, mapping the server id
+//! to the replica's own `SANDBOX_AGENT_REPLICA_URL`, and appends every
+//! envelope to the Redis stream `sandbox-agent:events:{serverId}` under its
+//! sequence number. Any replica can then serve `GET /v1/acp/{server_id}` by
+//! reading that stream, replaying from `Last-Event-ID` as the owner would,
+//! and every other request for the server is forwarded to the replica that
+//! owns it. Agent processes stay on the replica that started them. Each
+//! replica refreshes `sandbox-agent:replicas:{replicaUrl}`, which expires
+//! shortly after it stops; a lookup that finds a server of a replica whose
+//! key is gone drops the server from the hash, which also ends the streams
+//! other replicas serve for it.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use acp_http_adapter::process::is_log_envelope;
+use axum::body::Body;
+use axum::http::{header, HeaderName, HeaderValue, Request, StatusCode};
+use axum::response::sse::Event;
+use axum::response::Response;
+use futures::StreamExt;
+use redis::aio::{ConnectionManager, MultiplexedConnection};
+use redis::streams::{StreamMaxlen, StreamReadOptions, StreamReadReply};
+use redis::{AsyncCommands, AsyncConnectionConfig, RedisError};
+use sandbox_agent_error::SandboxError;
+use serde_json::Value;
+use tokio::sync::{broadcast, OnceCell};
+
+use crate::acp_proxy_runtime::{AcpProxyRuntime, PinBoxSseStream};
+use crate::event_filter::EventFilter;
+use crate::request_limits;
+
+const SERVERS_KEY: &str = "sandbox-agent:servers";
+const EVENTS_KEY_PREFIX: &str = "sandbox-agent:events:";
+const HEARTBEAT_KEY_PREFIX: &str = "sandbox-agent:replicas:";
+/// How often a replica refreshes its heartbeat key, and how long the key
+/// outlives the last refresh.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+const HEARTBEAT_TTL_SECS: u64 = 15;
+const REGISTER_RETRY_MIN: Duration = Duration::from_millis(500);
+const REGISTER_RETRY_MAX: Duration = Duration::from_secs(30);
+/// Redis streams are trimmed to roughly this many envelopes per server.
+const EVENTS_MAXLEN: usize = 10_000;
+/// How long a closed server's envelopes stay readable.
+const CLOSED_EVENTS_TTL_SECS: i64 = 300;
+/// How long one blocking stream read waits before checking that the server
+/// still exists.
+const READ_BLOCK_MS: usize = 15_000;
+const READ_COUNT: usize = 100;
+
+/// Record a server for a replica unless another replica holds it, clearing
+/// an events stream left over from an earlier server with the same id,
+/// whose higher sequence numbers would reject the new server's.
+const REGISTER_SCRIPT: &str = r#"
+local owner = redis.call('HGET', KEYS[1], ARGV[1])
+if owner and owner ~= ARGV[2] then
+    return 0
+end
+redis.call('HSET', KEYS[1], ARGV[1], ARGV[2])
+redis.call('DEL', KEYS[2])
+return 1
+"#;
+
+/// Owner of a server, dropping the entry when the owner's heartbeat has
+/// expired. The asking replica is alive, so its own entries are kept.
+const OWNER_SCRIPT: &str = r#"
+local owner = redis.call('HGET', KEYS[1], ARGV[1])
+if not owner then
+    return false
+end
+if owner == ARGV[3] or redis.call('EXISTS', ARGV[2] .. owner) == 1 then
+    return owner
+end
+redis.call('HDEL', KEYS[1], ARGV[1])
+return false
+"#;
+
+/// Drop a server's entry only while it still names the given replica, so a
+/// replica that took the id over in the meantime keeps it.
+const RELEASE_SCRIPT: &str = r#"
+if redis.call('HGET', KEYS[1], ARGV[1]) == ARGV[2] then
+    return redis.call('HDEL', KEYS[1], ARGV[1])
+end
+return 0
+"#;
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Cluster {
+    inner: Option<Arc<ClusterInner>>,
+}
+
+#[derive(Debug)]
+struct ClusterInner {
+    client: redis::Client,
+    replica_url: String,
+    /// Multiplexed connection for short commands, opened on first use. It
+    /// reconnects by itself, and a command dropped mid-flight leaves it
+    /// usable. Blocking reads open their own.
+    connection: OnceCell<ConnectionManager>,
+    http: reqwest::Client,
+}
+
+impl Cluster {
+    pub(crate) fn from_env(acp_proxy: &AcpProxyRuntime) -> Self {
+        let Some(raw) = std::env::var("SANDBOX_AGENT_CLUSTER_REDIS_URL")
+            .ok()
+            .filter(|raw| !raw.trim().is_empty())
+        else {
+            return Self::default();
+        };
+        let client = match redis::Client::open(raw.trim()) {
+            Ok(client) => client,
+            Err(err) => {
+                tracing::warn!(error = %err, "cluster: ignoring SANDBOX_AGENT_CLUSTER_REDIS_URL");
+                return Self::default();
+            }
+        };
+        let Some(replica_url) = std::env::var("SANDBOX_AGENT_REPLICA_URL")
+            .ok()
+            .map(|url| url.trim().trim_end_matches('/').to_string())
+            .filter(|url| !url.is_empty())
+        else {
+            tracing::warn!(
+                "cluster: SANDBOX_AGENT_REPLICA_URL is required with SANDBOX_AGENT_CLUSTER_REDIS_URL; clustering disabled"
+            );
+            return Self::default();
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            tracing::warn!("cluster: no async runtime; clustering disabled");
+            return Self::default();
+        };
+
+        let inner = Arc::new(ClusterInner {
+            client,
+            replica_url,
+            connection: OnceCell::new(),
+            http: reqwest::Client::new(),
+        });
+        runtime.spawn(heartbeat(inner.clone()));
+        runtime.spawn(publish_servers(inner.clone(), acp_proxy.clone()));
+        Self { inner: Some(inner) }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// Replica running `server_id`, when it is not this one.
+    pub(crate) async fn remote_owner(
+        &self,
+        server_id: &str,
+    ) -> Result<Option<String>, SandboxError> {
+        let Some(inner) = &self.inner else {
+            return Ok(None);
+        };
+        Ok(inner
+            .owner(server_id)
+            .await?
+            .filter(|owner| *owner != inner.replica_url))
+    }
+
+    /// Reserve `server_id` for this replica before starting it. Returns the
+    /// replica that already holds it, if another one does.
+    pub(crate) async fn claim(&self, server_id: &str) -> Result<Option<String>, SandboxError> {
+        let Some(inner) = &self.inner else {
+            return Ok(None);
+        };
+        // A second attempt picks up an id whose dead owner the lookup just
+        // dropped.
+        for _ in 0..2 {
+            let claimed: bool = inner
+                .connection()
+                .await?
+                .hset_nx(SERVERS_KEY, server_id, &inner.replica_url)
+                .await
+                .map_err(redis_error)?;
+            if claimed {
+                return Ok(None);
+            }
+            if let Some(owner) = self.remote_owner(server_id).await? {
+                return Ok(Some(owner));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Drop this replica's hold on `server_id`.
+    pub(crate) async fn release(&self, server_id: &str) {
+        if let Some(inner) = &self.inner {
+            inner.release(server_id, &inner.replica_url).await;
+        }
+    }
+
+    /// SSE stream of a server running on another replica, read from its
+    /// Redis stream. Events match the owner's own `GET /v1/acp/{server_id}`.
+    pub(crate) async fn sse(
+        &self,
+        server_id: &str,
+        last_event_id: Option<u64>,
+        named_events: bool,
+        include_logs: bool,
+        filter: EventFilter,
+    ) -> Result<PinBoxSseStream, SandboxError> {
+        let not_found = || SandboxError::SessionNotFound {
+            session_id: server_id.to_string(),
+        };
+        let inner = self.inner.clone().ok_or_else(not_found)?;
+        if inner.owner(server_id).await?.is_none() {
+            return Err(not_found());
+        }
+        // Blocking reads would hold up every other command sharing their
+        // connection, so each stream gets one that outlasts a read.
+        let config = AsyncConnectionConfig::new().set_response_timeout(Some(
+            Duration::from_millis(READ_BLOCK_MS as u64) + Duration::from_secs(5),
+        ));
+        let connection = inner
+            .client
+            .get_multiplexed_async_connection_with_config(&config)
+            .await
+            .map_err(redis_error)?;
+
+        let state = EventsCursor {
+            inner,
+            connection,
+            server_id: server_id.to_string(),
+            after: last_event_id.unwrap_or(0),
+            pending: VecDeque::new(),
+        };
+        let stream = futures::stream::unfold(state, move |mut cursor| {
+            let filter = filter.clone();
+            async move {
+                loop {
+                    while let Some((sequence, payload)) = cursor.pending.pop_front() {
+                        if (!include_logs && is_log_envelope(&payload)) || !filter.matches(&payload)
+                        {
+                            continue;
+                        }
+                        let name = if named_events {
+                            payload
+                                .get("method")
+                                .and_then(Value::as_str)
+                                .unwrap_or("response")
+                        } else {
+                            "message"
+                        };
+                        let event = Event::default()
+                            .event(name)
+                            .id(sequence.to_string())
+                            .data(payload.to_string());
+                        return Some((Ok(event), cursor));
+                    }
+                    if !cursor.fill().await {
+                        return None;
+                    }
+                }
+            }
+        });
+        Ok(Box::pin(stream))
+    }
+
+    /// Send `request` to `owner` and relay its response. `authorization`
+    /// replaces the caller's credentials, since the request was already
+    /// authorized, and rewritten, on this replica. Bodies are buffered up to
+    /// `limit_bytes`.
+    pub(crate) async fn forward(
+        &self,
+        owner: &str,
+        server_id: &str,
+        request: Request<Body>,
+        authorization: Option<&str>,
+        limit_bytes: usize,
+    ) -> Result<Response, SandboxError> {
+        let Some(inner) = &self.inner else {
+            return Err(SandboxError::SessionNotFound {
+                session_id: server_id.to_string(),
+            });
+        };
+        let (parts, body) = request.into_parts();
+        let path = parts
+            .uri
+            .path_and_query()
+            .map(|path| path.as_str())
+            .unwrap_or("/");
+        let method =
+            reqwest::Method::from_bytes(parts.method.as_str().as_bytes()).map_err(|_| {
+                SandboxError::InvalidRequest {
+                    message: format!("unsupported method {}", parts.method),
+                }
+            })?;
+        let mut outgoing = inner.http.request(method, format!("{owner}{path}"));
+        for (name, value) in &parts.headers {
+            if *name == header::HOST
+                || *name == header::AUTHORIZATION
+                || *name == header::CONTENT_LENGTH
+                || is_hop_by_hop(name.as_str())
+            {
+                continue;
+            }
+            outgoing = outgoing.header(name.as_str(), value.as_bytes());
+        }
+        if let Some(authorization) = authorization {
+            outgoing = outgoing.header("authorization", authorization);
+        }
+        let body = axum::body::to_bytes(body, limit_bytes)
+            .await
+            .map_err(|_| request_limits::body_too_large(limit_bytes))?;
+        let outgoing = outgoing.body(body);
+
+        let upstream = match outgoing.send().await {
+            Ok(upstream) => upstream,
+            Err(err) if err.is_connect() => {
+                // The owner is gone, and its agent processes with it.
+                inner.release(server_id, owner).await;
+                return Err(SandboxError::SessionNotFound {
+                    session_id: server_id.to_string(),
+                });
+            }
+            Err(err) => {
+                return Err(SandboxError::StreamError {
+                    message: format!("forwarding to {owner} failed: {err}"),
+                })
+            }
+        };
+
+        let mut response = Response::builder().status(
+            StatusCode::from_u16(upstream.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY),
+        );
+        for (name, value) in upstream.headers() {
+            if is_hop_by_hop(name.as_str()) {
+                continue;
+            }
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_str().as_bytes()),
+                HeaderValue::from_bytes(value.as_bytes()),
+            ) {
+                response = response.header(name, value);
+            }
+        }
+        response
+            .body(Body::from_stream(upstream.bytes_stream()))
+            .map_err(|err| SandboxError::StreamError {
+                message: err.to_string(),
+            })
+    }
+}
+
+impl ClusterInner {
+    async fn connection(&self) -> Result<ConnectionManager, SandboxError> {
+        self.connection
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await
+            .cloned()
+            .map_err(redis_error)
+    }
+
+    /// Replica running `server_id`, if it is still alive.
+    async fn owner(&self, server_id: &str) -> Result<Option<String>, SandboxError> {
+        redis::cmd("EVAL")
+            .arg(OWNER_SCRIPT)
+            .arg(1)
+            .arg(SERVERS_KEY)
+            .arg(server_id)
+            .arg(HEARTBEAT_KEY_PREFIX)
+            .arg(&self.replica_url)
+            .query_async(&mut self.connection().await?)
+            .await
+            .map_err(redis_error)
+    }
+
+    /// Record `server_id` as running here. Returns false when another
+    /// replica holds it.
+    async fn register(&self, server_id: &str) -> Result<bool, SandboxError> {
+        redis::cmd("EVAL")
+            .arg(REGISTER_SCRIPT)
+            .arg(2)
+            .arg(SERVERS_KEY)
+            .arg(events_key(server_id))
+            .arg(server_id)
+            .arg(&self.replica_url)
+            .query_async(&mut self.connection().await?)
+            .await
+            .map_err(redis_error)
+    }
+
+    /// Drop `server_id` from the hash if `owner` still holds it.
+    async fn release(&self, server_id: &str, owner: &str) {
+        let released: Result<i64, SandboxError> = async {
+            redis::cmd("EVAL")
+                .arg(RELEASE_SCRIPT)
+                .arg(1)
+                .arg(SERVERS_KEY)
+                .arg(server_id)
+                .arg(owner)
+                .query_async(&mut self.connection().await?)
+                .await
+                .map_err(redis_error)
+        }
+        .await;
+        if let Err(err) = released {
+            tracing::warn!(server_id = %server_id, error = %err, "cluster: failed to release server");
+        }
+    }
+}
+
+/// Read position of one remote SSE stream.
+struct EventsCursor {
+    inner: Arc<ClusterInner>,
+    connection: MultiplexedConnection,
+    server_id: String,
+    after: u64,
+    pending: VecDeque<(u64, Value)>,
+}
+
+impl EventsCursor {
+    /// Wait for envelopes after the cursor. Returns false once the server is
+    /// gone or Redis fails.
+    async fn fill(&mut self) -> bool {
+        let key = events_key(&self.server_id);
+        let after = format!("{}-0", self.after);
+        let options = StreamReadOptions::default()
+            .count(READ_COUNT)
+            .block(READ_BLOCK_MS);
+        let reply: Result<Option<StreamReadReply>, RedisError> = self
+            .connection
+            .xread_options(&[&key], &[&after], &options)
+            .await;
+        match reply {
+            Ok(Some(reply)) => {
+                for (sequence, payload) in stream_entries(reply) {
+                    self.after = self.after.max(sequence);
+                    self.pending.push_back((sequence, payload));
+                }
+                true
+            }
+            // Timed out; keep waiting while the server exists.
+            Ok(None) => matches!(self.inner.owner(&self.server_id).await, Ok(Some(_))),
+            Err(err) => {
+                tracing::warn!(server_id = %self.server_id, error = %err, "cluster: event stream read failed");
+                false
+            }
+        }
+    }
+}
+
+/// Keep this replica's heartbeat key alive until the runtime shuts down.
+async fn heartbeat(inner: Arc<ClusterInner>) {
+    let key = format!("{HEARTBEAT_KEY_PREFIX}{}", inner.replica_url);
+    let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        interval.tick().await;
+        let refreshed: Result<(), SandboxError> = async {
+            inner
+                .connection()
+                .await?
+                .set_ex(&key, 1, HEARTBEAT_TTL_SECS)
+                .await
+                .map_err(redis_error)
+        }
+        .await;
+        if let Err(err) = refreshed {
+            tracing::warn!(error = %err, "cluster: failed to refresh replica heartbeat");
+        }
+    }
+}
+
+/// Register every server this replica starts and mirror its envelopes to
+/// Redis until the runtime shuts down.
+async fn publish_servers(inner: Arc<ClusterInner>, acp_proxy: AcpProxyRuntime) {
+    let publishing = Arc::new(std::sync::Mutex::new(HashSet::new()));
+    let mut created = acp_proxy.subscribe_created();
+    loop {
+        let server_ids = match created.recv().await {
+            Ok((server_id, _)) => vec![server_id],
+            // Missed some creations; pick up whatever is running now.
+            Err(broadcast::error::RecvError::Lagged(_)) => acp_proxy
+                .server_sequences()
+                .await
+                .into_iter()
+                .map(|(server_id, _, _)| server_id)
+                .collect(),
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        for server_id in server_ids {
+            let is_new = publishing
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .insert(server_id.clone());
+            if is_new {
+                tokio::spawn(publish_server(
+                    inner.clone(),
+                    acp_proxy.clone(),
+                    server_id,
+                    publishing.clone(),
+                ));
+            }
+        }
+    }
+}
+
+async fn publish_server(
+    inner: Arc<ClusterInner>,
+    acp_proxy: AcpProxyRuntime,
+    server_id: String,
+    publishing: Arc<std::sync::Mutex<HashSet<String>>>,
+) {
+    let key = events_key(&server_id);
+    let Some(registered) = register_while_running(&inner, &acp_proxy, &server_id).await else {
+        publishing
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(&server_id);
+        return;
+    };
+    if !registered {
+        // Started outside the HTTP API while another replica ran the same
+        // id; that replica's stream stays authoritative.
+        tracing::warn!(server_id = %server_id, "cluster: server is registered to another replica; not publishing its events");
+    } else if let Ok(mut stream) = acp_proxy.sequenced_stream(&server_id, Some(0), true).await {
+        while let Some((sequence, payload)) = stream.next().await {
+            let published: Result<Option<String>, SandboxError> = async {
+                inner
+                    .connection()
+                    .await?
+                    .xadd_maxlen(
+                        &key,
+                        StreamMaxlen::Approx(EVENTS_MAXLEN),
+                        format!("{sequence}-0"),
+                        &[("payload", payload.to_string())],
+                    )
+                    .await
+                    .map_err(redis_error)
+            }
+            .await;
+            if let Err(err) = published {
+                tracing::warn!(server_id = %server_id, sequence, error = %err, "cluster: failed to publish envelope");
+            }
+        }
+    }
+
+    if registered {
+        inner.release(&server_id, &inner.replica_url).await;
+        let expired: Result<bool, SandboxError> = async {
+            inner
+                .connection()
+                .await?
+                .expire(&key, CLOSED_EVENTS_TTL_SECS)
+                .await
+                .map_err(redis_error)
+        }
+        .await;
+        if let Err(err) = expired {
+            tracing::warn!(server_id = %server_id, error = %err, "cluster: failed to expire event stream");
+        }
+    }
+    publishing
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .remove(&server_id);
+}
+
+/// Register `server_id`, retrying with backoff while Redis fails. Returns
+/// `None` if the server stopped first. Its envelopes are replayed from the
+/// start once registered, so none are lost while waiting.
+async fn register_while_running(
+    inner: &ClusterInner,
+    acp_proxy: &AcpProxyRuntime,
+    server_id: &str,
+) -> Option<bool> {
+    let mut backoff = REGISTER_RETRY_MIN;
+    loop {
+        match inner.register(server_id).await {
+            Ok(registered) => return Some(registered),
+            Err(err) => tracing::warn!(
+                server_id = %server_id,
+                error = %err,
+                retry_ms = backoff.as_millis() as u64,
+                "cluster: failed to register server"
+            ),
+        }
+        tokio::time::sleep(backoff).await;
+        if !acp_proxy.has_server(server_id).await {
+            return None;
+        }
+        backoff = (backoff * 2).min(REGISTER_RETRY_MAX);
+    }
+}
+
+fn is_hop_by_hop(name: &str) -> bool {
+    matches!(
+        name,
+        "connection" | "keep-alive" | "transfer-encoding" | "upgrade" | "te" | "trailer"
+    )
+}
+
+fn events_key(server_id: &str) -> String {
+    format!("{EVENTS_KEY_PREFIX}{server_id}")
+}
+
+/// `(sequence, payload)` pairs from an `XREAD` reply.
+fn stream_entries(reply: StreamReadReply) -> Vec<(u64, Value)> {
+    reply
+        .keys
+        .into_iter()
+        .flat_map(|stream| stream.ids)
+        .filter_map(|entry| {
+            let sequence = entry.id.split_once('-')?.0.parse().ok()?;
+            let payload = entry.get::<String>("payload")?;
+            Some((sequence, serde_json::from_str(&payload).ok()?))
+        })
+        .collect()
+}
+
+fn redis_error(err: RedisError) -> SandboxError {
+    SandboxError::StreamError {
+        message: format!("cluster state unavailable: {err}"),
+    }
+}
@@ -0,0 +1,133 @@
+//! Context compaction for long sessions.
+//!
+//! `POST /v1/acp/{server_id}/compact` runs one extra turn on a session. When
+//! the agent advertised a `compact` command through
+//! `available_commands_update`, as Claude Code and Codex do, the turn is the
+//! native `/compact`. Otherwise the agent is asked to summarize the session,
+//! and the summary is returned so the caller can seed a fresh session with
+//! it. Either way an `_adapter/compaction` notification records token
+//! estimates from before and after: a quarter of the characters of the
+//! session's buffered text since its last compaction, and of the
+//! compaction turn's own reply.
+
+use serde_json::Value;
+
+pub(crate) const SUMMARY_PROMPT: &str = "Summarize this conversation so far so that it can \
+replace the full history. Keep the goal, decisions made, files touched, open problems and next \
+steps. Reply with the summary only.";
+
+pub(crate) const COMPACTION_METHOD: &str = "_adapter/compaction";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompactionMode {
+    Native,
+    Summary,
+}
+
+impl CompactionMode {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Native => "native",
+            Self::Summary => "summary",
+        }
+    }
+
+    pub(crate) fn prompt(self) -> &'static str {
+        match self {
+            Self::Native => "/compact",
+            Self::Summary => SUMMARY_PROMPT,
+        }
+    }
+}
+
+/// Pick the mode for `session_id` from the server's buffered envelopes.
+pub(crate) fn mode_for(events: &[(u64, Value)], session_id: &str) -> CompactionMode {
+    let commands = events
+        .iter()
+        .rev()
+        .filter_map(|(_, payload)| session_update(payload, session_id))
+        .find(|update| {
+            update.get("sessionUpdate").and_then(Value::as_str) == Some("available_commands_update")
+        })
+        .and_then(|update| update.get("availableCommands"))
+        .and_then(Value::as_array);
+    let native = commands.is_some_and(|commands| {
+        commands
+            .iter()
+            .any(|command| command.get("name").and_then(Value::as_str) == Some("compact"))
+    });
+    if native {
+        CompactionMode::Native
+    } else {
+        CompactionMode::Summary
+    }
+}
+
+/// Rough token count of `session_id`'s text since its last compaction.
+pub(crate) fn estimate_session_tokens(events: &[(u64, Value)], session_id: &str) -> u64 {
+    let since = events
+        .iter()
+        .rposition(|(_, payload)| {
+            payload.get("method").and_then(Value::as_str) == Some(COMPACTION_METHOD)
+                && payload.pointer("/params/sessionId").and_then(Value::as_str) == Some(session_id)
+        })
+        .map_or(0, |index| index + 1);
+    estimate_tokens(&session_text(&events[since..], session_id))
+}
+
+/// Text of `session_id`'s message, thought and tool call updates.
+fn session_text(events: &[(u64, Value)], session_id: &str) -> String {
+    let mut text = String::new();
+    for update in events
+        .iter()
+        .filter_map(|(_, payload)| session_update(payload, session_id))
+    {
+        match update.get("sessionUpdate").and_then(Value::as_str) {
+            Some("agent_message_chunk" | "agent_thought_chunk" | "user_message_chunk") => {
+                if let Some(chunk) = update.pointer("/content/text").and_then(Value::as_str) {
+                    text.push_str(chunk);
+                }
+            }
+            Some("tool_call" | "tool_call_update") => {
+                for content in update
+                    .get("content")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                {
+                    if let Some(chunk) = content.pointer("/content/text").and_then(Value::as_str) {
+                        text.push_str(chunk);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Text the agent replied with in `events`, such as a summary.
+pub(crate) fn agent_reply(events: &[(u64, Value)], session_id: &str) -> String {
+    events
+        .iter()
+        .filter_map(|(_, payload)| session_update(payload, session_id))
+        .filter(|update| {
+            update.get("sessionUpdate").and_then(Value::as_str) == Some("agent_message_chunk")
+        })
+        .filter_map(|update| update.pointer("/content/text").and_then(Value::as_str))
+        .collect()
+}
+
+pub(crate) fn estimate_tokens(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(4)
+}
+
+pub(crate) fn session_update<'a>(payload: &'a Value, session_id: &str) -> Option<&'a Value> {
+    if payload.get("method").and_then(Value::as_str) != Some("session/update") {
+        return None;
+    }
+    if payload.pointer("/params/sessionId").and_then(Value::as_str) != Some(session_id) {
+        return None;
+    }
+    payload.pointer("/params/update")
+}
@@ -0,0 +1,47 @@
+//! Response compression, from `SANDBOX_AGENT_COMPRESSION`.
+//!
+//! Clients that send `Accept-Encoding: gzip` or `br` get compressed
+//! responses. By default (`events`) only `GET /v1/acp/{server_id}/events`
+//! is compressed: its pages of buffered envelopes are large and repetitive.
+//! `all` compresses every response and `off` none. SSE streams and bodies
+//! under 32 bytes are never compressed, so each event is delivered as soon
+//! as it is written.
+
+use tower_http::compression::CompressionLayer;
+
+const COMPRESSION_VAR: &str = "SANDBOX_AGENT_COMPRESSION";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Compression {
+    Off,
+    Events,
+    All,
+}
+
+impl Compression {
+    fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "off" | "0" | "false" => Some(Self::Off),
+            "" | "events" => Some(Self::Events),
+            "all" => Some(Self::All),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn from_env() -> Self {
+        let Ok(raw) = std::env::var(COMPRESSION_VAR) else {
+            return Self::Events;
+        };
+        Self::parse(&raw).unwrap_or_else(|| {
+            tracing::warn!(
+                value = %raw,
+                "ignoring {COMPRESSION_VAR}; expected off, events or all"
+            );
+            Self::Events
+        })
+    }
+
+    pub(crate) fn layer() -> CompressionLayer {
+        CompressionLayer::new().gzip(true).br(true)
+    }
+}
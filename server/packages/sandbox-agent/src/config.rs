@@ -0,0 +1,497 @@
+//! Server configuration file.
+//!
+//! `sandbox-agent server` reads `--config <path>`, else the file named by
+//! `SANDBOX_AGENT_CONFIG`, else `./sandbox-agent.toml` when it exists.
+//! Settings apply in increasing precedence: the file, environment variables,
+//! then CLI flags. [`ServerConfig::effective`] lays the `SANDBOX_AGENT_*`
+//! variable of each file setting over the file value once, when
+//! [`AppState`](crate::router::AppState) is built, and modules take their
+//! settings from that resolved config. The process environment is never
+//! written, so a variable always holds what the operator set.
+//!
+//! The file covers the settings below. Every other `SANDBOX_AGENT_*`
+//! variable is read from the environment only, including
+//! `SANDBOX_AGENT_COMPRESSION`, `SANDBOX_AGENT_NAMESPACE_TOKENS`,
+//! `SANDBOX_AGENT_NAMESPACE_ROOT`, `SANDBOX_AGENT_CLUSTER_REDIS_URL`,
+//! `SANDBOX_AGENT_CLAUDE_SETTINGS_DIR`, `SANDBOX_AGENT_IDEMPOTENCY_TTL_MS`,
+//! the `SANDBOX_AGENT_CDC_*` sinks, the spill and artifact directories and
+//! the logging switches.
+//!
+//! ```toml
+//! [auth]
+//! token = "..."
+//!
+//! [server]
+//! host = "0.0.0.0"
+//! port = 2468
+//! cors_allow_origins = ["http://localhost:5173"]
+//! cors_allow_methods = ["GET", "POST"]
+//! cors_allow_headers = ["authorization", "content-type"]
+//! cors_allow_credentials = true
+//! # Serve on a Unix socket, or over stdin/stdout, instead of TCP.
+//! unix_socket = "/run/sandbox-agent.sock"
+//! stdio = false
+//!
+//! [install]
+//! dir = "/opt/sandbox-agent/bin"
+//! preinstall = ["claude"]
+//! require_preinstall = true
+//!
+//! [limits]
+//! turn_timeout_ms = 600000
+//!
+//! [telemetry]
+//! enabled = false
+//!
+//! [models]
+//! fast = "claude:claude-3-5-haiku"
+//! smart = ["codex:o3", "codex:o4-mini"]
+//!
+//! [agents.codex]
+//! model = "smart"
+//! mode = "auto"
+//!
+//! [agents.claude.env]
+//! CLAUDE_CODE_MAX_OUTPUT_TOKENS = "8192"
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use sandbox_agent_agent_management::agents::AgentId;
+use toml_edit::{DocumentMut, Item, TableLike};
+
+use crate::model_routing::ModelTarget;
+use crate::session_defaults::SessionDefaults;
+
+pub(crate) const CONFIG_ENV: &str = "SANDBOX_AGENT_CONFIG";
+const DEFAULT_CONFIG_FILE: &str = "sandbox-agent.toml";
+/// Per-agent spawn environment as a JSON object of objects, keyed by agent.
+const AGENT_ENV_VAR: &str = "SANDBOX_AGENT_AGENT_ENV";
+/// Per-agent session defaults as a JSON object of `{ model, mode }`
+/// objects, keyed by agent.
+const AGENT_DEFAULTS_VAR: &str = "SANDBOX_AGENT_AGENT_DEFAULTS";
+/// Model aliases as a JSON object of `agent:model` arrays.
+const MODEL_ALIASES_VAR: &str = "SANDBOX_AGENT_MODEL_ALIASES";
+const PREINSTALL_VAR: &str = "SANDBOX_AGENT_PREINSTALL";
+const REQUIRE_PREINSTALL_VAR: &str = "SANDBOX_AGENT_REQUIRE_PREINSTALL";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuthSettings {
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerSettings {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub cors_allow_origins: Vec<String>,
+    /// Empty allows any method.
+    pub cors_allow_methods: Vec<String>,
+    /// Empty allows any header.
+    pub cors_allow_headers: Vec<String>,
+    pub cors_allow_credentials: Option<bool>,
+    /// Takes the place of `host` and `port` when set.
+    pub unix_socket: Option<PathBuf>,
+    /// Serve one HTTP/1.1 connection over stdin/stdout.
+    pub stdio: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstallSettings {
+    pub dir: Option<PathBuf>,
+    pub preinstall: Vec<String>,
+    pub require_preinstall: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LimitSettings {
+    pub acp_request_timeout_ms: Option<u64>,
+    pub turn_timeout_ms: Option<u64>,
+    pub turn_idle_timeout_ms: Option<u64>,
+    pub pending_request_timeout_ms: Option<u64>,
+    pub max_raw_bytes: Option<u64>,
+    pub max_body_bytes: Option<u64>,
+    pub max_prompt_bytes: Option<u64>,
+}
+
+impl LimitSettings {
+    /// Each limit with the variable its module reads.
+    fn vars_mut(&mut self) -> [(&'static str, &'static str, &mut Option<u64>); 7] {
+        [
+            (
+                "acp_request_timeout_ms",
+                "SANDBOX_AGENT_ACP_REQUEST_TIMEOUT_MS",
+                &mut self.acp_request_timeout_ms,
+            ),
+            (
+                "turn_timeout_ms",
+                "SANDBOX_AGENT_TURN_TIMEOUT_MS",
+                &mut self.turn_timeout_ms,
+            ),
+            (
+                "turn_idle_timeout_ms",
+                "SANDBOX_AGENT_TURN_IDLE_TIMEOUT_MS",
+                &mut self.turn_idle_timeout_ms,
+            ),
+            (
+                "pending_request_timeout_ms",
+                "SANDBOX_AGENT_PENDING_REQUEST_TIMEOUT_MS",
+                &mut self.pending_request_timeout_ms,
+            ),
+            (
+                "max_raw_bytes",
+                "SANDBOX_AGENT_MAX_RAW_BYTES",
+                &mut self.max_raw_bytes,
+            ),
+            (
+                "max_body_bytes",
+                "SANDBOX_AGENT_MAX_BODY_BYTES",
+                &mut self.max_body_bytes,
+            ),
+            (
+                "max_prompt_bytes",
+                "SANDBOX_AGENT_MAX_PROMPT_BYTES",
+                &mut self.max_prompt_bytes,
+            ),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TelemetrySettings {
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AgentSettings {
+    /// Spawn environment for the agent's processes. `_meta` env on a request
+    /// wins over these.
+    pub env: BTreeMap<String, String>,
+    /// Model new sessions switch to unless `session/new` picks one.
+    pub model: Option<String>,
+    /// ACP mode new sessions switch to unless `session/new` picks one.
+    pub mode: Option<String>,
+}
+
+impl AgentSettings {
+    fn session_defaults(&self) -> SessionDefaults {
+        SessionDefaults {
+            model: self.model.clone(),
+            mode: self.mode.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerConfig {
+    /// File the settings were read from, if any.
+    pub source: Option<PathBuf>,
+    pub auth: AuthSettings,
+    pub server: ServerSettings,
+    pub install: InstallSettings,
+    pub limits: LimitSettings,
+    pub telemetry: TelemetrySettings,
+    /// Model aliases: each alias's `agent:model` targets, in fallback
+    /// order.
+    pub models: BTreeMap<String, Vec<String>>,
+    /// Keyed by agent id.
+    pub agents: BTreeMap<String, AgentSettings>,
+}
+
+impl ServerConfig {
+    /// Read `explicit`, else `SANDBOX_AGENT_CONFIG`, else
+    /// `./sandbox-agent.toml`. Only the last may be missing.
+    pub fn load(explicit: Option<&Path>) -> Result<Self, String> {
+        let (path, required) = match explicit {
+            Some(path) => (path.to_path_buf(), true),
+            None => match std::env::var_os(CONFIG_ENV) {
+                Some(path) => (PathBuf::from(path), true),
+                None => (PathBuf::from(DEFAULT_CONFIG_FILE), false),
+            },
+        };
+        let raw = match std::fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(err) if !required && err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self::default());
+            }
+            Err(err) => return Err(format!("failed to read {}: {err}", path.display())),
+        };
+        let mut config =
+            Self::parse(&raw).map_err(|err| format!("invalid config {}: {err}", path.display()))?;
+        config.source = Some(path);
+        Ok(config)
+    }
+
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let document = raw.parse::<DocumentMut>().map_err(|err| err.to_string())?;
+        let mut config = Self::default();
+        for (section, item) in document.iter() {
+            let table = table(section, item)?;
+            for (key, value) in table.iter() {
+                let path = format!("{section}.{key}");
+                match (section, key) {
+                    ("auth", "token") => config.auth.token = Some(string(&path, value)?),
+                    ("server", "host") => config.server.host = Some(string(&path, value)?),
+                    ("server", "port") => {
+                        let port = integer(&path, value)?;
+                        config.server.port = Some(
+                            u16::try_from(port).map_err(|_| format!("`{path}` is not a port"))?,
+                        );
+                    }
+                    ("server", "cors_allow_origins") => {
+                        config.server.cors_allow_origins = strings(&path, value)?
+                    }
+                    ("server", "cors_allow_methods") => {
+                        config.server.cors_allow_methods = strings(&path, value)?
+                    }
+                    ("server", "cors_allow_headers") => {
+                        config.server.cors_allow_headers = strings(&path, value)?
+                    }
+                    ("server", "cors_allow_credentials") => {
+                        config.server.cors_allow_credentials = Some(boolean(&path, value)?)
+                    }
+                    ("server", "unix_socket") => {
+                        config.server.unix_socket = Some(PathBuf::from(string(&path, value)?))
+                    }
+                    ("server", "stdio") => config.server.stdio = Some(boolean(&path, value)?),
+                    ("install", "dir") => {
+                        config.install.dir = Some(PathBuf::from(string(&path, value)?))
+                    }
+                    ("install", "preinstall") => config.install.preinstall = strings(&path, value)?,
+                    ("install", "require_preinstall") => {
+                        config.install.require_preinstall = Some(boolean(&path, value)?)
+                    }
+                    ("limits", _) => {
+                        let slot = config
+                            .limits
+                            .vars_mut()
+                            .into_iter()
+                            .find(|(name, _, _)| *name == key)
+                            .map(|(_, _, slot)| slot)
+                            .ok_or_else(|| format!("unknown setting `{path}`"))?;
+                        *slot = Some(integer(&path, value)?);
+                    }
+                    ("telemetry", "enabled") => {
+                        config.telemetry.enabled = Some(boolean(&path, value)?)
+                    }
+                    ("models", alias) => {
+                        let targets = match value.as_str() {
+                            Some(target) => vec![target.to_string()],
+                            None => strings(&path, value).map_err(|_| {
+                                format!("`{path}` must be a string or an array of strings")
+                            })?,
+                        };
+                        if targets.is_empty() {
+                            return Err(format!("`{path}` needs at least one model"));
+                        }
+                        for target in &targets {
+                            ModelTarget::parse(target).map_err(|err| format!("`{path}`: {err}"))?;
+                        }
+                        config.models.insert(alias.to_string(), targets);
+                    }
+                    ("agents", agent_name) => {
+                        if AgentId::parse(agent_name).is_none() {
+                            return Err(format!("unknown agent `{path}`"));
+                        }
+                        config
+                            .agents
+                            .insert(agent_name.to_string(), agent_settings(&path, value)?);
+                    }
+                    ("auth" | "server" | "install" | "telemetry", _) => {
+                        return Err(format!("unknown setting `{path}`"));
+                    }
+                    _ => return Err(format!("unknown section `{section}`")),
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// `self` with each setting replaced by its `SANDBOX_AGENT_*` variable
+    /// where the environment sets one. This is the config the server runs
+    /// with and reports from `GET /v1/config`.
+    pub fn effective(&self) -> Self {
+        let mut config = self.clone();
+        for (_, var, slot) in config.limits.vars_mut() {
+            if let Some(value) = std::env::var(var)
+                .ok()
+                .and_then(|raw| raw.trim().parse::<u64>().ok())
+            {
+                *slot = Some(value);
+            }
+        }
+        if let Ok(raw) = std::env::var(REQUIRE_PREINSTALL_VAR) {
+            let trimmed = raw.trim();
+            config.install.require_preinstall = Some(
+                trimmed == "1"
+                    || trimmed.eq_ignore_ascii_case("true")
+                    || trimmed.eq_ignore_ascii_case("yes"),
+            );
+        }
+        if let Ok(raw) = std::env::var(PREINSTALL_VAR) {
+            config.install.preinstall = raw
+                .split(',')
+                .map(str::trim)
+                .filter(|spec| !spec.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+        if let Ok(raw) = std::env::var(MODEL_ALIASES_VAR) {
+            config.models = serde_json::from_str(&raw).unwrap_or_else(|err| {
+                tracing::warn!(error = %err, "ignoring {MODEL_ALIASES_VAR}; expected a JSON object of string arrays");
+                BTreeMap::new()
+            });
+        }
+        if std::env::var_os(AGENT_ENV_VAR).is_some() {
+            for settings in config.agents.values_mut() {
+                settings.env.clear();
+            }
+            for (agent, env) in agent_env_from_env() {
+                config
+                    .agents
+                    .entry(agent.as_str().to_string())
+                    .or_default()
+                    .env = env.into_iter().collect();
+            }
+        }
+        if std::env::var_os(AGENT_DEFAULTS_VAR).is_some() {
+            for settings in config.agents.values_mut() {
+                settings.model = None;
+                settings.mode = None;
+            }
+            for (agent, defaults) in agent_defaults_from_env() {
+                let settings = config.agents.entry(agent.as_str().to_string()).or_default();
+                settings.model = defaults.model;
+                settings.mode = defaults.mode;
+            }
+        }
+        config
+            .agents
+            .retain(|_, settings| *settings != AgentSettings::default());
+        config
+    }
+
+    /// Spawn environment for each agent that has one.
+    pub(crate) fn agent_env(&self) -> HashMap<AgentId, HashMap<String, String>> {
+        self.agents
+            .iter()
+            .filter(|(_, settings)| !settings.env.is_empty())
+            .filter_map(|(agent, settings)| {
+                let env = settings.env.clone().into_iter().collect();
+                AgentId::parse(agent).map(|agent| (agent, env))
+            })
+            .collect()
+    }
+
+    /// Session defaults for each agent that has any.
+    pub(crate) fn session_defaults(&self) -> HashMap<AgentId, SessionDefaults> {
+        self.agents
+            .iter()
+            .map(|(agent, settings)| (agent, settings.session_defaults()))
+            .filter(|(_, defaults)| !defaults.is_empty())
+            .filter_map(|(agent, defaults)| AgentId::parse(agent).map(|agent| (agent, defaults)))
+            .collect()
+    }
+}
+
+/// Per-agent spawn environment from `SANDBOX_AGENT_AGENT_ENV`.
+fn agent_env_from_env() -> HashMap<AgentId, HashMap<String, String>> {
+    let Ok(raw) = std::env::var(AGENT_ENV_VAR) else {
+        return HashMap::new();
+    };
+    let parsed = match serde_json::from_str::<BTreeMap<String, HashMap<String, String>>>(&raw) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            tracing::warn!(error = %err, "ignoring {AGENT_ENV_VAR}; expected a JSON object of string maps");
+            return HashMap::new();
+        }
+    };
+    parsed
+        .into_iter()
+        .filter_map(|(agent, env)| match AgentId::parse(&agent) {
+            Some(agent) => Some((agent, env)),
+            None => {
+                tracing::warn!(agent = %agent, "ignoring {AGENT_ENV_VAR} entry for unknown agent");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Per-agent session defaults from `SANDBOX_AGENT_AGENT_DEFAULTS`.
+fn agent_defaults_from_env() -> HashMap<AgentId, SessionDefaults> {
+    let Ok(raw) = std::env::var(AGENT_DEFAULTS_VAR) else {
+        return HashMap::new();
+    };
+    let parsed = match serde_json::from_str::<BTreeMap<String, SessionDefaults>>(&raw) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            tracing::warn!(error = %err, "ignoring {AGENT_DEFAULTS_VAR}; expected a JSON object of {{ model, mode }} objects");
+            return HashMap::new();
+        }
+    };
+    parsed
+        .into_iter()
+        .filter_map(|(agent, defaults)| match AgentId::parse(&agent) {
+            Some(agent) => Some((agent, defaults)),
+            None => {
+                tracing::warn!(agent = %agent, "ignoring {AGENT_DEFAULTS_VAR} entry for unknown agent");
+                None
+            }
+        })
+        .collect()
+}
+
+fn table<'a>(path: &str, item: &'a Item) -> Result<&'a dyn TableLike, String> {
+    item.as_table_like()
+        .ok_or_else(|| format!("`{path}` must be a table"))
+}
+
+fn string(path: &str, item: &Item) -> Result<String, String> {
+    item.as_str()
+        .map(str::to_string)
+        .ok_or_else(|| format!("`{path}` must be a string"))
+}
+
+fn integer(path: &str, item: &Item) -> Result<u64, String> {
+    item.as_integer()
+        .and_then(|value| u64::try_from(value).ok())
+        .ok_or_else(|| format!("`{path}` must be a non-negative integer"))
+}
+
+fn boolean(path: &str, item: &Item) -> Result<bool, String> {
+    item.as_bool()
+        .ok_or_else(|| format!("`{path}` must be true or false"))
+}
+
+fn strings(path: &str, item: &Item) -> Result<Vec<String>, String> {
+    item.as_array()
+        .and_then(|array| {
+            array
+                .iter()
+                .map(|value| value.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+        })
+        .ok_or_else(|| format!("`{path}` must be an array of strings"))
+}
+
+fn agent_settings(path: &str, item: &Item) -> Result<AgentSettings, String> {
+    let mut settings = AgentSettings::default();
+    for (key, value) in table(path, item)?.iter() {
+        let path = format!("{path}.{key}");
+        match key {
+            "env" => {
+                for (name, value) in table(&path, value)?.iter() {
+                    settings
+                        .env
+                        .insert(name.to_string(), string(&format!("{path}.{name}"), value)?);
+                }
+            }
+            "model" => settings.model = Some(string(&path, value)?),
+            "mode" => settings.mode = Some(string(&path, value)?),
+            _ => return Err(format!("unknown setting `{path}`")),
+        }
+    }
+    Ok(settings)
+}
@@ -437,8 +437,8 @@ fn stop_process(pid: u32, host: &str, port: u16, pid_path: &Path) -> Result<(),
         libc::kill(pid as i32, libc::SIGTERM);
     }
 
-    // Wait up to 5 seconds for graceful exit
-    for _ in 0..50 {
+    // Wait up to 10 seconds for graceful exit
+    for _ in 0..100 {
         std::thread::sleep(Duration::from_millis(100));
         if !is_process_running(pid) {
             let _ = remove_pid(pid_path);
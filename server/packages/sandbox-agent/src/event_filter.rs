@@ -0,0 +1,69 @@
+//! `types=` and `exclude=` filters for a server's event stream.
+//!
+//! An envelope's kind is the `sessionUpdate` of a `session/update`
+//! notification (such as `agent_message_chunk` or `tool_call_update`),
+//! else its method (such as `session/request_permission`), else `response`.
+//! A filter entry matches either the kind or the method, so
+//! `types=session/update` keeps every update while
+//! `exclude=tool_call_update` drops only tool progress.
+
+use std::collections::HashSet;
+
+use serde_json::Value;
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EventFilter {
+    /// Kinds to keep; everything when unset.
+    types: Option<HashSet<String>>,
+    /// Kinds to drop, checked after `types`.
+    exclude: HashSet<String>,
+}
+
+impl EventFilter {
+    /// Build from comma-separated `types` and `exclude` query values.
+    pub(crate) fn parse(types: Option<&str>, exclude: Option<&str>) -> Self {
+        Self {
+            types: types.map(split_kinds),
+            exclude: exclude.map(split_kinds).unwrap_or_default(),
+        }
+    }
+
+    pub(crate) fn from_lists(types: Vec<String>, exclude: Vec<String>) -> Self {
+        Self {
+            types: (!types.is_empty()).then(|| types.into_iter().collect()),
+            exclude: exclude.into_iter().collect(),
+        }
+    }
+
+    pub(crate) fn matches(&self, payload: &Value) -> bool {
+        let kind = event_kind(payload);
+        let method = payload.get("method").and_then(Value::as_str);
+        let listed = |kinds: &HashSet<String>| {
+            kinds.contains(kind) || method.is_some_and(|method| kinds.contains(method))
+        };
+        self.types.as_ref().is_none_or(listed) && !listed(&self.exclude)
+    }
+}
+
+/// The kind `types=` and `exclude=` match an envelope by.
+pub(crate) fn event_kind(payload: &Value) -> &str {
+    if let Some(update) = payload
+        .pointer("/params/update/sessionUpdate")
+        .and_then(Value::as_str)
+    {
+        return update;
+    }
+    payload
+        .get("method")
+        .and_then(Value::as_str)
+        .unwrap_or("response")
+}
+
+fn split_kinds(value: &str) -> HashSet<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|kind| !kind.is_empty())
+        .map(ToOwned::to_owned)
+        .collect()
+}
@@ -0,0 +1,234 @@
+//! gRPC mirror of the `/v1/acp` routes.
+//!
+//! The `sandbox_agent.v1.SandboxAgent` service in
+//! `proto/sandbox_agent/v1/sandbox_agent.proto` is served on the same
+//! listener as HTTP (gRPC runs over HTTP/2 cleartext) and drives the same
+//! [`AcpProxyRuntime`], so sessions created over one surface are visible on
+//! the other. Token auth applies as it does to `/v1`.
+
+use std::sync::Arc;
+
+use futures::StreamExt;
+use sandbox_agent_agent_management::agents::AgentId;
+use sandbox_agent_error::SandboxError;
+use serde_json::{json, Value};
+use tonic::server::NamedService;
+use tonic::{Request, Response, Status};
+
+use crate::acp_proxy_runtime::AcpProxyRuntime;
+use crate::event_filter::EventFilter;
+use crate::router::AppState;
+use crate::subagents;
+use crate::tasks::new_task_id;
+
+pub mod proto {
+    tonic::include_proto!("sandbox_agent.v1");
+}
+
+use proto::sandbox_agent_server::{SandboxAgent, SandboxAgentServer};
+use proto::{
+    CreateSessionRequest, CreateSessionResponse, Event, ReplyPermissionRequest,
+    ReplyQuestionRequest, ReplyResponse, SendMessageRequest, SendMessageResponse,
+    StreamEventsRequest,
+};
+
+/// Routes for the gRPC service, ready to merge into the HTTP router.
+pub(crate) fn router(state: Arc<AppState>) -> axum::Router {
+    let service = SandboxAgentServer::new(GrpcService { state });
+    axum::Router::new().route_service(
+        &format!("/{}/*method", SandboxAgentServer::<GrpcService>::NAME),
+        service,
+    )
+}
+
+struct GrpcService {
+    state: Arc<AppState>,
+}
+
+#[tonic::async_trait]
+impl SandboxAgent for GrpcService {
+    async fn create_session(
+        &self,
+        request: Request<CreateSessionRequest>,
+    ) -> Result<Response<CreateSessionResponse>, Status> {
+        let request = request.into_inner();
+        let agent = AgentId::parse(&request.agent).ok_or_else(|| {
+            status_from_sandbox_error(SandboxError::UnsupportedAgent {
+                agent: request.agent.clone(),
+            })
+        })?;
+        let acp_proxy = self.state.acp_proxy();
+        let initialize = json!({
+            "jsonrpc": "2.0",
+            "id": request_id("initialize"),
+            "method": "initialize",
+            "params": { "protocolVersion": 1, "clientCapabilities": {} }
+        });
+        acp_proxy
+            .post_expect_result(&request.server_id, Some(agent), initialize)
+            .await
+            .map_err(status_from_sandbox_error)?;
+
+        let session_new = json!({
+            "jsonrpc": "2.0",
+            "id": request_id("session-new"),
+            "method": "session/new",
+            "params": { "cwd": request.cwd, "mcpServers": [] }
+        });
+        let session = acp_proxy
+            .post_expect_result(&request.server_id, None, session_new)
+            .await
+            .map_err(status_from_sandbox_error)?;
+        let session_id = session
+            .get("sessionId")
+            .and_then(Value::as_str)
+            .ok_or_else(|| Status::internal("agent did not return a sessionId for session/new"))?
+            .to_string();
+        Ok(Response::new(CreateSessionResponse { session_id }))
+    }
+
+    async fn send_message(
+        &self,
+        request: Request<SendMessageRequest>,
+    ) -> Result<Response<SendMessageResponse>, Status> {
+        let request = request.into_inner();
+        let session_prompt = json!({
+            "jsonrpc": "2.0",
+            "id": request_id("prompt"),
+            "method": "session/prompt",
+            "params": {
+                "sessionId": request.session_id,
+                "prompt": [{ "type": "text", "text": request.text }]
+            }
+        });
+        let result = self
+            .state
+            .acp_proxy()
+            .post_expect_result(&request.server_id, None, session_prompt)
+            .await
+            .map_err(status_from_sandbox_error)?;
+        let stop_reason = result
+            .get("stopReason")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        Ok(Response::new(SendMessageResponse { stop_reason }))
+    }
+
+    type StreamEventsStream = futures::stream::BoxStream<'static, Result<Event, Status>>;
+
+    // tonic fixes the item type; `Status` is large but never constructed here.
+    #[allow(clippy::result_large_err)]
+    async fn stream_events(
+        &self,
+        request: Request<StreamEventsRequest>,
+    ) -> Result<Response<Self::StreamEventsStream>, Status> {
+        let request = request.into_inner();
+        let filter = EventFilter::from_lists(request.types, request.exclude);
+        let envelopes = self
+            .state
+            .acp_proxy()
+            .sequenced_stream(&request.server_id, request.offset, request.include_logs)
+            .await
+            .map_err(status_from_sandbox_error)?;
+        let events = envelopes
+            .filter(move |(_, payload)| std::future::ready(filter.matches(payload)))
+            .map(|(sequence, payload)| {
+                Ok(Event {
+                    sequence,
+                    parent_id: subagents::parent_id(&payload),
+                    payload_json: payload.to_string(),
+                })
+            });
+        Ok(Response::new(Box::pin(events)))
+    }
+
+    async fn reply_permission(
+        &self,
+        request: Request<ReplyPermissionRequest>,
+    ) -> Result<Response<ReplyResponse>, Status> {
+        let request = request.into_inner();
+        let outcome = if request.option_id.is_empty() {
+            json!({ "outcome": "cancelled" })
+        } else {
+            json!({ "outcome": "selected", "optionId": request.option_id })
+        };
+        reply(
+            &self.state.acp_proxy(),
+            &request.server_id,
+            &request.request_id,
+            json!({ "outcome": outcome }),
+        )
+        .await?;
+        Ok(Response::new(ReplyResponse {}))
+    }
+
+    async fn reply_question(
+        &self,
+        request: Request<ReplyQuestionRequest>,
+    ) -> Result<Response<ReplyResponse>, Status> {
+        let request = request.into_inner();
+        let result = serde_json::from_str::<Value>(&request.result_json)
+            .map_err(|err| Status::invalid_argument(format!("invalid result_json: {err}")))?;
+        reply(
+            &self.state.acp_proxy(),
+            &request.server_id,
+            &request.request_id,
+            result,
+        )
+        .await?;
+        Ok(Response::new(ReplyResponse {}))
+    }
+}
+
+/// Answer the pending agent request whose JSON-RPC id is `request_id`,
+/// reusing the id exactly as the agent sent it.
+async fn reply(
+    acp_proxy: &AcpProxyRuntime,
+    server_id: &str,
+    request_id: &str,
+    result: Value,
+) -> Result<(), Status> {
+    let pending = acp_proxy
+        .pending_requests(server_id, None)
+        .await
+        .map_err(status_from_sandbox_error)?
+        .into_iter()
+        .find(|pending| match &pending.id {
+            Value::String(id) => id == request_id,
+            id => serde_json::from_str::<Value>(request_id).is_ok_and(|parsed| parsed == *id),
+        })
+        .ok_or_else(|| Status::not_found(format!("no pending request with id {request_id}")))?;
+    let response = json!({ "jsonrpc": "2.0", "id": pending.id, "result": result });
+    acp_proxy
+        .post(server_id, None, response)
+        .await
+        .map(|_| ())
+        .map_err(status_from_sandbox_error)
+}
+
+fn request_id(kind: &str) -> String {
+    format!("sandboxagent-grpc-{kind}-{}", new_task_id())
+}
+
+/// The gRPC code for the HTTP status the same error gets on `/v1`.
+fn status_from_sandbox_error(error: SandboxError) -> Status {
+    let message = error.to_string();
+    match error {
+        SandboxError::InvalidRequest { .. } => return Status::invalid_argument(message),
+        SandboxError::Timeout { .. } => return Status::deadline_exceeded(message),
+        _ => {}
+    }
+    match error.to_problem_details().status {
+        400 | 413 | 415 | 422 => Status::invalid_argument(message),
+        401 => Status::unauthenticated(message),
+        403 => Status::permission_denied(message),
+        404 => Status::not_found(message),
+        409 => Status::failed_precondition(message),
+        429 => Status::resource_exhausted(message),
+        501 => Status::unimplemented(message),
+        503 => Status::unavailable(message),
+        504 => Status::deadline_exceeded(message),
+        _ => Status::internal(message),
+    }
+}
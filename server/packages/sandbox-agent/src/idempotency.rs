@@ -0,0 +1,172 @@
+//! Replay of retried `POST /v1/acp/{server_id}` requests.
+//!
+//! A POST carrying an `Idempotency-Key` header runs once per server and
+//! key. A retry with the same key and body gets the first response back,
+//! waiting for it if the first request is still running, so a controller
+//! that lost its connection mid-prompt can retry without running the prompt
+//! twice. Failed requests are forgotten, so their retries run again.
+//! Outcomes are kept for `SANDBOX_AGENT_IDEMPOTENCY_TTL_MS` (default ten
+//! minutes) after they finish.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use sandbox_agent_error::SandboxError;
+use sha2::{Digest, Sha256};
+use tokio::sync::watch;
+
+const DEFAULT_TTL: Duration = Duration::from_secs(10 * 60);
+const MAX_KEY_LEN: usize = 255;
+
+type EntryKey = (String, String);
+
+#[derive(Debug)]
+pub(crate) struct IdempotencyCache<T> {
+    ttl: Duration,
+    entries: Arc<Mutex<HashMap<EntryKey, Entry<T>>>>,
+}
+
+#[derive(Debug)]
+struct Entry<T> {
+    fingerprint: [u8; 32],
+    /// When the outcome was stored; `None` while the request runs.
+    finished_at: Option<Instant>,
+    outcome: watch::Receiver<Option<T>>,
+}
+
+/// What a request with an idempotency key should do.
+pub(crate) enum Claim<T> {
+    /// First request with this key: run it and record the outcome.
+    Run(IdempotencyGuard<T>),
+    /// A request with this key already ran or is running.
+    Replay(watch::Receiver<Option<T>>),
+}
+
+/// Held while the first request for a key runs. Dropping it without
+/// [`IdempotencyGuard::complete`] forgets the key so a retry runs again.
+pub(crate) struct IdempotencyGuard<T> {
+    entries: Arc<Mutex<HashMap<EntryKey, Entry<T>>>>,
+    key: EntryKey,
+    sender: watch::Sender<Option<T>>,
+    completed: bool,
+}
+
+impl<T: Clone> IdempotencyCache<T> {
+    pub(crate) fn from_env() -> Self {
+        let ttl = std::env::var("SANDBOX_AGENT_IDEMPOTENCY_TTL_MS")
+            .ok()
+            .and_then(|raw| raw.trim().parse::<u64>().ok())
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_TTL);
+        Self {
+            ttl,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Claim `key` for a request on `server_id` whose body hashes to
+    /// `fingerprint`. Reusing a key with a different body is an error.
+    pub(crate) fn claim(
+        &self,
+        server_id: &str,
+        key: &str,
+        fingerprint: [u8; 32],
+    ) -> Result<Claim<T>, SandboxError> {
+        if key.is_empty() || key.len() > MAX_KEY_LEN {
+            return Err(SandboxError::InvalidRequest {
+                message: format!("Idempotency-Key must be 1 to {MAX_KEY_LEN} characters"),
+            });
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        entries.retain(|_, entry| {
+            !matches!(entry.finished_at, Some(finished) if now.duration_since(finished) >= self.ttl)
+        });
+
+        let entry_key = (server_id.to_string(), key.to_string());
+        if let Some(entry) = entries.get(&entry_key) {
+            if entry.fingerprint != fingerprint {
+                return Err(SandboxError::Conflict {
+                    message: format!(
+                        "Idempotency-Key '{key}' was already used with a different request"
+                    ),
+                });
+            }
+            return Ok(Claim::Replay(entry.outcome.clone()));
+        }
+
+        let (sender, outcome) = watch::channel(None);
+        entries.insert(
+            entry_key.clone(),
+            Entry {
+                fingerprint,
+                finished_at: None,
+                outcome,
+            },
+        );
+        Ok(Claim::Run(IdempotencyGuard {
+            entries: self.entries.clone(),
+            key: entry_key,
+            sender,
+            completed: false,
+        }))
+    }
+
+    /// Forget every key of `server_id`.
+    pub(crate) fn remove_server(&self, server_id: &str) {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|(server, _), _| server != server_id);
+    }
+}
+
+impl<T: Clone> IdempotencyGuard<T> {
+    /// Store `outcome` and hand it to every waiting retry.
+    pub(crate) fn complete(mut self, outcome: &T) {
+        self.completed = true;
+        if let Some(entry) = self
+            .entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_mut(&self.key)
+        {
+            entry.finished_at = Some(Instant::now());
+        }
+        let _ = self.sender.send(Some(outcome.clone()));
+    }
+}
+
+impl<T> Drop for IdempotencyGuard<T> {
+    fn drop(&mut self) {
+        // The entry goes before the sender, so waiters woken by the closed
+        // channel find the key free and run the request themselves.
+        if !self.completed {
+            self.entries
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .remove(&self.key);
+        }
+    }
+}
+
+/// Wait for the outcome of the request that claimed the key first. `None`
+/// means that request failed and the key is free again.
+pub(crate) async fn wait_for<T: Clone>(mut outcome: watch::Receiver<Option<T>>) -> Option<T> {
+    outcome
+        .wait_for(Option::is_some)
+        .await
+        .ok()
+        .and_then(|outcome| outcome.clone())
+}
+
+/// Hash of everything that makes two requests the same request.
+pub(crate) fn fingerprint(parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
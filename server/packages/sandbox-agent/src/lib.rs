@@ -1,9 +1,48 @@
 //! Sandbox agent core utilities.
 
 mod acp_proxy_runtime;
+mod agent_compat;
+mod agent_exit;
+mod agent_failover;
+mod anthropic_compat;
+mod attachments;
+mod cdc;
+mod cdc_bus;
+mod claude_settings;
 pub mod cli;
+mod cluster;
+mod compaction;
+mod compression;
+pub mod config;
 pub mod daemon;
+mod event_filter;
+pub mod grpc;
+mod idempotency;
+pub mod log_buffer;
+mod message_queue;
+mod model_routing;
+mod namespaces;
+mod permission_mode;
+mod plans;
+mod prompt_policy;
+mod request_limits;
+mod retry;
 pub mod router;
+mod schedules;
+mod secrets;
 pub mod server_logs;
+mod session_defaults;
+mod session_end;
+mod session_home;
+mod stream_mux;
+mod subagents;
+mod tasks;
 pub mod telemetry;
+mod transcript;
+pub mod transport;
+mod turn_limits;
+mod turn_logs;
+mod turn_result;
 pub mod ui;
+mod unattended;
+mod warm_pool;
@@ -0,0 +1,269 @@
+//! In-memory ring of recent server log events.
+//!
+//! [`layer`] is installed next to the logfmt writer, so every event that
+//! passes the `RUST_LOG` filter is also kept here as a structured entry.
+//! Fields from enclosing spans are merged in, so a request span's `uri`
+//! shows up on each line it logs, minus its query string, which can carry
+//! credentials. `GET /v1/logs` reads the ring and `GET /v1/logs/stream`
+//! tails it. The newest `SANDBOX_AGENT_LOG_BUFFER_LINES` entries (default
+//! 2000) are kept.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{Map, Value};
+use tokio::sync::broadcast;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+const DEFAULT_CAPACITY: usize = 2000;
+const TAIL_CHANNEL_SIZE: usize = 1024;
+/// Fields that tie a log line to an ACP server or session.
+const SESSION_FIELDS: [&str; 2] = ["server_id", "session_id"];
+
+static BUFFER: OnceLock<LogBuffer> = OnceLock::new();
+
+#[derive(Debug, Clone)]
+pub(crate) struct LogEntry {
+    pub(crate) sequence: u64,
+    pub(crate) timestamp_ms: i64,
+    pub(crate) level: Level,
+    pub(crate) target: String,
+    pub(crate) message: String,
+    pub(crate) fields: Map<String, Value>,
+}
+
+/// Which entries a reader wants.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LogFilter {
+    /// Matches `server_id` or `session_id` on the event or an enclosing span.
+    pub(crate) session: Option<String>,
+    /// Least severe level to include.
+    pub(crate) level: Option<Level>,
+    /// Only entries after this sequence number.
+    pub(crate) since: Option<u64>,
+}
+
+impl LogFilter {
+    pub(crate) fn matches(&self, entry: &LogEntry) -> bool {
+        if self.since.is_some_and(|since| entry.sequence <= since) {
+            return false;
+        }
+        // `Level` orders ERROR as the smallest, so "at least as severe" is <=.
+        if self.level.is_some_and(|level| entry.level > level) {
+            return false;
+        }
+        match &self.session {
+            Some(session) => SESSION_FIELDS.iter().any(|name| {
+                entry.fields.get(*name).and_then(Value::as_str) == Some(session.as_str())
+            }),
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct LogBuffer {
+    capacity: usize,
+    state: Mutex<BufferState>,
+    tail: broadcast::Sender<LogEntry>,
+}
+
+#[derive(Debug, Default)]
+struct BufferState {
+    next_sequence: u64,
+    entries: VecDeque<LogEntry>,
+}
+
+impl LogBuffer {
+    fn from_env() -> Self {
+        let capacity = std::env::var("SANDBOX_AGENT_LOG_BUFFER_LINES")
+            .ok()
+            .and_then(|raw| raw.trim().parse::<usize>().ok())
+            .unwrap_or(DEFAULT_CAPACITY);
+        Self {
+            capacity,
+            state: Mutex::new(BufferState::default()),
+            tail: broadcast::channel(TAIL_CHANNEL_SIZE).0,
+        }
+    }
+
+    fn push(&self, mut entry: LogEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        {
+            let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+            state.next_sequence += 1;
+            entry.sequence = state.next_sequence;
+            if state.entries.len() == self.capacity {
+                state.entries.pop_front();
+            }
+            state.entries.push_back(entry.clone());
+        }
+        let _ = self.tail.send(entry);
+    }
+
+    /// Up to `limit` of the oldest buffered entries that match `filter`.
+    pub(crate) fn query(&self, filter: &LogFilter, limit: usize) -> Vec<LogEntry> {
+        let state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        state
+            .entries
+            .iter()
+            .filter(|entry| filter.matches(entry))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Matching buffered entries, and a receiver for entries logged after
+    /// them.
+    pub(crate) fn tail(
+        &self,
+        filter: &LogFilter,
+    ) -> (Vec<LogEntry>, broadcast::Receiver<LogEntry>) {
+        let state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        let receiver = self.tail.subscribe();
+        let replay = state
+            .entries
+            .iter()
+            .filter(|entry| filter.matches(entry))
+            .cloned()
+            .collect();
+        (replay, receiver)
+    }
+}
+
+pub(crate) fn global() -> &'static LogBuffer {
+    BUFFER.get_or_init(LogBuffer::from_env)
+}
+
+/// Layer that copies every event into the process-wide ring.
+pub fn layer() -> LogBufferLayer {
+    LogBufferLayer { _private: () }
+}
+
+pub struct LogBufferLayer {
+    _private: (),
+}
+
+/// Fields recorded on a span, kept in its extensions.
+struct SpanFields(Map<String, Value>);
+
+impl<S> Layer<S> for LogBufferLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        span.extensions_mut().insert(SpanFields(visitor.fields));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(SpanFields(fields)) = extensions.get_mut::<SpanFields>() {
+            let mut visitor = FieldVisitor {
+                fields: std::mem::take(fields),
+                message: None,
+            };
+            values.record(&mut visitor);
+            *fields = visitor.fields;
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = Map::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(SpanFields(span_fields)) = span.extensions().get::<SpanFields>() {
+                    fields.extend(span_fields.clone());
+                }
+            }
+        }
+        let mut visitor = FieldVisitor {
+            fields,
+            message: None,
+        };
+        event.record(&mut visitor);
+
+        let metadata = event.metadata();
+        global().push(LogEntry {
+            sequence: 0,
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_millis() as i64)
+                .unwrap_or(0),
+            level: *metadata.level(),
+            target: metadata.target().to_string(),
+            message: visitor.message.unwrap_or_default(),
+            fields: visitor.fields,
+        });
+    }
+}
+
+#[derive(Default)]
+struct FieldVisitor {
+    fields: Map<String, Value>,
+    message: Option<String>,
+}
+
+impl FieldVisitor {
+    fn insert(&mut self, field: &Field, value: Value) {
+        if field.name() == "message" {
+            self.message = Some(match value {
+                Value::String(text) => text,
+                other => other.to_string(),
+            });
+        } else if field.name() == "uri" {
+            let value = match value {
+                Value::String(uri) => match uri.split_once('?') {
+                    Some((path, _)) => Value::String(path.to_string()),
+                    None => Value::String(uri),
+                },
+                other => other,
+            };
+            self.fields.insert("uri".to_string(), value);
+        } else {
+            self.fields.insert(field.name().to_string(), value);
+        }
+    }
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, Value::String(value.to_string()));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, Value::Bool(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, Value::String(format!("{value:?}")));
+    }
+}
@@ -0,0 +1,146 @@
+//! Messages queued to be sent once a session's current turn completes.
+//!
+//! Each session has its own queue. A dispatcher task drains it in order,
+//! sending one `session/prompt` at a time whenever the session has no
+//! prompt in flight. The prompt's JSON-RPC id is `sandboxagent-message-{id}`,
+//! so the turn notifications it produces carry that `requestId`.
+
+use std::collections::{HashMap, VecDeque};
+
+use sandbox_agent_error::SandboxError;
+use serde_json::{json, Value};
+
+pub(crate) const AFTER_CURRENT_TURN: &str = "after_current_turn";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageStatus {
+    Queued,
+    /// Its prompt was sent and the turn has not finished.
+    Sending,
+}
+
+impl MessageStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Sending => "sending",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QueuedMessage {
+    pub id: String,
+    pub session_id: String,
+    /// ACP content blocks for `params.prompt`.
+    pub prompt: Vec<Value>,
+    pub status: MessageStatus,
+    pub created_at_ms: i64,
+}
+
+impl QueuedMessage {
+    pub fn request_id(&self) -> String {
+        format!("sandboxagent-message-{}", self.id)
+    }
+
+    pub(crate) fn envelope(&self) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": self.request_id(),
+            "method": "session/prompt",
+            "params": { "sessionId": self.session_id, "prompt": self.prompt }
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct SessionQueue {
+    messages: VecDeque<QueuedMessage>,
+    /// Set while a dispatcher task drains this queue.
+    dispatching: bool,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct MessageQueue {
+    next_id: u64,
+    sessions: HashMap<String, SessionQueue>,
+}
+
+impl MessageQueue {
+    /// Queue a message. Returns it, and whether a dispatcher must be started.
+    pub(crate) fn push(
+        &mut self,
+        session_id: &str,
+        prompt: Vec<Value>,
+        created_at_ms: i64,
+    ) -> (QueuedMessage, bool) {
+        self.next_id += 1;
+        let message = QueuedMessage {
+            id: format!("msg-{}", self.next_id),
+            session_id: session_id.to_string(),
+            prompt,
+            status: MessageStatus::Queued,
+            created_at_ms,
+        };
+        let queue = self.sessions.entry(session_id.to_string()).or_default();
+        queue.messages.push_back(message.clone());
+        let start = !queue.dispatching;
+        queue.dispatching = true;
+        (message, start)
+    }
+
+    pub(crate) fn list(&self, session_id: &str) -> Vec<QueuedMessage> {
+        self.sessions
+            .get(session_id)
+            .map(|queue| queue.messages.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Remove a message that has not been sent yet. `None` when there is no
+    /// such message.
+    pub(crate) fn cancel(
+        &mut self,
+        session_id: &str,
+        message_id: &str,
+    ) -> Result<Option<QueuedMessage>, SandboxError> {
+        let Some(queue) = self.sessions.get_mut(session_id) else {
+            return Ok(None);
+        };
+        let Some(index) = queue
+            .messages
+            .iter()
+            .position(|message| message.id == message_id)
+        else {
+            return Ok(None);
+        };
+        if queue.messages[index].status == MessageStatus::Sending {
+            return Err(SandboxError::Conflict {
+                message: format!("message '{message_id}' was already sent"),
+            });
+        }
+        Ok(queue.messages.remove(index))
+    }
+
+    /// Mark the oldest message as sending and return it. With none left,
+    /// the dispatcher stops and `None` is returned.
+    pub(crate) fn next(&mut self, session_id: &str) -> Option<QueuedMessage> {
+        let queue = self.sessions.get_mut(session_id)?;
+        match queue.messages.front_mut() {
+            Some(message) => {
+                message.status = MessageStatus::Sending;
+                Some(message.clone())
+            }
+            None => {
+                self.sessions.remove(session_id);
+                None
+            }
+        }
+    }
+
+    /// Drop a message whose turn finished.
+    pub(crate) fn finish(&mut self, session_id: &str, message_id: &str) {
+        if let Some(queue) = self.sessions.get_mut(session_id) {
+            queue.messages.retain(|message| message.id != message_id);
+        }
+    }
+}
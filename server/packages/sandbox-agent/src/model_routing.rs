@@ -0,0 +1,113 @@
+//! Model aliases and fallback chains.
+//!
+//! `[models]` in the config file names models once for every client, such as
+//! `fast = "claude:claude-3-5-haiku"`, or a chain to fall back through such as
+//! `smart = ["codex:o3", "codex:o4-mini"]`; `SANDBOX_AGENT_MODEL_ALIASES`
+//! replaces the table with a JSON object of the same shape. Wherever a session picks a model (the
+//! `model` of a `session/new` envelope or an agent's default, and
+//! `PATCH .../sessions/{session_id}`) an alias resolves to its targets for
+//! the server's agent. The session starts on the first; the rest are tried in
+//! order when a prompt fails with a rate-limit or quota error.
+
+use std::collections::{BTreeMap, HashMap};
+
+use sandbox_agent_agent_management::agents::AgentId;
+use sandbox_agent_error::SandboxError;
+use serde_json::Value;
+
+use crate::agent_exit;
+
+pub(crate) const MODEL_FALLBACK_METHOD: &str = "_adapter/model_fallback";
+
+/// A model an alias routes to, for one agent or for any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ModelTarget {
+    pub(crate) agent: Option<AgentId>,
+    pub(crate) model: String,
+}
+
+impl ModelTarget {
+    /// Parse `agent:model`, or a bare model for any agent. Text before the
+    /// first `:` that is not an agent id is part of the model.
+    pub(crate) fn parse(raw: &str) -> Result<Self, String> {
+        let (agent, model) = match raw.split_once(':') {
+            Some((agent, model)) if AgentId::parse(agent).is_some() => {
+                (AgentId::parse(agent), model)
+            }
+            _ => (None, raw),
+        };
+        if model.trim().is_empty() {
+            return Err(format!("'{raw}' names no model"));
+        }
+        Ok(Self {
+            agent,
+            model: model.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ModelRoutes {
+    aliases: HashMap<String, Vec<ModelTarget>>,
+}
+
+impl ModelRoutes {
+    /// Routes for the resolved `[models]` table. Aliases with a target
+    /// that does not parse, or with no targets, are skipped with a warning.
+    pub(crate) fn from_aliases(parsed: &BTreeMap<String, Vec<String>>) -> Self {
+        let mut aliases = HashMap::new();
+        for (alias, targets) in parsed {
+            match targets
+                .iter()
+                .map(|target| ModelTarget::parse(target))
+                .collect::<Result<Vec<_>, _>>()
+            {
+                Ok(targets) if !targets.is_empty() => {
+                    aliases.insert(alias.clone(), targets);
+                }
+                Ok(_) => {
+                    tracing::warn!(alias = %alias, "ignoring model alias with no targets")
+                }
+                Err(err) => {
+                    tracing::warn!(alias = %alias, error = %err, "ignoring model alias")
+                }
+            }
+        }
+        Self { aliases }
+    }
+
+    /// Models to try, in order, when a session of `agent` picks `model`:
+    /// the alias's targets for the agent, or `model` itself when it is not
+    /// an alias.
+    pub(crate) fn resolve(&self, model: &str, agent: AgentId) -> Result<Vec<String>, SandboxError> {
+        let Some(targets) = self.aliases.get(model) else {
+            return Ok(vec![model.to_string()]);
+        };
+        let models = targets
+            .iter()
+            .filter(|target| target.agent.is_none_or(|target| target == agent))
+            .map(|target| target.model.clone())
+            .collect::<Vec<_>>();
+        if models.is_empty() {
+            let agents = targets
+                .iter()
+                .filter_map(|target| target.agent.map(AgentId::as_str))
+                .collect::<Vec<_>>();
+            return Err(SandboxError::InvalidRequest {
+                message: format!(
+                    "model alias '{model}' routes to {}, not '{}'",
+                    agents.join(", "),
+                    agent.as_str()
+                ),
+            });
+        }
+        Ok(models)
+    }
+}
+
+/// Whether a JSON-RPC `error` from the agent reports a rate limit or an
+/// exhausted quota.
+pub(crate) fn is_rate_limited(error: &Value) -> bool {
+    error.get("code").and_then(Value::as_i64) == Some(429)
+        || agent_exit::mentions_rate_limit(&error.to_string().to_lowercase())
+}
@@ -0,0 +1,160 @@
+//! Tenant namespaces for one server shared by several teams.
+//!
+//! `SANDBOX_AGENT_NAMESPACE_TOKENS` binds tokens to namespaces, as
+//! `team-a=<token>,team-b=<token>`. A request with a namespace token sees
+//! only its own ACP servers: `/v1/acp/{server_id}` is stored as
+//! `{namespace}~{server_id}`, listings and stream channels are filtered to
+//! the namespace, and ids are handed back without the prefix. Namespace
+//! tokens reach only the routes a tenant needs (health, reading agents, ACP
+//! servers and streams); installs, the filesystem API, config, logs, tasks
+//! and schedules stay with the server token, which still sees everything
+//! under the full ids. With `SANDBOX_AGENT_NAMESPACE_ROOT` set, sessions of
+//! a namespace must use a `cwd` inside `<root>/<namespace>`.
+
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use axum::http::Method;
+use sandbox_agent_error::SandboxError;
+use serde_json::Value;
+
+const SEPARATOR: char = '~';
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Namespaces {
+    /// Namespace name by token.
+    tokens: HashMap<String, String>,
+    root: Option<PathBuf>,
+}
+
+/// Namespace of a request made with a namespace token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Namespace(String);
+
+impl Namespaces {
+    pub(crate) fn from_env() -> Self {
+        let mut tokens = HashMap::new();
+        if let Ok(raw) = std::env::var("SANDBOX_AGENT_NAMESPACE_TOKENS") {
+            for entry in raw
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+            {
+                match entry.split_once('=') {
+                    Some((name, token)) if valid_name(name.trim()) && !token.trim().is_empty() => {
+                        tokens.insert(token.trim().to_string(), name.trim().to_string());
+                    }
+                    _ => tracing::warn!(
+                        "ignoring SANDBOX_AGENT_NAMESPACE_TOKENS entry; expected <namespace>=<token> with a namespace of letters, digits, '-' and '_'"
+                    ),
+                }
+            }
+        }
+        let root = std::env::var_os("SANDBOX_AGENT_NAMESPACE_ROOT")
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from);
+        Self { tokens, root }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        !self.tokens.is_empty()
+    }
+
+    pub(crate) fn resolve(&self, token: &str) -> Option<Namespace> {
+        self.tokens.get(token).cloned().map(Namespace)
+    }
+
+    /// The configured namespace called `name`.
+    pub(crate) fn named(&self, name: &str) -> Option<Namespace> {
+        self.tokens
+            .values()
+            .any(|known| known == name)
+            .then(|| Namespace(name.to_string()))
+    }
+
+    /// Reject a `session/new` or `session/load` whose `cwd` lies outside the
+    /// namespace's workspace root, when one is configured.
+    pub(crate) fn check_cwd(&self, namespace: &Namespace, cwd: &str) -> Result<(), SandboxError> {
+        let Some(root) = &self.root else {
+            return Ok(());
+        };
+        let workspace = root.join(&namespace.0);
+        let path = Path::new(cwd);
+        let escapes = path
+            .components()
+            .any(|component| matches!(component, Component::ParentDir));
+        if !path.is_absolute() || escapes || !path.starts_with(&workspace) {
+            return Err(SandboxError::PermissionDenied {
+                message: Some(format!(
+                    "cwd must be inside {} for namespace '{}'",
+                    workspace.display(),
+                    namespace.0
+                )),
+            });
+        }
+        Ok(())
+    }
+
+    /// [`Self::check_cwd`] for the `cwd` of an ACP envelope, if it has one.
+    pub(crate) fn check_payload(
+        &self,
+        namespace: &Namespace,
+        payload: &Value,
+    ) -> Result<(), SandboxError> {
+        let method = payload.get("method").and_then(Value::as_str);
+        if !matches!(method, Some("session/new" | "session/load")) {
+            return Ok(());
+        }
+        match payload.pointer("/params/cwd").and_then(Value::as_str) {
+            Some(cwd) => self.check_cwd(namespace, cwd),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Namespace {
+    pub(crate) fn name(&self) -> &str {
+        &self.0
+    }
+
+    /// Id a server of this namespace is stored under.
+    pub(crate) fn scope(&self, server_id: &str) -> String {
+        format!("{}{SEPARATOR}{server_id}", self.0)
+    }
+
+    /// The namespace's own id for a stored server id, or `None` when the
+    /// server belongs to someone else.
+    pub(crate) fn unscope<'a>(&self, server_id: &'a str) -> Option<&'a str> {
+        server_id.strip_prefix(&self.0)?.strip_prefix(SEPARATOR)
+    }
+
+    /// Path a namespace request for `path` is routed to, or `None` when
+    /// namespace tokens may not use the route.
+    pub(crate) fn route(&self, method: &Method, path: &str) -> Option<String> {
+        match path {
+            "/v1/health" | "/v1/openapi.json" | "/v1/acp" | "/v1/stream" => {
+                return Some(path.to_string())
+            }
+            _ => {}
+        }
+        if path == "/v1/agents" || path.starts_with("/v1/agents/") {
+            return (method == Method::GET).then(|| path.to_string());
+        }
+        if path.starts_with("/v1/stream/") {
+            return Some(path.to_string());
+        }
+        let rest = path.strip_prefix("/v1/acp/")?;
+        let (server_id, tail) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        if server_id.is_empty() {
+            return None;
+        }
+        Some(format!("/v1/acp/{}{tail}", self.scope(server_id)))
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_')
+}
@@ -0,0 +1,667 @@
+//! Permission modes for agent processes.
+//!
+//! The envelope that starts an agent process can pick a mode with
+//! `permissionMode` under `_meta["sandboxagent.dev"]`. Each agent enforces it
+//! with its own settings: Claude gets `permissionMode` in the
+//! `_meta.claudeCode.options` of each `session/new`, Codex gets `-c
+//! sandbox_mode=` and `-c approval_policy=` launch arguments, and OpenCode
+//! gets an `OPENCODE_PERMISSION` config. A mode the agent cannot enforce is
+//! rejected rather than silently ignored. A `codex` block next to it sets
+//! Codex's own sandbox mode and approval policy on top of the mode's: it can
+//! tighten what the mode picked but not loosen it.
+
+use std::collections::HashMap;
+
+use sandbox_agent_agent_management::agents::AgentId;
+use sandbox_agent_error::SandboxError;
+use serde_json::{json, Map, Value};
+
+const MODES: [&str; 6] = [
+    "default",
+    "plan",
+    "acceptEdits",
+    "readOnly",
+    "bypass",
+    "custom",
+];
+
+/// Codex sandbox modes, strictest first.
+const CODEX_SANDBOX_MODES: [&str; 3] = ["read-only", "workspace-write", "danger-full-access"];
+
+/// Codex approval policies, from asking the most to never asking.
+const CODEX_APPROVAL_POLICIES: [&str; 4] = ["untrusted", "on-request", "on-failure", "never"];
+
+const CODEX_KEYS: [&str; 4] = [
+    "sandboxMode",
+    "approvalPolicy",
+    "networkAccess",
+    "writableRoots",
+];
+
+/// What the agent may do without asking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Rule {
+    Allow,
+    Ask,
+    Deny,
+}
+
+impl Rule {
+    fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "allow" => Some(Self::Allow),
+            "ask" => Some(Self::Ask),
+            "deny" => Some(Self::Deny),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Allow => "allow",
+            Self::Ask => "ask",
+            Self::Deny => "deny",
+        }
+    }
+}
+
+/// Rules for a `custom` mode. A missing rule keeps the agent's default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct PermissionPolicy {
+    /// Writing files.
+    pub(crate) edit: Option<Rule>,
+    /// Running shell commands.
+    pub(crate) bash: Option<Rule>,
+    /// Network access from tools.
+    pub(crate) webfetch: Option<Rule>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum PermissionMode {
+    /// Whatever the agent does on its own.
+    #[default]
+    Default,
+    /// Plan without changing anything.
+    Plan,
+    /// Edit files freely; ask before running commands.
+    AcceptEdits,
+    /// Read and run nothing that writes.
+    ReadOnly,
+    /// Never ask.
+    Bypass,
+    Custom(PermissionPolicy),
+}
+
+/// Codex settings from `params._meta["sandboxagent.dev"].codex`. Each one
+/// set replaces the value the permission mode picked, as long as it is at
+/// least as strict.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct CodexOptions {
+    /// `sandbox_mode`: `read-only`, `workspace-write` or
+    /// `danger-full-access`.
+    pub(crate) sandbox_mode: Option<&'static str>,
+    /// `approval_policy`: `untrusted`, `on-request`, `on-failure` or `never`.
+    pub(crate) approval_policy: Option<&'static str>,
+    /// Network access from commands in the `workspace-write` sandbox.
+    pub(crate) network_access: Option<bool>,
+    /// Directories the `workspace-write` sandbox may write besides the
+    /// workspace.
+    pub(crate) writable_roots: Option<Vec<String>>,
+}
+
+/// How an agent process is started or driven to enforce a mode.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct PermissionLaunch {
+    pub(crate) args: Vec<String>,
+    pub(crate) env: HashMap<String, String>,
+    /// Merged into `_meta` of each `session/new` and `session/load`.
+    pub(crate) session_meta: Option<Value>,
+}
+
+impl PermissionMode {
+    /// Read `params._meta["sandboxagent.dev"].permissionMode`: a mode name,
+    /// or `{ "mode": "custom", "policy": { "edit", "bash", "webfetch" } }`.
+    pub(crate) fn from_payload(payload: &Value) -> Result<Self, SandboxError> {
+        let Some(raw) = payload.pointer("/params/_meta/sandboxagent.dev/permissionMode") else {
+            return Ok(Self::Default);
+        };
+        Self::parse(raw).map_err(|message| SandboxError::InvalidRequest {
+            message: format!("_meta[\"sandboxagent.dev\"].permissionMode {message}"),
+        })
+    }
+
+    /// Parse a mode name or a `custom` object. Errors complete a sentence
+    /// that starts with the field name.
+    pub(crate) fn parse(raw: &Value) -> Result<Self, String> {
+        let (name, policy) = match raw {
+            Value::String(name) => (name.as_str(), None),
+            Value::Object(object) => (
+                object
+                    .get("mode")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| "needs a string 'mode'".to_string())?,
+                object.get("policy"),
+            ),
+            _ => return Err("must be a string or an object".to_string()),
+        };
+        let mode = match name {
+            "default" => Self::Default,
+            "plan" => Self::Plan,
+            "acceptEdits" => Self::AcceptEdits,
+            "readOnly" => Self::ReadOnly,
+            "bypass" => Self::Bypass,
+            "custom" => {
+                let policy = policy
+                    .and_then(Value::as_object)
+                    .ok_or_else(|| "'custom' needs a 'policy' object".to_string())?;
+                Self::Custom(parse_policy(policy)?)
+            }
+            other => return Err(format!("'{other}' is not one of {}", MODES.join(", "))),
+        };
+        if policy.is_some() && !matches!(mode, Self::Custom(_)) {
+            return Err("'policy' only applies to 'custom'".to_string());
+        }
+        Ok(mode)
+    }
+
+    /// The ACP session mode that switches a running session of `agent` to
+    /// this mode, for agents that enforce it per session rather than at
+    /// launch.
+    pub(crate) fn session_mode_for(self, agent: AgentId) -> Option<&'static str> {
+        match (agent, self) {
+            (AgentId::Claude, Self::Default) => Some("default"),
+            (AgentId::Claude, Self::Plan) => Some("plan"),
+            (AgentId::Claude, Self::AcceptEdits) => Some("acceptEdits"),
+            (AgentId::Claude, Self::Bypass) => Some("bypassPermissions"),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Plan => "plan",
+            Self::AcceptEdits => "acceptEdits",
+            Self::ReadOnly => "readOnly",
+            Self::Bypass => "bypass",
+            Self::Custom(_) => "custom",
+        }
+    }
+
+    /// Map the mode onto `agent`'s own settings, or explain why it cannot
+    /// enforce it.
+    pub(crate) fn launch_for(self, agent: AgentId) -> Result<PermissionLaunch, SandboxError> {
+        let unsupported = |detail: &str| SandboxError::InvalidRequest {
+            message: format!(
+                "agent '{}' does not support permission mode '{}'{detail}",
+                agent.as_str(),
+                self.name()
+            ),
+        };
+        if self == Self::Default {
+            return Ok(PermissionLaunch::default());
+        }
+        match agent {
+            AgentId::Claude => {
+                let mode = match self {
+                    Self::Plan => "plan",
+                    Self::AcceptEdits => "acceptEdits",
+                    Self::Bypass => "bypassPermissions",
+                    _ => return Err(unsupported("")),
+                };
+                Ok(PermissionLaunch {
+                    session_meta: Some(
+                        json!({ "claudeCode": { "options": { "permissionMode": mode } } }),
+                    ),
+                    ..PermissionLaunch::default()
+                })
+            }
+            AgentId::Codex => {
+                let (sandbox, approval, network) = match self {
+                    Self::AcceptEdits => ("workspace-write", "on-request", false),
+                    Self::ReadOnly => ("read-only", "on-request", false),
+                    Self::Bypass => ("danger-full-access", "never", true),
+                    Self::Custom(policy) => {
+                        let sandbox = match policy.edit {
+                            None | Some(Rule::Allow) => "workspace-write",
+                            Some(Rule::Deny) => "read-only",
+                            Some(Rule::Ask) => return Err(unsupported(": edit cannot be 'ask'")),
+                        };
+                        let approval = match policy.bash {
+                            None | Some(Rule::Ask) => "on-request",
+                            Some(Rule::Allow) => "never",
+                            Some(Rule::Deny) => return Err(unsupported(": bash cannot be 'deny'")),
+                        };
+                        let network = match policy.webfetch {
+                            None | Some(Rule::Deny) => false,
+                            Some(Rule::Allow) => true,
+                            Some(Rule::Ask) => {
+                                return Err(unsupported(": webfetch cannot be 'ask'"))
+                            }
+                        };
+                        (sandbox, approval, network)
+                    }
+                    _ => return Err(unsupported("")),
+                };
+                let mut args = vec![
+                    "-c".to_string(),
+                    format!("sandbox_mode=\"{sandbox}\""),
+                    "-c".to_string(),
+                    format!("approval_policy=\"{approval}\""),
+                ];
+                if network && sandbox == "workspace-write" {
+                    args.push("-c".to_string());
+                    args.push("sandbox_workspace_write.network_access=true".to_string());
+                }
+                Ok(PermissionLaunch {
+                    args,
+                    ..PermissionLaunch::default()
+                })
+            }
+            AgentId::Opencode => {
+                let policy = match self {
+                    Self::AcceptEdits => PermissionPolicy {
+                        edit: Some(Rule::Allow),
+                        bash: Some(Rule::Ask),
+                        webfetch: Some(Rule::Ask),
+                    },
+                    Self::ReadOnly => PermissionPolicy {
+                        edit: Some(Rule::Deny),
+                        bash: Some(Rule::Deny),
+                        webfetch: Some(Rule::Allow),
+                    },
+                    Self::Bypass => PermissionPolicy {
+                        edit: Some(Rule::Allow),
+                        bash: Some(Rule::Allow),
+                        webfetch: Some(Rule::Allow),
+                    },
+                    Self::Custom(policy) => policy,
+                    _ => return Err(unsupported("")),
+                };
+                let config = [
+                    ("edit", policy.edit),
+                    ("bash", policy.bash),
+                    ("webfetch", policy.webfetch),
+                ]
+                .into_iter()
+                .filter_map(|(key, rule)| rule.map(|rule| (key.to_string(), json!(rule.as_str()))))
+                .collect::<Map<_, _>>();
+                Ok(PermissionLaunch {
+                    env: HashMap::from([(
+                        "OPENCODE_PERMISSION".to_string(),
+                        Value::Object(config).to_string(),
+                    )]),
+                    ..PermissionLaunch::default()
+                })
+            }
+            AgentId::Amp | AgentId::Pi | AgentId::Cursor | AgentId::Mock => Err(unsupported("")),
+        }
+    }
+}
+
+impl CodexOptions {
+    /// Read the `codex` block, if the envelope has one.
+    pub(crate) fn from_payload(payload: &Value) -> Result<Option<Self>, SandboxError> {
+        let invalid = |detail: String| SandboxError::InvalidRequest {
+            message: format!("_meta[\"sandboxagent.dev\"].codex{detail}"),
+        };
+        let codex = match payload.pointer("/params/_meta/sandboxagent.dev/codex") {
+            None | Some(Value::Null) => return Ok(None),
+            Some(Value::Object(codex)) => codex,
+            Some(_) => return Err(invalid(" must be an object".to_string())),
+        };
+        if let Some(key) = codex.keys().find(|key| !CODEX_KEYS.contains(&key.as_str())) {
+            return Err(invalid(format!(
+                " has unknown key '{key}'; expected {}",
+                CODEX_KEYS.join(", ")
+            )));
+        }
+        let one_of = |key: &str, allowed: &[&'static str]| match codex.get(key) {
+            None | Some(Value::Null) => Ok(None),
+            Some(value) => value
+                .as_str()
+                .and_then(|value| allowed.iter().find(|allowed| **allowed == value).copied())
+                .map(Some)
+                .ok_or_else(|| invalid(format!(".{key} must be one of {}", allowed.join(", ")))),
+        };
+        let network_access = match codex.get("networkAccess") {
+            None | Some(Value::Null) => None,
+            Some(Value::Bool(allowed)) => Some(*allowed),
+            Some(_) => return Err(invalid(".networkAccess must be a boolean".to_string())),
+        };
+        let writable_roots = match codex.get("writableRoots") {
+            None | Some(Value::Null) => None,
+            Some(Value::Array(roots)) => Some(
+                roots
+                    .iter()
+                    .map(|root| root.as_str().map(ToOwned::to_owned))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| {
+                        invalid(".writableRoots must be an array of paths".to_string())
+                    })?,
+            ),
+            Some(_) => {
+                return Err(invalid(
+                    ".writableRoots must be an array of paths".to_string(),
+                ))
+            }
+        };
+        Ok(Some(Self {
+            sandbox_mode: one_of("sandboxMode", &CODEX_SANDBOX_MODES)?,
+            approval_policy: one_of("approvalPolicy", &CODEX_APPROVAL_POLICIES)?,
+            network_access,
+            writable_roots,
+        }))
+    }
+
+    /// The `-c` settings to override, as TOML values.
+    fn config(&self) -> Vec<(&'static str, String)> {
+        let mut config = Vec::new();
+        if let Some(mode) = self.sandbox_mode {
+            config.push(("sandbox_mode", format!("\"{mode}\"")));
+        }
+        if let Some(policy) = self.approval_policy {
+            config.push(("approval_policy", format!("\"{policy}\"")));
+        }
+        if let Some(allowed) = self.network_access {
+            config.push((
+                "sandbox_workspace_write.network_access",
+                allowed.to_string(),
+            ));
+        }
+        if let Some(roots) = &self.writable_roots {
+            config.push((
+                "sandbox_workspace_write.writable_roots",
+                Value::from(roots.clone()).to_string(),
+            ));
+        }
+        config
+    }
+}
+
+impl PermissionLaunch {
+    /// Layer a `codex` block over the mode's launch arguments. A value
+    /// looser than the one the mode picked is rejected: a sandbox with more
+    /// access, an approval policy that asks less, network access or extra
+    /// writable roots the mode did not grant. Settings for the
+    /// `workspace-write` sandbox are dropped when the sandbox ends up another
+    /// one, and rejected when the block set them itself. Other launch
+    /// arguments are kept as they are.
+    pub(crate) fn with_codex_options(
+        mut self,
+        agent: AgentId,
+        options: &CodexOptions,
+    ) -> Result<Self, SandboxError> {
+        let invalid = |detail: String| SandboxError::InvalidRequest {
+            message: format!("_meta[\"sandboxagent.dev\"].codex {detail}"),
+        };
+        if agent != AgentId::Codex {
+            return Err(SandboxError::InvalidRequest {
+                message: format!(
+                    "_meta[\"sandboxagent.dev\"].codex only applies to agent 'codex', not '{}'",
+                    agent.as_str()
+                ),
+            });
+        }
+        let picked_sandbox = codex_setting(&self.args, "sandbox_mode").map(unquote);
+        let looser = |ranks: &[&str], value: &str, picked: &str| {
+            let rank = |value: &str| ranks.iter().position(|rank| *rank == value);
+            rank(value) > rank(picked)
+        };
+        if let (Some(mode), Some(picked)) = (options.sandbox_mode, picked_sandbox.as_deref()) {
+            if looser(&CODEX_SANDBOX_MODES, mode, picked) {
+                return Err(invalid(format!(
+                    "sandboxMode '{mode}' is looser than the permission mode's '{picked}'"
+                )));
+            }
+        }
+        let picked_approval = codex_setting(&self.args, "approval_policy").map(unquote);
+        if let (Some(policy), Some(picked)) = (options.approval_policy, picked_approval.as_deref())
+        {
+            if looser(&CODEX_APPROVAL_POLICIES, policy, picked) {
+                return Err(invalid(format!(
+                    "approvalPolicy '{policy}' is looser than the permission mode's '{picked}'"
+                )));
+            }
+        }
+        if picked_sandbox.is_some() {
+            let picked_network =
+                codex_setting(&self.args, "sandbox_workspace_write.network_access") == Some("true");
+            if options.network_access == Some(true) && !picked_network {
+                return Err(invalid(
+                    "networkAccess cannot grant network access the permission mode denies"
+                        .to_string(),
+                ));
+            }
+            if options.writable_roots.is_some() {
+                return Err(invalid(
+                    "writableRoots cannot widen the sandbox the permission mode picked".to_string(),
+                ));
+            }
+        }
+
+        for (key, value) in options.config() {
+            let setting = format!("{key}={value}");
+            let existing = self
+                .args
+                .windows(2)
+                .position(|pair| pair[0] == "-c" && setting_key(&pair[1]) == Some(key));
+            match existing {
+                Some(index) => self.args[index + 1] = setting,
+                None => self.args.extend(["-c".to_string(), setting]),
+            }
+        }
+        if codex_setting(&self.args, "sandbox_mode") != Some("\"workspace-write\"") {
+            if options.network_access.is_some() || options.writable_roots.is_some() {
+                return Err(invalid(
+                    "networkAccess and writableRoots need sandboxMode 'workspace-write'"
+                        .to_string(),
+                ));
+            }
+            let mut kept = Vec::with_capacity(self.args.len());
+            let mut args = std::mem::take(&mut self.args).into_iter().peekable();
+            while let Some(arg) = args.next() {
+                let workspace_write = arg == "-c"
+                    && args
+                        .peek()
+                        .and_then(|next| setting_key(next))
+                        .is_some_and(|key| key.starts_with("sandbox_workspace_write."));
+                if workspace_write {
+                    args.next();
+                } else {
+                    kept.push(arg);
+                }
+            }
+            self.args = kept;
+        }
+        Ok(self)
+    }
+
+    /// Merge [`Self::session_meta`] into a `session/new` or `session/load`
+    /// envelope. Keys the client set itself win.
+    pub(crate) fn apply_to_session(&self, payload: &mut Value) {
+        let Some(meta) = &self.session_meta else {
+            return;
+        };
+        let Some(params) = payload.get_mut("params").and_then(Value::as_object_mut) else {
+            return;
+        };
+        let target = params
+            .entry("_meta")
+            .or_insert_with(|| Value::Object(Map::new()));
+        merge_missing(target, meta);
+    }
+}
+
+/// Key of a Codex `-c key=value` setting.
+fn setting_key(setting: &str) -> Option<&str> {
+    setting.split_once('=').map(|(key, _)| key)
+}
+
+/// Value of the `-c key=value` setting for `key` among Codex launch args.
+fn codex_setting<'a>(args: &'a [String], key: &str) -> Option<&'a str> {
+    args.windows(2).find_map(|pair| {
+        let (found, value) = pair[1].split_once('=')?;
+        (pair[0] == "-c" && found == key).then_some(value)
+    })
+}
+
+fn unquote(value: &str) -> String {
+    value.trim_matches('"').to_string()
+}
+
+/// Permission modes `agent` can enforce, for `GET /v1/agents`.
+pub(crate) fn supported_modes(agent: AgentId) -> Vec<String> {
+    [
+        PermissionMode::Default,
+        PermissionMode::Plan,
+        PermissionMode::AcceptEdits,
+        PermissionMode::ReadOnly,
+        PermissionMode::Bypass,
+        PermissionMode::Custom(PermissionPolicy::default()),
+    ]
+    .into_iter()
+    .filter(|mode| mode.launch_for(agent).is_ok())
+    .map(|mode| mode.name().to_string())
+    .collect()
+}
+
+fn parse_policy(policy: &Map<String, Value>) -> Result<PermissionPolicy, String> {
+    let mut parsed = PermissionPolicy::default();
+    for (key, value) in policy {
+        let rule = value
+            .as_str()
+            .and_then(Rule::parse)
+            .ok_or_else(|| format!("policy.{key} must be 'allow', 'ask' or 'deny'"))?;
+        match key.as_str() {
+            "edit" => parsed.edit = Some(rule),
+            "bash" => parsed.bash = Some(rule),
+            "webfetch" => parsed.webfetch = Some(rule),
+            other => {
+                return Err(format!(
+                    "policy has unknown key '{other}'; expected edit, bash or webfetch"
+                ))
+            }
+        }
+    }
+    Ok(parsed)
+}
+
+/// Copy keys of `source` missing from `target` into it, recursing into
+/// objects both have.
+pub(crate) fn merge_missing(target: &mut Value, source: &Value) {
+    let (Some(target), Some(source)) = (target.as_object_mut(), source.as_object()) else {
+        return;
+    };
+    for (key, value) in source {
+        match target.get_mut(key) {
+            Some(existing) => merge_missing(existing, value),
+            None => {
+                target.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codex(block: Value) -> CodexOptions {
+        CodexOptions::from_payload(&json!({
+            "params": { "_meta": { "sandboxagent.dev": { "codex": block } } }
+        }))
+        .expect("parse")
+        .expect("codex block")
+    }
+
+    #[test]
+    fn codex_options_keep_other_launch_args() {
+        let launch = PermissionLaunch {
+            args: vec![
+                "--profile".to_string(),
+                "ci".to_string(),
+                "-c".to_string(),
+                "sandbox_mode=\"workspace-write\"".to_string(),
+                "-c".to_string(),
+                "model=\"o3\"".to_string(),
+            ],
+            ..PermissionLaunch::default()
+        };
+        let launch = launch
+            .with_codex_options(
+                AgentId::Codex,
+                &codex(json!({ "sandboxMode": "read-only", "approvalPolicy": "untrusted" })),
+            )
+            .expect("layer");
+        assert_eq!(
+            launch.args,
+            [
+                "--profile",
+                "ci",
+                "-c",
+                "sandbox_mode=\"read-only\"",
+                "-c",
+                "model=\"o3\"",
+                "-c",
+                "approval_policy=\"untrusted\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn codex_options_only_tighten_the_permission_mode() {
+        let read_only = PermissionMode::ReadOnly
+            .launch_for(AgentId::Codex)
+            .expect("read only");
+        for block in [
+            json!({ "sandboxMode": "danger-full-access" }),
+            json!({ "approvalPolicy": "never" }),
+        ] {
+            assert!(
+                read_only
+                    .clone()
+                    .with_codex_options(AgentId::Codex, &codex(block.clone()))
+                    .is_err(),
+                "{block}"
+            );
+        }
+
+        let accept_edits = PermissionMode::AcceptEdits
+            .launch_for(AgentId::Codex)
+            .expect("accept edits");
+        for block in [
+            json!({ "networkAccess": true }),
+            json!({ "writableRoots": ["/data"] }),
+        ] {
+            assert!(
+                accept_edits
+                    .clone()
+                    .with_codex_options(AgentId::Codex, &codex(block.clone()))
+                    .is_err(),
+                "{block}"
+            );
+        }
+
+        let bypass = PermissionMode::Bypass
+            .launch_for(AgentId::Codex)
+            .expect("bypass");
+        let tightened = bypass
+            .with_codex_options(
+                AgentId::Codex,
+                &codex(json!({ "sandboxMode": "workspace-write", "networkAccess": false })),
+            )
+            .expect("tighten");
+        assert_eq!(
+            tightened.args,
+            [
+                "-c",
+                "sandbox_mode=\"workspace-write\"",
+                "-c",
+                "approval_policy=\"never\"",
+                "-c",
+                "sandbox_workspace_write.network_access=false",
+            ]
+        );
+    }
+}
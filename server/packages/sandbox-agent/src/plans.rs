@@ -0,0 +1,236 @@
+//! Plans from sessions in plan mode.
+//!
+//! A session is in plan mode when its server was started with
+//! `permissionMode: "plan"`, when `session/new` reports `plan` as its current
+//! mode, or once `session/set_mode` switches it to `plan`. A
+//! `current_mode_update` during a planning turn, as Claude sends once its
+//! plan is accepted, switches it back. After each turn in plan mode the plan
+//! is taken from the agent's last ACP `plan` update, else from the plan
+//! Claude hands to `ExitPlanMode`, else from the reply text, as Codex answers
+//! with a plain-text plan. Tool calls that edited, deleted or moved files
+//! during the turn are kept with it as violations, since a planning agent
+//! should not change anything.
+//!
+//! Approving a plan switches the session to the agent's build mode with
+//! `session/set_mode` and sends the plan back as the next prompt.
+
+use sandbox_agent_agent_management::agents::AgentId;
+use serde_json::{json, Value};
+
+use crate::compaction::{agent_reply, session_update};
+
+pub(crate) const PLAN_METHOD: &str = "_adapter/plan";
+
+pub(crate) const PLAN_APPROVED_METHOD: &str = "_adapter/plan_approved";
+
+pub(crate) const PLAN_MODE_ID: &str = "plan";
+
+/// Tool call kinds that change files.
+pub(crate) const WRITE_KINDS: [&str; 3] = ["edit", "delete", "move"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlanSource {
+    /// An ACP `plan` session update.
+    PlanUpdate,
+    /// The `plan` input of Claude's `ExitPlanMode` tool call.
+    ExitPlanMode,
+    /// The agent's reply text.
+    Reply,
+}
+
+impl PlanSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::PlanUpdate => "plan_update",
+            Self::ExitPlanMode => "exit_plan_mode",
+            Self::Reply => "reply",
+        }
+    }
+}
+
+/// A tool call that changed files while the session was planning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanViolation {
+    pub tool_call_id: Option<String>,
+    pub kind: String,
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionPlan {
+    pub session_id: String,
+    pub turn: Option<u64>,
+    pub text: String,
+    /// Entries of the ACP `plan` update, when the plan came from one.
+    pub entries: Vec<Value>,
+    pub source: PlanSource,
+    pub violations: Vec<PlanViolation>,
+    pub created_at_ms: i64,
+    pub approval: Option<PlanApproval>,
+}
+
+/// The decision to build from a plan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanApproval {
+    /// Mode the session was switched to.
+    pub mode_id: String,
+    pub note: Option<String>,
+    pub approved_at_ms: i64,
+}
+
+impl PlanApproval {
+    fn record(&self) -> Value {
+        json!({
+            "modeId": self.mode_id,
+            "note": self.note,
+            "approvedAtMs": self.approved_at_ms,
+        })
+    }
+}
+
+impl SessionPlan {
+    /// Params of the `_adapter/plan` notification and `plan.created` record.
+    pub(crate) fn record(&self) -> Value {
+        json!({
+            "sessionId": self.session_id,
+            "turn": self.turn,
+            "text": self.text,
+            "entries": self.entries,
+            "source": self.source.as_str(),
+            "readOnly": self.violations.is_empty(),
+            "violations": self
+                .violations
+                .iter()
+                .map(|violation| json!({
+                    "toolCallId": violation.tool_call_id,
+                    "kind": violation.kind,
+                    "title": violation.title,
+                }))
+                .collect::<Vec<_>>(),
+            "createdAtMs": self.created_at_ms,
+            "approval": self.approval.as_ref().map(PlanApproval::record),
+        })
+    }
+}
+
+/// The ACP mode an agent builds in once its plan is approved.
+pub(crate) fn build_mode_for(agent: AgentId) -> &'static str {
+    match agent {
+        AgentId::Codex => "auto",
+        AgentId::Opencode => "build",
+        AgentId::Claude | AgentId::Amp | AgentId::Pi | AgentId::Cursor | AgentId::Mock => "default",
+    }
+}
+
+/// The prompt that starts building from an approved plan.
+pub(crate) fn build_prompt(plan: &str, note: Option<&str>) -> String {
+    let mut prompt = format!("The plan below is approved. Implement it now.\n\n{plan}");
+    if let Some(note) = note.filter(|note| !note.trim().is_empty()) {
+        prompt.push_str(&format!("\n\nReviewer note: {note}"));
+    }
+    prompt
+}
+
+/// The mode `events` last switched `session_id` to, if any.
+pub(crate) fn mode_switch(events: &[(u64, Value)], session_id: &str) -> Option<String> {
+    events
+        .iter()
+        .rev()
+        .filter_map(|(_, payload)| session_update(payload, session_id))
+        .find(|update| update_kind(update) == Some("current_mode_update"))
+        .and_then(|update| update.get("currentModeId"))
+        .and_then(Value::as_str)
+        .map(ToOwned::to_owned)
+}
+
+/// The plan `session_id` produced in `events`, one turn's worth, or `None`
+/// when the turn produced no plan text.
+pub(crate) fn extract(
+    events: &[(u64, Value)],
+    session_id: &str,
+    turn: Option<u64>,
+    created_at_ms: i64,
+) -> Option<SessionPlan> {
+    let updates = events
+        .iter()
+        .filter_map(|(_, payload)| session_update(payload, session_id))
+        .collect::<Vec<_>>();
+    let entries = updates
+        .iter()
+        .rev()
+        .find(|update| update_kind(update) == Some("plan"))
+        .and_then(|update| update.get("entries"))
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+    let exit_plan = updates
+        .iter()
+        .rev()
+        .filter(|update| matches!(update_kind(update), Some("tool_call" | "tool_call_update")))
+        .find_map(|update| update.pointer("/rawInput/plan").and_then(Value::as_str));
+    let (text, source) = if !entries.is_empty() {
+        let text = entries
+            .iter()
+            .filter_map(|entry| entry.get("content").and_then(Value::as_str))
+            .map(|content| format!("- {content}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        (text, PlanSource::PlanUpdate)
+    } else if let Some(plan) = exit_plan {
+        (plan.to_string(), PlanSource::ExitPlanMode)
+    } else {
+        (agent_reply(events, session_id), PlanSource::Reply)
+    };
+    let text = text.trim().to_string();
+    if text.is_empty() {
+        return None;
+    }
+
+    let mut violations: Vec<PlanViolation> = Vec::new();
+    for update in updates
+        .iter()
+        .filter(|update| matches!(update_kind(update), Some("tool_call" | "tool_call_update")))
+    {
+        let Some(kind) = update
+            .get("kind")
+            .and_then(Value::as_str)
+            .filter(|kind| WRITE_KINDS.contains(kind))
+        else {
+            continue;
+        };
+        let tool_call_id = update
+            .get("toolCallId")
+            .and_then(Value::as_str)
+            .map(ToOwned::to_owned);
+        if tool_call_id.is_some()
+            && violations
+                .iter()
+                .any(|violation| violation.tool_call_id == tool_call_id)
+        {
+            continue;
+        }
+        violations.push(PlanViolation {
+            tool_call_id,
+            kind: kind.to_string(),
+            title: update
+                .get("title")
+                .and_then(Value::as_str)
+                .map(ToOwned::to_owned),
+        });
+    }
+
+    Some(SessionPlan {
+        session_id: session_id.to_string(),
+        turn,
+        text,
+        entries,
+        source,
+        violations,
+        created_at_ms,
+        approval: None,
+    })
+}
+
+fn update_kind(update: &Value) -> Option<&str> {
+    update.get("sessionUpdate").and_then(Value::as_str)
+}
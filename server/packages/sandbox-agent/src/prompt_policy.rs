@@ -0,0 +1,289 @@
+//! Pre-dispatch content policy for `session/prompt` envelopes.
+//!
+//! Built-in rules catch common credential formats; operators can add their
+//! own patterns and an external HTTP scanner. Nothing runs unless
+//! `SANDBOX_AGENT_PROMPT_POLICY` or `SANDBOX_AGENT_PROMPT_SCANNER_URL` is set.
+
+use std::time::Duration;
+
+use regex::Regex;
+use sandbox_agent_error::SandboxError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const REDACTED: &str = "[REDACTED]";
+const SCANNER_TIMEOUT: Duration = Duration::from_secs(10);
+
+const BUILTIN_RULES: &[(&str, &str)] = &[
+    ("aws-access-key", r"\b(AKIA|ASIA)[0-9A-Z]{16}\b"),
+    (
+        "private-key",
+        r"-----BEGIN [A-Z ]*PRIVATE KEY-----[\s\S]*?(-----END [A-Z ]*PRIVATE KEY-----|$)",
+    ),
+    (
+        "github-token",
+        r"\b(gh[pousr]_[A-Za-z0-9]{36,}|github_pat_[A-Za-z0-9_]{22,})\b",
+    ),
+    ("slack-token", r"\bxox[abprs]-[A-Za-z0-9-]{10,}\b"),
+    ("api-key", r"\bsk-(ant-)?[A-Za-z0-9_-]{20,}\b"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyAction {
+    Off,
+    Warn,
+    Redact,
+    Reject,
+}
+
+impl PolicyAction {
+    fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "" | "off" => Some(Self::Off),
+            "warn" => Some(Self::Warn),
+            "redact" => Some(Self::Redact),
+            "reject" => Some(Self::Reject),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PolicyRule {
+    name: String,
+    pattern: Regex,
+}
+
+#[derive(Debug)]
+pub struct PromptPolicy {
+    action: PolicyAction,
+    rules: Vec<PolicyRule>,
+    scanner_url: Option<String>,
+    http: reqwest::Client,
+}
+
+/// Outcome of a policy pass that let the prompt through.
+#[derive(Debug, Default)]
+pub struct PolicyReport {
+    /// Rules that matched but were only warned about or redacted.
+    pub matched: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScannerRequest<'a> {
+    server_id: &'a str,
+    texts: &'a [String],
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScannerResponse {
+    action: String,
+    #[serde(default)]
+    reason: Option<String>,
+    #[serde(default)]
+    texts: Option<Vec<String>>,
+}
+
+impl PromptPolicy {
+    pub fn from_env() -> Self {
+        let action = std::env::var("SANDBOX_AGENT_PROMPT_POLICY")
+            .ok()
+            .and_then(|raw| {
+                let parsed = PolicyAction::parse(&raw);
+                if parsed.is_none() {
+                    tracing::warn!(value = %raw, "ignoring unknown SANDBOX_AGENT_PROMPT_POLICY");
+                }
+                parsed
+            })
+            .unwrap_or(PolicyAction::Off);
+
+        let mut rules = BUILTIN_RULES
+            .iter()
+            .map(|(name, pattern)| PolicyRule {
+                name: (*name).to_string(),
+                pattern: Regex::new(pattern).expect("built-in policy rule"),
+            })
+            .collect::<Vec<_>>();
+        if let Ok(path) = std::env::var("SANDBOX_AGENT_PROMPT_POLICY_RULES") {
+            rules.extend(load_rules_file(&path));
+        }
+
+        let scanner_url = std::env::var("SANDBOX_AGENT_PROMPT_SCANNER_URL")
+            .ok()
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty());
+
+        Self {
+            action,
+            rules,
+            scanner_url,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.action != PolicyAction::Off || self.scanner_url.is_some()
+    }
+
+    /// Inspect text blocks in a `session/prompt` payload, redacting in place
+    /// when configured. Returns an error when the prompt must not reach the
+    /// agent.
+    pub async fn apply(
+        &self,
+        server_id: &str,
+        payload: &mut Value,
+    ) -> Result<PolicyReport, SandboxError> {
+        let mut report = PolicyReport::default();
+        let Some(blocks) = payload
+            .pointer_mut("/params/prompt")
+            .and_then(Value::as_array_mut)
+        else {
+            return Ok(report);
+        };
+
+        if self.action != PolicyAction::Off {
+            for block in blocks.iter_mut() {
+                let Some(text) = block_text_mut(block) else {
+                    continue;
+                };
+                for rule in &self.rules {
+                    if !rule.pattern.is_match(text) {
+                        continue;
+                    }
+                    if !report.matched.contains(&rule.name) {
+                        report.matched.push(rule.name.clone());
+                    }
+                    if self.action == PolicyAction::Redact {
+                        *text = rule.pattern.replace_all(text, REDACTED).into_owned();
+                    }
+                }
+            }
+
+            if self.action == PolicyAction::Reject && !report.matched.is_empty() {
+                return Err(SandboxError::PolicyViolation {
+                    message: "prompt contains blocked content".to_string(),
+                    rules: report.matched,
+                });
+            }
+        }
+
+        if let Some(url) = self.scanner_url.as_deref() {
+            self.scan_external(url, server_id, blocks, &mut report)
+                .await?;
+        }
+
+        Ok(report)
+    }
+
+    async fn scan_external(
+        &self,
+        url: &str,
+        server_id: &str,
+        blocks: &mut [Value],
+        report: &mut PolicyReport,
+    ) -> Result<(), SandboxError> {
+        let texts = blocks
+            .iter_mut()
+            .filter_map(|block| block_text_mut(block).map(|text| text.clone()))
+            .collect::<Vec<_>>();
+        if texts.is_empty() {
+            return Ok(());
+        }
+
+        // Fail closed: a prompt that could not be scanned is not sent.
+        let unavailable = |detail: String| SandboxError::PolicyViolation {
+            message: format!("prompt scanner unavailable: {detail}"),
+            rules: vec!["scanner".to_string()],
+        };
+        let response = self
+            .http
+            .post(url)
+            .timeout(SCANNER_TIMEOUT)
+            .json(&ScannerRequest {
+                server_id,
+                texts: &texts,
+            })
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| unavailable(err.to_string()))?
+            .json::<ScannerResponse>()
+            .await
+            .map_err(|err| unavailable(err.to_string()))?;
+
+        let reason = response
+            .reason
+            .unwrap_or_else(|| "rejected by prompt scanner".to_string());
+        match response.action.as_str() {
+            "allow" => {}
+            "warn" => report.matched.push("scanner".to_string()),
+            "reject" => {
+                return Err(SandboxError::PolicyViolation {
+                    message: reason,
+                    rules: vec!["scanner".to_string()],
+                });
+            }
+            "redact" => {
+                let replacements = response
+                    .texts
+                    .filter(|replacements| replacements.len() == texts.len())
+                    .ok_or_else(|| {
+                        unavailable("redact response must return one text per input".to_string())
+                    })?;
+                let mut replacements = replacements.into_iter();
+                for block in blocks.iter_mut() {
+                    if let Some(text) = block_text_mut(block) {
+                        if let Some(replacement) = replacements.next() {
+                            *text = replacement;
+                        }
+                    }
+                }
+                report.matched.push("scanner".to_string());
+            }
+            other => return Err(unavailable(format!("unknown action '{other}'"))),
+        }
+        Ok(())
+    }
+}
+
+fn block_text_mut(block: &mut Value) -> Option<&mut String> {
+    let pointer = match block.get("type").and_then(Value::as_str) {
+        Some("text") => "/text",
+        Some("resource") => "/resource/text",
+        _ => return None,
+    };
+    match block.pointer_mut(pointer) {
+        Some(Value::String(text)) => Some(text),
+        _ => None,
+    }
+}
+
+fn load_rules_file(path: &str) -> Vec<PolicyRule> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) => {
+            tracing::warn!(path = %path, error = %err, "failed to read prompt policy rules");
+            return Vec::new();
+        }
+    };
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        })
+        .filter_map(|(index, line)| match Regex::new(line.trim()) {
+            Ok(pattern) => Some(PolicyRule {
+                name: format!("custom:{}", index + 1),
+                pattern,
+            }),
+            Err(err) => {
+                tracing::warn!(path = %path, line = index + 1, error = %err, "invalid prompt policy rule");
+                None
+            }
+        })
+        .collect()
+}
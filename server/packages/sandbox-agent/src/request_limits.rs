@@ -0,0 +1,74 @@
+//! Size limits on request bodies and prompts.
+//!
+//! Every route rejects bodies over `max_body_bytes` under `[limits]` (or
+//! `SANDBOX_AGENT_MAX_BODY_BYTES`, default 2 MiB, axum's own default) with
+//! `413`. `session/prompt` envelopes are also capped at `max_prompt_bytes`
+//! (or `SANDBOX_AGENT_MAX_PROMPT_BYTES`, default 1 MiB) of text,
+//! counting text blocks and embedded text resources, so one oversized
+//! message fails fast instead of filling the agent's context or memory.
+//! `0` turns the prompt limit off.
+
+use sandbox_agent_error::SandboxError;
+use serde_json::Value;
+
+const DEFAULT_MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+const DEFAULT_MAX_PROMPT_BYTES: usize = 1024 * 1024;
+
+pub(crate) fn max_body_bytes(configured: Option<u64>) -> usize {
+    configured
+        .and_then(|max| usize::try_from(max).ok())
+        .filter(|max| *max > 0)
+        .unwrap_or(DEFAULT_MAX_BODY_BYTES)
+}
+
+pub(crate) fn body_too_large(limit_bytes: usize) -> SandboxError {
+    SandboxError::PayloadTooLarge {
+        message: format!("request body is larger than {limit_bytes} bytes"),
+        limit_bytes: limit_bytes as u64,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PromptLimit {
+    max_bytes: Option<usize>,
+}
+
+impl PromptLimit {
+    pub(crate) fn new(configured: Option<u64>) -> Self {
+        let max_bytes = configured
+            .and_then(|max| usize::try_from(max).ok())
+            .unwrap_or(DEFAULT_MAX_PROMPT_BYTES);
+        Self {
+            max_bytes: (max_bytes > 0).then_some(max_bytes),
+        }
+    }
+
+    /// Reject a `session/prompt` envelope whose text is over the limit.
+    pub(crate) fn check(&self, payload: &Value) -> Result<(), SandboxError> {
+        let Some(max_bytes) = self.max_bytes else {
+            return Ok(());
+        };
+        let bytes = prompt_text_bytes(payload);
+        if bytes > max_bytes {
+            return Err(SandboxError::PayloadTooLarge {
+                message: format!("prompt text is {bytes} bytes; the limit is {max_bytes}"),
+                limit_bytes: max_bytes as u64,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn prompt_text_bytes(payload: &Value) -> usize {
+    let Some(blocks) = payload.pointer("/params/prompt").and_then(Value::as_array) else {
+        return 0;
+    };
+    blocks
+        .iter()
+        .map(|block| {
+            let text = block.get("text").and_then(Value::as_str);
+            let resource = block.pointer("/resource/text").and_then(Value::as_str);
+            text.map_or(0, str::len) + resource.map_or(0, str::len)
+        })
+        .sum()
+}
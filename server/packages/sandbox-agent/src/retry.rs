@@ -0,0 +1,61 @@
+//! Retrying a session's last turn.
+//!
+//! The replay buffer is append-only, so a retry does not delete the old
+//! turn. It appends an `_adapter/turn_retracted` notification naming the
+//! sequence range the turn's envelopes used, and clients drop that
+//! session's envelopes in the range. The prompt is then sent again, with its
+//! text replaced when the caller edited it. The agent's own context still
+//! holds the retracted turn.
+
+use serde_json::{json, Value};
+
+pub(crate) const TURN_RETRACTED_METHOD: &str = "_adapter/turn_retracted";
+
+/// The latest `session/prompt` of a session.
+#[derive(Debug, Clone)]
+pub(crate) struct LastPrompt {
+    /// `params.prompt` as the client sent it.
+    pub(crate) prompt: Value,
+    pub(crate) turn: Option<u64>,
+    /// Sequence of the turn's `_adapter/turn_started`.
+    pub(crate) first_sequence: u64,
+}
+
+/// What a retry retracted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetractedTurn {
+    pub turn: Option<u64>,
+    pub first_sequence: u64,
+    pub last_sequence: u64,
+}
+
+impl RetractedTurn {
+    /// Params of the `_adapter/turn_retracted` notification and
+    /// `turn.retracted` record.
+    pub(crate) fn record(&self, session_id: &str) -> Value {
+        json!({
+            "sessionId": session_id,
+            "turn": self.turn,
+            "firstSequence": self.first_sequence,
+            "lastSequence": self.last_sequence,
+        })
+    }
+}
+
+/// `prompt` with its text blocks replaced by one block of `text`. Other
+/// blocks, such as images and resources, are kept after it.
+pub(crate) fn edited_prompt(prompt: &Value, text: Option<&str>) -> Value {
+    let Some(text) = text else {
+        return prompt.clone();
+    };
+    let mut blocks = vec![json!({ "type": "text", "text": text })];
+    blocks.extend(
+        prompt
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|block| block.get("type").and_then(Value::as_str) != Some("text"))
+            .cloned(),
+    );
+    Value::Array(blocks)
+}
@@ -2,24 +2,27 @@ use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::io::Cursor;
 use std::path::{Path as StdPath, PathBuf};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, PoisonError};
 use std::time::Duration;
 
 use axum::body::Bytes;
-use axum::extract::{Path, Query, State};
-use axum::http::{header, HeaderMap, Request, StatusCode};
+use axum::extract::multipart::MultipartRejection;
+use axum::extract::{DefaultBodyLimit, Multipart, Path, Query, State};
+use axum::http::{header, HeaderMap, HeaderName, HeaderValue, Request, StatusCode};
 use axum::middleware::Next;
-use axum::response::sse::KeepAlive;
+use axum::response::sse::{Event, KeepAlive};
 use axum::response::{IntoResponse, Response, Sse};
-use axum::routing::{delete, get, post};
-use axum::{Json, Router};
+use axum::routing::{delete, get, post, put};
+use axum::{Extension, Json, Router};
+use futures::StreamExt;
 use sandbox_agent_agent_management::agents::{
-    AgentId, AgentManager, InstallOptions, InstallResult, InstallSource, InstalledArtifactKind,
+    AgentId, AgentManager, AgentPin, AgentRelease, InstallOptions, InstallProgress, InstallResult,
+    InstallSource, InstalledArtifactKind,
 };
 use sandbox_agent_agent_management::credentials::{
     extract_all_credentials, CredentialExtractionOptions,
 };
-use sandbox_agent_error::{ErrorType, ProblemDetails, SandboxError};
+use sandbox_agent_error::{ErrorCode, ErrorType, ProblemDetails, SandboxError};
 use sandbox_agent_opencode_adapter::{build_opencode_router, OpenCodeAdapterConfig};
 use sandbox_agent_opencode_server_manager::{OpenCodeServerManager, OpenCodeServerManagerConfig};
 use schemars::JsonSchema;
@@ -31,8 +34,33 @@ use tower_http::trace::TraceLayer;
 use tracing::Span;
 use utoipa::{Modify, OpenApi, ToSchema};
 
-use crate::acp_proxy_runtime::{AcpProxyRuntime, ProxyPostOutcome};
+use crate::acp_proxy_runtime::{
+    AcpProxyRuntime, AcpSessionDetail, AcpSessionPatch, ProxyPostOutcome,
+};
+use crate::agent_compat::AgentIncompatibility;
+use crate::anthropic_compat;
+use crate::attachments;
+use crate::cluster::Cluster;
+use crate::compaction::CompactionMode;
+use crate::compression::Compression;
+use crate::config::ServerConfig;
+use crate::event_filter::EventFilter;
+use crate::grpc;
+use crate::log_buffer::{self, LogEntry, LogFilter};
+use crate::message_queue::{self, QueuedMessage};
+use crate::namespaces::{Namespace, Namespaces};
+use crate::plans::PlanApproval;
+use crate::request_limits;
+use crate::retry::RetractedTurn;
+use crate::schedules::{ScheduleRecord, ScheduleRunner};
+use crate::secrets::SecretInput;
+use crate::session_defaults::SessionDefaults;
+use crate::stream_mux::{FirehoseFilter, StreamMux};
+use crate::subagents::{self, ChildSession};
+use crate::tasks::{TaskAgent, TaskRecord, TaskRunStatus, TaskRunner, TaskSpec};
+use crate::transcript;
 use crate::ui;
+use crate::warm_pool::WarmPoolStatus;
 
 mod support;
 mod types;
@@ -41,6 +69,22 @@ pub use self::types::*;
 
 const APPLICATION_JSON: &str = "application/json";
 const TEXT_EVENT_STREAM: &str = "text/event-stream";
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+/// Request headers logged as `<redacted>` by `SANDBOX_AGENT_LOG_HTTP_HEADERS`.
+const REDACTED_HEADERS: [&str; 4] = [
+    "authorization",
+    "cookie",
+    "proxy-authorization",
+    "x-api-key",
+];
+const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+const DEFAULT_SHARE_TTL_MS: u64 = 5 * 60 * 1000;
+const MAX_SHARE_TTL_MS: u64 = 60 * 60 * 1000;
+const DEFAULT_EVENTS_LIMIT: usize = 100;
+const MAX_EVENTS_LIMIT: usize = 1000;
+const MAX_EVENTS_WAIT_MS: u64 = 20 * 1000;
+const DEFAULT_TURN_RESULT_TIMEOUT_MS: u64 = 60 * 1000;
+const MAX_TURN_RESULT_TIMEOUT_MS: u64 = 30 * 60 * 1000;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum BrandingMode {
@@ -79,6 +123,13 @@ pub struct AppState {
     opencode_server_manager: Arc<OpenCodeServerManager>,
     pub(crate) branding: BrandingMode,
     version_cache: Mutex<HashMap<AgentId, CachedAgentVersion>>,
+    preinstall: Mutex<Vec<PreinstallStatusInfo>>,
+    stream_mux: StreamMux,
+    tasks: TaskRunner,
+    schedules: ScheduleRunner,
+    server_config: ServerConfig,
+    namespaces: Namespaces,
+    cluster: Cluster,
 }
 
 impl AppState {
@@ -91,8 +142,21 @@ impl AppState {
         agent_manager: AgentManager,
         branding: BrandingMode,
     ) -> Self {
+        Self::with_config(auth, agent_manager, branding, ServerConfig::default())
+    }
+
+    /// Builds the state from the server's config file. Environment
+    /// variables are laid over it here, once; modules read the resolved
+    /// settings from the state and `GET /v1/config` reports them.
+    pub fn with_config(
+        auth: AuthConfig,
+        agent_manager: AgentManager,
+        branding: BrandingMode,
+        config: ServerConfig,
+    ) -> Self {
+        let server_config = config.effective();
         let agent_manager = Arc::new(agent_manager);
-        let acp_proxy = Arc::new(AcpProxyRuntime::new(agent_manager.clone()));
+        let acp_proxy = Arc::new(AcpProxyRuntime::new(agent_manager.clone(), &server_config));
         let opencode_server_manager = Arc::new(OpenCodeServerManager::new(
             agent_manager.clone(),
             OpenCodeServerManagerConfig {
@@ -100,6 +164,7 @@ impl AppState {
                 auto_restart: true,
             },
         ));
+        let cluster = Cluster::from_env(&acp_proxy);
         Self {
             auth,
             agent_manager,
@@ -107,9 +172,25 @@ impl AppState {
             opencode_server_manager,
             branding,
             version_cache: Mutex::new(HashMap::new()),
+            preinstall: Mutex::new(Vec::new()),
+            stream_mux: StreamMux::default(),
+            tasks: TaskRunner::default(),
+            schedules: ScheduleRunner::default(),
+            server_config,
+            namespaces: Namespaces::from_env(),
+            cluster,
         }
     }
 
+    /// Settings the server is running with, file and environment resolved.
+    pub fn server_config(&self) -> &ServerConfig {
+        &self.server_config
+    }
+
+    pub(crate) fn max_body_bytes(&self) -> usize {
+        request_limits::max_body_bytes(self.server_config.limits.max_body_bytes)
+    }
+
     pub(crate) fn acp_proxy(&self) -> Arc<AcpProxyRuntime> {
         self.acp_proxy.clone()
     }
@@ -123,8 +204,94 @@ impl AppState {
     }
 
     pub(crate) fn purge_version_cache(&self, agent: AgentId) {
-        self.version_cache.lock().unwrap().remove(&agent);
+        self.version_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&agent);
+    }
+
+    /// Install each `agent[@version]` spec in the background. Progress is
+    /// reported through `/v1/health` until every install settles.
+    pub fn start_preinstall(self: &Arc<Self>, specs: &[String]) -> Result<(), SandboxError> {
+        let parsed = specs
+            .iter()
+            .map(|spec| parse_preinstall_spec(spec))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        *self
+            .preinstall
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = parsed
+            .iter()
+            .map(|(agent, version)| PreinstallStatusInfo {
+                agent: agent.as_str().to_string(),
+                version: version.clone(),
+                status: PreinstallStatus::Pending,
+                error: None,
+            })
+            .collect();
+
+        for (index, (agent, version)) in parsed.into_iter().enumerate() {
+            let state = self.clone();
+            tokio::spawn(async move {
+                state.set_preinstall_status(index, PreinstallStatus::Installing, None);
+                let result = state.acp_proxy.preinstall(agent, version).await;
+                match result {
+                    Ok(()) => {
+                        state.purge_version_cache(agent);
+                        state.set_preinstall_status(index, PreinstallStatus::Installed, None);
+                        tracing::info!(agent = agent.as_str(), "preinstall finished");
+                    }
+                    Err(err) => {
+                        tracing::warn!(agent = agent.as_str(), error = %err, "preinstall failed");
+                        state.set_preinstall_status(
+                            index,
+                            PreinstallStatus::Failed,
+                            Some(err.to_string()),
+                        );
+                    }
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    fn set_preinstall_status(&self, index: usize, status: PreinstallStatus, error: Option<String>) {
+        if let Some(entry) = self
+            .preinstall
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_mut(index)
+        {
+            entry.status = status;
+            entry.error = error;
+        }
+    }
+
+    pub(crate) fn preinstall_snapshot(&self) -> Vec<PreinstallStatusInfo> {
+        self.preinstall
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+fn parse_preinstall_spec(spec: &str) -> Result<(AgentId, Option<String>), SandboxError> {
+    let spec = spec.trim();
+    let (name, version) = match spec.split_once('@') {
+        Some((name, version)) => (name, Some(version.trim().to_string())),
+        None => (spec, None),
+    };
+    let agent = AgentId::parse(name.trim()).ok_or_else(|| SandboxError::UnsupportedAgent {
+        agent: name.trim().to_string(),
+    })?;
+    if version.as_deref() == Some("") {
+        return Err(SandboxError::InvalidRequest {
+            message: format!("preinstall entry '{spec}' has an empty version"),
+        });
     }
+    Ok((agent, version))
 }
 
 fn default_opencode_server_log_dir() -> PathBuf {
@@ -154,18 +321,37 @@ pub fn build_router(state: AppState) -> Router {
 }
 
 pub fn build_router_with_state(shared: Arc<AppState>) -> (Router, Arc<AppState>) {
+    let compression = Compression::from_env();
+    let mut events_route = get(get_v1_acp_events);
+    if compression == Compression::Events {
+        events_route = events_route.layer(Compression::layer());
+    }
+
     let mut v1_router = Router::new()
         .route("/health", get(get_v1_health))
+        .route("/openapi.json", get(get_v1_openapi))
         .route("/agents", get(get_v1_agents))
-        .route("/agents/:agent", get(get_v1_agent))
+        .route("/agents/:agent", get(get_v1_agent).delete(delete_v1_agent))
+        .route("/agents/:agent/models", get(get_v1_agent_models))
         .route("/agents/:agent/install", post(post_v1_agent_install))
+        .route(
+            "/agents/:agent/install/progress",
+            get(get_v1_agent_install_progress),
+        )
+        .route("/agents/:agent/versions", get(get_v1_agent_versions))
+        .route("/agents/:agent/pin", put(put_v1_agent_pin))
+        .route("/agents/:agent/upgrade", post(post_v1_agent_upgrade))
         .route("/fs/entries", get(get_v1_fs_entries))
         .route("/fs/file", get(get_v1_fs_file).put(put_v1_fs_file))
         .route("/fs/entry", delete(delete_v1_fs_entry))
         .route("/fs/mkdir", post(post_v1_fs_mkdir))
         .route("/fs/move", post(post_v1_fs_move))
         .route("/fs/stat", get(get_v1_fs_stat))
+        .route("/fs/workspace", get(get_v1_fs_workspace))
         .route("/fs/upload-batch", post(post_v1_fs_upload_batch))
+        .route("/config", get(get_v1_config))
+        .route("/logs", get(get_v1_logs))
+        .route("/logs/stream", get(get_v1_logs_stream))
         .route(
             "/config/mcp",
             get(get_v1_config_mcp)
@@ -183,9 +369,104 @@ pub fn build_router_with_state(shared: Arc<AppState>) -> (Router, Arc<AppState>)
             "/acp/:server_id",
             post(post_v1_acp).get(get_v1_acp).delete(delete_v1_acp),
         )
+        .route("/acp/:server_id/events", events_route)
+        .route("/acp/:server_id/requests", get(get_v1_acp_requests))
+        .route(
+            "/acp/:server_id/sessions/:session_id",
+            get(get_v1_acp_session).patch(patch_v1_acp_session),
+        )
+        .route(
+            "/acp/:server_id/sessions/:session_id/plan",
+            get(get_v1_acp_session_plan),
+        )
+        .route(
+            "/acp/:server_id/sessions/:session_id/plan/approve",
+            post(post_v1_acp_session_plan_approve),
+        )
+        .route(
+            "/acp/:server_id/sessions/:session_id/children",
+            get(get_v1_acp_session_children),
+        )
+        .route(
+            "/acp/:server_id/sessions/:session_id/retry",
+            post(post_v1_acp_session_retry),
+        )
+        .route(
+            "/acp/:server_id/sessions/:session_id/interrupt",
+            post(post_v1_acp_session_interrupt),
+        )
+        .route(
+            "/acp/:server_id/sessions/:session_id/messages",
+            get(get_v1_acp_session_messages).post(post_v1_acp_session_messages),
+        )
+        .route(
+            "/acp/:server_id/sessions/:session_id/messages/:message_id",
+            delete(delete_v1_acp_session_message),
+        )
+        .route(
+            "/acp/:server_id/sessions/:session_id/artifacts/:artifact_id",
+            get(get_v1_acp_session_artifact),
+        )
+        .route(
+            "/acp/:server_id/sessions/:session_id/transcript",
+            get(get_v1_acp_session_transcript),
+        )
+        .route(
+            "/acp/:server_id/sessions/:session_id/result",
+            get(get_v1_acp_session_result),
+        )
+        .route(
+            "/acp/:server_id/turns/:turn/logs",
+            get(get_v1_acp_turn_logs),
+        )
+        .route("/acp/:server_id/import", post(post_v1_acp_import))
+        .route("/acp/:server_id/compact", post(post_v1_acp_compact))
+        .route("/acp/:server_id/share", post(post_v1_acp_share))
+        .route("/acp/:server_id/secrets", post(post_v1_acp_secrets))
+        .route("/acp/:server_id/validate", post(post_v1_acp_validate))
+        .route("/stream", get(get_v1_stream))
+        .route(
+            "/stream/:channel_id/subscribe",
+            post(post_v1_stream_subscribe),
+        )
+        .route(
+            "/stream/:channel_id/unsubscribe",
+            post(post_v1_stream_unsubscribe),
+        )
+        .route("/tasks", get(get_v1_tasks).post(post_v1_tasks))
+        .route("/tasks/:task_id", get(get_v1_task).delete(delete_v1_task))
+        .route("/schedules", get(get_v1_schedules).post(post_v1_schedules))
+        .route(
+            "/compat/anthropic/v1/messages",
+            post(post_v1_compat_anthropic_messages),
+        )
+        .route(
+            "/schedules/:schedule_id",
+            get(get_v1_schedule).delete(delete_v1_schedule),
+        )
         .with_state(shared.clone());
 
-    if shared.auth.token.is_some() {
+    // Attachment uploads get their own, larger body limit; every other
+    // route keeps the general one.
+    let max_body_bytes = shared.max_body_bytes();
+    let max_attachment_bytes = shared.acp_proxy().attachment_limit();
+    let attachments_router = Router::new()
+        .route("/acp/:server_id/attachments", post(post_v1_acp_attachments))
+        .with_state(shared.clone())
+        .layer(DefaultBodyLimit::max(max_attachment_bytes))
+        .layer(axum::middleware::from_fn_with_state(
+            max_attachment_bytes,
+            reject_oversized_body,
+        ));
+    v1_router = v1_router
+        .layer(DefaultBodyLimit::max(max_body_bytes))
+        .layer(axum::middleware::from_fn_with_state(
+            max_body_bytes,
+            reject_oversized_body,
+        ))
+        .merge(attachments_router);
+
+    if shared.auth.token.is_some() || shared.namespaces.is_enabled() {
         v1_router = v1_router.layer(axum::middleware::from_fn_with_state(
             shared.clone(),
             require_token,
@@ -204,7 +485,12 @@ pub fn build_router_with_state(shared: Arc<AppState>) -> (Router, Arc<AppState>)
     .unwrap_or_else(|err| {
         tracing::error!(error = %err, "failed to initialize opencode adapter router; using fallback");
         Router::new().fallback(opencode_unavailable)
-    });
+    })
+    .layer(DefaultBodyLimit::max(max_body_bytes))
+    .layer(axum::middleware::from_fn_with_state(
+        max_body_bytes,
+        reject_oversized_body,
+    ));
 
     let mut router = Router::new()
         .route("/", get(get_root))
@@ -212,7 +498,43 @@ pub fn build_router_with_state(shared: Arc<AppState>) -> (Router, Arc<AppState>)
         .nest("/opencode", opencode_router)
         .fallback(not_found);
 
-    router = router.merge(ui::router());
+    let mut ui_router = ui::router();
+    if shared.auth.token.is_some() {
+        ui_router = ui_router.layer(axum::middleware::from_fn_with_state(
+            shared.clone(),
+            require_ui_token,
+        ));
+    }
+    router = router.merge(ui_router);
+
+    // gRPC has no way to carry a namespace, so it is only offered without
+    // namespaced tokens.
+    if !shared.namespaces.is_enabled() {
+        let mut grpc_router = grpc::router(shared.clone());
+        if shared.auth.token.is_some() {
+            grpc_router = grpc_router.layer(axum::middleware::from_fn_with_state(
+                shared.clone(),
+                require_token,
+            ));
+        }
+        router = router.merge(grpc_router);
+    }
+
+    if shared.cluster.is_enabled() {
+        let owner = axum::middleware::from_fn_with_state(shared.clone(), route_to_owner);
+        router = Router::new().fallback_service(router).layer(owner);
+    }
+
+    if shared.namespaces.is_enabled() {
+        // Wrapped as a fallback so the rewrite runs before routing and the
+        // routes match the namespaced server ids.
+        let scope = axum::middleware::from_fn_with_state(shared.clone(), scope_namespace);
+        router = Router::new().fallback_service(router).layer(scope);
+    }
+
+    if compression == Compression::All {
+        router = router.layer(Compression::layer());
+    }
 
     let http_logging = match std::env::var("SANDBOX_AGENT_LOG_HTTP") {
         Ok(value) if value == "0" || value.eq_ignore_ascii_case("false") => false,
@@ -227,7 +549,10 @@ pub fn build_router_with_state(shared: Arc<AppState>) -> (Router, Arc<AppState>)
                     let mut headers = Vec::new();
                     for (name, value) in req.headers().iter() {
                         let name_str = name.as_str();
-                        let display_value = if name_str.eq_ignore_ascii_case("authorization") {
+                        let display_value = if REDACTED_HEADERS
+                            .iter()
+                            .any(|redacted| name_str.eq_ignore_ascii_case(redacted))
+                        {
                             "<redacted>".to_string()
                         } else {
                             value.to_str().unwrap_or("<binary>").to_string()
@@ -237,14 +562,14 @@ pub fn build_router_with_state(shared: Arc<AppState>) -> (Router, Arc<AppState>)
                     tracing::info_span!(
                         "http.request",
                         method = %req.method(),
-                        uri = %req.uri(),
+                        uri = %loggable_uri(req.uri()),
                         headers = ?headers
                     )
                 } else {
                     tracing::info_span!(
                         "http.request",
                         method = %req.method(),
-                        uri = %req.uri()
+                        uri = %loggable_uri(req.uri())
                     )
                 }
             })
@@ -284,9 +609,16 @@ pub async fn shutdown_servers(state: &Arc<AppState>) {
 #[openapi(
     paths(
         get_v1_health,
+        get_v1_openapi,
         get_v1_agents,
         get_v1_agent,
+        delete_v1_agent,
+        get_v1_agent_models,
         post_v1_agent_install,
+        get_v1_agent_install_progress,
+        get_v1_agent_versions,
+        put_v1_agent_pin,
+        post_v1_agent_upgrade,
         get_v1_fs_entries,
         get_v1_fs_file,
         put_v1_fs_file,
@@ -294,7 +626,11 @@ pub async fn shutdown_servers(state: &Arc<AppState>) {
         post_v1_fs_mkdir,
         post_v1_fs_move,
         get_v1_fs_stat,
+        get_v1_fs_workspace,
         post_v1_fs_upload_batch,
+        get_v1_config,
+        get_v1_logs,
+        get_v1_logs_stream,
         get_v1_config_mcp,
         put_v1_config_mcp,
         delete_v1_config_mcp,
@@ -304,19 +640,67 @@ pub async fn shutdown_servers(state: &Arc<AppState>) {
         get_v1_acp_servers,
         post_v1_acp,
         get_v1_acp,
-        delete_v1_acp
+        delete_v1_acp,
+        get_v1_acp_events,
+        get_v1_acp_requests,
+        get_v1_acp_session_plan,
+        post_v1_acp_session_plan_approve,
+        get_v1_acp_session_children,
+        post_v1_acp_session_retry,
+        post_v1_acp_session_interrupt,
+        get_v1_acp_session_messages,
+        post_v1_acp_session_messages,
+        delete_v1_acp_session_message,
+        get_v1_acp_session_artifact,
+        get_v1_acp_session,
+        patch_v1_acp_session,
+        get_v1_acp_session_transcript,
+        get_v1_acp_session_result,
+        get_v1_acp_turn_logs,
+        post_v1_acp_attachments,
+        post_v1_acp_import,
+        post_v1_acp_compact,
+        post_v1_acp_share,
+        post_v1_acp_secrets,
+        post_v1_acp_validate,
+        get_v1_stream,
+        post_v1_stream_subscribe,
+        post_v1_stream_unsubscribe,
+        get_v1_tasks,
+        post_v1_tasks,
+        get_v1_task,
+        delete_v1_task,
+        get_v1_schedules,
+        post_v1_schedules,
+        get_v1_schedule,
+        delete_v1_schedule,
+        post_v1_compat_anthropic_messages
     ),
     components(
         schemas(
             HealthResponse,
+            PreinstallStatus,
+            PreinstallStatusInfo,
             ServerStatus,
             ServerStatusInfo,
             AgentCapabilities,
             AgentInfo,
+            AgentIncompatibilityInfo,
+            WarmPoolInfo,
+            AgentSessionDefaultsInfo,
             AgentListResponse,
+            AgentModelInfo,
+            AgentModelsResponse,
             AgentInstallRequest,
             AgentInstallArtifact,
             AgentInstallResponse,
+            AgentInstallProgressEvent,
+            AgentUninstallResponse,
+            AgentPinInfo,
+            AgentReleaseInfo,
+            AgentVersionsResponse,
+            AgentUpgradeRequest,
+            AgentUpgradeResponse,
             FsPathQuery,
             FsEntriesQuery,
             FsDeleteQuery,
@@ -324,20 +708,90 @@ pub async fn shutdown_servers(state: &Arc<AppState>) {
             FsEntryType,
             FsEntry,
             FsStat,
+            FsWorkspaceInfo,
             FsWriteResponse,
             FsMoveRequest,
             FsMoveResponse,
             FsActionResponse,
             FsUploadBatchResponse,
             AcpPostQuery,
+            AcpAttachmentsQuery,
+            AcpAttachmentInfo,
+            AcpAttachmentsResponse,
             AcpServerInfo,
+            AcpStartupTimingInfo,
+            AcpTurnTimingInfo,
+            AcpTurnUsageInfo,
+            AcpPipelineInfo,
             AcpServerListResponse,
+            AcpEventEnvelope,
+            AcpEventsResponse,
+            AcpTranscriptMessage,
+            AcpTranscriptResponse,
+            AcpChangedFile,
+            AcpTurnResultResponse,
+            AcpSessionEndInfo,
+            AcpSessionDetailResponse,
+            AcpSessionUpdateRequest,
+            AcpPendingRequestInfo,
+            AcpPendingRequestsResponse,
+            AcpPlanResponse,
+            AcpPlanViolation,
+            AcpChildSession,
+            AcpChildSessionsResponse,
+            AcpRetryRequest,
+            AcpRetractedTurn,
+            AcpRetryResponse,
+            AcpInterruptResponse,
+            AcpQueueMessageRequest,
+            AcpQueuedMessage,
+            AcpQueuedMessagesResponse,
+            AcpPlanApprovalInfo,
+            AcpPlanApproveRequest,
+            AcpPlanApproveResponse,
+            AcpImportSessionRequest,
+            AcpCompactRequest,
+            AcpCompactionMode,
+            AcpCompactResponse,
+            AcpImportSessionResponse,
+            AcpShareRequest,
+            AcpShareResponse,
+            AcpSecretInput,
+            AcpSecretsRequest,
+            AcpSecretsResponse,
+            AcpValidateResponse,
+            AcpValidationProblemInfo,
+            StreamSubscribeRequest,
+            StreamUnsubscribeRequest,
+            StreamSubscriptionsResponse,
+            TaskCreateRequest,
+            TaskAgentConfig,
+            TaskStatus,
+            TaskRunInfo,
+            TaskInfo,
+            TaskListResponse,
+            ScheduleCreateRequest,
+            ScheduleRunInfo,
+            ScheduleInfo,
+            ScheduleListResponse,
+            AnthropicMessagesRequest,
+            AnthropicMessageParam,
+            LogEntryInfo,
+            LogsResponse,
+            ServerConfigResponse,
+            ServerConfigAuthInfo,
+            ServerConfigServerInfo,
+            ServerConfigInstallInfo,
+            ServerConfigLimitsInfo,
+            ServerConfigTelemetryInfo,
+            ServerConfigAgentInfo,
             McpConfigQuery,
             SkillsConfigQuery,
             McpServerConfig,
             SkillsConfig,
             SkillSource,
             ProblemDetails,
+            ErrorCode,
             ErrorType,
             AcpEnvelope
         )
@@ -386,6 +840,18 @@ async fn get_root() -> Json<Value> {
     }))
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/openapi.json",
+    tag = "v1",
+    responses(
+        (status = 200, description = "OpenAPI document for this server's HTTP API, with JSON Schemas for every request, response and envelope body. Its `info.version` is the server version")
+    )
+)]
+async fn get_v1_openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
 #[utoipa::path(
     get,
     path = "/v1/health",
@@ -394,9 +860,17 @@ async fn get_root() -> Json<Value> {
         (status = 200, description = "Service health response", body = HealthResponse)
     )
 )]
-async fn get_v1_health() -> Json<HealthResponse> {
+async fn get_v1_health(State(state): State<Arc<AppState>>) -> Json<HealthResponse> {
+    let preinstall = state.preinstall_snapshot();
+    let settling = preinstall.iter().any(|entry| {
+        matches!(
+            entry.status,
+            PreinstallStatus::Pending | PreinstallStatus::Installing
+        )
+    });
     Json(HealthResponse {
-        status: "ok".to_string(),
+        status: if settling { "starting" } else { "ok" }.to_string(),
+        preinstall,
     })
 }
 
@@ -410,13 +884,15 @@ async fn get_v1_health() -> Json<HealthResponse> {
     ),
     responses(
         (status = 200, description = "List of v1 agents", body = AgentListResponse),
+        (status = 304, description = "Unchanged since the ETag in `If-None-Match`"),
         (status = 401, description = "Authentication required", body = ProblemDetails)
     )
 )]
 async fn get_v1_agents(
     State(state): State<Arc<AppState>>,
     Query(query): Query<AgentsQuery>,
-) -> Result<Json<AgentListResponse>, ApiError> {
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
     let credentials = tokio::task::spawn_blocking(move || {
         extract_all_credentials(&CredentialExtractionOptions::new())
     })
@@ -437,6 +913,17 @@ async fn get_v1_agents(
             .push(instance.created_at_ms);
     }
 
+    let manager = state.agent_manager();
+    let (disk_usage, disk_usage_bytes) = tokio::task::spawn_blocking(move || {
+        let per_agent = AgentId::all()
+            .iter()
+            .map(|agent_id| (*agent_id, manager.disk_usage(*agent_id)))
+            .collect::<HashMap<_, _>>();
+        (per_agent, manager.install_dir_usage())
+    })
+    .await
+    .unwrap_or_default();
+
     let load_config = query.config.unwrap_or(false);
     let no_cache = query.no_cache.unwrap_or(false);
 
@@ -471,6 +958,19 @@ async fn get_v1_agents(
             server_status,
             config_options: None,
             config_error: None,
+            incompatibility: state
+                .acp_proxy()
+                .agent_incompatibility(agent_id)
+                .map(agent_incompatibility_info),
+            disk_usage_bytes: disk_usage.get(&agent_id).copied().unwrap_or(0),
+            warm_pool: state
+                .acp_proxy()
+                .warm_pool_status(agent_id)
+                .map(warm_pool_info),
+            session_defaults: state
+                .acp_proxy()
+                .session_defaults(agent_id)
+                .map(session_defaults_info),
         });
     }
 
@@ -483,7 +983,12 @@ async fn get_v1_agents(
             .filter_map(|(idx, agent)| {
                 let agent_id = AgentId::parse(&agent.id)?;
                 if !no_cache {
-                    if state.version_cache.lock().unwrap().contains_key(&agent_id) {
+                    if state
+                        .version_cache
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .contains_key(&agent_id)
+                    {
                         return None;
                     }
                 }
@@ -509,14 +1014,20 @@ async fn get_v1_agents(
             .await
             .unwrap_or_default();
 
-            let mut cache = state.version_cache.lock().unwrap();
+            let mut cache = state
+                .version_cache
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
             for (agent_id, entry) in results {
                 cache.insert(agent_id, entry);
             }
         }
 
         // Apply cached version/path + hardcoded config options
-        let cache = state.version_cache.lock().unwrap();
+        let cache = state
+            .version_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
         for agent in &mut agents {
             let Some(agent_id) = AgentId::parse(&agent.id) else {
                 continue;
@@ -532,7 +1043,14 @@ async fn get_v1_agents(
         }
     }
 
-    Ok(Json(AgentListResponse { agents }))
+    Ok(json_with_etag(
+        &headers,
+        &AgentListResponse {
+            agents,
+            disk_usage_bytes,
+            disk_quota_bytes: state.acp_proxy().install_quota_bytes(),
+        },
+    ))
 }
 
 #[utoipa::path(
@@ -546,6 +1064,7 @@ async fn get_v1_agents(
     ),
     responses(
         (status = 200, description = "Agent info", body = AgentInfo),
+        (status = 304, description = "Unchanged since the ETag in `If-None-Match`"),
         (status = 400, description = "Unknown agent", body = ProblemDetails),
         (status = 401, description = "Authentication required", body = ProblemDetails)
     )
@@ -554,7 +1073,8 @@ async fn get_v1_agent(
     State(state): State<Arc<AppState>>,
     Path(agent): Path<String>,
     Query(query): Query<AgentsQuery>,
-) -> Result<Json<AgentInfo>, ApiError> {
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
     let agent_id = AgentId::parse(&agent).ok_or_else(|| SandboxError::UnsupportedAgent {
         agent: agent.clone(),
     })?;
@@ -594,6 +1114,11 @@ async fn get_v1_agent(
         })
     };
 
+    let manager = state.agent_manager();
+    let disk_usage_bytes = tokio::task::spawn_blocking(move || manager.disk_usage(agent_id))
+        .await
+        .unwrap_or(0);
+
     let mut info = AgentInfo {
         id: agent_id.as_str().to_string(),
         installed,
@@ -604,6 +1129,19 @@ async fn get_v1_agent(
         server_status,
         config_options: None,
         config_error: None,
+        incompatibility: state
+            .acp_proxy()
+            .agent_incompatibility(agent_id)
+            .map(agent_incompatibility_info),
+        disk_usage_bytes,
+        warm_pool: state
+            .acp_proxy()
+            .warm_pool_status(agent_id)
+            .map(warm_pool_info),
+        session_defaults: state
+            .acp_proxy()
+            .session_defaults(agent_id)
+            .map(session_defaults_info),
     };
 
     if query.config.unwrap_or(false) {
@@ -611,7 +1149,12 @@ async fn get_v1_agent(
 
         // Version/path (cached, slow — subprocess calls)
         let cached = if !no_cache {
-            state.version_cache.lock().unwrap().get(&agent_id).cloned()
+            state
+                .version_cache
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get(&agent_id)
+                .cloned()
         } else {
             None
         };
@@ -636,7 +1179,11 @@ async fn get_v1_agent(
             });
             info.version = result.version.clone();
             info.path = result.path.clone();
-            state.version_cache.lock().unwrap().insert(agent_id, result);
+            state
+                .version_cache
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(agent_id, result);
         }
 
         // Hardcoded config options
@@ -646,7 +1193,7 @@ async fn get_v1_agent(
         }
     }
 
-    Ok(Json(info))
+    Ok(json_with_etag(&headers, &info))
 }
 
 // TODO: Re-enable ACP config probing once agent processes reliably return
@@ -715,6 +1262,30 @@ async fn get_v1_agent(
 //     }
 // }
 
+#[utoipa::path(
+    get,
+    path = "/v1/agents/{agent}/models",
+    tag = "v1",
+    params(
+        ("agent" = String, Path, description = "Agent id")
+    ),
+    responses(
+        (status = 200, description = "Models the agent accepts", body = AgentModelsResponse),
+        (status = 304, description = "Unchanged since the ETag in `If-None-Match`"),
+        (status = 400, description = "Unknown agent", body = ProblemDetails),
+        (status = 401, description = "Authentication required", body = ProblemDetails)
+    )
+)]
+async fn get_v1_agent_models(
+    Path(agent): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let agent_id = AgentId::parse(&agent).ok_or_else(|| SandboxError::UnsupportedAgent {
+        agent: agent.clone(),
+    })?;
+    Ok(json_with_etag(&headers, &agent_model_catalog(agent_id)))
+}
+
 #[utoipa::path(
     post,
     path = "/v1/agents/{agent}/install",
@@ -747,6 +1318,7 @@ async fn post_v1_agent_install(
                 reinstall,
                 version: request.agent_version,
                 agent_process_version: request.agent_process_version,
+                artifact_dir: request.artifact_dir.map(PathBuf::from),
             },
         )
     })
@@ -762,10 +1334,226 @@ async fn post_v1_agent_install(
 
     // Purge version cache so next ?config=true picks up the new version
     state.purge_version_cache(agent_id);
+    state.acp_proxy().collect_agent_garbage(agent_id).await;
 
     Ok(Json(map_install_result(install_result)))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/v1/agents/{agent}",
+    tag = "v1",
+    params(
+        ("agent" = String, Path, description = "Agent id")
+    ),
+    responses(
+        (status = 200, description = "Agent files removed; pins are kept", body = AgentUninstallResponse),
+        (status = 400, description = "Unknown agent", body = ProblemDetails),
+        (status = 409, description = "A server of the agent is running", body = ProblemDetails)
+    )
+)]
+async fn delete_v1_agent(
+    State(state): State<Arc<AppState>>,
+    Path(agent): Path<String>,
+) -> Result<Json<AgentUninstallResponse>, ApiError> {
+    let agent_id = AgentId::parse(&agent).ok_or_else(|| SandboxError::UnsupportedAgent {
+        agent: agent.clone(),
+    })?;
+
+    let freed_bytes = state.acp_proxy().uninstall_agent(agent_id).await?;
+    state.purge_version_cache(agent_id);
+
+    Ok(Json(AgentUninstallResponse {
+        agent: agent_id.as_str().to_string(),
+        freed_bytes,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/agents/{agent}/install/progress",
+    tag = "v1",
+    params(
+        ("agent" = String, Path, description = "Agent id")
+    ),
+    responses(
+        (status = 200, description = "SSE stream of `progress` events (AgentInstallProgressEvent)"),
+        (status = 400, description = "Unknown agent", body = ProblemDetails)
+    )
+)]
+async fn get_v1_agent_install_progress(
+    State(state): State<Arc<AppState>>,
+    Path(agent): Path<String>,
+) -> Result<Sse<PinBoxSseStream>, ApiError> {
+    let agent_id = AgentId::parse(&agent).ok_or_else(|| SandboxError::UnsupportedAgent {
+        agent: agent.clone(),
+    })?;
+
+    let stream = install_progress_stream(state.agent_manager(), agent_id);
+    Ok(Sse::new(stream).keep_alive(KeepAlive::new().interval(Duration::from_secs(15))))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/agents/{agent}/versions",
+    tag = "v1",
+    params(
+        ("agent" = String, Path, description = "Agent id")
+    ),
+    responses(
+        (status = 200, description = "Available releases and the current pin", body = AgentVersionsResponse),
+        (status = 400, description = "Unknown agent", body = ProblemDetails),
+        (status = 502, description = "Release listing failed", body = ProblemDetails)
+    )
+)]
+async fn get_v1_agent_versions(
+    State(state): State<Arc<AppState>>,
+    Path(agent): Path<String>,
+) -> Result<Json<AgentVersionsResponse>, ApiError> {
+    let agent_id = AgentId::parse(&agent).ok_or_else(|| SandboxError::UnsupportedAgent {
+        agent: agent.clone(),
+    })?;
+
+    let manager = state.agent_manager();
+    let (installed_version, releases) = tokio::task::spawn_blocking(move || {
+        let installed = if manager.is_installed(agent_id) {
+            manager.version(agent_id).ok().flatten()
+        } else {
+            None
+        };
+        (installed, manager.available_versions(agent_id))
+    })
+    .await
+    .map_err(|err| SandboxError::StreamError {
+        message: format!("release lookup task failed: {err}"),
+    })?;
+    let releases = releases.map_err(|err| SandboxError::StreamError {
+        message: format!("failed to list releases for {agent}: {err}"),
+    })?;
+
+    Ok(Json(AgentVersionsResponse {
+        agent: agent_id.as_str().to_string(),
+        installed_version,
+        pinned: state.agent_manager().pin(agent_id).map(map_agent_pin),
+        releases: releases.into_iter().map(map_agent_release).collect(),
+    }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/v1/agents/{agent}/pin",
+    tag = "v1",
+    params(
+        ("agent" = String, Path, description = "Agent id")
+    ),
+    request_body = AgentPinInfo,
+    responses(
+        (status = 200, description = "Stored pin; an empty body clears it", body = AgentPinInfo),
+        (status = 400, description = "Unknown agent", body = ProblemDetails)
+    )
+)]
+async fn put_v1_agent_pin(
+    State(state): State<Arc<AppState>>,
+    Path(agent): Path<String>,
+    Json(request): Json<AgentPinInfo>,
+) -> Result<Json<AgentPinInfo>, ApiError> {
+    let agent_id = AgentId::parse(&agent).ok_or_else(|| SandboxError::UnsupportedAgent {
+        agent: agent.clone(),
+    })?;
+
+    let pin = AgentPin {
+        version: request.agent_version.filter(|v| !v.trim().is_empty()),
+        agent_process_version: request
+            .agent_process_version
+            .filter(|v| !v.trim().is_empty()),
+    };
+    state
+        .agent_manager()
+        .set_pin(agent_id, pin.clone())
+        .map_err(|err| SandboxError::StreamError {
+            message: format!("failed to store pin: {err}"),
+        })?;
+
+    Ok(Json(map_agent_pin(pin)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/agents/{agent}/upgrade",
+    tag = "v1",
+    params(
+        ("agent" = String, Path, description = "Agent id")
+    ),
+    request_body = AgentUpgradeRequest,
+    responses(
+        (status = 200, description = "Upgrade result with changelog", body = AgentUpgradeResponse),
+        (status = 400, description = "Invalid request", body = ProblemDetails),
+        (status = 500, description = "Install failed", body = ProblemDetails)
+    )
+)]
+async fn post_v1_agent_upgrade(
+    State(state): State<Arc<AppState>>,
+    Path(agent): Path<String>,
+    Json(request): Json<AgentUpgradeRequest>,
+) -> Result<Json<AgentUpgradeResponse>, ApiError> {
+    let agent_id = AgentId::parse(&agent).ok_or_else(|| SandboxError::UnsupportedAgent {
+        agent: agent.clone(),
+    })?;
+
+    let manager = state.agent_manager();
+    let (previous_version, installed_version, install_result, releases) =
+        tokio::task::spawn_blocking(move || {
+            let previous = if manager.is_installed(agent_id) {
+                manager.version(agent_id).ok().flatten()
+            } else {
+                None
+            };
+            // Changelog is best-effort; an unreachable release feed should not
+            // block the upgrade itself.
+            let releases = manager
+                .available_versions(agent_id)
+                .inspect_err(|err| {
+                    tracing::warn!(agent = %agent_id, error = %err, "failed to list releases for upgrade changelog");
+                })
+                .unwrap_or_default();
+            let result = manager.install(
+                agent_id,
+                InstallOptions {
+                    reinstall: true,
+                    version: request.agent_version,
+                    agent_process_version: request.agent_process_version,
+                    ..InstallOptions::default()
+                },
+            );
+            let installed = manager.version(agent_id).ok().flatten();
+            (previous, installed, result, releases)
+        })
+        .await
+        .map_err(|err| SandboxError::InstallFailed {
+            agent: agent.clone(),
+            stderr: Some(format!("installer task failed: {err}")),
+        })?;
+    let install_result = install_result.map_err(|err| SandboxError::InstallFailed {
+        agent: agent_id.as_str().to_string(),
+        stderr: Some(err.to_string()),
+    })?;
+
+    state.purge_version_cache(agent_id);
+    state.acp_proxy().collect_agent_garbage(agent_id).await;
+
+    let changelog = upgrade_changelog(
+        &releases,
+        previous_version.as_deref(),
+        installed_version.as_deref(),
+    );
+    Ok(Json(AgentUpgradeResponse {
+        previous_version,
+        installed_version,
+        artifacts: map_install_result(install_result).artifacts,
+        changelog,
+    }))
+}
+
 #[utoipa::path(
     get,
     path = "/v1/fs/entries",
@@ -999,8 +1787,33 @@ async fn get_v1_fs_stat(Query(query): Query<FsPathQuery>) -> Result<Json<FsStat>
 }
 
 #[utoipa::path(
-    post,
-    path = "/v1/fs/upload-batch",
+    get,
+    path = "/v1/fs/workspace",
+    tag = "v1",
+    params(
+        ("path" = String, Query, description = "Workspace root directory")
+    ),
+    responses(
+        (status = 200, description = "Detected languages, package managers, and test commands", body = FsWorkspaceInfo)
+    )
+)]
+async fn get_v1_fs_workspace(
+    Query(query): Query<FsPathQuery>,
+) -> Result<Json<FsWorkspaceInfo>, ApiError> {
+    let target = resolve_fs_path(&query.path)?;
+    let metadata = fs::metadata(&target).map_err(|err| map_fs_error(&target, err))?;
+    if !metadata.is_dir() {
+        return Err(SandboxError::InvalidRequest {
+            message: format!("path is not a directory: {}", target.display()),
+        }
+        .into());
+    }
+    Ok(Json(detect_workspace(&target)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/fs/upload-batch",
     tag = "v1",
     params(
         ("path" = Option<String>, Query, description = "Destination path")
@@ -1075,6 +1888,196 @@ async fn post_v1_fs_upload_batch(
     }))
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/config",
+    tag = "v1",
+    responses(
+        (status = 200, description = "Effective server settings with secrets masked", body = ServerConfigResponse),
+        (status = 401, description = "Authentication required", body = ProblemDetails)
+    )
+)]
+async fn get_v1_config(State(state): State<Arc<AppState>>) -> Json<ServerConfigResponse> {
+    const MASK: &str = "********";
+    let config = state.server_config.clone();
+    Json(ServerConfigResponse {
+        source: config.source.map(|path| path.to_string_lossy().to_string()),
+        auth: ServerConfigAuthInfo {
+            token: state.auth.token.as_ref().map(|_| MASK.to_string()),
+        },
+        server: ServerConfigServerInfo {
+            host: config.server.host,
+            port: config.server.port,
+            cors_allow_origins: config.server.cors_allow_origins,
+            cors_allow_methods: config.server.cors_allow_methods,
+            cors_allow_headers: config.server.cors_allow_headers,
+            cors_allow_credentials: config.server.cors_allow_credentials == Some(true),
+            unix_socket: config
+                .server
+                .unix_socket
+                .map(|path| path.to_string_lossy().to_string()),
+            stdio: config.server.stdio == Some(true),
+        },
+        install: ServerConfigInstallInfo {
+            dir: config
+                .install
+                .dir
+                .map(|dir| dir.to_string_lossy().to_string()),
+            preinstall: config.install.preinstall,
+            require_preinstall: config.install.require_preinstall.unwrap_or(false),
+        },
+        limits: ServerConfigLimitsInfo {
+            acp_request_timeout_ms: config.limits.acp_request_timeout_ms,
+            turn_timeout_ms: config.limits.turn_timeout_ms,
+            turn_idle_timeout_ms: config.limits.turn_idle_timeout_ms,
+            pending_request_timeout_ms: config.limits.pending_request_timeout_ms,
+            max_raw_bytes: config.limits.max_raw_bytes,
+            max_body_bytes: config.limits.max_body_bytes,
+            max_prompt_bytes: config.limits.max_prompt_bytes,
+        },
+        telemetry: ServerConfigTelemetryInfo {
+            enabled: config.telemetry.enabled,
+        },
+        models: config.models,
+        agents: config
+            .agents
+            .into_iter()
+            .map(|(agent, settings)| {
+                let env = settings
+                    .env
+                    .into_keys()
+                    .map(|name| (name, MASK.to_string()))
+                    .collect();
+                (
+                    agent,
+                    ServerConfigAgentInfo {
+                        env,
+                        model: settings.model,
+                        mode: settings.mode,
+                    },
+                )
+            })
+            .collect(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/logs",
+    tag = "v1",
+    params(
+        ("session" = Option<String>, Query, description = "Only entries whose `server_id` or `session_id` field, on the event or an enclosing span, equals this"),
+        ("level" = Option<String>, Query, description = "Least severe level to include: `error`, `warn`, `info`, `debug` or `trace`"),
+        ("since" = Option<u64>, Query, description = "Return entries after this sequence number"),
+        ("limit" = Option<usize>, Query, description = "Maximum entries to return (default 100, max 1000)")
+    ),
+    responses(
+        (status = 200, description = "Buffered server log entries, oldest first", body = LogsResponse),
+        (status = 400, description = "Unknown level", body = ProblemDetails),
+        (status = 401, description = "Authentication required", body = ProblemDetails)
+    )
+)]
+async fn get_v1_logs(Query(query): Query<LogsQuery>) -> Result<Json<LogsResponse>, ApiError> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_EVENTS_LIMIT)
+        .clamp(1, MAX_EVENTS_LIMIT);
+    let since = query.since;
+    let entries = log_buffer::global().query(&log_filter(query, None)?, limit);
+    let next_since = entries
+        .last()
+        .map_or(since.unwrap_or(0), |entry| entry.sequence);
+    Ok(Json(LogsResponse {
+        entries: entries.into_iter().map(log_entry_info).collect(),
+        next_since,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/logs/stream",
+    tag = "v1",
+    params(
+        ("session" = Option<String>, Query, description = "Only entries whose `server_id` or `session_id` field, on the event or an enclosing span, equals this"),
+        ("level" = Option<String>, Query, description = "Least severe level to include: `error`, `warn`, `info`, `debug` or `trace`"),
+        ("since" = Option<u64>, Query, description = "Replay buffered entries after this sequence number. The Last-Event-ID header wins when both are set")
+    ),
+    responses(
+        (status = 200, description = "SSE stream of `log` events (LogEntryInfo), replaying buffered matches first; the event id is the sequence number"),
+        (status = 400, description = "Unknown level", body = ProblemDetails),
+        (status = 406, description = "Client does not accept SSE responses", body = ProblemDetails)
+    )
+)]
+async fn get_v1_logs_stream(
+    Query(query): Query<LogsQuery>,
+    headers: HeaderMap,
+) -> Result<Sse<PinBoxSseStream>, ApiError> {
+    if !accept_allows(&headers, TEXT_EVENT_STREAM) {
+        return Err(SandboxError::NotAcceptable {
+            message: "accept must allow text/event-stream".to_string(),
+        }
+        .into());
+    }
+    let filter = log_filter(query, parse_last_event_id(&headers)?)?;
+    let (replay, receiver) = log_buffer::global().tail(&filter);
+    // An entry logged while the replay was taken can arrive on the receiver
+    // too; skip anything already sent.
+    let mut last_sent = replay.last().map(|entry| entry.sequence);
+    let live = tokio_stream::wrappers::BroadcastStream::new(receiver).filter_map(move |entry| {
+        let entry = entry.ok().filter(|entry| {
+            filter.matches(entry) && last_sent.is_none_or(|last| entry.sequence > last)
+        });
+        if let Some(entry) = &entry {
+            last_sent = Some(entry.sequence);
+        }
+        std::future::ready(entry)
+    });
+    let stream = futures::stream::iter(replay).chain(live).map(|entry| {
+        Ok(Event::default()
+            .event("log")
+            .id(entry.sequence.to_string())
+            .data(serde_json::to_string(&log_entry_info(entry)).unwrap_or_default()))
+    });
+    Ok(Sse::new(Box::pin(stream) as PinBoxSseStream).keep_alive(
+        KeepAlive::new()
+            .interval(Duration::from_secs(15))
+            .text("heartbeat"),
+    ))
+}
+
+fn log_filter(query: LogsQuery, last_event_id: Option<u64>) -> Result<LogFilter, SandboxError> {
+    let level = query
+        .level
+        .as_deref()
+        .map(|level| {
+            level
+                .trim()
+                .parse::<tracing::Level>()
+                .map_err(|_| SandboxError::InvalidRequest {
+                    message: format!(
+                        "unknown log level '{level}'; expected error, warn, info, debug or trace"
+                    ),
+                })
+        })
+        .transpose()?;
+    Ok(LogFilter {
+        session: query.session.filter(|session| !session.is_empty()),
+        level,
+        since: last_event_id.or(query.since),
+    })
+}
+
+fn log_entry_info(entry: LogEntry) -> LogEntryInfo {
+    LogEntryInfo {
+        sequence: entry.sequence,
+        timestamp_ms: entry.timestamp_ms,
+        level: entry.level.as_str().to_ascii_lowercase(),
+        target: entry.target,
+        message: entry.message,
+        fields: Value::Object(entry.fields),
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/v1/config/mcp",
@@ -1249,16 +2252,52 @@ async fn delete_v1_config_skills(
 )]
 async fn get_v1_acp_servers(
     State(state): State<Arc<AppState>>,
+    namespace: Option<Extension<Namespace>>,
 ) -> Result<Json<AcpServerListResponse>, ApiError> {
     let servers = state
         .acp_proxy()
         .list_instances()
         .await
         .into_iter()
+        .filter_map(|mut instance| {
+            if let Some(Extension(namespace)) = &namespace {
+                instance.server_id = namespace.unscope(&instance.server_id)?.to_string();
+            }
+            Some(instance)
+        })
         .map(|instance| AcpServerInfo {
             server_id: instance.server_id,
             agent: instance.agent.as_str().to_string(),
             created_at_ms: instance.created_at_ms,
+            startup_timing: AcpStartupTimingInfo {
+                install_ms: instance.startup_timing.install_ms,
+                resolve_ms: instance.startup_timing.resolve_ms,
+                spawn_ms: instance.startup_timing.spawn_ms,
+                warm: instance.startup_timing.warm,
+            },
+            last_turn_timing: instance.last_turn_timing.map(|timing| AcpTurnTimingInfo {
+                session_id: timing.session_id,
+                started_at_ms: timing.started_at_ms,
+                instance_ms: timing.instance_ms,
+                time_to_first_event_ms: timing.time_to_first_event_ms,
+                duration_ms: timing.duration_ms,
+                stop_reason: timing.stop_reason,
+                usage: timing.usage.map(|usage| AcpTurnUsageInfo {
+                    input_tokens: usage.input_tokens,
+                    output_tokens: usage.output_tokens,
+                    cached_read_tokens: usage.cached_read_tokens,
+                    cached_write_tokens: usage.cached_write_tokens,
+                    thought_tokens: usage.thought_tokens,
+                    total_tokens: usage.total_tokens,
+                    cost: usage.cost,
+                    cost_currency: usage.cost_currency,
+                }),
+            }),
+            pipeline: AcpPipelineInfo {
+                buffered_envelopes: instance.pipeline.buffered_envelopes as u64,
+                buffered_bytes: instance.pipeline.buffered_bytes as u64,
+                oversized_lines: instance.pipeline.oversized_lines,
+            },
         })
         .collect::<Vec<_>>();
 
@@ -1271,7 +2310,8 @@ async fn get_v1_acp_servers(
     tag = "v1",
     params(
         ("server_id" = String, Path, description = "Client-defined ACP server id"),
-        ("agent" = Option<String>, Query, description = "Agent id required for first POST")
+        ("agent" = Option<String>, Query, description = "Agent id required for first POST"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Run the request once per key; retries with the same key and body get the first response, with `Idempotent-Replayed: true`")
     ),
     request_body = AcpEnvelope,
     responses(
@@ -1281,7 +2321,8 @@ async fn get_v1_acp_servers(
         (status = 415, description = "Unsupported media type", body = ProblemDetails),
         (status = 400, description = "Invalid ACP envelope", body = ProblemDetails),
         (status = 404, description = "Unknown ACP server", body = ProblemDetails),
-        (status = 409, description = "ACP server bound to different agent", body = ProblemDetails),
+        (status = 409, description = "ACP server bound to different agent, or Idempotency-Key reused with a different request", body = ProblemDetails),
+        (status = 413, description = "Request body or prompt text over the size limit", body = ProblemDetails),
         (status = 504, description = "ACP agent process response timeout", body = ProblemDetails)
     )
 )]
@@ -1289,6 +2330,7 @@ async fn post_v1_acp(
     State(state): State<Arc<AppState>>,
     Path(server_id): Path<String>,
     Query(query): Query<AcpPostQuery>,
+    namespace: Option<Extension<Namespace>>,
     headers: HeaderMap,
     body: Bytes,
 ) -> Result<Response, ApiError> {
@@ -1309,6 +2351,9 @@ async fn post_v1_acp(
         serde_json::from_slice::<Value>(&body).map_err(|err| SandboxError::InvalidRequest {
             message: format!("invalid JSON body: {err}"),
         })?;
+    if let Some(Extension(namespace)) = &namespace {
+        state.namespaces.check_payload(namespace, &payload)?;
+    }
 
     let bootstrap_agent = match query.agent {
         Some(agent) => {
@@ -1321,14 +2366,43 @@ async fn post_v1_acp(
         None => None,
     };
 
-    match state
-        .acp_proxy()
-        .post(&server_id, bootstrap_agent, payload)
-        .await?
-    {
-        ProxyPostOutcome::Response(value) => Ok((StatusCode::OK, Json(value)).into_response()),
-        ProxyPostOutcome::Accepted => Ok(StatusCode::ACCEPTED.into_response()),
+    let idempotency_key = headers
+        .get(IDEMPOTENCY_KEY)
+        .map(|value| {
+            value
+                .to_str()
+                .map(str::trim)
+                .map_err(|_| SandboxError::InvalidRequest {
+                    message: "Idempotency-Key must be visible ASCII".to_string(),
+                })
+        })
+        .transpose()?;
+    let (outcome, replayed) = match idempotency_key {
+        Some(key) => {
+            state
+                .acp_proxy()
+                .post_idempotent(&server_id, bootstrap_agent, payload, key)
+                .await?
+        }
+        None => (
+            state
+                .acp_proxy()
+                .post(&server_id, bootstrap_agent, payload)
+                .await?,
+            false,
+        ),
+    };
+
+    let mut response = match outcome {
+        ProxyPostOutcome::Response(value) => (StatusCode::OK, Json(value)).into_response(),
+        ProxyPostOutcome::Accepted => StatusCode::ACCEPTED.into_response(),
+    };
+    if replayed {
+        response
+            .headers_mut()
+            .insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
     }
+    Ok(response)
 }
 
 #[utoipa::path(
@@ -1336,7 +2410,12 @@ async fn post_v1_acp(
     path = "/v1/acp/{server_id}",
     tag = "v1",
     params(
-        ("server_id" = String, Path, description = "Client-defined ACP server id")
+        ("server_id" = String, Path, description = "Client-defined ACP server id"),
+        ("offset" = Option<u64>, Query, description = "Replay buffered envelopes after this event id. The Last-Event-ID header wins when both are set"),
+        ("namedEvents" = Option<bool>, Query, description = "When true, name each event after the envelope's method, or `response` for responses, instead of `message`"),
+        ("includeLogs" = Option<bool>, Query, description = "When true, include `_adapter/log` notifications carrying the agent process's stderr lines"),
+        ("types" = Option<String>, Query, description = "Comma-separated event kinds to keep: a `sessionUpdate` value such as `agent_message_chunk`, a method such as `session/request_permission`, or `response`"),
+        ("exclude" = Option<String>, Query, description = "Comma-separated event kinds to drop, matched like `types`")
     ),
     responses(
         (status = 200, description = "SSE stream of ACP envelopes"),
@@ -1348,6 +2427,7 @@ async fn post_v1_acp(
 async fn get_v1_acp(
     State(state): State<Arc<AppState>>,
     Path(server_id): Path<String>,
+    Query(query): Query<AcpStreamQuery>,
     headers: HeaderMap,
 ) -> Result<Sse<PinBoxSseStream>, ApiError> {
     if !accept_allows(&headers, TEXT_EVENT_STREAM) {
@@ -1357,8 +2437,36 @@ async fn get_v1_acp(
         .into());
     }
 
-    let last_event_id = parse_last_event_id(&headers)?;
-    let stream = state.acp_proxy().sse(&server_id, last_event_id).await?;
+    let last_event_id = parse_last_event_id(&headers)?.or(query.offset);
+    let named_events = query.named_events.unwrap_or(false);
+    let include_logs = query.include_logs.unwrap_or(false);
+    let filter = EventFilter::parse(query.types.as_deref(), query.exclude.as_deref());
+    let stream = match state
+        .acp_proxy()
+        .sse(
+            &server_id,
+            last_event_id,
+            named_events,
+            include_logs,
+            filter.clone(),
+        )
+        .await
+    {
+        // Running on another replica; read its envelopes from the cluster.
+        Err(SandboxError::SessionNotFound { .. }) if state.cluster.is_enabled() => {
+            state
+                .cluster
+                .sse(
+                    &server_id,
+                    last_event_id,
+                    named_events,
+                    include_logs,
+                    filter,
+                )
+                .await?
+        }
+        result => result?,
+    };
 
     Ok(Sse::new(stream).keep_alive(
         KeepAlive::new()
@@ -1368,24 +2476,1373 @@ async fn get_v1_acp(
 }
 
 #[utoipa::path(
-    delete,
-    path = "/v1/acp/{server_id}",
+    get,
+    path = "/v1/acp/{server_id}/events",
     tag = "v1",
     params(
-        ("server_id" = String, Path, description = "Client-defined ACP server id")
+        ("server_id" = String, Path, description = "Client-defined ACP server id"),
+        ("offset" = Option<u64>, Query, description = "Return envelopes after this event id"),
+        ("waitMs" = Option<u64>, Query, description = "When no envelope is buffered after `offset`, wait up to this long for one (capped at 20000)"),
+        ("limit" = Option<usize>, Query, description = "Maximum envelopes to return (default 100, max 1000)"),
+        ("includeLogs" = Option<bool>, Query, description = "When true, include `_adapter/log` notifications carrying the agent process's stderr lines"),
+        ("types" = Option<String>, Query, description = "Comma-separated event kinds to keep: a `sessionUpdate` value such as `agent_message_chunk`, a method such as `session/request_permission`, or `response`"),
+        ("exclude" = Option<String>, Query, description = "Comma-separated event kinds to drop, matched like `types`")
     ),
     responses(
-        (status = 204, description = "ACP server closed")
+        (status = 200, description = "Buffered ACP envelopes", body = AcpEventsResponse),
+        (status = 304, description = "Same page as the ETag in `If-None-Match`"),
+        (status = 404, description = "Unknown ACP server", body = ProblemDetails)
     )
 )]
-async fn delete_v1_acp(
+async fn get_v1_acp_events(
+    State(state): State<Arc<AppState>>,
+    Path(server_id): Path<String>,
+    Query(query): Query<AcpEventsQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_EVENTS_LIMIT)
+        .clamp(1, MAX_EVENTS_LIMIT);
+    let wait = Duration::from_millis(query.wait_ms.unwrap_or(0).min(MAX_EVENTS_WAIT_MS));
+    let filter = EventFilter::parse(query.types.as_deref(), query.exclude.as_deref());
+    let events = state
+        .acp_proxy()
+        .poll_filtered_events(
+            &server_id,
+            query.offset,
+            limit,
+            wait,
+            query.include_logs.unwrap_or(false),
+            &filter,
+        )
+        .await?;
+
+    let next_offset = events
+        .last()
+        .map_or(query.offset.unwrap_or(0), |(sequence, _)| *sequence);
+    Ok(json_with_etag(
+        &headers,
+        &AcpEventsResponse {
+            events: events
+                .into_iter()
+                .map(|(sequence, payload)| AcpEventEnvelope {
+                    sequence,
+                    parent_id: subagents::parent_id(&payload),
+                    payload,
+                })
+                .collect(),
+            next_offset,
+        },
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/acp/{server_id}/requests",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id"),
+        ("sessionId" = Option<String>, Query, description = "Only list requests for this ACP session")
+    ),
+    responses(
+        (status = 200, description = "Agent requests waiting for a client response, oldest first", body = AcpPendingRequestsResponse),
+        (status = 404, description = "Unknown ACP server", body = ProblemDetails)
+    )
+)]
+async fn get_v1_acp_requests(
     State(state): State<Arc<AppState>>,
     Path(server_id): Path<String>,
+    Query(query): Query<AcpPendingRequestsQuery>,
+) -> Result<Json<AcpPendingRequestsResponse>, ApiError> {
+    let requests = state
+        .acp_proxy()
+        .pending_requests(&server_id, query.session_id.as_deref())
+        .await?;
+    let now = now_ms();
+    Ok(Json(AcpPendingRequestsResponse {
+        requests: requests
+            .into_iter()
+            .map(|request| AcpPendingRequestInfo {
+                sequence: request.sequence,
+                id: request.id,
+                method: request.method,
+                session_id: request.session_id,
+                payload: request.payload,
+                received_at_ms: request.received_at_ms,
+                age_ms: now.saturating_sub(request.received_at_ms).max(0) as u64,
+                expires_at_ms: request.expires_at_ms,
+            })
+            .collect(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/acp/{server_id}/sessions/{session_id}/plan",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id"),
+        ("session_id" = String, Path, description = "ACP session id")
+    ),
+    responses(
+        (status = 200, description = "The plan from the session's latest planning turn", body = AcpPlanResponse),
+        (status = 404, description = "Unknown ACP server, or no planning turn produced a plan", body = ProblemDetails)
+    )
+)]
+async fn get_v1_acp_session_plan(
+    State(state): State<Arc<AppState>>,
+    Path((server_id, session_id)): Path<(String, String)>,
+) -> Result<Json<AcpPlanResponse>, ApiError> {
+    let plan = state
+        .acp_proxy()
+        .session_plan(&server_id, &session_id)
+        .await?;
+    Ok(Json(AcpPlanResponse {
+        session_id: plan.session_id,
+        turn: plan.turn,
+        read_only: plan.violations.is_empty(),
+        text: plan.text,
+        entries: plan.entries,
+        source: plan.source.as_str().to_string(),
+        violations: plan
+            .violations
+            .into_iter()
+            .map(|violation| AcpPlanViolation {
+                tool_call_id: violation.tool_call_id,
+                kind: violation.kind,
+                title: violation.title,
+            })
+            .collect(),
+        created_at_ms: plan.created_at_ms,
+        approval: plan.approval.map(map_plan_approval),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/acp/{server_id}/sessions/{session_id}/plan/approve",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id"),
+        ("session_id" = String, Path, description = "ACP session id")
+    ),
+    request_body = AcpPlanApproveRequest,
+    responses(
+        (status = 200, description = "Session switched out of plan mode and the build turn finished", body = AcpPlanApproveResponse),
+        (status = 400, description = "`modeId` is `plan`", body = ProblemDetails),
+        (status = 404, description = "Unknown ACP server, or the session has no plan", body = ProblemDetails),
+        (status = 409, description = "The latest plan is already approved", body = ProblemDetails),
+        (status = 502, description = "Agent rejected the mode switch or the build turn", body = ProblemDetails)
+    )
+)]
+async fn post_v1_acp_session_plan_approve(
+    State(state): State<Arc<AppState>>,
+    Path((server_id, session_id)): Path<(String, String)>,
+    Json(request): Json<AcpPlanApproveRequest>,
+) -> Result<Json<AcpPlanApproveResponse>, ApiError> {
+    let approved = state
+        .acp_proxy()
+        .approve_plan(&server_id, &session_id, request.mode_id, request.note)
+        .await?;
+    Ok(Json(AcpPlanApproveResponse {
+        session_id: approved.session_id,
+        approval: map_plan_approval(approved.approval),
+        stop_reason: approved.stop_reason,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/acp/{server_id}/sessions/{session_id}/children",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id"),
+        ("session_id" = String, Path, description = "ACP session id")
+    ),
+    responses(
+        (status = 200, description = "Sub-agents the session started, in the order they started", body = AcpChildSessionsResponse),
+        (status = 404, description = "Unknown ACP server", body = ProblemDetails)
+    )
+)]
+async fn get_v1_acp_session_children(
+    State(state): State<Arc<AppState>>,
+    Path((server_id, session_id)): Path<(String, String)>,
+) -> Result<Json<AcpChildSessionsResponse>, ApiError> {
+    let children = state
+        .acp_proxy()
+        .child_sessions(&server_id, &session_id)
+        .await?;
+    Ok(Json(AcpChildSessionsResponse {
+        session_id,
+        child_sessions: children.into_iter().map(map_child_session).collect(),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/acp/{server_id}/sessions/{session_id}/retry",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id"),
+        ("session_id" = String, Path, description = "ACP session id")
+    ),
+    request_body = AcpRetryRequest,
+    responses(
+        (status = 200, description = "The last turn was retracted and its prompt ran again", body = AcpRetryResponse),
+        (status = 400, description = "Empty text", body = ProblemDetails),
+        (status = 404, description = "Unknown ACP server, or the session has no prompt yet", body = ProblemDetails),
+        (status = 409, description = "The session has a prompt in flight", body = ProblemDetails),
+        (status = 502, description = "Agent rejected the new turn", body = ProblemDetails)
+    )
+)]
+async fn post_v1_acp_session_retry(
+    State(state): State<Arc<AppState>>,
+    Path((server_id, session_id)): Path<(String, String)>,
+    Json(request): Json<AcpRetryRequest>,
+) -> Result<Json<AcpRetryResponse>, ApiError> {
+    let retry = state
+        .acp_proxy()
+        .retry_turn(&server_id, &session_id, request.text)
+        .await?;
+    Ok(Json(AcpRetryResponse {
+        session_id: retry.session_id,
+        retracted: map_retracted_turn(retry.retracted),
+        stop_reason: retry.stop_reason,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/acp/{server_id}/sessions/{session_id}/interrupt",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id"),
+        ("session_id" = String, Path, description = "ACP session id")
+    ),
+    responses(
+        (status = 200, description = "The session's turn ended, or it had none in flight", body = AcpInterruptResponse),
+        (status = 404, description = "Unknown ACP server", body = ProblemDetails)
+    )
+)]
+async fn post_v1_acp_session_interrupt(
+    State(state): State<Arc<AppState>>,
+    Path((server_id, session_id)): Path<(String, String)>,
+) -> Result<Json<AcpInterruptResponse>, ApiError> {
+    let outcome = state.acp_proxy().interrupt(&server_id, &session_id).await?;
+    Ok(Json(AcpInterruptResponse {
+        session_id,
+        outcome: outcome.as_str().to_string(),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/acp/{server_id}/sessions/{session_id}/messages",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id"),
+        ("session_id" = String, Path, description = "ACP session id")
+    ),
+    request_body = AcpQueueMessageRequest,
+    responses(
+        (status = 201, description = "Message queued; it is sent once the session has no prompt in flight", body = AcpQueuedMessage),
+        (status = 400, description = "Empty prompt or unsupported `when`", body = ProblemDetails),
+        (status = 404, description = "Unknown ACP server", body = ProblemDetails)
+    )
+)]
+async fn post_v1_acp_session_messages(
+    State(state): State<Arc<AppState>>,
+    Path((server_id, session_id)): Path<(String, String)>,
+    Json(request): Json<AcpQueueMessageRequest>,
+) -> Result<(StatusCode, Json<AcpQueuedMessage>), ApiError> {
+    if let Some(when) = request
+        .when
+        .as_deref()
+        .filter(|when| *when != message_queue::AFTER_CURRENT_TURN)
+    {
+        return Err(SandboxError::InvalidRequest {
+            message: format!(
+                "when '{when}' is not supported; use '{}'",
+                message_queue::AFTER_CURRENT_TURN
+            ),
+        }
+        .into());
+    }
+    let message = state
+        .acp_proxy()
+        .queue_message(&server_id, &session_id, request.prompt)
+        .await?;
+    Ok((StatusCode::CREATED, Json(map_queued_message(message))))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/acp/{server_id}/sessions/{session_id}/messages",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id"),
+        ("session_id" = String, Path, description = "ACP session id")
+    ),
+    responses(
+        (status = 200, description = "Messages queued or being sent, oldest first", body = AcpQueuedMessagesResponse),
+        (status = 404, description = "Unknown ACP server", body = ProblemDetails)
+    )
+)]
+async fn get_v1_acp_session_messages(
+    State(state): State<Arc<AppState>>,
+    Path((server_id, session_id)): Path<(String, String)>,
+) -> Result<Json<AcpQueuedMessagesResponse>, ApiError> {
+    let messages = state
+        .acp_proxy()
+        .queued_messages(&server_id, &session_id)
+        .await?;
+    Ok(Json(AcpQueuedMessagesResponse {
+        session_id,
+        messages: messages.into_iter().map(map_queued_message).collect(),
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/acp/{server_id}/sessions/{session_id}/messages/{message_id}",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id"),
+        ("session_id" = String, Path, description = "ACP session id"),
+        ("message_id" = String, Path, description = "Queued message id")
+    ),
+    responses(
+        (status = 204, description = "Message removed from the queue"),
+        (status = 404, description = "Unknown ACP server or message", body = ProblemDetails),
+        (status = 409, description = "The message was already sent", body = ProblemDetails)
+    )
+)]
+async fn delete_v1_acp_session_message(
+    State(state): State<Arc<AppState>>,
+    Path((server_id, session_id, message_id)): Path<(String, String, String)>,
 ) -> Result<StatusCode, ApiError> {
-    state.acp_proxy().delete(&server_id).await?;
+    state
+        .acp_proxy()
+        .cancel_message(&server_id, &session_id, &message_id)
+        .await?;
     Ok(StatusCode::NO_CONTENT)
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/acp/{server_id}/sessions/{session_id}/artifacts/{artifact_id}",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id"),
+        ("session_id" = String, Path, description = "Agent session id"),
+        ("artifact_id" = String, Path, description = "Artifact id from `_meta[\"sandboxagent.dev\"].artifacts` of a tool call update")
+    ),
+    responses(
+        (status = 200, description = "Full tool output the update carries a preview of, as text/plain, or application/json for a structured `rawOutput`"),
+        (status = 404, description = "No such artifact", body = ProblemDetails)
+    )
+)]
+async fn get_v1_acp_session_artifact(
+    State(state): State<Arc<AppState>>,
+    Path((server_id, session_id, artifact_id)): Path<(String, String, String)>,
+) -> Result<Response, ApiError> {
+    let (mime_type, content) = state
+        .acp_proxy()
+        .artifact(&server_id, &session_id, &artifact_id)
+        .await?;
+    Ok(([(header::CONTENT_TYPE, mime_type)], content).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/acp/{server_id}/sessions/{session_id}",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id"),
+        ("session_id" = String, Path, description = "ACP session id")
+    ),
+    responses(
+        (status = 200, description = "Snapshot of the session: modes, model, pending requests, buffered events, and how it ended once its agent process exited", body = AcpSessionDetailResponse),
+        (status = 404, description = "No such session on the server", body = ProblemDetails)
+    )
+)]
+async fn get_v1_acp_session(
+    State(state): State<Arc<AppState>>,
+    Path((server_id, session_id)): Path<(String, String)>,
+) -> Result<Json<AcpSessionDetailResponse>, ApiError> {
+    let detail = state
+        .acp_proxy()
+        .session_detail(&server_id, &session_id)
+        .await?;
+    Ok(Json(session_detail_response(detail)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/v1/acp/{server_id}/sessions/{session_id}",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id"),
+        ("session_id" = String, Path, description = "ACP session id")
+    ),
+    request_body = AcpSessionUpdateRequest,
+    responses(
+        (status = 200, description = "Settings applied; snapshot of the session after the change", body = AcpSessionDetailResponse),
+        (status = 400, description = "No field set, or a mode, model or permission mode the agent does not offer", body = ProblemDetails),
+        (status = 404, description = "No such session on the server", body = ProblemDetails),
+        (status = 409, description = "The session has ended, or the agent only applies the permission mode at launch", body = ProblemDetails),
+        (status = 502, description = "Agent rejected `session/set_mode` or `session/set_model`", body = ProblemDetails)
+    )
+)]
+async fn patch_v1_acp_session(
+    State(state): State<Arc<AppState>>,
+    Path((server_id, session_id)): Path<(String, String)>,
+    Json(request): Json<AcpSessionUpdateRequest>,
+) -> Result<Json<AcpSessionDetailResponse>, ApiError> {
+    let detail = state
+        .acp_proxy()
+        .patch_session(
+            &server_id,
+            &session_id,
+            AcpSessionPatch {
+                mode_id: request.mode_id,
+                model_id: request.model_id,
+                permission_mode: request.permission_mode,
+            },
+        )
+        .await?;
+    Ok(Json(session_detail_response(detail)))
+}
+
+fn session_detail_response(detail: AcpSessionDetail) -> AcpSessionDetailResponse {
+    AcpSessionDetailResponse {
+        server_id: detail.server_id,
+        session_id: detail.session_id,
+        agent: detail.record.agent.as_str().to_string(),
+        created_at_ms: detail.record.created_at_ms,
+        mode_id: detail.mode_id,
+        available_modes: detail.record.available_modes,
+        model_id: detail.record.model_id,
+        available_models: detail.record.available_models,
+        prompt_in_flight: detail.prompt_in_flight,
+        prompts: detail.record.prompts,
+        last_prompt_at_ms: detail.record.last_prompt_at_ms,
+        pending_permissions: detail.pending_permissions as u64,
+        pending_questions: detail.pending_questions as u64,
+        buffered_events: detail.buffered_events as u64,
+        next_offset: detail.next_offset,
+        ended: detail.record.ended.map(|end| AcpSessionEndInfo {
+            reason: end.reason.as_str().to_string(),
+            exit_code: end.exit_code,
+            signal: end.signal,
+            ended_at_ms: end.ended_at_ms,
+            duration_ms: end.duration_ms,
+        }),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/acp/{server_id}/sessions/{session_id}/transcript",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id"),
+        ("session_id" = String, Path, description = "ACP session id"),
+        ("format" = Option<String>, Query, description = "`json` (default), `text` for the assistant's messages as text/plain, or `markdown` for every message under a role heading")
+    ),
+    responses(
+        (status = 200, description = "Messages merged from the session's buffered `agent_message_chunk` and `user_message_chunk` updates", body = AcpTranscriptResponse),
+        (status = 400, description = "Unknown format", body = ProblemDetails),
+        (status = 404, description = "Unknown ACP server", body = ProblemDetails)
+    )
+)]
+async fn get_v1_acp_session_transcript(
+    State(state): State<Arc<AppState>>,
+    Path((server_id, session_id)): Path<(String, String)>,
+    Query(query): Query<AcpTranscriptQuery>,
+) -> Result<Response, ApiError> {
+    let format = query.format.as_deref().unwrap_or("json");
+    if !matches!(format, "json" | "text" | "markdown") {
+        return Err(SandboxError::InvalidRequest {
+            message: format!("format '{format}' is not supported; use json, text or markdown"),
+        }
+        .into());
+    }
+    let events = state
+        .acp_proxy()
+        .poll_events(&server_id, None, usize::MAX, Duration::ZERO, false)
+        .await?;
+    let messages = transcript::collect(&events, &session_id);
+    Ok(match format {
+        "text" => (
+            [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+            transcript::to_text(&messages),
+        )
+            .into_response(),
+        "markdown" => (
+            [(header::CONTENT_TYPE, "text/markdown; charset=utf-8")],
+            transcript::to_markdown(&messages),
+        )
+            .into_response(),
+        _ => Json(AcpTranscriptResponse {
+            session_id,
+            messages: messages
+                .into_iter()
+                .map(|message| AcpTranscriptMessage {
+                    role: message.role.to_string(),
+                    text: message.text,
+                    turn: message.turn,
+                })
+                .collect(),
+        })
+        .into_response(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/acp/{server_id}/sessions/{session_id}/result",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id"),
+        ("session_id" = String, Path, description = "ACP session id"),
+        ("timeoutMs" = Option<u64>, Query, description = "How long to wait for a prompt in flight to finish (default 60000, max 1800000)")
+    ),
+    responses(
+        (status = 200, description = "Outcome of the session's latest turn", body = AcpTurnResultResponse),
+        (status = 404, description = "Unknown ACP server, or the session has no prompt yet", body = ProblemDetails),
+        (status = 504, description = "The prompt was still in flight when the timeout expired", body = ProblemDetails)
+    )
+)]
+async fn get_v1_acp_session_result(
+    State(state): State<Arc<AppState>>,
+    Path((server_id, session_id)): Path<(String, String)>,
+    Query(query): Query<AcpTurnResultQuery>,
+) -> Result<Json<AcpTurnResultResponse>, ApiError> {
+    let wait = Duration::from_millis(
+        query
+            .timeout_ms
+            .unwrap_or(DEFAULT_TURN_RESULT_TIMEOUT_MS)
+            .min(MAX_TURN_RESULT_TIMEOUT_MS),
+    );
+    let result = state
+        .acp_proxy()
+        .turn_result(&server_id, &session_id, wait)
+        .await?;
+    let summary = result.summary;
+    Ok(Json(AcpTurnResultResponse {
+        session_id: result.session_id,
+        turn: result.turn,
+        status: summary.status.as_str().to_string(),
+        stop_reason: summary.stop_reason,
+        error: summary.error,
+        message: summary.message,
+        usage: summary
+            .usage
+            .and_then(|usage| serde_json::from_value(usage).ok()),
+        changed_files: summary
+            .changed_files
+            .into_iter()
+            .map(|changed| AcpChangedFile {
+                path: changed.path,
+                kind: changed.kind,
+            })
+            .collect(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/acp/{server_id}/turns/{turn}/logs",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id"),
+        ("turn" = u64, Path, description = "Turn number from the `turn` field of `_adapter/turn_started`")
+    ),
+    responses(
+        (status = 200, description = "Every stdout and stderr line the agent process wrote during the turn, as text/plain, one `[stdout]` or `[stderr]` line each"),
+        (status = 400, description = "Turn logs are off (`SANDBOX_AGENT_TURN_LOG_DIR` is unset)", body = ProblemDetails),
+        (status = 404, description = "No log kept for this turn", body = ProblemDetails)
+    )
+)]
+async fn get_v1_acp_turn_logs(
+    State(state): State<Arc<AppState>>,
+    Path((server_id, turn)): Path<(String, u64)>,
+) -> Result<Response, ApiError> {
+    let text = state.acp_proxy().turn_log(&server_id, turn).await?;
+    Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], text).into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/acp/{server_id}/attachments",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id"),
+        ("dir" = Option<String>, Query, description = "Directory to store the files in, such as the session `cwd`; defaults to a directory removed with the server")
+    ),
+    request_body(content = String, description = "multipart/form-data body; every part with a filename is stored as one file", content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "Stored files, in upload order", body = AcpAttachmentsResponse),
+        (status = 400, description = "Body is not multipart or a part has no filename", body = ProblemDetails),
+        (status = 404, description = "Unknown ACP server", body = ProblemDetails),
+        (status = 413, description = "Upload is larger than `SANDBOX_AGENT_MAX_ATTACHMENT_BYTES`", body = ProblemDetails)
+    )
+)]
+async fn post_v1_acp_attachments(
+    State(state): State<Arc<AppState>>,
+    Path(server_id): Path<String>,
+    Query(query): Query<AcpAttachmentsQuery>,
+    namespace: Option<Extension<Namespace>>,
+    multipart: Result<Multipart, MultipartRejection>,
+) -> Result<Json<AcpAttachmentsResponse>, ApiError> {
+    let mut multipart = multipart.map_err(|err| SandboxError::InvalidRequest {
+        message: err.body_text(),
+    })?;
+    if namespace.is_some() && query.dir.is_some() {
+        return Err(SandboxError::PermissionDenied {
+            message: Some("namespace tokens cannot choose an attachment dir".to_string()),
+        }
+        .into());
+    }
+    let dir = query.dir.as_deref().map(resolve_fs_path).transpose()?;
+    let runtime = state.acp_proxy();
+    let dir = runtime.attachment_dir(&server_id, dir).await?;
+    let limit_bytes = runtime.attachment_limit();
+
+    let mut attachments = Vec::new();
+    loop {
+        let field = multipart.next_field().await.map_err(|err| {
+            if err.status() == StatusCode::PAYLOAD_TOO_LARGE {
+                request_limits::body_too_large(limit_bytes)
+            } else {
+                SandboxError::InvalidRequest {
+                    message: format!("invalid multipart body: {err}"),
+                }
+            }
+        })?;
+        let Some(field) = field else {
+            break;
+        };
+        if field.file_name().is_none() {
+            continue;
+        }
+        let stored = attachments::store(&dir, field, limit_bytes).await?;
+        let path = stored.path.to_string_lossy().into_owned();
+        attachments.push(AcpAttachmentInfo {
+            name: stored.name,
+            uri: format!("file://{path}"),
+            path,
+            mime_type: stored.mime_type,
+            size_bytes: stored.size_bytes,
+        });
+    }
+    Ok(Json(AcpAttachmentsResponse { attachments }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/acp/{server_id}",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id")
+    ),
+    responses(
+        (status = 204, description = "ACP server closed")
+    )
+)]
+async fn delete_v1_acp(
+    State(state): State<Arc<AppState>>,
+    Path(server_id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    state.acp_proxy().delete(&server_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/acp/{server_id}/compact",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id")
+    ),
+    request_body = AcpCompactRequest,
+    responses(
+        (status = 200, description = "Compaction turn finished; an `_adapter/compaction` notification was appended to the stream", body = AcpCompactResponse),
+        (status = 400, description = "Missing session id", body = ProblemDetails),
+        (status = 404, description = "Unknown ACP server", body = ProblemDetails),
+        (status = 502, description = "Agent rejected the compaction turn", body = ProblemDetails)
+    )
+)]
+async fn post_v1_acp_compact(
+    State(state): State<Arc<AppState>>,
+    Path(server_id): Path<String>,
+    Json(request): Json<AcpCompactRequest>,
+) -> Result<Json<AcpCompactResponse>, ApiError> {
+    if request.session_id.trim().is_empty() {
+        return Err(SandboxError::InvalidRequest {
+            message: "sessionId must not be empty".to_string(),
+        }
+        .into());
+    }
+    let compaction = state
+        .acp_proxy()
+        .compact(&server_id, &request.session_id)
+        .await?;
+    Ok(Json(AcpCompactResponse {
+        session_id: compaction.session_id,
+        mode: match compaction.mode {
+            CompactionMode::Native => AcpCompactionMode::Native,
+            CompactionMode::Summary => AcpCompactionMode::Summary,
+        },
+        before_tokens: compaction.before_tokens,
+        after_tokens: compaction.after_tokens,
+        summary: compaction.summary,
+        stop_reason: compaction.stop_reason,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/acp/{server_id}/import",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id")
+    ),
+    request_body = AcpImportSessionRequest,
+    responses(
+        (status = 200, description = "Native session loaded into the ACP server", body = AcpImportSessionResponse),
+        (status = 400, description = "Empty nativeSessionId or cwd, or agent cannot load native sessions", body = ProblemDetails),
+        (status = 409, description = "ACP server bound to different agent", body = ProblemDetails),
+        (status = 502, description = "Agent rejected the session load", body = ProblemDetails)
+    )
+)]
+async fn post_v1_acp_import(
+    State(state): State<Arc<AppState>>,
+    Path(server_id): Path<String>,
+    namespace: Option<Extension<Namespace>>,
+    Json(request): Json<AcpImportSessionRequest>,
+) -> Result<Json<AcpImportSessionResponse>, ApiError> {
+    let agent = AgentId::parse(&request.agent).ok_or_else(|| SandboxError::UnsupportedAgent {
+        agent: request.agent.clone(),
+    })?;
+    if request.native_session_id.trim().is_empty() {
+        return Err(SandboxError::InvalidRequest {
+            message: "nativeSessionId must not be empty".to_string(),
+        }
+        .into());
+    }
+    if request.cwd.trim().is_empty() {
+        return Err(SandboxError::InvalidRequest {
+            message: "cwd must not be empty".to_string(),
+        }
+        .into());
+    }
+    if let Some(Extension(namespace)) = &namespace {
+        state.namespaces.check_cwd(namespace, &request.cwd)?;
+    }
+
+    let result = state
+        .acp_proxy()
+        .import_session(&server_id, agent, &request.native_session_id, &request.cwd)
+        .await?;
+
+    Ok(Json(AcpImportSessionResponse {
+        server_id: caller_server_id(namespace, server_id),
+        agent: agent.as_str().to_string(),
+        session_id: request.native_session_id,
+        result: if result.is_null() { None } else { Some(result) },
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/acp/{server_id}/secrets",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id")
+    ),
+    request_body = AcpSecretsRequest,
+    responses(
+        (status = 200, description = "Stored secret names (values are never returned)", body = AcpSecretsResponse),
+        (status = 400, description = "Invalid secret name", body = ProblemDetails)
+    )
+)]
+async fn post_v1_acp_secrets(
+    State(state): State<Arc<AppState>>,
+    Path(server_id): Path<String>,
+    Json(request): Json<AcpSecretsRequest>,
+) -> Result<Json<AcpSecretsResponse>, ApiError> {
+    let secrets = request
+        .secrets
+        .into_iter()
+        .map(|secret| SecretInput {
+            name: secret.name,
+            value: secret.value,
+            as_file: secret.file,
+        })
+        .collect();
+    let (names, restart_required) = state.acp_proxy().put_secrets(&server_id, secrets).await?;
+    Ok(Json(AcpSecretsResponse {
+        names,
+        restart_required,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/acp/{server_id}/validate",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id"),
+        ("agent" = Option<String>, Query, description = "Agent id the first POST would bootstrap")
+    ),
+    request_body = AcpEnvelope,
+    responses(
+        (status = 200, description = "Validation result; nothing is installed or started", body = AcpValidateResponse)
+    )
+)]
+async fn post_v1_acp_validate(
+    State(state): State<Arc<AppState>>,
+    Path(server_id): Path<String>,
+    Query(query): Query<AcpPostQuery>,
+    namespace: Option<Extension<Namespace>>,
+    Json(payload): Json<Value>,
+) -> Result<Json<AcpValidateResponse>, ApiError> {
+    let validation = state
+        .acp_proxy()
+        .validate(&server_id, query.agent.as_deref(), &payload)
+        .await;
+    Ok(Json(AcpValidateResponse {
+        server_id: caller_server_id(namespace, server_id),
+        valid: validation.problems.is_empty(),
+        agent: validation.agent.map(|agent| agent.as_str().to_string()),
+        exists: validation.exists,
+        agent_installed: validation.agent_installed,
+        env_keys: validation.env_keys,
+        problems: validation
+            .problems
+            .into_iter()
+            .map(|problem| AcpValidationProblemInfo {
+                field: problem.field,
+                message: problem.message,
+            })
+            .collect(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/stream",
+    tag = "v1",
+    params(
+        ("all" = Option<bool>, Query, description = "When true, follow every ACP server: running ones from their latest envelope and new ones from their first"),
+        ("agent" = Option<String>, Query, description = "With all=true, only follow servers running this agent"),
+        ("methods" = Option<String>, Query, description = "With all=true, comma-separated envelope methods to forward; responses and other methods are dropped")
+    ),
+    responses(
+        (status = 200, description = "SSE stream: a `channel` event with the channel id, then `message` events ({serverId, sequence, payload}) and `closed` events for subscribed servers"),
+        (status = 400, description = "Unknown agent, or a filter without all=true", body = ProblemDetails),
+        (status = 406, description = "Client does not accept SSE responses", body = ProblemDetails)
+    )
+)]
+async fn get_v1_stream(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<StreamQuery>,
+    namespace: Option<Extension<Namespace>>,
+    headers: HeaderMap,
+) -> Result<Sse<PinBoxSseStream>, ApiError> {
+    if !accept_allows(&headers, TEXT_EVENT_STREAM) {
+        return Err(SandboxError::NotAcceptable {
+            message: "accept must allow text/event-stream".to_string(),
+        }
+        .into());
+    }
+
+    let namespace = namespace.map(|Extension(namespace)| namespace);
+    let stream = if query.all.unwrap_or(false) {
+        let agent = query
+            .agent
+            .as_deref()
+            .map(str::trim)
+            .map(|agent| {
+                AgentId::parse(agent).ok_or_else(|| SandboxError::UnsupportedAgent {
+                    agent: agent.to_string(),
+                })
+            })
+            .transpose()?;
+        let methods = query.methods.as_deref().map(|methods| {
+            methods
+                .split(',')
+                .map(str::trim)
+                .filter(|method| !method.is_empty())
+                .map(ToOwned::to_owned)
+                .collect()
+        });
+        state.stream_mux.open_firehose(
+            state.acp_proxy().as_ref().clone(),
+            FirehoseFilter { agent, methods },
+            namespace,
+        )
+    } else {
+        if query.agent.is_some() || query.methods.is_some() {
+            return Err(SandboxError::InvalidRequest {
+                message: "agent and methods filters require all=true".to_string(),
+            }
+            .into());
+        }
+        state.stream_mux.open(namespace)
+    };
+
+    Ok(Sse::new(stream).keep_alive(
+        KeepAlive::new()
+            .interval(Duration::from_secs(15))
+            .text("heartbeat"),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/stream/{channel_id}/subscribe",
+    tag = "v1",
+    params(
+        ("channel_id" = String, Path, description = "Channel id from the stream's `channel` event")
+    ),
+    request_body = StreamSubscribeRequest,
+    responses(
+        (status = 200, description = "Servers the channel is subscribed to", body = StreamSubscriptionsResponse),
+        (status = 404, description = "Unknown channel or ACP server", body = ProblemDetails)
+    )
+)]
+async fn post_v1_stream_subscribe(
+    State(state): State<Arc<AppState>>,
+    Path(channel_id): Path<String>,
+    namespace: Option<Extension<Namespace>>,
+    Json(request): Json<StreamSubscribeRequest>,
+) -> Result<Json<StreamSubscriptionsResponse>, ApiError> {
+    let server_ids = state
+        .stream_mux
+        .subscribe(
+            &state.acp_proxy(),
+            &channel_id,
+            namespace.as_ref().map(|Extension(namespace)| namespace),
+            request.server_ids,
+            request.last_event_ids,
+        )
+        .await?;
+    Ok(Json(StreamSubscriptionsResponse {
+        channel_id,
+        server_ids,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/stream/{channel_id}/unsubscribe",
+    tag = "v1",
+    params(
+        ("channel_id" = String, Path, description = "Channel id from the stream's `channel` event")
+    ),
+    request_body = StreamUnsubscribeRequest,
+    responses(
+        (status = 200, description = "Servers the channel is still subscribed to", body = StreamSubscriptionsResponse),
+        (status = 404, description = "Unknown channel", body = ProblemDetails)
+    )
+)]
+async fn post_v1_stream_unsubscribe(
+    State(state): State<Arc<AppState>>,
+    Path(channel_id): Path<String>,
+    namespace: Option<Extension<Namespace>>,
+    Json(request): Json<StreamUnsubscribeRequest>,
+) -> Result<Json<StreamSubscriptionsResponse>, ApiError> {
+    let server_ids = state.stream_mux.unsubscribe(
+        &channel_id,
+        namespace.as_ref().map(|Extension(namespace)| namespace),
+        &request.server_ids,
+    )?;
+    Ok(Json(StreamSubscriptionsResponse {
+        channel_id,
+        server_ids,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/tasks",
+    tag = "v1",
+    responses(
+        (status = 200, description = "Every task, oldest first", body = TaskListResponse)
+    )
+)]
+async fn get_v1_tasks(State(state): State<Arc<AppState>>) -> Json<TaskListResponse> {
+    Json(TaskListResponse {
+        tasks: state.tasks.list().into_iter().map(task_info).collect(),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/tasks",
+    tag = "v1",
+    request_body = TaskCreateRequest,
+    responses(
+        (status = 201, description = "Task started; poll it for each run's status and output", body = TaskInfo),
+        (status = 400, description = "Empty prompt, no agents, or an unknown agent", body = ProblemDetails)
+    )
+)]
+async fn post_v1_tasks(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<TaskCreateRequest>,
+) -> Result<(StatusCode, Json<TaskInfo>), ApiError> {
+    let spec = task_spec(request.prompt, request.agents, request.cwd)?;
+    let record = state.tasks.start(state.acp_proxy().as_ref().clone(), spec);
+    Ok((StatusCode::CREATED, Json(task_info(record))))
+}
+
+/// Validate a task body shared by `/v1/tasks` and `/v1/schedules`.
+fn task_spec(
+    prompt: String,
+    agents: Vec<TaskAgentConfig>,
+    cwd: Option<String>,
+) -> Result<TaskSpec, SandboxError> {
+    if prompt.trim().is_empty() {
+        return Err(SandboxError::InvalidRequest {
+            message: "prompt must not be empty".to_string(),
+        });
+    }
+    if agents.is_empty() {
+        return Err(SandboxError::InvalidRequest {
+            message: "agents must list at least one agent".to_string(),
+        });
+    }
+    let agents = agents
+        .into_iter()
+        .map(|config| {
+            let agent = AgentId::parse(config.agent.trim()).ok_or_else(|| {
+                SandboxError::UnsupportedAgent {
+                    agent: config.agent.clone(),
+                }
+            })?;
+            Ok(TaskAgent {
+                agent,
+                env: config.env,
+            })
+        })
+        .collect::<Result<Vec<_>, SandboxError>>()?;
+    let cwd = match cwd {
+        Some(cwd) => cwd,
+        None => std::env::current_dir()
+            .map_err(|err| SandboxError::StreamError {
+                message: err.to_string(),
+            })?
+            .to_string_lossy()
+            .to_string(),
+    };
+    Ok(TaskSpec {
+        prompt,
+        cwd,
+        agents,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/tasks/{task_id}",
+    tag = "v1",
+    params(
+        ("task_id" = String, Path, description = "Task id returned when the task was created")
+    ),
+    responses(
+        (status = 200, description = "Status and output of every run", body = TaskInfo),
+        (status = 404, description = "Unknown task", body = ProblemDetails)
+    )
+)]
+async fn get_v1_task(
+    State(state): State<Arc<AppState>>,
+    Path(task_id): Path<String>,
+) -> Result<Json<TaskInfo>, ApiError> {
+    let record = state
+        .tasks
+        .get(&task_id)
+        .ok_or(SandboxError::SessionNotFound {
+            session_id: task_id,
+        })?;
+    Ok(Json(task_info(record)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/tasks/{task_id}",
+    tag = "v1",
+    params(
+        ("task_id" = String, Path, description = "Task id returned when the task was created")
+    ),
+    responses(
+        (status = 204, description = "Task forgotten and its ACP servers closed"),
+        (status = 404, description = "Unknown task", body = ProblemDetails)
+    )
+)]
+async fn delete_v1_task(
+    State(state): State<Arc<AppState>>,
+    Path(task_id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    state.tasks.delete(&state.acp_proxy(), &task_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn task_info(record: TaskRecord) -> TaskInfo {
+    TaskInfo {
+        finished: record.is_finished(),
+        task_id: record.task_id,
+        prompt: record.prompt,
+        created_at_ms: record.created_at_ms,
+        runs: record
+            .runs
+            .into_iter()
+            .map(|run| TaskRunInfo {
+                server_id: run.server_id,
+                agent: run.agent.as_str().to_string(),
+                status: match run.status {
+                    TaskRunStatus::Starting => TaskStatus::Starting,
+                    TaskRunStatus::Running => TaskStatus::Running,
+                    TaskRunStatus::Completed => TaskStatus::Completed,
+                    TaskRunStatus::Failed => TaskStatus::Failed,
+                },
+                session_id: run.session_id,
+                stop_reason: run.stop_reason,
+                output: run.output,
+                error: run.error,
+                finished_at_ms: run.finished_at_ms,
+            })
+            .collect(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/schedules",
+    tag = "v1",
+    responses(
+        (status = 200, description = "Every schedule, oldest first", body = ScheduleListResponse)
+    )
+)]
+async fn get_v1_schedules(State(state): State<Arc<AppState>>) -> Json<ScheduleListResponse> {
+    Json(ScheduleListResponse {
+        schedules: state
+            .schedules
+            .list()
+            .into_iter()
+            .map(schedule_info)
+            .collect(),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/schedules",
+    tag = "v1",
+    request_body = ScheduleCreateRequest,
+    responses(
+        (status = 201, description = "Schedule created; each firing starts a task", body = ScheduleInfo),
+        (status = 400, description = "Invalid cron expression, empty prompt, no agents, or an unknown agent", body = ProblemDetails)
+    )
+)]
+async fn post_v1_schedules(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ScheduleCreateRequest>,
+) -> Result<(StatusCode, Json<ScheduleInfo>), ApiError> {
+    let spec = task_spec(request.prompt, request.agents, request.cwd)?;
+    let record = state.schedules.create(
+        state.acp_proxy().as_ref().clone(),
+        state.tasks.clone(),
+        &request.cron,
+        spec,
+    )?;
+    Ok((StatusCode::CREATED, Json(schedule_info(record))))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/schedules/{schedule_id}",
+    tag = "v1",
+    params(
+        ("schedule_id" = String, Path, description = "Schedule id returned when the schedule was created")
+    ),
+    responses(
+        (status = 200, description = "Schedule with its next firing and recent runs", body = ScheduleInfo),
+        (status = 404, description = "Unknown schedule", body = ProblemDetails)
+    )
+)]
+async fn get_v1_schedule(
+    State(state): State<Arc<AppState>>,
+    Path(schedule_id): Path<String>,
+) -> Result<Json<ScheduleInfo>, ApiError> {
+    let record = state
+        .schedules
+        .get(&schedule_id)
+        .ok_or(SandboxError::SessionNotFound {
+            session_id: schedule_id,
+        })?;
+    Ok(Json(schedule_info(record)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/v1/schedules/{schedule_id}",
+    tag = "v1",
+    params(
+        ("schedule_id" = String, Path, description = "Schedule id returned when the schedule was created")
+    ),
+    responses(
+        (status = 204, description = "Schedule stopped; tasks it started are kept"),
+        (status = 404, description = "Unknown schedule", body = ProblemDetails)
+    )
+)]
+async fn delete_v1_schedule(
+    State(state): State<Arc<AppState>>,
+    Path(schedule_id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    state.schedules.delete(&schedule_id)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn schedule_info(record: ScheduleRecord) -> ScheduleInfo {
+    ScheduleInfo {
+        schedule_id: record.schedule_id,
+        cron: record.cron,
+        prompt: record.spec.prompt,
+        cwd: record.spec.cwd,
+        agents: record
+            .spec
+            .agents
+            .iter()
+            .map(|agent| agent.agent.as_str().to_string())
+            .collect(),
+        created_at_ms: record.created_at_ms,
+        next_run_at_ms: record.next_run_at_ms,
+        skipped_runs: record.skipped_runs,
+        runs: record
+            .runs
+            .into_iter()
+            .map(|run| ScheduleRunInfo {
+                task_id: run.task_id,
+                started_at_ms: run.started_at_ms,
+            })
+            .collect(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/compat/anthropic/v1/messages",
+    tag = "v1",
+    request_body = AnthropicMessagesRequest,
+    params(
+        ("x-sandbox-agent" = Option<String>, Header, description = "Agent to run; overrides `model`")
+    ),
+    responses(
+        (status = 200, description = "Messages API `message`, or its SSE events when `stream` is true"),
+        (status = 400, description = "Unknown agent or unsupported message content"),
+        (status = 500, description = "The agent failed before answering")
+    )
+)]
+async fn post_v1_compat_anthropic_messages(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(request): Json<AnthropicMessagesRequest>,
+) -> Response {
+    let result: Result<Response, SandboxError> = async {
+        let agent = anthropic_compat::resolve_agent(
+            headers
+                .get("x-sandbox-agent")
+                .and_then(|value| value.to_str().ok()),
+            &request.model,
+        )?;
+        let turn =
+            anthropic_compat::start_turn(state.acp_proxy().as_ref().clone(), agent, &request)
+                .await?;
+        if request.stream {
+            return Ok(Sse::new(turn.into_sse())
+                .keep_alive(KeepAlive::new().interval(Duration::from_secs(15)))
+                .into_response());
+        }
+        Ok(Json(turn.into_message().await?).into_response())
+    }
+    .await;
+    result.unwrap_or_else(|err| {
+        let problem = problem_from_sandbox_error(&err);
+        anthropic_compat::error_response(
+            StatusCode::from_u16(problem.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            problem.detail.unwrap_or(problem.title),
+        )
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/acp/{server_id}/share",
+    tag = "v1",
+    params(
+        ("server_id" = String, Path, description = "Client-defined ACP server id")
+    ),
+    request_body = AcpShareRequest,
+    responses(
+        (status = 200, description = "Short-lived signed SSE URL", body = AcpShareResponse),
+        (status = 400, description = "Invalid TTL", body = ProblemDetails),
+        (status = 404, description = "Unknown ACP server", body = ProblemDetails)
+    )
+)]
+async fn post_v1_acp_share(
+    State(state): State<Arc<AppState>>,
+    Path(server_id): Path<String>,
+    body: Option<Json<AcpShareRequest>>,
+) -> Result<Json<AcpShareResponse>, ApiError> {
+    let request = body.map(|Json(body)| body).unwrap_or_default();
+    let ttl_ms = request.ttl_ms.unwrap_or(DEFAULT_SHARE_TTL_MS);
+    if ttl_ms == 0 || ttl_ms > MAX_SHARE_TTL_MS {
+        return Err(SandboxError::InvalidRequest {
+            message: format!("ttlMs must be between 1 and {MAX_SHARE_TTL_MS}"),
+        }
+        .into());
+    }
+
+    let exists = state
+        .acp_proxy()
+        .list_instances()
+        .await
+        .iter()
+        .any(|instance| instance.server_id == server_id);
+    if !exists {
+        return Err(SandboxError::SessionNotFound {
+            session_id: server_id,
+        }
+        .into());
+    }
+
+    let expires_at_ms = now_ms() + ttl_ms as i64;
+    let url = match state.auth.token.as_deref() {
+        Some(token) => format!(
+            "/v1/acp/{server_id}?expires={expires_at_ms}&signature={}",
+            sign_sse_access(token, &server_id, expires_at_ms)
+        ),
+        None => format!("/v1/acp/{server_id}"),
+    };
+
+    Ok(Json(AcpShareResponse { url, expires_at_ms }))
+}
+
+/// `server_id` as the caller named it, without a namespace prefix.
+fn caller_server_id(namespace: Option<Extension<Namespace>>, server_id: String) -> String {
+    match namespace {
+        Some(Extension(namespace)) => namespace
+            .unscope(&server_id)
+            .map(str::to_string)
+            .unwrap_or(server_id),
+        None => server_id,
+    }
+}
+
 fn validate_named_query(value: &str, field_name: &str) -> Result<(), SandboxError> {
     if value.trim().is_empty() {
         return Err(SandboxError::InvalidRequest {
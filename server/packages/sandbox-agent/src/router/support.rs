@@ -1,3 +1,9 @@
+use axum::http::Method;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
 use super::*;
 
 pub(super) async fn not_found() -> Response {
@@ -5,6 +11,8 @@ pub(super) async fn not_found() -> Response {
         type_: ErrorType::InvalidRequest.as_urn().to_string(),
         title: "Not Found".to_string(),
         status: 404,
+        code: ErrorCode::NotFound,
+        retryable: false,
         detail: Some("endpoint not found".to_string()),
         instance: None,
         extensions: serde_json::Map::new(),
@@ -18,13 +26,236 @@ pub(super) async fn not_found() -> Response {
         .into_response()
 }
 
+/// Answer a declared body over the limit with a problem document. Bodies
+/// without a `Content-Length` are cut off by `DefaultBodyLimit` instead.
+pub(super) async fn reject_oversized_body(
+    State(max_body_bytes): State<usize>,
+    request: Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let declared = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if declared.is_some_and(|length| length > max_body_bytes as u64) {
+        return Err(request_limits::body_too_large(max_body_bytes).into());
+    }
+    Ok(next.run(request).await)
+}
+
+/// Namespace of a request another replica forwarded here, as
+/// `{namespace}.{signature}`, signed with the server token.
+const FORWARDED_NAMESPACE_HEADER: &str = "x-sandbox-agent-namespace";
+
+/// Route requests made with a namespace token to their namespace's servers
+/// and tag them with it. Runs before routing so path ids are rewritten
+/// before they are matched. Requests forwarded by another replica arrive
+/// rewritten, with the server token and a signed namespace header.
+pub(super) async fn scope_namespace(
+    State(state): State<Arc<AppState>>,
+    mut request: Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let namespace = bearer
+        .filter(|token| state.auth.token.as_deref() != Some(*token))
+        .and_then(|token| state.namespaces.resolve(token));
+    let Some(namespace) = namespace else {
+        if state.auth.token.is_none() {
+            // Namespace tokens are the only credentials, so nothing else,
+            // including `/opencode` and `/ui`, is served without one.
+            return Err(ApiError::Sandbox(SandboxError::TokenInvalid {
+                message: Some("missing or invalid namespace token".to_string()),
+            }));
+        }
+        if request.headers().contains_key(FORWARDED_NAMESPACE_HEADER) {
+            let namespace = forwarded_namespace(&state, &request).ok_or_else(|| {
+                SandboxError::TokenInvalid {
+                    message: Some("invalid forwarded namespace".to_string()),
+                }
+            })?;
+            request.extensions_mut().insert(namespace);
+        }
+        return Ok(next.run(request).await);
+    };
+
+    let path = namespace
+        .route(request.method(), request.uri().path())
+        .ok_or_else(|| SandboxError::PermissionDenied {
+            message: Some(format!(
+                "namespace '{}' tokens cannot use this endpoint",
+                namespace.name()
+            )),
+        })?;
+    let path_and_query = match request.uri().query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path,
+    };
+    let invalid_path = || SandboxError::InvalidRequest {
+        message: "invalid request path".to_string(),
+    };
+    let mut parts = request.uri().clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse().map_err(|_| invalid_path())?);
+    *request.uri_mut() = axum::http::Uri::from_parts(parts).map_err(|_| invalid_path())?;
+    request.extensions_mut().insert(namespace);
+    Ok(next.run(request).await)
+}
+
+/// Forward requests for ACP servers running on another replica to that
+/// replica, and reserve the id of a server about to start here. Runs before
+/// routing, after namespace rewriting. Streams are left to `get_v1_acp`,
+/// which reads them from the cluster, and unauthenticated requests to
+/// `require_token`.
+pub(super) async fn route_to_owner(
+    State(state): State<Arc<AppState>>,
+    mut request: Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let Some(rest) = request.uri().path().strip_prefix("/v1/acp/") else {
+        return Ok(next.run(request).await);
+    };
+    let (server_id, tail) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let targets_server = tail.is_empty();
+    let server_id = server_id.to_string();
+    let is_stream = request.method() == Method::GET && targets_server;
+    if server_id.is_empty()
+        || is_stream
+        || !is_authenticated(&state, &request)
+        || state.acp_proxy().has_server(&server_id).await
+    {
+        return Ok(next.run(request).await);
+    }
+
+    let creates = request.method() == Method::POST && targets_server;
+    let owner = if creates {
+        state.cluster.claim(&server_id).await?
+    } else {
+        state.cluster.remote_owner(&server_id).await?
+    };
+    let Some(owner) = owner else {
+        let response = next.run(request).await;
+        if creates && !state.acp_proxy().has_server(&server_id).await {
+            state.cluster.release(&server_id).await;
+        }
+        return Ok(response);
+    };
+
+    // The request is already authorized, and namespace-rewritten, here. The
+    // owner still needs the namespace for its own checks, such as the
+    // workspace root of a `session/new`.
+    request.headers_mut().remove(FORWARDED_NAMESPACE_HEADER);
+    if let (Some(token), Some(namespace)) = (
+        state.auth.token.as_deref(),
+        request.extensions().get::<Namespace>(),
+    ) {
+        let signature = forwarded_namespace_mac(
+            token,
+            namespace.name(),
+            request.method(),
+            request.uri().path(),
+        )
+        .finalize()
+        .into_bytes();
+        let value = format!("{}.{}", namespace.name(), URL_SAFE_NO_PAD.encode(signature));
+        if let Ok(value) = header::HeaderValue::from_str(&value) {
+            request
+                .headers_mut()
+                .insert(FORWARDED_NAMESPACE_HEADER, value);
+        }
+    }
+    let authorization = state
+        .auth
+        .token
+        .as_ref()
+        .map(|token| format!("Bearer {token}"));
+    let limit_bytes = state
+        .max_body_bytes()
+        .max(state.acp_proxy().attachment_limit());
+    Ok(state
+        .cluster
+        .forward(
+            &owner,
+            &server_id,
+            request,
+            authorization.as_deref(),
+            limit_bytes,
+        )
+        .await?)
+}
+
+/// Namespace of a request forwarded by another replica, when it carries the
+/// server token and a namespace header signed for its method and path.
+fn forwarded_namespace(state: &AppState, request: &Request<axum::body::Body>) -> Option<Namespace> {
+    let token = state.auth.token.as_deref()?;
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if bearer != Some(token) {
+        return None;
+    }
+    let (name, signature) = request
+        .headers()
+        .get(FORWARDED_NAMESPACE_HEADER)?
+        .to_str()
+        .ok()?
+        .split_once('.')?;
+    let decoded = URL_SAFE_NO_PAD.decode(signature).ok()?;
+    forwarded_namespace_mac(token, name, request.method(), request.uri().path())
+        .verify_slice(&decoded)
+        .ok()?;
+    state.namespaces.named(name)
+}
+
+fn forwarded_namespace_mac(
+    token: &str,
+    namespace: &str,
+    method: &Method,
+    path: &str,
+) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(token.as_bytes()).expect("hmac accepts keys of any length");
+    mac.update(format!("forwarded-namespace:{namespace}:{method}:{path}").as_bytes());
+    mac
+}
+
+/// Whether `require_token` would let `request` through on its bearer token
+/// or namespace alone.
+fn is_authenticated(state: &AppState, request: &Request<axum::body::Body>) -> bool {
+    if request.extensions().get::<Namespace>().is_some() {
+        return true;
+    }
+    let Some(expected) = state.auth.token.as_deref() else {
+        return !state.namespaces.is_enabled();
+    };
+    request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        == Some(expected)
+}
+
 pub(super) async fn require_token(
     State(state): State<Arc<AppState>>,
     request: Request<axum::body::Body>,
     next: Next,
 ) -> Result<Response, ApiError> {
-    let Some(expected) = state.auth.token.as_ref() else {
+    if request.extensions().get::<Namespace>().is_some() {
         return Ok(next.run(request).await);
+    }
+    let Some(expected) = state.auth.token.as_ref() else {
+        // Only reached with namespaces configured and no server token:
+        // every request needs a namespace token.
+        return Err(ApiError::Sandbox(SandboxError::TokenInvalid {
+            message: Some("missing or invalid namespace token".to_string()),
+        }));
     };
 
     let bearer = request
@@ -37,13 +268,246 @@ pub(super) async fn require_token(
         return Ok(next.run(request).await);
     }
 
+    // Anthropic clients send their key as `x-api-key`.
+    if request
+        .uri()
+        .path()
+        .ends_with("/compat/anthropic/v1/messages")
+        && request
+            .headers()
+            .get("x-api-key")
+            .and_then(|value| value.to_str().ok())
+            == Some(expected.as_str())
+    {
+        return Ok(next.run(request).await);
+    }
+
+    if signed_sse_access_allowed(expected, &request) {
+        return Ok(next.run(request).await);
+    }
+
     Err(ApiError::Sandbox(SandboxError::TokenInvalid {
         message: Some("missing or invalid bearer token".to_string()),
     }))
 }
 
+/// Cookie that keeps a browser signed in to `/ui` after it opened the
+/// console with `?token=`, so asset and deep-link requests pass too. Its
+/// value is the issue time and a MAC over it, so it lapses on its own.
+const UI_SESSION_COOKIE: &str = "sandbox_agent_ui";
+const UI_SESSION_MAX_AGE_SECS: i64 = 12 * 60 * 60;
+
+#[derive(Debug, Deserialize)]
+struct UiTokenQuery {
+    token: Option<String>,
+}
+
+/// Gate the embedded console behind the server token. A browser cannot send
+/// a bearer header on navigation, so `?token=` is accepted too and answered
+/// with a session cookie derived from the token.
+pub(super) async fn require_ui_token(
+    State(state): State<Arc<AppState>>,
+    request: Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let Some(expected) = state.auth.token.as_ref() else {
+        return Ok(next.run(request).await);
+    };
+
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if bearer == Some(expected.as_str()) || ui_cookie_allowed(expected, &request) {
+        return Ok(next.run(request).await);
+    }
+
+    let query_token = Query::<UiTokenQuery>::try_from_uri(request.uri())
+        .ok()
+        .and_then(|Query(query)| query.token);
+    if query_token.as_deref() == Some(expected.as_str()) {
+        let issued_at_ms = now_ms();
+        let cookie = format!(
+            "{UI_SESSION_COOKIE}={issued_at_ms}.{}; Path=/ui; Max-Age={UI_SESSION_MAX_AGE_SECS}; HttpOnly; SameSite=Strict",
+            URL_SAFE_NO_PAD.encode(
+                ui_session_mac(expected, issued_at_ms)
+                    .finalize()
+                    .into_bytes()
+            )
+        );
+        let mut response = next.run(request).await;
+        if let Ok(value) = header::HeaderValue::from_str(&cookie) {
+            response.headers_mut().append(header::SET_COOKIE, value);
+        }
+        return Ok(response);
+    }
+
+    Err(ApiError::Sandbox(SandboxError::TokenInvalid {
+        message: Some("open the console with ?token=<server token>".to_string()),
+    }))
+}
+
+fn ui_cookie_allowed(token: &str, request: &Request<axum::body::Body>) -> bool {
+    request
+        .headers()
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .filter(|(name, _)| *name == UI_SESSION_COOKIE)
+        .any(|(_, value)| {
+            let Some((issued_at_ms, signature)) = value.split_once('.') else {
+                return false;
+            };
+            let Ok(issued_at_ms) = issued_at_ms.parse::<i64>() else {
+                return false;
+            };
+            let age_ms = now_ms() - issued_at_ms;
+            if !(0..UI_SESSION_MAX_AGE_SECS * 1000).contains(&age_ms) {
+                return false;
+            }
+            URL_SAFE_NO_PAD.decode(signature).is_ok_and(|decoded| {
+                ui_session_mac(token, issued_at_ms)
+                    .verify_slice(&decoded)
+                    .is_ok()
+            })
+        })
+}
+
+fn ui_session_mac(token: &str, issued_at_ms: i64) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(token.as_bytes()).expect("hmac accepts keys of any length");
+    mac.update(format!("ui-session:{issued_at_ms}").as_bytes());
+    mac
+}
+
+/// Query parameters that carry credentials: the console's `token` and the
+/// `signature` of shared stream URLs.
+const SECRET_QUERY_PARAMS: [&str; 2] = ["token", "signature"];
+
+/// `uri` as it may be logged, with credential query values redacted.
+pub(super) fn loggable_uri(uri: &axum::http::Uri) -> String {
+    let Some(query) = uri.query() else {
+        return uri.to_string();
+    };
+    let query = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if SECRET_QUERY_PARAMS.contains(&name) => {
+                format!("{name}=<redacted>")
+            }
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("{}?{query}", uri.path())
+}
+
+#[derive(Debug, Deserialize)]
+struct SignedSseQuery {
+    expires: Option<i64>,
+    signature: Option<String>,
+}
+
+/// Signed share URLs only grant `GET /v1/acp/{server_id}` for the server they
+/// were issued for, and only until `expires`.
+fn signed_sse_access_allowed(token: &str, request: &Request<axum::body::Body>) -> bool {
+    if request.method() != axum::http::Method::GET {
+        return false;
+    }
+    let path = request.uri().path();
+    let path = path.strip_prefix("/v1").unwrap_or(path);
+    let Some(server_id) = path.strip_prefix("/acp/") else {
+        return false;
+    };
+    if server_id.is_empty() || server_id.contains('/') {
+        return false;
+    }
+
+    let Ok(Query(query)) = Query::<SignedSseQuery>::try_from_uri(request.uri()) else {
+        return false;
+    };
+    let (Some(expires), Some(signature)) = (query.expires, query.signature) else {
+        return false;
+    };
+    if expires < now_ms() {
+        return false;
+    }
+
+    verify_sse_signature(token, server_id, expires, &signature)
+}
+
+pub(super) fn sign_sse_access(token: &str, server_id: &str, expires_at_ms: i64) -> String {
+    let digest = sse_signature_mac(token, server_id, expires_at_ms)
+        .finalize()
+        .into_bytes();
+    URL_SAFE_NO_PAD.encode(digest)
+}
+
+fn verify_sse_signature(token: &str, server_id: &str, expires_at_ms: i64, signature: &str) -> bool {
+    let Ok(decoded) = URL_SAFE_NO_PAD.decode(signature) else {
+        return false;
+    };
+    sse_signature_mac(token, server_id, expires_at_ms)
+        .verify_slice(&decoded)
+        .is_ok()
+}
+
+fn sse_signature_mac(token: &str, server_id: &str, expires_at_ms: i64) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(token.as_bytes()).expect("hmac accepts keys of any length");
+    mac.update(format!("acp-sse:{server_id}:{expires_at_ms}").as_bytes());
+    mac
+}
+
 pub(super) type PinBoxSseStream = crate::acp_proxy_runtime::PinBoxSseStream;
 
+const INSTALL_PROGRESS_POLL: Duration = Duration::from_millis(250);
+
+/// Poll the agent manager and emit a `progress` event whenever the running
+/// install advances, finishing with an inactive event once it returns.
+pub(super) fn install_progress_stream(
+    manager: Arc<AgentManager>,
+    agent: AgentId,
+) -> PinBoxSseStream {
+    let stream = futures::stream::unfold(
+        (manager, None::<InstallProgress>, false),
+        move |(manager, last, finished)| async move {
+            if finished {
+                return None;
+            }
+            loop {
+                let Some(current) = manager.install_progress(agent) else {
+                    let event = install_progress_event(agent, None);
+                    return Some((Ok(event), (manager, None, true)));
+                };
+                if last.as_ref() != Some(&current) {
+                    let event = install_progress_event(agent, Some(&current));
+                    return Some((Ok(event), (manager, Some(current), false)));
+                }
+                tokio::time::sleep(INSTALL_PROGRESS_POLL).await;
+            }
+        },
+    );
+    Box::pin(stream)
+}
+
+fn install_progress_event(agent: AgentId, progress: Option<&InstallProgress>) -> Event {
+    let payload = AgentInstallProgressEvent {
+        agent: agent.as_str().to_string(),
+        active: progress.is_some(),
+        url: progress.and_then(|progress| progress.url.clone()),
+        downloaded_bytes: progress.map_or(0, |progress| progress.downloaded_bytes),
+        total_bytes: progress.and_then(|progress| progress.total_bytes),
+    };
+    Event::default()
+        .event("progress")
+        .json_data(&payload)
+        .unwrap_or_else(|_| Event::default().event("progress").data("{}"))
+}
+
 pub(super) fn credentials_available_for(
     agent: AgentId,
     has_anthropic: bool,
@@ -138,6 +602,101 @@ pub(super) fn fallback_config_options(agent: AgentId) -> Vec<Value> {
     }
 }
 
+/// Model catalog for an agent, derived from the same committed resources as
+/// `fallback_config_options()`. Provider and context window are inferred from
+/// the model id; unknown families leave them unset.
+pub(super) fn agent_model_catalog(agent: AgentId) -> AgentModelsResponse {
+    let options = fallback_config_options(agent);
+    let model_option = options
+        .iter()
+        .find(|opt| opt.get("category").and_then(Value::as_str) == Some("model"));
+
+    let default_model = model_option
+        .and_then(|opt| opt.get("currentValue"))
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let models = model_option
+        .and_then(|opt| opt.get("options"))
+        .and_then(Value::as_array)
+        .map(|entries| {
+            entries
+                .iter()
+                .filter_map(|entry| {
+                    let id = entry.get("value").and_then(Value::as_str)?;
+                    let name = entry.get("name").and_then(Value::as_str).unwrap_or(id);
+                    let provider = model_provider(agent, id);
+                    Some(AgentModelInfo {
+                        id: id.to_string(),
+                        name: name.to_string(),
+                        context_window: model_context_window(provider.as_deref(), id),
+                        provider,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    AgentModelsResponse {
+        agent: agent.as_str().to_string(),
+        default_model,
+        models,
+    }
+}
+
+fn model_provider(agent: AgentId, model_id: &str) -> Option<String> {
+    // OpenCode ids are `provider/model`.
+    if let Some((provider, _)) = model_id.split_once('/') {
+        return Some(provider.to_string());
+    }
+    let provider = match agent {
+        AgentId::Claude => "anthropic",
+        AgentId::Codex => "openai",
+        AgentId::Amp => "amp",
+        AgentId::Mock => "mock",
+        AgentId::Pi | AgentId::Opencode => return None,
+        AgentId::Cursor => {
+            let lower = model_id.to_ascii_lowercase();
+            if ["claude", "opus", "sonnet", "haiku"]
+                .iter()
+                .any(|family| lower.contains(family))
+            {
+                "anthropic"
+            } else if lower.starts_with("gpt") {
+                "openai"
+            } else if lower.starts_with("gemini") {
+                "google"
+            } else if lower.starts_with("grok") {
+                "xai"
+            } else {
+                "cursor"
+            }
+        }
+    };
+    Some(provider.to_string())
+}
+
+fn model_context_window(provider: Option<&str>, model_id: &str) -> Option<u64> {
+    let model = model_id
+        .rsplit_once('/')
+        .map(|(_, model)| model)
+        .unwrap_or(model_id)
+        .to_ascii_lowercase();
+    if model.contains("claude")
+        || model.starts_with("opus")
+        || model.starts_with("sonnet")
+        || model.starts_with("haiku")
+        || (provider == Some("anthropic") && model == "default")
+    {
+        Some(200_000)
+    } else if model.starts_with("gpt-5") {
+        Some(400_000)
+    } else if model.starts_with("gemini") {
+        Some(1_000_000)
+    } else {
+        None
+    }
+}
+
 /// Parse an agent config JSON file (from `scripts/agent-configs/resources/`) into
 /// ACP `SessionConfigOption` values. The JSON format is:
 /// ```json
@@ -196,6 +755,120 @@ fn parse_agent_config(json_str: &str) -> Vec<Value> {
     options
 }
 
+pub(super) fn agent_incompatibility_info(
+    incompatibility: AgentIncompatibility,
+) -> AgentIncompatibilityInfo {
+    let (suggested_version, suggested_agent_process_version) = incompatibility
+        .suggested
+        .map(|pin| (pin.version, pin.agent_process_version))
+        .unwrap_or_default();
+    AgentIncompatibilityInfo {
+        reason: incompatibility.reason.as_str().to_string(),
+        detail: incompatibility.detail,
+        installed_version: incompatibility.installed_version,
+        suggested_version,
+        suggested_agent_process_version,
+        auto_installed: incompatibility.auto_installed,
+        detected_at_ms: incompatibility.detected_at_ms,
+    }
+}
+
+/// `body` as JSON with a weak ETag of its content, or `304 Not Modified`
+/// when `If-None-Match` already names that ETag. `Cache-Control: no-cache`
+/// lets clients keep the body but makes them revalidate before using it.
+pub(super) fn json_with_etag<T: Serialize>(headers: &HeaderMap, body: &T) -> Response {
+    let bytes = match serde_json::to_vec(body) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            return ApiError::from(SandboxError::StreamError {
+                message: format!("failed to serialize response: {err}"),
+            })
+            .into_response()
+        }
+    };
+    let digest = Sha256::digest(&bytes);
+    let etag = format!("W/\"{}\"", URL_SAFE_NO_PAD.encode(&digest[..16]));
+    let cache_headers = [
+        (header::ETAG, etag.clone()),
+        (header::CACHE_CONTROL, "no-cache".to_string()),
+    ];
+    let matched = headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|candidate| {
+            candidate == "*" || candidate.trim_start_matches("W/") == etag.trim_start_matches("W/")
+        });
+    if matched {
+        return (StatusCode::NOT_MODIFIED, cache_headers).into_response();
+    }
+    (
+        cache_headers,
+        [(header::CONTENT_TYPE, "application/json".to_string())],
+        bytes,
+    )
+        .into_response()
+}
+
+pub(super) fn warm_pool_info(status: WarmPoolStatus) -> WarmPoolInfo {
+    WarmPoolInfo {
+        size: status.size,
+        ready: status.ready,
+        cold_starts: status.stats.cold_starts,
+        warm_starts: status.stats.warm_starts,
+        cold_start_ms_avg: status.stats.cold_start_ms_avg(),
+        warm_start_ms_avg: status.stats.warm_start_ms_avg(),
+    }
+}
+
+pub(super) fn session_defaults_info(defaults: SessionDefaults) -> AgentSessionDefaultsInfo {
+    AgentSessionDefaultsInfo {
+        model: defaults.model,
+        mode: defaults.mode,
+    }
+}
+
+pub(super) fn map_plan_approval(approval: PlanApproval) -> AcpPlanApprovalInfo {
+    AcpPlanApprovalInfo {
+        mode_id: approval.mode_id,
+        note: approval.note,
+        approved_at_ms: approval.approved_at_ms,
+    }
+}
+
+pub(super) fn map_child_session(child: ChildSession) -> AcpChildSession {
+    AcpChildSession {
+        id: child.id,
+        parent_id: child.parent_id,
+        agent: child.agent,
+        description: child.description,
+        status: child.status,
+        started_sequence: child.started_sequence,
+        event_count: child.event_count as u64,
+    }
+}
+
+pub(super) fn map_retracted_turn(retracted: RetractedTurn) -> AcpRetractedTurn {
+    AcpRetractedTurn {
+        turn: retracted.turn,
+        first_sequence: retracted.first_sequence,
+        last_sequence: retracted.last_sequence,
+    }
+}
+
+pub(super) fn map_queued_message(message: QueuedMessage) -> AcpQueuedMessage {
+    AcpQueuedMessage {
+        request_id: message.request_id(),
+        status: message.status.as_str().to_string(),
+        id: message.id,
+        session_id: message.session_id,
+        prompt: message.prompt,
+        created_at_ms: message.created_at_ms,
+    }
+}
+
 pub(super) fn agent_capabilities_for(agent: AgentId) -> AgentCapabilities {
     match agent {
         AgentId::Claude => AgentCapabilities {
@@ -217,6 +890,7 @@ pub(super) fn agent_capabilities_for(agent: AgentId) -> AgentCapabilities {
             streaming_deltas: true,
             item_started: false,
             shared_process: false,
+            permission_modes: crate::permission_mode::supported_modes(agent),
         },
         AgentId::Codex => AgentCapabilities {
             plan_mode: true,
@@ -237,6 +911,7 @@ pub(super) fn agent_capabilities_for(agent: AgentId) -> AgentCapabilities {
             streaming_deltas: true,
             item_started: true,
             shared_process: false,
+            permission_modes: crate::permission_mode::supported_modes(agent),
         },
         AgentId::Opencode => AgentCapabilities {
             plan_mode: false,
@@ -257,6 +932,7 @@ pub(super) fn agent_capabilities_for(agent: AgentId) -> AgentCapabilities {
             streaming_deltas: true,
             item_started: true,
             shared_process: false,
+            permission_modes: crate::permission_mode::supported_modes(agent),
         },
         AgentId::Amp => AgentCapabilities {
             plan_mode: false,
@@ -277,6 +953,7 @@ pub(super) fn agent_capabilities_for(agent: AgentId) -> AgentCapabilities {
             streaming_deltas: false,
             item_started: false,
             shared_process: false,
+            permission_modes: crate::permission_mode::supported_modes(agent),
         },
         AgentId::Pi => AgentCapabilities {
             plan_mode: false,
@@ -297,6 +974,7 @@ pub(super) fn agent_capabilities_for(agent: AgentId) -> AgentCapabilities {
             streaming_deltas: true,
             item_started: true,
             shared_process: false,
+            permission_modes: crate::permission_mode::supported_modes(agent),
         },
         AgentId::Cursor => AgentCapabilities {
             plan_mode: true,
@@ -317,6 +995,7 @@ pub(super) fn agent_capabilities_for(agent: AgentId) -> AgentCapabilities {
             streaming_deltas: true,
             item_started: true,
             shared_process: false,
+            permission_modes: crate::permission_mode::supported_modes(agent),
         },
         AgentId::Mock => AgentCapabilities {
             plan_mode: true,
@@ -337,6 +1016,7 @@ pub(super) fn agent_capabilities_for(agent: AgentId) -> AgentCapabilities {
             streaming_deltas: true,
             item_started: true,
             shared_process: false,
+            permission_modes: crate::permission_mode::supported_modes(agent),
         },
     }
 }
@@ -357,12 +1037,64 @@ pub(super) fn map_install_result(result: InstallResult) -> AgentInstallResponse
     }
 }
 
+pub(super) fn map_agent_pin(pin: AgentPin) -> AgentPinInfo {
+    AgentPinInfo {
+        agent_version: pin.version,
+        agent_process_version: pin.agent_process_version,
+    }
+}
+
+pub(super) fn map_agent_release(release: AgentRelease) -> AgentReleaseInfo {
+    AgentReleaseInfo {
+        version: release.version,
+        published_at: release.published_at,
+        url: release.url,
+        notes: release.notes,
+    }
+}
+
+/// Releases between `previous` (exclusive) and `installed` (inclusive). Release
+/// tags (`rust-v0.46.0`) and `--version` output (`codex-cli 0.46.0`) differ in
+/// shape, so they are matched on the numeric part of the tag. Fresh installs
+/// only get the installed release.
+pub(super) fn upgrade_changelog(
+    releases: &[AgentRelease],
+    previous: Option<&str>,
+    installed: Option<&str>,
+) -> Vec<AgentReleaseInfo> {
+    let matches = |release: &AgentRelease, reported: &str| {
+        let numeric = release
+            .version
+            .trim_start_matches(|c: char| !c.is_ascii_digit());
+        !numeric.is_empty() && reported.contains(numeric)
+    };
+
+    let Some(start) =
+        installed.and_then(|installed| releases.iter().position(|r| matches(r, installed)))
+    else {
+        return Vec::new();
+    };
+    let limit = if previous.is_some() {
+        releases.len()
+    } else {
+        1
+    };
+    releases[start..]
+        .iter()
+        .take_while(|release| !previous.is_some_and(|previous| matches(release, previous)))
+        .take(limit)
+        .cloned()
+        .map(map_agent_release)
+        .collect()
+}
+
 pub(super) fn map_install_source(source: InstallSource) -> String {
     match source {
         InstallSource::Registry => "registry",
         InstallSource::Fallback => "fallback",
         InstallSource::LocalPath => "local_path",
         InstallSource::Builtin => "builtin",
+        InstallSource::Artifact => "artifact",
     }
     .to_string()
 }
@@ -375,6 +1107,117 @@ pub(super) fn map_artifact_kind(kind: InstalledArtifactKind) -> String {
     .to_string()
 }
 
+/// Marker-file heuristics for what a repository is built with. Only the root
+/// directory is inspected so the call stays cheap on large checkouts.
+pub(super) fn detect_workspace(root: &StdPath) -> FsWorkspaceInfo {
+    let mut info = FsWorkspaceInfo {
+        path: root.to_string_lossy().to_string(),
+        ..FsWorkspaceInfo::default()
+    };
+    let has = |name: &str| root.join(name).exists();
+    let push = |list: &mut Vec<String>, value: &str| {
+        if !list.iter().any(|existing| existing == value) {
+            list.push(value.to_string());
+        }
+    };
+
+    if has("Cargo.toml") {
+        push(&mut info.languages, "rust");
+        push(&mut info.package_managers, "cargo");
+        push(&mut info.test_commands, "cargo test");
+    }
+
+    if has("package.json") {
+        let language = if has("tsconfig.json") {
+            "typescript"
+        } else {
+            "javascript"
+        };
+        push(&mut info.languages, language);
+        let manager = if has("pnpm-lock.yaml") {
+            "pnpm"
+        } else if has("yarn.lock") {
+            "yarn"
+        } else if has("bun.lockb") || has("bun.lock") {
+            "bun"
+        } else {
+            "npm"
+        };
+        push(&mut info.package_managers, manager);
+        let has_test_script = fs::read(root.join("package.json"))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<Value>(&bytes).ok())
+            .is_some_and(|package| package.pointer("/scripts/test").is_some());
+        if has_test_script {
+            push(&mut info.test_commands, &format!("{manager} test"));
+        }
+    }
+
+    if has("pyproject.toml") || has("requirements.txt") || has("setup.py") {
+        push(&mut info.languages, "python");
+        let manager = if has("uv.lock") {
+            "uv"
+        } else if has("poetry.lock") {
+            "poetry"
+        } else {
+            "pip"
+        };
+        push(&mut info.package_managers, manager);
+        if has("pytest.ini") || has("conftest.py") || has("tests") {
+            push(&mut info.test_commands, "pytest");
+        }
+    }
+
+    if has("go.mod") {
+        push(&mut info.languages, "go");
+        push(&mut info.package_managers, "go");
+        push(&mut info.test_commands, "go test ./...");
+    }
+
+    if has("Gemfile") {
+        push(&mut info.languages, "ruby");
+        push(&mut info.package_managers, "bundler");
+        if has("spec") {
+            push(&mut info.test_commands, "bundle exec rspec");
+        }
+    }
+
+    if has("pom.xml") {
+        push(&mut info.languages, "java");
+        push(&mut info.package_managers, "maven");
+        push(&mut info.test_commands, "mvn test");
+    }
+
+    if has("build.gradle") || has("build.gradle.kts") {
+        let language = if has("build.gradle.kts") {
+            "kotlin"
+        } else {
+            "java"
+        };
+        push(&mut info.languages, language);
+        push(&mut info.package_managers, "gradle");
+        let command = if has("gradlew") {
+            "./gradlew test"
+        } else {
+            "gradle test"
+        };
+        push(&mut info.test_commands, command);
+    }
+
+    if has("composer.json") {
+        push(&mut info.languages, "php");
+        push(&mut info.package_managers, "composer");
+    }
+
+    if has("mix.exs") {
+        push(&mut info.languages, "elixir");
+        push(&mut info.package_managers, "mix");
+        push(&mut info.test_commands, "mix test");
+    }
+
+    info
+}
+
 pub(super) fn resolve_fs_path(raw_path: &str) -> Result<PathBuf, SandboxError> {
     let path = PathBuf::from(raw_path);
     if path.is_absolute() {
@@ -5,6 +5,28 @@ use super::*;
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
 pub struct HealthResponse {
     pub status: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub preinstall: Vec<PreinstallStatusInfo>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PreinstallStatus {
+    Pending,
+    Installing,
+    Installed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PreinstallStatusInfo {
+    pub agent: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    pub status: PreinstallStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
@@ -43,6 +65,9 @@ pub struct AgentCapabilities {
     pub streaming_deltas: bool,
     pub item_started: bool,
     pub shared_process: bool,
+    /// Values `_meta["sandboxagent.dev"].permissionMode` accepts for this
+    /// agent.
+    pub permission_modes: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
@@ -62,12 +87,99 @@ pub struct AgentInfo {
     pub config_options: Option<Vec<Value>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub config_error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub incompatibility: Option<AgentIncompatibilityInfo>,
+    /// Bytes the agent's binary, launcher and extracted files take up in the
+    /// install directory. Binaries found on `PATH` count as 0.
+    pub disk_usage_bytes: u64,
+    /// Standby processes from `SANDBOX_AGENT_WARM_POOL`, when the agent has
+    /// a pool.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warm_pool: Option<WarmPoolInfo>,
+    /// Model and mode from `[agents.<id>]` in the server config, when set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_defaults: Option<AgentSessionDefaultsInfo>,
+}
+
+/// Model and mode from `[agents.<id>]` in the server config, which new
+/// sessions of the agent switch to when `session/new` does not pick its own.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentSessionDefaultsInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// ACP mode id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+}
+
+/// Standby processes kept for an agent from `SANDBOX_AGENT_WARM_POOL`, and
+/// how fast its servers started.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WarmPoolInfo {
+    /// Standbys the pool keeps.
+    pub size: usize,
+    /// Standbys spawned and waiting for a server.
+    pub ready: usize,
+    /// Servers that spawned their own process.
+    pub cold_starts: u64,
+    /// Servers that adopted a standby.
+    pub warm_starts: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cold_start_ms_avg: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warm_start_ms_avg: Option<u64>,
+}
+
+/// A detected mismatch between the installed agent and the proxy, with the
+/// versions last seen working.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentIncompatibilityInfo {
+    /// `usage_error` or `schema_drift`.
+    pub reason: String,
+    pub detail: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installed_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggested_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggested_agent_process_version: Option<String>,
+    pub auto_installed: bool,
+    pub detected_at_ms: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct AgentListResponse {
     pub agents: Vec<AgentInfo>,
+    /// Bytes the whole install directory takes up.
+    pub disk_usage_bytes: u64,
+    /// Quota the install directory is held to, from
+    /// `SANDBOX_AGENT_INSTALL_QUOTA_BYTES`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_quota_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentModelInfo {
+    pub id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentModelsResponse {
+    pub agent: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_model: Option<String>,
+    pub models: Vec<AgentModelInfo>,
 }
 
 #[derive(Debug, Clone, Deserialize, JsonSchema)]
@@ -84,6 +196,8 @@ pub struct AgentInstallRequest {
     pub reinstall: Option<bool>,
     pub agent_version: Option<String>,
     pub agent_process_version: Option<String>,
+    /// Install from this server-local directory instead of downloading.
+    pub artifact_dir: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
@@ -94,12 +208,84 @@ pub struct AgentInstallArtifact {
     pub version: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentUninstallResponse {
+    pub agent: String,
+    pub freed_bytes: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
 pub struct AgentInstallResponse {
     pub already_installed: bool,
     pub artifacts: Vec<AgentInstallArtifact>,
 }
 
+/// Payload of each `progress` event on the install progress stream.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentInstallProgressEvent {
+    pub agent: String,
+    /// False once no install is running; this is always the last event.
+    pub active: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    pub downloaded_bytes: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentPinInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_process_version: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentReleaseInfo {
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentVersionsResponse {
+    pub agent: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installed_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pinned: Option<AgentPinInfo>,
+    pub releases: Vec<AgentReleaseInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentUpgradeRequest {
+    pub agent_version: Option<String>,
+    pub agent_process_version: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentUpgradeResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installed_version: Option<String>,
+    pub artifacts: Vec<AgentInstallArtifact>,
+    /// Releases newer than `previousVersion`, up to the installed one.
+    pub changelog: Vec<AgentReleaseInfo>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct FsPathQuery {
@@ -156,6 +342,15 @@ pub struct FsStat {
     pub modified: Option<String>,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FsWorkspaceInfo {
+    pub path: String,
+    pub languages: Vec<String>,
+    pub package_managers: Vec<String>,
+    pub test_commands: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct FsWriteResponse {
@@ -201,16 +396,912 @@ pub struct AcpPostQuery {
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct AcpServerInfo {
+pub struct AcpAttachmentsQuery {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpAttachmentInfo {
+    pub name: String,
+    pub path: String,
+    /// `file://` URI of `path`, ready for a `resource_link` prompt block.
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    pub size_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpAttachmentsResponse {
+    pub attachments: Vec<AcpAttachmentInfo>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpStreamQuery {
+    #[serde(default)]
+    pub offset: Option<u64>,
+    #[serde(default)]
+    pub named_events: Option<bool>,
+    #[serde(default)]
+    pub include_logs: Option<bool>,
+    #[serde(default)]
+    pub types: Option<String>,
+    #[serde(default)]
+    pub exclude: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamQuery {
+    #[serde(default)]
+    pub all: Option<bool>,
+    #[serde(default)]
+    pub agent: Option<String>,
+    #[serde(default)]
+    pub methods: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpEventsQuery {
+    #[serde(default)]
+    pub offset: Option<u64>,
+    #[serde(default)]
+    pub wait_ms: Option<u64>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub include_logs: Option<bool>,
+    #[serde(default)]
+    pub types: Option<String>,
+    #[serde(default)]
+    pub exclude: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpEventEnvelope {
+    pub sequence: u64,
+    pub payload: Value,
+    /// Child session that sent this envelope, when it came from a sub-agent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpTranscriptQuery {
+    #[serde(default)]
+    pub format: Option<String>,
+}
+
+/// One speaker's text for a turn, merged from streamed message chunks.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpTranscriptMessage {
+    /// `user` or `assistant`.
+    pub role: String,
+    pub text: String,
+    /// Turn number from `_adapter/turn_started`; absent for replayed history.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub turn: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpTranscriptResponse {
+    pub session_id: String,
+    pub messages: Vec<AcpTranscriptMessage>,
+}
+
+/// How a session ended.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpSessionEndInfo {
+    /// `completed` or `crashed` when the agent process exited on its own,
+    /// `timeout` when a turn limit killed it, or `terminated` when the
+    /// server was deleted or an interrupt killed it.
+    pub reason: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// Signal that terminated the process (unix only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal: Option<i32>,
+    pub ended_at_ms: i64,
+    /// Time from `session/new` or `session/load` to the end.
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpSessionDetailResponse {
     pub server_id: String,
+    pub session_id: String,
     pub agent: String,
     pub created_at_ms: i64,
+    /// Current ACP mode id, while the session is open.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode_id: Option<String>,
+    /// `modes.availableModes` from the agent's `session/new` or
+    /// `session/load` result.
+    pub available_modes: Vec<Value>,
+    /// Current model id, from that result or the last `session/set_model`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_id: Option<String>,
+    pub available_models: Vec<Value>,
+    pub prompt_in_flight: bool,
+    /// `session/prompt` requests sent to the session.
+    pub prompts: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_prompt_at_ms: Option<i64>,
+    /// Unanswered `session/request_permission` requests from the agent.
+    pub pending_permissions: u64,
+    /// Unanswered agent requests of other methods.
+    pub pending_questions: u64,
+    /// Envelopes for the session still in the server's replay buffer.
+    pub buffered_events: u64,
+    /// The server's latest event id. Pass it as `offset` or `Last-Event-ID`
+    /// to follow the session from this snapshot on.
+    pub next_offset: u64,
+    /// Set once the session's agent process has exited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ended: Option<AcpSessionEndInfo>,
+}
+
+/// Settings to change on an open session. At least one is required.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpSessionUpdateRequest {
+    /// ACP mode id to switch to with `session/set_mode`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode_id: Option<String>,
+    /// Model id to switch to with `session/set_model`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_id: Option<String>,
+    /// Permission mode, as in `_meta["sandboxagent.dev"].permissionMode`.
+    /// Only agents that enforce it per session (Claude) can change it on an
+    /// open session.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permission_mode: Option<Value>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpTurnResultQuery {
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
 }
 
+/// A file a tool call edited, deleted or moved during the turn.
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct AcpServerListResponse {
-    pub servers: Vec<AcpServerInfo>,
+pub struct AcpChangedFile {
+    pub path: String,
+    /// `edit`, `delete` or `move`.
+    pub kind: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpTurnResultResponse {
+    pub session_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub turn: Option<u64>,
+    /// `completed`, `cancelled`, or `failed` when the agent answered with an
+    /// error or the turn ended without an answer.
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+    /// JSON-RPC error of a failed turn.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<Value>,
+    /// The last assistant message of the turn, merged from its chunks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<AcpTurnUsageInfo>,
+    pub changed_files: Vec<AcpChangedFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpEventsResponse {
+    pub events: Vec<AcpEventEnvelope>,
+    /// Pass back as `offset` to read the envelopes after these.
+    pub next_offset: u64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpPendingRequestsQuery {
+    #[serde(default)]
+    pub session_id: Option<String>,
+}
+
+/// A request from the agent, such as `session/request_permission`, that no
+/// client has answered yet.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpPendingRequestInfo {
+    /// Event id of the request on the server's stream.
+    pub sequence: u64,
+    /// JSON-RPC id to answer with.
+    pub id: Value,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// The request envelope as the agent sent it.
+    pub payload: Value,
+    pub received_at_ms: i64,
+    pub age_ms: u64,
+    /// When the server rejects the request on the client's behalf.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpPendingRequestsResponse {
+    pub requests: Vec<AcpPendingRequestInfo>,
+}
+
+/// A tool call that edited, deleted or moved files while the session was
+/// planning.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpPlanViolation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    /// ACP tool kind: `edit`, `delete` or `move`.
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpPlanResponse {
+    pub session_id: String,
+    /// Turn number from `_adapter/turn_started`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub turn: Option<u64>,
+    pub text: String,
+    /// Entries of the ACP `plan` update, when the plan came from one.
+    pub entries: Vec<Value>,
+    /// Where the text came from: `plan_update`, `exit_plan_mode` or `reply`.
+    pub source: String,
+    /// True when no tool call changed files during the turn.
+    pub read_only: bool,
+    pub violations: Vec<AcpPlanViolation>,
+    pub created_at_ms: i64,
+    /// Set once the plan was approved for building.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval: Option<AcpPlanApprovalInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpInterruptResponse {
+    pub session_id: String,
+    /// `idle` when no prompt was in flight, `cancelled` when the agent ended
+    /// the turn after `session/cancel`, or `killed` when it ignored it and
+    /// its process was stopped.
+    pub outcome: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpQueueMessageRequest {
+    /// ACP content blocks, as in `session/prompt`.
+    pub prompt: Vec<Value>,
+    /// When to send. Only `after_current_turn`, the default, is supported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpQueuedMessage {
+    pub id: String,
+    pub session_id: String,
+    pub prompt: Vec<Value>,
+    /// `queued`, or `sending` once its prompt went to the agent.
+    pub status: String,
+    /// JSON-RPC id of the prompt, and `requestId` of its turn notifications.
+    pub request_id: String,
+    pub created_at_ms: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpQueuedMessagesResponse {
+    pub session_id: String,
+    pub messages: Vec<AcpQueuedMessage>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpRetryRequest {
+    /// Replaces the text of the last prompt. Images and resources are kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpRetractedTurn {
+    /// Turn number from `_adapter/turn_started`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub turn: Option<u64>,
+    /// Envelopes of the session from this sequence to `lastSequence`
+    /// belong to the retracted turn.
+    pub first_sequence: u64,
+    pub last_sequence: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpRetryResponse {
+    pub session_id: String,
+    pub retracted: AcpRetractedTurn,
+    /// Stop reason of the new turn.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpChildSession {
+    /// `toolCallId` of the tool call that started the sub-agent.
+    pub id: String,
+    /// Child session this one was started from, when sub-agents nest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    /// Sub-agent type the tool call asked for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Latest tool call status.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    pub started_sequence: u64,
+    /// Envelopes the sub-agent sent itself.
+    pub event_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpChildSessionsResponse {
+    pub session_id: String,
+    pub child_sessions: Vec<AcpChildSession>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpPlanApprovalInfo {
+    /// Mode the session was switched to.
+    pub mode_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    pub approved_at_ms: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpPlanApproveRequest {
+    /// Mode to build in; defaults to the agent's build mode (`default` for
+    /// Claude, `auto` for Codex, `build` for OpenCode).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode_id: Option<String>,
+    /// Added to the build prompt after the plan.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpPlanApproveResponse {
+    pub session_id: String,
+    pub approval: AcpPlanApprovalInfo,
+    /// Stop reason of the build turn.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpServerInfo {
+    pub server_id: String,
+    pub agent: String,
+    pub created_at_ms: i64,
+    pub startup_timing: AcpStartupTimingInfo,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_turn_timing: Option<AcpTurnTimingInfo>,
+    pub pipeline: AcpPipelineInfo,
+}
+
+/// Depth of the server's agent output pipeline.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpPipelineInfo {
+    /// Envelopes held in the replay buffer.
+    pub buffered_envelopes: u64,
+    /// Approximate size of the buffered envelopes in bytes.
+    pub buffered_bytes: u64,
+    /// Agent stdout lines discarded for exceeding the line limit.
+    pub oversized_lines: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpStartupTimingInfo {
+    pub install_ms: u64,
+    pub resolve_ms: u64,
+    pub spawn_ms: u64,
+    /// The server adopted a standby process from the warm pool.
+    pub warm: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpTurnTimingInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    pub started_at_ms: i64,
+    pub instance_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_to_first_event_ms: Option<u64>,
+    pub duration_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<AcpTurnUsageInfo>,
+}
+
+/// Token counts and cost the agent reported for the turn. Fields the agent
+/// did not report are omitted.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpTurnUsageInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_read_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_write_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thought_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_tokens: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_currency: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpServerListResponse {
+    pub servers: Vec<AcpServerInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpImportSessionRequest {
+    pub agent: String,
+    pub native_session_id: String,
+    /// Working directory the agent loads the session in. Required, since
+    /// agents look native sessions up by the directory they ran in.
+    pub cwd: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpImportSessionResponse {
+    pub server_id: String,
+    pub agent: String,
+    pub session_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpCompactRequest {
+    pub session_id: String,
+}
+
+/// `native` ran the agent's own `/compact`; `summary` asked the agent to
+/// summarize the session.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AcpCompactionMode {
+    Native,
+    Summary,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpCompactResponse {
+    pub session_id: String,
+    pub mode: AcpCompactionMode,
+    /// Estimated tokens of session text since the last compaction.
+    pub before_tokens: u64,
+    /// Estimated tokens of the compaction turn's reply.
+    pub after_tokens: u64,
+    /// The agent's summary, in `summary` mode, for seeding a new session.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpShareRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpShareResponse {
+    pub url: String,
+    pub expires_at_ms: i64,
+}
+
+#[derive(Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpSecretInput {
+    /// Environment variable name the agent process sees.
+    pub name: String,
+    pub value: String,
+    /// Write the value to an owner-only file and set the variable to its path.
+    #[serde(default)]
+    pub file: bool,
+}
+
+impl std::fmt::Debug for AcpSecretInput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AcpSecretInput")
+            .field("name", &self.name)
+            .field("file", &self.file)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpSecretsRequest {
+    pub secrets: Vec<AcpSecretInput>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpSecretsResponse {
+    pub names: Vec<String>,
+    /// The agent process is already running and will not see these values
+    /// until the server is recreated.
+    pub restart_required: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpValidateResponse {
+    pub server_id: String,
+    pub valid: bool,
+    /// Agent the server is, or would be, bound to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+    /// The server already has a running agent process.
+    pub exists: bool,
+    pub agent_installed: bool,
+    /// Variables from `_meta["sandboxagent.dev"].env`. Values are not echoed.
+    pub env_keys: Vec<String>,
+    pub problems: Vec<AcpValidationProblemInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AcpValidationProblemInfo {
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamSubscribeRequest {
+    pub server_ids: Vec<String>,
+    /// Resume point per server, as the `sequence` of the last event seen.
+    #[serde(default)]
+    pub last_event_ids: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamUnsubscribeRequest {
+    pub server_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamSubscriptionsResponse {
+    pub channel_id: String,
+    pub server_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskCreateRequest {
+    pub prompt: String,
+    pub agents: Vec<TaskAgentConfig>,
+    /// Working directory for every session. Defaults to the server's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskAgentConfig {
+    pub agent: String,
+    /// Extra environment for this agent's process.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskStatus {
+    Starting,
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskRunInfo {
+    /// ACP server running this agent, readable through `/v1/acp/{server_id}`.
+    pub server_id: String,
+    pub agent: String,
+    pub status: TaskStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<String>,
+    /// Text the agent replied with.
+    pub output: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskInfo {
+    pub task_id: String,
+    pub prompt: String,
+    pub created_at_ms: i64,
+    /// Whether every run has completed or failed.
+    pub finished: bool,
+    pub runs: Vec<TaskRunInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskListResponse {
+    pub tasks: Vec<TaskInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleCreateRequest {
+    /// Five-field cron expression (minute, hour, day of month, month, day of
+    /// week) evaluated in UTC, or one of `@hourly`, `@daily`, `@weekly`,
+    /// `@monthly`, `@yearly`.
+    pub cron: String,
+    pub prompt: String,
+    pub agents: Vec<TaskAgentConfig>,
+    /// Working directory for every session. Defaults to the server's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleRunInfo {
+    /// Task started for this firing, readable through `/v1/tasks/{task_id}`.
+    pub task_id: String,
+    pub started_at_ms: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleInfo {
+    pub schedule_id: String,
+    pub cron: String,
+    pub prompt: String,
+    pub cwd: String,
+    pub agents: Vec<String>,
+    pub created_at_ms: i64,
+    /// Absent when the expression never fires again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_run_at_ms: Option<i64>,
+    /// Firings skipped because the previous run had not finished.
+    pub skipped_runs: u64,
+    /// Most recent runs, oldest first.
+    pub runs: Vec<ScheduleRunInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleListResponse {
+    pub schedules: Vec<ScheduleInfo>,
+}
+
+/// A Messages API request. Only the fields below are read; others, such as
+/// `temperature` or `tools`, are accepted and ignored.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+pub struct AnthropicMessagesRequest {
+    /// Agent to run, such as `claude`, optionally followed by `/` and
+    /// anything else. The `x-sandbox-agent` header takes precedence.
+    pub model: String,
+    pub messages: Vec<AnthropicMessageParam>,
+    /// System prompt as a string or an array of text blocks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<Value>,
+    /// Accepted for compatibility; agents do not take a token limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u64>,
+    #[serde(default)]
+    pub stream: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+pub struct AnthropicMessageParam {
+    /// `user` or `assistant`.
+    pub role: String,
+    /// A string or an array of `text` content blocks.
+    pub content: Value,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LogsQuery {
+    #[serde(default)]
+    pub session: Option<String>,
+    #[serde(default)]
+    pub level: Option<String>,
+    #[serde(default)]
+    pub since: Option<u64>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// One server log event.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntryInfo {
+    pub sequence: u64,
+    pub timestamp_ms: i64,
+    /// `error`, `warn`, `info`, `debug` or `trace`.
+    pub level: String,
+    /// Module that logged the event.
+    pub target: String,
+    pub message: String,
+    /// Event fields, with fields of enclosing spans such as `http.request`
+    /// merged in.
+    pub fields: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LogsResponse {
+    pub entries: Vec<LogEntryInfo>,
+    /// Pass back as `since` to read the entries after these.
+    pub next_since: u64,
+}
+
+/// Settings the server is running with, after the config file, environment
+/// and CLI flags are applied. Secrets are masked.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerConfigResponse {
+    /// Config file the server read, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    pub auth: ServerConfigAuthInfo,
+    pub server: ServerConfigServerInfo,
+    pub install: ServerConfigInstallInfo,
+    pub limits: ServerConfigLimitsInfo,
+    pub telemetry: ServerConfigTelemetryInfo,
+    /// Model aliases and their `agent:model` targets, in fallback order.
+    pub models: BTreeMap<String, Vec<String>>,
+    /// Keyed by agent id.
+    pub agents: BTreeMap<String, ServerConfigAgentInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerConfigAuthInfo {
+    /// `********` when a token is required; absent otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerConfigServerInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    pub cors_allow_origins: Vec<String>,
+    /// Empty allows any method.
+    pub cors_allow_methods: Vec<String>,
+    /// Empty allows any header.
+    pub cors_allow_headers: Vec<String>,
+    pub cors_allow_credentials: bool,
+    /// Set when serving on a Unix domain socket instead of TCP.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unix_socket: Option<String>,
+    /// True when serving over stdin/stdout.
+    pub stdio: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerConfigInstallInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+    pub preinstall: Vec<String>,
+    pub require_preinstall: bool,
+}
+
+/// Unset limits use the server defaults.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerConfigLimitsInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acp_request_timeout_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub turn_timeout_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub turn_idle_timeout_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_request_timeout_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_raw_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_body_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_prompt_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerConfigTelemetryInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerConfigAgentInfo {
+    /// Spawn environment names; every value reads `********`.
+    pub env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, ToSchema)]
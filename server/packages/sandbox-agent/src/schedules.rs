@@ -0,0 +1,398 @@
+//! Recurring agent jobs.
+//!
+//! `POST /v1/schedules` takes a five-field cron expression (minute, hour,
+//! day of month, month, day of week, evaluated in UTC) and a task body. Each
+//! time the expression fires, the schedule starts a task through
+//! [`TaskRunner`], so every run is an ordinary task with its own ACP servers
+//! and sessions. A firing is skipped while the previous run is still going.
+//! Schedules live in memory and stop with the server.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Datelike, Duration, TimeZone, Timelike, Utc};
+use sandbox_agent_error::SandboxError;
+
+use crate::acp_proxy_runtime::AcpProxyRuntime;
+use crate::tasks::{new_task_id, now_ms, TaskRunner, TaskSpec};
+
+/// Runs older than this many firings are dropped from a schedule's history.
+const MAX_RECORDED_RUNS: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct ScheduleRun {
+    pub task_id: String,
+    pub started_at_ms: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScheduleRecord {
+    pub schedule_id: String,
+    pub cron: String,
+    pub spec: TaskSpec,
+    pub created_at_ms: i64,
+    /// `None` when the expression never fires again.
+    pub next_run_at_ms: Option<i64>,
+    /// Firings skipped because the previous run had not finished.
+    pub skipped_runs: u64,
+    /// Most recent runs, oldest first.
+    pub runs: Vec<ScheduleRun>,
+}
+
+struct ScheduleEntry {
+    record: ScheduleRecord,
+    timer: tokio::task::JoinHandle<()>,
+}
+
+type ScheduleMap = Arc<Mutex<HashMap<String, ScheduleEntry>>>;
+
+#[derive(Default)]
+pub struct ScheduleRunner {
+    schedules: ScheduleMap,
+}
+
+impl std::fmt::Debug for ScheduleRunner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScheduleRunner")
+            .field("schedules", &self.lock().len())
+            .finish()
+    }
+}
+
+impl ScheduleRunner {
+    /// Record the schedule and start its timer.
+    pub fn create(
+        &self,
+        acp_proxy: AcpProxyRuntime,
+        tasks: TaskRunner,
+        cron: &str,
+        spec: TaskSpec,
+    ) -> Result<ScheduleRecord, SandboxError> {
+        let expression = CronExpr::parse(cron).map_err(|message| SandboxError::InvalidRequest {
+            message: format!("invalid cron expression {cron:?}: {message}"),
+        })?;
+        let schedule_id = new_task_id();
+        let record = ScheduleRecord {
+            schedule_id: schedule_id.clone(),
+            cron: cron.trim().to_string(),
+            spec,
+            created_at_ms: now_ms(),
+            next_run_at_ms: expression
+                .next_after(Utc::now())
+                .map(|at| at.timestamp_millis()),
+            skipped_runs: 0,
+            runs: Vec::new(),
+        };
+        // Hold the lock so the timer cannot look the schedule up before it
+        // is inserted.
+        let mut schedules = self.lock();
+        let timer = tokio::spawn(run_timer(
+            self.schedules.clone(),
+            schedule_id.clone(),
+            expression,
+            acp_proxy,
+            tasks,
+        ));
+        schedules.insert(
+            schedule_id,
+            ScheduleEntry {
+                record: record.clone(),
+                timer,
+            },
+        );
+        Ok(record)
+    }
+
+    pub fn get(&self, schedule_id: &str) -> Option<ScheduleRecord> {
+        self.lock()
+            .get(schedule_id)
+            .map(|entry| entry.record.clone())
+    }
+
+    /// Every schedule, oldest first.
+    pub fn list(&self) -> Vec<ScheduleRecord> {
+        let mut schedules = self
+            .lock()
+            .values()
+            .map(|entry| entry.record.clone())
+            .collect::<Vec<_>>();
+        schedules.sort_by(|left, right| {
+            (left.created_at_ms, &left.schedule_id).cmp(&(right.created_at_ms, &right.schedule_id))
+        });
+        schedules
+    }
+
+    /// Stop the schedule. Tasks it already started are left alone.
+    pub fn delete(&self, schedule_id: &str) -> Result<(), SandboxError> {
+        let entry =
+            self.lock()
+                .remove(schedule_id)
+                .ok_or_else(|| SandboxError::SessionNotFound {
+                    session_id: schedule_id.to_string(),
+                })?;
+        entry.timer.abort();
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, ScheduleEntry>> {
+        self.schedules
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+async fn run_timer(
+    schedules: ScheduleMap,
+    schedule_id: String,
+    expression: CronExpr,
+    acp_proxy: AcpProxyRuntime,
+    tasks: TaskRunner,
+) {
+    let lock = || {
+        schedules
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    };
+    let mut after = Utc::now();
+    while let Some(fire_at) = expression.next_after(after) {
+        let wait = (fire_at - Utc::now()).to_std().unwrap_or_default();
+        tokio::time::sleep(wait).await;
+        after = fire_at;
+        let next_run_at_ms = expression
+            .next_after(fire_at)
+            .map(|at| at.timestamp_millis());
+
+        let mut entries = lock();
+        let Some(entry) = entries.get_mut(&schedule_id) else {
+            return;
+        };
+        let record = &mut entry.record;
+        record.next_run_at_ms = next_run_at_ms;
+        let previous_running = record.runs.last().is_some_and(|run| {
+            tasks
+                .get(&run.task_id)
+                .is_some_and(|task| !task.is_finished())
+        });
+        if previous_running {
+            record.skipped_runs += 1;
+            tracing::info!(
+                schedule_id = %schedule_id,
+                "schedules: previous run still going; skipping this one"
+            );
+            continue;
+        }
+        let task = tasks.start(acp_proxy.clone(), record.spec.clone());
+        record.runs.push(ScheduleRun {
+            task_id: task.task_id,
+            started_at_ms: task.created_at_ms,
+        });
+        if record.runs.len() > MAX_RECORDED_RUNS {
+            record.runs.remove(0);
+        }
+    }
+    if let Some(entry) = lock().get_mut(&schedule_id) {
+        entry.record.next_run_at_ms = None;
+    }
+}
+
+/// A parsed five-field cron expression. Each field is a bitmask of the
+/// values it allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    /// Sunday is 0.
+    days_of_week: u64,
+    /// Whether day of month or day of week was `*`; when neither was, a day
+    /// matching either field fires, as in cron.
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+const MONTH_NAMES: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAY_NAMES: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+impl CronExpr {
+    fn parse(raw: &str) -> Result<Self, String> {
+        let raw = raw.trim();
+        let expanded = match raw {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+        let fields = expanded.split_whitespace().collect::<Vec<_>>();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(format!("expected 5 fields, got {}", fields.len()));
+        };
+        let mut days_of_week = parse_field(day_of_week, 0, 7, WEEKDAY_NAMES, 0)?;
+        // 7 is another spelling of Sunday.
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week & !(1 << 7)) | 1;
+        }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59, &[], 0)?,
+            hours: parse_field(hour, 0, 23, &[], 0)?,
+            days_of_month: parse_field(day_of_month, 1, 31, &[], 0)?,
+            months: parse_field(month, 1, 12, MONTH_NAMES, 1)?,
+            days_of_week,
+            any_day_of_month: day_of_month.starts_with('*'),
+            any_day_of_week: day_of_week.starts_with('*'),
+        })
+    }
+
+    /// The first whole minute strictly after `after` that the expression
+    /// matches, searching up to 30 years ahead.
+    fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut at = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = at + Duration::days(366 * 30);
+        while at < limit {
+            if !has(self.months, at.month()) {
+                let (year, month) = match at.month() {
+                    12 => (at.year() + 1, 1),
+                    month => (at.year(), month + 1),
+                };
+                at = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()?;
+            } else if !self.day_matches(at) {
+                let midnight = at.date_naive().and_hms_opt(0, 0, 0)?;
+                at = Utc.from_utc_datetime(&midnight) + Duration::days(1);
+            } else if !has(self.hours, at.hour()) {
+                at = at.with_minute(0)? + Duration::hours(1);
+            } else if !has(self.minutes, at.minute()) {
+                at += Duration::minutes(1);
+            } else {
+                return Some(at);
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, at: DateTime<Utc>) -> bool {
+        let day_of_month = has(self.days_of_month, at.day());
+        let day_of_week = has(self.days_of_week, at.weekday().num_days_from_sunday());
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        }
+    }
+}
+
+fn has(mask: u64, value: u32) -> bool {
+    mask & (1 << value) != 0
+}
+
+/// Parse a comma-separated list of `*`, `n`, `a-b`, each optionally with a
+/// `/step`. `names` spell values from `first_name` onwards.
+fn parse_field(
+    field: &str,
+    min: u32,
+    max: u32,
+    names: &[&str],
+    first_name: u32,
+) -> Result<u64, String> {
+    let value = |raw: &str| -> Result<u32, String> {
+        let parsed = match names.iter().position(|name| name.eq_ignore_ascii_case(raw)) {
+            Some(index) => index as u32 + first_name,
+            None => raw
+                .parse::<u32>()
+                .map_err(|_| format!("invalid value {raw:?} in {field:?}"))?,
+        };
+        if parsed < min || parsed > max {
+            return Err(format!("{parsed} is outside {min}-{max} in {field:?}"));
+        }
+        Ok(parsed)
+    };
+
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid step {step:?} in {field:?}"))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (value(start)?, value(end)?),
+                // `n/step` runs from n to the end of the field.
+                None if step > 1 => (value(range)?, max),
+                None => {
+                    let single = value(range)?;
+                    (single, single)
+                }
+            },
+        };
+        if start > end {
+            return Err(format!("range {range:?} runs backwards in {field:?}"));
+        }
+        for selected in (start..=end).step_by(step as usize) {
+            mask |= 1 << selected;
+        }
+    }
+    Ok(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(raw: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(raw)
+            .expect("timestamp")
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn next_after_steps_through_fields() {
+        let every_quarter_hour = CronExpr::parse("*/15 * * * *").expect("parse");
+        assert_eq!(
+            every_quarter_hour.next_after(at("2026-03-01T10:07:30Z")),
+            Some(at("2026-03-01T10:15:00Z"))
+        );
+
+        let weekday_mornings = CronExpr::parse("30 9 * * mon-fri").expect("parse");
+        // 2026-03-07 is a Saturday.
+        assert_eq!(
+            weekday_mornings.next_after(at("2026-03-06T09:30:00Z")),
+            Some(at("2026-03-09T09:30:00Z"))
+        );
+
+        let leap_day = CronExpr::parse("0 0 29 feb *").expect("parse");
+        assert_eq!(
+            leap_day.next_after(at("2026-03-01T00:00:00Z")),
+            Some(at("2028-02-29T00:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn restricted_day_fields_match_either() {
+        // The 1st of the month or any Sunday (7 spells Sunday too).
+        let expression = CronExpr::parse("0 12 1 * 7").expect("parse");
+        // 2026-03-08 is a Sunday.
+        assert_eq!(
+            expression.next_after(at("2026-03-02T00:00:00Z")),
+            Some(at("2026-03-08T12:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn parse_rejects_malformed_expressions() {
+        assert!(CronExpr::parse("* * * *").is_err());
+        assert!(CronExpr::parse("60 * * * *").is_err());
+        assert!(CronExpr::parse("*/0 * * * *").is_err());
+        assert!(CronExpr::parse("5-1 * * * *").is_err());
+        assert!(CronExpr::parse("@hourly").is_ok());
+    }
+}
@@ -0,0 +1,188 @@
+//! Per-server secrets sealed in memory.
+//!
+//! Values are encrypted with a key generated at startup and only decrypted
+//! when an agent process is spawned. Nothing here is ever serialized back to
+//! API clients.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use sandbox_agent_error::SandboxError;
+
+#[derive(Debug, Clone)]
+pub struct SecretInput {
+    pub name: String,
+    pub value: String,
+    pub as_file: bool,
+}
+
+/// A decrypted secret, ready to be placed into an agent process environment.
+#[derive(Debug, Clone)]
+pub struct RevealedSecret {
+    pub name: String,
+    pub value: String,
+    pub as_file: bool,
+}
+
+struct SealedSecret {
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+    as_file: bool,
+}
+
+pub struct SecretVault {
+    cipher: Aes256Gcm,
+    entries: HashMap<String, BTreeMap<String, SealedSecret>>,
+}
+
+impl std::fmt::Debug for SecretVault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretVault")
+            .field("servers", &self.entries.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for SecretVault {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SecretVault {
+    pub fn new() -> Self {
+        Self {
+            cipher: Aes256Gcm::new(&Aes256Gcm::generate_key(OsRng)),
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Seal `secrets` for `server_id`, replacing any with the same name.
+    /// Returns every secret name now stored for the server.
+    pub fn put(
+        &mut self,
+        server_id: &str,
+        secrets: Vec<SecretInput>,
+    ) -> Result<Vec<String>, SandboxError> {
+        for secret in &secrets {
+            if !is_valid_name(&secret.name) {
+                return Err(SandboxError::InvalidRequest {
+                    message: format!(
+                        "secret name '{}' must be a valid environment variable name",
+                        secret.name
+                    ),
+                });
+            }
+        }
+
+        let entries = self.entries.entry(server_id.to_string()).or_default();
+        for secret in secrets {
+            let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+            let ciphertext = self
+                .cipher
+                .encrypt(&nonce, secret.value.as_bytes())
+                .map_err(|_| SandboxError::StreamError {
+                    message: "failed to seal secret".to_string(),
+                })?;
+            entries.insert(
+                secret.name,
+                SealedSecret {
+                    nonce: nonce.to_vec(),
+                    ciphertext,
+                    as_file: secret.as_file,
+                },
+            );
+        }
+        Ok(entries.keys().cloned().collect())
+    }
+
+    pub fn reveal(&self, server_id: &str) -> Result<Vec<RevealedSecret>, SandboxError> {
+        let Some(entries) = self.entries.get(server_id) else {
+            return Ok(Vec::new());
+        };
+        entries
+            .iter()
+            .map(|(name, sealed)| {
+                let plaintext = self
+                    .cipher
+                    .decrypt(Nonce::from_slice(&sealed.nonce), sealed.ciphertext.as_ref())
+                    .map_err(|_| SandboxError::StreamError {
+                        message: format!("failed to unseal secret '{name}'"),
+                    })?;
+                Ok(RevealedSecret {
+                    name: name.clone(),
+                    value: String::from_utf8_lossy(&plaintext).into_owned(),
+                    as_file: sealed.as_file,
+                })
+            })
+            .collect()
+    }
+
+    pub fn remove_server(&mut self, server_id: &str) {
+        self.entries.remove(server_id);
+        let dir = secret_file_dir(server_id);
+        if dir.exists() {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
+
+/// Turn revealed secrets into spawn env. File secrets are written with owner-
+/// only permissions and the variable points at the file.
+pub fn secrets_to_env(
+    server_id: &str,
+    secrets: Vec<RevealedSecret>,
+) -> Result<HashMap<String, String>, SandboxError> {
+    let mut env = HashMap::new();
+    for secret in secrets {
+        if secret.as_file {
+            let path = write_secret_file(&secret_file_dir(server_id), &secret).map_err(|err| {
+                SandboxError::StreamError {
+                    message: format!("failed to write secret file '{}': {err}", secret.name),
+                }
+            })?;
+            env.insert(secret.name, path.to_string_lossy().into_owned());
+        } else {
+            env.insert(secret.name, secret.value);
+        }
+    }
+    Ok(env)
+}
+
+fn write_secret_file(dir: &Path, secret: &RevealedSecret) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(&secret.name);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    std::io::Write::write_all(&mut options.open(&path)?, secret.value.as_bytes())?;
+    Ok(path)
+}
+
+fn secret_file_dir(server_id: &str) -> PathBuf {
+    let safe = server_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    std::env::temp_dir()
+        .join("sandbox-agent-secrets")
+        .join(safe)
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
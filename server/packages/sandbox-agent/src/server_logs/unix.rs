@@ -6,6 +6,7 @@ use chrono::{Datelike, Duration, TimeDelta, TimeZone, Utc};
 pub struct ServerLogs {
     path: PathBuf,
     retention: Duration,
+    keep_stdout: bool,
 
     last_rotation: chrono::DateTime<Utc>,
     next_rotation: chrono::DateTime<Utc>,
@@ -16,11 +17,18 @@ impl ServerLogs {
         Self {
             path,
             retention: chrono::Duration::from_std(retention).expect("invalid retention duration"),
+            keep_stdout: false,
             last_rotation: Utc.timestamp_opt(0, 0).unwrap(),
             next_rotation: Utc.timestamp_opt(0, 0).unwrap(),
         }
     }
 
+    /// Leave stdout alone, for when it carries protocol traffic.
+    pub fn keep_stdout(mut self) -> Self {
+        self.keep_stdout = true;
+        self
+    }
+
     pub fn start_sync(mut self) -> Result<std::thread::JoinHandle<()>, std::io::Error> {
         std::fs::create_dir_all(&self.path)?;
         self.rotate_sync()?;
@@ -71,7 +79,9 @@ impl ServerLogs {
         let log_fd = log_file.as_raw_fd();
 
         unsafe {
-            libc::dup2(log_fd, libc::STDOUT_FILENO);
+            if !self.keep_stdout {
+                libc::dup2(log_fd, libc::STDOUT_FILENO);
+            }
             libc::dup2(log_fd, libc::STDERR_FILENO);
         }
 
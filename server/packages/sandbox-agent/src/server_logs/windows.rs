@@ -15,6 +15,7 @@ use windows::{
 pub struct ServerLogs {
     path: PathBuf,
     retention: Duration,
+    keep_stdout: bool,
 
     last_rotation: chrono::DateTime<Utc>,
     next_rotation: chrono::DateTime<Utc>,
@@ -25,11 +26,18 @@ impl ServerLogs {
         Self {
             path,
             retention: chrono::Duration::from_std(retention).expect("invalid retention duration"),
+            keep_stdout: false,
             last_rotation: Utc.timestamp_opt(0, 0).unwrap(),
             next_rotation: Utc.timestamp_opt(0, 0).unwrap(),
         }
     }
 
+    /// Leave stdout alone, for when it carries protocol traffic.
+    pub fn keep_stdout(mut self) -> Self {
+        self.keep_stdout = true;
+        self
+    }
+
     pub fn start_sync(mut self) -> Result<std::thread::JoinHandle<()>, std::io::Error> {
         std::fs::create_dir_all(&self.path)?;
         self.rotate_sync()?;
@@ -97,8 +105,11 @@ impl ServerLogs {
                 ));
             }
 
-            SetStdHandle(STD_OUTPUT_HANDLE, file_handle)
-                .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err.to_string()))?;
+            if !self.keep_stdout {
+                SetStdHandle(STD_OUTPUT_HANDLE, file_handle).map_err(|err| {
+                    std::io::Error::new(std::io::ErrorKind::Other, err.to_string())
+                })?;
+            }
             SetStdHandle(STD_ERROR_HANDLE, file_handle)
                 .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err.to_string()))?;
         }
@@ -0,0 +1,128 @@
+//! Per-agent default model and mode for new sessions.
+//!
+//! Operators set `model` and `mode` under `[agents.<id>]` in the config file,
+//! which reaches the runtime as `SANDBOX_AGENT_AGENT_DEFAULTS`. Once an agent
+//! answers `session/new`, the server switches the session to them with
+//! `session/set_model` and `session/set_mode`, and rewrites the current ids in
+//! the result so the client sees what the session got. A `session/new`
+//! envelope can pick its own with `model` and `mode` under
+//! `_meta["sandboxagent.dev"]`. Values the agent did not advertise for the
+//! session are skipped with a warning rather than failing the session.
+
+use sandbox_agent_error::SandboxError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct SessionDefaults {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) mode: Option<String>,
+}
+
+/// A session setting the server can switch after `session/new`.
+#[derive(Debug)]
+pub(crate) struct Setting {
+    pub(crate) method: &'static str,
+    pub(crate) param: &'static str,
+    /// Object of the `session/new` result holding the current and
+    /// advertised values.
+    pub(crate) group: &'static str,
+    pub(crate) current: &'static str,
+    available: &'static str,
+    /// Key of an advertised entry's id.
+    entry_id: &'static str,
+}
+
+const MODEL: Setting = Setting {
+    method: "session/set_model",
+    param: "modelId",
+    group: "models",
+    current: "currentModelId",
+    available: "availableModels",
+    entry_id: "modelId",
+};
+
+const MODE: Setting = Setting {
+    method: "session/set_mode",
+    param: "modeId",
+    group: "modes",
+    current: "currentModeId",
+    available: "availableModes",
+    entry_id: "id",
+};
+
+impl SessionDefaults {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.model.is_none() && self.mode.is_none()
+    }
+
+    /// Read `model` and `mode` from `_meta["sandboxagent.dev"]` of a
+    /// `session/new` envelope.
+    pub(crate) fn from_payload(payload: &Value) -> Result<Self, SandboxError> {
+        let field = |name: &str| match payload
+            .pointer("/params/_meta/sandboxagent.dev")
+            .and_then(|meta| meta.get(name))
+        {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::String(value)) if !value.is_empty() => Ok(Some(value.clone())),
+            Some(_) => Err(SandboxError::InvalidRequest {
+                message: format!("_meta[\"sandboxagent.dev\"].{name} must be a non-empty string"),
+            }),
+        };
+        Ok(Self {
+            model: field("model")?,
+            mode: field("mode")?,
+        })
+    }
+
+    /// `self`, with `defaults` filling in what it leaves unset.
+    pub(crate) fn or(self, defaults: Option<&Self>) -> Self {
+        let defaults = defaults.cloned().unwrap_or_default();
+        Self {
+            model: self.model.or(defaults.model),
+            mode: self.mode.or(defaults.mode),
+        }
+    }
+
+    /// The settings to switch, with their values, for a session whose
+    /// `session/new` result is `result`. Values already current are left
+    /// out, and so are values the agent advertised a list without.
+    pub(crate) fn switches(&self, result: &Value) -> Vec<(&'static Setting, String)> {
+        [(&MODEL, &self.model), (&MODE, &self.mode)]
+            .into_iter()
+            .filter_map(|(setting, value)| {
+                let value = value.as_deref()?;
+                let group = result.get(setting.group);
+                if group
+                    .and_then(|group| group.get(setting.current))
+                    .and_then(Value::as_str)
+                    == Some(value)
+                {
+                    return None;
+                }
+                let advertised = group
+                    .and_then(|group| group.get(setting.available))
+                    .and_then(Value::as_array)
+                    .map(|entries| {
+                        entries
+                            .iter()
+                            .filter_map(|entry| entry.get(setting.entry_id))
+                            .filter_map(Value::as_str)
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                if !advertised.is_empty() && !advertised.contains(&value) {
+                    tracing::warn!(
+                        setting = setting.param,
+                        value,
+                        "skipping session default the agent does not offer"
+                    );
+                    return None;
+                }
+                Some((setting, value.to_string()))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,137 @@
+//! What a server knows about its ACP sessions, and how they end.
+//!
+//! A session lives as long as its agent process. When the process exits,
+//! every session it held is ended with an `_adapter/session_ended`
+//! notification and a `session.ended` change record, so clients do not have
+//! to infer the end from `_adapter/agent_exited` or failed requests. The
+//! reason says whether the agent exited on its own (`completed` or
+//! `crashed`) or the server stopped it (`timeout` or `terminated`).
+//! Changes a client makes to an open session's mode or model are announced
+//! the same way, with `_adapter/session_updated` and `session.updated`.
+
+use sandbox_agent_agent_management::agents::AgentId;
+use serde_json::{json, Value};
+
+pub(crate) const SESSION_ENDED_METHOD: &str = "_adapter/session_ended";
+pub(crate) const SESSION_UPDATED_METHOD: &str = "_adapter/session_updated";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEndReason {
+    /// The agent process exited with status 0.
+    Completed,
+    /// The agent process exited with a failure status or a signal the
+    /// server did not send.
+    Crashed,
+    /// A turn limit fired and the agent ignored `session/cancel`.
+    Timeout,
+    /// The server was deleted, or an interrupt killed the process.
+    Terminated,
+}
+
+impl SessionEndReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Completed => "completed",
+            Self::Crashed => "crashed",
+            Self::Timeout => "timeout",
+            Self::Terminated => "terminated",
+        }
+    }
+
+    /// Why the sessions ended, given what stopped the process, if the
+    /// server did, and the exit code.
+    pub(crate) fn for_exit(stopped: Option<Self>, code: Option<i32>) -> Self {
+        match stopped {
+            Some(reason) => reason,
+            None if code == Some(0) => Self::Completed,
+            None => Self::Crashed,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionEnd {
+    pub reason: SessionEndReason,
+    pub exit_code: Option<i32>,
+    /// Signal that terminated the process (unix only).
+    pub signal: Option<i32>,
+    pub ended_at_ms: i64,
+    pub duration_ms: u64,
+}
+
+/// A session a server created or loaded.
+#[derive(Debug, Clone)]
+pub struct SessionRecord {
+    pub agent: AgentId,
+    pub created_at_ms: i64,
+    /// `modes.availableModes` of the agent's `session/new` or `session/load`
+    /// result.
+    pub available_modes: Vec<Value>,
+    /// `models.currentModelId` of that result, then the model of each
+    /// accepted `session/set_model`.
+    pub model_id: Option<String>,
+    pub available_models: Vec<Value>,
+    /// `session/prompt` requests sent to the session.
+    pub prompts: u64,
+    pub last_prompt_at_ms: Option<i64>,
+    pub ended: Option<SessionEnd>,
+}
+
+impl SessionRecord {
+    /// A session started at `created_at_ms`, with the agent's `result`.
+    pub(crate) fn new(agent: AgentId, created_at_ms: i64, result: Option<&Value>) -> Self {
+        let list = |pointer: &str| {
+            result
+                .and_then(|result| result.pointer(pointer))
+                .and_then(Value::as_array)
+                .cloned()
+                .unwrap_or_default()
+        };
+        Self {
+            agent,
+            created_at_ms,
+            available_modes: list("/modes/availableModes"),
+            model_id: result
+                .and_then(|result| result.pointer("/models/currentModelId"))
+                .and_then(Value::as_str)
+                .map(ToOwned::to_owned),
+            available_models: list("/models/availableModels"),
+            prompts: 0,
+            last_prompt_at_ms: None,
+            ended: None,
+        }
+    }
+
+    /// Mark the session ended at `ended_at_ms`, unless it already was.
+    /// Returns the params of its `_adapter/session_ended` notification and
+    /// `session.ended` record.
+    pub(crate) fn end(
+        &mut self,
+        session_id: &str,
+        reason: SessionEndReason,
+        exit_code: Option<i32>,
+        signal: Option<i32>,
+        ended_at_ms: i64,
+    ) -> Option<Value> {
+        if self.ended.is_some() {
+            return None;
+        }
+        let end = SessionEnd {
+            reason,
+            exit_code,
+            signal,
+            ended_at_ms,
+            duration_ms: ended_at_ms.saturating_sub(self.created_at_ms).max(0) as u64,
+        };
+        let record = json!({
+            "sessionId": session_id,
+            "reason": end.reason.as_str(),
+            "exitCode": end.exit_code,
+            "signal": end.signal,
+            "endedAtMs": end.ended_at_ms,
+            "durationMs": end.duration_ms,
+        });
+        self.ended = Some(end);
+        Some(record)
+    }
+}
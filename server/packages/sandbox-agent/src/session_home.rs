@@ -0,0 +1,88 @@
+//! Per-server `HOME` directories for agent processes.
+//!
+//! Agents keep global state under `$HOME` (Claude session files, OpenCode
+//! storage), so two servers for the same agent can read each other's state.
+//! With `SANDBOX_AGENT_SESSION_HOMES_DIR` set, every agent process gets
+//! `<dir>/<server_id>` as its `HOME`. Credential files from the server's own
+//! home are symlinked in so agents stay logged in; the list comes from
+//! `SANDBOX_AGENT_SESSION_HOME_LINKS` (comma-separated paths relative to
+//! `HOME`) or `DEFAULT_HOME_LINKS`.
+
+use std::path::{Path, PathBuf};
+
+const DEFAULT_HOME_LINKS: &[&str] = &[
+    ".claude/.credentials.json",
+    ".codex/auth.json",
+    ".local/share/opencode/auth.json",
+    ".config/amp",
+    ".config/gh",
+    ".gitconfig",
+    ".ssh",
+];
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SessionHomes {
+    root: Option<PathBuf>,
+    source: Option<PathBuf>,
+    links: Vec<PathBuf>,
+}
+
+impl SessionHomes {
+    pub(crate) fn from_env() -> Self {
+        let Some(root) = std::env::var_os("SANDBOX_AGENT_SESSION_HOMES_DIR")
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+        else {
+            return Self::default();
+        };
+        let links = match std::env::var("SANDBOX_AGENT_SESSION_HOME_LINKS") {
+            Ok(raw) => raw
+                .split(',')
+                .map(str::trim)
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from)
+                .collect(),
+            Err(_) => DEFAULT_HOME_LINKS.iter().map(PathBuf::from).collect(),
+        };
+        Self {
+            root: Some(root),
+            source: dirs::home_dir(),
+            links,
+        }
+    }
+
+    /// Create the home for `dir_name` and link credentials into it. Returns
+    /// `None` when isolation is off. Existing homes are reused, so a server
+    /// recreated under the same id can load its earlier sessions.
+    pub(crate) fn prepare(&self, dir_name: &str) -> std::io::Result<Option<PathBuf>> {
+        let Some(root) = self.root.as_ref() else {
+            return Ok(None);
+        };
+        let home = root.join(dir_name);
+        std::fs::create_dir_all(&home)?;
+        if let Some(source) = self.source.as_ref() {
+            for link in &self.links {
+                link_into(&source.join(link), &home.join(link))?;
+            }
+        }
+        Ok(Some(home))
+    }
+}
+
+fn link_into(target: &Path, link: &Path) -> std::io::Result<()> {
+    if !target.exists() || link.symlink_metadata().is_ok() {
+        return Ok(());
+    }
+    if let Some(parent) = link.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, link)?;
+    #[cfg(windows)]
+    if target.is_dir() {
+        std::os::windows::fs::symlink_dir(target, link)?;
+    } else {
+        std::os::windows::fs::symlink_file(target, link)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,370 @@
+//! One SSE connection carrying envelopes from many ACP servers.
+//!
+//! A client opens `GET /v1/stream`, reads its channel id from the first
+//! event, then adds and removes servers through the subscribe endpoints.
+//! Browsers cap concurrent connections per origin, so one `GET /v1/acp/{id}`
+//! per server stops scaling after a handful.
+//!
+//! A firehose channel (`GET /v1/stream?all=true`) subscribes itself: to every
+//! running server from its latest envelope, and to each new server from its
+//! first, optionally narrowed to one agent and a set of methods.
+//!
+//! A channel opened with a namespace token belongs to that namespace: only
+//! the same namespace can change its subscriptions, it reaches only the
+//! namespace's servers, and it names them without the namespace prefix.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use axum::response::sse::Event;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use futures::StreamExt;
+use sandbox_agent_agent_management::agents::AgentId;
+use sandbox_agent_error::SandboxError;
+use serde_json::{json, Value};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+
+use crate::acp_proxy_runtime::{AcpProxyRuntime, PinBoxSseStream, SequencedPayloadStream};
+use crate::namespaces::Namespace;
+use crate::subagents;
+
+const CHANNEL_BUFFER: usize = 256;
+
+#[derive(Debug, Default)]
+pub struct StreamMux {
+    channels: Arc<Mutex<HashMap<String, MuxChannel>>>,
+}
+
+#[derive(Debug)]
+struct MuxChannel {
+    sender: mpsc::Sender<Event>,
+    subscriptions: HashMap<String, JoinHandle<()>>,
+    /// Envelope methods to forward; everything when unset.
+    methods: Option<Arc<HashSet<String>>>,
+    /// Firehose task subscribing the channel to servers as they appear.
+    follower: Option<JoinHandle<()>>,
+    namespace: Option<Namespace>,
+}
+
+/// Which servers and envelopes a firehose channel carries.
+#[derive(Debug, Clone, Default)]
+pub struct FirehoseFilter {
+    pub agent: Option<AgentId>,
+    pub methods: Option<HashSet<String>>,
+}
+
+impl MuxChannel {
+    /// Subscribed servers, as the channel's owner names them.
+    fn server_ids(&self) -> Vec<String> {
+        let mut ids = self
+            .subscriptions
+            .iter()
+            .filter(|(_, task)| !task.is_finished())
+            .filter_map(|(server_id, _)| display_id(self.namespace.as_ref(), server_id))
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    /// Id the runtime stores a server the owner calls `server_id` under.
+    fn stored_id(&self, server_id: &str) -> String {
+        match &self.namespace {
+            Some(namespace) => namespace.scope(server_id),
+            None => server_id.to_string(),
+        }
+    }
+}
+
+/// Id the owner of a channel in `namespace` calls a stored server by, or
+/// `None` when the server is outside the namespace.
+fn display_id(namespace: Option<&Namespace>, server_id: &str) -> Option<String> {
+    match namespace {
+        Some(namespace) => namespace.unscope(server_id).map(str::to_string),
+        None => Some(server_id.to_string()),
+    }
+}
+
+/// Drops the channel, and stops its forwarders, when the client disconnects.
+struct ChannelGuard {
+    channels: Arc<Mutex<HashMap<String, MuxChannel>>>,
+    channel_id: String,
+}
+
+impl Drop for ChannelGuard {
+    fn drop(&mut self) {
+        let removed = self
+            .channels
+            .lock()
+            .ok()
+            .and_then(|mut channels| channels.remove(&self.channel_id));
+        if let Some(channel) = removed {
+            if let Some(follower) = channel.follower {
+                follower.abort();
+            }
+            for task in channel.subscriptions.into_values() {
+                task.abort();
+            }
+        }
+    }
+}
+
+impl StreamMux {
+    /// Open a channel. The stream starts with a `channel` event naming it.
+    pub fn open(&self, namespace: Option<Namespace>) -> PinBoxSseStream {
+        self.open_channel(None, namespace).1
+    }
+
+    /// Open a channel that follows every server matching `filter`, including
+    /// ones created after it opens.
+    pub fn open_firehose(
+        &self,
+        acp_proxy: AcpProxyRuntime,
+        filter: FirehoseFilter,
+        namespace: Option<Namespace>,
+    ) -> PinBoxSseStream {
+        let methods = filter.methods.map(Arc::new);
+        let (channel_id, stream) = self.open_channel(methods, namespace.clone());
+        // Subscribe to creations before the follower lists running servers
+        // so none created in between, or before it first runs, are missed.
+        let created = acp_proxy.subscribe_created();
+        let follower = tokio::spawn(follow_servers(
+            self.channels.clone(),
+            channel_id.clone(),
+            acp_proxy,
+            created,
+            filter.agent,
+            namespace,
+        ));
+        match self.lock().get_mut(&channel_id) {
+            Some(channel) => channel.follower = Some(follower),
+            None => follower.abort(),
+        }
+        stream
+    }
+
+    fn open_channel(
+        &self,
+        methods: Option<Arc<HashSet<String>>>,
+        namespace: Option<Namespace>,
+    ) -> (String, PinBoxSseStream) {
+        let channel_id = new_channel_id();
+        let (sender, receiver) = mpsc::channel(CHANNEL_BUFFER);
+        let _ = sender.try_send(
+            Event::default()
+                .event("channel")
+                .data(json!({ "channelId": channel_id }).to_string()),
+        );
+        self.lock().insert(
+            channel_id.clone(),
+            MuxChannel {
+                sender,
+                subscriptions: HashMap::new(),
+                methods,
+                follower: None,
+                namespace,
+            },
+        );
+
+        let guard = ChannelGuard {
+            channels: self.channels.clone(),
+            channel_id: channel_id.clone(),
+        };
+        let stream =
+            futures::stream::unfold((receiver, guard), |(mut receiver, guard)| async move {
+                let event = receiver.recv().await?;
+                Some((Ok(event), (receiver, guard)))
+            });
+        (channel_id, Box::pin(stream))
+    }
+
+    /// Start forwarding each server's envelopes into the channel, replacing
+    /// any existing subscription so a new `last_event_ids` entry takes effect.
+    /// Returns every server the channel is now subscribed to.
+    pub async fn subscribe(
+        &self,
+        acp_proxy: &AcpProxyRuntime,
+        channel_id: &str,
+        namespace: Option<&Namespace>,
+        server_ids: Vec<String>,
+        last_event_ids: BTreeMap<String, u64>,
+    ) -> Result<Vec<String>, SandboxError> {
+        let (sender, stored_ids) = {
+            let mut channels = self.lock();
+            let channel = owned_channel(&mut channels, channel_id, namespace)?;
+            let stored_ids = server_ids
+                .iter()
+                .map(|server_id| channel.stored_id(server_id))
+                .collect::<Vec<_>>();
+            (channel.sender.clone(), stored_ids)
+        };
+
+        // Resolve every server before touching the channel so an unknown id
+        // leaves existing subscriptions alone.
+        let mut streams = Vec::with_capacity(server_ids.len());
+        for (server_id, stored_id) in server_ids.into_iter().zip(stored_ids) {
+            let last_event_id = last_event_ids.get(&server_id).copied();
+            let stream = acp_proxy
+                .sequenced_stream(&stored_id, last_event_id, false)
+                .await
+                .map_err(|err| match err {
+                    SandboxError::SessionNotFound { .. } => SandboxError::SessionNotFound {
+                        session_id: server_id.clone(),
+                    },
+                    other => other,
+                })?;
+            streams.push((server_id, stored_id, stream));
+        }
+
+        let mut channels = self.lock();
+        let channel = channels
+            .get_mut(channel_id)
+            .ok_or_else(|| channel_not_found(channel_id))?;
+        for (server_id, stored_id, stream) in streams {
+            let task = spawn_forwarder(sender.clone(), server_id, stream, channel.methods.clone());
+            if let Some(previous) = channel.subscriptions.insert(stored_id, task) {
+                previous.abort();
+            }
+        }
+        Ok(channel.server_ids())
+    }
+
+    pub fn unsubscribe(
+        &self,
+        channel_id: &str,
+        namespace: Option<&Namespace>,
+        server_ids: &[String],
+    ) -> Result<Vec<String>, SandboxError> {
+        let mut channels = self.lock();
+        let channel = owned_channel(&mut channels, channel_id, namespace)?;
+        for server_id in server_ids {
+            if let Some(task) = channel.subscriptions.remove(&channel.stored_id(server_id)) {
+                task.abort();
+            }
+        }
+        Ok(channel.server_ids())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, MuxChannel>> {
+        self.channels
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn spawn_forwarder(
+    sender: mpsc::Sender<Event>,
+    server_id: String,
+    mut stream: SequencedPayloadStream,
+    methods: Option<Arc<HashSet<String>>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Some((sequence, payload)) = stream.next().await {
+            if let Some(methods) = &methods {
+                let method = payload.get("method").and_then(Value::as_str);
+                if !method.is_some_and(|method| methods.contains(method)) {
+                    continue;
+                }
+            }
+            let mut envelope = json!({
+                "serverId": server_id,
+                "sequence": sequence,
+            });
+            if let Some(parent_id) = subagents::parent_id(&payload) {
+                envelope["parentId"] = json!(parent_id);
+            }
+            envelope["payload"] = payload;
+            let event = Event::default().event("message").data(envelope.to_string());
+            if sender.send(event).await.is_err() {
+                return;
+            }
+        }
+        let _ = sender
+            .send(
+                Event::default()
+                    .event("closed")
+                    .data(json!({ "serverId": server_id }).to_string()),
+            )
+            .await;
+    })
+}
+
+/// Subscribe a firehose channel to running servers of its namespace from
+/// their latest envelope and to new ones from their first, until the channel
+/// closes.
+async fn follow_servers(
+    channels: Arc<Mutex<HashMap<String, MuxChannel>>>,
+    channel_id: String,
+    acp_proxy: AcpProxyRuntime,
+    mut created: broadcast::Receiver<(String, AgentId)>,
+    agent: Option<AgentId>,
+    namespace: Option<Namespace>,
+) {
+    let mut seen = HashSet::new();
+    let mut pending = acp_proxy.server_sequences().await;
+    loop {
+        for (server_id, server_agent, after) in pending.drain(..) {
+            if agent.is_some_and(|agent| agent != server_agent) || !seen.insert(server_id.clone()) {
+                continue;
+            }
+            let Some(display_id) = display_id(namespace.as_ref(), &server_id) else {
+                continue;
+            };
+            let Ok(stream) = acp_proxy
+                .sequenced_stream(&server_id, Some(after), false)
+                .await
+            else {
+                continue;
+            };
+            let mut channels = channels
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            let Some(channel) = channels.get_mut(&channel_id) else {
+                return;
+            };
+            let task = spawn_forwarder(
+                channel.sender.clone(),
+                display_id,
+                stream,
+                channel.methods.clone(),
+            );
+            channel.subscriptions.insert(server_id, task);
+        }
+        match created.recv().await {
+            Ok((server_id, server_agent)) => pending.push((server_id, server_agent, 0)),
+            // Missed some creations; pick up whatever is running now.
+            Err(broadcast::error::RecvError::Lagged(_)) => {
+                pending = acp_proxy.server_sequences().await;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+/// The channel, if `namespace` owns it. Channels of other namespaces read as
+/// unknown.
+fn owned_channel<'a>(
+    channels: &'a mut HashMap<String, MuxChannel>,
+    channel_id: &str,
+    namespace: Option<&Namespace>,
+) -> Result<&'a mut MuxChannel, SandboxError> {
+    channels
+        .get_mut(channel_id)
+        .filter(|channel| channel.namespace.as_ref() == namespace)
+        .ok_or_else(|| channel_not_found(channel_id))
+}
+
+fn channel_not_found(channel_id: &str) -> SandboxError {
+    SandboxError::SessionNotFound {
+        session_id: channel_id.to_string(),
+    }
+}
+
+fn new_channel_id() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
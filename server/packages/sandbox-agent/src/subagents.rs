@@ -0,0 +1,113 @@
+//! Sub-agents started from a session.
+//!
+//! Claude's `Task` tool and OpenCode's `task` tool hand work to a nested
+//! agent. Both take a `subagent_type` input, so a tool call with one is a
+//! child session, named after its `toolCallId`. Claude forwards the nested
+//! agent's own updates in the parent session with
+//! `_meta.claudeCode.parentToolUseId` set to that id; those envelopes get it
+//! as their `parentId`. OpenCode only reports the task call itself.
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::compaction::session_update;
+
+/// Tool names that start a sub-agent, from `_meta.claudeCode.toolName`.
+const SUBAGENT_TOOLS: [&str; 2] = ["Task", "task"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChildSession {
+    /// `toolCallId` of the tool call that started the sub-agent.
+    pub id: String,
+    /// Child session this one was started from, when sub-agents nest.
+    pub parent_id: Option<String>,
+    /// The `subagent_type` input.
+    pub agent: Option<String>,
+    pub description: Option<String>,
+    /// Latest tool call status.
+    pub status: Option<String>,
+    /// Sequence of the envelope that started it.
+    pub started_sequence: u64,
+    /// Envelopes the sub-agent sent itself.
+    pub event_count: usize,
+}
+
+/// The child session an envelope belongs to.
+pub(crate) fn parent_id(payload: &Value) -> Option<String> {
+    payload
+        .pointer("/params/update/_meta/claudeCode/parentToolUseId")
+        .and_then(Value::as_str)
+        .map(ToOwned::to_owned)
+}
+
+/// Sub-agents `session_id` started in `events`, in the order they started.
+pub(crate) fn child_sessions(events: &[(u64, Value)], session_id: &str) -> Vec<ChildSession> {
+    let mut children: Vec<ChildSession> = Vec::new();
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for (sequence, payload) in events {
+        let Some(update) = session_update(payload, session_id) else {
+            continue;
+        };
+        let parent = parent_id(payload);
+        if let Some(parent) = &parent {
+            *counts.entry(parent.clone()).or_default() += 1;
+        }
+        if !matches!(
+            update.get("sessionUpdate").and_then(Value::as_str),
+            Some("tool_call" | "tool_call_update")
+        ) {
+            continue;
+        }
+        let Some(tool_call_id) = update.get("toolCallId").and_then(Value::as_str) else {
+            continue;
+        };
+        let known = children.iter().position(|child| child.id == tool_call_id);
+        if known.is_none() && !starts_subagent(update) {
+            continue;
+        }
+        let index = known.unwrap_or_else(|| {
+            children.push(ChildSession {
+                id: tool_call_id.to_string(),
+                parent_id: parent.clone(),
+                agent: None,
+                description: None,
+                status: None,
+                started_sequence: *sequence,
+                event_count: 0,
+            });
+            children.len() - 1
+        });
+        let child = &mut children[index];
+        let input = |key: &str| {
+            update
+                .get("rawInput")
+                .and_then(|input| input.get(key))
+                .and_then(Value::as_str)
+                .map(ToOwned::to_owned)
+        };
+        if let Some(agent) = input("subagent_type") {
+            child.agent = Some(agent);
+        }
+        if let Some(description) = input("description") {
+            child.description = Some(description);
+        }
+        if let Some(status) = update.get("status").and_then(Value::as_str) {
+            child.status = Some(status.to_string());
+        }
+    }
+    for child in &mut children {
+        child.event_count = counts.get(&child.id).copied().unwrap_or(0);
+    }
+    children
+}
+
+fn starts_subagent(update: &Value) -> bool {
+    update
+        .pointer("/rawInput/subagent_type")
+        .is_some_and(Value::is_string)
+        || update
+            .pointer("/_meta/claudeCode/toolName")
+            .and_then(Value::as_str)
+            .is_some_and(|name| SUBAGENT_TOOLS.contains(&name))
+}
@@ -0,0 +1,322 @@
+//! One prompt fanned out to several agents.
+//!
+//! `POST /v1/tasks` starts an ACP server per agent, each with its own
+//! process and session, sends every one the same prompt, and tracks how each
+//! run ends along with the text the agent replied with. The servers stay up
+//! afterwards, so clients can read their streams or keep prompting them.
+//! Deleting the task closes them.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use futures::{FutureExt, StreamExt};
+use sandbox_agent_agent_management::agents::AgentId;
+use sandbox_agent_error::SandboxError;
+use serde_json::{json, Value};
+
+use crate::acp_proxy_runtime::AcpProxyRuntime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskRunStatus {
+    /// Starting the agent and creating its session.
+    Starting,
+    /// The prompt was sent and the agent has not answered yet.
+    Running,
+    Completed,
+    Failed,
+}
+
+impl TaskRunStatus {
+    fn is_finished(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskRun {
+    pub server_id: String,
+    pub agent: AgentId,
+    pub status: TaskRunStatus,
+    pub session_id: Option<String>,
+    pub stop_reason: Option<String>,
+    /// Text of the agent's `agent_message_chunk` updates for the prompt.
+    pub output: String,
+    pub error: Option<String>,
+    pub finished_at_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskRecord {
+    pub task_id: String,
+    pub prompt: String,
+    pub created_at_ms: i64,
+    pub runs: Vec<TaskRun>,
+}
+
+impl TaskRecord {
+    /// Whether every run has completed or failed.
+    pub fn is_finished(&self) -> bool {
+        self.runs.iter().all(|run| run.status.is_finished())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskAgent {
+    pub agent: AgentId,
+    /// Spawn environment for the agent process, as in `_meta["sandboxagent.dev"].env`.
+    pub env: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskSpec {
+    pub prompt: String,
+    pub cwd: String,
+    pub agents: Vec<TaskAgent>,
+}
+
+type TaskMap = Arc<Mutex<HashMap<String, TaskRecord>>>;
+
+#[derive(Debug, Clone, Default)]
+pub struct TaskRunner {
+    tasks: TaskMap,
+}
+
+impl TaskRunner {
+    /// Record the task and start every run in the background.
+    pub fn start(&self, acp_proxy: AcpProxyRuntime, spec: TaskSpec) -> TaskRecord {
+        let task_id = new_task_id();
+        let record = TaskRecord {
+            task_id: task_id.clone(),
+            prompt: spec.prompt.clone(),
+            created_at_ms: now_ms(),
+            runs: spec
+                .agents
+                .iter()
+                .enumerate()
+                .map(|(index, agent)| TaskRun {
+                    server_id: format!("task-{task_id}-{index}"),
+                    agent: agent.agent,
+                    status: TaskRunStatus::Starting,
+                    session_id: None,
+                    stop_reason: None,
+                    output: String::new(),
+                    error: None,
+                    finished_at_ms: None,
+                })
+                .collect(),
+        };
+        self.lock().insert(task_id.clone(), record.clone());
+
+        for (index, agent) in spec.agents.into_iter().enumerate() {
+            let run = RunHandle {
+                tasks: self.tasks.clone(),
+                task_id: task_id.clone(),
+                index,
+            };
+            tokio::spawn(run.drive(
+                acp_proxy.clone(),
+                agent,
+                spec.prompt.clone(),
+                spec.cwd.clone(),
+            ));
+        }
+        record
+    }
+
+    pub fn get(&self, task_id: &str) -> Option<TaskRecord> {
+        self.lock().get(task_id).cloned()
+    }
+
+    /// Every task, oldest first.
+    pub fn list(&self) -> Vec<TaskRecord> {
+        let mut tasks = self.lock().values().cloned().collect::<Vec<_>>();
+        tasks.sort_by(|left, right| {
+            (left.created_at_ms, &left.task_id).cmp(&(right.created_at_ms, &right.task_id))
+        });
+        tasks
+    }
+
+    /// Forget the task and close its servers.
+    pub async fn delete(
+        &self,
+        acp_proxy: &AcpProxyRuntime,
+        task_id: &str,
+    ) -> Result<(), SandboxError> {
+        let record = self
+            .lock()
+            .remove(task_id)
+            .ok_or_else(|| SandboxError::SessionNotFound {
+                session_id: task_id.to_string(),
+            })?;
+        for run in record.runs {
+            acp_proxy.delete(&run.server_id).await?;
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, TaskRecord>> {
+        self.tasks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// One run's slot in a task, updated as the run progresses.
+struct RunHandle {
+    tasks: TaskMap,
+    task_id: String,
+    index: usize,
+}
+
+impl RunHandle {
+    async fn drive(
+        self,
+        acp_proxy: AcpProxyRuntime,
+        agent: TaskAgent,
+        prompt: String,
+        cwd: String,
+    ) {
+        let Some(server_id) = self.update(|_| {}).map(|run| run.server_id) else {
+            return;
+        };
+        match run_prompt(&acp_proxy, &self, &server_id, agent, &prompt, &cwd).await {
+            Ok(stop_reason) => self.update(|run| {
+                run.status = TaskRunStatus::Completed;
+                run.stop_reason = stop_reason;
+                run.finished_at_ms = Some(now_ms());
+            }),
+            Err(err) => {
+                tracing::warn!(
+                    task_id = %self.task_id,
+                    server_id = %server_id,
+                    error = %err,
+                    "tasks: run failed"
+                );
+                self.update(|run| {
+                    run.status = TaskRunStatus::Failed;
+                    run.error = Some(err.to_string());
+                    run.finished_at_ms = Some(now_ms());
+                })
+            }
+        };
+    }
+
+    /// Apply `change` to the run and return it, or `None` once the task
+    /// was deleted.
+    fn update(&self, change: impl FnOnce(&mut TaskRun)) -> Option<TaskRun> {
+        let mut tasks = self
+            .tasks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let run = tasks.get_mut(&self.task_id)?.runs.get_mut(self.index)?;
+        change(run);
+        Some(run.clone())
+    }
+}
+
+/// Initialize the agent, create a session and prompt it. Returns the
+/// prompt's stop reason.
+async fn run_prompt(
+    acp_proxy: &AcpProxyRuntime,
+    run: &RunHandle,
+    server_id: &str,
+    agent: TaskAgent,
+    prompt: &str,
+    cwd: &str,
+) -> Result<Option<String>, SandboxError> {
+    let initialize = json!({
+        "jsonrpc": "2.0",
+        "id": "sandboxagent-task-initialize",
+        "method": "initialize",
+        "params": {
+            "protocolVersion": 1,
+            "clientCapabilities": {},
+            "_meta": { "sandboxagent.dev": { "env": agent.env } }
+        }
+    });
+    acp_proxy
+        .post_expect_result(server_id, Some(agent.agent), initialize)
+        .await?;
+
+    let session_new = json!({
+        "jsonrpc": "2.0",
+        "id": "sandboxagent-task-session-new",
+        "method": "session/new",
+        "params": { "cwd": cwd, "mcpServers": [] }
+    });
+    let session = acp_proxy
+        .post_expect_result(server_id, None, session_new)
+        .await?;
+    let session_id = session
+        .get("sessionId")
+        .and_then(Value::as_str)
+        .ok_or_else(|| SandboxError::UpstreamAgentError {
+            message: "agent did not return a sessionId for session/new".to_string(),
+        })?
+        .to_string();
+    run.update(|run| {
+        run.status = TaskRunStatus::Running;
+        run.session_id = Some(session_id.clone());
+    });
+
+    // Subscribe before prompting so no reply chunk is missed.
+    let mut envelopes = acp_proxy.sequenced_stream(server_id, None, false).await?;
+    let session_prompt = json!({
+        "jsonrpc": "2.0",
+        "id": "sandboxagent-task-prompt",
+        "method": "session/prompt",
+        "params": {
+            "sessionId": session_id,
+            "prompt": [{ "type": "text", "text": prompt }]
+        }
+    });
+    let result = acp_proxy
+        .post_expect_result(server_id, None, session_prompt)
+        .await;
+
+    // The agent writes its updates before the prompt response, so they are
+    // all buffered by now.
+    let mut output = String::new();
+    while let Some(Some((_, payload))) = envelopes.next().now_or_never() {
+        if payload.pointer("/params/sessionId").and_then(Value::as_str) != Some(session_id.as_str())
+        {
+            continue;
+        }
+        let update = payload.pointer("/params/update");
+        if update
+            .and_then(|update| update.get("sessionUpdate"))
+            .and_then(Value::as_str)
+            == Some("agent_message_chunk")
+        {
+            if let Some(text) = update
+                .and_then(|update| update.pointer("/content/text"))
+                .and_then(Value::as_str)
+            {
+                output.push_str(text);
+            }
+        }
+    }
+    run.update(|run| run.output = output);
+
+    Ok(result?
+        .get("stopReason")
+        .and_then(Value::as_str)
+        .map(ToOwned::to_owned))
+}
+
+pub(crate) fn new_task_id() -> String {
+    let mut bytes = [0u8; 9];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub(crate) fn now_ms() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or(0)
+}
@@ -0,0 +1,103 @@
+//! Session transcripts collapsed from the event stream.
+//!
+//! ACP agents stream each reply as many `agent_message_chunk` deltas, with
+//! tool calls and thoughts interleaved. `GET /v1/acp/{server_id}/sessions/
+//! {session_id}/transcript` merges them here into one message per speaker
+//! and turn, so scripts get final text without reimplementing the merge.
+//! `user_message_chunk` updates, which agents send when replaying a loaded
+//! session, become user messages the same way.
+
+use serde_json::Value;
+
+use crate::compaction::session_update;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TranscriptMessage {
+    pub role: &'static str,
+    pub text: String,
+    /// Turn number from `_adapter/turn_started`; unset for replayed history.
+    pub turn: Option<u64>,
+}
+
+/// Messages for `session_id` in stream order. A new message starts when the
+/// speaker or the turn changes. Text the agent streams on either side of a
+/// tool call is joined with a blank line.
+pub(crate) fn collect(events: &[(u64, Value)], session_id: &str) -> Vec<TranscriptMessage> {
+    let mut messages: Vec<TranscriptMessage> = Vec::new();
+    let mut turn = None;
+    // Set by a tool call so the next chunk starts a new paragraph.
+    let mut paragraph_break = false;
+    for (_, payload) in events {
+        if payload.get("method").and_then(Value::as_str) == Some("_adapter/turn_started")
+            && payload.pointer("/params/sessionId").and_then(Value::as_str) == Some(session_id)
+        {
+            turn = payload.pointer("/params/turn").and_then(Value::as_u64);
+            paragraph_break = false;
+            continue;
+        }
+        let Some(update) = session_update(payload, session_id) else {
+            continue;
+        };
+        let role = match update.get("sessionUpdate").and_then(Value::as_str) {
+            Some("agent_message_chunk") => "assistant",
+            Some("user_message_chunk") => "user",
+            Some("tool_call") => {
+                paragraph_break = true;
+                continue;
+            }
+            _ => continue,
+        };
+        let Some(text) = update.pointer("/content/text").and_then(Value::as_str) else {
+            continue;
+        };
+        match messages.last_mut() {
+            Some(last) if last.role == role && last.turn == turn => {
+                if paragraph_break && !last.text.is_empty() {
+                    last.text.push_str("\n\n");
+                }
+                last.text.push_str(text);
+            }
+            _ => messages.push(TranscriptMessage {
+                role,
+                text: text.to_string(),
+                turn,
+            }),
+        }
+        paragraph_break = false;
+    }
+    for message in &mut messages {
+        message.text = message.text.trim().to_string();
+    }
+    messages.retain(|message| !message.text.is_empty());
+    messages
+}
+
+/// The assistant's messages separated by blank lines.
+pub(crate) fn to_text(messages: &[TranscriptMessage]) -> String {
+    let mut text = messages
+        .iter()
+        .filter(|message| message.role == "assistant")
+        .map(|message| message.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if !text.is_empty() {
+        text.push('\n');
+    }
+    text
+}
+
+/// Every message under a `## User` or `## Assistant` heading.
+pub(crate) fn to_markdown(messages: &[TranscriptMessage]) -> String {
+    messages
+        .iter()
+        .map(|message| {
+            let heading = if message.role == "user" {
+                "User"
+            } else {
+                "Assistant"
+            };
+            format!("## {heading}\n\n{}\n", message.text)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,97 @@
+//! Non-TCP listeners for the HTTP router.
+//!
+//! A supervisor that embeds the server can skip the TCP port entirely: a
+//! Unix domain socket is guarded by its file permissions, and stdio mode
+//! serves a single HTTP/1.1 connection over the process's stdin/stdout.
+//! Log output already goes to stderr, so stdout carries only responses.
+
+use std::future::Future;
+use std::io;
+#[cfg(unix)]
+use std::path::Path;
+
+use axum::Router;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::service::TowerToHyperService;
+
+/// Accept connections on `path` until `shutdown` resolves.
+///
+/// A stale socket left by a previous run is replaced; any other file at
+/// `path` is an error. The socket is created owner-only (0600) and removed
+/// on return.
+#[cfg(unix)]
+pub async fn serve_unix(
+    path: &Path,
+    router: Router,
+    shutdown: impl Future<Output = ()>,
+) -> io::Result<()> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", path.display()),
+            ));
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = tokio::net::UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+
+    tokio::pin!(shutdown);
+    let result = loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let stream = match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(err) => break Err(err),
+                };
+                let service = TowerToHyperService::new(router.clone());
+                tokio::spawn(async move {
+                    if let Err(err) = Builder::new(TokioExecutor::new())
+                        .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                        .await
+                    {
+                        tracing::debug!(error = %err, "unix socket connection ended");
+                    }
+                });
+            }
+            _ = &mut shutdown => break Ok(()),
+        }
+    };
+    let _ = std::fs::remove_file(path);
+    result
+}
+
+#[cfg(not(unix))]
+pub async fn serve_unix(
+    path: &std::path::Path,
+    _router: Router,
+    _shutdown: impl Future<Output = ()>,
+) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "unix sockets are not supported on this platform: {}",
+            path.display()
+        ),
+    ))
+}
+
+/// Serve one connection over stdin/stdout. Returns when the peer closes
+/// stdin or `shutdown` resolves.
+pub async fn serve_stdio(router: Router, shutdown: impl Future<Output = ()>) -> io::Result<()> {
+    let io = tokio::io::join(tokio::io::stdin(), tokio::io::stdout());
+    let service = TowerToHyperService::new(router);
+    let mut builder = Builder::new(TokioExecutor::new());
+    // Let a peer that closes stdin after its last request still read the
+    // response.
+    builder.http1().half_close(true);
+    let connection = builder.serve_connection_with_upgrades(TokioIo::new(io), service);
+    tokio::select! {
+        result = connection => result.map_err(io::Error::other),
+        _ = shutdown => Ok(()),
+    }
+}
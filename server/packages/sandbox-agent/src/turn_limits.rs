@@ -0,0 +1,118 @@
+//! Per-turn time limits for `session/prompt`.
+//!
+//! A wedged agent can hold a prompt open with no output and no exit. Two
+//! limits catch that: `turn` caps the whole prompt round trip and `idle` caps
+//! the gap between envelopes from the agent process. Server-wide defaults
+//! come from `turn_timeout_ms` and `turn_idle_timeout_ms` under `[limits]`
+//! (or `SANDBOX_AGENT_TURN_TIMEOUT_MS` and
+//! `SANDBOX_AGENT_TURN_IDLE_TIMEOUT_MS`); a `session/new` envelope can
+//! override them for its session with `turnTimeoutMs` and `idleTimeoutMs`
+//! under `_meta["sandboxagent.dev"]`. Both are off unless configured.
+
+use std::time::{Duration, Instant};
+
+use acp_http_adapter::process::AdapterRuntime;
+use sandbox_agent_error::SandboxError;
+use serde_json::Value;
+
+use crate::config::LimitSettings;
+
+/// How often the idle watchdog checks for new output.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct TurnLimits {
+    pub(crate) turn: Option<Duration>,
+    pub(crate) idle: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TurnTimeoutKind {
+    Turn,
+    Idle,
+}
+
+impl TurnTimeoutKind {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Turn => "turn_timeout",
+            Self::Idle => "idle_timeout",
+        }
+    }
+}
+
+impl TurnLimits {
+    pub(crate) fn from_config(limits: &LimitSettings) -> Self {
+        Self {
+            turn: positive_ms(limits.turn_timeout_ms),
+            idle: positive_ms(limits.turn_idle_timeout_ms),
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.turn.is_some() || self.idle.is_some()
+    }
+
+    /// Apply `turnTimeoutMs` / `idleTimeoutMs` from a `session/new` envelope
+    /// on top of `self`. `0` turns a limit off.
+    pub(crate) fn with_overrides(self, payload: &Value) -> Result<Self, SandboxError> {
+        Ok(Self {
+            turn: override_ms(payload, "turnTimeoutMs")?.unwrap_or(self.turn),
+            idle: override_ms(payload, "idleTimeoutMs")?.unwrap_or(self.idle),
+        })
+    }
+
+    /// Resolve once the turn that started at `started` exceeds a limit.
+    /// Any envelope from the agent process, including notifications for
+    /// other sessions, counts as output for the idle limit. Pending forever
+    /// when no limit is set.
+    pub(crate) async fn watch(
+        &self,
+        runtime: &AdapterRuntime,
+        started: Instant,
+    ) -> TurnTimeoutKind {
+        let mut last_sequence = runtime.last_sequence();
+        let mut last_output = started;
+        loop {
+            let now = Instant::now();
+            if self
+                .turn
+                .is_some_and(|turn| now.saturating_duration_since(started) >= turn)
+            {
+                return TurnTimeoutKind::Turn;
+            }
+            let sequence = runtime.last_sequence();
+            if sequence != last_sequence {
+                last_sequence = sequence;
+                last_output = now;
+            } else if self
+                .idle
+                .is_some_and(|idle| now.saturating_duration_since(last_output) >= idle)
+            {
+                return TurnTimeoutKind::Idle;
+            }
+            if self.is_enabled() {
+                tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+            } else {
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+fn override_ms(payload: &Value, key: &str) -> Result<Option<Option<Duration>>, SandboxError> {
+    let Some(value) = payload
+        .pointer("/params/_meta/sandboxagent.dev")
+        .and_then(|meta| meta.get(key))
+    else {
+        return Ok(None);
+    };
+    let millis = value.as_u64().ok_or_else(|| SandboxError::InvalidRequest {
+        message: format!("_meta[\"sandboxagent.dev\"].{key} must be a non-negative integer"),
+    })?;
+    Ok(Some((millis > 0).then(|| Duration::from_millis(millis))))
+}
+
+fn positive_ms(millis: Option<u64>) -> Option<Duration> {
+    millis.filter(|value| *value > 0).map(Duration::from_millis)
+}
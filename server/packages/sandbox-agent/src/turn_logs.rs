@@ -0,0 +1,215 @@
+//! Raw agent output per turn.
+//!
+//! With `SANDBOX_AGENT_TURN_LOG_DIR` set, every stdout and stderr line an
+//! agent process writes while a `session/prompt` is in flight is saved,
+//! gzip-compressed, to `<dir>/<server_id>/turn-<n>.log.gz`. An
+//! `_adapter/invalid_stdout` notification can then be traced back to
+//! exactly what the agent printed around it. Each turn keeps up to
+//! `SANDBOX_AGENT_TURN_LOG_MAX_BYTES` (default 4 MiB) of uncompressed
+//! output, and only the newest `KEPT_TURNS` turns of a server are kept.
+//! Turns that overlap on one process share its output, so each file holds
+//! every line written while its own turn was running.
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use acp_http_adapter::process::{AdapterRuntime, RawOutputLine};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+use tokio::sync::{broadcast, oneshot};
+use tokio::task::JoinHandle;
+
+const DEFAULT_MAX_BYTES: usize = 4 * 1024 * 1024;
+const KEPT_TURNS: u64 = 50;
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TurnLogs {
+    dir: Option<PathBuf>,
+    max_bytes: usize,
+}
+
+impl TurnLogs {
+    pub(crate) fn from_env() -> Self {
+        let dir = std::env::var_os("SANDBOX_AGENT_TURN_LOG_DIR")
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from);
+        let max_bytes = std::env::var("SANDBOX_AGENT_TURN_LOG_MAX_BYTES")
+            .ok()
+            .and_then(|raw| raw.trim().parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MAX_BYTES);
+        Self { dir, max_bytes }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    /// Directory holding the turns of the server stored as `dir_name`.
+    pub(crate) fn server_dir(&self, dir_name: &str) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| dir.join(dir_name))
+    }
+
+    /// Start recording `runtime`'s output as turn `turn`. The file is
+    /// written when the returned capture is finished or dropped.
+    pub(crate) fn start(
+        &self,
+        dir_name: &str,
+        turn: u64,
+        runtime: &AdapterRuntime,
+    ) -> Option<TurnCapture> {
+        let dir = self.server_dir(dir_name)?;
+        let receiver = runtime.subscribe_raw_output();
+        let (stop, stopped) = oneshot::channel();
+        let task = tokio::spawn(record(receiver, stopped, dir, turn, self.max_bytes));
+        Some(TurnCapture {
+            stop: Some(stop),
+            task: Some(task),
+        })
+    }
+
+    /// Decompressed output of turn `turn`, or `None` when it was not kept.
+    pub(crate) async fn read(&self, dir_name: &str, turn: u64) -> std::io::Result<Option<String>> {
+        let Some(dir) = self.server_dir(dir_name) else {
+            return Ok(None);
+        };
+        let path = turn_path(&dir, turn);
+        tokio::task::spawn_blocking(move || {
+            let file = match std::fs::File::open(&path) {
+                Ok(file) => file,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                Err(err) => return Err(err),
+            };
+            let mut text = String::new();
+            GzDecoder::new(file).read_to_string(&mut text)?;
+            Ok(Some(text))
+        })
+        .await
+        .map_err(std::io::Error::other)?
+    }
+}
+
+/// A turn being recorded.
+#[derive(Debug)]
+pub(crate) struct TurnCapture {
+    stop: Option<oneshot::Sender<()>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl TurnCapture {
+    /// Stop recording and wait for the file to be written.
+    pub(crate) async fn finish(mut self) {
+        self.stop.take();
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+    }
+}
+
+impl Drop for TurnCapture {
+    fn drop(&mut self) {
+        // Dropping the sender stops the recorder, which still writes what it
+        // has; an abandoned POST keeps its turn log.
+        self.stop.take();
+    }
+}
+
+async fn record(
+    mut receiver: broadcast::Receiver<RawOutputLine>,
+    mut stopped: oneshot::Receiver<()>,
+    dir: PathBuf,
+    turn: u64,
+    max_bytes: usize,
+) {
+    let mut log = TurnLog::new(max_bytes);
+    loop {
+        tokio::select! {
+            line = receiver.recv() => match line {
+                Ok(line) => log.push(&line),
+                Err(RecvError::Lagged(missed)) => log.note(&format!("{missed} lines dropped")),
+                Err(RecvError::Closed) => break,
+            },
+            _ = &mut stopped => {
+                // Lines already read off the pipe belong to this turn.
+                loop {
+                    match receiver.try_recv() {
+                        Ok(line) => log.push(&line),
+                        Err(TryRecvError::Lagged(missed)) => {
+                            log.note(&format!("{missed} lines dropped"))
+                        }
+                        Err(_) => break,
+                    }
+                }
+                break;
+            }
+        }
+    }
+
+    let result = tokio::task::spawn_blocking(move || {
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(turn_path(&dir, turn), log.finish()?)?;
+        if let Some(expired) = turn.checked_sub(KEPT_TURNS) {
+            let _ = std::fs::remove_file(turn_path(&dir, expired));
+        }
+        Ok::<_, std::io::Error>(())
+    })
+    .await;
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => tracing::warn!(turn, error = %err, "turn_logs: failed to write turn log"),
+        Err(err) => tracing::warn!(turn, error = %err, "turn_logs: writer task failed"),
+    }
+}
+
+fn turn_path(dir: &Path, turn: u64) -> PathBuf {
+    dir.join(format!("turn-{turn}.log.gz"))
+}
+
+/// Compressed lines of one turn, cut off at `max_bytes` of input.
+struct TurnLog {
+    encoder: GzEncoder<Vec<u8>>,
+    written: usize,
+    max_bytes: usize,
+    truncated: bool,
+}
+
+impl TurnLog {
+    fn new(max_bytes: usize) -> Self {
+        Self {
+            encoder: GzEncoder::new(Vec::new(), Compression::default()),
+            written: 0,
+            max_bytes,
+            truncated: false,
+        }
+    }
+
+    fn push(&mut self, line: &RawOutputLine) {
+        self.write(&format!("[{}] {}\n", line.stream.as_str(), line.line));
+    }
+
+    fn note(&mut self, message: &str) {
+        self.write(&format!("[sandbox-agent] {message}\n"));
+    }
+
+    fn write(&mut self, text: &str) {
+        if self.truncated {
+            return;
+        }
+        if self.written + text.len() > self.max_bytes {
+            self.truncated = true;
+            let note = format!(
+                "[sandbox-agent] output truncated at {} bytes\n",
+                self.max_bytes
+            );
+            let _ = self.encoder.write_all(note.as_bytes());
+            return;
+        }
+        self.written += text.len();
+        let _ = self.encoder.write_all(text.as_bytes());
+    }
+
+    fn finish(self) -> std::io::Result<Vec<u8>> {
+        self.encoder.finish()
+    }
+}
@@ -0,0 +1,150 @@
+//! The outcome of a session's latest turn, for automation.
+//!
+//! CI jobs want the answer rather than the event stream, so
+//! `GET /v1/acp/{server_id}/sessions/{session_id}/result` waits for the
+//! session's prompt to finish and summarizes the turn's events here: the
+//! final assistant message, how the turn ended, the usage the agent
+//! reported, and the files its tool calls wrote.
+
+use serde_json::Value;
+
+use crate::compaction::session_update;
+use crate::plans::WRITE_KINDS;
+use crate::transcript;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TurnStatus {
+    /// The agent ended the turn with a stop reason other than `cancelled`.
+    Completed,
+    Cancelled,
+    /// The agent answered `session/prompt` with an error, or the turn ended
+    /// without an answer, such as when the process exited.
+    Failed,
+}
+
+impl TurnStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Completed => "completed",
+            Self::Cancelled => "cancelled",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// A file a tool call in the turn edited, deleted or moved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedFile {
+    pub path: String,
+    /// The tool call's ACP `kind`: `edit`, `delete` or `move`.
+    pub kind: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct TurnSummary {
+    pub status: TurnStatus,
+    pub stop_reason: Option<String>,
+    /// The JSON-RPC error of a failed turn.
+    pub error: Option<Value>,
+    /// The last assistant message, merged from its streamed chunks.
+    pub message: Option<String>,
+    /// `usage` of the turn's `_adapter/turn_timing` notification.
+    pub usage: Option<Value>,
+    pub changed_files: Vec<ChangedFile>,
+}
+
+/// Summarize `session_id`'s turn from `events`, which hold one turn's worth
+/// of envelopes.
+pub(crate) fn summarize(events: &[(u64, Value)], session_id: &str) -> TurnSummary {
+    let mut summary = TurnSummary {
+        status: TurnStatus::Failed,
+        stop_reason: None,
+        error: None,
+        message: transcript::collect(events, session_id)
+            .into_iter()
+            .rev()
+            .find(|message| message.role == "assistant")
+            .map(|message| message.text),
+        usage: None,
+        changed_files: Vec::new(),
+    };
+    for (_, payload) in events {
+        let params = payload.get("params");
+        let for_session = params
+            .and_then(|params| params.get("sessionId"))
+            .and_then(Value::as_str)
+            == Some(session_id);
+        match payload.get("method").and_then(Value::as_str) {
+            Some("_adapter/turn_completed") if for_session => {
+                summary.stop_reason = params
+                    .and_then(|params| params.get("stopReason"))
+                    .and_then(Value::as_str)
+                    .map(ToOwned::to_owned);
+                summary.status = if summary.stop_reason.as_deref() == Some("cancelled") {
+                    TurnStatus::Cancelled
+                } else {
+                    TurnStatus::Completed
+                };
+            }
+            Some("_adapter/turn_failed") if for_session => {
+                summary.status = TurnStatus::Failed;
+                summary.error = params.and_then(|params| params.get("error")).cloned();
+            }
+            Some("_adapter/turn_timing") if for_session => {
+                summary.usage = params
+                    .and_then(|params| params.get("usage"))
+                    .filter(|usage| !usage.is_null())
+                    .cloned();
+            }
+            _ => {}
+        }
+
+        let Some(update) = session_update(payload, session_id) else {
+            continue;
+        };
+        if !matches!(
+            update.get("sessionUpdate").and_then(Value::as_str),
+            Some("tool_call" | "tool_call_update")
+        ) {
+            continue;
+        }
+        let Some(kind) = update
+            .get("kind")
+            .and_then(Value::as_str)
+            .filter(|kind| WRITE_KINDS.contains(kind))
+        else {
+            continue;
+        };
+        for path in written_paths(update) {
+            if !summary
+                .changed_files
+                .iter()
+                .any(|changed| changed.path == path)
+            {
+                summary.changed_files.push(ChangedFile {
+                    path: path.to_string(),
+                    kind: kind.to_string(),
+                });
+            }
+        }
+    }
+    summary
+}
+
+/// Paths from a tool call's `locations` and its `diff` content blocks.
+fn written_paths(update: &Value) -> Vec<&str> {
+    let locations = update
+        .get("locations")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|location| location.get("path").and_then(Value::as_str));
+    let diffs = update
+        .get("content")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|content| content.get("type").and_then(Value::as_str) == Some("diff"))
+        .filter_map(|content| content.get("path").and_then(Value::as_str));
+    locations.chain(diffs).collect()
+}
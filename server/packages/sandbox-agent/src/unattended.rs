@@ -0,0 +1,146 @@
+//! Automatic answers for agent requests no client responds to.
+//!
+//! Unattended runs, such as CI jobs, have nobody to answer a
+//! `session/request_permission`, so the agent would wait forever. With a
+//! timeout set, the server answers each request still pending when it runs
+//! out. Permission requests get the configured default reply; any other
+//! request gets a JSON-RPC error. Server-wide defaults come from
+//! `pending_request_timeout_ms` under `[limits]` (or
+//! `SANDBOX_AGENT_PENDING_REQUEST_TIMEOUT_MS`) and
+//! `SANDBOX_AGENT_DEFAULT_PERMISSION_REPLY`; a `session/new` envelope can
+//! override them for its session with `requestTimeoutMs` and
+//! `defaultPermissionReply` under `_meta["sandboxagent.dev"].unattended`.
+
+use std::time::Duration;
+
+use sandbox_agent_error::SandboxError;
+use serde_json::{json, Value};
+
+use crate::config::LimitSettings;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum PermissionReply {
+    /// Answer with the `cancelled` outcome.
+    #[default]
+    Cancel,
+    /// Select the request's first `reject_once` or `reject_always` option.
+    Reject,
+    /// Select the request's first `allow_once` or `allow_always` option.
+    Allow,
+}
+
+impl PermissionReply {
+    fn parse(raw: &str) -> Option<Self> {
+        match raw.trim() {
+            "cancel" => Some(Self::Cancel),
+            "reject" => Some(Self::Reject),
+            "allow" => Some(Self::Allow),
+            _ => None,
+        }
+    }
+
+    fn option_kinds(self) -> &'static [&'static str] {
+        match self {
+            Self::Cancel => &[],
+            Self::Reject => &["reject_once", "reject_always"],
+            Self::Allow => &["allow_once", "allow_always"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct UnattendedPolicy {
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) permission_reply: PermissionReply,
+}
+
+impl UnattendedPolicy {
+    pub(crate) fn from_config(limits: &LimitSettings) -> Self {
+        let permission_reply = match std::env::var("SANDBOX_AGENT_DEFAULT_PERMISSION_REPLY") {
+            Ok(raw) => PermissionReply::parse(&raw).unwrap_or_else(|| {
+                tracing::warn!(
+                    value = %raw,
+                    "ignoring SANDBOX_AGENT_DEFAULT_PERMISSION_REPLY; expected cancel, reject or allow"
+                );
+                PermissionReply::default()
+            }),
+            Err(_) => PermissionReply::default(),
+        };
+        Self {
+            timeout: limits
+                .pending_request_timeout_ms
+                .filter(|value| *value > 0)
+                .map(Duration::from_millis),
+            permission_reply,
+        }
+    }
+
+    /// Apply `unattended` from a `session/new` envelope on top of `self`.
+    /// A `requestTimeoutMs` of `0` turns the timeout off.
+    pub(crate) fn with_overrides(self, payload: &Value) -> Result<Self, SandboxError> {
+        let Some(overrides) = payload.pointer("/params/_meta/sandboxagent.dev/unattended") else {
+            return Ok(self);
+        };
+        let mut policy = self;
+        if let Some(value) = overrides.get("requestTimeoutMs") {
+            let millis = value.as_u64().ok_or_else(|| {
+                invalid("requestTimeoutMs must be a non-negative integer".to_string())
+            })?;
+            policy.timeout = (millis > 0).then(|| Duration::from_millis(millis));
+        }
+        if let Some(value) = overrides.get("defaultPermissionReply") {
+            policy.permission_reply =
+                value
+                    .as_str()
+                    .and_then(PermissionReply::parse)
+                    .ok_or_else(|| {
+                        invalid(
+                            "defaultPermissionReply must be \"cancel\", \"reject\" or \"allow\""
+                                .to_string(),
+                        )
+                    })?;
+        }
+        Ok(policy)
+    }
+
+    /// The JSON-RPC response that answers `request` on the client's behalf.
+    pub(crate) fn response(&self, request: &Value) -> Value {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        if request.get("method").and_then(Value::as_str) != Some("session/request_permission") {
+            return json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": {
+                    "code": -32000,
+                    "message": "no client responded before the request expired",
+                }
+            });
+        }
+        let options = request
+            .pointer("/params/options")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let selected = self
+            .permission_reply
+            .option_kinds()
+            .iter()
+            .find_map(|kind| {
+                options
+                    .iter()
+                    .find(|option| option.get("kind").and_then(Value::as_str) == Some(*kind))
+                    .and_then(|option| option.get("optionId"))
+            });
+        let outcome = match selected {
+            Some(option_id) => json!({ "outcome": "selected", "optionId": option_id }),
+            None => json!({ "outcome": "cancelled" }),
+        };
+        json!({ "jsonrpc": "2.0", "id": id, "result": { "outcome": outcome } })
+    }
+}
+
+fn invalid(detail: String) -> SandboxError {
+    SandboxError::InvalidRequest {
+        message: format!("_meta[\"sandboxagent.dev\"].unattended.{detail}"),
+    }
+}
@@ -0,0 +1,181 @@
+//! Standby agent processes, started ahead of the servers that use them.
+//!
+//! `SANDBOX_AGENT_WARM_POOL` keeps a number of processes spawned per agent,
+//! for example `claude=1,codex=2`. A new server adopts a standby instead of
+//! spawning, which skips the agent's startup (node bootstrapping for the
+//! Claude and Amp adapters). Standbys are started with the agent's default
+//! environment, so a server whose launch differs from it in any way, such as
+//! `_meta` env, secrets, an isolated home or permission arguments, starts
+//! cold. The pool is refilled in the background after each adoption.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+
+use acp_http_adapter::process::AdapterRuntime;
+use acp_http_adapter::registry::LaunchSpec;
+use sandbox_agent_agent_management::agents::AgentId;
+
+const WARM_POOL_VAR: &str = "SANDBOX_AGENT_WARM_POOL";
+
+/// A spawned process waiting for a server.
+#[derive(Debug)]
+pub(crate) struct Standby {
+    pub(crate) runtime: AdapterRuntime,
+    pub(crate) spec: LaunchSpec,
+}
+
+/// Server starts of one agent, split by whether they adopted a standby.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StartStats {
+    pub cold_starts: u64,
+    pub cold_start_ms: u64,
+    pub warm_starts: u64,
+    pub warm_start_ms: u64,
+}
+
+impl StartStats {
+    pub fn cold_start_ms_avg(&self) -> Option<u64> {
+        (self.cold_starts > 0).then(|| self.cold_start_ms / self.cold_starts)
+    }
+
+    pub fn warm_start_ms_avg(&self) -> Option<u64> {
+        (self.warm_starts > 0).then(|| self.warm_start_ms / self.warm_starts)
+    }
+}
+
+/// Pool state of one agent, for `GET /v1/agents`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WarmPoolStatus {
+    /// Standbys the pool keeps.
+    pub size: usize,
+    /// Standbys spawned and waiting.
+    pub ready: usize,
+    pub stats: StartStats,
+}
+
+#[derive(Debug, Default)]
+struct AgentPool {
+    standby: Vec<Standby>,
+    /// Standbys being spawned.
+    spawning: usize,
+    stats: StartStats,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct WarmPool {
+    sizes: HashMap<AgentId, usize>,
+    agents: Mutex<HashMap<AgentId, AgentPool>>,
+}
+
+impl WarmPool {
+    pub(crate) fn from_env() -> Self {
+        let Ok(raw) = std::env::var(WARM_POOL_VAR) else {
+            return Self::default();
+        };
+        let mut sizes = HashMap::new();
+        for entry in raw
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let parsed = entry.split_once('=').and_then(|(agent, size)| {
+                Some((
+                    AgentId::parse(agent.trim())?,
+                    size.trim().parse::<usize>().ok()?,
+                ))
+            });
+            match parsed {
+                Some((agent, size)) if size > 0 => {
+                    sizes.insert(agent, size);
+                }
+                Some(_) => {}
+                None => {
+                    tracing::warn!(entry = %entry, "ignoring {WARM_POOL_VAR} entry; expected agent=count")
+                }
+            }
+        }
+        Self {
+            sizes,
+            agents: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Agents with a pool.
+    pub(crate) fn agents(&self) -> Vec<AgentId> {
+        self.sizes.keys().copied().collect()
+    }
+
+    /// Claim a slot to spawn a standby in, if the pool of `agent` is short.
+    pub(crate) fn reserve(&self, agent: AgentId) -> bool {
+        let size = self.sizes.get(&agent).copied().unwrap_or(0);
+        let mut agents = self.agents.lock().unwrap_or_else(PoisonError::into_inner);
+        let pool = agents.entry(agent).or_default();
+        pool.standby
+            .retain(|standby| standby.runtime.exit_status().is_none());
+        if pool.standby.len() + pool.spawning >= size {
+            return false;
+        }
+        pool.spawning += 1;
+        true
+    }
+
+    /// Fill a slot from [`Self::reserve`]. `None` when the spawn failed.
+    pub(crate) fn fill(&self, agent: AgentId, standby: Option<Standby>) {
+        let mut agents = self.agents.lock().unwrap_or_else(PoisonError::into_inner);
+        let pool = agents.entry(agent).or_default();
+        pool.spawning = pool.spawning.saturating_sub(1);
+        pool.standby.extend(standby);
+    }
+
+    /// Take a live standby of `agent` launched exactly as `spec`.
+    pub(crate) fn take(&self, agent: AgentId, spec: &LaunchSpec) -> Option<Standby> {
+        let mut agents = self.agents.lock().unwrap_or_else(PoisonError::into_inner);
+        let pool = agents.get_mut(&agent)?;
+        pool.standby
+            .retain(|standby| standby.runtime.exit_status().is_none());
+        let index = pool
+            .standby
+            .iter()
+            .position(|standby| standby.spec == *spec)?;
+        Some(pool.standby.remove(index))
+    }
+
+    /// Remove every standby of `agent`, so they can be shut down.
+    pub(crate) fn drain(&self, agent: AgentId) -> Vec<Standby> {
+        self.agents
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_mut(&agent)
+            .map(|pool| std::mem::take(&mut pool.standby))
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn record_start(&self, agent: AgentId, warm: bool, elapsed_ms: u64) {
+        let mut agents = self.agents.lock().unwrap_or_else(PoisonError::into_inner);
+        let stats = &mut agents.entry(agent).or_default().stats;
+        if warm {
+            stats.warm_starts += 1;
+            stats.warm_start_ms += elapsed_ms;
+        } else {
+            stats.cold_starts += 1;
+            stats.cold_start_ms += elapsed_ms;
+        }
+    }
+
+    /// Pool state of `agent`, or `None` when it has no pool.
+    pub(crate) fn status(&self, agent: AgentId) -> Option<WarmPoolStatus> {
+        let size = self.sizes.get(&agent).copied()?;
+        let agents = self.agents.lock().unwrap_or_else(PoisonError::into_inner);
+        let pool = agents.get(&agent);
+        Some(WarmPoolStatus {
+            size,
+            ready: pool.map_or(0, |pool| {
+                pool.standby
+                    .iter()
+                    .filter(|standby| standby.runtime.exit_status().is_none())
+                    .count()
+            }),
+            stats: pool.map(|pool| pool.stats).unwrap_or_default(),
+        })
+    }
+}
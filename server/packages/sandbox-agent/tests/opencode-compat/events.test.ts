@@ -61,6 +61,17 @@ describe("OpenCode-compatible Event Streaming", () => {
       expect((response as any).stream).toBeDefined();
     });
 
+    it("should serve the same stream at /event/subscribe", async () => {
+      const controller = new AbortController();
+      const response = await fetch(`${handle.baseUrl}/opencode/event/subscribe`, {
+        headers: { Authorization: `Bearer ${handle.token}` },
+        signal: controller.signal,
+      });
+      expect(response.status).toBe(200);
+      expect(response.headers.get("content-type")).toContain("text/event-stream");
+      controller.abort();
+    });
+
     it("should receive session.created event when session is created", async () => {
       const events: any[] = [];
 
@@ -9,7 +9,7 @@ use axum::http::{header, HeaderMap, Method, Request, StatusCode};
 use axum::Router;
 use futures::StreamExt;
 use http_body_util::BodyExt;
-use sandbox_agent::router::{build_router, AppState, AuthConfig};
+use sandbox_agent::router::{build_router, build_router_with_state, AppState, AuthConfig};
 use sandbox_agent_agent_management::agents::AgentManager;
 use serde_json::{json, Value};
 use serial_test::serial;
@@ -285,9 +285,130 @@ fn parse_sse_event_id(chunk: &str) -> u64 {
         .expect("sse event id")
 }
 
+fn write_stub_native(path: &Path, agent: &str) {
+    let script = format!("#!/usr/bin/env sh\necho \"{agent} 0.0.1\"\nexit 0\n");
+    write_executable(path, &script);
+}
+
+fn write_stub_agent_process(path: &Path, agent: &str) {
+    let script = format!(
+        r#"#!/usr/bin/env sh
+if [ "${{1:-}}" = "--help" ] || [ "${{1:-}}" = "--version" ] || [ "${{1:-}}" = "version" ] || [ "${{1:-}}" = "-V" ]; then
+  echo "{agent}-agent-process 0.0.1"
+  exit 0
+fi
+
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}}]*\).*/\1/p')
+
+  if [ -n "$method" ]; then
+    printf '{{"jsonrpc":"2.0","method":"server/echo","params":{{"method":"%s"}}}}\n' "$method"
+  fi
+
+  if [ -n "$method" ] && [ -n "$id" ]; then
+    printf '{{"jsonrpc":"2.0","id":%s,"result":{{"ok":true,"echoedMethod":"%s"}}}}\n' "$id" "$method"
+  elif [ -z "$method" ] && [ -n "$id" ]; then
+    printf '{{"jsonrpc":"2.0","method":"server/client_response","params":{{"id":%s}}}}\n' "$id"
+  fi
+done
+"#
+    );
+
+    write_executable(path, &script);
+}
+
+fn setup_stub_artifacts(install_dir: &Path, agent: &str) {
+    let native = install_dir.join(agent);
+    write_stub_native(&native, agent);
+
+    let agent_processes = install_dir.join("agent_processes");
+    fs::create_dir_all(&agent_processes).expect("create agent processes dir");
+    let launcher = if cfg!(windows) {
+        agent_processes.join(format!("{agent}-acp.cmd"))
+    } else {
+        agent_processes.join(format!("{agent}-acp"))
+    };
+    write_stub_agent_process(&launcher, agent);
+}
+
+/// Replaces the codex stub with one that asks the client for permission
+/// during every prompt and reports each client response it receives.
+fn write_permission_stub(install_dir: &Path) {
+    write_executable(
+        &install_dir.join("agent_processes/codex-acp"),
+        r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/prompt)
+      printf '{"jsonrpc":"2.0","id":"perm-1","method":"session/request_permission","params":{"sessionId":"s-1","toolCall":{"toolCallId":"call-1"},"options":[{"optionId":"allow","name":"Allow","kind":"allow_once"},{"optionId":"deny","name":"Deny","kind":"reject_once"}]}}\n'
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"end_turn"}}\n' "$id" ;;
+    session/new) printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-1"}}\n' "$id" ;;
+    "") printf '{"jsonrpc":"2.0","method":"server/client_response","params":{"line":%s}}\n' "$line" ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+    );
+}
+
+async fn list_pending_requests(app: &Router, path: &str) -> Value {
+    let (status, _, body) = send_request(app, Method::GET, path, None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    parse_json(&body)["requests"].clone()
+}
+
+/// Next complete SSE event carrying data, buffering partial chunks.
+async fn next_sse_event(stream: &mut axum::body::BodyDataStream, pending: &mut String) -> String {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Some(end) = pending.find("\n\n") {
+                let event = pending[..end].to_string();
+                pending.drain(..end + 2);
+                if event.contains("data:") {
+                    return event;
+                }
+                continue;
+            }
+            let chunk = stream
+                .next()
+                .await
+                .expect("stream ended")
+                .expect("stream chunk");
+            pending.push_str(&String::from_utf8_lossy(&chunk));
+        }
+    })
+    .await
+    .expect("timed out reading stream")
+}
+
 #[path = "v1_api/acp_transport.rs"]
 mod acp_transport;
+#[path = "v1_api/agent_launch.rs"]
+mod agent_launch;
+#[path = "v1_api/agent_output.rs"]
+mod agent_output;
+#[path = "v1_api/cluster.rs"]
+mod cluster;
+#[path = "v1_api/compat.rs"]
+mod compat;
 #[path = "v1_api/config_endpoints.rs"]
 mod config_endpoints;
 #[path = "v1_api/control_plane.rs"]
 mod control_plane;
+#[path = "v1_api/namespaces.rs"]
+mod namespaces;
+#[path = "v1_api/opencode_sidecar.rs"]
+mod opencode_sidecar;
+#[path = "v1_api/pending_requests.rs"]
+mod pending_requests;
+#[path = "v1_api/prompts.rs"]
+mod prompts;
+#[path = "v1_api/sessions.rs"]
+mod sessions;
+#[path = "v1_api/streams.rs"]
+mod streams;
+#[path = "v1_api/tasks.rs"]
+mod tasks;
@@ -1,52 +1,5 @@
 use super::*;
 
-fn write_stub_native(path: &Path, agent: &str) {
-    let script = format!("#!/usr/bin/env sh\necho \"{agent} 0.0.1\"\nexit 0\n");
-    write_executable(path, &script);
-}
-
-fn write_stub_agent_process(path: &Path, agent: &str) {
-    let script = format!(
-        r#"#!/usr/bin/env sh
-if [ "${{1:-}}" = "--help" ] || [ "${{1:-}}" = "--version" ] || [ "${{1:-}}" = "version" ] || [ "${{1:-}}" = "-V" ]; then
-  echo "{agent}-agent-process 0.0.1"
-  exit 0
-fi
-
-while IFS= read -r line; do
-  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
-  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}}]*\).*/\1/p')
-
-  if [ -n "$method" ]; then
-    printf '{{"jsonrpc":"2.0","method":"server/echo","params":{{"method":"%s"}}}}\n' "$method"
-  fi
-
-  if [ -n "$method" ] && [ -n "$id" ]; then
-    printf '{{"jsonrpc":"2.0","id":%s,"result":{{"ok":true,"echoedMethod":"%s"}}}}\n' "$id" "$method"
-  elif [ -z "$method" ] && [ -n "$id" ]; then
-    printf '{{"jsonrpc":"2.0","method":"server/client_response","params":{{"id":%s}}}}\n' "$id"
-  fi
-done
-"#
-    );
-
-    write_executable(path, &script);
-}
-
-fn setup_stub_artifacts(install_dir: &Path, agent: &str) {
-    let native = install_dir.join(agent);
-    write_stub_native(&native, agent);
-
-    let agent_processes = install_dir.join("agent_processes");
-    fs::create_dir_all(&agent_processes).expect("create agent processes dir");
-    let launcher = if cfg!(windows) {
-        agent_processes.join(format!("{agent}-acp.cmd"))
-    } else {
-        agent_processes.join(format!("{agent}-acp"))
-    };
-    write_stub_agent_process(&launcher, agent);
-}
-
 #[tokio::test]
 async fn acp_bootstrap_requires_agent_query() {
     let test_app = TestApp::new(AuthConfig::disabled());
@@ -115,6 +68,263 @@ async fn acp_round_trip_and_replay() {
     assert!(second_event_id > first_event_id);
 }
 
+#[cfg(unix)]
+#[tokio::test]
+async fn acp_stream_names_events_and_resumes_from_offset() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+    });
+    bootstrap_server(&test_app.app, "server-named", "codex").await;
+
+    let read_first_chunk = |uri: String| {
+        let app = test_app.app.clone();
+        async move {
+            let request = Request::builder()
+                .method(Method::GET)
+                .uri(uri)
+                .body(Body::empty())
+                .expect("build request");
+            let response = app.oneshot(request).await.expect("sse response");
+            assert_eq!(response.status(), StatusCode::OK);
+            let mut stream = response.into_body().into_data_stream();
+            tokio::time::timeout(Duration::from_secs(5), async move {
+                while let Some(chunk) = stream.next().await {
+                    let text = String::from_utf8_lossy(&chunk.expect("stream chunk")).to_string();
+                    if text.contains("data:") {
+                        return text;
+                    }
+                }
+                panic!("SSE stream ended before data chunk")
+            })
+            .await
+            .expect("timed out reading sse")
+        }
+    };
+
+    let first = read_first_chunk("/v1/acp/server-named?namedEvents=true".to_string()).await;
+    assert!(first.contains("event: server/echo"));
+    let first_id = parse_sse_event_id(&first);
+
+    let second = read_first_chunk(format!(
+        "/v1/acp/server-named?namedEvents=true&offset={first_id}"
+    ))
+    .await;
+    assert!(second.contains("event: response"));
+    assert_eq!(parse_sse_event_id(&second), first_id + 1);
+
+    let plain = read_first_chunk(format!("/v1/acp/server-named?offset={first_id}")).await;
+    assert!(plain.contains("event: message"));
+    assert_eq!(parse_sse_event_id(&plain), first_id + 1);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn acp_events_long_poll_waits_for_new_envelopes() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+    });
+    bootstrap_server(&test_app.app, "server-poll", "codex").await;
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-poll/events",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let buffered = parse_json(&body);
+    let events = buffered["events"].as_array().expect("events array");
+    assert!(!events.is_empty());
+    let next_offset = buffered["nextOffset"].as_u64().expect("next offset");
+    assert_eq!(events.last().unwrap()["sequence"], next_offset);
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        &format!("/v1/acp/server-poll/events?offset={next_offset}&waitMs=50"),
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let idle = parse_json(&body);
+    assert_eq!(idle["events"], json!([]));
+    assert_eq!(idle["nextOffset"], next_offset);
+
+    let app = test_app.app.clone();
+    let poll = tokio::spawn(async move {
+        send_request(
+            &app,
+            Method::GET,
+            &format!("/v1/acp/server-poll/events?offset={next_offset}&waitMs=5000"),
+            None,
+            &[],
+        )
+        .await
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let prompt = json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "session/prompt",
+        "params": {
+            "sessionId": "s-1",
+            "prompt": [{"type": "text", "text": "hello"}]
+        }
+    });
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-poll",
+        Some(prompt),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, body) = poll.await.expect("poll task");
+    assert_eq!(status, StatusCode::OK);
+    let woken = parse_json(&body);
+    assert_eq!(woken["events"][0]["sequence"], next_offset + 1);
+    assert!(woken["nextOffset"].as_u64().expect("next offset") > next_offset);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn acp_events_are_compressed_for_clients_that_accept_it() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+    });
+    bootstrap_server(&test_app.app, "server-gzip", "codex").await;
+
+    let (status, headers, plain) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-gzip/events",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers.get(header::CONTENT_ENCODING).is_none());
+
+    let (status, headers, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-gzip/events",
+        None,
+        &[("accept-encoding", "gzip")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::CONTENT_ENCODING], "gzip");
+    let mut decoded = Vec::new();
+    flate2::read::GzDecoder::new(body.as_slice())
+        .read_to_end(&mut decoded)
+        .expect("gunzip events");
+    assert_eq!(parse_json(&decoded), parse_json(&plain));
+
+    // Other routes are only compressed with SANDBOX_AGENT_COMPRESSION=all.
+    let (status, headers, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/agents",
+        None,
+        &[("accept-encoding", "gzip")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers.get(header::CONTENT_ENCODING).is_none());
+
+    let _all = EnvVarGuard::set("SANDBOX_AGENT_COMPRESSION", "all");
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+    });
+    let (status, headers, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/agents",
+        None,
+        &[("accept-encoding", "br")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::CONTENT_ENCODING], "br");
+
+    let _off = EnvVarGuard::set("SANDBOX_AGENT_COMPRESSION", "off");
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+    });
+    bootstrap_server(&test_app.app, "server-plain", "codex").await;
+    let (status, headers, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-plain/events",
+        None,
+        &[("accept-encoding", "gzip")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers.get(header::CONTENT_ENCODING).is_none());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn unchanged_event_pages_and_agent_listings_return_not_modified() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+    });
+    bootstrap_server(&test_app.app, "server-etag", "codex").await;
+
+    let get = |path: &'static str, etag: Option<String>| {
+        let app = test_app.app.clone();
+        async move {
+            let headers = etag
+                .as_deref()
+                .map(|etag| vec![("if-none-match", etag)])
+                .unwrap_or_default();
+            send_request(&app, Method::GET, path, None, &headers).await
+        }
+    };
+
+    let (status, headers, _) = get("/v1/acp/server-etag/events", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::CACHE_CONTROL], "no-cache");
+    let etag = headers[header::ETAG].to_str().expect("etag").to_string();
+
+    let (status, headers, body) = get("/v1/acp/server-etag/events", Some(etag.clone())).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+    assert_eq!(headers[header::ETAG], etag.as_str());
+    assert!(body.is_empty());
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-etag",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "session/prompt",
+            "params": { "sessionId": "s-1", "prompt": [{ "type": "text", "text": "hello" }] }
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, headers, _) = get("/v1/acp/server-etag/events", Some(etag.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(headers[header::ETAG], etag.as_str());
+
+    let (status, headers, _) = get("/v1/agents/codex/models", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let etag = headers[header::ETAG].to_str().expect("etag").to_string();
+    let (status, _, _) = get("/v1/agents/codex/models", Some(etag)).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+}
+
 #[cfg(unix)]
 #[tokio::test]
 async fn acp_agent_mismatch_returns_conflict() {
@@ -222,6 +432,93 @@ async fn acp_list_servers_returns_active_instances() {
         .any(|server| server["serverId"] == "server-2"));
 }
 
+#[cfg(unix)]
+#[tokio::test]
+async fn acp_list_servers_reports_turn_timing() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+    });
+
+    bootstrap_server(&test_app.app, "server-timing", "codex").await;
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-timing",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "session/prompt",
+            "params": {
+                "sessionId": "s-1",
+                "prompt": [{"type": "text", "text": "hello"}]
+            }
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, body) = send_request(&test_app.app, Method::GET, "/v1/acp", None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    let parsed = parse_json(&body);
+    let server = &parsed["servers"][0];
+    assert!(server["startupTiming"]["spawnMs"].is_u64());
+    assert_eq!(server["lastTurnTiming"]["sessionId"], "s-1");
+    assert!(server["lastTurnTiming"]["durationMs"].is_u64());
+    assert!(server["lastTurnTiming"]["timeToFirstEventMs"].is_u64());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn acp_list_servers_reports_turn_usage() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/prompt) printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"end_turn","usage":{"inputTokens":12,"outputTokens":34,"totalTokens":46,"cost":{"amount":0.25,"currency":"USD"}}}}\n' "$id" ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+
+    bootstrap_server(&test_app.app, "server-usage", "codex").await;
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-usage",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "session/prompt",
+            "params": {
+                "sessionId": "s-1",
+                "prompt": [{"type": "text", "text": "hello"}]
+            }
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, body) = send_request(&test_app.app, Method::GET, "/v1/acp", None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    let parsed = parse_json(&body);
+    let usage = &parsed["servers"][0]["lastTurnTiming"]["usage"];
+    assert_eq!(usage["inputTokens"], 12);
+    assert_eq!(usage["outputTokens"], 34);
+    assert_eq!(usage["totalTokens"], 46);
+    assert_eq!(usage["cost"], 0.25);
+    assert_eq!(usage["costCurrency"], "USD");
+    assert!(usage.get("thoughtTokens").is_none());
+}
+
 #[cfg(unix)]
 #[tokio::test]
 async fn sandboxagent_methods_are_not_handled_specially() {
@@ -305,3 +602,289 @@ async fn invalid_last_event_id_returns_bad_request() {
         "invalid request: Last-Event-ID must be a positive integer"
     );
 }
+
+#[cfg(unix)]
+#[tokio::test]
+async fn signed_share_url_grants_sse_for_single_server() {
+    let test_app = TestApp::with_setup(
+        AuthConfig::with_token("secret-token".to_string()),
+        |install_dir| {
+            setup_stub_artifacts(install_dir, "codex");
+        },
+    );
+    let auth = [("authorization", "Bearer secret-token")];
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-share?agent=codex",
+        Some(initialize_payload()),
+        &auth,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-share/share",
+        Some(json!({ "ttlMs": 60000 })),
+        &auth,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let url = parse_json(&body)["url"]
+        .as_str()
+        .expect("share url")
+        .to_string();
+    assert!(url.starts_with("/v1/acp/server-share?expires="));
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri(&url)
+        .body(Body::empty())
+        .expect("build request");
+    let response = test_app
+        .app
+        .clone()
+        .oneshot(request)
+        .await
+        .expect("sse response");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let other_server = url.replace("/v1/acp/server-share?", "/v1/acp/server-other?");
+    let (status, _, _) = send_request(&test_app.app, Method::GET, &other_server, None, &[]).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _, _) = send_request(&test_app.app, Method::DELETE, &url, None, &[]).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn idempotency_key_runs_a_prompt_once() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+runs=0
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/prompt)
+      runs=$((runs + 1))
+      sleep 1
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"end_turn","_meta":{"run":%s}}}\n' "$id" "$runs" ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+
+    bootstrap_server(&test_app.app, "server-idem", "codex").await;
+    let prompt = |id: u64, text: &str| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "session/prompt",
+            "params": {
+                "sessionId": "s-1",
+                "prompt": [{ "type": "text", "text": text }]
+            }
+        })
+    };
+
+    // A retry sent while the first request is still running waits for it.
+    let (first, retry) = tokio::join!(
+        send_request(
+            &test_app.app,
+            Method::POST,
+            "/v1/acp/server-idem",
+            Some(prompt(7, "hello")),
+            &[("idempotency-key", "turn-1")],
+        ),
+        async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            send_request(
+                &test_app.app,
+                Method::POST,
+                "/v1/acp/server-idem",
+                Some(prompt(7, "hello")),
+                &[("idempotency-key", "turn-1")],
+            )
+            .await
+        }
+    );
+    assert_eq!(first.0, StatusCode::OK);
+    assert_eq!(parse_json(&first.2)["result"]["_meta"]["run"], 1);
+    assert!(first.1.get("idempotent-replayed").is_none());
+    assert_eq!(retry.0, StatusCode::OK);
+    assert_eq!(parse_json(&retry.2)["result"]["_meta"]["run"], 1);
+    assert_eq!(retry.1["idempotent-replayed"], "true");
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-idem",
+        Some(prompt(8, "something else")),
+        &[("idempotency-key", "turn-1")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(parse_json(&body)["code"], "conflict");
+
+    let (status, headers, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-idem",
+        Some(prompt(9, "hello")),
+        &[("idempotency-key", "turn-2")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body)["result"]["_meta"]["run"], 2);
+    assert!(headers.get("idempotent-replayed").is_none());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn event_polls_and_sse_filter_by_event_kind() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/new) printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-1"}}\n' "$id" ;;
+    session/prompt)
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"tool_call","toolCallId":"t-1","title":"Read","status":"pending"}}}\n'
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"tool_call_update","toolCallId":"t-1","status":"completed"}}}\n'
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"done"}}}}\n'
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"end_turn"}}\n' "$id" ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+
+    bootstrap_server(&test_app.app, "server-filter", "codex").await;
+    for (id, method, params) in [
+        (2, "session/new", json!({"cwd": "/tmp", "mcpServers": []})),
+        (
+            3,
+            "session/prompt",
+            json!({"sessionId": "s-1", "prompt": [{"type": "text", "text": "hi"}]}),
+        ),
+    ] {
+        let (status, _, _) = send_request(
+            &test_app.app,
+            Method::POST,
+            "/v1/acp/server-filter",
+            Some(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})),
+            &[],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let kinds = |body: &[u8]| {
+        parse_json(body)["events"]
+            .as_array()
+            .expect("events")
+            .iter()
+            .map(|event| {
+                let payload = &event["payload"];
+                payload
+                    .pointer("/params/update/sessionUpdate")
+                    .or_else(|| payload.get("method"))
+                    .and_then(Value::as_str)
+                    .unwrap_or("response")
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-filter/events?types=agent_message_chunk",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(kinds(&body), vec!["agent_message_chunk"]);
+
+    let (_, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-filter/events?types=session/update&exclude=tool_call_update",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(kinds(&body), vec!["tool_call", "agent_message_chunk"]);
+
+    let (_, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-filter/events?exclude=session/update",
+        None,
+        &[],
+    )
+    .await;
+    let kept = kinds(&body);
+    assert_eq!(kept.iter().filter(|kind| *kind == "response").count(), 3);
+    assert!(!kept
+        .iter()
+        .any(|kind| kind.starts_with("tool_call") || kind == "agent_message_chunk"));
+
+    // The limit counts only envelopes that pass the filter.
+    let (_, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-filter/events?types=tool_call_update,agent_message_chunk&limit=1",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(kinds(&body), vec!["tool_call_update"]);
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri("/v1/acp/server-filter?types=agent_message_chunk")
+        .body(Body::empty())
+        .expect("build request");
+    let response = test_app
+        .app
+        .clone()
+        .oneshot(request)
+        .await
+        .expect("sse response");
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut stream = response.into_body().into_data_stream();
+    let chunk = tokio::time::timeout(Duration::from_secs(5), async move {
+        loop {
+            let bytes = stream.next().await.expect("sse open").expect("chunk");
+            let text = String::from_utf8_lossy(&bytes).to_string();
+            if text.contains("data:") {
+                return text;
+            }
+        }
+    })
+    .await
+    .expect("timed out reading sse");
+    let payload = parse_sse_data(&chunk);
+    assert_eq!(
+        payload["params"]["update"]["sessionUpdate"],
+        "agent_message_chunk"
+    );
+}
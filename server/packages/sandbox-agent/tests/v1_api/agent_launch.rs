@@ -0,0 +1,910 @@
+use super::*;
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn warm_pool_standbys_are_adopted_by_new_servers() {
+    let _pool = EnvVarGuard::set("SANDBOX_AGENT_WARM_POOL", "codex=1");
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+    });
+
+    bootstrap_server(&test_app.app, "server-cold", "codex").await;
+
+    let mut ready = false;
+    for _ in 0..50 {
+        let (status, _, body) =
+            send_request(&test_app.app, Method::GET, "/v1/agents/codex", None, &[]).await;
+        assert_eq!(status, StatusCode::OK);
+        let agent = parse_json(&body);
+        assert_eq!(agent["warmPool"]["size"], 1);
+        if agent["warmPool"]["ready"] == 1 {
+            ready = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(ready, "warm pool never filled");
+
+    bootstrap_server(&test_app.app, "server-warm", "codex").await;
+
+    let (status, _, body) = send_request(&test_app.app, Method::GET, "/v1/acp", None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    let parsed = parse_json(&body);
+    let warm_by_server = parsed["servers"]
+        .as_array()
+        .expect("servers")
+        .iter()
+        .map(|server| {
+            (
+                server["serverId"].as_str().expect("serverId").to_string(),
+                server["startupTiming"]["warm"].as_bool().expect("warm"),
+            )
+        })
+        .collect::<std::collections::HashMap<_, _>>();
+    assert!(!warm_by_server["server-cold"]);
+    assert!(warm_by_server["server-warm"]);
+
+    let (status, _, body) =
+        send_request(&test_app.app, Method::GET, "/v1/agents/codex", None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    let pool = &parse_json(&body)["warmPool"];
+    assert_eq!(pool["coldStarts"], 1);
+    assert_eq!(pool["warmStarts"], 1);
+    assert!(pool["warmStartMsAvg"].is_u64());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn bootstrap_env_is_templated_into_agent_process() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  printf '{"jsonrpc":"2.0","id":%s,"result":{"greeting":"%s"}}\n' "$id" "$GREETING"
+done
+"#,
+        );
+    });
+
+    let with_env = |env: Value| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "1.0",
+                "clientCapabilities": {},
+                "_meta": { "sandboxagent.dev": { "env": env } }
+            }
+        })
+    };
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-env-denied?agent=codex",
+        Some(with_env(
+            json!({ "GREETING": "${SANDBOX_AGENT_TEST_SECRET}" }),
+        )),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-env?agent=codex",
+        Some(with_env(json!({ "GREETING": "hi ${HOME}" }))),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let home = std::env::var("HOME").unwrap_or_default();
+    assert_eq!(
+        parse_json(&body)["result"]["greeting"],
+        format!("hi {home}")
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn permission_mode_maps_to_agent_launch_args() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+args=$(printf '%s' "$*" | sed 's/"/\\"/g')
+while IFS= read -r line; do
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  printf '{"jsonrpc":"2.0","id":%s,"result":{"args":"%s"}}\n' "$id" "$args"
+done
+"#,
+        );
+    });
+
+    let with_mode = |mode: Value| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "1.0",
+                "clientCapabilities": {},
+                "_meta": { "sandboxagent.dev": { "permissionMode": mode } }
+            }
+        })
+    };
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-read-only?agent=codex",
+        Some(with_mode(json!("readOnly"))),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        parse_json(&body)["result"]["args"],
+        r#"-c sandbox_mode="read-only" -c approval_policy="on-request""#
+    );
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-custom?agent=codex",
+        Some(with_mode(json!({
+            "mode": "custom",
+            "policy": { "edit": "allow", "bash": "allow", "webfetch": "allow" }
+        }))),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        parse_json(&body)["result"]["args"],
+        r#"-c sandbox_mode="workspace-write" -c approval_policy="never" -c sandbox_workspace_write.network_access=true"#
+    );
+
+    for mode in [
+        json!("plan"),
+        json!("sometimes"),
+        json!({ "mode": "custom", "policy": { "bash": "deny" } }),
+        json!({ "mode": "custom", "policy": { "network": "allow" } }),
+    ] {
+        let (status, _, _) = send_request(
+            &test_app.app,
+            Method::POST,
+            "/v1/acp/server-rejected?agent=codex",
+            Some(with_mode(mode.clone())),
+            &[],
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{mode}");
+    }
+
+    let (status, _, body) =
+        send_request(&test_app.app, Method::GET, "/v1/agents/codex", None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        parse_json(&body)["capabilities"]["permissionModes"],
+        json!(["default", "acceptEdits", "readOnly", "bypass", "custom"])
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn codex_options_layer_over_permission_mode() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        setup_stub_artifacts(install_dir, "claude");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+args=$(printf '%s' "$*" | sed 's/"/\\"/g')
+while IFS= read -r line; do
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  printf '{"jsonrpc":"2.0","id":%s,"result":{"args":"%s"}}\n' "$id" "$args"
+done
+"#,
+        );
+    });
+
+    let with_meta = |meta: Value| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "1.0",
+                "clientCapabilities": {},
+                "_meta": { "sandboxagent.dev": meta }
+            }
+        })
+    };
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-layered?agent=codex",
+        Some(with_meta(json!({
+            "permissionMode": "readOnly",
+            "codex": { "approvalPolicy": "untrusted" }
+        }))),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        parse_json(&body)["result"]["args"],
+        r#"-c sandbox_mode="read-only" -c approval_policy="untrusted""#
+    );
+
+    // The block can tighten the mode but not loosen it.
+    for codex in [
+        json!({ "sandboxMode": "danger-full-access" }),
+        json!({ "approvalPolicy": "never" }),
+    ] {
+        let (status, _, body) = send_request(
+            &test_app.app,
+            Method::POST,
+            "/v1/acp/server-loosened?agent=codex",
+            Some(with_meta(json!({
+                "permissionMode": "readOnly",
+                "codex": codex.clone()
+            }))),
+            &[],
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{codex}");
+        assert!(
+            parse_json(&body)["detail"]
+                .as_str()
+                .is_some_and(|detail| detail.contains("looser")),
+            "{body:?}"
+        );
+    }
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-workspace?agent=codex",
+        Some(with_meta(json!({
+            "codex": {
+                "sandboxMode": "workspace-write",
+                "networkAccess": true,
+                "writableRoots": ["/data"]
+            }
+        }))),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        parse_json(&body)["result"]["args"],
+        r#"-c sandbox_mode="workspace-write" -c sandbox_workspace_write.network_access=true -c sandbox_workspace_write.writable_roots=["/data"]"#
+    );
+
+    for codex in [
+        json!({ "sandboxMode": "open" }),
+        json!({ "approvalPolicy": "always" }),
+        json!({ "networkAccess": true }),
+        json!({ "sandboxMode": "read-only", "writableRoots": ["/data"] }),
+        json!({ "network": true }),
+    ] {
+        let (status, _, _) = send_request(
+            &test_app.app,
+            Method::POST,
+            "/v1/acp/server-rejected?agent=codex",
+            Some(with_meta(json!({ "codex": codex.clone() }))),
+            &[],
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{codex}");
+    }
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-claude?agent=claude",
+        Some(with_meta(
+            json!({ "codex": { "sandboxMode": "read-only" } }),
+        )),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn session_homes_give_each_server_its_own_home() {
+    let homes = tempfile::tempdir().expect("create homes dir");
+    let _homes = EnvVarGuard::set_os("SANDBOX_AGENT_SESSION_HOMES_DIR", homes.path().as_os_str());
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  printf '{"jsonrpc":"2.0","id":%s,"result":{"home":"%s"}}\n' "$id" "$HOME"
+done
+"#,
+        );
+    });
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-home.a?agent=codex",
+        Some(initialize_payload()),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let home = homes.path().join("server-home_a");
+    assert_eq!(
+        parse_json(&body)["result"]["home"],
+        home.to_string_lossy().as_ref()
+    );
+    assert!(home.is_dir());
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-home-explicit?agent=codex",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "1.0",
+                "clientCapabilities": {},
+                "_meta": { "sandboxagent.dev": { "env": { "HOME": "/tmp/explicit-home" } } }
+            }
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body)["result"]["home"], "/tmp/explicit-home");
+    assert!(!homes.path().join("server-home-explicit").exists());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn validate_reports_problems_without_starting_server() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+    });
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-validate/validate?agent=nope",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "session/new",
+            "params": {
+                "cwd": "relative/dir",
+                "mcpServers": [],
+                "_meta": { "sandboxagent.dev": { "env": { "TOKEN": "${SANDBOX_AGENT_TEST_SECRET}" } } }
+            }
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let parsed = parse_json(&body);
+    assert_eq!(parsed["valid"], false);
+    let fields = parsed["problems"]
+        .as_array()
+        .expect("problems")
+        .iter()
+        .map(|problem| problem["field"].as_str().expect("field").to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(fields, vec!["agent", "env.TOKEN", "params.cwd"]);
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-validate/validate?agent=codex",
+        Some(initialize_payload()),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let parsed = parse_json(&body);
+    assert_eq!(parsed["valid"], true);
+    assert_eq!(parsed["agent"], "codex");
+    assert_eq!(parsed["exists"], false);
+    assert_eq!(parsed["agentInstalled"], true);
+
+    let (status, _, body) = send_request(&test_app.app, Method::GET, "/v1/acp", None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body)["servers"], json!([]));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn secrets_are_injected_and_redacted() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  printf '{"jsonrpc":"2.0","id":%s,"result":{"greeting":"token=%s file=%s"}}\n' "$id" "$API_TOKEN" "$(cat "$KEY_FILE")"
+done
+"#,
+        );
+    });
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-secrets/secrets",
+        Some(json!({ "secrets": [{ "name": "bad-name", "value": "x" }] })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-secrets/secrets",
+        Some(json!({
+            "secrets": [
+                { "name": "API_TOKEN", "value": "tok-123456" },
+                { "name": "KEY_FILE", "value": "key-abcdef", "file": true }
+            ]
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let parsed = parse_json(&body);
+    assert_eq!(parsed["names"], json!(["API_TOKEN", "KEY_FILE"]));
+    assert_eq!(parsed["restartRequired"], false);
+    assert!(!String::from_utf8_lossy(&body).contains("tok-123456"));
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-secrets?agent=codex",
+        Some(initialize_payload()),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        parse_json(&body)["result"]["greeting"],
+        "token=[REDACTED] file=[REDACTED]"
+    );
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-secrets/secrets",
+        Some(json!({ "secrets": [{ "name": "LATE", "value": "late-value" }] })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body)["restartRequired"], true);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn session_new_applies_agent_defaults() {
+    let _defaults = EnvVarGuard::set(
+        "SANDBOX_AGENT_AGENT_DEFAULTS",
+        r#"{"codex":{"model":"large","mode":"code"}}"#,
+    );
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+count=0
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/new)
+      count=$((count + 1))
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-%s","modes":{"currentModeId":"ask","availableModes":[{"id":"ask","name":"Ask"},{"id":"code","name":"Code"}]},"models":{"currentModelId":"small","availableModels":[{"modelId":"small","name":"Small"},{"modelId":"large","name":"Large"}]}}}\n' "$id" "$count" ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+
+    let (status, _, body) =
+        send_request(&test_app.app, Method::GET, "/v1/agents/codex", None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        parse_json(&body)["sessionDefaults"],
+        json!({"model": "large", "mode": "code"})
+    );
+
+    bootstrap_server(&test_app.app, "server-defaults", "codex").await;
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-defaults",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "session/new",
+            "params": {"cwd": "/tmp", "mcpServers": []}
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let result = &parse_json(&body)["result"];
+    assert_eq!(result["models"]["currentModelId"], "large");
+    assert_eq!(result["modes"]["currentModeId"], "code");
+
+    // The envelope's own choice wins over the default.
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-defaults",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "session/new",
+            "params": {
+                "cwd": "/tmp",
+                "mcpServers": [],
+                "_meta": {"sandboxagent.dev": {"model": "small"}}
+            }
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let result = &parse_json(&body)["result"];
+    assert_eq!(result["models"]["currentModelId"], "small");
+    assert_eq!(result["modes"]["currentModeId"], "code");
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-defaults/sessions/s-1",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let detail = parse_json(&body);
+    assert_eq!(detail["modelId"], "large");
+    assert_eq!(detail["modeId"], "code");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn model_alias_falls_back_when_rate_limited() {
+    let _aliases = EnvVarGuard::set(
+        "SANDBOX_AGENT_MODEL_ALIASES",
+        r#"{"smart":["codex:large","codex:small"],"fast":["claude:haiku"]}"#,
+    );
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+model=small
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/new) printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-1","models":{"currentModelId":"small","availableModels":[{"modelId":"small","name":"Small"},{"modelId":"large","name":"Large"}]}}}\n' "$id" ;;
+    session/set_model)
+      model=$(printf '%s\n' "$line" | sed -n 's/.*"modelId"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+      printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+    session/prompt)
+      if [ "$model" = large ]; then
+        printf '{"jsonrpc":"2.0","id":%s,"error":{"code":-32000,"message":"Rate limit reached for large"}}\n' "$id"
+      else
+        printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"end_turn"}}\n' "$id"
+      fi ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+
+    bootstrap_server(&test_app.app, "server-alias", "codex").await;
+    let session_new = |id: u64, model: &str| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "session/new",
+            "params": {
+                "cwd": "/tmp",
+                "mcpServers": [],
+                "_meta": {"sandboxagent.dev": {"model": model}}
+            }
+        })
+    };
+
+    // `fast` only routes to Claude.
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-alias",
+        Some(session_new(2, "fast")),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-alias",
+        Some(session_new(3, "smart")),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        parse_json(&body)["result"]["models"]["currentModelId"],
+        "large"
+    );
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-alias",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 4,
+            "method": "session/prompt",
+            "params": {"sessionId": "s-1", "prompt": [{"type": "text", "text": "hi"}]}
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body)["result"]["stopReason"], "end_turn");
+
+    let (_, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-alias/sessions/s-1",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(parse_json(&body)["modelId"], "small");
+
+    let (_, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-alias/events",
+        None,
+        &[],
+    )
+    .await;
+    let fallbacks = parse_json(&body)["events"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|event| event["payload"]["method"] == "_adapter/model_fallback")
+        .collect::<Vec<_>>();
+    assert_eq!(fallbacks.len(), 1);
+    assert_eq!(fallbacks[0]["payload"]["params"]["from"], "large");
+    assert_eq!(fallbacks[0]["payload"]["params"]["to"], "small");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn bootstrap_fails_over_to_fallback_agent() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        setup_stub_artifacts(install_dir, "claude");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+if [ "${1:-}" = "--version" ]; then
+  echo "codex-agent-process 0.0.1"
+  exit 0
+fi
+echo "codex: missing credentials" >&2
+exit 1
+"#,
+        );
+    });
+    let initialize = |fallback_agents: Value| {
+        let mut payload = initialize_payload();
+        payload["params"]["_meta"] =
+            json!({"sandboxagent.dev": {"fallbackAgents": fallback_agents}});
+        payload
+    };
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-failover?agent=codex",
+        Some(initialize(json!(["nope"]))),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-failover?agent=codex",
+        Some(initialize(json!(["claude"]))),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body)["result"]["echoedMethod"], "initialize");
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-failover/events",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let events = parse_json(&body);
+    let failover = events["events"]
+        .as_array()
+        .expect("events")
+        .iter()
+        .find(|event| event["payload"]["method"] == "_adapter/agent_failover")
+        .expect("failover event");
+    assert_eq!(failover["payload"]["params"]["from"], "codex");
+    assert_eq!(failover["payload"]["params"]["to"], "claude");
+    assert_eq!(failover["payload"]["params"]["reason"], "exited");
+
+    // Later requests go to the agent the server failed over to.
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-failover?agent=codex",
+        Some(initialize_payload()),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn claude_settings_are_written_and_passed_with_settings_flag() {
+    let settings_dir = TempDir::new().expect("settings dir");
+    let _dir = EnvVarGuard::set(
+        "SANDBOX_AGENT_CLAUDE_SETTINGS_DIR",
+        settings_dir.path().to_str().expect("utf-8 path"),
+    );
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        setup_stub_artifacts(install_dir, "claude");
+        write_executable(
+            &install_dir.join("agent_processes/claude-acp"),
+            r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  settings=$(printf '%s\n' "$line" | sed -n 's/.*"extraArgs"[[:space:]]*:[[:space:]]*{"settings"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  case "$method" in
+    session/new) printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-1","settings":"%s"}}\n' "$id" "$settings" ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+    let hooks = json!({
+        "PreToolUse": [{
+            "matcher": "Bash",
+            "hooks": [{"type": "command", "command": "/opt/guard.sh"}]
+        }]
+    });
+    let session_new = |claude: Value| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "session/new",
+            "params": {
+                "cwd": "/tmp",
+                "mcpServers": [],
+                "_meta": {"sandboxagent.dev": {"claude": claude}}
+            }
+        })
+    };
+
+    bootstrap_server(&test_app.app, "server-claude", "claude").await;
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-claude",
+        Some(session_new(json!({
+            "settingsJson": {"model": "sonnet", "hooks": {}},
+            "hooks": hooks,
+            "outputStyle": "Explanatory"
+        }))),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let path = parse_json(&body)["result"]["settings"]
+        .as_str()
+        .expect("settings path")
+        .to_string();
+    let process_dir = Path::new(&path)
+        .parent()
+        .and_then(Path::parent)
+        .expect("process dir");
+    assert_eq!(process_dir.parent(), Some(settings_dir.path()));
+    assert!(process_dir
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with(&format!("{}-", std::process::id()))));
+    let written: Value =
+        serde_json::from_str(&fs::read_to_string(&path).expect("settings file")).expect("json");
+    assert_eq!(
+        written,
+        json!({"model": "sonnet", "hooks": hooks, "outputStyle": "Explanatory"})
+    );
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-claude",
+        Some(session_new(json!({"statusLine": {}}))),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    bootstrap_server(&test_app.app, "server-codex", "codex").await;
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-codex",
+        Some(session_new(json!({"outputStyle": "Explanatory"}))),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::DELETE,
+        "/v1/acp/server-claude",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(!Path::new(&path).exists());
+}
@@ -0,0 +1,427 @@
+use super::*;
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn oversized_invalid_stdout_is_truncated_and_spilled() {
+    let spill_dir = tempfile::tempdir().expect("create spill dir");
+    let _max = EnvVarGuard::set("SANDBOX_AGENT_MAX_RAW_BYTES", "64");
+    let _dir = EnvVarGuard::set(
+        "SANDBOX_AGENT_RAW_SPILL_DIR",
+        spill_dir.path().to_str().expect("utf8 path"),
+    );
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+read -r line
+id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+head -c 200 /dev/zero | tr '\0' 'x'
+echo
+printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id"
+while IFS= read -r line; do :; done
+"#,
+        );
+    });
+
+    bootstrap_server(&test_app.app, "server-raw", "codex").await;
+    let event = parse_sse_data(&read_first_sse_data(&test_app.app, "server-raw").await);
+    assert_eq!(event["method"], "_adapter/invalid_stdout");
+    let params = &event["params"];
+    assert_eq!(params["truncated"], true);
+    assert_eq!(params["rawBytes"], 200);
+    assert_eq!(params["raw"], "x".repeat(64));
+    let raw_path = std::path::PathBuf::from(params["rawPath"].as_str().expect("rawPath"));
+    assert!(raw_path.starts_with(spill_dir.path()));
+    assert_eq!(
+        fs::read_to_string(&raw_path).expect("read spilled line"),
+        "x".repeat(200)
+    );
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::DELETE,
+        "/v1/acp/server-raw",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(!raw_path.exists());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn large_tool_outputs_are_stored_as_artifacts() {
+    let artifacts_dir = tempfile::tempdir().expect("create artifacts dir");
+    let _min = EnvVarGuard::set("SANDBOX_AGENT_ARTIFACT_MIN_BYTES", "100");
+    let _preview = EnvVarGuard::set("SANDBOX_AGENT_ARTIFACT_PREVIEW_BYTES", "16");
+    let _dir = EnvVarGuard::set(
+        "SANDBOX_AGENT_ARTIFACTS_DIR",
+        artifacts_dir.path().to_str().expect("utf8 path"),
+    );
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+read -r line
+id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+output=$(head -c 200 /dev/zero | tr '\0' 'x')
+printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"tool_call_update","toolCallId":"call-1","content":[{"type":"content","content":{"type":"text","text":"%s"}}],"rawOutput":{"stdout":"%s"}}}}\n' "$output" "$output"
+printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id"
+while IFS= read -r line; do :; done
+"#,
+        );
+    });
+
+    bootstrap_server(&test_app.app, "server-artifacts", "codex").await;
+    let event = parse_sse_data(&read_first_sse_data(&test_app.app, "server-artifacts").await);
+    let update = &event["params"]["update"];
+    assert_eq!(update["content"][0]["content"]["text"], "x".repeat(16));
+    let artifacts = update["_meta"]["sandboxagent.dev"]["artifacts"]
+        .as_array()
+        .expect("artifacts");
+    assert_eq!(artifacts.len(), 2);
+    assert_eq!(artifacts[0]["pointer"], "/content/0/content/text");
+    assert_eq!(artifacts[0]["bytes"], 200);
+    assert_eq!(artifacts[0]["mimeType"], "text/plain");
+    assert_eq!(artifacts[1]["pointer"], "/rawOutput");
+    assert_eq!(artifacts[1]["mimeType"], "application/json");
+    assert!(update["rawOutput"].is_string());
+
+    let artifact_path = |index: usize| {
+        format!(
+            "/v1/acp/server-artifacts/sessions/s-1/artifacts/{}",
+            artifacts[index]["id"].as_str().expect("artifact id")
+        )
+    };
+    let (status, headers, body) =
+        send_request(&test_app.app, Method::GET, &artifact_path(0), None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers
+        .get("content-type")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/plain")));
+    assert_eq!(String::from_utf8_lossy(&body), "x".repeat(200));
+
+    let (status, _, body) =
+        send_request(&test_app.app, Method::GET, &artifact_path(1), None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body), json!({ "stdout": "x".repeat(200) }));
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-artifacts/sessions/s-1/artifacts/..%2Fsecret",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::DELETE,
+        "/v1/acp/server-artifacts",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _, _) =
+        send_request(&test_app.app, Method::GET, &artifact_path(0), None, &[]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn overlong_stdout_line_is_discarded_with_marker() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+read -r line
+id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+head -c 17000000 /dev/zero | tr '\0' 'x'
+echo
+printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id"
+while IFS= read -r line; do :; done
+"#,
+        );
+    });
+
+    bootstrap_server(&test_app.app, "server-overflow", "codex").await;
+    let event = parse_sse_data(&read_first_sse_data(&test_app.app, "server-overflow").await);
+    assert_eq!(event["method"], "_adapter/stdout_overflow");
+    assert_eq!(event["params"]["lineBytes"], 17_000_000);
+
+    let (status, _, body) = send_request(&test_app.app, Method::GET, "/v1/acp", None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    let servers = parse_json(&body)["servers"].clone();
+    let pipeline = &servers[0]["pipeline"];
+    assert_eq!(pipeline["oversizedLines"], 1);
+    assert_eq!(pipeline["bufferedEnvelopes"], 2);
+    assert!(pipeline["bufferedBytes"].as_u64().expect("buffered bytes") < 1024);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn agent_exit_is_classified_from_stderr() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+read -r line
+echo "Error: Invalid API key provided" >&2
+exit 1
+"#,
+        );
+    });
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-exit?agent=codex",
+        Some(initialize_payload()),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    let parsed = parse_json(&body);
+    assert_eq!(
+        parsed["type"],
+        "urn:sandbox-agent:error:agent_process_exited"
+    );
+    assert_eq!(parsed["details"]["exitCode"], 1);
+    assert_eq!(parsed["details"]["reason"], "auth_failed");
+    assert_eq!(parsed["code"], "agent_auth_failed");
+    assert_eq!(parsed["retryable"], false);
+    assert!(parsed["details"]["stderr"]
+        .as_str()
+        .is_some_and(|stderr| stderr.contains("Invalid API key")));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn spawn_failure_has_its_own_error_code() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        let launcher = install_dir.join("agent_processes/codex-acp");
+        fs::set_permissions(&launcher, fs::Permissions::from_mode(0o644)).expect("clear exec bit");
+    });
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-spawn?agent=codex",
+        Some(initialize_payload()),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    let parsed = parse_json(&body);
+    assert_eq!(parsed["type"], "urn:sandbox-agent:error:stream_error");
+    assert_eq!(parsed["code"], "spawn_failed");
+    assert_eq!(parsed["retryable"], false);
+    assert_eq!(parsed["agent"], "codex");
+
+    let (status, _, body) =
+        send_request(&test_app.app, Method::GET, "/v1/acp/missing", None, &[]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(parse_json(&body)["code"], "not_found");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn agent_stderr_is_streamed_as_opt_in_logs() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+echo "npm warn deprecated inflight@1.0.6" >&2
+while IFS= read -r line; do
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id"
+done
+"#,
+        );
+    });
+
+    bootstrap_server(&test_app.app, "server-logs", "codex").await;
+
+    let mut log = None;
+    for _ in 0..50 {
+        let (status, _, body) = send_request(
+            &test_app.app,
+            Method::GET,
+            "/v1/acp/server-logs/events?offset=0&includeLogs=true",
+            None,
+            &[],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        log = parse_json(&body)["events"]
+            .as_array()
+            .expect("events")
+            .iter()
+            .find(|event| event["payload"]["method"] == "_adapter/log")
+            .cloned();
+        if log.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let log = log.expect("stderr log notification");
+    assert_eq!(log["payload"]["params"]["stream"], "stderr");
+    assert_eq!(log["payload"]["params"]["level"], "warn");
+    assert_eq!(
+        log["payload"]["params"]["text"],
+        "npm warn deprecated inflight@1.0.6"
+    );
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-logs/events?offset=0",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!parse_json(&body)["events"]
+        .as_array()
+        .expect("events")
+        .iter()
+        .any(|event| event["payload"]["method"] == "_adapter/log"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn rejected_flag_is_reported_as_agent_incompatibility() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+read -r line
+echo "error: unexpected argument '--experimental-acp' found" >&2
+exit 2
+"#,
+        );
+        fs::write(
+            install_dir.join("known-good.json"),
+            r#"{"codex":{"version":"0.0.0"}}"#,
+        )
+        .expect("write known-good table");
+    });
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-usage?agent=codex",
+        Some(initialize_payload()),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let mut incompatibility = Value::Null;
+    for _ in 0..50 {
+        let (status, _, body) =
+            send_request(&test_app.app, Method::GET, "/v1/agents/codex", None, &[]).await;
+        assert_eq!(status, StatusCode::OK);
+        incompatibility = parse_json(&body)["incompatibility"].clone();
+        if !incompatibility.is_null() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    assert_eq!(incompatibility["reason"], "usage_error");
+    assert_eq!(incompatibility["installedVersion"], "codex 0.0.1");
+    assert_eq!(incompatibility["suggestedVersion"], "0.0.0");
+    assert_eq!(incompatibility["autoInstalled"], false);
+    assert!(incompatibility["detail"]
+        .as_str()
+        .is_some_and(|detail| detail.contains("unexpected argument")));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn turn_logs_keep_raw_agent_output() {
+    let log_dir = tempfile::tempdir().expect("turn log dir");
+    let _turn_logs = EnvVarGuard::set_os("SANDBOX_AGENT_TURN_LOG_DIR", log_dir.path().as_os_str());
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/prompt)
+      printf 'warming up, not json\n'
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"end_turn"}}\n' "$id" ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+
+    bootstrap_server(&test_app.app, "server-turn-logs", "codex").await;
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-turn-logs",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "method": "session/prompt",
+            "params": {
+                "sessionId": "s-1",
+                "prompt": [{"type": "text", "text": "hello"}]
+            }
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, headers, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-turn-logs/turns/1/logs",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/plain")));
+    let text = String::from_utf8_lossy(&body);
+    assert!(text.contains("[stdout] warming up, not json"), "{text}");
+    assert!(text.contains(r#""stopReason":"end_turn""#), "{text}");
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-turn-logs/turns/2/logs",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,449 @@
+use super::*;
+
+#[derive(Default)]
+struct FakeRedis {
+    hashes: std::collections::HashMap<String, String>,
+    streams: std::collections::HashMap<String, Vec<(u64, String)>>,
+    heartbeats: std::collections::HashSet<String>,
+    /// Replicas whose heartbeats are dropped, as if they had stopped.
+    stopped: std::collections::HashSet<String>,
+    /// Registrations still to fail, as if Redis were briefly unreachable.
+    failing_registrations: usize,
+}
+
+/// Redis stand-in answering the hash, stream and script commands the cluster
+/// uses.
+async fn spawn_fake_redis() -> (
+    std::net::SocketAddr,
+    std::sync::Arc<std::sync::Mutex<FakeRedis>>,
+) {
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind fake redis");
+    let address = listener.local_addr().expect("fake redis address");
+    let state = std::sync::Arc::new(std::sync::Mutex::new(FakeRedis::default()));
+    let shared = state.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let state = shared.clone();
+            tokio::spawn(async move {
+                let (read, mut write) = stream.into_split();
+                let mut read = BufReader::new(read);
+                let mut header = String::new();
+                while read.read_line(&mut header).await.is_ok_and(|len| len > 0) {
+                    let count = header.trim().trim_start_matches('*').parse().unwrap_or(0);
+                    let mut args = Vec::with_capacity(count);
+                    for _ in 0..count {
+                        header.clear();
+                        let _ = read.read_line(&mut header).await;
+                        let len = header.trim().trim_start_matches('$').parse().unwrap_or(0);
+                        let mut arg = vec![0; len + 2];
+                        if read.read_exact(&mut arg).await.is_err() {
+                            return;
+                        }
+                        arg.truncate(len);
+                        args.push(String::from_utf8_lossy(&arg).into_owned());
+                    }
+                    header.clear();
+                    let reply = fake_redis_reply(&state, &args).await;
+                    if write.write_all(reply.as_bytes()).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    (address, state)
+}
+
+async fn fake_redis_reply(
+    state: &std::sync::Arc<std::sync::Mutex<FakeRedis>>,
+    args: &[String],
+) -> String {
+    let bulk = |value: &str| format!("${}\r\n{value}\r\n", value.len());
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    match args.as_slice() {
+        ["CLIENT", ..] => "+OK\r\n".to_string(),
+        // Register: claim the field unless another value holds it, then
+        // drop the events stream.
+        ["EVAL", _, "2", _, events, field, value] => {
+            let mut state = state.lock().unwrap();
+            if state.failing_registrations > 0 {
+                state.failing_registrations -= 1;
+                return "-ERR try again\r\n".to_string();
+            }
+            if state.hashes.get(*field).is_some_and(|owner| owner != value) {
+                return ":0\r\n".to_string();
+            }
+            state.hashes.insert(field.to_string(), value.to_string());
+            state.streams.remove(*events);
+            ":1\r\n".to_string()
+        }
+        // Owner lookup: drop the entry of a replica without a heartbeat.
+        ["EVAL", _, "1", _, field, prefix, asker] => {
+            let mut state = state.lock().unwrap();
+            let Some(owner) = state.hashes.get(*field).cloned() else {
+                return "$-1\r\n".to_string();
+            };
+            if owner == *asker || state.heartbeats.contains(&format!("{prefix}{owner}")) {
+                return bulk(&owner);
+            }
+            state.hashes.remove(*field);
+            "$-1\r\n".to_string()
+        }
+        // Release: compare-and-delete.
+        ["EVAL", _, "1", _, field, value] => {
+            let mut state = state.lock().unwrap();
+            if state.hashes.get(*field).map(String::as_str) != Some(*value) {
+                return ":0\r\n".to_string();
+            }
+            state.hashes.remove(*field);
+            ":1\r\n".to_string()
+        }
+        ["HSETNX", _, field, value] => {
+            let mut state = state.lock().unwrap();
+            if state.hashes.contains_key(*field) {
+                ":0\r\n".to_string()
+            } else {
+                state.hashes.insert(field.to_string(), value.to_string());
+                ":1\r\n".to_string()
+            }
+        }
+        ["HGET", _, field] => match state.lock().unwrap().hashes.get(*field) {
+            Some(value) => bulk(value),
+            None => "$-1\r\n".to_string(),
+        },
+        ["SETEX", key, _, _] => {
+            let mut state = state.lock().unwrap();
+            let replica = key.trim_start_matches("sandbox-agent:replicas:");
+            if !state.stopped.contains(replica) {
+                state.heartbeats.insert(key.to_string());
+            }
+            "+OK\r\n".to_string()
+        }
+        ["EXPIRE", ..] => ":1\r\n".to_string(),
+        ["XADD", key, "MAXLEN", "~", _, id, "payload", payload] => {
+            let sequence = id.trim_end_matches("-0").parse().expect("sequence id");
+            state
+                .lock()
+                .unwrap()
+                .streams
+                .entry(key.to_string())
+                .or_default()
+                .push((sequence, payload.to_string()));
+            bulk(id)
+        }
+        ["XREAD", "BLOCK", _, "COUNT", _, "STREAMS", key, after] => {
+            let after = after
+                .trim_end_matches("-0")
+                .parse::<u64>()
+                .expect("after id");
+            for _ in 0..100 {
+                let entries = state
+                    .lock()
+                    .unwrap()
+                    .streams
+                    .get(*key)
+                    .map(|entries| {
+                        entries
+                            .iter()
+                            .filter(|(sequence, _)| *sequence > after)
+                            .cloned()
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                if !entries.is_empty() {
+                    let mut reply = format!("*1\r\n*2\r\n{}*{}\r\n", bulk(key), entries.len());
+                    for (sequence, payload) in entries {
+                        reply.push_str("*2\r\n");
+                        reply.push_str(&bulk(&format!("{sequence}-0")));
+                        reply.push_str("*2\r\n");
+                        reply.push_str(&bulk("payload"));
+                        reply.push_str(&bulk(&payload));
+                    }
+                    return reply;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            "*-1\r\n".to_string()
+        }
+        _ => "-ERR unknown command\r\n".to_string(),
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn replicas_share_servers_and_events_through_redis() {
+    let (address, redis) = spawn_fake_redis().await;
+    let _redis_url = EnvVarGuard::set(
+        "SANDBOX_AGENT_CLUSTER_REDIS_URL",
+        &format!("redis://{address}"),
+    );
+
+    // Nothing listens on the owner's address, so forwarding to it fails the
+    // way it would after the owner went away, though its heartbeat goes on.
+    let owner_url = "http://127.0.0.1:1";
+    let owner = {
+        let _replica = EnvVarGuard::set("SANDBOX_AGENT_REPLICA_URL", owner_url);
+        TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+            setup_stub_artifacts(install_dir, "codex");
+        })
+    };
+    let other = {
+        let _replica = EnvVarGuard::set("SANDBOX_AGENT_REPLICA_URL", "http://127.0.0.1:2");
+        TestApp::new(AuthConfig::disabled())
+    };
+
+    bootstrap_server(&owner.app, "server-shared", "codex").await;
+    assert_eq!(
+        redis
+            .lock()
+            .unwrap()
+            .hashes
+            .get("server-shared")
+            .map(String::as_str),
+        Some(owner_url)
+    );
+
+    // The other replica serves the stream from Redis, replay included.
+    let first = read_first_sse_data(&other.app, "server-shared").await;
+    let first = parse_sse_data(&first);
+    assert_eq!(first["method"], "server/echo");
+    assert_eq!(first["params"]["method"], "initialize");
+
+    let (status, _, _) = send_request(
+        &other.app,
+        Method::GET,
+        "/v1/acp/server-unknown",
+        None,
+        &[("accept", "text/event-stream")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Other requests go to the owner; an unreachable owner is dropped.
+    let (status, _, _) = send_request(
+        &other.app,
+        Method::DELETE,
+        "/v1/acp/server-shared",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(!redis.lock().unwrap().hashes.contains_key("server-shared"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn forwarded_requests_keep_their_namespace() {
+    let (address, redis) = spawn_fake_redis().await;
+    let _redis_url = EnvVarGuard::set(
+        "SANDBOX_AGENT_CLUSTER_REDIS_URL",
+        &format!("redis://{address}"),
+    );
+    let workspaces = tempfile::tempdir().expect("create namespace root");
+    let _tokens = EnvVarGuard::set("SANDBOX_AGENT_NAMESPACE_TOKENS", "team-a=token-a");
+    let _root = EnvVarGuard::set_os(
+        "SANDBOX_AGENT_NAMESPACE_ROOT",
+        workspaces.path().as_os_str(),
+    );
+    let team_a = [("authorization", "Bearer token-a")];
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind owner replica");
+    let owner_url = format!("http://{}", listener.local_addr().expect("owner address"));
+    let owner = {
+        let _replica = EnvVarGuard::set("SANDBOX_AGENT_REPLICA_URL", &owner_url);
+        TestApp::with_setup(
+            AuthConfig::with_token("secret-token".to_string()),
+            |install_dir| {
+                setup_stub_artifacts(install_dir, "codex");
+            },
+        )
+    };
+    let served = owner.app.clone().into_make_service();
+    tokio::spawn(async move { axum::serve(listener, served).await });
+    let other = {
+        let _replica = EnvVarGuard::set("SANDBOX_AGENT_REPLICA_URL", "http://127.0.0.1:2");
+        TestApp::new(AuthConfig::with_token("secret-token".to_string()))
+    };
+
+    let (status, _, _) = send_request(
+        &owner.app,
+        Method::POST,
+        "/v1/acp/server-a?agent=codex",
+        Some(initialize_payload()),
+        &team_a,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        redis
+            .lock()
+            .unwrap()
+            .hashes
+            .get("team-a~server-a")
+            .map(String::as_str),
+        Some(owner_url.as_str())
+    );
+
+    // The owner applies the namespace's workspace root to requests the
+    // other replica forwards to it.
+    let outside_cwd = json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "session/new",
+        "params": { "cwd": "/tmp", "mcpServers": [] }
+    });
+    let (status, _, _) = send_request(
+        &other.app,
+        Method::POST,
+        "/v1/acp/server-a",
+        Some(outside_cwd.clone()),
+        &team_a,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // A namespace header is only honored with a valid signature.
+    let (status, _, _) = send_request(
+        &owner.app,
+        Method::POST,
+        "/v1/acp/team-a~server-a",
+        Some(outside_cwd),
+        &[
+            ("authorization", "Bearer secret-token"),
+            ("x-sandbox-agent-namespace", "team-a.forged"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _, _) = send_request(
+        &other.app,
+        Method::DELETE,
+        "/v1/acp/server-a",
+        None,
+        &team_a,
+    )
+    .await;
+    assert!(status.is_success());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn streams_end_when_the_owner_stops() {
+    let (address, redis) = spawn_fake_redis().await;
+    let _redis_url = EnvVarGuard::set(
+        "SANDBOX_AGENT_CLUSTER_REDIS_URL",
+        &format!("redis://{address}"),
+    );
+    let owner_url = "http://127.0.0.1:1";
+    let owner = {
+        let _replica = EnvVarGuard::set("SANDBOX_AGENT_REPLICA_URL", owner_url);
+        TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+            setup_stub_artifacts(install_dir, "codex");
+        })
+    };
+    let other = {
+        let _replica = EnvVarGuard::set("SANDBOX_AGENT_REPLICA_URL", "http://127.0.0.1:2");
+        TestApp::new(AuthConfig::disabled())
+    };
+    bootstrap_server(&owner.app, "server-orphaned", "codex").await;
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri("/v1/acp/server-orphaned")
+        .header("accept", "text/event-stream")
+        .body(Body::empty())
+        .expect("build request");
+    let response = other
+        .app
+        .clone()
+        .oneshot(request)
+        .await
+        .expect("sse response");
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut stream = response.into_body().into_data_stream();
+    let first = tokio::time::timeout(Duration::from_secs(5), stream.next())
+        .await
+        .expect("replayed event");
+    assert!(first.is_some());
+
+    // The owner crashes: its heartbeat lapses and is never refreshed.
+    {
+        let mut state = redis.lock().unwrap();
+        state.stopped.insert(owner_url.to_string());
+        state
+            .heartbeats
+            .remove(&format!("sandbox-agent:replicas:{owner_url}"));
+    }
+    let ended = tokio::time::timeout(Duration::from_secs(10), async {
+        while let Some(chunk) = stream.next().await {
+            if chunk.is_err() {
+                break;
+            }
+        }
+    })
+    .await;
+    assert!(ended.is_ok(), "stream kept waiting on a dead owner");
+    assert!(!redis.lock().unwrap().hashes.contains_key("server-orphaned"));
+
+    let (status, _, _) = send_request(
+        &other.app,
+        Method::GET,
+        "/v1/acp/server-orphaned",
+        None,
+        &[("accept", "text/event-stream")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    drop(owner);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn registration_is_retried_after_redis_errors() {
+    let (address, redis) = spawn_fake_redis().await;
+    let _redis_url = EnvVarGuard::set(
+        "SANDBOX_AGENT_CLUSTER_REDIS_URL",
+        &format!("redis://{address}"),
+    );
+    let _replica = EnvVarGuard::set("SANDBOX_AGENT_REPLICA_URL", "http://127.0.0.1:1");
+    let owner = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+    });
+    redis.lock().unwrap().failing_registrations = 2;
+    bootstrap_server(&owner.app, "server-retried", "codex").await;
+
+    let published = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            {
+                let state = redis.lock().unwrap();
+                if state.failing_registrations == 0
+                    && state.hashes.contains_key("server-retried")
+                    && state
+                        .streams
+                        .get("sandbox-agent:events:server-retried")
+                        .is_some_and(|entries| !entries.is_empty())
+                {
+                    return;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await;
+    assert!(
+        published.is_ok(),
+        "server was not registered after retrying"
+    );
+}
@@ -0,0 +1,229 @@
+use super::*;
+
+#[cfg(unix)]
+#[tokio::test]
+async fn anthropic_messages_compat_runs_a_turn() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "claude");
+        write_executable(
+            &install_dir.join("agent_processes/claude-acp"),
+            r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/new) printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-1"}}\n' "$id" ;;
+    session/prompt)
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"Hello "}}}}\n'
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"there"}}}}\n'
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"end_turn","usage":{"inputTokens":12,"outputTokens":3}}}\n' "$id" ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/compat/anthropic/v1/messages",
+        Some(json!({
+            "model": "not-an-agent",
+            "max_tokens": 64,
+            "messages": [{ "role": "user", "content": "hi" }]
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(parse_json(&body)["error"]["type"], "invalid_request_error");
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/compat/anthropic/v1/messages",
+        Some(json!({
+            "model": "claude/sonnet",
+            "max_tokens": 64,
+            "system": "Be brief.",
+            "messages": [
+                { "role": "user", "content": "hi" },
+                { "role": "assistant", "content": [{ "type": "text", "text": "hello" }] },
+                { "role": "user", "content": "greet me" }
+            ]
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let message = parse_json(&body);
+    assert_eq!(message["type"], "message");
+    assert_eq!(message["model"], "claude/sonnet");
+    assert_eq!(
+        message["content"],
+        json!([{ "type": "text", "text": "Hello there" }])
+    );
+    assert_eq!(message["stop_reason"], "end_turn");
+    assert_eq!(
+        message["usage"],
+        json!({ "input_tokens": 12, "output_tokens": 3 })
+    );
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/compat/anthropic/v1/messages",
+        Some(json!({
+            "model": "claude",
+            "max_tokens": 64,
+            "stream": true,
+            "messages": [{ "role": "user", "content": "greet me" }]
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let events = String::from_utf8(body)
+        .expect("utf8 body")
+        .split("\n\n")
+        .filter(|chunk| chunk.contains("data: "))
+        .map(parse_sse_data)
+        .collect::<Vec<_>>();
+    let types = events
+        .iter()
+        .map(|event| event["type"].as_str().unwrap_or_default())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        types,
+        [
+            "message_start",
+            "content_block_start",
+            "content_block_delta",
+            "content_block_delta",
+            "content_block_stop",
+            "message_delta",
+            "message_stop"
+        ]
+    );
+    assert_eq!(events[3]["delta"]["text"], "there");
+    assert_eq!(events[5]["delta"]["stop_reason"], "end_turn");
+
+    // Each request's server is closed once its turn ends.
+    let servers = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let (_, _, body) = send_request(&test_app.app, Method::GET, "/v1/acp", None, &[]).await;
+            let servers = parse_json(&body)["servers"].clone();
+            if servers == json!([]) {
+                return servers;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("servers closed");
+    assert_eq!(servers, json!([]));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn grpc_service_drives_sessions_on_the_http_listener() {
+    use sandbox_agent::grpc::proto::sandbox_agent_client::SandboxAgentClient;
+    use sandbox_agent::grpc::proto::{
+        CreateSessionRequest, ReplyPermissionRequest, SendMessageRequest, StreamEventsRequest,
+    };
+
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_permission_stub(install_dir);
+    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind listener");
+    let address = listener.local_addr().expect("listener address");
+    let app = test_app.app.clone();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let mut client = SandboxAgentClient::connect(format!("http://{address}"))
+        .await
+        .expect("connect grpc client");
+    let session = client
+        .create_session(CreateSessionRequest {
+            server_id: "server-grpc".to_string(),
+            agent: "codex".to_string(),
+            cwd: "/tmp".to_string(),
+        })
+        .await
+        .expect("create session")
+        .into_inner();
+    assert_eq!(session.session_id, "s-1");
+
+    let mut events = client
+        .stream_events(StreamEventsRequest {
+            server_id: "server-grpc".to_string(),
+            offset: None,
+            include_logs: false,
+            types: vec!["session/request_permission".to_string()],
+            exclude: Vec::new(),
+        })
+        .await
+        .expect("stream events")
+        .into_inner();
+
+    let reply = client
+        .send_message(SendMessageRequest {
+            server_id: "server-grpc".to_string(),
+            session_id: "s-1".to_string(),
+            text: "hello".to_string(),
+        })
+        .await
+        .expect("send message")
+        .into_inner();
+    assert_eq!(reply.stop_reason, "end_turn");
+
+    let request = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let event = events.message().await.expect("event").expect("stream open");
+            let payload: Value = serde_json::from_str(&event.payload_json).expect("payload json");
+            if payload["method"] == "session/request_permission" {
+                return payload;
+            }
+        }
+    })
+    .await
+    .expect("timed out waiting for permission request");
+    assert_eq!(request["id"], "perm-1");
+
+    client
+        .reply_permission(ReplyPermissionRequest {
+            server_id: "server-grpc".to_string(),
+            request_id: "perm-1".to_string(),
+            option_id: "allow".to_string(),
+        })
+        .await
+        .expect("reply permission");
+    let requests = list_pending_requests(&test_app.app, "/v1/acp/server-grpc/requests").await;
+    assert_eq!(requests, json!([]));
+
+    let error = client
+        .reply_permission(ReplyPermissionRequest {
+            server_id: "server-grpc".to_string(),
+            request_id: "perm-1".to_string(),
+            option_id: "allow".to_string(),
+        })
+        .await
+        .expect_err("already answered");
+    assert_eq!(error.code(), tonic::Code::NotFound);
+
+    let error = client
+        .send_message(SendMessageRequest {
+            server_id: "missing".to_string(),
+            session_id: "s-1".to_string(),
+            text: "hello".to_string(),
+        })
+        .await
+        .expect_err("unknown server");
+    // As over HTTP, a new server needs an agent.
+    assert_eq!(error.code(), tonic::Code::InvalidArgument);
+}
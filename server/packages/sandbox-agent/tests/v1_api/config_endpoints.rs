@@ -150,3 +150,184 @@ async fn skills_config_crud_round_trip() {
     .await;
     assert_eq!(status, StatusCode::NOT_FOUND);
 }
+
+#[tokio::test]
+#[serial]
+async fn server_config_reports_effective_settings_with_secrets_masked() {
+    use sandbox_agent::config::ServerConfig;
+    use sandbox_agent::router::BrandingMode;
+
+    let error = ServerConfig::parse("[limits]\nturn_timeout = 5\n").expect_err("unknown key");
+    assert!(error.contains("limits.turn_timeout"), "{error}");
+
+    let config = ServerConfig::parse(
+        r#"
+[auth]
+token = "file-token"
+
+[server]
+port = 3000
+cors_allow_origins = ["http://localhost:5173"]
+cors_allow_headers = ["authorization"]
+cors_allow_credentials = true
+unix_socket = "/run/sandbox-agent.sock"
+
+[limits]
+turn_timeout_ms = 1000
+max_raw_bytes = 2048
+
+[models]
+fast = "claude:haiku"
+
+[agents.mock]
+model = "large"
+
+[agents.mock.env]
+API_KEY = "sk-secret"
+"#,
+    )
+    .expect("parse config");
+    assert_eq!(config.server.port, Some(3000));
+
+    // The environment wins over the file.
+    let _turn_timeout = EnvVarGuard::set("SANDBOX_AGENT_TURN_TIMEOUT_MS", "5000");
+    let install_dir = tempfile::tempdir().expect("create temp install dir");
+    let manager = AgentManager::new(install_dir.path()).expect("create agent manager");
+    let state = AppState::with_config(
+        AuthConfig::with_token("file-token".to_string()),
+        manager,
+        BrandingMode::SandboxAgent,
+        config,
+    );
+    let app = build_router(state);
+
+    let (status, _, _) = send_request(&app, Method::GET, "/v1/config", None, &[]).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _, body) = send_request(
+        &app,
+        Method::GET,
+        "/v1/config",
+        None,
+        &[("authorization", "Bearer file-token")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let parsed = parse_json(&body);
+    assert_eq!(parsed["auth"]["token"], "********");
+    assert_eq!(parsed["server"]["port"], 3000);
+    assert_eq!(
+        parsed["server"]["corsAllowOrigins"],
+        json!(["http://localhost:5173"])
+    );
+    assert_eq!(parsed["server"]["corsAllowMethods"], json!([]));
+    assert_eq!(
+        parsed["server"]["corsAllowHeaders"],
+        json!(["authorization"])
+    );
+    assert_eq!(parsed["server"]["corsAllowCredentials"], true);
+    assert_eq!(parsed["server"]["unixSocket"], "/run/sandbox-agent.sock");
+    assert_eq!(parsed["server"]["stdio"], false);
+    assert_eq!(parsed["limits"]["turnTimeoutMs"], 5000);
+    assert_eq!(parsed["limits"]["maxRawBytes"], 2048);
+    assert_eq!(parsed["agents"]["mock"]["env"]["API_KEY"], "********");
+    assert_eq!(parsed["agents"]["mock"]["model"], "large");
+    assert_eq!(parsed["models"]["fast"], json!(["claude:haiku"]));
+    assert!(!String::from_utf8_lossy(&body).contains("sk-secret"));
+}
+
+#[tokio::test]
+#[serial]
+async fn file_limits_reach_the_runtime_without_touching_env() {
+    use sandbox_agent::config::ServerConfig;
+    use sandbox_agent::router::BrandingMode;
+
+    let config = ServerConfig::parse("[limits]\nmax_prompt_bytes = 10\n").expect("parse config");
+    let install_dir = tempfile::tempdir().expect("create temp install dir");
+    setup_stub_artifacts(install_dir.path(), "codex");
+    let manager = AgentManager::new(install_dir.path()).expect("create agent manager");
+    let app = build_router(AppState::with_config(
+        AuthConfig::disabled(),
+        manager,
+        BrandingMode::SandboxAgent,
+        config,
+    ));
+    assert!(std::env::var_os("SANDBOX_AGENT_MAX_PROMPT_BYTES").is_none());
+
+    bootstrap_server(&app, "server-file-limits", "codex").await;
+    let (status, _, response) = send_request(
+        &app,
+        Method::POST,
+        "/v1/acp/server-file-limits",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "session/prompt",
+            "params": {
+                "sessionId": "s-1",
+                "prompt": [{"type": "text", "text": "longer than ten bytes"}]
+            }
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(parse_json(&response)["details"]["limitBytes"], 10);
+    assert!(std::env::var_os("SANDBOX_AGENT_MAX_PROMPT_BYTES").is_none());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn server_serves_router_over_unix_socket() {
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let test_app = TestApp::new(AuthConfig::disabled());
+    let dir = tempfile::tempdir().expect("tempdir");
+    let path = dir.path().join("sandbox-agent.sock");
+    // A socket left over from a crashed run is replaced.
+    drop(std::os::unix::net::UnixListener::bind(&path).expect("stale socket"));
+
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn({
+        let path = path.clone();
+        let app = test_app.app.clone();
+        async move {
+            sandbox_agent::transport::serve_unix(&path, app, async {
+                let _ = stopped.await;
+            })
+            .await
+        }
+    });
+
+    let mut stream = None;
+    for _ in 0..50 {
+        if let Ok(connected) = tokio::net::UnixStream::connect(&path).await {
+            stream = Some(connected);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let mut stream = stream.expect("connect to unix socket");
+    let mode = std::fs::metadata(&path)
+        .expect("socket metadata")
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    stream
+        .write_all(b"GET /v1/health HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+        .await
+        .expect("write request");
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .await
+        .expect("read response");
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    assert!(response.contains(r#"{"status":"ok"}"#), "{response}");
+
+    stop.send(()).expect("stop server");
+    server.await.expect("join").expect("serve");
+    assert!(!path.exists());
+}
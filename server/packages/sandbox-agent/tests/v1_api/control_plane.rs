@@ -17,6 +17,19 @@ async fn v1_health_removed_legacy_and_opencode_unmounted() {
     assert_eq!(status, StatusCode::OK);
 }
 
+#[tokio::test]
+async fn v1_openapi_document_is_served() {
+    let test_app = TestApp::new(AuthConfig::disabled());
+
+    let (status, _, body) =
+        send_request(&test_app.app, Method::GET, "/v1/openapi.json", None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    let document = parse_json(&body);
+    assert_eq!(document["info"]["version"], env!("CARGO_PKG_VERSION"));
+    assert!(document["paths"]["/v1/acp/{server_id}"].is_object());
+    assert!(document["components"]["schemas"]["AcpEnvelope"].is_object());
+}
+
 #[tokio::test]
 async fn v1_auth_enforced_when_token_configured() {
     let test_app = TestApp::new(AuthConfig::with_token("secret-token".to_string()));
@@ -36,6 +49,279 @@ async fn v1_auth_enforced_when_token_configured() {
     assert_eq!(parse_json(&body)["status"], "ok");
 }
 
+#[tokio::test]
+async fn ui_requires_token_and_remembers_it_in_a_cookie() {
+    let test_app = TestApp::new(AuthConfig::with_token("secret-token".to_string()));
+
+    for uri in ["/ui/", "/ui/?token=wrong", "/ui/assets/index.js"] {
+        let (status, _, _) = send_request(&test_app.app, Method::GET, uri, None, &[]).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{uri}");
+    }
+
+    // Whether the console itself was embedded depends on the build, so only
+    // check that the request got past the token check.
+    let (status, headers, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/ui/?token=secret-token",
+        None,
+        &[],
+    )
+    .await;
+    assert_ne!(status, StatusCode::UNAUTHORIZED);
+    let set_cookie = headers
+        .get(header::SET_COOKIE)
+        .and_then(|value| value.to_str().ok())
+        .expect("session cookie");
+    assert!(set_cookie.contains("HttpOnly"), "{set_cookie}");
+    assert!(set_cookie.contains("Max-Age="), "{set_cookie}");
+    let cookie = set_cookie.split(';').next().expect("cookie pair");
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/ui/assets/index.js",
+        None,
+        &[("cookie", cookie)],
+    )
+    .await;
+    assert_ne!(status, StatusCode::UNAUTHORIZED);
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/ui/assets/index.js",
+        None,
+        &[("cookie", "sandbox_agent_ui=forged")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // A cookie past its lifetime is refused even with a valid signature.
+    let (_, signature) = cookie
+        .split_once('.')
+        .expect("cookie carries its issue time");
+    let expired = format!("sandbox_agent_ui=1000.{signature}");
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/ui/assets/index.js",
+        None,
+        &[("cookie", expired.as_str())],
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn v1_agent_models_returns_catalog() {
+    let test_app = TestApp::new(AuthConfig::disabled());
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/agents/claude/models",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let parsed = parse_json(&body);
+    assert_eq!(parsed["agent"], "claude");
+    assert_eq!(parsed["defaultModel"], "default");
+    let models = parsed["models"].as_array().expect("models array");
+    let sonnet = models
+        .iter()
+        .find(|model| model["id"] == "sonnet")
+        .expect("sonnet model");
+    assert_eq!(sonnet["provider"], "anthropic");
+    assert_eq!(sonnet["contextWindow"], 200_000);
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/agents/opencode/models",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let parsed = parse_json(&body);
+    assert!(parsed["models"]
+        .as_array()
+        .expect("models array")
+        .iter()
+        .any(|model| model["provider"] == "openai"));
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/agents/unknown/models",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn v1_agent_install_progress_ends_when_idle() {
+    let test_app = TestApp::new(AuthConfig::disabled());
+
+    let (status, headers, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/agents/codex/install/progress",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers
+        .get("content-type")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/event-stream")));
+
+    let text = String::from_utf8(body).expect("utf8 stream");
+    assert!(text.contains("event: progress"), "{text}");
+    let data = text
+        .lines()
+        .find_map(|line| line.strip_prefix("data: "))
+        .expect("progress payload");
+    let parsed: Value = serde_json::from_str(data).expect("progress json");
+    assert_eq!(parsed["agent"], "codex");
+    assert_eq!(parsed["active"], false);
+    assert_eq!(parsed["downloadedBytes"], 0);
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/agents/not-an-agent/install/progress",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn v1_agent_pin_and_upgrade_round_trip() {
+    let test_app = TestApp::new(AuthConfig::disabled());
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::PUT,
+        "/v1/agents/mock/pin",
+        Some(json!({ "agentVersion": "1.2.3" })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body)["agentVersion"], "1.2.3");
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/agents/mock/versions",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let parsed = parse_json(&body);
+    assert_eq!(parsed["agent"], "mock");
+    assert_eq!(parsed["pinned"]["agentVersion"], "1.2.3");
+    assert_eq!(parsed["releases"], json!([]));
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/agents/mock/upgrade",
+        Some(json!({})),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let parsed = parse_json(&body);
+    assert_eq!(parsed["installedVersion"], "builtin");
+    assert!(!parsed["artifacts"]
+        .as_array()
+        .expect("artifacts")
+        .is_empty());
+    assert_eq!(parsed["changelog"], json!([]));
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::PUT,
+        "/v1/agents/mock/pin",
+        Some(json!({})),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/agents/mock/versions",
+        None,
+        &[],
+    )
+    .await;
+    assert!(parse_json(&body).get("pinned").is_none());
+}
+
+#[tokio::test]
+async fn v1_agent_uninstall_reports_and_frees_disk_usage() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_path| {
+        fs::create_dir_all(install_path.join("agent_processes"))
+            .expect("create agent processes dir");
+        fs::write(install_path.join("codex"), vec![0_u8; 4096]).expect("write codex binary");
+        write_executable(
+            &install_path.join("agent_processes/codex-acp"),
+            "#!/usr/bin/env sh\nexit 0\n",
+        );
+    });
+
+    let (status, _, body) = send_request(&test_app.app, Method::GET, "/v1/agents", None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    let parsed = parse_json(&body);
+    let codex = parsed["agents"]
+        .as_array()
+        .expect("agents")
+        .iter()
+        .find(|agent| agent["id"] == "codex")
+        .expect("codex agent");
+    assert!(codex["diskUsageBytes"].as_u64().expect("agent usage") >= 4096);
+    assert!(parsed["diskUsageBytes"].as_u64().expect("total usage") >= 4096);
+    assert!(parsed.get("diskQuotaBytes").is_none());
+
+    let (status, _, body) =
+        send_request(&test_app.app, Method::DELETE, "/v1/agents/codex", None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    let parsed = parse_json(&body);
+    assert_eq!(parsed["agent"], "codex");
+    assert!(parsed["freedBytes"].as_u64().expect("freed bytes") >= 4096);
+    assert!(!test_app.install_path().join("codex").exists());
+    assert!(!test_app
+        .install_path()
+        .join("agent_processes/codex-acp")
+        .exists());
+
+    let (status, _, body) =
+        send_request(&test_app.app, Method::GET, "/v1/agents/codex", None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body)["diskUsageBytes"], 0);
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::DELETE,
+        "/v1/agents/unknown",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
 #[tokio::test]
 async fn v1_filesystem_endpoints_round_trip() {
     let test_app = TestApp::new(AuthConfig::disabled());
@@ -134,6 +420,37 @@ async fn v1_filesystem_endpoints_round_trip() {
     assert_eq!(status, StatusCode::OK);
 }
 
+#[tokio::test]
+async fn v1_fs_workspace_detects_project_tooling() {
+    let test_app = TestApp::new(AuthConfig::disabled());
+    let workspace = tempfile::tempdir().expect("create workspace");
+    fs::write(workspace.path().join("Cargo.toml"), "[package]\n").expect("write Cargo.toml");
+    fs::write(
+        workspace.path().join("package.json"),
+        r#"{"scripts":{"test":"vitest"}}"#,
+    )
+    .expect("write package.json");
+    fs::write(workspace.path().join("pnpm-lock.yaml"), "").expect("write lockfile");
+
+    let uri = format!(
+        "/v1/fs/workspace?path={}",
+        workspace.path().to_string_lossy()
+    );
+    let (status, _, body) = send_request(&test_app.app, Method::GET, &uri, None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    let parsed = parse_json(&body);
+    assert_eq!(parsed["languages"], json!(["rust", "javascript"]));
+    assert_eq!(parsed["packageManagers"], json!(["cargo", "pnpm"]));
+    assert_eq!(parsed["testCommands"], json!(["cargo test", "pnpm test"]));
+
+    let file_uri = format!(
+        "/v1/fs/workspace?path={}",
+        workspace.path().join("Cargo.toml").to_string_lossy()
+    );
+    let (status, _, _) = send_request(&test_app.app, Method::GET, &file_uri, None, &[]).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
 #[tokio::test]
 #[serial]
 async fn require_preinstall_blocks_missing_agent() {
@@ -216,3 +533,261 @@ async fn lazy_install_runs_on_first_bootstrap() {
         .join("agent_processes/codex-acp")
         .exists());
 }
+
+#[tokio::test]
+#[serial]
+async fn preinstall_reports_progress_in_health() {
+    let registry_url = serve_registry_once(json!({
+        "agents": [
+            {
+                "id": "codex-acp",
+                "version": "1.2.3",
+                "distribution": {
+                    "npx": {
+                        "package": "@example/codex-acp@1.2.3",
+                        "args": [],
+                        "env": {}
+                    }
+                }
+            }
+        ]
+    }));
+    let _registry = EnvVarGuard::set("SANDBOX_AGENT_ACP_REGISTRY_URL", &registry_url);
+
+    let install_dir = tempfile::tempdir().expect("create temp install dir");
+    fs::create_dir_all(install_dir.path().join("agent_processes"))
+        .expect("create agent processes dir");
+    write_executable(
+        &install_dir.path().join("codex"),
+        "#!/usr/bin/env sh\nexit 0\n",
+    );
+    fs::create_dir_all(install_dir.path().join("bin")).expect("create bin dir");
+    write_executable(
+        &install_dir.path().join("bin").join("npx"),
+        "#!/usr/bin/env sh\nexit 0\n",
+    );
+    let original_path = std::env::var_os("PATH").unwrap_or_default();
+    let mut paths = vec![install_dir.path().join("bin")];
+    paths.extend(std::env::split_paths(&original_path));
+    let merged_path = std::env::join_paths(paths).expect("join PATH");
+    let _path_guard = EnvVarGuard::set_os("PATH", merged_path.as_os_str());
+
+    let manager = AgentManager::new(install_dir.path()).expect("create agent manager");
+    let state = std::sync::Arc::new(AppState::new(AuthConfig::disabled(), manager));
+    assert!(state.start_preinstall(&["nope".to_string()]).is_err());
+    let (app, state) = build_router_with_state(state);
+    state
+        .start_preinstall(&["codex".to_string()])
+        .expect("start preinstall");
+
+    let mut health = Value::Null;
+    for _ in 0..50 {
+        let (status, _, body) = send_request(&app, Method::GET, "/v1/health", None, &[]).await;
+        assert_eq!(status, StatusCode::OK);
+        health = parse_json(&body);
+        if health["status"] == "ok" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    assert_eq!(health["status"], "ok");
+    assert_eq!(health["preinstall"][0]["agent"], "codex");
+    assert_eq!(health["preinstall"][0]["status"], "installed");
+    assert!(install_dir
+        .path()
+        .join("agent_processes/codex-acp")
+        .exists());
+}
+
+#[tokio::test]
+async fn logs_are_queryable_by_session_and_level() {
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+
+    let _subscriber = tracing_subscriber::registry()
+        .with(sandbox_agent::log_buffer::layer())
+        .set_default();
+    let test_app = TestApp::new(AuthConfig::disabled());
+
+    tracing::info!(server_id = "logs-server-a", "starting up");
+    tracing::warn!(server_id = "logs-server-a", attempt = 2, "agent is slow");
+    tracing::warn!(server_id = "logs-server-b", "other server");
+    let span = tracing::info_span!("turn", session_id = "logs-session-c");
+    span.in_scope(|| tracing::error!("turn failed"));
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/logs?session=logs-server-a&level=warn",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let parsed = parse_json(&body);
+    let entries = parsed["entries"].as_array().expect("entries");
+    assert_eq!(entries.len(), 1, "{parsed}");
+    assert_eq!(entries[0]["level"], "warn");
+    assert_eq!(entries[0]["message"], "agent is slow");
+    assert_eq!(entries[0]["fields"]["attempt"], 2);
+    assert_eq!(parsed["nextSince"], entries[0]["sequence"]);
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/logs?session=logs-session-c",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body)["entries"][0]["message"], "turn failed");
+
+    let (status, _, _) =
+        send_request(&test_app.app, Method::GET, "/v1/logs?level=loud", None, &[]).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri("/v1/logs/stream?session=logs-server-a")
+        .body(Body::empty())
+        .expect("build request");
+    let response = test_app
+        .app
+        .clone()
+        .oneshot(request)
+        .await
+        .expect("sse response");
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut stream = response.into_body().into_data_stream();
+    let chunk = tokio::time::timeout(Duration::from_secs(5), async move {
+        loop {
+            let bytes = stream.next().await.expect("sse chunk").expect("chunk");
+            let text = String::from_utf8_lossy(&bytes).to_string();
+            if text.contains("data:") {
+                return text;
+            }
+        }
+    })
+    .await
+    .expect("timed out reading sse");
+    assert!(chunk.contains("event: log"), "{chunk}");
+    assert_eq!(parse_sse_data(&chunk)["message"], "starting up");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn agent_uninstall_conflicts_with_running_servers() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+    });
+    bootstrap_server(&test_app.app, "server-uninstall", "codex").await;
+
+    let (status, _, body) =
+        send_request(&test_app.app, Method::DELETE, "/v1/agents/codex", None, &[]).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(parse_json(&body)["status"], 409);
+    assert!(test_app.install_path().join("codex").exists());
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::DELETE,
+        "/v1/acp/server-uninstall",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, _, _) =
+        send_request(&test_app.app, Method::DELETE, "/v1/agents/codex", None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!test_app.install_path().join("codex").exists());
+}
+
+#[tokio::test]
+#[serial]
+async fn logged_request_headers_redact_credentials() {
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+
+    let _subscriber = tracing_subscriber::registry()
+        .with(sandbox_agent::log_buffer::layer())
+        .set_default();
+    let _headers = EnvVarGuard::set("SANDBOX_AGENT_LOG_HTTP_HEADERS", "1");
+    let test_app = TestApp::new(AuthConfig::with_token("secret-token".to_string()));
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/health",
+        None,
+        &[
+            ("x-api-key", "secret-token"),
+            ("proxy-authorization", "Basic secret-token"),
+            ("cookie", "sandbox_agent_ui=secret-token"),
+            ("authorization", "Bearer secret-token"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/logs?limit=1000",
+        None,
+        &[("authorization", "Bearer secret-token")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let body = String::from_utf8_lossy(&body);
+    assert!(body.contains("x-api-key"), "{body}");
+    assert!(!body.contains("secret-token"), "{body}");
+}
+
+#[tokio::test]
+async fn logs_drop_query_strings_from_request_uris() {
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+
+    let _subscriber = tracing_subscriber::registry()
+        .with(sandbox_agent::log_buffer::layer())
+        .set_default();
+    let test_app = TestApp::new(AuthConfig::with_token("secret-token".to_string()));
+    let admin = [("authorization", "Bearer secret-token")];
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/ui/?token=secret-token",
+        None,
+        &[],
+    )
+    .await;
+    assert_ne!(status, StatusCode::UNAUTHORIZED);
+    let span = tracing::info_span!("http.request", uri = "/v1/acp/logs-share?signature=abc123");
+    span.in_scope(|| tracing::info!("shared stream opened"));
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/logs?limit=1000",
+        None,
+        &admin,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let text = String::from_utf8_lossy(&body);
+    assert!(!text.contains("secret-token"), "{text}");
+    assert!(!text.contains("abc123"), "{text}");
+    let entries = parse_json(&body)["entries"].clone();
+    let shared = entries
+        .as_array()
+        .expect("entries")
+        .iter()
+        .find(|entry| entry["message"] == "shared stream opened")
+        .expect("span entry");
+    assert_eq!(shared["fields"]["uri"], "/v1/acp/logs-share");
+}
@@ -0,0 +1,126 @@
+use super::*;
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn namespace_tokens_only_see_their_own_servers() {
+    let workspaces = tempfile::tempdir().expect("create namespace root");
+    let _tokens = EnvVarGuard::set(
+        "SANDBOX_AGENT_NAMESPACE_TOKENS",
+        "team-a=token-a,team-b=token-b",
+    );
+    let _root = EnvVarGuard::set_os(
+        "SANDBOX_AGENT_NAMESPACE_ROOT",
+        workspaces.path().as_os_str(),
+    );
+    let test_app = TestApp::with_setup(
+        AuthConfig::with_token("secret-token".to_string()),
+        |install_dir| {
+            setup_stub_artifacts(install_dir, "codex");
+        },
+    );
+    let admin = [("authorization", "Bearer secret-token")];
+    let team_a = [("authorization", "Bearer token-a")];
+    let team_b = [("authorization", "Bearer token-b")];
+
+    for auth in [&team_a, &team_b] {
+        let (status, _, _) = send_request(
+            &test_app.app,
+            Method::POST,
+            "/v1/acp/shared-id?agent=codex",
+            Some(initialize_payload()),
+            auth,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, _, body) =
+        send_request(&test_app.app, Method::GET, "/v1/acp", None, &team_a).await;
+    assert_eq!(status, StatusCode::OK);
+    let servers = parse_json(&body)["servers"].clone();
+    assert_eq!(servers.as_array().map(Vec::len), Some(1));
+    assert_eq!(servers[0]["serverId"], "shared-id");
+
+    let (status, _, body) = send_request(&test_app.app, Method::GET, "/v1/acp", None, &admin).await;
+    assert_eq!(status, StatusCode::OK);
+    let mut ids = parse_json(&body)["servers"]
+        .as_array()
+        .expect("servers")
+        .iter()
+        .filter_map(|server| server["serverId"].as_str().map(str::to_string))
+        .collect::<Vec<_>>();
+    ids.sort();
+    assert_eq!(ids, ["team-a~shared-id", "team-b~shared-id"]);
+
+    let outside_cwd = json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "session/new",
+        "params": { "cwd": "/tmp", "mcpServers": [] }
+    });
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/shared-id",
+        Some(outside_cwd),
+        &team_a,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/fs/entries?path=/",
+        None,
+        &team_a,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::DELETE,
+        "/v1/acp/shared-id",
+        None,
+        &team_b,
+    )
+    .await;
+    assert!(status.is_success());
+    let (status, _, body) =
+        send_request(&test_app.app, Method::GET, "/v1/acp", None, &team_a).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body)["servers"][0]["serverId"], "shared-id");
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp",
+        None,
+        &[("authorization", "Bearer token-c")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[serial]
+async fn namespaces_without_server_token_reject_unauthenticated_requests() {
+    let _tokens = EnvVarGuard::set("SANDBOX_AGENT_NAMESPACE_TOKENS", "team-a=token-a");
+    let test_app = TestApp::new(AuthConfig::disabled());
+    let team_a = [("authorization", "Bearer token-a")];
+
+    for path in ["/v1/health", "/opencode/session", "/ui/"] {
+        let (status, _, _) = send_request(&test_app.app, Method::GET, path, None, &[]).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{path}");
+    }
+
+    let (status, _, _) =
+        send_request(&test_app.app, Method::GET, "/v1/health", None, &team_a).await;
+    assert_eq!(status, StatusCode::OK);
+    for path in ["/opencode/session", "/ui/"] {
+        let (status, _, _) = send_request(&test_app.app, Method::GET, path, None, &team_a).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{path}");
+    }
+}
@@ -0,0 +1,265 @@
+use super::*;
+
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use axum::routing::get;
+
+/// Stand-in for the `opencode` binary. It records the `--port` it was given
+/// and its pid, then idles; the test serves HTTP on that port itself.
+#[cfg(unix)]
+fn write_opencode_sidecar_stub(install_dir: &Path) {
+    write_executable(
+        &install_dir.join("opencode"),
+        r#"#!/usr/bin/env sh
+dir="$(dirname "$0")"
+printf '%s\n' "$3" >> "$dir/opencode.ports"
+printf '%s\n' "$$" >> "$dir/opencode.pids"
+exec sleep 600
+"#,
+    );
+}
+
+/// Lines the sidecar stub appended to `name`, waiting until there are at
+/// least `count` of them.
+#[cfg(unix)]
+async fn wait_for_sidecar_lines(install_dir: &Path, name: &str, count: usize) -> Vec<String> {
+    let path = install_dir.join(name);
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let lines = fs::read_to_string(&path)
+                .unwrap_or_default()
+                .lines()
+                .map(str::to_string)
+                .collect::<Vec<_>>();
+            if lines.len() >= count {
+                return lines;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("sidecar never wrote {name}"))
+}
+
+#[cfg(unix)]
+async fn serve_on_port(port: &str, router: Router) {
+    let listener = tokio::net::TcpListener::bind(format!("127.0.0.1:{port}"))
+        .await
+        .expect("bind sidecar port");
+    tokio::spawn(async move { axum::serve(listener, router).await });
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn opencode_sidecar_health_check_backs_off_until_ready() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), write_opencode_sidecar_stub);
+    let health_checks = Arc::new(Mutex::new(Vec::<Instant>::new()));
+    let sidecar = Router::new()
+        .route(
+            "/health",
+            get({
+                let health_checks = health_checks.clone();
+                move || async move {
+                    let mut checks = health_checks.lock().unwrap();
+                    checks.push(Instant::now());
+                    if checks.len() < 4 {
+                        StatusCode::SERVICE_UNAVAILABLE
+                    } else {
+                        StatusCode::OK
+                    }
+                }
+            }),
+        )
+        .route(
+            "/command",
+            get(|| async { axum::Json(json!([{ "name": "from-sidecar" }])) }),
+        );
+
+    let app = test_app.app.clone();
+    let commands = tokio::spawn(async move {
+        send_request(&app, Method::GET, "/opencode/command", None, &[]).await
+    });
+    let ports = wait_for_sidecar_lines(test_app.install_path(), "opencode.ports", 1).await;
+    serve_on_port(&ports[0], sidecar).await;
+
+    let (status, _, body) = commands.await.expect("command request");
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body), json!([{ "name": "from-sidecar" }]));
+
+    let checks = health_checks.lock().unwrap().clone();
+    assert_eq!(checks.len(), 4, "polling stops once the sidecar is healthy");
+    let gaps = checks
+        .windows(2)
+        .map(|pair| pair[1] - pair[0])
+        .collect::<Vec<_>>();
+    assert!(
+        gaps.iter().all(|gap| *gap >= Duration::from_millis(50)),
+        "{gaps:?}"
+    );
+    assert!(gaps[2] > gaps[0], "delay between polls grows: {gaps:?}");
+}
+
+#[tokio::test]
+#[serial]
+async fn opencode_native_proxy_sends_default_headers() {
+    let seen = Arc::new(Mutex::new(None::<HeaderMap>));
+    let native = Router::new().route(
+        "/command",
+        get({
+            let seen = seen.clone();
+            move |headers: HeaderMap| async move {
+                *seen.lock().unwrap() = Some(headers);
+                axum::Json(json!([]))
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind native opencode");
+    let address = listener.local_addr().expect("native opencode address");
+    tokio::spawn(async move { axum::serve(listener, native).await });
+
+    let _url = EnvVarGuard::set("OPENCODE_COMPAT_PROXY_URL", &format!("http://{address}"));
+    let _headers = EnvVarGuard::set(
+        "OPENCODE_COMPAT_PROXY_HEADERS",
+        r#"{"X-Api-Key": "managed-key", "Cookie": "session=managed"}"#,
+    );
+    let test_app = TestApp::new(AuthConfig::disabled());
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/opencode/command",
+        None,
+        &[
+            ("authorization", "Bearer client-token"),
+            ("cookie", "session=client"),
+        ],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let headers = seen.lock().unwrap().take().expect("request proxied");
+    assert_eq!(headers["x-api-key"], "managed-key");
+    assert_eq!(headers["authorization"], "Bearer client-token");
+    let cookies = headers.get_all("cookie").iter().collect::<Vec<_>>();
+    assert_eq!(cookies, ["session=managed"], "configured headers win");
+
+    let _headers = EnvVarGuard::set("OPENCODE_COMPAT_PROXY_HEADERS", r#"["not", "an object"]"#);
+    let test_app = TestApp::new(AuthConfig::disabled());
+    let (status, _, _) =
+        send_request(&test_app.app, Method::GET, "/opencode/command", None, &[]).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn opencode_sidecar_restarts_after_a_crash_and_announces_it() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), write_opencode_sidecar_stub);
+    let sidecar = |name: &'static str| {
+        Router::new()
+            .route("/health", get(|| async { StatusCode::OK }))
+            .route(
+                "/command",
+                get(move || async move { axum::Json(json!([{ "name": name }])) }),
+            )
+    };
+
+    let app = test_app.app.clone();
+    let commands = tokio::spawn(async move {
+        send_request(&app, Method::GET, "/opencode/command", None, &[]).await
+    });
+    let ports = wait_for_sidecar_lines(test_app.install_path(), "opencode.ports", 1).await;
+    serve_on_port(&ports[0], sidecar("first")).await;
+    let (_, _, body) = commands.await.expect("command request");
+    assert_eq!(parse_json(&body), json!([{ "name": "first" }]));
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri("/opencode/event")
+        .body(Body::empty())
+        .expect("build request");
+    let response = test_app
+        .app
+        .clone()
+        .oneshot(request)
+        .await
+        .expect("event stream");
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut stream = response.into_body().into_data_stream();
+    let mut pending = String::new();
+
+    let pids = wait_for_sidecar_lines(test_app.install_path(), "opencode.pids", 1).await;
+    let killed = std::process::Command::new("kill")
+        .args(["-9", &pids[0]])
+        .status()
+        .expect("kill sidecar");
+    assert!(killed.success());
+
+    let ports = wait_for_sidecar_lines(test_app.install_path(), "opencode.ports", 2).await;
+    serve_on_port(&ports[1], sidecar("second")).await;
+
+    let reconnected = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let event = parse_sse_data(&next_sse_event(&mut stream, &mut pending).await);
+            if event["type"] == "server.connected" && event["properties"]["reconnected"] == true {
+                return event;
+            }
+        }
+    })
+    .await
+    .expect("restart announced");
+    assert_eq!(reconnected["properties"]["restartCount"], 1);
+    assert!(
+        reconnected["properties"]["reason"]
+            .as_str()
+            .unwrap_or_default()
+            .contains("exited"),
+        "{reconnected}"
+    );
+
+    let (_, _, body) =
+        send_request(&test_app.app, Method::GET, "/opencode/command", None, &[]).await;
+    assert_eq!(parse_json(&body), json!([{ "name": "second" }]));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn opencode_sidecar_is_killed_and_reaped_on_shutdown() {
+    let install_dir = tempfile::tempdir().expect("create temp install dir");
+    write_opencode_sidecar_stub(install_dir.path());
+    let manager = AgentManager::new(install_dir.path()).expect("create agent manager");
+    let state = std::sync::Arc::new(AppState::new(AuthConfig::disabled(), manager));
+    let (app, state) = build_router_with_state(state);
+
+    let commands = tokio::spawn(async move {
+        send_request(&app, Method::GET, "/opencode/command", None, &[]).await
+    });
+    let ports = wait_for_sidecar_lines(install_dir.path(), "opencode.ports", 1).await;
+    serve_on_port(
+        &ports[0],
+        Router::new()
+            .route("/health", get(|| async { StatusCode::OK }))
+            .route("/command", get(|| async { axum::Json(json!([])) })),
+    )
+    .await;
+    let (status, _, _) = commands.await.expect("command request");
+    assert_eq!(status, StatusCode::OK);
+
+    let pid = wait_for_sidecar_lines(install_dir.path(), "opencode.pids", 1).await[0].clone();
+    let proc_dir = Path::new("/proc").join(&pid);
+    assert!(proc_dir.exists(), "sidecar running");
+
+    tokio::time::timeout(
+        Duration::from_secs(10),
+        sandbox_agent::router::shutdown_servers(&state),
+    )
+    .await
+    .expect("shutdown finished");
+
+    // A killed but unreaped child would linger as a zombie under /proc.
+    assert!(!proc_dir.exists(), "sidecar {pid} was not reaped");
+}
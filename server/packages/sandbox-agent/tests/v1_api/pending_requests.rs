@@ -0,0 +1,500 @@
+use super::*;
+
+async fn prompt_for_permission(app: &Router, server_id: &str) {
+    let (status, _, _) = send_request(
+        app,
+        Method::POST,
+        &format!("/v1/acp/{server_id}"),
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "session/prompt",
+            "params": {
+                "sessionId": "s-1",
+                "prompt": [{"type": "text", "text": "hello"}]
+            }
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+async fn wait_for_pending_requests(app: &Router, path: &str, count: usize) -> Value {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let requests = list_pending_requests(app, path).await;
+            if requests.as_array().map(Vec::len) == Some(count) {
+                return requests;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting for pending requests")
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn unanswered_agent_requests_are_listed_until_answered() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_permission_stub(install_dir);
+    });
+
+    bootstrap_server(&test_app.app, "server-inbox", "codex").await;
+    prompt_for_permission(&test_app.app, "server-inbox").await;
+
+    let requests =
+        wait_for_pending_requests(&test_app.app, "/v1/acp/server-inbox/requests", 1).await;
+    let request = &requests[0];
+    assert_eq!(request["id"], "perm-1");
+    assert_eq!(request["method"], "session/request_permission");
+    assert_eq!(request["sessionId"], "s-1");
+    assert_eq!(
+        request["payload"]["params"]["toolCall"]["toolCallId"],
+        "call-1"
+    );
+    assert!(request["sequence"].as_u64().is_some());
+    assert!(request["ageMs"].as_u64().is_some());
+    assert!(request.get("expiresAtMs").is_none());
+
+    let other_session =
+        list_pending_requests(&test_app.app, "/v1/acp/server-inbox/requests?sessionId=s-2").await;
+    assert_eq!(other_session, json!([]));
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-inbox",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": "perm-1",
+            "result": { "outcome": { "outcome": "selected", "optionId": "allow" } }
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let requests = list_pending_requests(&test_app.app, "/v1/acp/server-inbox/requests").await;
+    assert_eq!(requests, json!([]));
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/missing/requests",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn expired_permission_requests_are_cancelled() {
+    let _timeout = EnvVarGuard::set("SANDBOX_AGENT_PENDING_REQUEST_TIMEOUT_MS", "200");
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_permission_stub(install_dir);
+    });
+
+    bootstrap_server(&test_app.app, "server-expiry", "codex").await;
+    prompt_for_permission(&test_app.app, "server-expiry").await;
+
+    let requests =
+        wait_for_pending_requests(&test_app.app, "/v1/acp/server-expiry/requests", 1).await;
+    let request = &requests[0];
+    assert!(
+        request["expiresAtMs"].as_i64() > request["receivedAtMs"].as_i64(),
+        "{request}"
+    );
+
+    wait_for_pending_requests(&test_app.app, "/v1/acp/server-expiry/requests", 0).await;
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri("/v1/acp/server-expiry")
+        .header("last-event-id", "0")
+        .body(Body::empty())
+        .expect("build request");
+    let response = test_app
+        .app
+        .clone()
+        .oneshot(request)
+        .await
+        .expect("sse response");
+    let mut stream = response.into_body().into_data_stream();
+    let mut pending = String::new();
+    let (mut cancelled, mut expired) = (false, false);
+    while !(cancelled && expired) {
+        let data = parse_sse_data(&next_sse_event(&mut stream, &mut pending).await);
+        match data["method"].as_str() {
+            Some("server/client_response") => {
+                assert_eq!(data["params"]["line"]["id"], "perm-1");
+                assert_eq!(
+                    data["params"]["line"]["result"]["outcome"]["outcome"],
+                    "cancelled"
+                );
+                cancelled = true;
+            }
+            Some("_adapter/request_expired") => {
+                assert_eq!(data["params"]["requestId"], "perm-1");
+                assert_eq!(data["params"]["sessionId"], "s-1");
+                expired = true;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn unattended_session_rejects_expired_permission_requests() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_permission_stub(install_dir);
+    });
+
+    bootstrap_server(&test_app.app, "server-unattended", "codex").await;
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-unattended",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "session/new",
+            "params": {
+                "cwd": "/tmp",
+                "mcpServers": [],
+                "_meta": {
+                    "sandboxagent.dev": {
+                        "unattended": {
+                            "requestTimeoutMs": 200,
+                            "defaultPermissionReply": "reject"
+                        }
+                    }
+                }
+            }
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    prompt_for_permission(&test_app.app, "server-unattended").await;
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri("/v1/acp/server-unattended")
+        .header("last-event-id", "0")
+        .body(Body::empty())
+        .expect("build request");
+    let response = test_app
+        .app
+        .clone()
+        .oneshot(request)
+        .await
+        .expect("sse response");
+    let mut stream = response.into_body().into_data_stream();
+    let mut pending = String::new();
+    let expired = loop {
+        let data = parse_sse_data(&next_sse_event(&mut stream, &mut pending).await);
+        if data["method"] == "_adapter/request_expired" {
+            break data;
+        }
+    };
+    assert_eq!(expired["params"]["requestId"], "perm-1");
+    assert_eq!(
+        expired["params"]["response"]["result"]["outcome"],
+        json!({ "outcome": "selected", "optionId": "deny" })
+    );
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-unattended",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "session/new",
+            "params": {
+                "cwd": "/tmp",
+                "mcpServers": [],
+                "_meta": {
+                    "sandboxagent.dev": {
+                        "unattended": { "defaultPermissionReply": "maybe" }
+                    }
+                }
+            }
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn opencode_tool_lists_answer_permissions_and_reach_agent_settings() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+log="$(dirname "$0")/codex.log"
+printf 'args: %s\n' "$*" >> "$log"
+while IFS= read -r line; do
+  printf '%s\n' "$line" >> "$log"
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/new) printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-1"}}\n' "$id" ;;
+    session/prompt)
+      printf '{"jsonrpc":"2.0","id":"perm-bash","method":"session/request_permission","params":{"sessionId":"s-1","toolCall":{"toolCallId":"call-1","title":"Bash"},"options":[{"optionId":"allow","name":"Allow","kind":"allow_once"},{"optionId":"deny","name":"Deny","kind":"reject_once"}]}}\n'
+      printf '{"jsonrpc":"2.0","id":"perm-edit","method":"session/request_permission","params":{"sessionId":"s-1","toolCall":{"toolCallId":"call-2","title":"Edit"},"options":[{"optionId":"allow","name":"Allow","kind":"allow_once"},{"optionId":"deny","name":"Deny","kind":"reject_once"}]}}\n'
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"end_turn"}}\n' "$id" ;;
+    "") ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/opencode/session",
+        Some(json!({ "tools": { "allow": ["bash"], "deny": ["edit"] } })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let session_id = parse_json(&body)["id"]
+        .as_str()
+        .expect("session id")
+        .to_string();
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        &format!("/opencode/session/{session_id}/prompt_async"),
+        Some(json!({
+            "model": { "providerID": "codex", "modelID": "gpt-5" },
+            "parts": [{ "type": "text", "text": "hello" }]
+        })),
+        &[],
+    )
+    .await;
+    assert!(status.is_success(), "prompt failed: {status}");
+
+    let log_path = test_app
+        .install_dir
+        .path()
+        .join("agent_processes/codex.log");
+    let log = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let log = fs::read_to_string(&log_path).unwrap_or_default();
+            if log.contains("\"perm-bash\"") && log.contains("\"perm-edit\"") {
+                return log;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting for permission replies");
+
+    // Codex enforces the lists itself through its sandbox and approval policy.
+    assert!(
+        log.starts_with(r#"args: -c sandbox_mode="read-only" -c approval_policy="never""#),
+        "{log}"
+    );
+    let reply = |id: &str| {
+        log.lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap_or(Value::Null))
+            .find(|line| line["id"] == id && line.get("result").is_some())
+            .unwrap_or_else(|| panic!("no reply to {id} in {log}"))
+    };
+    assert_eq!(
+        reply("perm-bash")["result"]["selectedOption"]["kind"],
+        "allow_once"
+    );
+    assert_eq!(
+        reply("perm-edit")["result"]["selectedOption"]["kind"],
+        "reject_once"
+    );
+}
+
+#[tokio::test]
+async fn opencode_tool_lists_reject_what_the_agent_cannot_enforce() {
+    let test_app = TestApp::new(AuthConfig::disabled());
+
+    let (status, headers, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/opencode/session",
+        Some(json!({ "tools": { "allow": ["bash"], "deny": ["bash"] } })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok()),
+        Some("application/problem+json")
+    );
+    let problem = parse_json(&body);
+    assert_eq!(
+        problem["type"],
+        "urn:sandbox-agent:error:mode_not_supported"
+    );
+    assert_eq!(problem["code"], "mode_not_supported");
+
+    for (tools, agent) in [
+        (json!({ "deny": ["bash"] }), "codex"),
+        (json!({ "allow": ["read"] }), "opencode"),
+        (json!({ "allow": ["bash"] }), "amp"),
+    ] {
+        let (status, _, body) = send_request(
+            &test_app.app,
+            Method::POST,
+            "/opencode/session",
+            Some(json!({ "tools": tools })),
+            &[],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let session_id = parse_json(&body)["id"]
+            .as_str()
+            .expect("session id")
+            .to_string();
+
+        let (status, _, body) = send_request(
+            &test_app.app,
+            Method::POST,
+            &format!("/opencode/session/{session_id}/message"),
+            Some(json!({
+                "agent": agent,
+                "parts": [{ "type": "text", "text": "hello" }]
+            })),
+            &[],
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{agent} {tools}");
+        assert_eq!(parse_json(&body)["code"], "mode_not_supported");
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn opencode_tool_calls_keep_one_part_per_call() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+update() {
+  printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":%s}}\n' "$1"
+}
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/new) printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-1"}}\n' "$id" ;;
+    session/prompt)
+      update '{"sessionUpdate":"tool_call","toolCallId":"call-1","title":"Read file","status":"pending","rawInput":{"path":"notes.md"}}'
+      update '{"sessionUpdate":"tool_call_update","toolCallId":"call-1","status":"in_progress"}'
+      update '{"sessionUpdate":"tool_call_update","toolCallId":"call-1","status":"in_progress"}'
+      update '{"sessionUpdate":"tool_call_update","toolCallId":"call-1","status":"completed","content":[{"type":"content","content":{"type":"text","text":"file body"}}]}'
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"end_turn"}}\n' "$id" ;;
+    "") ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/opencode/session",
+        Some(json!({})),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let session_id = parse_json(&body)["id"]
+        .as_str()
+        .expect("session id")
+        .to_string();
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri("/opencode/event")
+        .body(Body::empty())
+        .expect("build request");
+    let response = test_app
+        .app
+        .clone()
+        .oneshot(request)
+        .await
+        .expect("event stream");
+    let mut stream = response.into_body().into_data_stream();
+    let mut pending = String::new();
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        &format!("/opencode/session/{session_id}/prompt_async"),
+        Some(json!({
+            "model": { "providerID": "codex", "modelID": "gpt-5" },
+            "parts": [{ "type": "text", "text": "read notes.md" }]
+        })),
+        &[],
+    )
+    .await;
+    assert!(status.is_success(), "prompt failed: {status}");
+
+    let mut tool_parts = Vec::new();
+    while tool_parts
+        .last()
+        .map(|part: &Value| part["state"]["status"] != "completed")
+        .unwrap_or(true)
+    {
+        let event = parse_sse_data(&next_sse_event(&mut stream, &mut pending).await);
+        if event["type"] == "message.part.updated" && event["properties"]["part"]["type"] == "tool"
+        {
+            tool_parts.push(event["properties"]["part"].clone());
+        }
+    }
+
+    let statuses = tool_parts
+        .iter()
+        .map(|part| part["state"]["status"].as_str().unwrap_or_default())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        statuses,
+        ["pending", "running", "completed"],
+        "repeated updates are dropped"
+    );
+    assert!(
+        tool_parts
+            .iter()
+            .all(|part| part["id"] == tool_parts[0]["id"]),
+        "every update targets the same part: {tool_parts:?}"
+    );
+    let completed = tool_parts.last().expect("completed part");
+    assert_eq!(completed["callID"], "call-1");
+    assert_eq!(completed["tool"], "Read file");
+    assert_eq!(completed["state"]["input"], json!({ "path": "notes.md" }));
+    assert_eq!(completed["state"]["output"], "file body");
+    assert!(completed["state"]["time"]["end"].is_number());
+}
@@ -0,0 +1,1734 @@
+use super::*;
+
+#[cfg(unix)]
+#[tokio::test]
+async fn plan_mode_captures_the_plan_and_builds_once_approved() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/new) printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-1"}}\n' "$id" ;;
+    session/prompt)
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"plan","entries":[{"content":"Read the code","priority":"high","status":"pending"},{"content":"Write tests","priority":"medium","status":"pending"}]}}}\n'
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"tool_call","toolCallId":"t-1","title":"Edit main.rs","kind":"edit","status":"completed"}}}\n'
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"tool_call_update","toolCallId":"t-1","kind":"edit","status":"completed"}}}\n'
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"end_turn"}}\n' "$id" ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+    bootstrap_server(&test_app.app, "server-plan", "codex").await;
+
+    let post = |payload: Value| {
+        send_request(
+            &test_app.app,
+            Method::POST,
+            "/v1/acp/server-plan",
+            Some(payload),
+            &[],
+        )
+    };
+    let (status, _, _) = post(json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "session/new",
+        "params": { "cwd": "/tmp", "mcpServers": [] }
+    }))
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let prompt = |id: u64| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "session/prompt",
+            "params": { "sessionId": "s-1", "prompt": [{ "type": "text", "text": "plan it" }] }
+        })
+    };
+
+    // Not in plan mode yet: nothing is captured.
+    let (status, _, _) = post(prompt(3)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-plan/sessions/s-1/plan",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _, _) = post(json!({
+        "jsonrpc": "2.0",
+        "id": 4,
+        "method": "session/set_mode",
+        "params": { "sessionId": "s-1", "modeId": "plan" }
+    }))
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _, _) = post(prompt(5)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-plan/sessions/s-1/plan",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let plan = parse_json(&body);
+    assert_eq!(plan["sessionId"], "s-1");
+    assert_eq!(plan["source"], "plan_update");
+    assert_eq!(plan["text"], "- Read the code\n- Write tests");
+    assert_eq!(plan["entries"].as_array().map(Vec::len), Some(2));
+    assert_eq!(plan["readOnly"], false);
+    assert_eq!(
+        plan["violations"],
+        json!([{ "toolCallId": "t-1", "kind": "edit", "title": "Edit main.rs" }])
+    );
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-plan/events",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let events = parse_json(&body);
+    let notifications = events["events"]
+        .as_array()
+        .expect("events")
+        .iter()
+        .filter(|event| event["payload"]["method"] == "_adapter/plan")
+        .collect::<Vec<_>>();
+    assert_eq!(notifications.len(), 1);
+    assert_eq!(notifications[0]["payload"]["params"]["readOnly"], false);
+
+    let approve = |body: Value| {
+        send_request(
+            &test_app.app,
+            Method::POST,
+            "/v1/acp/server-plan/sessions/s-1/plan/approve",
+            Some(body),
+            &[],
+        )
+    };
+    let (status, _, _) = approve(json!({ "modeId": "plan" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _, body) = approve(json!({ "note": "Skip the tests" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let approved = parse_json(&body);
+    assert_eq!(approved["approval"]["modeId"], "auto");
+    assert_eq!(approved["approval"]["note"], "Skip the tests");
+    assert_eq!(approved["stopReason"], "end_turn");
+
+    // The build turn ran outside plan mode, so the approved plan is kept.
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-plan/sessions/s-1/plan",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body)["approval"]["modeId"], "auto");
+
+    let (status, _, _) = approve(json!({})).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn subagent_tool_calls_are_listed_as_child_sessions() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/new) printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-1"}}\n' "$id" ;;
+    session/prompt)
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"tool_call","toolCallId":"task-1","title":"Task","kind":"think","status":"pending","rawInput":{"description":"Find the tests","prompt":"find them","subagent_type":"Explore"},"_meta":{"claudeCode":{"toolName":"Task"}}}}}\n'
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"tool_call","toolCallId":"read-1","title":"Read lib.rs","kind":"read","status":"completed","_meta":{"claudeCode":{"toolName":"Read","parentToolUseId":"task-1"}}}}}\n'
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"tool_call","toolCallId":"task-2","title":"Task","kind":"think","status":"completed","rawInput":{"subagent_type":"general"},"_meta":{"claudeCode":{"toolName":"Task","parentToolUseId":"task-1"}}}}}\n'
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"tool_call_update","toolCallId":"task-1","status":"completed"}}}\n'
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"end_turn"}}\n' "$id" ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+    bootstrap_server(&test_app.app, "server-children", "codex").await;
+
+    for payload in [
+        json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "session/new",
+            "params": { "cwd": "/tmp", "mcpServers": [] }
+        }),
+        json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "session/prompt",
+            "params": { "sessionId": "s-1", "prompt": [{ "type": "text", "text": "find the tests" }] }
+        }),
+    ] {
+        let (status, _, _) = send_request(
+            &test_app.app,
+            Method::POST,
+            "/v1/acp/server-children",
+            Some(payload),
+            &[],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-children/sessions/s-1/children",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let children = parse_json(&body);
+    assert_eq!(children["sessionId"], "s-1");
+    let children = children["childSessions"].as_array().expect("childSessions");
+    assert_eq!(children.len(), 2);
+    assert_eq!(children[0]["id"], "task-1");
+    assert_eq!(children[0]["agent"], "Explore");
+    assert_eq!(children[0]["description"], "Find the tests");
+    assert_eq!(children[0]["status"], "completed");
+    assert_eq!(children[0]["eventCount"], 2);
+    assert!(children[0].get("parentId").is_none());
+    assert_eq!(children[1]["id"], "task-2");
+    assert_eq!(children[1]["parentId"], "task-1");
+    assert_eq!(children[1]["agent"], "general");
+    assert_eq!(children[1]["eventCount"], 0);
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-children/events",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let events = parse_json(&body);
+    let nested = events["events"]
+        .as_array()
+        .expect("events")
+        .iter()
+        .filter(|event| event["parentId"] == "task-1")
+        .map(|event| event["payload"]["params"]["update"]["toolCallId"].clone())
+        .collect::<Vec<_>>();
+    assert_eq!(nested, vec![json!("read-1"), json!("task-2")]);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn retry_retracts_the_last_turn_and_resends_its_prompt() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/new) printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-1"}}\n' "$id" ;;
+    session/prompt)
+      text=$(printf '%s\n' "$line" | sed -n 's/.*"text"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"echo: %s"}}}}\n' "$text"
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"end_turn"}}\n' "$id" ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+    bootstrap_server(&test_app.app, "server-retry", "codex").await;
+
+    let retry = |body: Value| {
+        send_request(
+            &test_app.app,
+            Method::POST,
+            "/v1/acp/server-retry/sessions/s-1/retry",
+            Some(body),
+            &[],
+        )
+    };
+    let (status, _, _) = retry(json!({})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    for payload in [
+        json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "session/new",
+            "params": { "cwd": "/tmp", "mcpServers": [] }
+        }),
+        json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "session/prompt",
+            "params": { "sessionId": "s-1", "prompt": [{ "type": "text", "text": "first" }] }
+        }),
+    ] {
+        let (status, _, _) = send_request(
+            &test_app.app,
+            Method::POST,
+            "/v1/acp/server-retry",
+            Some(payload),
+            &[],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, _, _) = retry(json!({ "text": "  " })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _, body) = retry(json!({ "text": "second" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let retried = parse_json(&body);
+    assert_eq!(retried["sessionId"], "s-1");
+    assert_eq!(retried["stopReason"], "end_turn");
+    assert_eq!(retried["retracted"]["turn"], 1);
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-retry/events",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let events = parse_json(&body);
+    let events = events["events"].as_array().expect("events");
+    let sequence_of = |predicate: &dyn Fn(&Value) -> bool| {
+        events
+            .iter()
+            .find(|event| predicate(&event["payload"]))
+            .and_then(|event| event["sequence"].as_u64())
+            .expect("event")
+    };
+    let first_started = sequence_of(&|payload| {
+        payload["method"] == "_adapter/turn_started" && payload["params"]["turn"] == 1
+    });
+    let first_reply =
+        sequence_of(&|payload| payload["params"]["update"]["content"]["text"] == "echo: first");
+    let retracted = sequence_of(&|payload| payload["method"] == "_adapter/turn_retracted");
+    let second_reply =
+        sequence_of(&|payload| payload["params"]["update"]["content"]["text"] == "echo: second");
+    assert_eq!(retried["retracted"]["firstSequence"], first_started);
+    let last_sequence = retried["retracted"]["lastSequence"]
+        .as_u64()
+        .expect("lastSequence");
+    assert!((first_started..=last_sequence).contains(&first_reply));
+    assert!(last_sequence < retracted && retracted < second_reply);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn queued_messages_are_sent_after_the_current_turn() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/new) printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-1"}}\n' "$id" ;;
+    session/prompt)
+      text=$(printf '%s\n' "$line" | sed -n 's/.*"text"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+      if [ "$text" = "slow" ]; then sleep 1; fi
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"echo: %s"}}}}\n' "$text"
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"end_turn"}}\n' "$id" ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+    bootstrap_server(&test_app.app, "server-queue", "codex").await;
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-queue",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "session/new",
+            "params": { "cwd": "/tmp", "mcpServers": [] }
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let messages_path = "/v1/acp/server-queue/sessions/s-1/messages";
+    let queue = |text: &str| {
+        send_request(
+            &test_app.app,
+            Method::POST,
+            messages_path,
+            Some(json!({
+                "prompt": [{ "type": "text", "text": text }],
+                "when": "after_current_turn"
+            })),
+            &[],
+        )
+    };
+    let (slow, ()) = tokio::join!(
+        send_request(
+            &test_app.app,
+            Method::POST,
+            "/v1/acp/server-queue",
+            Some(json!({
+                "jsonrpc": "2.0",
+                "id": 3,
+                "method": "session/prompt",
+                "params": { "sessionId": "s-1", "prompt": [{ "type": "text", "text": "slow" }] }
+            })),
+            &[],
+        ),
+        async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            let (status, _, body) = queue("one").await;
+            assert_eq!(status, StatusCode::CREATED);
+            let one = parse_json(&body);
+            assert_eq!(one["status"], "queued");
+            assert_eq!(
+                one["requestId"],
+                format!("sandboxagent-message-{}", one["id"].as_str().unwrap())
+            );
+            let (status, _, body) = queue("two").await;
+            assert_eq!(status, StatusCode::CREATED);
+            let two = parse_json(&body);
+
+            let (status, _, body) =
+                send_request(&test_app.app, Method::GET, messages_path, None, &[]).await;
+            assert_eq!(status, StatusCode::OK);
+            let listed = parse_json(&body);
+            let ids = listed["messages"]
+                .as_array()
+                .expect("messages")
+                .iter()
+                .map(|message| message["id"].clone())
+                .collect::<Vec<_>>();
+            assert_eq!(ids, vec![one["id"].clone(), two["id"].clone()]);
+
+            let (status, _, _) = send_request(
+                &test_app.app,
+                Method::DELETE,
+                &format!("{messages_path}/{}", two["id"].as_str().unwrap()),
+                None,
+                &[],
+            )
+            .await;
+            assert_eq!(status, StatusCode::NO_CONTENT);
+            let (status, _, _) = send_request(
+                &test_app.app,
+                Method::DELETE,
+                &format!("{messages_path}/msg-missing"),
+                None,
+                &[],
+            )
+            .await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+    );
+    assert_eq!(slow.0, StatusCode::OK);
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        messages_path,
+        Some(json!({ "prompt": [{ "type": "text", "text": "x" }], "when": "now" })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let mut drained = false;
+    for _ in 0..50 {
+        let (_, _, body) = send_request(&test_app.app, Method::GET, messages_path, None, &[]).await;
+        if parse_json(&body)["messages"] == json!([]) {
+            drained = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(drained, "queued message was never sent");
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-queue/events",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let events = parse_json(&body);
+    let replies = events["events"]
+        .as_array()
+        .expect("events")
+        .iter()
+        .filter_map(|event| event["payload"]["params"]["update"]["content"]["text"].as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(replies, vec!["echo: slow", "echo: one"]);
+    let queued_turn = events["events"]
+        .as_array()
+        .expect("events")
+        .iter()
+        .find(|event| {
+            event["payload"]["method"] == "_adapter/turn_completed"
+                && event["payload"]["params"]["requestId"] == "sandboxagent-message-msg-1"
+        });
+    assert!(queued_turn.is_some());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn interrupt_cancels_the_turn_and_kills_agents_that_ignore_it() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+pending=""
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/new) printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-1"}}\n' "$id" ;;
+    session/prompt)
+      text=$(printf '%s\n' "$line" | sed -n 's/.*"text"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+      if [ "$text" = "wait" ]; then pending="$id"; fi ;;
+    session/cancel)
+      if [ -n "$pending" ]; then
+        printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"cancelled"}}\n' "$pending"
+        pending=""
+      fi ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+    bootstrap_server(&test_app.app, "server-interrupt", "codex").await;
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-interrupt",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "session/new",
+            "params": { "cwd": "/tmp", "mcpServers": [] }
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let interrupt = || {
+        send_request(
+            &test_app.app,
+            Method::POST,
+            "/v1/acp/server-interrupt/sessions/s-1/interrupt",
+            None,
+            &[],
+        )
+    };
+    let prompt = |id: u64, text: &str| {
+        send_request(
+            &test_app.app,
+            Method::POST,
+            "/v1/acp/server-interrupt",
+            Some(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "session/prompt",
+                "params": { "sessionId": "s-1", "prompt": [{ "type": "text", "text": text }] }
+            })),
+            &[],
+        )
+    };
+
+    let (status, _, body) = interrupt().await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body)["outcome"], "idle");
+
+    let (turn, (status, _, body)) = tokio::join!(prompt(3, "wait"), async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        interrupt().await
+    });
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body)["outcome"], "cancelled");
+    assert_eq!(turn.0, StatusCode::OK);
+    assert_eq!(parse_json(&turn.2)["result"]["stopReason"], "cancelled");
+
+    // An agent that ignores session/cancel is killed once the grace period ends.
+    let (turn, (status, _, body)) = tokio::join!(prompt(4, "ignore"), async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        interrupt().await
+    });
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body)["outcome"], "killed");
+    assert_ne!(turn.0, StatusCode::OK);
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-interrupt/sessions/s-1/children",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn shutdown_cancels_prompts_in_flight_and_stops_agent_processes() {
+    let install_dir = tempfile::tempdir().expect("create temp install dir");
+    setup_stub_artifacts(install_dir.path(), "codex");
+    write_executable(
+        &install_dir.path().join("agent_processes/codex-acp"),
+        r#"#!/usr/bin/env sh
+printf '%s\n' "$$" > "$(dirname "$0")/codex.pid"
+pending=""
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/new) printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-1"}}\n' "$id" ;;
+    session/prompt) pending="$id" ;;
+    session/cancel)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"cancelled"}}\n' "$pending" ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+    );
+    let manager = AgentManager::new(install_dir.path()).expect("create agent manager");
+    let state = std::sync::Arc::new(AppState::new(AuthConfig::disabled(), manager));
+    let (app, state) = build_router_with_state(state);
+
+    bootstrap_server(&app, "server-shutdown", "codex").await;
+    let (status, _, _) = send_request(
+        &app,
+        Method::POST,
+        "/v1/acp/server-shutdown",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "session/new",
+            "params": { "cwd": "/tmp", "mcpServers": [] }
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let pid = fs::read_to_string(install_dir.path().join("agent_processes/codex.pid"))
+        .expect("agent pid")
+        .trim()
+        .to_string();
+    let proc_dir = Path::new("/proc").join(&pid);
+    assert!(proc_dir.exists(), "agent running");
+
+    let (turn, ()) = tokio::join!(
+        send_request(
+            &app,
+            Method::POST,
+            "/v1/acp/server-shutdown",
+            Some(json!({
+                "jsonrpc": "2.0",
+                "id": 3,
+                "method": "session/prompt",
+                "params": { "sessionId": "s-1", "prompt": [{ "type": "text", "text": "wait" }] }
+            })),
+            &[],
+        ),
+        async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            tokio::time::timeout(
+                Duration::from_secs(10),
+                sandbox_agent::router::shutdown_servers(&state),
+            )
+            .await
+            .expect("shutdown finished");
+        }
+    );
+
+    // The prompt in flight is answered rather than dropped.
+    assert_eq!(turn.0, StatusCode::OK);
+    assert_eq!(parse_json(&turn.2)["result"]["stopReason"], "cancelled");
+
+    let (_, _, body) = send_request(&app, Method::GET, "/v1/acp", None, &[]).await;
+    assert_eq!(parse_json(&body)["servers"], json!([]));
+    assert!(!proc_dir.exists(), "agent {pid} was not reaped");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn prompt_turn_is_bracketed_by_lifecycle_notifications() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+    });
+
+    bootstrap_server(&test_app.app, "server-lifecycle", "codex").await;
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-lifecycle",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "method": "session/prompt",
+            "params": {
+                "sessionId": "s-1",
+                "prompt": [{"type": "text", "text": "hello"}]
+            }
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri("/v1/acp/server-lifecycle")
+        .header("last-event-id", "0")
+        .body(Body::empty())
+        .expect("build request");
+    let response = test_app
+        .app
+        .clone()
+        .oneshot(request)
+        .await
+        .expect("sse response");
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut stream = response.into_body().into_data_stream();
+    let mut pending = String::new();
+
+    let mut methods = Vec::new();
+    loop {
+        let event = parse_sse_data(&next_sse_event(&mut stream, &mut pending).await);
+        let method = event["method"].as_str().unwrap_or_default().to_string();
+        if method.starts_with("_adapter/turn_") && method != "_adapter/turn_timing" {
+            assert_eq!(event["params"]["sessionId"], "s-1");
+            assert_eq!(event["params"]["requestId"], 7);
+        }
+        methods.push(method.clone());
+        if method == "_adapter/turn_completed" {
+            break;
+        }
+    }
+    let started = methods
+        .iter()
+        .position(|method| method == "_adapter/turn_started")
+        .expect("turn_started emitted");
+    let timing = methods
+        .iter()
+        .position(|method| method == "_adapter/turn_timing")
+        .expect("turn_timing emitted");
+    assert!(started < timing, "{methods:?}");
+    assert!(!methods
+        .iter()
+        .any(|method| method == "_adapter/turn_failed"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn idle_turn_is_cancelled_after_session_timeout() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/new) printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-1"}}\n' "$id" ;;
+    session/prompt) prompt_id="$id" ;;
+    session/cancel) printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"cancelled"}}\n' "$prompt_id" ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+
+    bootstrap_server(&test_app.app, "server-idle", "codex").await;
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-idle",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "session/new",
+            "params": {
+                "cwd": "/",
+                "mcpServers": [],
+                "_meta": { "sandboxagent.dev": { "idleTimeoutMs": 200 } }
+            }
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-idle",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "session/prompt",
+            "params": {
+                "sessionId": "s-1",
+                "prompt": [{"type": "text", "text": "hello"}]
+            }
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body)["result"]["stopReason"], "cancelled");
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-idle/events?offset=0",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let events = parse_json(&body)["events"].clone();
+    let timeout = events
+        .as_array()
+        .expect("events")
+        .iter()
+        .find(|event| event["payload"]["method"] == "_adapter/turn_timeout")
+        .expect("turn timeout notification");
+    assert_eq!(timeout["payload"]["params"]["sessionId"], "s-1");
+    assert_eq!(timeout["payload"]["params"]["reason"], "idle_timeout");
+    assert_eq!(timeout["payload"]["params"]["limitMs"], 200);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn compaction_uses_native_command_or_a_summary_turn() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+dir=$(dirname "$0")
+while IFS= read -r line; do
+  printf '%s\n' "$line" >> "$dir/stdin.log"
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  sid=$(printf '%s\n' "$line" | sed -n 's/.*"sessionId"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  case "$line" in
+    *'"session/new"'*)
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-2","update":{"sessionUpdate":"available_commands_update","availableCommands":[{"name":"compact","description":"Compact context"}]}}}\n'
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-2"}}\n' "$id"
+      ;;
+    *'"session/prompt"'*)
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"%s","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"0123456789abcdef"}}}}\n' "$sid"
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"end_turn"}}\n' "$id"
+      ;;
+    *)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id"
+      ;;
+  esac
+done
+"#,
+        );
+    });
+    let compact = |server_id: &'static str, session_id: &'static str| {
+        let app = test_app.app.clone();
+        async move {
+            send_request(
+                &app,
+                Method::POST,
+                &format!("/v1/acp/{server_id}/compact"),
+                Some(json!({ "sessionId": session_id })),
+                &[],
+            )
+            .await
+        }
+    };
+
+    let (status, _, _) = compact("server-missing", "s-1").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    bootstrap_server(&test_app.app, "server-compact", "codex").await;
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-compact",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "session/prompt",
+            "params": {"sessionId": "s-1", "prompt": [{"type": "text", "text": "hi"}]}
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // No `compact` command advertised: the agent is asked for a summary.
+    let (status, _, body) = compact("server-compact", "s-1").await;
+    assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+    let parsed = parse_json(&body);
+    assert_eq!(parsed["mode"], "summary");
+    assert_eq!(parsed["beforeTokens"], 4);
+    assert_eq!(parsed["afterTokens"], 4);
+    assert_eq!(parsed["summary"], "0123456789abcdef");
+    assert_eq!(parsed["stopReason"], "end_turn");
+
+    // Only text since the last compaction counts.
+    let (_, _, body) = compact("server-compact", "s-1").await;
+    assert_eq!(parse_json(&body)["beforeTokens"], 0);
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-compact",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "session/new",
+            "params": {"cwd": "/tmp", "mcpServers": []}
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _, body) = compact("server-compact", "s-2").await;
+    assert_eq!(status, StatusCode::OK);
+    let parsed = parse_json(&body);
+    assert_eq!(parsed["mode"], "native");
+    assert!(parsed.get("summary").is_none());
+
+    let stdin = fs::read_to_string(
+        test_app
+            .install_path()
+            .join("agent_processes")
+            .join("stdin.log"),
+    )
+    .expect("read stdin log");
+    assert!(stdin.contains(r#""text":"/compact""#));
+
+    let (_, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-compact/events?offset=0",
+        None,
+        &[],
+    )
+    .await;
+    let compactions = parse_json(&body)["events"]
+        .as_array()
+        .expect("events")
+        .iter()
+        .filter(|event| event["payload"]["method"] == "_adapter/compaction")
+        .map(|event| event["payload"]["params"].clone())
+        .collect::<Vec<_>>();
+    assert_eq!(compactions.len(), 3);
+    assert_eq!(compactions[0]["sessionId"], "s-1");
+    assert_eq!(compactions[0]["mode"], "summary");
+    assert_eq!(compactions[2]["sessionId"], "s-2");
+    assert_eq!(compactions[2]["mode"], "native");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn session_transcript_merges_streamed_chunks() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/new) printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-1"}}\n' "$id" ;;
+    session/prompt)
+      text=$(printf '%s\n' "$line" | sed -n 's/.*"text"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"Looking at "}}}}\n'
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"%s."}}}}\n' "$text"
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"tool_call","toolCallId":"t-1","title":"Read","status":"pending"}}}\n'
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"agent_thought_chunk","content":{"type":"text","text":"hmm"}}}}\n'
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"Done."}}}}\n'
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"end_turn"}}\n' "$id" ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+
+    bootstrap_server(&test_app.app, "server-transcript", "codex").await;
+    let mut requests = vec![(2, "session/new", json!({"cwd": "/tmp", "mcpServers": []}))];
+    for (id, text) in [(3, "main.rs"), (4, "lib.rs")] {
+        requests.push((
+            id,
+            "session/prompt",
+            json!({"sessionId": "s-1", "prompt": [{"type": "text", "text": text}]}),
+        ));
+    }
+    for (id, method, params) in requests {
+        let (status, _, _) = send_request(
+            &test_app.app,
+            Method::POST,
+            "/v1/acp/server-transcript",
+            Some(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})),
+            &[],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-transcript/sessions/s-1/transcript",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let parsed = parse_json(&body);
+    assert_eq!(parsed["sessionId"], "s-1");
+    let messages = parsed["messages"].as_array().expect("messages");
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0]["role"], "assistant");
+    assert_eq!(messages[0]["text"], "Looking at main.rs.\n\nDone.");
+    assert_eq!(messages[1]["text"], "Looking at lib.rs.\n\nDone.");
+    assert!(messages[0]["turn"].as_u64() < messages[1]["turn"].as_u64());
+
+    let (status, headers, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-transcript/sessions/s-1/transcript?format=text",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        headers.get(header::CONTENT_TYPE).unwrap(),
+        "text/plain; charset=utf-8"
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&body),
+        "Looking at main.rs.\n\nDone.\n\nLooking at lib.rs.\n\nDone.\n"
+    );
+
+    let (_, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-transcript/sessions/s-1/transcript?format=markdown",
+        None,
+        &[],
+    )
+    .await;
+    assert!(String::from_utf8_lossy(&body).starts_with("## Assistant\n\nLooking at main.rs."));
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-transcript/sessions/s-1/transcript?format=html",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-transcript/sessions/s-2/transcript",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body)["messages"], json!([]));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn session_result_waits_for_turn_and_summarizes_it() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/new) printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-1"}}\n' "$id" ;;
+    session/prompt)
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"tool_call","toolCallId":"t-1","title":"Edit","kind":"edit","status":"pending","locations":[{"path":"/work/src/main.rs"}]}}}\n'
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"tool_call_update","toolCallId":"t-1","kind":"edit","status":"completed","content":[{"type":"diff","path":"/work/src/lib.rs","oldText":"a","newText":"b"}]}}}\n'
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"tool_call","toolCallId":"t-2","title":"Read","kind":"read","status":"completed","locations":[{"path":"/work/README.md"}]}}}\n'
+      sleep 1
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"Fixed "}}}}\n'
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"the bug."}}}}\n'
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"end_turn","usage":{"inputTokens":12,"outputTokens":34,"totalTokens":46}}}\n' "$id" ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+
+    bootstrap_server(&test_app.app, "server-result", "codex").await;
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-result",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "session/new",
+            "params": {"cwd": "/tmp", "mcpServers": []}
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-result/sessions/s-1/result",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let app = test_app.app.clone();
+    let prompt = tokio::spawn(async move {
+        send_request(
+            &app,
+            Method::POST,
+            "/v1/acp/server-result",
+            Some(json!({
+                "jsonrpc": "2.0",
+                "id": 3,
+                "method": "session/prompt",
+                "params": {"sessionId": "s-1", "prompt": [{"type": "text", "text": "fix it"}]}
+            })),
+            &[],
+        )
+        .await
+    });
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-result/sessions/s-1/result?timeoutMs=50",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-result/sessions/s-1/result?timeoutMs=10000",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (prompt_status, _, _) = prompt.await.expect("prompt task");
+    assert_eq!(prompt_status, StatusCode::OK);
+
+    let parsed = parse_json(&body);
+    assert_eq!(parsed["sessionId"], "s-1");
+    assert_eq!(parsed["status"], "completed");
+    assert_eq!(parsed["stopReason"], "end_turn");
+    assert_eq!(parsed["message"], "Fixed the bug.");
+    assert_eq!(parsed["usage"]["inputTokens"], 12);
+    assert_eq!(parsed["usage"]["totalTokens"], 46);
+    assert_eq!(
+        parsed["changedFiles"],
+        json!([
+            {"path": "/work/src/main.rs", "kind": "edit"},
+            {"path": "/work/src/lib.rs", "kind": "edit"}
+        ])
+    );
+    assert!(parsed.get("error").is_none());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn session_ended_reports_agent_crash() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/new) printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-1"}}\n' "$id" ;;
+    session/prompt) echo "boom" >&2; exit 3 ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+
+    bootstrap_server(&test_app.app, "server-ended", "codex").await;
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-ended",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "session/new",
+            "params": {"cwd": "/tmp", "mcpServers": []}
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-ended/sessions/s-1",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let open = parse_json(&body);
+    assert_eq!(open["serverId"], "server-ended");
+    assert_eq!(open["agent"], "codex");
+    assert_eq!(open["promptInFlight"], false);
+    assert!(open.get("ended").is_none());
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-ended/sessions/s-2",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-ended",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "session/prompt",
+            "params": {"sessionId": "s-1", "prompt": [{"type": "text", "text": "hi"}]}
+        })),
+        &[],
+    )
+    .await;
+    assert!(!status.is_success());
+
+    let mut ended = Value::Null;
+    for _ in 0..50 {
+        let (_, _, body) = send_request(
+            &test_app.app,
+            Method::GET,
+            "/v1/acp/server-ended/sessions/s-1",
+            None,
+            &[],
+        )
+        .await;
+        ended = parse_json(&body)["ended"].clone();
+        if !ended.is_null() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(ended["reason"], "crashed");
+    assert_eq!(ended["exitCode"], 3);
+    assert!(ended["durationMs"].is_u64());
+
+    let (_, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-ended/events",
+        None,
+        &[],
+    )
+    .await;
+    let events = parse_json(&body)["events"]
+        .as_array()
+        .cloned()
+        .expect("events");
+    let notification = events
+        .iter()
+        .map(|event| &event["payload"])
+        .find(|payload| payload["method"] == "_adapter/session_ended")
+        .expect("session_ended notification");
+    assert_eq!(notification["params"]["sessionId"], "s-1");
+    assert_eq!(notification["params"]["reason"], "crashed");
+    assert_eq!(notification["params"]["exitCode"], 3);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn session_detail_snapshots_session_state() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/new) printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-1","modes":{"currentModeId":"ask","availableModes":[{"id":"ask","name":"Ask"},{"id":"code","name":"Code"}]},"models":{"currentModelId":"small","availableModels":[{"modelId":"small","name":"Small"},{"modelId":"large","name":"Large"}]}}}\n' "$id" ;;
+    session/prompt)
+      printf '{"jsonrpc":"2.0","id":"perm-1","method":"session/request_permission","params":{"sessionId":"s-1","toolCall":{"toolCallId":"call-1"},"options":[{"optionId":"allow","name":"Allow","kind":"allow_once"}]}}\n'
+      printf '{"jsonrpc":"2.0","id":"ask-1","method":"_codex/ask_user","params":{"sessionId":"s-1","question":"Which branch?"}}\n'
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"s-1","update":{"sessionUpdate":"agent_message_chunk","content":{"type":"text","text":"Waiting."}}}}\n'
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"end_turn"}}\n' "$id" ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+
+    bootstrap_server(&test_app.app, "server-detail", "codex").await;
+    let requests = [
+        (2, "session/new", json!({"cwd": "/tmp", "mcpServers": []})),
+        (
+            3,
+            "session/set_model",
+            json!({"sessionId": "s-1", "modelId": "large"}),
+        ),
+        (
+            4,
+            "session/prompt",
+            json!({"sessionId": "s-1", "prompt": [{"type": "text", "text": "hi"}]}),
+        ),
+    ];
+    for (id, method, params) in requests {
+        let (status, _, _) = send_request(
+            &test_app.app,
+            Method::POST,
+            "/v1/acp/server-detail",
+            Some(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})),
+            &[],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let mut detail = Value::Null;
+    for _ in 0..50 {
+        let (status, _, body) = send_request(
+            &test_app.app,
+            Method::GET,
+            "/v1/acp/server-detail/sessions/s-1",
+            None,
+            &[],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        detail = parse_json(&body);
+        if detail["pendingQuestions"] == 1 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(detail["modelId"], "large");
+    assert_eq!(detail["availableModels"].as_array().map(Vec::len), Some(2));
+    assert_eq!(detail["availableModes"][1]["id"], "code");
+    assert_eq!(detail["prompts"], 1);
+    assert!(detail["lastPromptAtMs"].as_i64() >= detail["createdAtMs"].as_i64());
+    assert_eq!(detail["promptInFlight"], false);
+    assert_eq!(detail["pendingPermissions"], 1);
+    assert_eq!(detail["pendingQuestions"], 1);
+    assert!(detail["bufferedEvents"].as_u64().unwrap_or_default() >= 3);
+
+    let (_, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-detail/events",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(detail["nextOffset"], parse_json(&body)["nextOffset"]);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn session_update_switches_mode_and_model() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    session/new) printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"s-1","modes":{"currentModeId":"ask","availableModes":[{"id":"ask","name":"Ask"},{"id":"code","name":"Code"}]},"models":{"currentModelId":"small","availableModels":[{"modelId":"small","name":"Small"},{"modelId":"large","name":"Large"}]}}}\n' "$id" ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+
+    bootstrap_server(&test_app.app, "server-update", "codex").await;
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-update",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "session/new",
+            "params": {"cwd": "/tmp", "mcpServers": []}
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::PATCH,
+        "/v1/acp/server-update/sessions/s-1",
+        Some(json!({"modeId": "code", "modelId": "large"})),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let detail = parse_json(&body);
+    assert_eq!(detail["modeId"], "code");
+    assert_eq!(detail["modelId"], "large");
+
+    for (body, expected) in [
+        (json!({}), StatusCode::BAD_REQUEST),
+        (json!({"modeId": "review"}), StatusCode::BAD_REQUEST),
+        (json!({"modelId": "huge"}), StatusCode::BAD_REQUEST),
+        (json!({"permissionMode": "readOnly"}), StatusCode::CONFLICT),
+    ] {
+        let (status, _, _) = send_request(
+            &test_app.app,
+            Method::PATCH,
+            "/v1/acp/server-update/sessions/s-1",
+            Some(body),
+            &[],
+        )
+        .await;
+        assert_eq!(status, expected);
+    }
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::PATCH,
+        "/v1/acp/server-update/sessions/missing",
+        Some(json!({"modeId": "code"})),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (_, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-update/events",
+        None,
+        &[],
+    )
+    .await;
+    let updates = parse_json(&body)["events"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|event| event["payload"]["method"] == "_adapter/session_updated")
+        .collect::<Vec<_>>();
+    assert_eq!(updates.len(), 1);
+    assert_eq!(
+        updates[0]["payload"]["params"],
+        json!({"sessionId": "s-1", "modeId": "code", "modelId": "large"})
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn import_loads_native_sessions_into_a_server() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "claude");
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  cwd=$(printf '%s\n' "$line" | sed -n 's/.*"cwd"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  case "$method" in
+    initialize) printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":1,"agentCapabilities":{"loadSession":true}}}\n' "$id" ;;
+    session/load)
+      printf '{"jsonrpc":"2.0","method":"session/update","params":{"sessionId":"native-1","update":{"sessionUpdate":"user_message_chunk","content":{"type":"text","text":"earlier prompt in %s"}}}}\n' "$cwd"
+      printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+    let import = |server_id: &str, body: Value| {
+        let app = test_app.app.clone();
+        let path = format!("/v1/acp/{server_id}/import");
+        async move { send_request(&app, Method::POST, &path, Some(body), &[]).await }
+    };
+
+    let (status, _, body) = import(
+        "server-import",
+        json!({ "agent": "codex", "nativeSessionId": " ", "cwd": "/workspace" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(parse_json(&body)["code"], "invalid_request");
+
+    let (status, _, body) = import(
+        "server-import",
+        json!({ "agent": "codex", "nativeSessionId": "native-1", "cwd": "" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(parse_json(&body)["code"], "invalid_request");
+
+    let (status, _, _) = import(
+        "server-import",
+        json!({ "agent": "codex", "nativeSessionId": "native-1" }),
+    )
+    .await;
+    assert!(status.is_client_error(), "missing cwd: {status}");
+
+    // The default claude stub does not advertise `loadSession`.
+    let (status, _, body) = import(
+        "server-no-load",
+        json!({ "agent": "claude", "nativeSessionId": "native-1", "cwd": "/workspace" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(parse_json(&body)["code"], "mode_not_supported");
+
+    let (status, _, body) = import(
+        "server-import",
+        json!({ "agent": "codex", "nativeSessionId": "native-1", "cwd": "/workspace" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let imported = parse_json(&body);
+    assert_eq!(imported["serverId"], "server-import");
+    assert_eq!(imported["agent"], "codex");
+    assert_eq!(imported["sessionId"], "native-1");
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/acp/server-import/events?offset=0",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let replayed = parse_json(&body)["events"]
+        .as_array()
+        .expect("events")
+        .iter()
+        .find(|event| event["payload"]["method"] == "session/update")
+        .cloned()
+        .expect("replayed transcript");
+    assert_eq!(
+        replayed["payload"]["params"]["update"]["content"]["text"],
+        "earlier prompt in /workspace"
+    );
+
+    let (status, _, body) = import(
+        "server-import",
+        json!({ "agent": "claude", "nativeSessionId": "native-1", "cwd": "/workspace" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(parse_json(&body)["code"], "conflict");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn opencode_sessions_resume_native_agent_sessions() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "claude");
+        setup_stub_artifacts(install_dir, "codex");
+        write_executable(
+            &install_dir.join("agent_processes/codex-acp"),
+            r#"#!/usr/bin/env sh
+log="$(dirname "$0")/codex.log"
+while IFS= read -r line; do
+  printf '%s\n' "$line" >> "$log"
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}]*\).*/\1/p')
+  case "$method" in
+    initialize) printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":1,"agentCapabilities":{"loadSession":true}}}\n' "$id" ;;
+    session/new) printf '{"jsonrpc":"2.0","id":%s,"result":{"sessionId":"fresh-1"}}\n' "$id" ;;
+    session/prompt) printf '{"jsonrpc":"2.0","id":%s,"result":{"stopReason":"end_turn"}}\n' "$id" ;;
+    "") ;;
+    *) printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+  esac
+done
+"#,
+        );
+    });
+    let create = |body: Value| {
+        let app = test_app.app.clone();
+        async move { send_request(&app, Method::POST, "/opencode/session", Some(body), &[]).await }
+    };
+    let prompt = |session_id: String| {
+        let app = test_app.app.clone();
+        async move {
+            send_request(
+                &app,
+                Method::POST,
+                &format!("/opencode/session/{session_id}/message"),
+                Some(json!({ "parts": [{ "type": "text", "text": "continue" }] })),
+                &[],
+            )
+            .await
+        }
+    };
+
+    for resume in [
+        json!({ "agentSessionId": "native-1" }),
+        json!({ "agent": "codex", "agentSessionId": " " }),
+        json!({ "agent": "pi", "agentSessionId": "native-1" }),
+    ] {
+        let (status, _, _) = create(json!({ "resume": resume })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{resume}");
+    }
+
+    // The default claude stub does not advertise `loadSession`.
+    let (status, _, body) = create(json!({
+        "resume": { "agent": "claude", "agentSessionId": "native-1" }
+    }))
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let session_id = parse_json(&body)["id"].as_str().expect("id").to_string();
+    let (status, _, body) = prompt(session_id).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(
+        String::from_utf8_lossy(&body).contains("does not support resuming sessions"),
+        "{}",
+        String::from_utf8_lossy(&body)
+    );
+
+    let (status, _, body) = create(json!({
+        "resume": { "agent": "codex", "agentSessionId": "native-1" }
+    }))
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let session_id = parse_json(&body)["id"].as_str().expect("id").to_string();
+    let (status, _, _) = prompt(session_id).await;
+    assert!(status.is_success(), "prompt failed: {status}");
+
+    let log_path = test_app
+        .install_dir
+        .path()
+        .join("agent_processes/codex.log");
+    let requests = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let requests = fs::read_to_string(&log_path)
+                .unwrap_or_default()
+                .lines()
+                .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+                .filter(|line| line.get("method").is_some())
+                .collect::<Vec<_>>();
+            if requests
+                .iter()
+                .any(|line| line["method"] == "session/prompt")
+            {
+                return requests;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting for the prompt");
+    let methods = requests
+        .iter()
+        .map(|line| line["method"].as_str().unwrap_or_default())
+        .collect::<Vec<_>>();
+    assert!(!methods.contains(&"session/new"), "{methods:?}");
+    let load = requests
+        .iter()
+        .find(|line| line["method"] == "session/load")
+        .expect("session/load");
+    assert_eq!(load["params"]["sessionId"], "native-1");
+    let turn = requests
+        .iter()
+        .find(|line| line["method"] == "session/prompt")
+        .expect("session/prompt");
+    assert_eq!(turn["params"]["sessionId"], "native-1");
+}
@@ -0,0 +1,265 @@
+use super::*;
+
+#[cfg(unix)]
+#[tokio::test]
+async fn multiplexed_stream_carries_subscribed_servers() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+    });
+    bootstrap_server(&test_app.app, "mux-a", "codex").await;
+    bootstrap_server(&test_app.app, "mux-b", "codex").await;
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri("/v1/stream")
+        .body(Body::empty())
+        .expect("build request");
+    let response = test_app
+        .app
+        .clone()
+        .oneshot(request)
+        .await
+        .expect("stream response");
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut stream = response.into_body().into_data_stream();
+
+    let mut pending = String::new();
+
+    let channel_event = next_sse_event(&mut stream, &mut pending).await;
+    assert!(channel_event.contains("event: channel"), "{channel_event}");
+    let channel_id = parse_sse_data(&channel_event)["channelId"]
+        .as_str()
+        .expect("channel id")
+        .to_string();
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        &format!("/v1/stream/{channel_id}/subscribe"),
+        Some(json!({ "serverIds": ["mux-a", "mux-b"] })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body)["serverIds"], json!(["mux-a", "mux-b"]));
+
+    let mut seen = std::collections::BTreeSet::new();
+    while seen.len() < 2 {
+        let event = next_sse_event(&mut stream, &mut pending).await;
+        assert!(event.contains("event: message"), "{event}");
+        let data = parse_sse_data(&event);
+        assert!(data["sequence"].as_u64().is_some());
+        // Each server's initialize response follows its echo; the two
+        // servers' envelopes interleave in any order.
+        if data["payload"].get("id").is_some() {
+            continue;
+        }
+        assert_eq!(data["payload"]["method"], "server/echo", "{event}");
+        seen.insert(data["serverId"].as_str().expect("server id").to_string());
+    }
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        &format!("/v1/stream/{channel_id}/unsubscribe"),
+        Some(json!({ "serverIds": ["mux-a"] })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body)["serverIds"], json!(["mux-b"]));
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        &format!("/v1/stream/{channel_id}/subscribe"),
+        Some(json!({ "serverIds": ["missing-server"] })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/stream/unknown-channel/subscribe",
+        Some(json!({ "serverIds": ["mux-b"] })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn firehose_stream_follows_new_servers_with_filters() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+        setup_stub_artifacts(install_dir, "claude");
+    });
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/stream?methods=server/echo",
+        None,
+        &[("accept", "text/event-stream")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        "/v1/stream?all=true&agent=not-an-agent",
+        None,
+        &[("accept", "text/event-stream")],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri("/v1/stream?all=true&agent=codex&methods=server/echo")
+        .body(Body::empty())
+        .expect("build request");
+    let response = test_app
+        .app
+        .clone()
+        .oneshot(request)
+        .await
+        .expect("stream response");
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut stream = response.into_body().into_data_stream();
+    let mut pending = String::new();
+
+    let channel_event = next_sse_event(&mut stream, &mut pending).await;
+    assert!(channel_event.contains("event: channel"), "{channel_event}");
+
+    bootstrap_server(&test_app.app, "fire-claude", "claude").await;
+    bootstrap_server(&test_app.app, "fire-codex", "codex").await;
+
+    let event = next_sse_event(&mut stream, &mut pending).await;
+    assert!(event.contains("event: message"), "{event}");
+    let data = parse_sse_data(&event);
+    assert_eq!(data["serverId"], "fire-codex");
+    assert_eq!(data["payload"]["method"], "server/echo");
+}
+
+#[tokio::test]
+#[serial]
+async fn cdc_file_records_server_lifecycle() {
+    let feed_dir = tempfile::tempdir().expect("create feed dir");
+    let feed_path = feed_dir.path().join("cdc.jsonl");
+    let _feed = EnvVarGuard::set_os("SANDBOX_AGENT_CDC_FILE", feed_path.as_os_str());
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+    });
+
+    bootstrap_server(&test_app.app, "server-cdc", "codex").await;
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/acp/server-cdc",
+        Some(json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "result": { "outcome": { "outcome": "selected", "optionId": "allow" } }
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::DELETE,
+        "/v1/acp/server-cdc",
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let mut records = Vec::new();
+    for _ in 0..50 {
+        records = fs::read_to_string(&feed_path)
+            .unwrap_or_default()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).expect("record is JSON"))
+            .collect::<Vec<_>>();
+        if ["server.closed", "envelope.appended"]
+            .iter()
+            .all(|kind| records.iter().any(|record| record["kind"] == *kind))
+        {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    let kinds = records
+        .iter()
+        .map(|record| record["kind"].as_str().unwrap_or_default())
+        .collect::<Vec<_>>();
+    assert_eq!(kinds.first(), Some(&"server.created"), "{kinds:?}");
+    assert!(kinds.contains(&"server.closed"), "{kinds:?}");
+    assert!(kinds.contains(&"envelope.appended"), "{kinds:?}");
+    let resolved = records
+        .iter()
+        .find(|record| record["kind"] == "permission.resolved")
+        .expect("permission.resolved record");
+    assert_eq!(resolved["serverId"], "server-cdc");
+    assert_eq!(resolved["requestId"], 7);
+    assert_eq!(resolved["outcome"]["optionId"], "allow");
+    assert!(records.iter().all(|record| record["seq"].is_u64()));
+}
+
+#[tokio::test]
+#[serial]
+async fn cdc_records_are_published_to_nats() {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind fake nats");
+    let address = listener.local_addr().expect("fake nats address");
+    let _bus = EnvVarGuard::set("SANDBOX_AGENT_CDC_NATS_URL", &format!("nats://{address}"));
+    let (published_tx, mut published_rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.expect("accept publisher");
+        let (read, mut write) = stream.into_split();
+        write
+            .write_all(b"INFO {\"server_id\":\"fake\",\"max_payload\":1048576}\r\n")
+            .await
+            .expect("write info");
+        let mut lines = BufReader::new(read).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if line == "PING" {
+                write.write_all(b"PONG\r\n").await.expect("write pong");
+            } else if let Some(subject) = line.strip_prefix("PUB ") {
+                let subject = subject.split(' ').next().unwrap_or_default().to_string();
+                let payload = lines.next_line().await.ok().flatten().unwrap_or_default();
+                let _ = published_tx.send((subject, payload));
+            }
+        }
+    });
+
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+    });
+    bootstrap_server(&test_app.app, "server.bus", "codex").await;
+
+    let (subject, payload) = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let (subject, payload) = published_rx.recv().await.expect("publisher connected");
+            let record = serde_json::from_str::<Value>(&payload).expect("record is JSON");
+            if record["kind"] == "server.created" {
+                return (subject, record);
+            }
+        }
+    })
+    .await
+    .expect("server.created published");
+    assert_eq!(subject, "sandbox.server_bus.events");
+    assert_eq!(payload["serverId"], "server.bus");
+    assert_eq!(payload["agent"], "codex");
+}
@@ -0,0 +1,191 @@
+use super::*;
+
+#[cfg(unix)]
+#[tokio::test]
+async fn task_runs_one_prompt_across_agents() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        for agent in ["codex", "claude"] {
+            setup_stub_artifacts(install_dir, agent);
+            write_executable(
+                &install_dir.join(format!("agent_processes/{agent}-acp")),
+                &format!(
+                    r#"#!/usr/bin/env sh
+while IFS= read -r line; do
+  method=$(printf '%s\n' "$line" | sed -n 's/.*"method"[[:space:]]*:[[:space:]]*"\([^"]*\)".*/\1/p')
+  id=$(printf '%s\n' "$line" | sed -n 's/.*"id"[[:space:]]*:[[:space:]]*\([^,}}]*\).*/\1/p')
+  case "$method" in
+    session/new) printf '{{"jsonrpc":"2.0","id":%s,"result":{{"sessionId":"{agent}-session"}}}}\n' "$id" ;;
+    session/prompt)
+      printf '{{"jsonrpc":"2.0","method":"session/update","params":{{"sessionId":"{agent}-session","update":{{"sessionUpdate":"agent_message_chunk","content":{{"type":"text","text":"hello from "}}}}}}}}\n'
+      printf '{{"jsonrpc":"2.0","method":"session/update","params":{{"sessionId":"{agent}-session","update":{{"sessionUpdate":"agent_message_chunk","content":{{"type":"text","text":"{agent}"}}}}}}}}\n'
+      printf '{{"jsonrpc":"2.0","id":%s,"result":{{"stopReason":"end_turn"}}}}\n' "$id" ;;
+    *) printf '{{"jsonrpc":"2.0","id":%s,"result":{{}}}}\n' "$id" ;;
+  esac
+done
+"#
+                ),
+            );
+        }
+    });
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/tasks",
+        Some(json!({ "prompt": "hi", "agents": [{ "agent": "not-an-agent" }] })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/tasks",
+        Some(json!({
+            "prompt": "say hello",
+            "agents": [{ "agent": "codex" }, { "agent": "claude" }]
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let created = parse_json(&body);
+    let task_id = created["taskId"].as_str().expect("task id").to_string();
+    assert_eq!(created["runs"].as_array().map(Vec::len), Some(2));
+
+    let task = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let (status, _, body) = send_request(
+                &test_app.app,
+                Method::GET,
+                &format!("/v1/tasks/{task_id}"),
+                None,
+                &[],
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            let task = parse_json(&body);
+            if task["finished"] == true {
+                return task;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting for task");
+    for (run, agent) in task["runs"]
+        .as_array()
+        .expect("runs")
+        .iter()
+        .zip(["codex", "claude"])
+    {
+        assert_eq!(run["agent"], agent, "{run}");
+        assert_eq!(run["status"], "completed", "{run}");
+        assert_eq!(run["stopReason"], "end_turn");
+        assert_eq!(run["sessionId"], format!("{agent}-session"));
+        assert_eq!(run["output"], format!("hello from {agent}"));
+    }
+
+    let (status, _, body) = send_request(&test_app.app, Method::GET, "/v1/tasks", None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body)["tasks"][0]["taskId"], task_id.as_str());
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::DELETE,
+        &format!("/v1/tasks/{task_id}"),
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _, body) = send_request(&test_app.app, Method::GET, "/v1/acp", None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(parse_json(&body)["servers"], json!([]));
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        &format!("/v1/tasks/{task_id}"),
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn schedule_reports_next_run_and_can_be_stopped() {
+    let test_app = TestApp::with_setup(AuthConfig::disabled(), |install_dir| {
+        setup_stub_artifacts(install_dir, "codex");
+    });
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/schedules",
+        Some(json!({
+            "cron": "61 * * * *",
+            "prompt": "run the tests",
+            "agents": [{ "agent": "codex" }]
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let before_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("clock")
+        .as_millis() as i64;
+    let (status, _, body) = send_request(
+        &test_app.app,
+        Method::POST,
+        "/v1/schedules",
+        Some(json!({
+            "cron": "*/5 * * * *",
+            "prompt": "run the tests",
+            "agents": [{ "agent": "codex" }]
+        })),
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let created = parse_json(&body);
+    let schedule_id = created["scheduleId"]
+        .as_str()
+        .expect("schedule id")
+        .to_string();
+    assert_eq!(created["agents"], json!(["codex"]));
+    assert_eq!(created["runs"], json!([]));
+    let next_run_at_ms = created["nextRunAtMs"].as_i64().expect("next run");
+    assert_eq!(next_run_at_ms % 300_000, 0);
+    assert!(next_run_at_ms > before_ms && next_run_at_ms <= before_ms + 300_000);
+
+    let (status, _, body) =
+        send_request(&test_app.app, Method::GET, "/v1/schedules", None, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        parse_json(&body)["schedules"][0]["scheduleId"],
+        schedule_id.as_str()
+    );
+
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::DELETE,
+        &format!("/v1/schedules/{schedule_id}"),
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _, _) = send_request(
+        &test_app.app,
+        Method::GET,
+        &format!("/v1/schedules/{schedule_id}"),
+        None,
+        &[],
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}